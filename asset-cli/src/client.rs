@@ -0,0 +1,140 @@
+//! Blocking client for the asset server HTTP API.
+
+use crate::models::AssetInfo;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read};
+use uuid::Uuid;
+
+/// All possible errors that can happen while talking to the asset server.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request failed or the server responded with error status code.
+    Http(Box<ureq::Error>),
+    /// The response body couldn't be read or parsed.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "{}", e),
+            ClientError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<ureq::Error> for ClientError {
+    fn from(e: ureq::Error) -> Self {
+        ClientError::Http(Box::new(e))
+    }
+}
+
+impl From<std::io::Error> for ClientError {
+    fn from(e: std::io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+pub struct Client {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// Creates a new client that will connect to server at `base_url`
+    /// (eg. `http://localhost:8000`).
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::agent(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn get_bytes(&self, path: &str) -> Result<Vec<u8>, ClientError> {
+        let mut bytes = Vec::new();
+        self.agent
+            .get(&self.url(path))
+            .call()?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn assets(&self) -> Result<Vec<AssetInfo>, ClientError> {
+        Ok(self.agent.get(&self.url("/assets")).call()?.into_json()?)
+    }
+
+    pub fn dirty_assets(&self) -> Result<Vec<Uuid>, ClientError> {
+        Ok(self
+            .agent
+            .get(&self.url("/assets/dirty"))
+            .call()?
+            .into_json()?)
+    }
+
+    /// Enqueues specified assets for compilation.
+    pub fn compile(&self, assets: &[Uuid]) -> Result<(), ClientError> {
+        self.agent
+            .post(&self.url("/compile"))
+            .send_json(json!({ "assets": assets }))?;
+        Ok(())
+    }
+
+    /// Asks the server to rescan the whole library.
+    pub fn refresh(&self) -> Result<(), ClientError> {
+        self.agent.post(&self.url("/refresh")).call()?;
+        Ok(())
+    }
+
+    /// Downloads a PNG preview of specified asset.
+    pub fn preview(&self, uuid: &Uuid) -> Result<Vec<u8>, ClientError> {
+        self.get_bytes(&format!("/assets/{}/preview", uuid.to_hyphenated()))
+    }
+
+    /// Downloads the compiled `.bf` file of specified asset.
+    pub fn compiled(&self, uuid: &Uuid) -> Result<Vec<u8>, ClientError> {
+        self.get_bytes(&format!("/assets/{}/compiled", uuid.to_hyphenated()))
+    }
+
+    /// Connects to the server event stream. Returned iterator blocks until
+    /// next event arrives and ends when the connection is closed.
+    pub fn events(&self) -> Result<Events, ClientError> {
+        let reader = self.agent.get(&self.url("/events")).call()?.into_reader();
+
+        Ok(Events {
+            reader: BufReader::new(reader),
+        })
+    }
+}
+
+/// Iterator over events sent by the server.
+pub struct Events {
+    reader: BufReader<Box<dyn Read + Send + Sync + 'static>>,
+}
+
+impl Iterator for Events {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => {}
+            }
+
+            // server also sends non-json keep-alive messages which we skip
+            if let Some(data) = line.trim_end().strip_prefix("data: ") {
+                if let Ok(event) = serde_json::from_str(data) {
+                    return Some(event);
+                }
+            }
+        }
+    }
+}
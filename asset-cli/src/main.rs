@@ -0,0 +1,252 @@
+use crate::client::{Client, ClientError};
+use crate::models::{AssetInfo, BundleEntry};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use uuid::Uuid;
+
+mod client;
+mod models;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "asset-cli")]
+struct Opt {
+    /// URL of the asset server
+    #[structopt(short, long, default_value = "http://localhost:8000")]
+    server: String,
+
+    #[structopt(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(StructOpt, Debug)]
+enum Cmd {
+    /// Lists assets, optionally filtered by search query
+    List {
+        /// Search query (eg. "tag:rocks type:image")
+        query: Option<String>,
+
+        /// Print assets as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Enqueues assets for compilation
+    Compile {
+        /// Assets to compile
+        assets: Vec<Uuid>,
+
+        /// Compile all dirty assets
+        #[structopt(long)]
+        dirty: bool,
+
+        /// Compile all assets
+        #[structopt(long)]
+        all: bool,
+
+        /// Rescan the library before compiling
+        #[structopt(long)]
+        refresh: bool,
+
+        /// Wait for all compilations to finish
+        #[structopt(short, long)]
+        wait: bool,
+    },
+    /// Prints server events (one JSON per line) as they arrive
+    Events,
+    /// Downloads preview of an asset
+    Preview {
+        uuid: Uuid,
+
+        /// Output file (.png)
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Downloads compiled files of assets matching the query into a directory
+    Export {
+        /// Search query (eg. "tag:level1"), exports all assets if not specified
+        query: Option<String>,
+
+        /// Output directory
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let client = Client::new(&opt.server);
+
+    match run(&client, opt.cmd) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Executes the command and returns whether it was successful.
+fn run(client: &Client, cmd: Cmd) -> Result<bool, ClientError> {
+    match cmd {
+        Cmd::List { query, json } => {
+            let assets = find_assets(client, query.as_deref())?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&assets).unwrap());
+            } else {
+                for x in assets {
+                    println!(
+                        "{}  {:<8}  {}  [{}]",
+                        x.uuid.to_hyphenated(),
+                        x.kind,
+                        x.name,
+                        x.tags.join(", ")
+                    );
+                }
+            }
+            Ok(true)
+        }
+        Cmd::Compile {
+            mut assets,
+            dirty,
+            all,
+            refresh,
+            wait,
+        } => {
+            if refresh {
+                client.refresh()?;
+            }
+
+            if all {
+                assets.extend(client.assets()?.iter().map(|x| x.uuid));
+            } else if dirty {
+                assets.extend(client.dirty_assets()?);
+            }
+
+            compile(client, assets, wait)
+        }
+        Cmd::Events => {
+            for event in client.events()? {
+                println!("{}", event);
+            }
+            Ok(true)
+        }
+        Cmd::Preview { uuid, output } => {
+            std::fs::write(output, client.preview(&uuid)?)?;
+            Ok(true)
+        }
+        Cmd::Export { query, output } => export(client, query.as_deref(), output),
+    }
+}
+
+/// Returns all assets matching the `query` or all assets if the `query` is `None`.
+fn find_assets(client: &Client, query: Option<&str>) -> Result<Vec<AssetInfo>, ClientError> {
+    let mut assets = client.assets()?;
+
+    if let Some(query) = query {
+        // only ask for dirty assets when the query needs them
+        let dirty: HashSet<Uuid> = if query.contains("dirty:") {
+            client.dirty_assets()?.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+
+        assets.retain(|x| x.matches(query, &dirty));
+    }
+
+    assets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(assets)
+}
+
+fn compile(client: &Client, assets: Vec<Uuid>, wait: bool) -> Result<bool, ClientError> {
+    let mut pending: HashSet<Uuid> = assets.iter().copied().collect();
+
+    if pending.is_empty() {
+        println!("nothing to compile");
+        return Ok(true);
+    }
+
+    // we need to subscribe before enqueueing the compilations so we
+    // do not miss any status events
+    let events = if wait { Some(client.events()?) } else { None };
+
+    client.compile(&assets)?;
+    println!("enqueued {} assets", pending.len());
+
+    let events = match events {
+        None => return Ok(true),
+        Some(t) => t,
+    };
+
+    let mut failed = 0;
+    for event in events {
+        if event["type"] != "AssetCompilationStatus" {
+            continue;
+        }
+
+        let uuid = match event["uuid"].as_str().map(Uuid::parse_str) {
+            Some(Ok(t)) => t,
+            _ => continue,
+        };
+
+        if !pending.contains(&uuid) {
+            continue;
+        }
+
+        match event["status"]["type"].as_str() {
+            Some("Compiled") => println!("compiled {}", uuid),
+            Some("Error") => {
+                failed += 1;
+                println!("failed {}: {}", uuid, event["status"]["error"]);
+            }
+            _ => continue,
+        }
+
+        pending.remove(&uuid);
+        if pending.is_empty() {
+            break;
+        }
+    }
+
+    Ok(failed == 0 && pending.is_empty())
+}
+
+fn export(client: &Client, query: Option<&str>, output: PathBuf) -> Result<bool, ClientError> {
+    std::fs::create_dir_all(&output)?;
+
+    let mut manifest = Vec::new();
+    let mut missing = 0;
+
+    for x in find_assets(client, query)? {
+        let file = format!("{}.bf", x.uuid.to_hyphenated());
+        let bytes = match client.compiled(&x.uuid) {
+            Ok(t) => t,
+            Err(e) => {
+                missing += 1;
+                eprintln!("cannot download {} ({}): {}", x.uuid, x.name, e);
+                continue;
+            }
+        };
+
+        std::fs::write(output.join(&file), bytes)?;
+        println!("exported {} ({})", x.uuid, x.name);
+
+        manifest.push(BundleEntry {
+            uuid: x.uuid,
+            name: x.name,
+            kind: x.kind,
+            file,
+        });
+    }
+
+    std::fs::write(
+        output.join("bundle.json"),
+        serde_json::to_string_pretty(&manifest).unwrap(),
+    )?;
+
+    println!("exported {} assets ({} missing)", manifest.len(), missing);
+
+    Ok(missing == 0)
+}
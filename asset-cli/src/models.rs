@@ -0,0 +1,102 @@
+//! Data objects returned by the asset server & searching in them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Subset of asset properties that are common for all asset types.
+///
+/// The server returns assets as internally tagged enum, so we only pick
+/// the tag and fields shared by all the variants.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetInfo {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub uuid: Uuid,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub input_path: Option<String>,
+}
+
+impl AssetInfo {
+    /// Returns whether this asset matches the search `query`. Query syntax is
+    /// the same as in the web UI search bar: whitespace separated tokens that
+    /// all must match. Supported special tokens are `tag:`, `type:` and `dirty:`.
+    pub fn matches(&self, query: &str, dirty: &HashSet<Uuid>) -> bool {
+        query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .all(|token| self.matches_token(&token, dirty))
+    }
+
+    fn matches_token(&self, token: &str, dirty: &HashSet<Uuid>) -> bool {
+        if let Some(tag) = token.strip_prefix("tag:") {
+            return self.tags.iter().any(|t| t.to_lowercase() == tag);
+        }
+
+        if let Some(kind) = token.strip_prefix("type:") {
+            return self.kind.to_lowercase() == kind;
+        }
+
+        if token == "dirty:" {
+            return dirty.contains(&self.uuid);
+        }
+
+        self.name.to_lowercase().contains(token)
+            || self.uuid.to_hyphenated().to_string().contains(token)
+            || self.tags.iter().any(|t| t.to_lowercase().contains(token))
+    }
+}
+
+/// Entry of the `bundle.json` manifest written by the `export` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BundleEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub file: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::AssetInfo;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    fn asset() -> AssetInfo {
+        AssetInfo {
+            kind: "Image".into(),
+            uuid: Uuid::parse_str("2d1aeb08-db87-48f9-a967-cfb5f06746dc").unwrap(),
+            name: "Rock_Albedo".into(),
+            tags: vec!["rocks".into(), "nature".into()],
+            input_path: Some("rocks/albedo.png".into()),
+        }
+    }
+
+    #[test]
+    fn test_matches_plain_tokens() {
+        let a = asset();
+        let dirty = HashSet::new();
+
+        assert!(a.matches("", &dirty));
+        assert!(a.matches("rock", &dirty));
+        assert!(a.matches("ALBEDO 2d1aeb08", &dirty));
+        assert!(a.matches("natu", &dirty));
+        assert!(!a.matches("rock normal", &dirty));
+    }
+
+    #[test]
+    fn test_matches_special_tokens() {
+        let a = asset();
+        let mut dirty = HashSet::new();
+
+        assert!(a.matches("tag:rocks type:image", &dirty));
+        assert!(!a.matches("tag:rock", &dirty));
+        assert!(!a.matches("type:mesh", &dirty));
+        assert!(!a.matches("dirty:", &dirty));
+
+        dirty.insert(a.uuid);
+        assert!(a.matches("dirty: albedo", &dirty));
+    }
+}
@@ -0,0 +1,209 @@
+//! Analysis of texture usage by materials. Produces lists of textures that
+//! should be cleaned up (unused textures, wrong formats, unusual resolutions).
+
+use crate::database::load_database;
+use crate::library::{create_library, Library};
+use crate::models::{Asset, Material};
+use crate::settings::Settings;
+use bf::image::Format;
+use bf::load_bf_from_bytes;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Textures whose larger side is this many times bigger or smaller than
+/// the median of all textures are reported as outliers.
+const OUTLIER_FACTOR: u32 = 4;
+
+/// Kind of data a texture contains, determines its expected color space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Role {
+    Color,
+    Normal,
+    Data,
+}
+
+/// Results of the analysis.
+#[derive(Default)]
+pub struct Report {
+    /// Images that are not referenced by any material.
+    pub unused: Vec<Uuid>,
+    /// Images referenced by many materials with the number of materials.
+    pub shared: Vec<(Uuid, usize)>,
+    /// Images with unusual resolution and the description of the problem.
+    pub outliers: Vec<(Uuid, String)>,
+    /// Material, image it references and why the image format does not fit.
+    pub mismatches: Vec<(Uuid, Uuid, &'static str)>,
+}
+
+/// Returns all maps of the material with their roles.
+fn material_maps(m: &Material) -> [(Role, Option<Uuid>); 8] {
+    [
+        (Role::Color, m.albedo_map),
+        (Role::Normal, m.normal_map),
+        (Role::Data, m.displacement_map),
+        (Role::Data, m.roughness_map),
+        (Role::Data, m.ao_map),
+        (Role::Data, m.metallic_map),
+        (Role::Data, m.opacity_map),
+        (Role::Color, m.emissive_map),
+    ]
+}
+
+/// Returns why an image in `format` should not be used for a map with `role`.
+fn format_mismatch(role: Role, format: Format) -> Option<&'static str> {
+    use Format::*;
+
+    let srgb = matches!(
+        format,
+        SrgbDxt1 | SrgbDxt3 | SrgbDxt5 | Srgb8 | Srgb8A8 | SrgbBC7
+    );
+
+    match role {
+        Role::Color if matches!(format, Dxt1 | Dxt3 | Dxt5 | Rgb8 | Rgba8 | BC7) => {
+            Some("color map is not in sRGB format")
+        }
+        Role::Normal if srgb => Some("normal map is in sRGB format"),
+        Role::Normal if matches!(format, R8 | R16) => Some("normal map has only one channel"),
+        Role::Data if srgb => Some("data map is in sRGB format"),
+        _ => None,
+    }
+}
+
+/// Analyzes how `materials` use images. The `formats` and `sizes` contain
+/// format and larger side of images, images missing in `sizes` (not compiled
+/// yet) are skipped when looking for resolution outliers. Images referenced by
+/// at least `shared_threshold` materials are reported as shared.
+pub fn analyze(
+    formats: &HashMap<Uuid, Format>,
+    materials: &[Material],
+    sizes: &HashMap<Uuid, u16>,
+    shared_threshold: usize,
+) -> Report {
+    let mut report = Report::default();
+    let mut references: HashMap<Uuid, usize> = HashMap::new();
+
+    for material in materials {
+        for (role, map) in material_maps(material).iter() {
+            let uuid = match map {
+                Some(t) => *t,
+                None => continue,
+            };
+            *references.entry(uuid).or_default() += 1;
+
+            if let Some(problem) = formats.get(&uuid).and_then(|f| format_mismatch(*role, *f)) {
+                report.mismatches.push((material.uuid, uuid, problem));
+            }
+        }
+    }
+
+    for uuid in formats.keys() {
+        match references.get(uuid) {
+            None => report.unused.push(*uuid),
+            Some(count) if *count >= shared_threshold => report.shared.push((*uuid, *count)),
+            Some(_) => {}
+        }
+    }
+    report.shared.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut sorted = sizes.values().copied().collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or(0) as u32;
+
+    for (uuid, size) in sizes {
+        let size = *size as u32;
+        if !size.is_power_of_two() {
+            report
+                .outliers
+                .push((*uuid, format!("{}px is not a power of two", size)));
+        } else if size > median * OUTLIER_FACTOR || size * OUTLIER_FACTOR < median {
+            report
+                .outliers
+                .push((*uuid, format!("{}px while median is {}px", size, median)));
+        }
+    }
+
+    report
+}
+
+/// Returns the larger side of compiled image or `None` if the image was not compiled.
+fn compiled_size(library: &Library, uuid: &Uuid) -> Option<u16> {
+    let bytes = std::fs::read(library.compute_output_path(uuid)).ok()?;
+    let image = load_bf_from_bytes(&bytes).ok()?.try_to_image().ok()?;
+
+    Some(image.width.max(image.height))
+}
+
+/// Analyzes all materials and images in the library and prints the report.
+pub fn analyze_library(settings: Arc<Settings>, shared_threshold: usize) {
+    let database = load_database(&settings);
+    let library = create_library(&settings);
+
+    let assets = database.get_assets();
+    let names = assets
+        .iter()
+        .map(|x| (x.uuid(), x.name().clone()))
+        .collect::<HashMap<_, _>>();
+    let mut formats = HashMap::new();
+    let mut materials = vec![];
+
+    for asset in assets {
+        match asset {
+            Asset::Image(t) => {
+                formats.insert(t.uuid, t.format);
+            }
+            Asset::Material(t) => materials.push(t),
+            Asset::Mesh(_) | Asset::Shader(_) => {}
+        }
+    }
+
+    let sizes = formats
+        .keys()
+        .filter_map(|uuid| compiled_size(&library, uuid).map(|size| (*uuid, size)))
+        .collect::<HashMap<_, _>>();
+
+    let report = analyze(&formats, &materials, &sizes, shared_threshold);
+    let name = |uuid: &Uuid| names.get(uuid).map_or("?", |x| x.as_str());
+
+    println!(
+        "analyzed {} materials and {} images",
+        materials.len(),
+        formats.len()
+    );
+
+    println!("\nunused images ({}):", report.unused.len());
+    let mut unused = report
+        .unused
+        .iter()
+        .map(|x| (name(x), x))
+        .collect::<Vec<_>>();
+    unused.sort();
+    for (name, uuid) in unused {
+        println!("  {}  {}", uuid.to_hyphenated(), name);
+    }
+
+    println!(
+        "\nimages used by {} or more materials ({}):",
+        shared_threshold,
+        report.shared.len()
+    );
+    for (uuid, count) in report.shared.iter() {
+        println!("  {}  {:>4}x  {}", uuid.to_hyphenated(), count, name(uuid));
+    }
+
+    println!("\nresolution outliers ({}):", report.outliers.len());
+    for (uuid, problem) in report.outliers.iter() {
+        println!("  {}  {}: {}", uuid.to_hyphenated(), name(uuid), problem);
+    }
+
+    println!("\nformat mismatches ({}):", report.mismatches.len());
+    for (material, image, problem) in report.mismatches.iter() {
+        println!(
+            "  {}  {} in material {}: {}",
+            image.to_hyphenated(),
+            name(image),
+            name(material),
+            problem
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Compilation of the whole library without the HTTP server (eg. on CI).
+
+use crate::compiler::Compiler;
+use crate::database::load_database;
+use crate::importer::create_importer;
+use crate::input2uuid::dump_input2uuid;
+use crate::library::create_library;
+use crate::models::{Asset, Compilation};
+use crate::scanner::create_scanner;
+use crate::settings::Settings;
+use futures::future::join_all;
+use log::info;
+use std::sync::Arc;
+
+/// Scans the library and compiles dirty (or `all`) assets using `jobs`
+/// concurrent compilations. Prints a summary and returns whether all
+/// compilations succeeded.
+pub async fn compile_batch(settings: Arc<Settings>, all: bool, jobs: Option<usize>) -> bool {
+    let database = load_database(&settings);
+    let library = create_library(&settings);
+    let importer = create_importer(database.clone(), library.clone());
+    let scanner = create_scanner(&settings, database.clone(), library.clone(), importer);
+    let jobs = jobs
+        .or(settings.max_concurrency)
+        .unwrap_or_else(num_cpus::get)
+        .max(1);
+    let compiler = Compiler::new(jobs, database.clone(), library, scanner.clone());
+
+    let results = scanner.full_rescan();
+    info!(
+        "Scan results: {} scanned, {} imported, {} removed, {} dirty.",
+        results.scanned,
+        results.imported,
+        results.removed,
+        results.dirty.len()
+    );
+
+    // sort the assets so the order of compilations and the summary is stable
+    let mut assets: Vec<Asset> = if all {
+        database.get_assets()
+    } else {
+        results
+            .dirty
+            .iter()
+            .filter_map(|x| database.get_asset(x))
+            .collect()
+    };
+    assets.sort_by(|a, b| a.name().cmp(b.name()).then(a.uuid().cmp(&b.uuid())));
+
+    if assets.is_empty() {
+        println!("nothing to compile");
+        return true;
+    }
+
+    println!("compiling {} assets using {} jobs", assets.len(), jobs);
+    let compilations = join_all(assets.iter().map(|x| compiler.compile_now(x.uuid()))).await;
+
+    // persist compilations and uuids of newly imported assets
+    database.flush();
+    dump_input2uuid(&settings.input2uuid, database.get_assets()).await;
+
+    print_summary(&assets, &compilations)
+}
+
+/// Prints table with results of compilations and returns whether all of them succeeded.
+fn print_summary(assets: &[Asset], compilations: &[Compilation]) -> bool {
+    let mut failed = 0;
+
+    println!();
+    println!("{:<6}  {:>9}  {:<36}  {}", "STATUS", "TIME", "UUID", "NAME");
+    for (asset, compilation) in assets.iter().zip(compilations) {
+        let status = match compilation.error {
+            None => "ok",
+            Some(_) => {
+                failed += 1;
+                "FAILED"
+            }
+        };
+
+        println!(
+            "{:<6}  {:>8.2}s  {}  {}",
+            status,
+            compilation.duration.as_secs_f32(),
+            asset.uuid().to_hyphenated(),
+            asset.name()
+        );
+    }
+
+    for (asset, compilation) in assets.iter().zip(compilations) {
+        if let Some(error) = &compilation.error {
+            eprintln!("\nfailed {} ({}):\n{}", asset.uuid(), asset.name(), error);
+        }
+    }
+
+    println!(
+        "\n{} compiled, {} failed",
+        compilations.len() - failed,
+        failed
+    );
+
+    failed == 0
+}
@@ -0,0 +1,268 @@
+//! Analysis of materials that could be merged to reduce the number of draw calls.
+//!
+//! Objects are drawn in batches of the same mesh and material, so materials that
+//! differ only by scalar parameters (which could be passed per instance) or only
+//! by maps (which could be packed into an atlas or an array texture) split batches
+//! that could otherwise be drawn by one instanced draw call. The report lists such
+//! groups of materials and, when a scene is given, estimates how many draw calls
+//! merging each group would save.
+
+use crate::database::{load_database, Database};
+use crate::library::{create_library, Library};
+use crate::models::{Asset, Material};
+use crate::settings::Settings;
+use bf::load_bf_from_bytes;
+use bf::material::BlendMode;
+use bf::tree::{Component, Tree};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How materials of a group differ.
+#[derive(Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeKind {
+    /// Same maps, different scalar parameters (could be per-instance data).
+    Parameters,
+    /// Same scalar parameters, different maps (could be atlased).
+    Textures,
+}
+
+/// Group of materials that could be merged into one.
+#[derive(Serialize, Clone, Debug)]
+pub struct MergeGroup {
+    pub kind: MergeKind,
+    pub materials: Vec<Uuid>,
+    pub names: Vec<String>,
+    /// Names of the properties whose values differ in the group.
+    pub differences: Vec<&'static str>,
+    /// Number of objects of the scene using materials of the group.
+    pub objects: usize,
+    /// Estimated number of draw calls saved by merging the group (zero without a scene).
+    pub draw_calls_saved: usize,
+}
+
+/// Results of the analysis serialized as JSON for the asset server UI.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BatchingReport {
+    /// Scene the usage of materials was taken from.
+    pub scene: Option<Uuid>,
+    pub materials: usize,
+    /// Number of objects of the scene.
+    pub objects: usize,
+    /// Estimated number of draw calls of the scene (one per mesh and material).
+    pub draw_calls: usize,
+    /// Groups sorted by the saved draw calls and the number of materials.
+    pub groups: Vec<MergeGroup>,
+}
+
+/// Scalar parameters of the material with their names.
+fn parameters(m: &Material) -> [(&'static str, String); 13] {
+    // formatted, so the values can be compared without caring about floats
+    [
+        ("albedo_color", format!("{:?}", m.albedo_color)),
+        ("roughness", format!("{:?}", m.roughness)),
+        ("metallic", format!("{:?}", m.metallic)),
+        ("alpha_cutoff", format!("{:?}", m.alpha_cutoff)),
+        ("opacity", format!("{:?}", m.opacity)),
+        ("ior", format!("{:?}", m.ior)),
+        ("sss", format!("{:?}", m.sss)),
+        ("emissive_color", format!("{:?}", m.emissive_color)),
+        ("clear_coat", format!("{:?}", m.clear_coat)),
+        (
+            "clear_coat_roughness",
+            format!("{:?}", m.clear_coat_roughness),
+        ),
+        ("anisotropy", format!("{:?}", m.anisotropy)),
+        ("foliage", format!("{:?}", m.foliage)),
+        ("double_sided", format!("{:?}", m.double_sided)),
+    ]
+}
+
+/// Maps of the material with their names.
+fn maps(m: &Material) -> [(&'static str, Option<Uuid>); 8] {
+    [
+        ("albedo_map", m.albedo_map),
+        ("normal_map", m.normal_map),
+        ("displacement_map", m.displacement_map),
+        ("roughness_map", m.roughness_map),
+        ("ao_map", m.ao_map),
+        ("metallic_map", m.metallic_map),
+        ("opacity_map", m.opacity_map),
+        ("emissive_map", m.emissive_map),
+    ]
+}
+
+/// Returns whether the materials are drawn by the same pipeline, which is
+/// required to merge them.
+fn same_pipeline(a: &Material, b: &Material) -> bool {
+    a.blend_mode.unwrap_or(BlendMode::Opaque) == b.blend_mode.unwrap_or(BlendMode::Opaque)
+        && a.foliage.unwrap_or(false) == b.foliage.unwrap_or(false)
+        && a.double_sided.unwrap_or(false) == b.double_sided.unwrap_or(false)
+}
+
+/// Returns names of the properties of `kind` that differ between the materials.
+fn differences(kind: MergeKind, materials: &[&Material]) -> Vec<&'static str> {
+    let first = materials[0];
+    let differ = |name: &&str| match kind {
+        MergeKind::Parameters => {
+            let value = |m: &Material| parameters(m).iter().find(|x| x.0 == *name).cloned();
+            materials.iter().any(|m| value(m) != value(first))
+        }
+        MergeKind::Textures => {
+            let value = |m: &Material| maps(m).iter().find(|x| x.0 == *name).copied();
+            materials.iter().any(|m| value(m) != value(first))
+        }
+    };
+
+    match kind {
+        MergeKind::Parameters => parameters(first)
+            .iter()
+            .map(|x| x.0)
+            .filter(differ)
+            .collect(),
+        MergeKind::Textures => maps(first).iter().map(|x| x.0).filter(differ).collect(),
+    }
+}
+
+/// Splits materials into groups of at least two materials that are drawn by the
+/// same pipeline and have the same maps (`Parameters`) or the same scalar
+/// parameters (`Textures`).
+fn group_materials(kind: MergeKind, materials: &[Material]) -> Vec<Vec<&Material>> {
+    let same = |a: &Material, b: &Material| {
+        same_pipeline(a, b)
+            && match kind {
+                MergeKind::Parameters => maps(a) == maps(b),
+                MergeKind::Textures => parameters(a) == parameters(b),
+            }
+    };
+
+    let mut groups: Vec<Vec<&Material>> = vec![];
+    for material in materials {
+        match groups.iter_mut().find(|g| same(g[0], material)) {
+            Some(g) => g.push(material),
+            None => groups.push(vec![material]),
+        }
+    }
+
+    // identical materials are duplicates, not merge candidates of this kind
+    groups.retain(|g| g.len() > 1 && !differences(kind, g).is_empty());
+    groups
+}
+
+/// Returns mesh and material of every object of the scene.
+pub fn scene_objects(tree: &Tree) -> Vec<(Uuid, Uuid)> {
+    let mut objects = vec![];
+    let mut stack = vec![tree.root()];
+
+    while let Some(node) = stack.pop() {
+        for component in node.components() {
+            if let Component::MeshRenderer { mesh, material } = component {
+                objects.push((*mesh, *material));
+            }
+        }
+        stack.extend(node.children().map(|x| tree.node(x)));
+    }
+
+    objects
+}
+
+/// Loads the scene tree from a BF file.
+pub fn load_scene(bytes: &[u8]) -> Result<Tree, String> {
+    load_bf_from_bytes(bytes)
+        .map_err(|e| format!("cannot load scene: {:?}", e))?
+        .try_to_tree()
+        .map_err(|_| "file is not a scene".to_string())
+}
+
+/// Analyzes the `materials` and estimates draw calls of `objects` (mesh and
+/// material pairs of a scene) saved by merging them. Objects of the same mesh
+/// and material are assumed to be drawn by one (instanced) draw call.
+pub fn analyze_batching(materials: &[Material], objects: &[(Uuid, Uuid)]) -> BatchingReport {
+    let draw_calls = |merged: &HashMap<Uuid, Uuid>| {
+        objects
+            .iter()
+            .map(|(mesh, material)| (*mesh, *merged.get(material).unwrap_or(material)))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    let before = draw_calls(&HashMap::new());
+
+    let mut groups = vec![];
+    for kind in [MergeKind::Parameters, MergeKind::Textures].iter() {
+        for group in group_materials(*kind, materials) {
+            let uuids = group.iter().map(|x| x.uuid).collect::<Vec<_>>();
+            let merged = uuids.iter().map(|x| (*x, uuids[0])).collect();
+
+            groups.push(MergeGroup {
+                kind: *kind,
+                names: group.iter().map(|x| x.name.clone()).collect(),
+                differences: differences(*kind, &group),
+                objects: objects.iter().filter(|x| uuids.contains(&x.1)).count(),
+                draw_calls_saved: before - draw_calls(&merged),
+                materials: uuids,
+            });
+        }
+    }
+    groups.sort_by(|a, b| {
+        (b.draw_calls_saved, b.materials.len()).cmp(&(a.draw_calls_saved, a.materials.len()))
+    });
+
+    BatchingReport {
+        scene: None,
+        materials: materials.len(),
+        objects: objects.len(),
+        draw_calls: before,
+        groups,
+    }
+}
+
+/// Analyzes the materials of the library. The usage of materials is taken from
+/// the compiled `scene` (a `Tree`) when it is specified.
+pub fn library_batching_report(
+    database: &Database,
+    library: &Library,
+    scene: Option<Uuid>,
+) -> Result<BatchingReport, String> {
+    let objects = match scene {
+        None => vec![],
+        Some(uuid) => {
+            let bytes = std::fs::read(library.compute_output_path(&uuid))
+                .map_err(|e| format!("cannot read compiled scene: {}", e))?;
+            scene_objects(&load_scene(&bytes)?)
+        }
+    };
+
+    let materials = database
+        .get_assets()
+        .into_iter()
+        .filter_map(|x| match x {
+            Asset::Material(t) => Some(t),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(BatchingReport {
+        scene,
+        ..analyze_batching(&materials, &objects)
+    })
+}
+
+/// Analyzes the materials of the library and prints the report as JSON.
+/// Returns whether the report was created.
+pub fn print_batching_report(settings: Arc<Settings>, scene: Option<Uuid>) -> bool {
+    let database = load_database(&settings);
+    let library = create_library(&settings);
+
+    match library_batching_report(&database, &library, scene) {
+        Ok(t) => {
+            println!("{}", serde_json::to_string_pretty(&t).unwrap());
+            true
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
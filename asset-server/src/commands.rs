@@ -1,11 +1,12 @@
 use crate::library::Library;
-use crate::models::{Asset, Image, Material, Mesh};
+use crate::models::{Asset, CompileSettings, Image, Material, Mesh, Shader};
 use bf::image::Format;
 use bf::material::BlendMode;
 use bf::mesh::{IndexType, VertexFormat};
-use core::fmt;
+use bf::shader::ShaderStage;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
+use std::fmt;
 use std::fmt::Formatter;
 
 /// Command for launching image importer (`img2bf`) tool.
@@ -14,6 +15,10 @@ pub const IMG2BF: &str = "img2bf.exe";
 pub const OBJ2BF: &str = "obj2bf.exe";
 /// Command for launching material compiler (`matcomp`) tool.
 pub const MATCOMP: &str = "matcomp.exe";
+/// Command for launching material baking (`matbake`) tool.
+pub const MATBAKE: &str = "matbake.exe";
+/// Command for launching shader compiler (`glsl2bf`) tool.
+pub const GLSL2BF: &str = "glsl2bf.exe";
 /// Command for launching information extractor (`bfinfo`) tool.
 pub const BFINFO: &str = "bfinfo.exe";
 
@@ -125,6 +130,11 @@ impl CompileCommand for Image {
             Format::BC6H => cmd.arg("bc6h"),
             Format::BC7 => cmd.arg("bc7"),
             Format::SrgbBC7 => cmd.arg("srgb_bc7"),
+            Format::BC5 => cmd.arg("bc5"),
+            Format::R16 => cmd.arg("r16"),
+            Format::Rg16 => cmd.arg("rg16"),
+            Format::Rgba16F => cmd.arg("rgba16f"),
+            Format::Rgba32F => cmd.arg("rgba32f"),
         };
 
         cmd_flag!(cmd, "--pack-normal-map", self.pack_normal_map);
@@ -165,6 +175,8 @@ impl CompileCommand for Mesh {
         cmd_optional_arg!(cmd, "--geometry-index", self.geometry_index);
         cmd_optional_arg!(cmd, "--lod", self.lod);
         cmd_flag!(cmd, "--recalculate-normals", self.recalculate_normals);
+        cmd_flag!(cmd, "--meshopt", self.meshopt);
+        cmd_flag!(cmd, "--wind-stiffness", self.wind_stiffness);
 
         cmd
     }
@@ -191,12 +203,22 @@ impl CompileCommand for Material {
                 .arg(format!("{},{},{}", t[0], t[1], t[2]));
         }
 
+        if let Some(t) = self.emissive_color {
+            cmd.arg("--emissive-color")
+                .arg(format!("{},{},{}", t[0], t[1], t[2]));
+        }
+
         cmd_optional_arg!(cmd, "--roughness", self.roughness);
         cmd_optional_arg!(cmd, "--metallic", self.metallic);
         cmd_optional_arg!(cmd, "--alpha-cutoff", self.alpha_cutoff);
         cmd_optional_arg!(cmd, "--ior", self.ior);
         cmd_optional_arg!(cmd, "--sss", self.sss);
         cmd_optional_arg!(cmd, "--opacity", self.opacity);
+        cmd_optional_arg!(cmd, "--clear-coat", self.clear_coat);
+        cmd_optional_arg!(cmd, "--clear-coat-roughness", self.clear_coat_roughness);
+        cmd_optional_arg!(cmd, "--anisotropy", self.anisotropy);
+        cmd_flag!(cmd, "--foliage", self.foliage);
+        cmd_flag!(cmd, "--double-sided", self.double_sided);
 
         cmd_optional_arg!(cmd, "--albedo-map", self.albedo_map);
         cmd_optional_arg!(cmd, "--normal-map", self.normal_map);
@@ -205,11 +227,62 @@ impl CompileCommand for Material {
         cmd_optional_arg!(cmd, "--opacity-map", self.opacity_map);
         cmd_optional_arg!(cmd, "--ao-map", self.ao_map);
         cmd_optional_arg!(cmd, "--metallic-map", self.metallic_map);
+        cmd_optional_arg!(cmd, "--emissive-map", self.emissive_map);
+
+        cmd
+    }
+}
+
+impl CompileCommand for Shader {
+    fn compile_command(&self, library: &Library) -> Command {
+        let mut cmd = Command::new(GLSL2BF);
+
+        cmd.arg("--input")
+            .arg(library.db_path_to_disk_path(&self.input_path));
+        cmd.arg("--output")
+            .arg(library.compute_output_path(&self.uuid));
+
+        if let Some(t) = self.stage {
+            cmd.arg("--stage");
+            match t {
+                ShaderStage::Vertex => cmd.arg("vert"),
+                ShaderStage::Fragment => cmd.arg("frag"),
+                ShaderStage::Compute => cmd.arg("comp"),
+            };
+        }
+
+        for variant in self.variants.iter().flatten() {
+            cmd.arg("--variant").arg(variant);
+        }
 
         cmd
     }
 }
 
+/// Generates a command that compiles the `asset` with its options overridden
+/// by the compile `settings`, including options the importer never sets.
+pub fn compile_command_with_settings(
+    asset: &Asset,
+    settings: &CompileSettings,
+    library: &Library,
+) -> Command {
+    let asset = settings.apply(asset);
+    let mut cmd = asset.compile_command(library);
+
+    match asset {
+        Asset::Image(_) => {
+            cmd_optional_arg!(cmd, "--mip-filter", settings.mip_filter);
+            cmd_optional_arg!(cmd, "--compression-level", settings.compression_level);
+        }
+        Asset::Mesh(_) => {
+            cmd_optional_arg!(cmd, "--sky-occlusion", settings.sky_occlusion);
+        }
+        Asset::Material(_) | Asset::Shader(_) => {}
+    }
+
+    cmd
+}
+
 // delegating impl for Asset type
 impl CompileCommand for Asset {
     fn compile_command(&self, library: &Library) -> Command {
@@ -217,6 +290,7 @@ impl CompileCommand for Asset {
             Asset::Image(t) => t.compile_command(library),
             Asset::Mesh(t) => t.compile_command(library),
             Asset::Material(t) => t.compile_command(library),
+            Asset::Shader(t) => t.compile_command(library),
         }
     }
 }
@@ -1,21 +1,35 @@
 //! Asynchronous executor of compile commands.
 
-use crate::commands::CompileCommand;
+use crate::commands::compile_command_with_settings;
 use crate::database::Database;
-use crate::http::models::{CompilationStatus, Event};
+use crate::http::models::{CompilationStatus, CompileProgress, Event};
 use crate::http::stream::publish_server_event;
+use crate::http::ws::publish_progress;
 use crate::library::Library;
 use crate::models::Compilation;
 use crate::scanner::Scanner;
 use crate::settings::Settings;
 use chrono::Utc;
 use log::{error, info};
+use std::future::Future;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
 use uuid::Uuid;
 
+/// How often the estimated progress of running compilation is published.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Estimates the progress of compilation (0 - 100) from time elapsed and the
+/// expected duration. Never reports 100 % as the estimate may be too low.
+fn estimate_progress(elapsed: Duration, eta: Duration) -> f32 {
+    if eta.as_millis() == 0 {
+        return 0.0;
+    }
+    (elapsed.as_secs_f32() / eta.as_secs_f32() * 100.0).min(99.0)
+}
+
 struct CompilerInner {
     max_concurrency: usize,
     semaphore: Semaphore,
@@ -53,7 +67,18 @@ impl Compiler {
         }
     }
 
+    /// Enqueues the asset for compilation in the background.
     pub fn enqueue(&self, uuid: Uuid) {
+        tokio::spawn(self.schedule(uuid));
+    }
+
+    /// Compiles the asset and waits until the compilation is done. The
+    /// compilation still respects the `max_concurrency` limit.
+    pub async fn compile_now(&self, uuid: Uuid) -> Compilation {
+        self.schedule(uuid).await
+    }
+
+    fn schedule(&self, uuid: Uuid) -> impl Future<Output = Compilation> {
         let eta = self
             .database
             .get_compilation_eta(&uuid)
@@ -71,14 +96,14 @@ impl Compiler {
             eta: Duration::from_millis(eta_stats as u64) + eta,
         });
 
-        tokio::spawn(Compiler::compile(
+        Compiler::compile(
             self.database.clone(),
             self.library.clone(),
             self.scanner.clone(),
             self.inner.clone(),
             uuid,
             eta,
-        ));
+        )
     }
 
     async fn compile(
@@ -88,7 +113,7 @@ impl Compiler {
         compiler: Arc<CompilerInner>,
         uuid: Uuid,
         eta: Duration,
-    ) {
+    ) -> Compilation {
         publish_server_event(Event::AssetCompilationStatus {
             uuid,
             status: CompilationStatus::Queued,
@@ -104,16 +129,32 @@ impl Compiler {
             status: CompilationStatus::Compiling,
         });
 
-        let command = asset.compile_command(&library);
+        let settings = database.get_compile_settings(&uuid).unwrap_or_default();
+        let command = compile_command_with_settings(&asset, &settings, &library);
         let start = Utc::now();
         let start_instant = Instant::now();
         let mut error = None;
 
         let cmd_string = command.to_string();
         info!("Run: {}", cmd_string);
+        publish_progress(CompileProgress::Started { uuid });
 
         let mut cmd: tokio::process::Command = command.into();
-        match cmd.output().await {
+        let output = cmd.output();
+        tokio::pin!(output);
+
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        let output = loop {
+            tokio::select! {
+                output = &mut output => break output,
+                _ = ticker.tick() => publish_progress(CompileProgress::Progress {
+                    uuid,
+                    percent: estimate_progress(start_instant.elapsed(), eta),
+                }),
+            }
+        };
+
+        match output {
             Ok(t) => {
                 if !t.status.success() {
                     let err = format!("Process execution failed with code {:?}!", t.status.code());
@@ -146,14 +187,26 @@ impl Compiler {
                 Some(e) => CompilationStatus::Error { error: e.clone() },
             },
         });
+        publish_progress(match &error {
+            None => CompileProgress::Finished {
+                uuid,
+                duration: start_instant.elapsed(),
+            },
+            Some(e) => CompileProgress::Failed {
+                uuid,
+                duration: start_instant.elapsed(),
+                error: e.clone(),
+            },
+        });
 
-        database.insert_compilation(Compilation {
+        let compilation = Compilation {
             uuid,
             timestamp: start,
             duration: start_instant.elapsed().into(),
             cmd: cmd_string,
             error,
-        });
+        };
+        database.insert_compilation(compilation.clone());
 
         scanner.is_dirty(&uuid);
         let eta_stats = compiler
@@ -170,6 +223,7 @@ impl Compiler {
         });
 
         drop(lock);
+        compilation
     }
 }
 
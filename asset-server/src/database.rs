@@ -1,7 +1,7 @@
 //! Persistent storage for application objects.
 
-use crate::input2uuid::dump_input2uuid;
-use crate::models::{Asset, Compilation};
+use crate::input2uuid::{dump_input2uuid, rekey_asset};
+use crate::models::{Asset, Compilation, CompileSettings};
 use crate::settings::Settings;
 use log::info;
 use serde::{Deserialize, Serialize};
@@ -18,6 +18,8 @@ use uuid::Uuid;
 struct DB {
     assets: Vec<Asset>,
     compilations: Vec<Compilation>,
+    #[serde(default)]
+    settings: HashMap<Uuid, CompileSettings>,
 }
 
 pub struct Database {
@@ -25,12 +27,14 @@ pub struct Database {
     dirty: AtomicBool,
     assets: RwLock<HashMap<Uuid, Asset>>,
     compilations: RwLock<HashMap<Uuid, Vec<Compilation>>>,
+    settings: RwLock<HashMap<Uuid, CompileSettings>>,
 }
 
 impl Database {
     pub fn new(file: PathBuf) -> Self {
         let mut assets = HashMap::new();
         let mut compilations: HashMap<Uuid, Vec<Compilation>> = HashMap::new();
+        let mut settings = HashMap::new();
 
         if file.exists() {
             let json = std::fs::read_to_string(&file).expect("cannot read database file");
@@ -52,6 +56,8 @@ impl Database {
                     Entry::Vacant(t) => t.insert(vec![]).push(x),
                 }
             }
+
+            settings = db.settings;
         }
 
         Self {
@@ -59,6 +65,7 @@ impl Database {
             dirty: AtomicBool::new(true),
             assets: RwLock::new(assets),
             compilations: RwLock::new(compilations),
+            settings: RwLock::new(settings),
         }
     }
 
@@ -73,9 +80,12 @@ impl Database {
             .cloned()
             .collect();
 
+        let settings = self.settings.read().unwrap().clone();
+
         let json = serde_json::to_string(&DB {
             assets,
             compilations,
+            settings,
         })
         .expect("cannot serialize database");
         std::fs::write(&self.file, json).expect("cannot write database file");
@@ -93,6 +103,37 @@ impl Database {
         self.assets.read().unwrap().values().cloned().collect()
     }
 
+    pub fn asset_count(&self) -> usize {
+        self.assets.read().unwrap().len()
+    }
+
+    /// Returns at most `limit` assets matching the `filter` ordered by `compare`
+    /// after skipping first `offset` of them, and the number of all matching assets.
+    pub fn query_assets<F, C>(
+        &self,
+        filter: F,
+        mut compare: C,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<Asset>, usize)
+    where
+        F: Fn(&Asset) -> bool,
+        C: FnMut(&Asset, &Asset) -> std::cmp::Ordering,
+    {
+        let assets = self.assets.read().unwrap();
+        let mut matching = assets.values().filter(|x| filter(x)).collect::<Vec<_>>();
+        matching.sort_by(|a, b| compare(a, b));
+
+        let total = matching.len();
+        let page = matching
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        (page, total)
+    }
+
     pub fn find_asset_by_path(&self, path: &str) -> Option<Asset> {
         self.assets
             .read()
@@ -114,6 +155,7 @@ impl Database {
 
     pub fn delete_asset(&self, uuid: &Uuid) {
         self.assets.write().unwrap().remove(uuid);
+        self.settings.write().unwrap().remove(uuid);
         self.dirty.fetch_or(true, Ordering::SeqCst);
     }
 
@@ -135,6 +177,15 @@ impl Database {
             .collect()
     }
 
+    pub fn get_compile_settings(&self, uuid: &Uuid) -> Option<CompileSettings> {
+        self.settings.read().unwrap().get(uuid).cloned()
+    }
+
+    pub fn set_compile_settings(&self, uuid: &Uuid, settings: CompileSettings) {
+        self.settings.write().unwrap().insert(*uuid, settings);
+        self.dirty.fetch_or(true, Ordering::SeqCst);
+    }
+
     pub fn get_compilations(&self, uuid: &Uuid) -> Option<Vec<Compilation>> {
         self.compilations.read().unwrap().get(uuid).cloned()
     }
@@ -147,6 +198,37 @@ impl Database {
             .and_then(|x| x.iter().max_by_key(|c| c.timestamp).cloned())
     }
 
+    /// Changes UUIDs of assets, their compilations and settings (and references between
+    /// assets) according to the `map` of old UUIDs to new ones.
+    pub fn rekey(&self, map: &HashMap<Uuid, Uuid>) {
+        let mut assets = self.assets.write().unwrap();
+        *assets = assets
+            .drain()
+            .map(|(_, mut asset)| {
+                rekey_asset(&mut asset, map);
+                (asset.uuid(), asset)
+            })
+            .collect();
+
+        let mut compilations = self.compilations.write().unwrap();
+        *compilations = compilations
+            .drain()
+            .map(|(uuid, mut list)| {
+                let uuid = *map.get(&uuid).unwrap_or(&uuid);
+                list.iter_mut().for_each(|x| x.uuid = uuid);
+                (uuid, list)
+            })
+            .collect();
+
+        let mut settings = self.settings.write().unwrap();
+        *settings = settings
+            .drain()
+            .map(|(uuid, x)| (*map.get(&uuid).unwrap_or(&uuid), x))
+            .collect();
+
+        self.dirty.fetch_or(true, Ordering::SeqCst);
+    }
+
     pub fn get_compilation_eta(&self, uuid: &Uuid) -> Option<Duration> {
         self.get_last_compilation(uuid).map(|x| x.duration)
     }
@@ -1,11 +1,14 @@
-use crate::http::models::Compile;
+use crate::http::models::{AssetQuery, Bake, BatchingQuery, Compile};
 use crate::http::stream::{create_event_stream, new_client};
-use crate::models::Asset;
+use crate::http::ws::{create_progress_stream, new_ws_client};
+use crate::models::{Asset, CompileSettings};
 use crate::ops::Ops;
 use actix_cors::Cors;
-use actix_web::http::StatusCode;
-use actix_web::web::{Bytes, Data, Json, Path};
-use actix_web::{rt, web, App, HttpResponse, HttpServer, Responder};
+use actix_multipart::Multipart;
+use actix_web::http::{header, StatusCode};
+use actix_web::web::{Bytes, Data, Json, Path, Query};
+use actix_web::{rt, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder};
+use futures::TryStreamExt;
 use log::info;
 use std::ops::Deref;
 use std::sync::Arc;
@@ -13,11 +16,16 @@ use uuid::Uuid;
 
 pub mod models;
 pub mod stream;
+pub mod ws;
+
+/// Maximal total size of files uploaded in one request.
+const MAX_UPLOAD_SIZE: usize = 512 * 1024 * 1024;
 
 pub async fn start_server(port: u16, ops: Arc<Ops>) -> std::io::Result<()> {
     let local = tokio::task::LocalSet::new();
     let sys = rt::System::run_in_tokio("server", &local);
     let stream = create_event_stream();
+    let progress = create_progress_stream();
     let ops = Data::new(ops);
 
     info!("The server is configured to listen on 0.0.0.0:{}!", port);
@@ -27,23 +35,38 @@ pub async fn start_server(port: u16, ops: Arc<Ops>) -> std::io::Result<()> {
         App::new()
             .wrap(Cors::permissive())
             .app_data(stream.clone())
+            .app_data(progress.clone())
             .app_data(ops.clone())
             .route("/", web::get().to(index))
             .route("/events", web::get().to(new_client))
+            .route("/ws", web::get().to(new_ws_client))
             .route("/assets", web::get().to(get_all_assets))
+            .route("/assets", web::post().to(upload_assets))
             .route("/assets/dirty", web::get().to(get_dirty_assets))
             .route("/assets/{uuid}", web::get().to(get_asset))
             .route("/assets/{uuid}", web::put().to(put_asset))
             .route("/assets/{uuid}", web::delete().to(delete_asset))
             .route("/assets/{uuid}/preview", web::get().to(get_asset_preview))
+            .route("/assets/{uuid}/compiled", web::get().to(get_compiled_asset))
             .route("/assets/{uuid}/open", web::post().to(open_in_external_tool))
+            .route("/assets/{uuid}/bake", web::post().to(bake_material))
+            .route(
+                "/assets/{uuid}/settings",
+                web::get().to(get_compile_settings),
+            )
+            .route(
+                "/assets/{uuid}/settings",
+                web::put().to(put_compile_settings),
+            )
             .route(
                 "/assets/{uuid}/compilations",
                 web::get().to(get_asset_compilations),
             )
+            .route("/analysis/batching", web::get().to(get_batching_report))
             .route("/compile", web::post().to(compile_all))
             .route("/refresh", web::post().to(refresh_all))
             .route("/open/root", web::post().to(open_library_root))
+            .route("/library/{uuid}.bf", web::get().to(get_library_file))
     })
     .bind(&format!("0.0.0.0:{}", port))?
     .run()
@@ -57,8 +80,66 @@ async fn index() -> impl Responder {
     format!("asset-server")
 }
 
-async fn get_all_assets(ops: Data<Arc<Ops>>) -> impl Responder {
-    Json(ops.get_all_assets())
+/// Responds with all assets, or with one page of assets matching the query
+/// (see [`AssetQuery`](models/struct.AssetQuery.html)) when there is one.
+async fn get_all_assets(
+    request: HttpRequest,
+    query: Query<AssetQuery>,
+    ops: Data<Arc<Ops>>,
+) -> impl Responder {
+    if request.query_string().is_empty() {
+        return HttpResponse::Ok().json(ops.get_all_assets());
+    }
+
+    match ops.query_assets(query.deref()) {
+        Ok(t) => HttpResponse::Ok().json(t),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+/// Stores source files uploaded as `multipart/form-data` into the library and
+/// imports them. Every field with a file name is a file, optional `directory`
+/// field is the target directory (relative to the library root) and `overwrite`
+/// field (`true`) allows replacing existing files.
+async fn upload_assets(mut payload: Multipart, ops: Data<Arc<Ops>>) -> Result<HttpResponse, Error> {
+    let mut directory = String::new();
+    let mut overwrite = false;
+    let mut files = vec![];
+    let mut size = 0;
+
+    while let Some(mut field) = payload.try_next().await? {
+        let disposition = field.content_disposition();
+        let name = disposition
+            .as_ref()
+            .and_then(|x| x.get_name())
+            .map(str::to_string);
+        let file_name = disposition
+            .as_ref()
+            .and_then(|x| x.get_filename())
+            .map(str::to_string);
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            size += chunk.len();
+            if size > MAX_UPLOAD_SIZE {
+                return Ok(HttpResponse::PayloadTooLarge().body("upload is too large"));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        match (name.as_deref(), file_name) {
+            (_, Some(t)) => files.push((t, data)),
+            (Some("directory"), None) => directory = String::from_utf8_lossy(&data).into_owned(),
+            (Some("overwrite"), None) => overwrite = data.as_slice() == b"true",
+            _ => {}
+        }
+    }
+
+    if files.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("no files were uploaded"));
+    }
+
+    Ok(HttpResponse::Ok().json(ops.upload_files(&directory, files, overwrite)))
 }
 
 async fn get_asset(uuid: Path<Uuid>, ops: Data<Arc<Ops>>) -> impl Responder {
@@ -88,10 +169,92 @@ async fn get_asset_preview(uuid: Path<Uuid>, ops: Data<Arc<Ops>>) -> impl Respon
     }
 }
 
+async fn get_compiled_asset(
+    request: HttpRequest,
+    uuid: Path<Uuid>,
+    ops: Data<Arc<Ops>>,
+) -> impl Responder {
+    compiled_file(&request, uuid.deref(), &ops)
+}
+
+/// Serves the compiled file of the asset as a static file of the library.
+async fn get_library_file(
+    request: HttpRequest,
+    uuid: Path<Uuid>,
+    ops: Data<Arc<Ops>>,
+) -> impl Responder {
+    compiled_file(&request, uuid.deref(), &ops)
+}
+
+/// Responds with the compiled file of the asset and its `ETag`, so clients
+/// (eg. the renderer) can cache it and revalidate it. Requests with matching
+/// `If-None-Match` header get `304 Not Modified` without the file.
+fn compiled_file(request: &HttpRequest, uuid: &Uuid, ops: &Ops) -> HttpResponse {
+    let etag = match ops.compiled_asset_etag(uuid) {
+        None => return HttpResponse::NotFound().body(""),
+        Some(t) => t,
+    };
+
+    let not_modified = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|x| x.to_str().ok())
+        .map_or(false, |x| x.split(',').any(|x| x.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .header(header::ETAG, etag)
+            .finish();
+    }
+
+    match ops.read_compiled_asset(uuid) {
+        None => HttpResponse::NotFound().body(""),
+        Some(t) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .header(header::ETAG, etag)
+            .body(Bytes::from(t)),
+    }
+}
+
+async fn get_compile_settings(uuid: Path<Uuid>, ops: Data<Arc<Ops>>) -> impl Responder {
+    Json(ops.get_compile_settings(uuid.deref()))
+}
+
+async fn put_compile_settings(
+    uuid: Path<Uuid>,
+    settings: Json<CompileSettings>,
+    ops: Data<Arc<Ops>>,
+) -> impl Responder {
+    match ops.update_compile_settings(uuid.deref(), settings.into_inner()) {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+/// Bakes the material into a flattened texture set and responds with UUID
+/// of the baked material.
+async fn bake_material(uuid: Path<Uuid>, bake: Json<Bake>, ops: Data<Arc<Ops>>) -> impl Responder {
+    match ops
+        .bake_material(uuid.deref(), bake.resolution, bake.tiling)
+        .await
+    {
+        Ok(t) => HttpResponse::Ok().json(t),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
 async fn get_asset_compilations(uuid: Path<Uuid>, ops: Data<Arc<Ops>>) -> impl Responder {
     Json(ops.get_compilations(uuid.deref()))
 }
 
+/// Responds with materials that could be merged to save draw calls (see
+/// [`BatchingReport`](../batching/struct.BatchingReport.html)).
+async fn get_batching_report(query: Query<BatchingQuery>, ops: Data<Arc<Ops>>) -> impl Responder {
+    match ops.batching_report(query.scene) {
+        Ok(t) => HttpResponse::Ok().json(t),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
 async fn compile_all(compile: Json<Compile>, ops: Data<Arc<Ops>>) -> impl Responder {
     Json(ops.compile_all(compile.assets.clone()))
 }
@@ -9,6 +9,75 @@ pub struct Compile {
     pub assets: Vec<Uuid>,
 }
 
+/// Query parameters of `GET /assets`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct AssetQuery {
+    /// Type of the assets (`image`, `mesh`, `material` or `shader`).
+    #[serde(rename = "type")]
+    pub asset_type: Option<String>,
+    /// Case-insensitive part of the name of the assets.
+    pub name_contains: Option<String>,
+    /// Tag the assets must have.
+    pub tag: Option<String>,
+    /// Whether the assets need recompilation.
+    pub dirty: Option<bool>,
+    /// Index of the page starting from 1.
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    /// Property the assets are sorted by (`name`, `mtime`, `type` or `uuid`),
+    /// prefixed with `-` for descending order.
+    pub sort: Option<String>,
+}
+
+/// Response of `GET /assets` with query parameters.
+#[derive(Serialize, Deserialize)]
+pub struct AssetPage {
+    pub assets: Vec<Asset>,
+    pub page: usize,
+    pub per_page: usize,
+    /// Number of pages of the matching assets.
+    pub pages: usize,
+    /// Number of assets matching the query.
+    pub total: usize,
+    /// Number of all assets in the library.
+    pub library_total: usize,
+}
+
+/// Result of storing and importing one file uploaded to `POST /assets`.
+#[derive(Serialize, Deserialize)]
+pub struct UploadedFile {
+    /// Path of the file relative to the library root.
+    pub path: String,
+    /// UUID of the imported (or already tracked) asset.
+    pub uuid: Option<Uuid>,
+    /// Why the file was not stored or imported.
+    pub error: Option<String>,
+}
+
+/// Response of `POST /assets`.
+#[derive(Serialize, Deserialize)]
+pub struct UploadReport {
+    pub files: Vec<UploadedFile>,
+    /// Material imported from the target directory (when it contains material maps).
+    pub material: Option<Uuid>,
+}
+
+/// Query parameters of `GET /analysis/batching`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct BatchingQuery {
+    /// Compiled scene (tree) used to estimate the saved draw calls.
+    pub scene: Option<Uuid>,
+}
+
+/// Request of `POST /assets/{uuid}/bake`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bake {
+    /// Width and height of the baked maps.
+    pub resolution: u32,
+    /// Number of repetitions of the source maps in U and V direction.
+    pub tiling: Option<[f32; 2]>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum CompilationStatus {
@@ -42,3 +111,26 @@ pub enum Event {
     },
     ScanResults(ScanResults),
 }
+
+/// Progress of a single asset compilation streamed over the `/ws` endpoint.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CompileProgress {
+    Started {
+        uuid: Uuid,
+    },
+    /// Estimated progress (0 - 100) based on duration of previous compilations.
+    Progress {
+        uuid: Uuid,
+        percent: f32,
+    },
+    Finished {
+        uuid: Uuid,
+        duration: Duration,
+    },
+    Failed {
+        uuid: Uuid,
+        duration: Duration,
+        error: String,
+    },
+}
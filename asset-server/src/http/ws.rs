@@ -0,0 +1,105 @@
+use crate::http::models::CompileProgress;
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::web::{Data, Payload};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use log::{error, warn};
+use once_cell::sync::OnceCell;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{channel, Receiver, RecvError, Sender};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub async fn new_ws_client(
+    req: HttpRequest,
+    stream: Payload,
+    progress: Data<Sender<String>>,
+) -> Result<HttpResponse, Error> {
+    ws::start(WsSession::new(progress.subscribe()), &req, stream)
+}
+
+static PROGRESS: OnceCell<Data<Sender<String>>> = OnceCell::new();
+
+pub fn create_progress_stream() -> Data<Sender<String>> {
+    let (tx, _) = channel(256);
+    let tx = Data::new(tx);
+    PROGRESS.get_or_init(|| tx.clone());
+    tx
+}
+
+/// Publishes the compilation progress to all connected websocket clients.
+pub fn publish_progress(progress: CompileProgress) {
+    if let Some(t) = PROGRESS.get() {
+        let json = match serde_json::to_string(&progress) {
+            Ok(json) => json,
+            Err(t) => {
+                error!("Cannot convert progress: {:?}", t);
+                return;
+            }
+        };
+
+        // sending fails only when there are no clients connected
+        t.send(json).unwrap_or(0);
+    }
+}
+
+struct WsSession {
+    last_heartbeat: Instant,
+    progress: Option<Receiver<String>>,
+}
+
+impl WsSession {
+    fn new(progress: Receiver<String>) -> Self {
+        Self {
+            last_heartbeat: Instant::now(),
+            progress: Some(progress),
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if act.last_heartbeat.elapsed() > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+
+        if let Some(progress) = self.progress.take() {
+            ctx.add_stream(progress);
+        }
+    }
+}
+
+impl StreamHandler<Result<String, RecvError>> for WsSession {
+    fn handle(&mut self, msg: Result<String, RecvError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(json) => ctx.text(json),
+            Err(RecvError::Lagged(n)) => warn!("Websocket client skipped {} messages!", n),
+            Err(RecvError::Closed) => ctx.stop(),
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.last_heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => self.last_heartbeat = Instant::now(),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
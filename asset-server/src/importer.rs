@@ -2,9 +2,10 @@
 
 use crate::database::Database;
 use crate::library::Library;
-use crate::models::{Asset, Image, Material, Mesh};
+use crate::models::{Asset, Image, Material, Mesh, Shader};
 use bf::image::Format;
 use bf::material::BlendMode;
+use bf::shader::ShaderStage;
 use chrono::Utc;
 use std::ffi::OsStr;
 use std::path::Path;
@@ -26,6 +27,7 @@ const GLOSSINESS_STRINGS: &[&str] = &["[gloss].", "_gloss."];
 const OCCLUSION_STRINGS: &[&str] = &["_ao.", "_ambientocclusion.", "_occlusion."];
 const METALLIC_STRINGS: &[&str] = &["_met.", "_metallic.", "_metalness."];
 const OPACITY_STRINGS: &[&str] = &["_opacity."];
+const EMISSIVE_STRINGS: &[&str] = &["_emissive.", "_emission."];
 
 #[derive(Debug)]
 pub enum ImportError {
@@ -57,8 +59,11 @@ impl Importer {
             .map(str::to_lowercase)
         {
             Some(t) => match t.as_str() {
-                "jpg" | "png" | "tiff" | "tif" | "tga" => self.try_import_image(uuid, disk_path)?,
+                "jpg" | "png" | "tiff" | "tif" | "tga" | "hdr" | "exr" => {
+                    self.try_import_image(uuid, disk_path)?
+                }
                 "obj" => self.try_import_mesh(uuid, disk_path)?,
+                "glsl" | "vert" | "frag" | "comp" => self.try_import_shader(uuid, disk_path)?,
                 _ => return Err(ImportError::UnsupportedExtension),
             },
             None => self.try_import_material(uuid, disk_path)?,
@@ -109,9 +114,16 @@ impl Importer {
             ao_map: Option::None,
             metallic_map: Option::None,
             opacity_map: Option::None,
+            emissive_map: Option::None,
             opacity: Option::None,
             ior: Option::None,
             sss: Option::None,
+            emissive_color: Option::None,
+            clear_coat: Option::None,
+            clear_coat_roughness: Option::None,
+            anisotropy: Option::None,
+            foliage: Option::None,
+            double_sided: Option::None,
         };
 
         for x in std::fs::read_dir(disk_path).map_err(|_| ImportError::ReadDirError)? {
@@ -148,6 +160,9 @@ impl Importer {
             } else if OPACITY_STRINGS.iter().any(|x| file_name.contains(x)) {
                 asset.opacity_map = Some(self.find_dependency_uuid(&x)?);
                 is_material = true;
+            } else if EMISSIVE_STRINGS.iter().any(|x| file_name.contains(x)) {
+                asset.emissive_map = Some(self.find_dependency_uuid(&x)?);
+                is_material = true;
             }
         }
 
@@ -177,6 +192,29 @@ impl Importer {
             geometry_index: Option::None,
             lod: Option::None,
             recalculate_normals: Option::None,
+            meshopt: Option::None,
+            wind_stiffness: Option::None,
+        }))
+    }
+
+    pub fn try_import_shader(&self, uuid: Uuid, disk_path: &Path) -> Result<Asset, ImportError> {
+        let input_path = self.library.disk_path_to_db_path(disk_path).to_string();
+
+        // files without recognizable stage (eg. `inc_brdf.glsl`) are only included by other shaders
+        let stage = disk_path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .and_then(ShaderStage::from_file_name)
+            .ok_or(ImportError::NothingToImport)?;
+
+        Ok(Asset::Shader(Shader {
+            uuid,
+            name: input_path.clone(),
+            input_path,
+            tags: vec!["shader".to_string()],
+            updated_at: Utc::now(),
+            stage: Some(stage),
+            variants: Option::None,
         }))
     }
 
@@ -194,16 +232,18 @@ impl Importer {
         };
         let mut tags = vec!["texture".to_string()];
         let mut format = Format::Rgba8;
-        let mut pack_normal_map = false;
 
         // determine correct format
-        if ALBEDO_STRINGS.iter().any(|x| file_name.contains(x)) {
+        if file_name.ends_with(".hdr") || file_name.ends_with(".exr") {
+            // high dynamic range images (eg. environment maps) are kept in floats
+            format = Format::Rgba16F;
+            tags.push("hdr".to_string());
+        } else if ALBEDO_STRINGS.iter().any(|x| file_name.contains(x)) {
             format = Format::SrgbDxt1;
         } else if DISPLACEMENT_STRINGS.iter().any(|x| file_name.contains(x)) {
             format = Format::R8;
         } else if NORMAL_STRINGS.iter().any(|x| file_name.contains(x)) {
-            format = Format::Dxt5;
-            pack_normal_map = true;
+            format = Format::BC5;
             tags.push("normal-map".to_string());
         } else if ROUGHNESS_STRINGS.iter().any(|x| file_name.contains(x)) {
             format = Format::R8;
@@ -215,6 +255,8 @@ impl Importer {
             format = Format::R8;
         } else if OPACITY_STRINGS.iter().any(|x| file_name.contains(x)) {
             format = Format::R8;
+        } else if EMISSIVE_STRINGS.iter().any(|x| file_name.contains(x)) {
+            format = Format::SrgbDxt1;
         }
 
         Ok(Asset::Image(Image {
@@ -224,7 +266,7 @@ impl Importer {
             tags,
             updated_at: Utc::now(),
             format,
-            pack_normal_map: Some(pack_normal_map),
+            pack_normal_map: Some(false),
             v_flip: Option::None,
             h_flip: Option::None,
         }))
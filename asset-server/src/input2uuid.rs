@@ -1,12 +1,177 @@
+//! Derivation of asset UUIDs from source paths and the `input2uuid` translation file.
+//!
+//! UUID of an imported asset is a UUID v5 of the path of its source file relative
+//! to the library root (directory for materials) in a namespace of the library.
+//! The path is normalized first, so the same library gets the same UUIDs on every
+//! machine and platform.
+//!
+//! Each importer (`image`, `mesh`, `material` and `shader`) can have a salt
+//! (`uuid_salts` setting). UUIDs of its assets are then derived in a namespace
+//! of the importer (UUID v5 of the salt in the namespace of the library), eg.
+//! to give new UUIDs to assets of an importer whose output changed incompatibly.
+//! Assets that got new UUIDs are recorded in the remap table (`uuid_remap`
+//! setting, see `bf::remap`), so references to the old UUIDs still resolve.
+
 use crate::models::Asset;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// Namespace of UUIDs of libraries that don't set the `uuid_namespace` setting.
+pub fn default_namespace() -> Uuid {
+    Uuid::parse_str("2d1aeb08-db87-48f9-a967-cfb5f06746dc").unwrap()
+}
+
+/// Returns the relative `path` with components separated by `/` and without
+/// empty and `.` components.
+pub fn normalize_source_path(path: &str) -> String {
+    path.split(|c| c == '/' || c == '\\')
+        .filter(|x| !x.is_empty() && *x != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the UUID of an asset imported from the source file at `path`
+/// (relative to the library root).
+pub fn deterministic_uuid(namespace: &Uuid, path: &str) -> Uuid {
+    Uuid::new_v5(namespace, normalize_source_path(path).as_bytes())
+}
+
+/// Returns the name of the importer of the source file (or material directory)
+/// at `path`.
+pub fn importer_name(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase);
+
+    match extension.as_deref() {
+        None => "material",
+        Some("obj") => "mesh",
+        Some("glsl") | Some("vert") | Some("frag") | Some("comp") => "shader",
+        Some(_) => "image",
+    }
+}
+
+/// Derivation of asset UUIDs configured by the namespace of the library and
+/// salts of importers.
+pub struct UuidDerivation {
+    namespace: Uuid,
+    importer_namespaces: HashMap<String, Uuid>,
+}
+
+impl UuidDerivation {
+    pub fn new(namespace: Uuid, salts: &HashMap<String, String>) -> Self {
+        let importer_namespaces = salts
+            .iter()
+            .filter(|(_, salt)| !salt.is_empty())
+            .map(|(importer, salt)| (importer.clone(), Uuid::new_v5(&namespace, salt.as_bytes())))
+            .collect();
+
+        Self {
+            namespace,
+            importer_namespaces,
+        }
+    }
+
+    /// Returns the namespace of the library.
+    pub fn namespace(&self) -> &Uuid {
+        &self.namespace
+    }
+
+    /// Returns the UUID of an asset imported from the source file at `path`
+    /// (relative to the library root).
+    pub fn uuid(&self, path: &str) -> Uuid {
+        let namespace = self
+            .importer_namespaces
+            .get(importer_name(path))
+            .unwrap_or(&self.namespace);
+
+        deterministic_uuid(namespace, path)
+    }
+}
+
+/// Returns the path (relative to the library root) the `asset` was imported from.
+pub fn source_path(asset: &Asset) -> String {
+    match asset.input_path() {
+        Some(t) => t.clone(),
+        // materials are imported from directories and named after them
+        None => asset.name().trim_end_matches(".mat").to_string(),
+    }
+}
+
+/// Returns new UUIDs (by the current ones) of `assets` whose UUIDs are not derived
+/// from their source paths, eg. because they were imported on another platform
+/// or their file was renamed. Fails when two assets would get the same UUID.
+pub fn rekey_map(
+    derivation: &UuidDerivation,
+    assets: &[Asset],
+) -> Result<HashMap<Uuid, Uuid>, String> {
+    let mut map = HashMap::new();
+    let mut owners = HashMap::new();
+
+    for asset in assets {
+        let path = source_path(asset);
+        let uuid = derivation.uuid(&path);
+
+        if let Some(other) = owners.insert(uuid, asset.name()) {
+            return Err(format!(
+                "assets {:?} and {:?} have the same source path {:?}",
+                other,
+                asset.name(),
+                path
+            ));
+        }
+        if uuid != asset.uuid() {
+            map.insert(asset.uuid(), uuid);
+        }
+    }
+
+    Ok(map)
+}
+
+/// Changes the UUID of the `asset` and UUIDs of assets it references according
+/// to the `map` (from [`rekey_map`](fn.rekey_map.html)).
+pub fn rekey_asset(asset: &mut Asset, map: &HashMap<Uuid, Uuid>) {
+    let rekey = |uuid: &mut Uuid| {
+        if let Some(t) = map.get(uuid) {
+            *uuid = *t;
+        }
+    };
+
+    match asset {
+        Asset::Image(t) => rekey(&mut t.uuid),
+        Asset::Mesh(t) => rekey(&mut t.uuid),
+        Asset::Shader(t) => rekey(&mut t.uuid),
+        Asset::Material(t) => {
+            rekey(&mut t.uuid);
+            for reference in [
+                &mut t.albedo_map,
+                &mut t.normal_map,
+                &mut t.displacement_map,
+                &mut t.roughness_map,
+                &mut t.ao_map,
+                &mut t.metallic_map,
+                &mut t.opacity_map,
+                &mut t.emissive_map,
+            ] {
+                if let Some(uuid) = reference {
+                    rekey(uuid);
+                }
+            }
+        }
+    }
+}
 
 pub async fn dump_input2uuid(input2uuid_file: &str, assets: Vec<Asset>) {
     let mut file = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
+        .truncate(true)
         .open(&input2uuid_file)
         .await
         .unwrap();
@@ -21,3 +186,165 @@ pub async fn dump_input2uuid(input2uuid_file: &str, assets: Vec<Asset>) {
         .unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::input2uuid::{
+        default_namespace, deterministic_uuid, normalize_source_path, rekey_asset, rekey_map,
+        UuidDerivation,
+    };
+    use crate::models::{Asset, Image};
+    use bf::image::Format;
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn image(path: &str, uuid: Uuid) -> Asset {
+        Asset::Image(Image {
+            uuid,
+            name: path.to_string(),
+            input_path: path.to_string(),
+            updated_at: Utc::now(),
+            tags: vec![],
+            format: Format::SrgbDxt1,
+            pack_normal_map: None,
+            v_flip: None,
+            h_flip: None,
+        })
+    }
+
+    fn material(name: &str, uuid: Uuid, albedo_map: Uuid) -> Asset {
+        serde_json::from_value(serde_json::json!({
+            "type": "Material",
+            "uuid": uuid,
+            "name": name,
+            "tags": [],
+            "updated_at": Utc::now(),
+            "albedo_map": albedo_map,
+        }))
+        .unwrap()
+    }
+
+    fn uuid(text: &str) -> Uuid {
+        Uuid::parse_str(text).unwrap()
+    }
+
+    #[test]
+    fn source_paths_are_normalized() {
+        assert_eq!(normalize_source_path("a\\b.png"), "a/b.png");
+        assert_eq!(normalize_source_path("./a/b.png"), "a/b.png");
+        assert_eq!(normalize_source_path(".\\a//b.png"), "a/b.png");
+        assert_eq!(normalize_source_path("b.png"), "b.png");
+    }
+
+    #[test]
+    fn uuids_do_not_depend_on_path_separators() {
+        let expected = uuid("5d6dc071-3043-5815-a0b0-82b8cdd60a6d");
+
+        assert_eq!(
+            deterministic_uuid(&default_namespace(), "a/b.png"),
+            expected
+        );
+        assert_eq!(
+            deterministic_uuid(&default_namespace(), "a\\b.png"),
+            expected
+        );
+        assert_eq!(
+            deterministic_uuid(&default_namespace(), "./a/b.png"),
+            expected
+        );
+        assert_ne!(deterministic_uuid(&Uuid::nil(), "a/b.png"), expected);
+    }
+
+    #[test]
+    fn rekey_map_contains_only_changed_uuids() {
+        let derivation = UuidDerivation::new(default_namespace(), &HashMap::new());
+        let derived = uuid("5d6dc071-3043-5815-a0b0-82b8cdd60a6d");
+        let assets = vec![
+            image("a\\b.png", derived),
+            image("a/c.png", Uuid::from_u128(1)),
+            material(
+                "materials/brick.mat",
+                Uuid::from_u128(2),
+                Uuid::from_u128(1),
+            ),
+        ];
+
+        let map = rekey_map(&derivation, &assets).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&Uuid::from_u128(1)], derivation.uuid("a/c.png"));
+        assert_eq!(
+            map[&Uuid::from_u128(2)],
+            uuid("c4406c15-74e2-57a1-9dea-bdf42f2a8fc1")
+        );
+    }
+
+    #[test]
+    fn rekey_map_rejects_same_source_paths() {
+        let derivation = UuidDerivation::new(default_namespace(), &HashMap::new());
+        let assets = vec![
+            image("a\\b.png", Uuid::from_u128(1)),
+            image("./a/b.png", Uuid::from_u128(2)),
+        ];
+
+        assert!(rekey_map(&derivation, &assets).is_err());
+    }
+
+    #[test]
+    fn salted_importers_derive_uuids_in_their_namespace() {
+        let mut salts = HashMap::new();
+        salts.insert("image".to_string(), "v2".to_string());
+        salts.insert("mesh".to_string(), "".to_string());
+        let salted = UuidDerivation::new(default_namespace(), &salts);
+        let unsalted = UuidDerivation::new(default_namespace(), &HashMap::new());
+
+        assert_eq!(
+            unsalted.uuid("a\\b.png"),
+            uuid("5d6dc071-3043-5815-a0b0-82b8cdd60a6d")
+        );
+        assert_eq!(
+            salted.uuid("a\\b.png"),
+            uuid("813c0f25-846f-5299-b12b-e188a254ae9b")
+        );
+        // empty salt is the same as no salt
+        assert_eq!(
+            salted.uuid("a/b.obj"),
+            uuid("f73f17f8-14af-5986-9168-23888a3e2181")
+        );
+        assert_eq!(salted.uuid("a/b.obj"), unsalted.uuid("a/b.obj"));
+    }
+
+    #[test]
+    fn rekey_map_maps_unsalted_uuids_to_salted() {
+        let mut salts = HashMap::new();
+        salts.insert("image".to_string(), "v2".to_string());
+        let derivation = UuidDerivation::new(default_namespace(), &salts);
+        let unsalted = uuid("5d6dc071-3043-5815-a0b0-82b8cdd60a6d");
+        let assets = vec![
+            image("a/b.png", unsalted),
+            material("brick.mat", derivation.uuid("brick"), unsalted),
+        ];
+
+        let map = rekey_map(&derivation, &assets).unwrap();
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&unsalted], uuid("813c0f25-846f-5299-b12b-e188a254ae9b"));
+    }
+
+    #[test]
+    fn rekeyed_materials_reference_new_uuids() {
+        let mut map = HashMap::new();
+        map.insert(Uuid::from_u128(1), Uuid::from_u128(3));
+        map.insert(Uuid::from_u128(2), Uuid::from_u128(4));
+        let mut asset = material("brick.mat", Uuid::from_u128(2), Uuid::from_u128(1));
+
+        rekey_asset(&mut asset, &map);
+
+        assert_eq!(asset.uuid(), Uuid::from_u128(4));
+        match asset {
+            Asset::Material(t) => assert_eq!(t.albedo_map, Some(Uuid::from_u128(3))),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,12 +1,24 @@
 //! Provides utility path functions related to asset library.
 
+use crate::input2uuid::{default_namespace, normalize_source_path, UuidDerivation};
 use crate::settings::Settings;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Errors that may happen when storing an uploaded source file.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The path is empty or points outside of the library root.
+    InvalidPath,
+    /// A file already exists at the path and overwriting was not allowed.
+    AlreadyExists,
+    Io(std::io::Error),
+}
+
 pub struct Library {
-    project_uuid: Uuid,
+    uuids: UuidDerivation,
     library_root: PathBuf,
     output_root: PathBuf,
 }
@@ -33,23 +45,56 @@ impl Library {
         self.library_root.join(db_path)
     }
 
+    /// Writes `data` of an uploaded source file to `db_path` (relative to the
+    /// library root) and returns its path on disk. Paths with parent directory
+    /// components are rejected, so files can't be written outside of the library.
+    pub fn store_source_file(
+        &self,
+        db_path: &str,
+        data: &[u8],
+        overwrite: bool,
+    ) -> Result<PathBuf, StoreError> {
+        let db_path = normalize_source_path(db_path);
+        if db_path.is_empty() || db_path.split('/').any(|x| x == ".." || x.contains(':')) {
+            return Err(StoreError::InvalidPath);
+        }
+
+        let disk_path = self.db_path_to_disk_path(&db_path);
+        if disk_path.exists() && !overwrite {
+            return Err(StoreError::AlreadyExists);
+        }
+        if let Some(parent) = disk_path.parent() {
+            std::fs::create_dir_all(parent).map_err(StoreError::Io)?;
+        }
+        std::fs::write(&disk_path, data).map_err(StoreError::Io)?;
+
+        Ok(disk_path)
+    }
+
     pub fn relativize_input_path<'a>(&self, path: &'a Path) -> &'a Path {
         path.strip_prefix(&self.library_root)
             .expect("cannot relativize path")
     }
 
+    /// Returns the derivation of UUIDs from source paths.
+    pub fn uuid_derivation(&self) -> &UuidDerivation {
+        &self.uuids
+    }
+
+    /// Returns the UUID of an asset imported from the file (or material directory)
+    /// at `disk_path`. The UUID is derived from the normalized relative path, so
+    /// it is the same on every platform (see `input2uuid` module).
     pub fn determine_uuid_by_path(&self, disk_path: &Path) -> Uuid {
-        // todo: maybe change this to uuid_v4 so we can rename files
-        Uuid::new_v5(
-            &self.project_uuid,
-            self.disk_path_to_db_path(disk_path).as_bytes(),
-        )
+        self.uuids.uuid(self.disk_path_to_db_path(disk_path))
     }
 }
 
 pub fn create_library(settings: &Settings) -> Arc<Library> {
     let library = Library {
-        project_uuid: Uuid::parse_str("2d1aeb08-db87-48f9-a967-cfb5f06746dc").unwrap(),
+        uuids: UuidDerivation::new(
+            settings.uuid_namespace.unwrap_or_else(default_namespace),
+            settings.uuid_salts.as_ref().unwrap_or(&HashMap::new()),
+        ),
         library_root: PathBuf::from(&settings.library_root),
         output_root: PathBuf::from(&settings.library_target),
     };
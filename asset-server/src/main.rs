@@ -1,16 +1,28 @@
+use crate::analyze::analyze_library;
+use crate::batch::compile_batch;
+use crate::batching::print_batching_report;
 use crate::compiler::create_compiler;
 use crate::database::load_database;
 use crate::ext_tools::create_ext_tools;
 use crate::http::start_server;
 use crate::importer::create_importer;
 use crate::library::create_library;
+use crate::migrate::migrate_uuids;
 use crate::ops::create_ops;
 use crate::preview::create_preview;
 use crate::scanner::create_scanner;
 use crate::settings::load_settings;
+use crate::settings::Settings;
 use crate::watch::create_watcher;
+use core::settings::parse_set_arg;
 use log::info;
+use std::sync::Arc;
+use structopt::StructOpt;
+use uuid::Uuid;
 
+pub mod analyze;
+pub mod batch;
+pub mod batching;
 pub mod commands;
 pub mod compiler;
 pub mod database;
@@ -19,6 +31,7 @@ pub mod http;
 pub mod importer;
 pub mod input2uuid;
 pub mod library;
+pub mod migrate;
 pub mod models;
 pub mod ops;
 pub mod preview;
@@ -26,13 +39,82 @@ pub mod scanner;
 pub mod settings;
 pub mod watch;
 
+#[derive(StructOpt, Debug)]
+#[structopt(name = "asset-server")]
+struct Opt {
+    /// Overrides a setting (`name=value`, see README for the list of settings)
+    #[structopt(long = "set", number_of_values = 1, parse(try_from_str = parse_set_arg))]
+    set: Vec<(String, String)>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Cmd {
+    /// Starts the server (default)
+    Serve,
+    /// Compiles the library without starting the server
+    Compile {
+        /// Compile all assets instead of only the dirty ones
+        #[structopt(long)]
+        all: bool,
+
+        /// Number of concurrent compilations (defaults to `max_concurrency` setting)
+        #[structopt(short, long)]
+        jobs: Option<usize>,
+    },
+    /// Reports unused textures and textures misused by materials
+    Analyze {
+        /// Report textures used by at least this many materials
+        #[structopt(long, default_value = "5")]
+        shared: usize,
+    },
+    /// Prints materials that could be merged to save draw calls as JSON
+    Batching {
+        /// Compiled scene (tree) used to estimate the saved draw calls
+        #[structopt(long)]
+        scene: Option<Uuid>,
+    },
+    /// Changes UUIDs of assets to the ones derived from their source paths
+    MigrateUuids {
+        /// Only print the changes
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
-    info!("Starting asset server...");
+    let opt = Opt::from_args();
 
     // load settings
-    let settings = load_settings();
+    let settings = load_settings(opt.set);
+
+    match opt.cmd.unwrap_or(Cmd::Serve) {
+        Cmd::Serve => serve(settings).await,
+        Cmd::Compile { all, jobs } => {
+            if !compile_batch(settings, all, jobs).await {
+                std::process::exit(1);
+            }
+        }
+        Cmd::Analyze { shared } => analyze_library(settings, shared),
+        Cmd::Batching { scene } => {
+            if !print_batching_report(settings, scene) {
+                std::process::exit(1);
+            }
+        }
+        Cmd::MigrateUuids { dry_run } => {
+            if !migrate_uuids(settings, dry_run).await {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn serve(settings: Arc<Settings>) {
+    info!("Starting asset server...");
     let app_port = settings.port.unwrap_or(8000);
 
     // create services
@@ -0,0 +1,101 @@
+//! Migration of asset UUIDs to the ones derived from source paths (see `input2uuid`
+//! module), eg. after changing the `uuid_namespace` setting, renaming source files
+//! or moving a library created on Windows. Old UUIDs of migrated assets are
+//! recorded in the remap table (`uuid_remap` setting).
+
+use crate::database::load_database;
+use crate::input2uuid::{dump_input2uuid, rekey_map};
+use crate::library::create_library;
+use crate::settings::Settings;
+use bf::remap::UuidRemap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Re-keys assets in the database, their compilations and compiled files to
+/// UUIDs derived from their source paths. Prints the changes and returns
+/// whether the migration succeeded. With `dry_run` nothing is changed.
+pub async fn migrate_uuids(settings: Arc<Settings>, dry_run: bool) -> bool {
+    let database = load_database(&settings);
+    let library = create_library(&settings);
+
+    let mut assets = database.get_assets();
+    assets.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let map = match rekey_map(library.uuid_derivation(), &assets) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("cannot migrate uuids: {}", e);
+            return false;
+        }
+    };
+
+    if map.is_empty() {
+        println!("all {} assets have up-to-date uuids", assets.len());
+        return true;
+    }
+
+    println!("{:<36}  {:<36}  {}", "OLD UUID", "NEW UUID", "NAME");
+    for asset in assets.iter().filter(|x| map.contains_key(&x.uuid())) {
+        println!(
+            "{:<36}  {:<36}  {}",
+            asset.uuid(),
+            map[&asset.uuid()],
+            asset.name()
+        );
+    }
+    println!();
+
+    if dry_run {
+        println!("{} of {} assets would be migrated", map.len(), assets.len());
+        return true;
+    }
+
+    // old uuids are recorded before anything is re-keyed
+    if let Some(path) = &settings.uuid_remap {
+        if let Err(e) = update_remap_table(path, &map) {
+            eprintln!("cannot update uuid remap table {:?}: {}", path, e);
+            return false;
+        }
+    }
+
+    // compiled files are moved through temporary names, as the new uuid of
+    // one asset may be the old uuid of another one
+    let mut moved = vec![];
+    for (old, new) in map.iter() {
+        let path = library.compute_output_path(old);
+        if path.exists() {
+            let tmp = path.with_extension("bf.migrate");
+            if let Err(e) = std::fs::rename(&path, &tmp) {
+                eprintln!("cannot move {:?}: {}", path, e);
+                return false;
+            }
+            moved.push((tmp, library.compute_output_path(new)));
+        }
+    }
+    for (tmp, path) in moved {
+        if let Err(e) = std::fs::rename(&tmp, &path) {
+            eprintln!("cannot move {:?}: {}", tmp, e);
+            return false;
+        }
+    }
+
+    database.rekey(&map);
+    database.flush();
+    dump_input2uuid(&settings.input2uuid, database.get_assets()).await;
+
+    println!("migrated {} of {} assets", map.len(), assets.len());
+    true
+}
+
+/// Adds the `rekeyed` assets to the remap table at `path` (created if missing).
+fn update_remap_table(path: &str, rekeyed: &HashMap<Uuid, Uuid>) -> Result<(), String> {
+    let mut remap = match std::fs::read_to_string(path) {
+        Ok(t) => UuidRemap::parse(&t)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => UuidRemap::default(),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    remap.extend(rekeyed);
+    std::fs::write(path, remap.to_string()).map_err(|e| e.to_string())
+}
@@ -3,6 +3,7 @@
 use bf::image::Format;
 use bf::material::BlendMode;
 use bf::mesh::{IndexType, VertexFormat};
+use bf::shader::ShaderStage;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -34,6 +35,9 @@ pub struct Mesh {
     pub geometry_index: Option<usize>,
     pub lod: Option<u8>,
     pub recalculate_normals: Option<bool>,
+    pub meshopt: Option<bool>,
+    /// Whether to store stiffness of vertices used by foliage materials.
+    pub wind_stiffness: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -54,9 +58,28 @@ pub struct Material {
     pub ao_map: Option<Uuid>,
     pub metallic_map: Option<Uuid>,
     pub opacity_map: Option<Uuid>,
+    pub emissive_map: Option<Uuid>,
     pub opacity: Option<f32>,
     pub ior: Option<f32>,
     pub sss: Option<f32>,
+    pub emissive_color: Option<[f32; 3]>,
+    pub clear_coat: Option<f32>,
+    pub clear_coat_roughness: Option<f32>,
+    pub anisotropy: Option<f32>,
+    pub foliage: Option<bool>,
+    pub double_sided: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Shader {
+    pub uuid: Uuid,
+    pub name: String,
+    pub input_path: String,
+    pub updated_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub stage: Option<ShaderStage>,
+    /// Permutations to compile, each one as comma separated preprocessor definitions.
+    pub variants: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -65,6 +88,7 @@ pub enum Asset {
     Image(Image),
     Mesh(Mesh),
     Material(Material),
+    Shader(Shader),
 }
 
 impl Asset {
@@ -74,6 +98,18 @@ impl Asset {
             Asset::Image(t) => t.uuid,
             Asset::Mesh(t) => t.uuid,
             Asset::Material(t) => t.uuid,
+            Asset::Shader(t) => t.uuid,
+        }
+    }
+
+    /// Returns lowercase name of the type of the asset (eg. `"image"`).
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Asset::Image(_) => "image",
+            Asset::Mesh(_) => "mesh",
+            Asset::Material(_) => "material",
+            Asset::Shader(_) => "shader",
         }
     }
 
@@ -83,6 +119,7 @@ impl Asset {
             Asset::Image(t) => &t.name,
             Asset::Mesh(t) => &t.name,
             Asset::Material(t) => &t.name,
+            Asset::Shader(t) => &t.name,
         }
     }
 
@@ -92,6 +129,7 @@ impl Asset {
             Asset::Image(t) => t.tags.as_slice(),
             Asset::Mesh(t) => t.tags.as_slice(),
             Asset::Material(t) => t.tags.as_slice(),
+            Asset::Shader(t) => t.tags.as_slice(),
         }
     }
 
@@ -101,6 +139,7 @@ impl Asset {
             Asset::Image(t) => t.updated_at,
             Asset::Mesh(t) => t.updated_at,
             Asset::Material(t) => t.updated_at,
+            Asset::Shader(t) => t.updated_at,
         }
     }
 
@@ -110,6 +149,7 @@ impl Asset {
             Asset::Image(t) => t.updated_at = Utc::now(),
             Asset::Mesh(t) => t.updated_at = Utc::now(),
             Asset::Material(t) => t.updated_at = Utc::now(),
+            Asset::Shader(t) => t.updated_at = Utc::now(),
         }
     }
 
@@ -118,6 +158,7 @@ impl Asset {
         match self {
             Asset::Image(t) => Some(&t.input_path),
             Asset::Mesh(t) => Some(&t.input_path),
+            Asset::Shader(t) => Some(&t.input_path),
             Asset::Material(_) => None,
         }
     }
@@ -127,11 +168,63 @@ impl Asset {
         match self {
             Asset::Image(t) => t.input_path = path.into(),
             Asset::Mesh(t) => t.input_path = path.into(),
+            Asset::Shader(t) => t.input_path = path.into(),
             Asset::Material(_) => {}
         }
     }
 }
 
+/// Per-asset overrides of options of the conversion tools. Options that are not
+/// set keep the values chosen by the importer. Options of other asset types are
+/// ignored (eg. `format` of a mesh).
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CompileSettings {
+    /// When the settings were changed (set by the server).
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    // images (img2bf)
+    pub format: Option<Format>,
+    pub pack_normal_map: Option<bool>,
+    pub v_flip: Option<bool>,
+    pub h_flip: Option<bool>,
+    /// Filter used to downscale mip-maps (`nearest`, `linear`, `gaussian`,
+    /// `cubic` or `lanczos`).
+    pub mip_filter: Option<String>,
+    pub compression_level: Option<i32>,
+    // meshes (obj2bf)
+    pub index_type: Option<IndexType>,
+    pub vertex_format: Option<VertexFormat>,
+    pub lod: Option<u8>,
+    pub recalculate_normals: Option<bool>,
+    pub meshopt: Option<bool>,
+    /// Number of rays used to bake sky occlusion of vertices.
+    pub sky_occlusion: Option<u32>,
+}
+
+impl CompileSettings {
+    /// Returns a copy of the `asset` with its options overridden by these settings.
+    pub fn apply(&self, asset: &Asset) -> Asset {
+        let mut asset = asset.clone();
+        match &mut asset {
+            Asset::Image(t) => {
+                t.format = self.format.unwrap_or(t.format);
+                t.pack_normal_map = self.pack_normal_map.or(t.pack_normal_map);
+                t.v_flip = self.v_flip.or(t.v_flip);
+                t.h_flip = self.h_flip.or(t.h_flip);
+            }
+            Asset::Mesh(t) => {
+                t.index_type = self.index_type.or(t.index_type);
+                t.vertex_format = self.vertex_format.or(t.vertex_format);
+                t.lod = self.lod.or(t.lod);
+                t.recalculate_normals = self.recalculate_normals.or(t.recalculate_normals);
+                t.meshopt = self.meshopt.or(t.meshopt);
+            }
+            Asset::Material(_) | Asset::Shader(_) => {}
+        }
+        asset
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Compilation {
     pub uuid: Uuid,
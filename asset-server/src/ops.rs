@@ -1,19 +1,31 @@
+use crate::batching::{library_batching_report, BatchingReport};
+use crate::commands::{Command, MATBAKE};
 use crate::compiler::Compiler;
 use crate::database::Database;
 use crate::ext_tools::ExtTools;
-use crate::http::models::Event;
+use crate::http::models::{AssetPage, AssetQuery, Event, UploadReport, UploadedFile};
 use crate::http::stream::publish_server_event;
-use crate::importer::Importer;
+use crate::importer::{ImportError, Importer};
+use crate::input2uuid::{normalize_source_path, source_path};
 use crate::library::Library;
-use crate::models::{Asset, Compilation};
+use crate::models::{Asset, Compilation, CompileSettings};
 use crate::preview::Preview;
 use crate::scanner::Scanner;
 use crate::settings::Settings;
+use chrono::Utc;
 use log::info;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 use uuid::Uuid;
 
+/// Number of assets on one page of `query_assets` if not specified.
+const DEFAULT_PAGE_SIZE: usize = 100;
+/// Maximal number of assets on one page of `query_assets`.
+const MAX_PAGE_SIZE: usize = 1000;
+
 pub struct Ops {
     database: Arc<Database>,
     compiler: Arc<Compiler>,
@@ -51,6 +63,85 @@ impl Ops {
         self.database.get_assets()
     }
 
+    /// Returns one page of assets matching the `query`.
+    pub fn query_assets(&self, query: &AssetQuery) -> Result<AssetPage, String> {
+        let sort = query.sort.as_deref().unwrap_or("name");
+        let (descending, key) = match sort.strip_prefix('-') {
+            Some(t) => (true, t),
+            None => (false, sort),
+        };
+        // ties are broken by the uuid, so the pages are stable
+        let compare: fn(&Asset, &Asset) -> Ordering = match key {
+            "name" => |a, b| a.name().cmp(b.name()).then_with(|| a.uuid().cmp(&b.uuid())),
+            "mtime" => |a, b| {
+                a.updated_at()
+                    .cmp(&b.updated_at())
+                    .then_with(|| a.uuid().cmp(&b.uuid()))
+            },
+            "type" => |a, b| {
+                a.type_name()
+                    .cmp(b.type_name())
+                    .then_with(|| a.name().cmp(b.name()))
+                    .then_with(|| a.uuid().cmp(&b.uuid()))
+            },
+            "uuid" => |a, b| a.uuid().cmp(&b.uuid()),
+            _ => return Err(format!("cannot sort by {:?}", key)),
+        };
+
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .max(1)
+            .min(MAX_PAGE_SIZE);
+        let page = query.page.unwrap_or(1).max(1);
+
+        // dirty status is cached by the scanner, so it's not checked again
+        let dirty = query.dirty.map(|_| {
+            self.scanner
+                .dirty_assets()
+                .into_iter()
+                .collect::<HashSet<_>>()
+        });
+        let asset_type = query.asset_type.as_ref().map(|x| x.to_lowercase());
+        let name_contains = query.name_contains.as_ref().map(|x| x.to_lowercase());
+
+        let (assets, total) = self.database.query_assets(
+            |asset| {
+                asset_type.as_ref().map_or(true, |x| asset.type_name() == x)
+                    && name_contains
+                        .as_ref()
+                        .map_or(true, |x| asset.name().to_lowercase().contains(x))
+                    && query
+                        .tag
+                        .as_ref()
+                        .map_or(true, |x| asset.tags().contains(x))
+                    && match (&dirty, query.dirty) {
+                        (Some(set), Some(t)) => set.contains(&asset.uuid()) == t,
+                        _ => true,
+                    }
+            },
+            |a, b| {
+                let ordering = compare(a, b);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            },
+            (page - 1).saturating_mul(per_page),
+            per_page,
+        );
+
+        Ok(AssetPage {
+            assets,
+            page,
+            per_page,
+            pages: (total + per_page - 1) / per_page,
+            total,
+            library_total: self.database.asset_count(),
+        })
+    }
+
     pub fn get_asset(&self, uuid: &Uuid) -> Option<Asset> {
         self.database.get_asset(uuid)
     }
@@ -73,6 +164,11 @@ impl Ops {
         self.scanner.is_dirty(uuid)
     }
 
+    /// Returns shaders that include the file at `disk_path`.
+    pub fn get_shaders_including(&self, disk_path: &Path) -> Vec<Uuid> {
+        self.scanner.shaders_including(disk_path)
+    }
+
     pub fn update_asset(&self, mut asset: Asset) {
         let uuid = asset.uuid();
 
@@ -85,6 +181,30 @@ impl Ops {
         publish_server_event(Event::AssetUpdate { asset });
     }
 
+    /// Returns compile settings of the asset (empty when it has none).
+    pub fn get_compile_settings(&self, uuid: &Uuid) -> CompileSettings {
+        self.database.get_compile_settings(uuid).unwrap_or_default()
+    }
+
+    /// Replaces compile settings of the asset. The asset becomes dirty, so it is
+    /// recompiled with the new settings.
+    pub fn update_compile_settings(
+        &self,
+        uuid: &Uuid,
+        mut settings: CompileSettings,
+    ) -> Result<(), String> {
+        if !self.database.has_asset(uuid) {
+            return Err("asset not found".to_string());
+        }
+
+        settings.updated_at = Some(Utc::now());
+        self.database.set_compile_settings(uuid, settings);
+        self.scanner.is_dirty(uuid);
+
+        info!("Updated compile settings of asset {:?}", uuid);
+        Ok(())
+    }
+
     pub fn compile_all(&self, uuids: Vec<Uuid>) {
         for x in uuids {
             self.compile_one(x);
@@ -111,6 +231,184 @@ impl Ops {
         }
     }
 
+    /// Stores uploaded source `files` (name and content) into the `directory` of
+    /// the library and imports them. Existing files are replaced only when
+    /// `overwrite` is set. When the directory contains material maps after the
+    /// upload, the material is imported as well.
+    pub fn upload_files(
+        &self,
+        directory: &str,
+        files: Vec<(String, Vec<u8>)>,
+        overwrite: bool,
+    ) -> UploadReport {
+        let mut report = UploadReport {
+            files: vec![],
+            material: None,
+        };
+
+        // images must be tracked before the material referencing them
+        for (name, data) in files {
+            // only the file name is used, clients may send full paths
+            let name = name.rsplit(|x| x == '/' || x == '\\').next().unwrap_or("");
+            let path = normalize_source_path(&format!("{}/{}", directory, name));
+            let result = self
+                .library
+                .store_source_file(&path, &data, overwrite)
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|disk_path| self.import_uploaded(&disk_path));
+
+            report.files.push(match result {
+                Ok(uuid) => UploadedFile {
+                    path,
+                    uuid: Some(uuid),
+                    error: None,
+                },
+                Err(e) => UploadedFile {
+                    path,
+                    uuid: None,
+                    error: Some(e),
+                },
+            });
+        }
+
+        let directory = normalize_source_path(directory);
+        if !directory.is_empty() && report.files.iter().any(|x| x.uuid.is_some()) {
+            let disk_path = self.library.db_path_to_disk_path(&directory);
+            if disk_path.is_dir() {
+                report.material = self.import_uploaded(&disk_path).ok();
+            }
+        }
+
+        report
+    }
+
+    /// Imports the file (or material directory) at `disk_path`, refreshes it and
+    /// notifies clients. Returns UUID of the asset, which may have been tracked before.
+    fn import_uploaded(&self, disk_path: &Path) -> Result<Uuid, String> {
+        let uuid = match self.importer.import_file(disk_path) {
+            Ok(t) => {
+                info!("Imported uploaded file {:?} as asset {:?}", disk_path, t);
+                t
+            }
+            Err(ImportError::AlreadyTracked(t)) => t,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        self.refresh_file(disk_path);
+
+        if let Some(asset) = self.get_asset(&uuid) {
+            publish_server_event(Event::AssetUpdate { asset });
+        }
+        if self.settings.auto_compile && self.is_asset_dirty(&uuid) {
+            self.compile_one(uuid);
+        }
+
+        Ok(uuid)
+    }
+
+    /// Bakes the material with `uuid` into a flattened texture set of `resolution`
+    /// using the `matbake` tool. The maps are written into a new directory next
+    /// to the material, which is imported as a new material with the same blend
+    /// settings. Returns UUID of the baked material.
+    pub async fn bake_material(
+        &self,
+        uuid: &Uuid,
+        resolution: u32,
+        tiling: Option<[f32; 2]>,
+    ) -> Result<Uuid, String> {
+        let material = match self.database.get_asset(uuid) {
+            Some(Asset::Material(t)) => t,
+            Some(_) => return Err("asset is not a material".to_string()),
+            None => return Err("asset not found".to_string()),
+        };
+
+        let directory = normalize_source_path(&format!(
+            "{}_baked{}",
+            source_path(&Asset::Material(material.clone())),
+            resolution
+        ));
+        let disk_path = self.library.db_path_to_disk_path(&directory);
+        let name = directory.rsplit('/').next().unwrap_or("baked").to_string();
+
+        let mut command = Command::new(MATBAKE);
+        command
+            .arg("--output-dir")
+            .arg(&disk_path)
+            .arg("--name")
+            .arg(&name)
+            .arg("--resolution")
+            .arg(resolution.to_string());
+        if let Some([u, v]) = tiling {
+            command.arg("--tiling").arg(format!("{},{}", u, v));
+        }
+        if let Some([r, g, b]) = material.albedo_color {
+            command
+                .arg("--albedo-color")
+                .arg(format!("{},{},{}", r, g, b));
+        }
+        if let Some(t) = material.roughness {
+            command.arg("--roughness").arg(t.to_string());
+        }
+        if let Some(t) = material.metallic {
+            command.arg("--metallic").arg(t.to_string());
+        }
+        for (flag, map) in [
+            ("--albedo-map", material.albedo_map),
+            ("--normal-map", material.normal_map),
+            ("--roughness-map", material.roughness_map),
+            ("--metallic-map", material.metallic_map),
+            ("--ao-map", material.ao_map),
+            ("--opacity-map", material.opacity_map),
+        ] {
+            let input_path = map
+                .and_then(|x| self.database.get_asset(&x))
+                .and_then(|x| x.input_path().cloned());
+            if let Some(t) = input_path {
+                command.arg(flag).arg(self.library.db_path_to_disk_path(&t));
+            }
+        }
+
+        info!("Baking material {:?}: {}", uuid, command);
+        let mut cmd: tokio::process::Command = command.into();
+        let output = cmd
+            .output()
+            .await
+            .map_err(|e| format!("cannot run sub-process: {:?}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+
+        // the tool prints paths of the written maps
+        for path in String::from_utf8_lossy(&output.stdout).lines() {
+            self.import_uploaded(Path::new(path.trim()))?;
+        }
+        let baked = self.import_uploaded(&disk_path)?;
+
+        if let Some(Asset::Material(mut t)) = self.get_asset(&baked) {
+            t.blend_mode = material.blend_mode;
+            t.alpha_cutoff = material.alpha_cutoff;
+            t.opacity = material.opacity;
+            t.ior = material.ior;
+            t.sss = material.sss;
+            t.emissive_color = material.emissive_color;
+            t.emissive_map = material.emissive_map;
+            t.clear_coat = material.clear_coat;
+            t.clear_coat_roughness = material.clear_coat_roughness;
+            t.anisotropy = material.anisotropy;
+            t.foliage = material.foliage;
+            t.double_sided = material.double_sided;
+            if !t.tags.iter().any(|x| x == "baked") {
+                t.tags.push("baked".to_string());
+            }
+            self.update_asset(Asset::Material(t));
+
+            if self.settings.auto_compile && self.is_asset_dirty(&baked) {
+                self.compile_one(baked);
+            }
+        }
+
+        Ok(baked)
+    }
+
     pub fn cancel_tracking(&self, uuid: &Uuid) {
         self.database.delete_asset(uuid);
 
@@ -145,6 +443,27 @@ impl Ops {
         }
     }
 
+    pub fn read_compiled_asset(&self, uuid: &Uuid) -> Option<Vec<u8>> {
+        std::fs::read(self.library.compute_output_path(uuid)).ok()
+    }
+
+    /// Returns the `ETag` of the compiled file of the asset (derived from its
+    /// size and modification time) or `None` if the asset is not compiled.
+    pub fn compiled_asset_etag(&self, uuid: &Uuid) -> Option<String> {
+        let metadata = std::fs::metadata(self.library.compute_output_path(uuid)).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(format!(
+            "\"{:x}-{:x}\"",
+            metadata.len(),
+            modified.as_nanos()
+        ))
+    }
+
+    /// Returns materials that could be merged to save draw calls of the `scene`.
+    pub fn batching_report(&self, scene: Option<Uuid>) -> Result<BatchingReport, String> {
+        library_batching_report(&self.database, &self.library, scene)
+    }
+
     pub async fn preview_asset(&self, uuid: &Uuid) -> Option<Vec<u8>> {
         self.preview.preview_file(uuid).await
     }
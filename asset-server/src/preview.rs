@@ -1,7 +1,7 @@
 use crate::commands::{Command, BFINFO};
 use crate::database::Database;
 use crate::library::Library;
-use crate::models::{Asset, Image, Material, Mesh};
+use crate::models::{Asset, Image, Material, Mesh, Shader};
 use log::error;
 use std::sync::Arc;
 use tempfile::tempdir;
@@ -20,36 +20,44 @@ impl Preview {
                 Asset::Image(t) => self.preview_image(t).await,
                 Asset::Mesh(t) => self.preview_mesh(t).await,
                 Asset::Material(t) => self.preview_material(t).await,
+                Asset::Shader(t) => self.preview_shader(t).await,
             },
         }
     }
 
-    async fn preview_mesh(&self, _mesh: Mesh) -> Option<Vec<u8>> {
-        None
+    async fn preview_mesh(&self, mesh: Mesh) -> Option<Vec<u8>> {
+        // `bfinfo` renders a turntable thumbnail of the mesh with default material
+        self.run_bfinfo(&mesh.uuid, "--preview", "preview.png")
+            .await
     }
 
     async fn preview_material(&self, _material: Material) -> Option<Vec<u8>> {
         None
     }
 
+    async fn preview_shader(&self, _shader: Shader) -> Option<Vec<u8>> {
+        None
+    }
+
     async fn preview_image(&self, image: Image) -> Option<Vec<u8>> {
-        let path = self.library.compute_output_path(&image.uuid);
-        let working_dir = tempdir().expect("cannot create temporary directory");
+        self.run_bfinfo(&image.uuid, "--dump", "dump_mipmap0.png")
+            .await
+    }
 
-        // run `bfinfo` utility with the "--dump" parameter set, then read
-        // the dumped file(s) to memory and return them from this function.
+    /// Runs the `bfinfo` utility with the `flag` on the compiled asset in a
+    /// temporary directory and returns contents of the `output` file it created.
+    async fn run_bfinfo(&self, uuid: &Uuid, flag: &str, output: &str) -> Option<Vec<u8>> {
+        let path = self.library.compute_output_path(uuid);
+        let working_dir = tempdir().expect("cannot create temporary directory");
 
         let mut command = Command::new(BFINFO);
-        command.arg("--input").arg(path).arg("--dump");
+        command.arg("--input").arg(path).arg(flag);
 
         let mut cmd: tokio::process::Command = command.into();
         match cmd.current_dir(&working_dir).output().await {
             Ok(t) => {
                 if !t.status.success() {
-                    error!(
-                        "Preview command failed for asset {:?}",
-                        &image.uuid.to_string(),
-                    );
+                    error!("Preview command failed for asset {:?}", &uuid.to_string());
                     error!("Error: {:?}", t);
                     return None;
                 }
@@ -60,7 +68,7 @@ impl Preview {
             }
         }
 
-        let file_path = working_dir.path().join("dump_mipmap0.png");
+        let file_path = working_dir.path().join(output);
         let bytes = tokio::fs::read(&file_path).await;
 
         if let Err(e) = &bytes {
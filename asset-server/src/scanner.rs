@@ -68,6 +68,15 @@ impl Scanner {
             if mtime(&input) > mtime(&output) {
                 return true;
             }
+
+            // shader includes a file that is newer than the output file
+            if matches!(asset, Asset::Shader(_))
+                && core::glsl::collect_includes(&input)
+                    .iter()
+                    .any(|x| x.exists() && mtime(x) > output_changed)
+            {
+                return true;
+            }
         }
 
         // todo: check file contents (hash) to determine changed file
@@ -85,6 +94,17 @@ impl Scanner {
             return true;
         }
 
+        // compile settings were changed after last compilation
+        let settings_changed = self
+            .database
+            .get_compile_settings(uuid)
+            .and_then(|x| x.updated_at);
+        if let Some(t) = settings_changed {
+            if t > DateTime::<Utc>::from(output_changed) {
+                return true;
+            }
+        }
+
         false
     }
 
@@ -105,6 +125,25 @@ impl Scanner {
         result
     }
 
+    /// Returns shader assets that include (directly or through other includes)
+    /// the file at `disk_path`.
+    pub fn shaders_including(&self, disk_path: &Path) -> Vec<Uuid> {
+        self.database
+            .get_assets()
+            .iter()
+            .filter(|x| matches!(x, Asset::Shader(_)))
+            .filter(|x| match x.input_path() {
+                Some(input) => {
+                    core::glsl::collect_includes(&self.library.db_path_to_disk_path(input))
+                        .iter()
+                        .any(|x| x == disk_path)
+                }
+                None => false,
+            })
+            .map(|x| x.uuid())
+            .collect()
+    }
+
     fn import_file(&self, disk_path: &Path) -> Result<Uuid, ()> {
         match self.importer.import_file(disk_path) {
             Ok(t) => {
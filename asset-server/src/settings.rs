@@ -1,9 +1,15 @@
+use core::settings::Overrides;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Prefix of environment variables overriding the settings.
+const ENV_PREFIX: &str = "ASSET_SERVER_";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct Settings {
     /// The root folder that contains all source files in the library.
     pub library_root: String,
@@ -34,19 +40,96 @@ pub struct Settings {
 
     /// Port to listen for connections on.
     pub port: Option<u16>,
+
+    /// Namespace of UUIDs derived from source paths of assets. Changing it
+    /// requires migration of the library (`migrate-uuids` command).
+    pub uuid_namespace: Option<Uuid>,
+
+    /// Salts of UUIDs of assets by their importer (`image`, `mesh`, `material`
+    /// or `shader`). Changing them requires migration of the library.
+    pub uuid_salts: Option<HashMap<String, String>>,
+
+    /// Path to the table of legacy UUIDs of migrated assets (see `bf::remap`).
+    /// Written by the `migrate-uuids` command.
+    pub uuid_remap: Option<String>,
 }
 
-pub fn load_settings() -> Arc<Settings> {
+impl Settings {
+    /// Overrides the settings by command line arguments and environment variables
+    /// prefixed with `ASSET_SERVER_` (see `core::settings` module). Extensions opened
+    /// by each external tool are specified by `external_tools_<tool>` setting as
+    /// comma separated list and salts of importers by `uuid_salts_<importer>`.
+    pub fn apply_overrides(&mut self, overrides: &Overrides) -> Result<(), String> {
+        overrides.apply("library_root", &mut self.library_root)?;
+        overrides.apply("library_target", &mut self.library_target)?;
+        overrides.apply("input2uuid", &mut self.input2uuid)?;
+        overrides.apply_option("db_file", &mut self.db_file)?;
+        overrides.apply_option("max_concurrency", &mut self.max_concurrency)?;
+        overrides.apply("auto_compile", &mut self.auto_compile)?;
+        overrides.apply("watch", &mut self.watch)?;
+        overrides.apply("allow_external_tools", &mut self.allow_external_tools)?;
+        for (tool, value) in overrides.get_prefixed("external_tools_") {
+            let extensions = value.split(',').map(|x| x.trim().to_string()).collect();
+            self.external_tools
+                .get_or_insert_with(HashMap::new)
+                .insert(tool, extensions);
+        }
+        overrides.apply_option("port", &mut self.port)?;
+        overrides.apply_option("uuid_namespace", &mut self.uuid_namespace)?;
+        for (importer, salt) in overrides.get_prefixed("uuid_salts_") {
+            self.uuid_salts
+                .get_or_insert_with(HashMap::new)
+                .insert(importer, salt.to_string());
+        }
+        overrides.apply_option("uuid_remap", &mut self.uuid_remap)?;
+        Ok(())
+    }
+
+    /// Returns the name of the first required setting that is not set.
+    fn missing(&self) -> Option<&'static str> {
+        [
+            ("library_root", &self.library_root),
+            ("library_target", &self.library_target),
+            ("input2uuid", &self.input2uuid),
+        ]
+        .iter()
+        .find(|(_, value)| value.is_empty())
+        .map(|(name, _)| *name)
+    }
+}
+
+/// Loads the settings from the settings file (if it exists) and applies overrides
+/// from the command line (`args`) and the environment.
+pub fn load_settings(args: Vec<(String, String)>) -> Arc<Settings> {
     let path = std::env::var("ASSET_SERVER_SETTINGS")
         .unwrap_or_else(|_| "./asset_server_settings.json".into());
     let path: PathBuf = path.into();
 
-    if !path.exists() {
-        panic!("Cannot find settings file: {:?}", path);
-    }
+    let mut settings: Settings = if path.exists() {
+        match std::fs::read_to_string(&path) {
+            Ok(t) => serde_json::from_str(&t).unwrap(),
+            Err(e) => panic!("Cannot read settings file: {:?}", e),
+        }
+    } else {
+        Settings::default()
+    };
 
-    match std::fs::read_to_string(path) {
-        Ok(t) => Arc::new(serde_json::from_str(&t).unwrap()),
-        Err(e) => panic!("Cannot find settings file: {:?}", e),
+    let overrides = Overrides::from_env(ENV_PREFIX, args);
+    if let Err(e) = settings.apply_overrides(&overrides) {
+        panic!("Invalid settings: {}", e);
     }
+    if let Some(name) = overrides.unused().first() {
+        panic!("Unknown setting {:?}", name);
+    }
+    if let Some(name) = settings.missing() {
+        panic!(
+            "Setting {} is required (set it in {:?}, by `--set {}=<value>` or by {} variable)",
+            name,
+            path,
+            name,
+            overrides.var_name(name)
+        );
+    }
+
+    Arc::new(settings)
 }
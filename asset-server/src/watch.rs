@@ -61,6 +61,14 @@ async fn handle_event(event: DebouncedEvent, ops: Arc<Ops>, settings: Arc<Settin
                     ops.compile_one(ass.uuid());
                 }
                 ops.refresh_file(&t);
+            } else {
+                // shared code of shaders (`inc_*.glsl`) is not an asset itself,
+                // but the shaders including it have to be compiled again
+                for uuid in ops.get_shaders_including(&t) {
+                    if ops.is_asset_dirty(&uuid) && settings.auto_compile {
+                        ops.compile_one(uuid);
+                    }
+                }
             }
         }
         DebouncedEvent::Remove(t) => {
@@ -0,0 +1,106 @@
+//! Measures compression and decompression of big (100MB+) mesh and image
+//! containers using one thread and using all threads of the `rayon` pool and
+//! the speed-up of using all threads.
+
+use bf::image::{Format, Image};
+use bf::mesh::{IndexType, Mesh, MeshEncoding, VertexFormat};
+use bf::{load_bf_from_bytes, save_bf_to_bytes, Container, File};
+use rayon::ThreadPoolBuilder;
+use std::time::{Duration, Instant};
+
+const SIZE: usize = 128 * 1024 * 1024;
+const ITERATIONS: u32 = 3;
+
+/// Pseudo-random data with small differences between neighbouring values, so
+/// they compress similarly to real vertex and texel data.
+fn data(size: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    let mut value = 0u8;
+
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            value = value.wrapping_add((state % 5) as u8);
+            value
+        })
+        .collect()
+}
+
+fn mesh() -> Container {
+    Container::Mesh(Mesh {
+        vertex_format: VertexFormat::PositionNormalUvTangent,
+        vertex_data: data(SIZE * 3 / 4).into(),
+        index_type: IndexType::U32,
+        index_data: data(SIZE / 4).into(),
+        lods: vec![],
+        encoding: MeshEncoding::None,
+        stiffness: None,
+    })
+}
+
+fn image() -> Container {
+    Container::Image(Image {
+        format: Format::Rgba8,
+        width: 8192,
+        height: 4096,
+        mipmap_data: data(SIZE).into(),
+    })
+}
+
+fn measure<F: FnMut() + Send>(threads: usize, mut f: F) -> Duration {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+
+    pool.install(|| {
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            f();
+        }
+        start.elapsed() / ITERATIONS
+    })
+}
+
+fn bench(name: &str, container: fn() -> Container) {
+    let threads = rayon::current_num_threads();
+    let file = File::create_compressed(container());
+    let bytes = save_bf_to_bytes(&file).unwrap();
+
+    println!(
+        "{} ({} MB, {} MB compressed)",
+        name,
+        SIZE / 1024 / 1024,
+        bytes.len() / 1024 / 1024
+    );
+
+    let mut thread_counts = vec![1, threads];
+    thread_counts.dedup();
+
+    // speed-up is relative to the first (single-threaded) measurement
+    let mut baseline = None;
+    for n in thread_counts {
+        let save = measure(n, || {
+            save_bf_to_bytes(&file).unwrap();
+        });
+        let load = measure(n, || {
+            load_bf_from_bytes(&bytes).unwrap();
+        });
+        let (save1, load1) = *baseline.get_or_insert((save, load));
+        println!(
+            "  {:>2} threads: compress {:>8.1?} ({:.1}x)  decompress {:>8.1?} ({:.1}x)",
+            n,
+            save,
+            save1.as_secs_f64() / save.as_secs_f64(),
+            load,
+            load1.as_secs_f64() / load.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    bench("mesh", mesh);
+    bench("image", image);
+}
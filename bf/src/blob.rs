@@ -0,0 +1,307 @@
+//! Byte buffers of big payloads (mip-maps of images, vertices of meshes) that
+//! can borrow from the loaded file instead of owning a copy.
+//!
+//! Payloads of uncompressed files are stored in the file as they are, so when
+//! the file is kept in memory anyway (eg. it is memory-mapped) they can refer
+//! to its bytes directly. See [`load_bf_shared`](../fn.load_bf_shared.html).
+
+use crate::image::{Format, Image};
+use crate::layout::bincode_options;
+use crate::mesh::{IndexType, Lod, Mesh, MeshEncoding, VertexFormat};
+use crate::virtual_texture::VirtualTexture;
+use crate::{Container, File, LoadError, BF_MAGIC, BF_VERSION, DATA_UNCOMPRESSED};
+use bincode::Options;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// Bytes (eg. a memory-mapped file) that blobs can be shared with.
+pub type SharedBytes = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// Bytes that are either owned or a range of bytes shared with other blobs.
+/// Dereferences to a slice of the bytes.
+#[derive(Clone)]
+pub struct Blob(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Owned(Vec<u8>),
+    Shared(SharedBytes, Range<usize>),
+}
+
+impl Blob {
+    /// Creates a blob referring to the `range` of the `bytes`.
+    ///
+    /// # Panics
+    /// Panics if the `range` is out of bounds of the `bytes`.
+    pub fn shared(bytes: SharedBytes, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= (*bytes).as_ref().len());
+        Blob(Repr::Shared(bytes, range))
+    }
+
+    /// Returns whether the bytes are shared (not owned by this blob).
+    pub fn is_shared(&self) -> bool {
+        matches!(self.0, Repr::Shared(..))
+    }
+
+    /// Converts the blob into a vector (copying the bytes if they are shared).
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.0 {
+            Repr::Owned(t) => t,
+            Repr::Shared(..) => self.to_vec(),
+        }
+    }
+}
+
+impl Deref for Blob {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            Repr::Owned(t) => t,
+            Repr::Shared(bytes, range) => &(**bytes).as_ref()[range.clone()],
+        }
+    }
+}
+
+impl AsRef<[u8]> for Blob {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Default for Blob {
+    fn default() -> Self {
+        Blob(Repr::Owned(vec![]))
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(t: Vec<u8>) -> Self {
+        Blob(Repr::Owned(t))
+    }
+}
+
+impl Debug for Blob {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Blob")
+            .field("len", &self.len())
+            .field("shared", &self.is_shared())
+            .finish()
+    }
+}
+
+impl PartialEq for Blob {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Blob {}
+
+impl PartialEq<Vec<u8>> for Blob {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        **self == **other
+    }
+}
+
+impl Hash for Blob {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl Serialize for Blob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Blob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(ByteBuf::deserialize(deserializer)?.into_vec().into())
+    }
+}
+
+/// Variant indices of `Container` that have payloads which can be shared.
+const CONTAINER_IMAGE: u32 = 0;
+const CONTAINER_MESH: u32 = 1;
+const CONTAINER_VIRTUAL_TEXTURE: u32 = 6;
+
+/// Start of an uncompressed file up to its container.
+#[derive(Deserialize)]
+struct ContainerHeader {
+    magic: u16,
+    version: u8,
+    data: u32,
+    container: u32,
+}
+
+/// `Image` with payload borrowed from the file.
+#[derive(Deserialize)]
+struct ImageRef<'a> {
+    format: Format,
+    width: u16,
+    height: u16,
+    mipmap_data: &'a [u8],
+}
+
+/// `Mesh` with payloads borrowed from the file.
+#[derive(Deserialize)]
+struct MeshRef<'a> {
+    vertex_format: VertexFormat,
+    vertex_data: &'a [u8],
+    index_type: IndexType,
+    index_data: &'a [u8],
+    lods: Vec<Lod>,
+    encoding: MeshEncoding,
+    stiffness: Option<&'a [u8]>,
+}
+
+/// `VirtualTexture` with pages borrowed from the file.
+#[derive(Deserialize)]
+struct VirtualTextureRef<'a> {
+    format: Format,
+    size: u32,
+    page_size: u32,
+    border: u32,
+    page_data: &'a [u8],
+}
+
+/// Loads uncompressed image, mesh or virtual texture file of the current version with payloads
+/// shared with the `bytes`. Returns `None` for any other file.
+pub(crate) fn load_shared(bytes: &SharedBytes) -> Option<Result<File, LoadError>> {
+    let data = (**bytes).as_ref();
+    let header: ContainerHeader = bincode_options()
+        .allow_trailing_bytes()
+        .deserialize(data)
+        .ok()?;
+
+    if header.magic != BF_MAGIC || header.version != BF_VERSION || header.data != DATA_UNCOMPRESSED
+    {
+        return None;
+    }
+
+    // blobs refer to the same bytes the borrowed slices were deserialized from
+    let blob = |slice: &[u8]| {
+        let start = slice.as_ptr() as usize - data.as_ptr() as usize;
+        Blob::shared(bytes.clone(), start..start + slice.len())
+    };
+
+    let container = match header.container {
+        CONTAINER_IMAGE => deserialize::<ImageRef>(data).map(|(_, image)| {
+            Container::Image(Image {
+                format: image.format,
+                width: image.width,
+                height: image.height,
+                mipmap_data: blob(image.mipmap_data),
+            })
+        }),
+        CONTAINER_MESH => deserialize::<MeshRef>(data).map(|(_, mesh)| {
+            Container::Mesh(Mesh {
+                vertex_format: mesh.vertex_format,
+                vertex_data: blob(mesh.vertex_data),
+                index_type: mesh.index_type,
+                index_data: blob(mesh.index_data),
+                lods: mesh.lods,
+                encoding: mesh.encoding,
+                stiffness: mesh.stiffness.map(blob),
+            })
+        }),
+        CONTAINER_VIRTUAL_TEXTURE => deserialize::<VirtualTextureRef>(data).map(|(_, texture)| {
+            Container::VirtualTexture(VirtualTexture {
+                format: texture.format,
+                size: texture.size,
+                page_size: texture.page_size,
+                border: texture.border,
+                page_data: blob(texture.page_data),
+            })
+        }),
+        _ => return None,
+    };
+
+    Some(container.map(File::create_uncompressed))
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(data: &'a [u8]) -> Result<(ContainerHeader, T), LoadError> {
+    bincode_options()
+        .deserialize(data)
+        .map_err(LoadError::BincodeError)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blob::{Blob, SharedBytes};
+    use crate::image::{Format, Image};
+    use crate::mesh::{IndexType, Mesh, MeshEncoding, VertexFormat};
+    use crate::virtual_texture::{PageId, VirtualTexture};
+    use crate::{load_bf_shared, save_bf_to_bytes, Container, File};
+    use std::sync::Arc;
+
+    fn shared(file: File) -> SharedBytes {
+        Arc::new(save_bf_to_bytes(&file).unwrap())
+    }
+
+    #[test]
+    fn uncompressed_payloads_are_shared() {
+        let image = Container::Image(Image {
+            format: Format::Rgba8,
+            width: 1,
+            height: 1,
+            mipmap_data: vec![1, 2, 3, 4].into(),
+        });
+        let mesh = Container::Mesh(Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vec![5; 12].into(),
+            index_type: IndexType::U16,
+            index_data: vec![0; 6].into(),
+            lods: vec![],
+            encoding: MeshEncoding::None,
+            stiffness: Some(vec![0, 0, 0x80, 0x3f].into()),
+        });
+
+        let image = load_bf_shared(shared(File::create_uncompressed(image)));
+        let image = image.unwrap().try_to_image().unwrap();
+        assert!(image.mipmap_data.is_shared());
+        assert_eq!(image.mipmap_data, vec![1, 2, 3, 4]);
+
+        let mesh = load_bf_shared(shared(File::create_uncompressed(mesh)));
+        let mesh = mesh.unwrap().try_to_mesh().unwrap();
+        assert!(mesh.vertex_data.is_shared() && mesh.index_data.is_shared());
+        assert_eq!(mesh.vertex_data, vec![5; 12]);
+        assert_eq!(mesh.stiffness.as_ref().map(Blob::is_shared), Some(true));
+        assert_eq!(mesh.index_data.into_vec(), vec![0; 6]);
+    }
+
+    #[test]
+    fn pages_of_virtual_textures_are_shared() {
+        let pixels = (0..16).collect::<Vec<u8>>();
+        let texture = VirtualTexture::from_pixels(Format::R8, 4, 2, 0, &pixels).unwrap();
+        let file = File::create_uncompressed(Container::VirtualTexture(texture));
+
+        let texture = load_bf_shared(shared(file)).unwrap();
+        let texture = texture.try_to_virtual_texture().unwrap();
+        assert!(texture.page_data.is_shared());
+        assert_eq!(texture.page(PageId::new(0, 1, 0)).unwrap(), [2, 3, 6, 7]);
+    }
+
+    #[test]
+    fn compressed_payloads_are_owned() {
+        let image = Container::Image(Image {
+            format: Format::R8,
+            width: 2,
+            height: 1,
+            mipmap_data: vec![7, 8].into(),
+        });
+
+        let image = load_bf_shared(shared(File::create_compressed(image)));
+        let image = image.unwrap().try_to_image().unwrap();
+        assert!(!image.mipmap_data.is_shared());
+        assert_eq!(image.mipmap_data, Blob::from(vec![7, 8]));
+    }
+}
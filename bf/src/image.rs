@@ -3,8 +3,22 @@
 //! This module also provides a way to iterate over stored mip-maps in
 //! the `Image` struct. You can use `Image::mipmaps()` function to get
 //! `MipMaps` iterator over individual `MipMap` structs.
+//!
+//! Selected mip-maps can be read from a serialized file without loading
+//! the whole image using `Image::read_mipmaps()`.
 
+use crate::blob::Blob;
+use crate::layout::bincode_options;
+use crate::lz4::ChunkIndex;
+use crate::{
+    load_bf_from_bytes, DataHeader, LoadError, BF_VERSION, DATA_COMPRESSED, DATA_COMPRESSED_ZSTD,
+    DATA_UNCOMPRESSED,
+};
+use bincode::Options;
 use serde::{Deserialize, Serialize};
+use serde_bytes::Bytes;
+use std::borrow::Cow;
+use std::ops::Range;
 
 /// All possible [`Image`](struct.Image.html) formats.
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
@@ -26,6 +40,14 @@ pub enum Format {
     BC6H = 11,
     BC7 = 12,
     SrgbBC7 = 13, // BC7 (srgb)
+    // two channel (RG) format, used for normal maps
+    BC5 = 14,
+    // 16-bit unsigned normalized formats (eg. height maps)
+    R16 = 15,
+    Rg16 = 16,
+    // floating point formats (eg. HDR environment maps), see `f32_to_f16`
+    Rgba16F = 17,
+    Rgba32F = 18,
 }
 
 impl Format {
@@ -46,6 +68,11 @@ impl Format {
             Format::BC6H => 3,
             Format::BC7 => 4,
             Format::SrgbBC7 => 3,
+            Format::BC5 => 2,
+            Format::R16 => 1,
+            Format::Rg16 => 2,
+            Format::Rgba16F => 4,
+            Format::Rgba32F => 4,
         }
     }
 
@@ -66,6 +93,11 @@ impl Format {
             Format::BC6H => true,
             Format::BC7 => true,
             Format::SrgbBC7 => true,
+            Format::BC5 => true,
+            Format::R16 => false,
+            Format::Rg16 => false,
+            Format::Rgba16F => false,
+            Format::Rgba32F => false,
         }
     }
 
@@ -87,7 +119,66 @@ impl Format {
             Format::BC6H => 8,
             Format::BC7 => 8,
             Format::SrgbBC7 => 8,
+            Format::BC5 => 8,
+            Format::R16 => 16,
+            Format::Rg16 => 32,
+            Format::Rgba16F => 64,
+            Format::Rgba32F => 128,
+        }
+    }
+}
+
+/// Converts the `value` to bits of a half precision float (IEEE 754 `binary16`)
+/// as stored in `Format::Rgba16F` images. Rounds to the nearest representable
+/// value, values out of range become infinities.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // infinity and nan (which must keep some bit of the mantissa)
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    // rounds `value` shifted right by `shift` bits to nearest, ties to even
+    let round = |value: u32, shift: u32| {
+        let result = value >> shift;
+        let rest = value & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        result + (rest > half || (rest == half && result & 1 == 1)) as u32
+    };
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent > 0 {
+        // the carry of rounding correctly increments the exponent (up to infinity)
+        sign | round((exponent as u32) << 23 | mantissa, 13) as u16
+    } else if exponent >= -10 {
+        // subnormal numbers with the implicit leading bit
+        sign | round(mantissa | 0x80_0000, (14 - exponent) as u32) as u16
+    } else {
+        sign
+    }
+}
+
+/// Converts bits of a half precision float (see [`f32_to_f16`](fn.f32_to_f16.html))
+/// to `f32`.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    match exponent {
+        0 => {
+            // zero and subnormal numbers
+            let value = mantissa as f32 / (1 << 24) as f32;
+            f32::from_bits(sign | value.to_bits())
         }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 127 - 15) << 23 | mantissa << 13),
     }
 }
 
@@ -103,8 +194,7 @@ pub struct Image {
     pub height: u16,
     /// Bytes of individual mip-maps ordered from highest resolution to
     /// lowest. The number of mip-maps can be computed from length of the payload.
-    #[serde(with = "serde_bytes")]
-    pub mipmap_data: Vec<u8>,
+    pub mipmap_data: Blob,
 }
 
 impl Image {
@@ -132,7 +222,7 @@ impl Image {
     /// type that represents individual mip-maps in this Image.
     pub fn mipmaps(&self) -> MipMaps {
         MipMaps {
-            data: self.mipmap_data.as_slice(),
+            data: &self.mipmap_data,
             format: self.format,
             width: self.width as usize,
             height: self.height as usize,
@@ -141,6 +231,177 @@ impl Image {
     }
 }
 
+impl Image {
+    /// Reads only the mip-maps in `mips` range (clamped to the stored mip-maps)
+    /// of the image stored in serialized BF file `bytes`. The returned image
+    /// starts with the first requested mip-map, so its `width` and `height`
+    /// are the dimensions of that mip-map.
+    ///
+    /// When the file is compressed only the chunks that contain the requested
+    /// mip-maps are decompressed. Files compressed with `zstd` and files created
+    /// by older versions of the format are loaded whole.
+    pub fn read_mipmaps(bytes: &[u8], mips: Range<u32>) -> Result<Image, LoadError> {
+        let prefix = DataHeader::read(bytes)?;
+
+        if prefix.version != BF_VERSION || prefix.data == DATA_COMPRESSED_ZSTD {
+            let image = load_bf_from_bytes(bytes)?
+                .try_to_image()
+                .map_err(|_| LoadError::UnexpectedContainer)?;
+            let (range, width, height) = image.mipmaps_range(mips);
+            return Ok(Image {
+                mipmap_data: image.mipmap_data[range].to_vec().into(),
+                width,
+                height,
+                ..image
+            });
+        }
+
+        match prefix.data {
+            DATA_COMPRESSED => {
+                let file: CompressedFile = bincode_options()
+                    .deserialize(bytes)
+                    .map_err(LoadError::BincodeError)?;
+                let index = ChunkIndex::new(file.chunks.into_iter().map(|x| &**x).collect())
+                    .map_err(LoadError::DecompressionError)?;
+
+                read_mipmaps(index.len(), mips, |range| {
+                    index
+                        .decompress_range(range)
+                        .map(Cow::Owned)
+                        .map_err(LoadError::DecompressionError)
+                })
+            }
+            DATA_UNCOMPRESSED => {
+                let prefix_len = bincode_options()
+                    .serialized_size(&prefix)
+                    .map_err(LoadError::BincodeError)? as usize;
+                let container = &bytes[prefix_len.min(bytes.len())..];
+
+                read_mipmaps(container.len(), mips, |range| {
+                    container
+                        .get(range)
+                        .map(Cow::Borrowed)
+                        .ok_or(LoadError::FileTooShort)
+                })
+            }
+            v => Err(LoadError::BincodeError(Box::new(
+                bincode::ErrorKind::Custom(format!("invalid variant index of data {}", v)),
+            ))),
+        }
+    }
+
+    /// Returns the range of bytes in `mipmap_data` that contains mip-maps in
+    /// `mips` range and dimensions of the first of them.
+    fn mipmaps_range(&self, mips: Range<u32>) -> (Range<usize>, u16, u16) {
+        mipmaps_range(
+            self.format,
+            self.width,
+            self.height,
+            self.mipmap_data.len(),
+            mips,
+        )
+    }
+}
+
+/// Variant index of `Container::Image`.
+const CONTAINER_IMAGE: u32 = 0;
+
+/// File with compressed data whose chunks are borrowed from the file bytes.
+#[derive(Deserialize)]
+struct CompressedFile<'a> {
+    #[allow(dead_code)]
+    prefix: DataHeader,
+    #[serde(borrow)]
+    chunks: Vec<&'a Bytes>,
+}
+
+/// Serialized `Container::Image` up to the length of the `mipmap_data`.
+#[derive(Serialize, Deserialize)]
+struct ImageHeader {
+    container: u32,
+    format: Format,
+    width: u16,
+    height: u16,
+    mipmap_data_len: u64,
+}
+
+/// Maximum size of serialized `ImageHeader` in bytes.
+const MAX_IMAGE_HEADER_SIZE: usize = 32;
+
+/// Reads mip-maps in `mips` range from serialized container of `len` bytes
+/// using `read` that returns the requested range of the container bytes.
+fn read_mipmaps<'a, F>(len: usize, mips: Range<u32>, read: F) -> Result<Image, LoadError>
+where
+    F: Fn(Range<usize>) -> Result<Cow<'a, [u8]>, LoadError>,
+{
+    let header: ImageHeader = bincode_options()
+        .allow_trailing_bytes()
+        .deserialize(&read(0..len.min(MAX_IMAGE_HEADER_SIZE))?)
+        .map_err(LoadError::BincodeError)?;
+
+    if header.container != CONTAINER_IMAGE {
+        return Err(LoadError::UnexpectedContainer);
+    }
+
+    let header_len = bincode_options()
+        .serialized_size(&header)
+        .map_err(LoadError::BincodeError)? as usize;
+    let (range, width, height) = mipmaps_range(
+        header.format,
+        header.width,
+        header.height,
+        header.mipmap_data_len as usize,
+        mips,
+    );
+
+    Ok(Image {
+        format: header.format,
+        width,
+        height,
+        mipmap_data: read(header_len + range.start..header_len + range.end)?
+            .into_owned()
+            .into(),
+    })
+}
+
+/// Returns the range of bytes that contains mip-maps in `mips` range of an image
+/// with `len` bytes of mip-map data and dimensions of the first of them.
+fn mipmaps_range(
+    format: Format,
+    width: u16,
+    height: u16,
+    len: usize,
+    mips: Range<u32>,
+) -> (Range<usize>, u16, u16) {
+    let mut start = len;
+    let mut end = len;
+    let mut offset = 0;
+    let (mut w, mut h) = (width as usize, height as usize);
+
+    for level in 0.. {
+        if offset >= len {
+            break;
+        }
+        if level == mips.start {
+            start = offset;
+        }
+        if level == mips.end {
+            end = offset;
+            break;
+        }
+
+        offset += w * h * format.bits_per_pixel() as usize / 8;
+        w /= 2;
+        h /= 2;
+    }
+
+    (
+        start..end.max(start),
+        width.checked_shr(mips.start).unwrap_or(0),
+        height.checked_shr(mips.start).unwrap_or(0),
+    )
+}
+
 /// Iterator over `Image` that provides individual mip-maps as `MipMap` structs.  
 pub struct MipMaps<'a> {
     data: &'a [u8],
@@ -183,3 +444,90 @@ impl<'a> Iterator for MipMaps<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::image::{f16_to_f32, f32_to_f16, Format, Image};
+    use crate::lz4::{Compressed, CompressionLevel};
+    use crate::tree::Tree;
+    use crate::{save_bf_to_bytes, Container, Data, File, LoadError};
+
+    fn image() -> Image {
+        // 64x64, 32x32, 16x16, ... 1x1 mip-maps of one byte pixels
+        let mipmap_data = (0..6)
+            .flat_map(|level| vec![level as u8; (64 >> level) * (64 >> level)])
+            .chain(std::iter::once(6))
+            .collect::<Vec<_>>();
+
+        Image {
+            format: Format::R8,
+            width: 64,
+            height: 64,
+            mipmap_data: mipmap_data.into(),
+        }
+    }
+
+    #[test]
+    fn reads_selected_mipmaps() {
+        let compressed = File::with_data(Data::Compressed(Compressed::new_with_compression_level(
+            Container::Image(image()),
+            CompressionLevel::Fast(1),
+        )));
+        let uncompressed = File::create_uncompressed(Container::Image(image()));
+
+        for file in [compressed, uncompressed].iter() {
+            let bytes = save_bf_to_bytes(file).unwrap();
+
+            let partial = Image::read_mipmaps(&bytes, 2..4).unwrap();
+            assert_eq!(partial.format, Format::R8);
+            assert_eq!((partial.width, partial.height), (16, 16));
+            assert_eq!(partial.mipmap_count(), 2);
+            assert_eq!(partial.mipmap_data.len(), 16 * 16 + 8 * 8);
+            assert!(partial.mipmap_data[..256].iter().all(|x| *x == 2));
+            assert!(partial.mipmap_data[256..].iter().all(|x| *x == 3));
+
+            let tail = Image::read_mipmaps(&bytes, 5..100).unwrap();
+            assert_eq!(tail.mipmap_data, vec![5, 5, 5, 5, 6]);
+
+            let all = Image::read_mipmaps(&bytes, 0..100).unwrap();
+            assert_eq!(all.mipmap_data, image().mipmap_data);
+            assert!(Image::read_mipmaps(&bytes, 7..8)
+                .unwrap()
+                .mipmap_data
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_other_containers() {
+        let file = File::create_compressed(Container::Tree(Tree::new()));
+        let bytes = save_bf_to_bytes(&file).unwrap();
+
+        assert!(matches!(
+            Image::read_mipmaps(&bytes, 0..1),
+            Err(LoadError::UnexpectedContainer)
+        ));
+    }
+
+    #[test]
+    fn converts_half_floats() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-26)), 0x0000);
+        // 1 + 2^-11 is exactly between two halves, rounds to even
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+        for x in &[0.0, 1.0, -2.5, 0.333_251_95, 65504.0, 2f32.powi(-20)] {
+            assert_eq!(f16_to_f32(f32_to_f16(*x)), *x);
+        }
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+    }
+}
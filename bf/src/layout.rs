@@ -0,0 +1,349 @@
+//! Binary layout of BF files and guarantees that keep it stable.
+//!
+//! All structs are serialized with `bincode` using [`bincode_options()`](fn.bincode_options.html).
+//! `bincode` is not self-describing, so the layout follows directly from the
+//! declarations of the structs:
+//!
+//! - struct fields are written in declaration order without any padding,
+//! - integers (including `u16`, `u32`, `usize` and enum variant indices) are
+//!   `varint` encoded: values below `251` take one byte, larger values are
+//!   prefixed with `251` (`u16`), `252` (`u32`) or `253` (`u64`) followed by
+//!   little-endian bytes,
+//! - floats are written as little-endian IEEE 754,
+//! - enum variant is written as its index in declaration order, followed by
+//!   its fields,
+//! - `Option` is one byte tag (`0` = `None`, `1` = `Some`) followed by the value,
+//! - `Vec`, `String` and byte buffers are `varint` length followed by elements,
+//! - `Uuid` is written as byte buffer of length `16`,
+//! - [`Compressed`](../lz4/struct.Compressed.html) is a `Vec` of byte buffers,
+//!   each containing `lz4` block of at most [`CHUNK_SIZE`](../lz4/constant.CHUNK_SIZE.html)
+//!   uncompressed bytes prefixed with its uncompressed size as little-endian `u32`,
+//! - [`zstd::Compressed`](../zstd/struct.Compressed.html) is a `Vec` of byte buffers,
+//!   each containing one `zstd` frame of at most `CHUNK_SIZE` uncompressed bytes.
+//!
+//! Therefore reordering fields or enum variants, changing a type of a field
+//! or inserting variants in the middle of an enum breaks all existing files.
+//! New enum variants may only be appended. Any other change requires bumping
+//! the [`BF_VERSION`](../constant.BF_VERSION.html) and writing a migration (see
+//! the [`migrate`](../migrate/index.html) module).
+//!
+//! Variant indices of field-less enums are verified at compile time below.
+//! The layout of the rest is pinned by tests with serialized bytes.
+
+use crate::image::Format;
+use crate::material::BlendMode;
+use crate::mesh::{IndexType, VertexFormat};
+use crate::Codec;
+use bincode::{options, Options};
+
+/// Returns `bincode` configuration used for all (de)serialization of BF
+/// files. Changing any of these options changes the binary format.
+#[inline]
+pub fn bincode_options() -> impl Options {
+    options()
+        .with_no_limit()
+        .with_little_endian()
+        .with_varint_encoding()
+        .reject_trailing_bytes()
+}
+
+/// Fails to compile if the variant of a field-less enum does not have the
+/// specified index (which `bincode` writes into the file).
+macro_rules! assert_variant_index {
+    ($($variant:path => $index:expr),+ $(,)?) => {
+        $(const _: [(); $index] = [(); $variant as usize];)+
+    };
+}
+
+assert_variant_index!(
+    Format::Dxt1 => 0,
+    Format::Dxt3 => 1,
+    Format::Dxt5 => 2,
+    Format::Rgb8 => 3,
+    Format::Rgba8 => 4,
+    Format::SrgbDxt1 => 5,
+    Format::SrgbDxt3 => 6,
+    Format::SrgbDxt5 => 7,
+    Format::Srgb8 => 8,
+    Format::Srgb8A8 => 9,
+    Format::R8 => 10,
+    Format::BC6H => 11,
+    Format::BC7 => 12,
+    Format::SrgbBC7 => 13,
+    Format::BC5 => 14,
+    Format::R16 => 15,
+    Format::Rg16 => 16,
+    Format::Rgba16F => 17,
+    Format::Rgba32F => 18,
+);
+
+assert_variant_index!(
+    VertexFormat::PositionNormalUvTangent => 0,
+    VertexFormat::PositionNormalUv => 1,
+    VertexFormat::Position => 2,
+);
+
+assert_variant_index!(IndexType::U16 => 0, IndexType::U32 => 1);
+
+assert_variant_index!(Codec::Lz4 => 0, Codec::Zstd => 1);
+
+assert_variant_index!(
+    BlendMode::Opaque => 0,
+    BlendMode::Masked => 1,
+    BlendMode::Translucent => 2,
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::image::{Format, Image};
+    use crate::layout::bincode_options;
+    use crate::material::{BlendMode, Material, MaterialFlags};
+    use crate::mesh::{IndexType, Lod, Mesh, MeshEncoding, VertexFormat};
+    use crate::sequence::Sequence;
+    use crate::shader::{Shader, ShaderStage, Variant};
+    use crate::tree::{Component, Tree};
+    use crate::virtual_texture::VirtualTexture;
+    use crate::{save_bf_to_bytes, Container, File};
+    use bincode::Options;
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    fn bytes<T: Serialize>(value: &T) -> Vec<u8> {
+        bincode_options().serialize(value).unwrap()
+    }
+
+    fn uncompressed(container: Container) -> Vec<u8> {
+        save_bf_to_bytes(&File::create_uncompressed(container)).unwrap()
+    }
+
+    #[test]
+    fn header() {
+        let bytes = uncompressed(Container::Material(Material::default()));
+
+        // varint u16 magic, version, `Data::Uncompressed`, `Container::Material`
+        assert_eq!(bytes[..6], [251, 0x42, 0x46, 11, 1, 2]);
+        assert_eq!(bytes[3], crate::BF_VERSION);
+    }
+
+    #[test]
+    fn container_variants() {
+        let image = Image {
+            format: Format::R8,
+            width: 1,
+            height: 1,
+            mipmap_data: vec![0].into(),
+        };
+        let mesh = Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vec![].into(),
+            index_type: IndexType::U16,
+            index_data: vec![].into(),
+            lods: vec![],
+            encoding: MeshEncoding::None,
+            stiffness: None,
+        };
+
+        assert_eq!(uncompressed(Container::Image(image))[5], 0);
+        assert_eq!(uncompressed(Container::Mesh(mesh))[5], 1);
+        assert_eq!(uncompressed(Container::Material(Material::default()))[5], 2);
+        assert_eq!(uncompressed(Container::Tree(Tree::new()))[5], 3);
+        assert_eq!(uncompressed(Container::Sequence(Sequence::default()))[5], 4);
+        let shader = Shader {
+            name: String::new(),
+            stage: ShaderStage::Vertex,
+            variants: vec![],
+        };
+        assert_eq!(uncompressed(Container::Shader(shader))[5], 5);
+        let texture = VirtualTexture {
+            format: Format::R8,
+            size: 1,
+            page_size: 1,
+            border: 0,
+            page_data: vec![0].into(),
+        };
+        assert_eq!(uncompressed(Container::VirtualTexture(texture))[5], 6);
+
+        let compressed = save_bf_to_bytes(&File::create_compressed(Container::Tree(Tree::new())));
+        assert_eq!(compressed.unwrap()[4], 0);
+
+        #[cfg(feature = "zstd")]
+        {
+            let tree = Container::Tree(Tree::new());
+            let file = File::create_compressed_with(tree, crate::Codec::Zstd, 1);
+            assert_eq!(save_bf_to_bytes(&file).unwrap()[4], 2);
+        }
+    }
+
+    #[test]
+    fn compressed() {
+        let bytes = save_bf_to_bytes(&File::create_compressed(Container::Tree(Tree::new())));
+        let bytes = bytes.unwrap();
+        let uncompressed = super::bincode_options()
+            .serialize(&Container::Tree(Tree::new()))
+            .unwrap();
+
+        // `Data::Compressed`, one chunk, length of the chunk, uncompressed size
+        assert_eq!(bytes[4..6], [0, 1]);
+        assert_eq!(bytes[6] as usize, bytes.len() - 7);
+        assert_eq!(bytes[7..11], (uncompressed.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn image() {
+        let image = Image {
+            format: Format::BC5,
+            width: 300,
+            height: 2,
+            mipmap_data: vec![1, 2, 3].into(),
+        };
+
+        assert_eq!(bytes(&image), [14, 251, 44, 1, 2, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn mesh() {
+        let mesh = Mesh {
+            vertex_format: VertexFormat::PositionNormalUv,
+            vertex_data: vec![7; 2].into(),
+            index_type: IndexType::U32,
+            index_data: vec![9].into(),
+            lods: vec![Lod {
+                first_index: 0,
+                index_count: 300,
+                min_coverage: 0.5,
+            }],
+            encoding: MeshEncoding::Meshopt {
+                vertex_count: 2,
+                index_count: 300,
+            },
+            stiffness: Some(vec![5].into()),
+        };
+
+        // one lod (first index, index count, min coverage), encoding with
+        // vertex and index count, stiffness
+        assert_eq!(
+            bytes(&mesh),
+            [1, 2, 7, 7, 1, 1, 9, 1, 0, 251, 44, 1, 0, 0, 0, 0x3f, 1, 2, 251, 44, 1, 1, 1, 5]
+        );
+    }
+
+    #[test]
+    fn material() {
+        let material = Material {
+            blend_mode: BlendMode::Masked,
+            albedo_color: [1.0, 0.5, 0.0],
+            roughness: 0.25,
+            metallic: 1.0,
+            alpha_cutoff: 0.5,
+            ior: 1.5,
+            opacity: 1.0,
+            sss: 0.0,
+            emissive_color: [2.0, 1.0, 0.0],
+            clear_coat: 1.0,
+            clear_coat_roughness: 0.25,
+            anisotropy: -0.5,
+            albedo_map: Some(Uuid::from_bytes([0xAA; 16])),
+            flags: MaterialFlags::FOLIAGE | MaterialFlags::CLEAR_COAT,
+            ..Material::default()
+        };
+
+        let mut expected = vec![1];
+        for x in &[
+            1.0f32, 0.5, 0.0, 0.25, 1.0, 0.5, 1.5, 1.0, 0.0, 2.0, 1.0, 0.0, 1.0, 0.25, -0.5,
+        ] {
+            expected.extend_from_slice(&x.to_le_bytes());
+        }
+        expected.extend_from_slice(&[1, 16]);
+        expected.extend_from_slice(&[0xAA; 16]);
+        expected.extend_from_slice(&[0; 7]);
+        // flags
+        expected.push(5);
+
+        assert_eq!(bytes(&material), expected);
+    }
+
+    #[test]
+    fn tree() {
+        // one node without children with `Component::Name("root")`, root handle
+        assert_eq!(
+            bytes(&Tree::new()),
+            [1, 0, 1, 0, 4, b'r', b'o', b'o', b't', 0]
+        );
+    }
+
+    #[test]
+    fn sequence() {
+        let mut seq = Sequence::default();
+        seq.fov.insert(0.5, 90.0);
+        seq.set_visible(1.0, 300, true);
+
+        // empty position and forward tracks, one fov key (time, value), one
+        // visibility key (time, object, visible)
+        assert_eq!(
+            bytes(&seq),
+            [0, 0, 1, 0, 0, 0, 0x3f, 0, 0, 0xb4, 0x42, 1, 0, 0, 0x80, 0x3f, 251, 0x2c, 1, 1]
+        );
+    }
+
+    #[test]
+    fn shader() {
+        let shader = Shader {
+            name: "vs".into(),
+            stage: ShaderStage::Fragment,
+            variants: vec![Variant {
+                defines: vec!["A".into()],
+                spirv: vec![0x03, 0x02, 0x23, 0x07],
+            }],
+        };
+
+        // name, stage, one variant with one define and 4 bytes of spirv
+        assert_eq!(
+            bytes(&shader),
+            [2, b'v', b's', 1, 1, 1, 1, b'A', 4, 0x03, 0x02, 0x23, 0x07]
+        );
+    }
+
+    #[test]
+    fn virtual_texture() {
+        let texture = VirtualTexture {
+            format: Format::R8,
+            size: 256,
+            page_size: 128,
+            border: 4,
+            page_data: vec![1, 2, 3].into(),
+        };
+
+        // format, varint sizes and border, 3 bytes of pages
+        assert_eq!(bytes(&texture), [10, 251, 0, 1, 128, 4, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn component_variants() {
+        let uuid = Uuid::nil();
+        let components = [
+            Component::Name(String::new()),
+            Component::Sky {
+                turbidity: 0.0,
+                ground_albedo: [0.0; 3],
+            },
+            Component::Transform {
+                position: [0.0; 3],
+                rotation: [0.0; 3],
+                scale: [0.0; 3],
+            },
+            Component::MeshRenderer {
+                mesh: uuid,
+                material: uuid,
+            },
+            Component::DirectionalLight {
+                direction: [0.0; 3],
+                intensity: 0.0,
+                color: [0.0; 3],
+            },
+        ];
+
+        for (idx, component) in components.iter().enumerate() {
+            assert_eq!(bytes(component)[0] as usize, idx);
+        }
+    }
+}
@@ -1,20 +1,34 @@
 //! This is a library for loading and storing BF files.
 
+use crate::blob::SharedBytes;
 use crate::image::Image;
-use crate::lz4::Compressed;
+use crate::layout::bincode_options;
+use crate::lz4::{Compressed, CompressionLevel};
 use crate::material::Material;
 use crate::mesh::Mesh;
+use crate::sequence::Sequence;
+use crate::shader::Shader;
 use crate::tree::{Tree, TreeError};
-use bincode::{options, Options};
+use crate::virtual_texture::VirtualTexture;
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 
 pub use uuid;
 
+pub mod blob;
 pub mod image;
+pub mod layout;
 pub mod lz4;
 pub mod material;
 pub mod mesh;
+pub mod migrate;
+pub mod pack;
+pub mod remap;
+pub mod sequence;
+pub mod shader;
 pub mod tree;
+pub mod virtual_texture;
+pub mod zstd;
 
 /// Possible BF file types (Image, Mesh...).
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,6 +37,9 @@ pub enum Container {
     Mesh(Mesh),
     Material(Material),
     Tree(Tree),
+    Sequence(Sequence),
+    Shader(Shader),
+    VirtualTexture(VirtualTexture),
 }
 
 /// Different data storage modes (compressed, uncompressed).
@@ -30,6 +47,28 @@ pub enum Container {
 pub enum Data {
     Compressed(Compressed<Container>),
     Uncompressed(Container),
+    /// Compressed with `zstd`, requires the `zstd` feature.
+    CompressedZstd(zstd::Compressed<Container>),
+}
+
+/// Codecs the data of a file can be compressed with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Codec {
+    Lz4,
+    /// Produces smaller files than `Lz4`, but it is slower and can only be
+    /// used when the `zstd` feature is enabled.
+    Zstd,
+}
+
+impl Codec {
+    /// Returns the compression level used by `File::create_compressed` for
+    /// `Lz4` and by default for `Zstd`.
+    pub fn default_level(self) -> i32 {
+        match self {
+            Codec::Lz4 => 17,
+            Codec::Zstd => zstd::DEFAULT_LEVEL,
+        }
+    }
 }
 
 /// BF file with its header and payload.
@@ -72,6 +111,7 @@ impl File {
         match &self.data {
             Data::Compressed(_) => true,
             Data::Uncompressed(_) => false,
+            Data::CompressedZstd(_) => true,
         }
     }
 
@@ -100,11 +140,29 @@ impl File {
         Self::with_data(Data::Compressed(Compressed::new(container)))
     }
 
+    /// Creates a new File object with correct header and specified container
+    /// value which will be compressed with the `codec` at specified `level`
+    /// when this object will be serialized. The `level` is `lz4` high
+    /// compression level or `zstd` level (see `Codec::default_level`).
+    ///
+    /// Note: Serialization of the file fails when `Zstd` codec is used without
+    /// the `zstd` feature.
+    pub fn create_compressed_with(container: Container, codec: Codec, level: i32) -> Self {
+        Self::with_data(match codec {
+            Codec::Lz4 => Data::Compressed(Compressed::new_with_compression_level(
+                container,
+                CompressionLevel::High(level),
+            )),
+            Codec::Zstd => Data::CompressedZstd(zstd::Compressed::new_with_level(container, level)),
+        })
+    }
+
     /// Unwraps the `Container` struct of this `File` and returns it.
     pub fn into_container(self) -> Container {
         match self.data {
             Data::Compressed(c) => c.into(),
             Data::Uncompressed(x) => x,
+            Data::CompressedZstd(c) => c.into(),
         }
     }
 
@@ -129,6 +187,27 @@ impl File {
         try_to_dynamic!(self.into_container(), Material)
     }
 
+    /// Tries to unwrap container (data) of this file as `Sequence`.
+    ///
+    /// This function returns `Ok(Sequence)` if the file contains a `Sequence` and `Err(())` otherwise.
+    pub fn try_to_sequence(self) -> Result<Sequence, ()> {
+        try_to_dynamic!(self.into_container(), Sequence)
+    }
+
+    /// Tries to unwrap container (data) of this file as `Shader`.
+    ///
+    /// This function returns `Ok(Shader)` if the file contains a `Shader` and `Err(())` otherwise.
+    pub fn try_to_shader(self) -> Result<Shader, ()> {
+        try_to_dynamic!(self.into_container(), Shader)
+    }
+
+    /// Tries to unwrap container (data) of this file as `VirtualTexture`.
+    ///
+    /// This function returns `Ok(VirtualTexture)` if the file contains a `VirtualTexture` and `Err(())` otherwise.
+    pub fn try_to_virtual_texture(self) -> Result<VirtualTexture, ()> {
+        try_to_dynamic!(self.into_container(), VirtualTexture)
+    }
+
     /// Tries to unwrap container (data) of this file as `Tree`.
     ///
     /// This function returns `Ok(Tree)` if the file contains a `Tree` and `Err(TreeError)` otherwise.
@@ -155,6 +234,10 @@ pub enum LoadError {
     UnsupportedVersion { library: u8, file: u8 },
     /// Internal `bincode` error.
     BincodeError(bincode::Error),
+    /// The file contains different type of container than was requested.
+    UnexpectedContainer,
+    /// Compressed data could not be decompressed.
+    DecompressionError(std::io::Error),
 }
 
 /* Constant representing the two byte magic sequence 'BF' */
@@ -162,22 +245,63 @@ pub enum LoadError {
 /// Two bytes magic that is present at the start of every .bf file.
 pub const BF_MAGIC: u16 = 17986;
 
-/// Version of BF format this version is able to read.
-pub const BF_VERSION: u8 = 5;
+/// Version of BF format this library writes. Files with older versions
+/// (down to [`migrate::MIN_SUPPORTED_VERSION`](migrate/constant.MIN_SUPPORTED_VERSION.html))
+/// can also be read.
+pub const BF_VERSION: u8 = 11;
 
-fn verify_bf_file_header(file: File) -> Result<File, LoadError> {
-    if file.magic != BF_MAGIC {
-        return Err(LoadError::InvalidMagic);
-    }
+/// Header present at the start of every .bf file. It is deserialized
+/// separately from the rest of the file so we can decide how the rest
+/// of the file should be decoded.
+#[derive(Deserialize)]
+struct Header {
+    magic: u16,
+    version: u8,
+}
 
-    if file.version != BF_VERSION {
-        return Err(LoadError::UnsupportedVersion {
-            library: BF_VERSION,
-            file: file.version,
-        });
+/// Variant indices of `Data`.
+pub(crate) const DATA_COMPRESSED: u32 = 0;
+pub(crate) const DATA_UNCOMPRESSED: u32 = 1;
+pub(crate) const DATA_COMPRESSED_ZSTD: u32 = 2;
+
+/// Header of the file followed by the variant index of its `Data`. Used to
+/// find out how the data is stored without deserializing it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DataHeader {
+    pub magic: u16,
+    pub version: u8,
+    pub data: u32,
+}
+
+impl DataHeader {
+    /// Reads the header from the start of the file `bytes` and verifies
+    /// the magic.
+    pub(crate) fn read(bytes: &[u8]) -> Result<Self, LoadError> {
+        if bytes.len() < 2 {
+            return Err(LoadError::FileTooShort);
+        }
+
+        let header: DataHeader = bincode_options()
+            .allow_trailing_bytes()
+            .deserialize(bytes)
+            .map_err(LoadError::BincodeError)?;
+
+        if header.magic != BF_MAGIC {
+            return Err(LoadError::InvalidMagic);
+        }
+
+        Ok(header)
     }
 
-    Ok(file)
+    /// Returns the codec the data is compressed with or `None` if it is not
+    /// compressed.
+    pub(crate) fn codec(&self) -> Option<Codec> {
+        match self.data {
+            DATA_COMPRESSED => Some(Codec::Lz4),
+            DATA_COMPRESSED_ZSTD => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
 }
 
 /// Tries to load provided array of bytes as File using `bincode`
@@ -185,6 +309,10 @@ fn verify_bf_file_header(file: File) -> Result<File, LoadError> {
 /// matches and version is supported. If these conditions are met
 /// and `bincode` deserialization succeeds this function returns
 /// File object. Error is returned otherwise.
+///
+/// Files created by older supported versions of the format are
+/// transparently upgraded to the current version (see the
+/// [`migrate`](migrate/index.html) module).
 pub fn load_bf_from_bytes(bytes: &[u8]) -> Result<File, LoadError> {
     // the `bytes` array could be shorter than two bytes. we need
     // to verify that this is not the case before trying to verify
@@ -193,11 +321,36 @@ pub fn load_bf_from_bytes(bytes: &[u8]) -> Result<File, LoadError> {
         return Err(LoadError::FileTooShort);
     }
 
-    options()
-        .with_little_endian()
+    let header: Header = bincode_options()
+        .allow_trailing_bytes()
         .deserialize(bytes)
-        .map_err(LoadError::BincodeError)
-        .and_then(verify_bf_file_header)
+        .map_err(LoadError::BincodeError)?;
+
+    if header.magic != BF_MAGIC {
+        return Err(LoadError::InvalidMagic);
+    }
+
+    match header.version {
+        BF_VERSION => bincode_options()
+            .deserialize(bytes)
+            .map_err(LoadError::BincodeError),
+        v if migrate::can_migrate(v) => migrate::migrate(v, bytes),
+        v => Err(LoadError::UnsupportedVersion {
+            library: BF_VERSION,
+            file: v,
+        }),
+    }
+}
+
+/// Same as [`load_bf_from_bytes()`](fn.load_bf_from_bytes.html), but payloads
+/// of images and meshes in uncompressed files refer to the `bytes` instead of
+/// being copied (see the [`blob`](blob/index.html) module). The `bytes` are
+/// kept alive until all blobs referring to them are dropped.
+pub fn load_bf_shared(bytes: SharedBytes) -> Result<File, LoadError> {
+    match blob::load_shared(&bytes) {
+        Some(file) => file,
+        None => load_bf_from_bytes((*bytes).as_ref()),
+    }
 }
 
 /// Serializes the specified file into a Vec of bytes using
@@ -205,8 +358,7 @@ pub fn load_bf_from_bytes(bytes: &[u8]) -> Result<File, LoadError> {
 /// as it is in `load_bf_from_bytes` function. This allows to
 /// write potentially invalid Files.
 pub fn save_bf_to_bytes(file: &File) -> Result<Vec<u8>, LoadError> {
-    options()
-        .with_little_endian()
+    bincode_options()
         .serialize(file)
         .map_err(LoadError::BincodeError)
 }
@@ -1,13 +1,29 @@
 //! Helper module for easy integration of compressed parts of
 //! struct into `serde`.
-
-use bincode::{options, Options};
-use lz4::block::{compress, decompress, CompressionMode};
-use serde::de::{DeserializeOwned, Error, Visitor};
+//!
+//! Compressed data are split into chunks of [`CHUNK_SIZE`](constant.CHUNK_SIZE.html)
+//! bytes that are compressed independently, so big containers can be
+//! (de)compressed in parallel on the `rayon` thread pool. The chunks also
+//! allow random access: [`ChunkIndex`](struct.ChunkIndex.html) decompresses
+//! only the chunks that contain the requested range of uncompressed data.
+
+use crate::layout::bincode_options;
+use bincode::Options;
+use lz4::block::{compress, decompress, decompress_to_buffer, CompressionMode};
+use rayon::prelude::*;
+use serde::de::{DeserializeOwned, Error as DeError, Visitor};
+use serde::ser::Error as SerError;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::{ByteBuf, Bytes};
+use std::convert::TryInto;
 use std::fmt::Formatter;
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::marker::PhantomData;
+use std::ops::Range;
+
+/// Maximum size of one uncompressed chunk of data in bytes.
+pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
 /// Compression level for `lz4` compression.
 ///
@@ -91,52 +107,220 @@ where
         assert!(std::mem::size_of::<T>() > 0);
 
         // 1. convert the `T` to bytes using `bincode`
-        // 2. compress the serialized bytes using `lz4`
+        // 2. compress chunks of the serialized bytes using `lz4` in parallel
+        // 3. write the compressed chunks as sequence of byte buffers
 
-        let serialized = options()
-            .with_little_endian()
+        let serialized = bincode_options()
             .serialize(&self.0)
-            .ok()
-            .unwrap();
-        let compressed = compress(serialized.as_slice(), self.1.into(), true)
-            .ok()
-            .unwrap();
+            .map_err(S::Error::custom)?;
+        let chunks = compress_chunks(&serialized, self.1, CHUNK_SIZE).map_err(S::Error::custom)?;
+
+        serializer.collect_seq(chunks.iter().map(|x| Bytes::new(x)))
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Compressed<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // 1. read all compressed chunks
+        // 2. decompress the chunks using `lz4` in parallel into one buffer
+        // 3. deserialize decompressed bytes to `T` using `bincode`
+
+        let chunks: Vec<ByteBuf> = Deserialize::deserialize(deserializer)?;
+        let decompressed = decompress_chunks(&chunks).map_err(D::Error::custom)?;
+        let deserialized: T = bincode_options()
+            .deserialize(decompressed.as_slice())
+            .map_err(D::Error::custom)?;
+
+        Ok(Compressed(deserialized, CompressionLevel::Default))
+    }
+}
+
+/// Splits the `bytes` into chunks of `chunk_size` bytes and compresses each
+/// of them (in parallel) into a `lz4` block prefixed with its uncompressed size.
+fn compress_chunks(
+    bytes: &[u8],
+    level: CompressionLevel,
+    chunk_size: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    bytes
+        .par_chunks(chunk_size)
+        .map(|chunk| compress(chunk, level.into(), true))
+        .collect()
+}
+
+/// Decompresses chunks created by `compress_chunks` (in parallel) and returns
+/// the concatenated data.
+fn decompress_chunks<C: AsRef<[u8]> + Sync>(chunks: &[C]) -> io::Result<Vec<u8>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let sizes = chunks
+        .iter()
+        .map(|x| uncompressed_size(x.as_ref()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut decompressed = vec![0; sizes.iter().sum()];
+    let mut outputs = Vec::with_capacity(chunks.len());
+    let mut rest = decompressed.as_mut_slice();
+    for size in sizes {
+        let (output, tail) = rest.split_at_mut(size);
+        outputs.push(output);
+        rest = tail;
+    }
+
+    chunks
+        .par_iter()
+        .zip(outputs.into_par_iter())
+        .try_for_each(|(chunk, output)| {
+            let len = output.len();
+            match decompress_to_buffer(chunk.as_ref(), None, output)? {
+                written if written == len => Ok(()),
+                _ => Err(invalid("compressed chunk has invalid size")),
+            }
+        })?;
+
+    Ok(decompressed)
+}
+
+/// Index of independently compressed chunks of a [`Compressed`](struct.Compressed.html)
+/// value that allows decompressing any range of the uncompressed (serialized)
+/// data without decompressing the chunks before it.
+///
+/// The index borrows the compressed chunks and is built only from their
+/// headers, so creating it is cheap.
+#[derive(Debug)]
+pub struct ChunkIndex<'a> {
+    chunks: Vec<&'a [u8]>,
+    /// Offset of each chunk in the uncompressed data.
+    offsets: Vec<usize>,
+    len: usize,
+}
+
+impl<'a> ChunkIndex<'a> {
+    /// Creates the index of `chunks` in the order they were serialized.
+    pub fn new(chunks: Vec<&'a [u8]>) -> io::Result<Self> {
+        let mut offsets = Vec::with_capacity(chunks.len());
+        let mut len = 0;
+
+        for chunk in chunks.iter() {
+            offsets.push(len);
+            len += uncompressed_size(chunk)?;
+        }
+
+        Ok(Self {
+            chunks,
+            offsets,
+            len,
+        })
+    }
+
+    /// Returns the length of the whole uncompressed data.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the uncompressed data is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decompresses the chunks overlapping the `range` (in parallel) and
+    /// returns bytes of uncompressed data in the `range`.
+    pub fn decompress_range(&self, range: Range<usize>) -> io::Result<Vec<u8>> {
+        if range.start > range.end || range.end > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "range is out of bounds of the uncompressed data",
+            ));
+        }
+        if range.start == range.end {
+            return Ok(vec![]);
+        }
+
+        // index of the first chunk that ends after the start of the range
+        // and of the first chunk that starts at or after the end of the range
+        let first = self.offsets.partition_point(|x| *x <= range.start) - 1;
+        let last = self.offsets.partition_point(|x| *x < range.end);
+
+        let decompressed = decompress_chunks(&self.chunks[first..last])?;
+        let start = range.start - self.offsets[first];
+
+        Ok(decompressed[start..start + range.len()].to_vec())
+    }
+}
+
+/// Returns the uncompressed size stored in first four bytes of a chunk.
+fn uncompressed_size(chunk: &[u8]) -> io::Result<usize> {
+    match chunk.get(..4) {
+        Some(size) => Ok(u32::from_le_bytes(size.try_into().unwrap()) as usize),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "compressed chunk is too short",
+        )),
+    }
+}
+
+/// Compressed data in the format used by BF files up to version `5`: the
+/// whole struct compressed as one `lz4` block. Used only to read (migrate)
+/// older files.
+#[derive(Debug)]
+pub struct SingleBlock<T>(pub T);
+
+impl<T> Serialize for SingleBlock<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        let serialized = bincode_options().serialize(&self.0).ok().unwrap();
+        let compressed = compress(
+            serialized.as_slice(),
+            CompressionLevel::Default.into(),
+            true,
+        )
+        .ok()
+        .unwrap();
 
         serializer.serialize_bytes(compressed.as_slice())
     }
 }
 
-struct CompressedVisitor<T>(PhantomData<T>);
+struct SingleBlockVisitor<T>(PhantomData<T>);
 
-impl<'de, T> Visitor<'de> for CompressedVisitor<T>
+impl<'de, T> Visitor<'de> for SingleBlockVisitor<T>
 where
     T: DeserializeOwned,
 {
-    type Value = Compressed<T>;
+    type Value = SingleBlock<T>;
 
     fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
-        formatter.write_fmt(format_args!("Compressed<{}>", std::any::type_name::<T>()))
+        formatter.write_fmt(format_args!("SingleBlock<{}>", std::any::type_name::<T>()))
     }
 
     fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
     where
-        E: Error,
+        E: DeError,
     {
         // 1. decompress bytes using `lz4`
-        // 2. deserialize decompressed bytes to `Compressed<T>` using `bincode`
+        // 2. deserialize decompressed bytes to `T` using `bincode`
 
-        let decompressed = decompress(v, None).ok().unwrap();
-        let deserialized: T = options()
-            .with_little_endian()
+        let decompressed = decompress(v, None).map_err(E::custom)?;
+        let deserialized: T = bincode_options()
             .deserialize(decompressed.as_slice())
-            .ok()
-            .unwrap();
+            .map_err(E::custom)?;
 
-        Ok(Compressed(deserialized, CompressionLevel::Default))
+        Ok(SingleBlock(deserialized))
     }
 }
 
-impl<'de, T> Deserialize<'de> for Compressed<T>
+impl<'de, T> Deserialize<'de> for SingleBlock<T>
 where
     T: DeserializeOwned,
 {
@@ -144,7 +328,7 @@ where
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_bytes(CompressedVisitor(PhantomData))
+        deserializer.deserialize_bytes(SingleBlockVisitor(PhantomData))
     }
 }
 
@@ -152,7 +336,9 @@ where
 mod tests {
     use quickcheck_macros::quickcheck;
 
-    use crate::lz4::Compressed;
+    use crate::lz4::{
+        compress_chunks, decompress_chunks, ChunkIndex, Compressed, CompressionLevel,
+    };
     use bincode::{deserialize, serialize};
     use serde::{Deserialize, Serialize};
 
@@ -261,4 +447,53 @@ mod tests {
 
         value == deserialized
     }
+
+    #[test]
+    fn test_chunks() {
+        let data: Vec<u8> = (0..10_000u32).map(|x| (x % 251) as u8).collect();
+
+        let chunks = compress_chunks(&data, CompressionLevel::Fast(1), 4096).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(decompress_chunks(&chunks).unwrap(), data);
+
+        let empty: Vec<Vec<u8>> = vec![];
+        assert!(compress_chunks(&[], CompressionLevel::Default, 4096)
+            .unwrap()
+            .is_empty());
+        assert!(decompress_chunks(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chunk_index() {
+        let data: Vec<u8> = (0..10_000u32).map(|x| (x % 251) as u8).collect();
+        let chunks = compress_chunks(&data, CompressionLevel::Fast(1), 4096).unwrap();
+        let index = ChunkIndex::new(chunks.iter().map(|x| x.as_slice()).collect()).unwrap();
+
+        assert_eq!(index.len(), data.len());
+        let ranges = [
+            0..10,
+            4090..4100,
+            4096..8192,
+            100..9000,
+            9999..10_000,
+            500..500,
+        ];
+        for range in ranges.iter().cloned() {
+            assert_eq!(index.decompress_range(range.clone()).unwrap(), &data[range]);
+        }
+        assert!(index.decompress_range(9000..10_001).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_chunks() {
+        let data = vec![7u8; 10_000];
+        let mut chunks = compress_chunks(&data, CompressionLevel::Default, 4096).unwrap();
+
+        // uncompressed size of the chunk does not match
+        chunks[1][0] = chunks[1][0].wrapping_add(1);
+        assert!(decompress_chunks(&chunks).is_err());
+
+        chunks[1].truncate(2);
+        assert!(decompress_chunks(&chunks).is_err());
+    }
 }
@@ -1,6 +1,7 @@
 //! Materials, their properties and blend mode.
 
 use serde::{Deserialize, Serialize};
+use std::ops::BitOr;
 use uuid::Uuid;
 
 /// Represents a mode in which the material is blended with content
@@ -18,6 +19,50 @@ pub enum BlendMode {
     Translucent,
 }
 
+/// Set of flags that select how surfaces with the material are rendered.
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MaterialFlags(u32);
+
+impl MaterialFlags {
+    /// Vertices are moved by the wind according to the stiffness stored in
+    /// the mesh (see [`Mesh::stiffness`](../mesh/struct.Mesh.html#structfield.stiffness)).
+    pub const FOLIAGE: MaterialFlags = MaterialFlags(1);
+    /// Back faces are not culled and are shaded with flipped normals (eg. leaves
+    /// or cloth modeled as a single plane).
+    pub const DOUBLE_SIDED: MaterialFlags = MaterialFlags(2);
+    /// Surface is covered by a transparent clear coat layer with its own specular
+    /// highlight (eg. car paint), see `Material::clear_coat`.
+    pub const CLEAR_COAT: MaterialFlags = MaterialFlags(4);
+    /// Specular highlight is stretched along the tangent or bitangent of the
+    /// surface (eg. brushed metal), see `Material::anisotropy`.
+    pub const ANISOTROPIC: MaterialFlags = MaterialFlags(8);
+
+    pub const fn empty() -> Self {
+        MaterialFlags(0)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether all flags of `other` are set.
+    pub const fn contains(self, other: MaterialFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: MaterialFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitOr for MaterialFlags {
+    type Output = MaterialFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MaterialFlags(self.0 | rhs.0)
+    }
+}
+
 /// Material is a descriptive asset that contains some properties and links to other assets (maps).
 #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Material {
@@ -37,6 +82,18 @@ pub struct Material {
     // subsurface scattering strength (1.0 = enabled, 0.0 = disabled)
     pub sss: f32,
 
+    // linear color of light emitted by the surface (multiplied by the emissive map),
+    // values above 1.0 are allowed so bright surfaces can bloom
+    pub emissive_color: [f32; 3],
+
+    // strength and roughness of the clear coat layer (used with `MaterialFlags::CLEAR_COAT`)
+    pub clear_coat: f32,
+    pub clear_coat_roughness: f32,
+
+    // stretching of the specular highlight along the tangent (positive values) or
+    // the bitangent (negative values) in range [-1, 1] (used with `MaterialFlags::ANISOTROPIC`)
+    pub anisotropy: f32,
+
     pub albedo_map: Option<Uuid>,
     pub normal_map: Option<Uuid>,
     pub displacement_map: Option<Uuid>,
@@ -44,6 +101,9 @@ pub struct Material {
     pub ao_map: Option<Uuid>,
     pub metallic_map: Option<Uuid>,
     pub opacity_map: Option<Uuid>,
+    pub emissive_map: Option<Uuid>,
+
+    pub flags: MaterialFlags,
 }
 
 impl Default for Material {
@@ -63,7 +123,13 @@ impl Default for Material {
             ao_map: None,
             metallic_map: None,
             opacity_map: None,
+            emissive_map: None,
             sss: 0.0,
+            emissive_color: [0.0, 0.0, 0.0],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: MaterialFlags::empty(),
         }
     }
 }
@@ -1,12 +1,15 @@
 //! Indexed triangular meshes stored in specified vertex format.
 
+use crate::blob::Blob;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Represents the individual vertex attributes, their loading and
 /// padding inside a single vertex in the vertex buffer.
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum VertexFormat {
-    // vec3(pos), vec3(nor), vec2(uv), vec3(tangent) + 1 byte padding
+    // vec3(pos), vec3(nor), vec2(uv), vec3(tangent), float(packed sky occlusion, see
+    // `pack_sky_occlusion`, zero when not computed)
     PositionNormalUvTangent,
     // vec3(pos), vec3(nor), vec2(uv)
     PositionNormalUv,
@@ -46,14 +49,344 @@ impl IndexType {
     }
 }
 
+/// Single level of detail of a `Mesh`. All levels share the vertex data and
+/// each of them uses a different range of the index data.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Lod {
+    /// Index of the first index (not byte offset) of this level in the index data.
+    pub first_index: u32,
+    /// Number of indices of this level.
+    pub index_count: u32,
+    /// Minimal fraction of the screen (`0.0` to `1.0`) covered by the mesh for
+    /// which this level is used.
+    pub min_coverage: f32,
+}
+
+/// Returns the index of the level that should be used to render a mesh that
+/// covers `coverage` fraction of the screen. Levels are expected to be sorted
+/// from the most detailed one. The least detailed level is used when the
+/// coverage is smaller than coverage of all levels.
+pub fn select_lod(lods: &[Lod], coverage: f32) -> usize {
+    lods.iter()
+        .position(|x| coverage >= x.min_coverage)
+        .unwrap_or_else(|| lods.len().saturating_sub(1))
+}
+
+/// Number of levels of the sky occlusion packed by [`pack_sky_occlusion`](fn.pack_sky_occlusion.html).
+const SKY_OCCLUSION_LEVELS: u32 = 63;
+
+/// Number of levels of each component of the octahedral encoding of bent normals.
+const BENT_NORMAL_LEVELS: u32 = 511;
+
+/// Packs the fraction of the sky visible from a vertex (`0.0` to `1.0`) and the
+/// bent normal (average unoccluded direction, normalized) into an integer stored
+/// as `f32`, so it fits the last component of the tangent.
+///
+/// The lowest 6 bits contain the occlusion (`1 - visibility`) and the next two
+/// 9-bit fields contain the octahedral encoding of the bent normal. All values
+/// are below 2^24, so they are represented exactly. Zero means the vertex is not
+/// occluded and the bent normal is the vertex normal, which is also how the
+/// padding of meshes without computed occlusion is interpreted. Must match
+/// `unpack_sky_occlusion` in `inc_normal.glsl`.
+pub fn pack_sky_occlusion(visibility: f32, bent_normal: [f32; 3]) -> f32 {
+    let occlusion = ((1.0 - visibility.clamp(0.0, 1.0)) * SKY_OCCLUSION_LEVELS as f32).round();
+    if occlusion == 0.0 {
+        return 0.0;
+    }
+
+    let [x, y, z] = bent_normal;
+    let sum = x.abs() + y.abs() + z.abs();
+    let (mut u, mut v) = (x / sum, y / sum);
+    if z < 0.0 {
+        let (pu, pv) = (u, v);
+        u = (1.0 - pv.abs()) * pu.signum();
+        v = (1.0 - pu.abs()) * pv.signum();
+    }
+    let quantize = |x: f32| ((x * 0.5 + 0.5) * BENT_NORMAL_LEVELS as f32).round() as u32;
+
+    (occlusion as u32 | quantize(u) << 6 | quantize(v) << 15) as f32
+}
+
+/// Returns the sky visibility and bent normal packed by [`pack_sky_occlusion`](fn.pack_sky_occlusion.html).
+/// Bent normal is `None` when it is the vertex normal.
+pub fn unpack_sky_occlusion(packed: f32) -> (f32, Option<[f32; 3]>) {
+    let bits = packed as u32;
+    let occlusion = bits & SKY_OCCLUSION_LEVELS;
+    if occlusion == 0 {
+        return (1.0, None);
+    }
+
+    let dequantize =
+        |x: u32| (x & BENT_NORMAL_LEVELS) as f32 / BENT_NORMAL_LEVELS as f32 * 2.0 - 1.0;
+    let (mut x, mut y) = (dequantize(bits >> 6), dequantize(bits >> 15));
+    let z = 1.0 - x.abs() - y.abs();
+    let t = (-z).max(0.0);
+    x += if x >= 0.0 { -t } else { t };
+    y += if y >= 0.0 { -t } else { t };
+    let length = (x * x + y * y + z * z).sqrt();
+
+    (
+        1.0 - occlusion as f32 / SKY_OCCLUSION_LEVELS as f32,
+        Some([x / length, y / length, z / length]),
+    )
+}
+
+/// How vertex and index data of a `Mesh` are stored.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum MeshEncoding {
+    /// Vertices and indices are stored as they are uploaded to the GPU.
+    None,
+    /// Vertex and index data are compressed with `meshoptimizer` vertex and
+    /// index codecs. The counts are needed to decode the data.
+    Meshopt { vertex_count: u32, index_count: u32 },
+}
+
+/// Errors that may happen when encoding or decoding mesh data.
+#[derive(Debug)]
+pub enum MeshEncodingError {
+    /// Length of the data is not a multiple of the vertex or index size.
+    InvalidLength,
+    /// The `meshoptimizer` codec failed.
+    Meshopt(String),
+}
+
 /// Asset type that is used to store indexed triangular geometry data. Each mesh has specified
 /// format of vertex data and index type.
+///
+/// Mesh without any `lods` has only one level of detail that uses the whole index data.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Mesh {
     pub vertex_format: VertexFormat,
-    #[serde(with = "serde_bytes")]
-    pub vertex_data: Vec<u8>,
+    pub vertex_data: Blob,
     pub index_type: IndexType,
-    #[serde(with = "serde_bytes")]
-    pub index_data: Vec<u8>,
+    pub index_data: Blob,
+    pub lods: Vec<Lod>,
+    pub encoding: MeshEncoding,
+    /// Stiffness of each vertex as little-endian `f32` from `0.0` (moved by the
+    /// wind the most) to `1.0` (doesn't move). Used by foliage materials (see
+    /// [`MaterialFlags::FOLIAGE`](../material/struct.MaterialFlags.html)). It is
+    /// never encoded, so it can be used even when `vertex_data` is.
+    pub stiffness: Option<Blob>,
+}
+
+impl Mesh {
+    /// Compresses vertex and index data with `meshoptimizer` codecs. The codecs
+    /// work best on meshes optimized for vertex cache and vertex fetch and make
+    /// the data compress much better by general purpose compression (LZ4).
+    pub fn encode_meshopt(self) -> Result<Self, MeshEncodingError> {
+        if self.encoding != MeshEncoding::None {
+            return Ok(self);
+        }
+
+        let vertex_size = self.vertex_format.size_of_one_vertex();
+        let index_size = self.index_type.size_of_one_index();
+        if self.vertex_data.len() % vertex_size != 0 || self.index_data.len() % index_size != 0 {
+            return Err(MeshEncodingError::InvalidLength);
+        }
+        let vertex_count = self.vertex_data.len() / vertex_size;
+        let indices = match self.index_type {
+            IndexType::U16 => words::<2>(&self.index_data)
+                .map(|x| u16::from_le_bytes(x) as u32)
+                .collect::<Vec<_>>(),
+            IndexType::U32 => words::<4>(&self.index_data)
+                .map(u32::from_le_bytes)
+                .collect::<Vec<_>>(),
+        };
+
+        let vertex_data = match self.vertex_format {
+            VertexFormat::PositionNormalUvTangent => encode_vertices::<12>(&self.vertex_data),
+            VertexFormat::PositionNormalUv => encode_vertices::<8>(&self.vertex_data),
+            VertexFormat::Position => encode_vertices::<4>(&self.vertex_data),
+        }?;
+        let index_data = meshopt::encode_index_buffer(&indices, vertex_count)
+            .map_err(|e| MeshEncodingError::Meshopt(e.to_string()))?;
+
+        Ok(Self {
+            vertex_data: vertex_data.into(),
+            index_data: index_data.into(),
+            encoding: MeshEncoding::Meshopt {
+                vertex_count: vertex_count as u32,
+                index_count: indices.len() as u32,
+            },
+            ..self
+        })
+    }
+
+    /// Returns vertex and index data in the form they are uploaded to the GPU.
+    /// The data is decoded if the mesh is encoded, otherwise it is borrowed.
+    pub fn decoded_data(&self) -> Result<(Cow<[u8]>, Cow<[u8]>), MeshEncodingError> {
+        match self.encoding {
+            MeshEncoding::None => Ok((
+                Cow::Borrowed(&self.vertex_data),
+                Cow::Borrowed(&self.index_data),
+            )),
+            MeshEncoding::Meshopt {
+                vertex_count,
+                index_count,
+            } => {
+                let (vertex_count, index_count) = (vertex_count as usize, index_count as usize);
+                let vertices = match self.vertex_format {
+                    VertexFormat::PositionNormalUvTangent => {
+                        decode_vertices::<12>(&self.vertex_data, vertex_count)
+                    }
+                    VertexFormat::PositionNormalUv => {
+                        decode_vertices::<8>(&self.vertex_data, vertex_count)
+                    }
+                    VertexFormat::Position => decode_vertices::<4>(&self.vertex_data, vertex_count),
+                }?;
+                let indices = match self.index_type {
+                    IndexType::U16 => {
+                        meshopt::decode_index_buffer::<u16>(&self.index_data, index_count)
+                            .map(|x| x.iter().flat_map(|i| i.to_le_bytes()).collect())
+                    }
+                    IndexType::U32 => {
+                        meshopt::decode_index_buffer::<u32>(&self.index_data, index_count)
+                            .map(|x| x.iter().flat_map(|i| i.to_le_bytes()).collect())
+                    }
+                }
+                .map_err(|e| MeshEncodingError::Meshopt(e.to_string()))?;
+
+                Ok((Cow::Owned(vertices), Cow::Owned(indices)))
+            }
+        }
+    }
+}
+
+/// Splits `bytes` into `N` byte long words.
+fn words<const N: usize>(bytes: &[u8]) -> impl Iterator<Item = [u8; N]> + '_ {
+    bytes.chunks_exact(N).map(|x| {
+        let mut word = [0; N];
+        word.copy_from_slice(x);
+        word
+    })
+}
+
+/// Encodes vertices consisting of `N` 32-bit values.
+fn encode_vertices<const N: usize>(bytes: &[u8]) -> Result<Vec<u8>, MeshEncodingError> {
+    let values = words::<4>(bytes)
+        .map(u32::from_le_bytes)
+        .collect::<Vec<_>>();
+    let vertices = values
+        .chunks_exact(N)
+        .map(|x| {
+            let mut vertex = [0; N];
+            vertex.copy_from_slice(x);
+            vertex
+        })
+        .collect::<Vec<[u32; N]>>();
+
+    meshopt::encode_vertex_buffer(&vertices).map_err(|e| MeshEncodingError::Meshopt(e.to_string()))
+}
+
+/// Decodes `count` vertices consisting of `N` 32-bit values.
+fn decode_vertices<const N: usize>(
+    encoded: &[u8],
+    count: usize,
+) -> Result<Vec<u8>, MeshEncodingError>
+where
+    [u32; N]: Default,
+{
+    meshopt::decode_vertex_buffer::<[u32; N]>(encoded, count)
+        .map(|x| {
+            x.iter()
+                .flat_map(|v| v.iter().flat_map(|i| i.to_le_bytes()))
+                .collect()
+        })
+        .map_err(|e| MeshEncodingError::Meshopt(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh::{
+        pack_sky_occlusion, select_lod, unpack_sky_occlusion, IndexType, Lod, Mesh, MeshEncoding,
+        VertexFormat,
+    };
+
+    fn lod(min_coverage: f32) -> Lod {
+        Lod {
+            first_index: 0,
+            index_count: 3,
+            min_coverage,
+        }
+    }
+
+    #[test]
+    fn selects_lod_by_coverage() {
+        let lods = [lod(0.1), lod(0.05), lod(0.01)];
+
+        assert_eq!(select_lod(&lods, 0.5), 0);
+        assert_eq!(select_lod(&lods, 0.1), 0);
+        assert_eq!(select_lod(&lods, 0.07), 1);
+        assert_eq!(select_lod(&lods, 0.02), 2);
+        assert_eq!(select_lod(&lods, 0.0), 2);
+        assert_eq!(select_lod(&[], 0.5), 0);
+    }
+
+    #[test]
+    fn sky_occlusion_roundtrip() {
+        assert_eq!(pack_sky_occlusion(1.0, [0.0, 0.0, -1.0]), 0.0);
+        assert_eq!(unpack_sky_occlusion(0.0), (1.0, None));
+
+        for normal in [[0.0, 1.0, 0.0], [0.6, 0.0, -0.8], [-0.48, -0.6, 0.64]] {
+            let packed = pack_sky_occlusion(0.25, normal);
+            assert_eq!(packed, packed.trunc());
+            assert!(packed < (1 << 24) as f32);
+
+            let (visibility, bent) = unpack_sky_occlusion(packed);
+            let bent = bent.unwrap();
+            assert!((visibility - 0.25).abs() < 1.0 / 63.0);
+            let dot: f32 = (0..3).map(|i| bent[i] * normal[i]).sum();
+            assert!(dot > 0.999, "{:?} != {:?}", bent, normal);
+        }
+    }
+
+    #[test]
+    fn meshopt_encoding_roundtrip() {
+        // grid of 4x4 vertices with position and 4 bytes of padding
+        let vertex_data = (0..16)
+            .flat_map(|i| [(i % 4) as f32, (i / 4) as f32, 0.0, 0.0])
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<_>>();
+        let index_data = (0..3)
+            .flat_map(|y| (0..3).map(move |x| y * 4 + x))
+            .flat_map(|i: u16| [i, i + 1, i + 4, i + 1, i + 5, i + 4])
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mesh = Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vertex_data.clone().into(),
+            index_type: IndexType::U16,
+            index_data: index_data.clone().into(),
+            lods: vec![],
+            encoding: MeshEncoding::None,
+            stiffness: None,
+        };
+
+        let encoded = mesh.encode_meshopt().unwrap();
+        assert_eq!(
+            encoded.encoding,
+            MeshEncoding::Meshopt {
+                vertex_count: 16,
+                index_count: 54,
+            }
+        );
+
+        let (vertices, indices) = encoded.decoded_data().unwrap();
+        assert_eq!(vertices.as_ref(), vertex_data.as_slice());
+
+        // the index codec may rotate vertices of triangles (keeping the winding)
+        let triangles = |x: &[u8]| -> Vec<[u8; 6]> {
+            x.chunks_exact(6)
+                .map(|t| {
+                    let rotations = [
+                        [t[0], t[1], t[2], t[3], t[4], t[5]],
+                        [t[2], t[3], t[4], t[5], t[0], t[1]],
+                        [t[4], t[5], t[0], t[1], t[2], t[3]],
+                    ];
+                    *rotations.iter().min().unwrap()
+                })
+                .collect()
+        };
+        assert_eq!(triangles(&indices), triangles(&index_data));
+    }
 }
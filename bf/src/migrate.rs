@@ -0,0 +1,1024 @@
+//! In-memory migration of files created by older versions of the BF format.
+//!
+//! Because `bincode` is not self-describing we keep copies of structs that
+//! changed between versions and deserialize older files into them. The result
+//! is then upgraded into current structs so the rest of the code only ever
+//! sees the latest version of the format.
+//!
+//! When bumping the `BF_VERSION` add a new submodule containing the structs of
+//! the previous version and a conversion into the current ones.
+
+use crate::layout::bincode_options;
+use crate::lz4::Compressed;
+use crate::mesh::MeshEncoding;
+use crate::{zstd, Container, Data, File, LoadError, BF_MAGIC, BF_VERSION};
+use bincode::Options;
+
+/// Oldest version of BF format this library is able to read (and migrate).
+pub const MIN_SUPPORTED_VERSION: u8 = 4;
+
+/// Returns whether a file with specified version can be migrated to the
+/// current version of the format.
+#[inline]
+pub fn can_migrate(version: u8) -> bool {
+    (MIN_SUPPORTED_VERSION..BF_VERSION).contains(&version)
+}
+
+/// Deserializes the file with specified (older) `version` from `bytes` and
+/// upgrades it to current version of the format.
+pub(crate) fn migrate(version: u8, bytes: &[u8]) -> Result<File, LoadError> {
+    match version {
+        4 => bincode_options()
+            .deserialize::<v4::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        5 => bincode_options()
+            .deserialize::<v5::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        6 => bincode_options()
+            .deserialize::<v6::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        7 => bincode_options()
+            .deserialize::<v7::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        8 => bincode_options()
+            .deserialize::<v8::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        9 => bincode_options()
+            .deserialize::<v9::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        10 => bincode_options()
+            .deserialize::<v10::File>(bytes)
+            .map(Into::into)
+            .map_err(LoadError::BincodeError),
+        _ => Err(LoadError::UnsupportedVersion {
+            library: BF_VERSION,
+            file: version,
+        }),
+    }
+}
+
+/// Version 4 of the format. Material did not have `ior` and `sss` properties.
+pub(crate) mod v4 {
+    use crate::image::Image;
+    use crate::lz4::SingleBlock;
+    use crate::material::BlendMode;
+    use crate::migrate::v6::Mesh;
+    use crate::tree::Tree;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(SingleBlock<Container>),
+        Uncompressed(Container),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+
+    #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+    pub struct Material {
+        pub blend_mode: BlendMode,
+        pub albedo_color: [f32; 3],
+        pub roughness: f32,
+        pub metallic: f32,
+        pub alpha_cutoff: f32,
+        pub opacity: f32,
+        pub albedo_map: Option<Uuid>,
+        pub normal_map: Option<Uuid>,
+        pub displacement_map: Option<Uuid>,
+        pub roughness_map: Option<Uuid>,
+        pub ao_map: Option<Uuid>,
+        pub metallic_map: Option<Uuid>,
+        pub opacity_map: Option<Uuid>,
+    }
+}
+
+/// Version 5 of the format. Compressed data were stored as one `lz4` block.
+pub(crate) mod v5 {
+    use crate::lz4::SingleBlock;
+    use crate::migrate::v6::Container;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(SingleBlock<Container>),
+        Uncompressed(Container),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+/// Version 6 of the format. Meshes did not have levels of detail.
+pub(crate) mod v6 {
+    use crate::image::Image;
+    use crate::lz4::Compressed;
+    use crate::mesh::{IndexType, VertexFormat};
+    use crate::migrate::v8::Material;
+    use crate::sequence::Sequence;
+    use crate::tree::Tree;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Mesh {
+        pub vertex_format: VertexFormat,
+        #[serde(with = "serde_bytes")]
+        pub vertex_data: Vec<u8>,
+        pub index_type: IndexType,
+        #[serde(with = "serde_bytes")]
+        pub index_data: Vec<u8>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+        Sequence(Sequence),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(Compressed<Container>),
+        Uncompressed(Container),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+/// Version 7 of the format. Mesh data could not be encoded.
+pub(crate) mod v7 {
+    use crate::image::Image;
+    use crate::lz4::Compressed;
+    use crate::mesh::{IndexType, Lod, VertexFormat};
+    use crate::migrate::v8::Material;
+    use crate::sequence::Sequence;
+    use crate::shader::Shader;
+    use crate::tree::Tree;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Mesh {
+        pub vertex_format: VertexFormat,
+        #[serde(with = "serde_bytes")]
+        pub vertex_data: Vec<u8>,
+        pub index_type: IndexType,
+        #[serde(with = "serde_bytes")]
+        pub index_data: Vec<u8>,
+        pub lods: Vec<Lod>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+        Sequence(Sequence),
+        Shader(Shader),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(Compressed<Container>),
+        Uncompressed(Container),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+/// Version 8 of the format. Materials did not have flags and meshes did not
+/// have stiffness of vertices.
+pub(crate) mod v8 {
+    use crate::blob::Blob;
+    use crate::image::Image;
+    use crate::lz4::Compressed;
+    use crate::material::BlendMode;
+    use crate::mesh::{IndexType, Lod, MeshEncoding, VertexFormat};
+    use crate::sequence::Sequence;
+    use crate::shader::Shader;
+    use crate::tree::Tree;
+    use crate::virtual_texture::VirtualTexture;
+    use crate::zstd;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+    pub struct Material {
+        pub blend_mode: BlendMode,
+        pub albedo_color: [f32; 3],
+        pub roughness: f32,
+        pub metallic: f32,
+        pub alpha_cutoff: f32,
+        pub ior: f32,
+        pub opacity: f32,
+        pub sss: f32,
+        pub albedo_map: Option<Uuid>,
+        pub normal_map: Option<Uuid>,
+        pub displacement_map: Option<Uuid>,
+        pub roughness_map: Option<Uuid>,
+        pub ao_map: Option<Uuid>,
+        pub metallic_map: Option<Uuid>,
+        pub opacity_map: Option<Uuid>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Mesh {
+        pub vertex_format: VertexFormat,
+        pub vertex_data: Blob,
+        pub index_type: IndexType,
+        pub index_data: Blob,
+        pub lods: Vec<Lod>,
+        pub encoding: MeshEncoding,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+        Sequence(Sequence),
+        Shader(Shader),
+        VirtualTexture(VirtualTexture),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(Compressed<Container>),
+        Uncompressed(Container),
+        CompressedZstd(zstd::Compressed<Container>),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+/// Version 9 of the format. Materials did not have emissive color and map.
+pub(crate) mod v9 {
+    use crate::image::Image;
+    use crate::lz4::Compressed;
+    use crate::material::{BlendMode, MaterialFlags};
+    use crate::mesh::Mesh;
+    use crate::sequence::Sequence;
+    use crate::shader::Shader;
+    use crate::tree::Tree;
+    use crate::virtual_texture::VirtualTexture;
+    use crate::zstd;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+    pub struct Material {
+        pub blend_mode: BlendMode,
+        pub albedo_color: [f32; 3],
+        pub roughness: f32,
+        pub metallic: f32,
+        pub alpha_cutoff: f32,
+        pub ior: f32,
+        pub opacity: f32,
+        pub sss: f32,
+        pub albedo_map: Option<Uuid>,
+        pub normal_map: Option<Uuid>,
+        pub displacement_map: Option<Uuid>,
+        pub roughness_map: Option<Uuid>,
+        pub ao_map: Option<Uuid>,
+        pub metallic_map: Option<Uuid>,
+        pub opacity_map: Option<Uuid>,
+        pub flags: MaterialFlags,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+        Sequence(Sequence),
+        Shader(Shader),
+        VirtualTexture(VirtualTexture),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(Compressed<Container>),
+        Uncompressed(Container),
+        CompressedZstd(zstd::Compressed<Container>),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+/// Version 10 of the format. Materials did not have clear coat and anisotropy.
+pub(crate) mod v10 {
+    use crate::image::Image;
+    use crate::lz4::Compressed;
+    use crate::material::{BlendMode, MaterialFlags};
+    use crate::mesh::Mesh;
+    use crate::sequence::Sequence;
+    use crate::shader::Shader;
+    use crate::tree::Tree;
+    use crate::virtual_texture::VirtualTexture;
+    use crate::zstd;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+    pub struct Material {
+        pub blend_mode: BlendMode,
+        pub albedo_color: [f32; 3],
+        pub roughness: f32,
+        pub metallic: f32,
+        pub alpha_cutoff: f32,
+        pub ior: f32,
+        pub opacity: f32,
+        pub sss: f32,
+        pub emissive_color: [f32; 3],
+        pub albedo_map: Option<Uuid>,
+        pub normal_map: Option<Uuid>,
+        pub displacement_map: Option<Uuid>,
+        pub roughness_map: Option<Uuid>,
+        pub ao_map: Option<Uuid>,
+        pub metallic_map: Option<Uuid>,
+        pub opacity_map: Option<Uuid>,
+        pub emissive_map: Option<Uuid>,
+        pub flags: MaterialFlags,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Container {
+        Image(Image),
+        Mesh(Mesh),
+        Material(Material),
+        Tree(Tree),
+        Sequence(Sequence),
+        Shader(Shader),
+        VirtualTexture(VirtualTexture),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum Data {
+        Compressed(Compressed<Container>),
+        Uncompressed(Container),
+        CompressedZstd(zstd::Compressed<Container>),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct File {
+        pub magic: u16,
+        pub version: u8,
+        pub data: Data,
+    }
+}
+
+impl From<v4::Material> for crate::material::Material {
+    fn from(m: v4::Material) -> Self {
+        Self {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            opacity: m.opacity,
+            albedo_map: m.albedo_map,
+            normal_map: m.normal_map,
+            displacement_map: m.displacement_map,
+            roughness_map: m.roughness_map,
+            ao_map: m.ao_map,
+            metallic_map: m.metallic_map,
+            opacity_map: m.opacity_map,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<v9::Material> for crate::material::Material {
+    fn from(m: v9::Material) -> Self {
+        Self {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            albedo_map: m.albedo_map,
+            normal_map: m.normal_map,
+            displacement_map: m.displacement_map,
+            roughness_map: m.roughness_map,
+            ao_map: m.ao_map,
+            metallic_map: m.metallic_map,
+            opacity_map: m.opacity_map,
+            flags: m.flags,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<v10::Material> for crate::material::Material {
+    fn from(m: v10::Material) -> Self {
+        Self {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            emissive_color: m.emissive_color,
+            albedo_map: m.albedo_map,
+            normal_map: m.normal_map,
+            displacement_map: m.displacement_map,
+            roughness_map: m.roughness_map,
+            ao_map: m.ao_map,
+            metallic_map: m.metallic_map,
+            opacity_map: m.opacity_map,
+            emissive_map: m.emissive_map,
+            flags: m.flags,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<v4::Container> for Container {
+    fn from(c: v4::Container) -> Self {
+        match c {
+            v4::Container::Image(t) => Container::Image(t),
+            v4::Container::Mesh(t) => Container::Mesh(t.into()),
+            v4::Container::Material(t) => Container::Material(t.into()),
+            v4::Container::Tree(t) => Container::Tree(t),
+        }
+    }
+}
+
+impl From<v8::Material> for crate::material::Material {
+    fn from(m: v8::Material) -> Self {
+        Self {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            albedo_map: m.albedo_map,
+            normal_map: m.normal_map,
+            displacement_map: m.displacement_map,
+            roughness_map: m.roughness_map,
+            ao_map: m.ao_map,
+            metallic_map: m.metallic_map,
+            opacity_map: m.opacity_map,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<v6::Mesh> for crate::mesh::Mesh {
+    fn from(m: v6::Mesh) -> Self {
+        Self {
+            vertex_format: m.vertex_format,
+            vertex_data: m.vertex_data.into(),
+            index_type: m.index_type,
+            index_data: m.index_data.into(),
+            lods: vec![],
+            encoding: MeshEncoding::None,
+            stiffness: None,
+        }
+    }
+}
+
+impl From<v7::Mesh> for crate::mesh::Mesh {
+    fn from(m: v7::Mesh) -> Self {
+        Self {
+            vertex_format: m.vertex_format,
+            vertex_data: m.vertex_data.into(),
+            index_type: m.index_type,
+            index_data: m.index_data.into(),
+            lods: m.lods,
+            encoding: MeshEncoding::None,
+            stiffness: None,
+        }
+    }
+}
+
+impl From<v8::Mesh> for crate::mesh::Mesh {
+    fn from(m: v8::Mesh) -> Self {
+        Self {
+            vertex_format: m.vertex_format,
+            vertex_data: m.vertex_data,
+            index_type: m.index_type,
+            index_data: m.index_data,
+            lods: m.lods,
+            encoding: m.encoding,
+            stiffness: None,
+        }
+    }
+}
+
+impl From<v6::Container> for Container {
+    fn from(c: v6::Container) -> Self {
+        match c {
+            v6::Container::Image(t) => Container::Image(t),
+            v6::Container::Mesh(t) => Container::Mesh(t.into()),
+            v6::Container::Material(t) => Container::Material(t.into()),
+            v6::Container::Tree(t) => Container::Tree(t),
+            v6::Container::Sequence(t) => Container::Sequence(t),
+        }
+    }
+}
+
+impl From<v7::Container> for Container {
+    fn from(c: v7::Container) -> Self {
+        match c {
+            v7::Container::Image(t) => Container::Image(t),
+            v7::Container::Mesh(t) => Container::Mesh(t.into()),
+            v7::Container::Material(t) => Container::Material(t.into()),
+            v7::Container::Tree(t) => Container::Tree(t),
+            v7::Container::Sequence(t) => Container::Sequence(t),
+            v7::Container::Shader(t) => Container::Shader(t),
+        }
+    }
+}
+
+impl From<v8::Container> for Container {
+    fn from(c: v8::Container) -> Self {
+        match c {
+            v8::Container::Image(t) => Container::Image(t),
+            v8::Container::Mesh(t) => Container::Mesh(t.into()),
+            v8::Container::Material(t) => Container::Material(t.into()),
+            v8::Container::Tree(t) => Container::Tree(t),
+            v8::Container::Sequence(t) => Container::Sequence(t),
+            v8::Container::Shader(t) => Container::Shader(t),
+            v8::Container::VirtualTexture(t) => Container::VirtualTexture(t),
+        }
+    }
+}
+
+impl From<v4::File> for File {
+    fn from(f: v4::File) -> Self {
+        // migrated file is stored in memory in the current version of
+        // the format and will be saved as such
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v4::Data::Compressed(c) => Data::Compressed(Compressed::new(c.0.into())),
+                v4::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+            },
+        }
+    }
+}
+
+impl From<v5::File> for File {
+    fn from(f: v5::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v5::Data::Compressed(c) => Data::Compressed(Compressed::new(c.0.into())),
+                v5::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+            },
+        }
+    }
+}
+
+impl From<v6::File> for File {
+    fn from(f: v6::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v6::Data::Compressed(c) => Data::Compressed(Compressed::new(c.into().into())),
+                v6::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+            },
+        }
+    }
+}
+
+impl From<v7::File> for File {
+    fn from(f: v7::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v7::Data::Compressed(c) => Data::Compressed(Compressed::new(c.into().into())),
+                v7::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+            },
+        }
+    }
+}
+
+impl From<v8::File> for File {
+    fn from(f: v8::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v8::Data::Compressed(c) => Data::Compressed(Compressed::new(c.into().into())),
+                v8::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+                v8::Data::CompressedZstd(c) => {
+                    Data::CompressedZstd(zstd::Compressed::new(c.into().into()))
+                }
+            },
+        }
+    }
+}
+
+impl From<v9::Container> for Container {
+    fn from(c: v9::Container) -> Self {
+        match c {
+            v9::Container::Image(t) => Container::Image(t),
+            v9::Container::Mesh(t) => Container::Mesh(t),
+            v9::Container::Material(t) => Container::Material(t.into()),
+            v9::Container::Tree(t) => Container::Tree(t),
+            v9::Container::Sequence(t) => Container::Sequence(t),
+            v9::Container::Shader(t) => Container::Shader(t),
+            v9::Container::VirtualTexture(t) => Container::VirtualTexture(t),
+        }
+    }
+}
+
+impl From<v9::File> for File {
+    fn from(f: v9::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v9::Data::Compressed(c) => Data::Compressed(Compressed::new(c.into().into())),
+                v9::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+                v9::Data::CompressedZstd(c) => {
+                    Data::CompressedZstd(zstd::Compressed::new(c.into().into()))
+                }
+            },
+        }
+    }
+}
+
+impl From<v10::Container> for Container {
+    fn from(c: v10::Container) -> Self {
+        match c {
+            v10::Container::Image(t) => Container::Image(t),
+            v10::Container::Mesh(t) => Container::Mesh(t),
+            v10::Container::Material(t) => Container::Material(t.into()),
+            v10::Container::Tree(t) => Container::Tree(t),
+            v10::Container::Sequence(t) => Container::Sequence(t),
+            v10::Container::Shader(t) => Container::Shader(t),
+            v10::Container::VirtualTexture(t) => Container::VirtualTexture(t),
+        }
+    }
+}
+
+impl From<v10::File> for File {
+    fn from(f: v10::File) -> Self {
+        File {
+            magic: BF_MAGIC,
+            version: BF_VERSION,
+            data: match f.data {
+                v10::Data::Compressed(c) => Data::Compressed(Compressed::new(c.into().into())),
+                v10::Data::Uncompressed(c) => Data::Uncompressed(c.into()),
+                v10::Data::CompressedZstd(c) => {
+                    Data::CompressedZstd(zstd::Compressed::new(c.into().into()))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::bincode_options;
+    use crate::lz4::Compressed;
+    use crate::lz4::SingleBlock;
+    use crate::material::{BlendMode, Material, MaterialFlags};
+    use crate::mesh::{IndexType, Lod, MeshEncoding, VertexFormat};
+    use crate::migrate::{can_migrate, v10, v4, v5, v6, v7, v8, v9};
+    use crate::{load_bf_from_bytes, LoadError, BF_MAGIC, BF_VERSION};
+    use bincode::Options;
+
+    fn v4_material() -> v4::Material {
+        v4::Material {
+            blend_mode: BlendMode::Masked,
+            albedo_color: [1.0, 0.5, 0.25],
+            roughness: 0.3,
+            metallic: 1.0,
+            alpha_cutoff: 0.5,
+            opacity: 0.75,
+            albedo_map: None,
+            normal_map: None,
+            displacement_map: None,
+            roughness_map: None,
+            ao_map: None,
+            metallic_map: None,
+            opacity_map: None,
+        }
+    }
+
+    fn v8_material() -> v8::Material {
+        let m = Material::default();
+        v8::Material {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            albedo_map: None,
+            normal_map: None,
+            displacement_map: None,
+            roughness_map: None,
+            ao_map: None,
+            metallic_map: None,
+            opacity_map: None,
+        }
+    }
+
+    fn v4_bytes(data: v4::Data) -> Vec<u8> {
+        let file = v4::File {
+            magic: BF_MAGIC,
+            version: 4,
+            data,
+        };
+        bincode_options().serialize(&file).unwrap()
+    }
+
+    #[test]
+    fn supported_versions() {
+        assert!(!can_migrate(3));
+        assert!(can_migrate(4));
+        assert!(can_migrate(5));
+        assert!(can_migrate(6));
+        assert!(can_migrate(7));
+        assert!(can_migrate(8));
+        assert!(can_migrate(9));
+        assert!(can_migrate(10));
+        assert!(!can_migrate(BF_VERSION));
+    }
+
+    fn assert_migrated(data: v4::Data) {
+        let file = load_bf_from_bytes(&v4_bytes(data)).unwrap();
+        assert_eq!(file.version(), BF_VERSION);
+
+        let material = file.try_to_material().unwrap();
+        assert_eq!(
+            material,
+            Material {
+                blend_mode: BlendMode::Masked,
+                albedo_color: [1.0, 0.5, 0.25],
+                roughness: 0.3,
+                metallic: 1.0,
+                alpha_cutoff: 0.5,
+                opacity: 0.75,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn migrates_v4_material() {
+        assert_migrated(v4::Data::Uncompressed(v4::Container::Material(
+            v4_material(),
+        )));
+        assert_migrated(v4::Data::Compressed(SingleBlock(v4::Container::Material(
+            v4_material(),
+        ))));
+    }
+
+    #[test]
+    fn migrates_v5_compressed() {
+        let file = v5::File {
+            magic: BF_MAGIC,
+            version: 5,
+            data: v5::Data::Compressed(SingleBlock(v6::Container::Material(v8_material()))),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let file = load_bf_from_bytes(&bytes).unwrap();
+        assert_eq!(file.version(), BF_VERSION);
+        assert!(file.is_compressed());
+        assert_eq!(file.try_to_material().unwrap(), Material::default());
+    }
+
+    #[test]
+    fn migrates_v6_mesh() {
+        let mesh = v6::Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vec![1; 16],
+            index_type: IndexType::U16,
+            index_data: vec![0; 6],
+        };
+        let file = v6::File {
+            magic: BF_MAGIC,
+            version: 6,
+            data: v6::Data::Compressed(Compressed::new(v6::Container::Mesh(mesh))),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let mesh = load_bf_from_bytes(&bytes).unwrap().try_to_mesh().unwrap();
+        assert_eq!(mesh.vertex_data, vec![1; 16]);
+        assert_eq!(mesh.index_data, vec![0; 6]);
+        assert!(mesh.lods.is_empty());
+    }
+
+    #[test]
+    fn migrates_v7_mesh() {
+        let lod = Lod {
+            first_index: 0,
+            index_count: 3,
+            min_coverage: 0.0,
+        };
+        let mesh = v7::Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vec![1; 16],
+            index_type: IndexType::U16,
+            index_data: vec![0; 6],
+            lods: vec![lod],
+        };
+        let file = v7::File {
+            magic: BF_MAGIC,
+            version: 7,
+            data: v7::Data::Uncompressed(v7::Container::Mesh(mesh)),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let mesh = load_bf_from_bytes(&bytes).unwrap().try_to_mesh().unwrap();
+        assert_eq!(mesh.vertex_data, vec![1; 16]);
+        assert_eq!(mesh.lods, vec![lod]);
+        assert_eq!(mesh.encoding, MeshEncoding::None);
+    }
+
+    #[test]
+    fn migrates_v8_material_and_mesh() {
+        let mut material = v8_material();
+        material.sss = 1.0;
+        let file = v8::File {
+            magic: BF_MAGIC,
+            version: 8,
+            data: v8::Data::Compressed(Compressed::new(v8::Container::Material(material))),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let migrated = load_bf_from_bytes(&bytes)
+            .unwrap()
+            .try_to_material()
+            .unwrap();
+        assert_eq!(migrated.sss, 1.0);
+        assert_eq!(migrated.flags, MaterialFlags::empty());
+
+        let mesh = v8::Mesh {
+            vertex_format: VertexFormat::Position,
+            vertex_data: vec![1; 16].into(),
+            index_type: IndexType::U16,
+            index_data: vec![0; 6].into(),
+            lods: vec![],
+            encoding: MeshEncoding::None,
+        };
+        let file = v8::File {
+            magic: BF_MAGIC,
+            version: 8,
+            data: v8::Data::Uncompressed(v8::Container::Mesh(mesh)),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let mesh = load_bf_from_bytes(&bytes).unwrap().try_to_mesh().unwrap();
+        assert_eq!(mesh.vertex_data, vec![1; 16]);
+        assert!(mesh.stiffness.is_none());
+    }
+
+    #[test]
+    fn migrates_v9_material() {
+        let m = v8_material();
+        let material = v9::Material {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            albedo_map: None,
+            normal_map: None,
+            displacement_map: None,
+            roughness_map: None,
+            ao_map: None,
+            metallic_map: None,
+            opacity_map: None,
+            flags: MaterialFlags::DOUBLE_SIDED,
+        };
+        let file = v9::File {
+            magic: BF_MAGIC,
+            version: 9,
+            data: v9::Data::Compressed(Compressed::new(v9::Container::Material(material))),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let migrated = load_bf_from_bytes(&bytes)
+            .unwrap()
+            .try_to_material()
+            .unwrap();
+        assert_eq!(migrated.flags, MaterialFlags::DOUBLE_SIDED);
+        assert_eq!(migrated.emissive_color, [0.0; 3]);
+        assert_eq!(migrated.emissive_map, None);
+    }
+
+    #[test]
+    fn migrates_v10_material() {
+        let m = v8_material();
+        let material = v10::Material {
+            blend_mode: m.blend_mode,
+            albedo_color: m.albedo_color,
+            roughness: m.roughness,
+            metallic: m.metallic,
+            alpha_cutoff: m.alpha_cutoff,
+            ior: m.ior,
+            opacity: m.opacity,
+            sss: m.sss,
+            emissive_color: [2.0, 1.0, 0.0],
+            albedo_map: None,
+            normal_map: None,
+            displacement_map: None,
+            roughness_map: None,
+            ao_map: None,
+            metallic_map: None,
+            opacity_map: None,
+            emissive_map: None,
+            flags: MaterialFlags::FOLIAGE,
+        };
+        let file = v10::File {
+            magic: BF_MAGIC,
+            version: 10,
+            data: v10::Data::Uncompressed(v10::Container::Material(material)),
+        };
+        let bytes = bincode_options().serialize(&file).unwrap();
+
+        let migrated = load_bf_from_bytes(&bytes)
+            .unwrap()
+            .try_to_material()
+            .unwrap();
+        assert_eq!(migrated.flags, MaterialFlags::FOLIAGE);
+        assert_eq!(migrated.emissive_color, [2.0, 1.0, 0.0]);
+        assert_eq!(migrated.clear_coat, 0.0);
+        assert_eq!(migrated.anisotropy, 0.0);
+    }
+
+    #[test]
+    fn rejects_too_old_version() {
+        let mut bytes = v4_bytes(v4::Data::Uncompressed(v4::Container::Material(
+            v4_material(),
+        )));
+        bytes[3] = 3; // version byte follows the varint encoded magic
+
+        match load_bf_from_bytes(&bytes) {
+            Err(LoadError::UnsupportedVersion { file: 3, .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
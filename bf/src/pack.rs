@@ -0,0 +1,233 @@
+//! Archives bundling many BF files into one file (packs).
+//!
+//! Reading thousands of small files is slow (especially on HDDs), so assets
+//! can be shipped in packs instead. A pack starts with an index that maps
+//! uuid of each asset to the range of bytes of its BF file, followed by the
+//! BF files themselves. The files are stored as they are, so each of them is
+//! still compressed (or not) on its own and is loaded by `load_bf_from_bytes`.
+//!
+//! ```text
+//! magic (u16), version (u8), entries (Vec<PackEntry>), data of all files
+//! ```
+//!
+//! Offsets of entries are relative to the end of the index.
+
+use crate::layout::bincode_options;
+use crate::{Codec, DataHeader, LoadError};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use uuid::Uuid;
+
+/// Two bytes magic that is present at the start of every pack ("BP").
+pub const PACK_MAGIC: u16 = 20546;
+
+/// Version of the pack format this library reads and writes.
+pub const PACK_VERSION: u8 = 1;
+
+/// Location of one BF file in the pack.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackEntry {
+    pub uuid: Uuid,
+    /// Offset of the file relative to the end of the index.
+    pub offset: u64,
+    /// Length of the file in bytes.
+    pub length: u64,
+    /// Codec the data of the file is compressed with (`None` if it is not).
+    pub codec: Option<Codec>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackIndex {
+    magic: u16,
+    version: u8,
+    entries: Vec<PackEntry>,
+}
+
+/// Writes a pack containing specified BF `files` (serialized BF files with
+/// uuids of their assets) into the `writer`.
+pub fn write_pack<W, B>(writer: &mut W, files: &[(Uuid, B)]) -> Result<(), LoadError>
+where
+    W: Write,
+    B: AsRef<[u8]>,
+{
+    let mut entries = Vec::with_capacity(files.len());
+    let mut offset = 0;
+
+    for (uuid, bytes) in files {
+        let bytes = bytes.as_ref();
+        let header = DataHeader::read(bytes)?;
+
+        entries.push(PackEntry {
+            uuid: *uuid,
+            offset,
+            length: bytes.len() as u64,
+            codec: header.codec(),
+        });
+        offset += bytes.len() as u64;
+    }
+
+    let index = PackIndex {
+        magic: PACK_MAGIC,
+        version: PACK_VERSION,
+        entries,
+    };
+
+    bincode_options()
+        .serialize_into(&mut *writer, &index)
+        .map_err(LoadError::BincodeError)?;
+
+    for (_, bytes) in files {
+        writer
+            .write_all(bytes.as_ref())
+            .map_err(|e| LoadError::BincodeError(e.into()))?;
+    }
+
+    Ok(())
+}
+
+/// Pack opened for reading. The bytes of the pack (eg. memory-mapped file) are
+/// owned by the pack and files are borrowed from them.
+pub struct Pack<B> {
+    bytes: B,
+    entries: HashMap<Uuid, PackEntry>,
+    /// Offset of the first file in `bytes` (the length of the index).
+    data_start: usize,
+}
+
+impl<B: AsRef<[u8]>> Pack<B> {
+    /// Reads the index of the pack stored in `bytes` and verifies that all
+    /// files are inside of the pack.
+    pub fn new(bytes: B) -> Result<Self, LoadError> {
+        let data = bytes.as_ref();
+
+        if data.len() < 2 {
+            return Err(LoadError::FileTooShort);
+        }
+
+        let index: PackIndex = bincode_options()
+            .allow_trailing_bytes()
+            .deserialize(data)
+            .map_err(LoadError::BincodeError)?;
+
+        if index.magic != PACK_MAGIC {
+            return Err(LoadError::InvalidMagic);
+        }
+
+        if index.version != PACK_VERSION {
+            return Err(LoadError::UnsupportedVersion {
+                library: PACK_VERSION,
+                file: index.version,
+            });
+        }
+
+        let data_start = bincode_options()
+            .serialized_size(&index)
+            .map_err(LoadError::BincodeError)? as usize;
+        let data_len = (data.len() - data_start) as u64;
+
+        if index.entries.iter().any(|x| {
+            x.offset
+                .checked_add(x.length)
+                .map_or(true, |end| end > data_len)
+        }) {
+            return Err(LoadError::FileTooShort);
+        }
+
+        Ok(Self {
+            entries: index.entries.into_iter().map(|x| (x.uuid, x)).collect(),
+            bytes,
+            data_start,
+        })
+    }
+
+    /// Returns the bytes of the BF file of asset with specified uuid.
+    pub fn get(&self, uuid: &Uuid) -> Option<&[u8]> {
+        let entry = self.entries.get(uuid)?;
+        let start = self.data_start + entry.offset as usize;
+
+        Some(&self.bytes.as_ref()[start..start + entry.length as usize])
+    }
+
+    /// Returns whether the pack contains asset with specified uuid.
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.entries.contains_key(uuid)
+    }
+
+    /// Returns iterator over entries of all files in the pack (in no
+    /// particular order).
+    pub fn entries(&self) -> impl Iterator<Item = &PackEntry> {
+        self.entries.values()
+    }
+
+    /// Returns the number of files in the pack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the pack contains no files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Material;
+    use crate::pack::{write_pack, Pack};
+    use crate::tree::Tree;
+    use crate::{load_bf_from_bytes, save_bf_to_bytes, Codec, Container, File, LoadError};
+    use uuid::Uuid;
+
+    #[test]
+    fn reads_written_files() {
+        let tree = save_bf_to_bytes(&File::create_compressed(Container::Tree(Tree::new())));
+        let material = File::create_uncompressed(Container::Material(Material::default()));
+        let material = save_bf_to_bytes(&material);
+        let files = vec![
+            (Uuid::from_u128(1), tree.unwrap()),
+            (Uuid::from_u128(2), material.unwrap()),
+        ];
+
+        let mut bytes = vec![];
+        write_pack(&mut bytes, &files).unwrap();
+        let pack = Pack::new(bytes).unwrap();
+
+        assert_eq!(pack.len(), 2);
+        assert!(!pack.contains(&Uuid::from_u128(3)));
+        for (uuid, file) in files.iter() {
+            assert_eq!(pack.get(uuid).unwrap(), file.as_slice());
+        }
+
+        let mut codecs = pack
+            .entries()
+            .map(|x| (x.uuid, x.codec))
+            .collect::<Vec<_>>();
+        codecs.sort_by_key(|x| x.0);
+        assert_eq!(
+            codecs,
+            vec![
+                (Uuid::from_u128(1), Some(Codec::Lz4)),
+                (Uuid::from_u128(2), None)
+            ]
+        );
+
+        let tree = load_bf_from_bytes(pack.get(&Uuid::from_u128(1)).unwrap());
+        assert!(tree.unwrap().try_to_tree().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_packs() {
+        let files = vec![(Uuid::from_u128(1), vec![1, 2, 3])];
+        assert!(write_pack(&mut vec![], &files).is_err());
+
+        let file = save_bf_to_bytes(&File::create_compressed(Container::Tree(Tree::new())));
+        let mut bytes = vec![];
+        write_pack(&mut bytes, &[(Uuid::from_u128(1), file.unwrap())]).unwrap();
+
+        bytes.pop();
+        assert!(matches!(Pack::new(bytes), Err(LoadError::FileTooShort)));
+        assert!(Pack::new(vec![1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Table of new UUIDs of re-keyed assets.
+//!
+//! When assets get new UUIDs (eg. `asset-server migrate-uuids` after changing
+//! the derivation of UUIDs), scenes and other projects may still reference the
+//! old ones. The table maps such legacy UUIDs to the current ones, so the
+//! references can be resolved without editing them.
+//!
+//! The table is stored as a text file with one `<old uuid>=<new uuid>` line
+//! per asset (the same format as the `input2uuid` file).
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+/// Mapping of legacy UUIDs to current UUIDs of assets.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UuidRemap {
+    map: HashMap<Uuid, Uuid>,
+}
+
+impl UuidRemap {
+    /// Parses the table from lines of `<old uuid>=<new uuid>`. Empty lines
+    /// and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = HashMap::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse = |x: &str| {
+                Uuid::parse_str(x.trim())
+                    .map_err(|e| format!("invalid uuid {:?} on line {}: {}", x, idx + 1, e))
+            };
+            let (old, new) = line
+                .split_once('=')
+                .ok_or_else(|| format!("missing = character on line {}", idx + 1))?;
+
+            map.insert(parse(old)?, parse(new)?);
+        }
+
+        Ok(Self { map })
+    }
+
+    /// Returns the current UUID of the asset with (possibly legacy) `uuid`.
+    pub fn resolve(&self, uuid: Uuid) -> Uuid {
+        self.map.get(&uuid).copied().unwrap_or(uuid)
+    }
+
+    /// Adds UUIDs of assets that were re-keyed again. Legacy UUIDs that mapped
+    /// to an old UUID of `rekeyed` assets now map to its new UUID.
+    pub fn extend(&mut self, rekeyed: &HashMap<Uuid, Uuid>) {
+        for new in self.map.values_mut() {
+            if let Some(t) = rekeyed.get(new) {
+                *new = *t;
+            }
+        }
+        self.map.extend(rekeyed.iter().map(|(k, v)| (*k, *v)));
+        // an asset may have got its legacy uuid back
+        self.map.retain(|k, v| k != v);
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl Display for UuidRemap {
+    /// Formats the table as lines sorted by the legacy UUIDs.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut entries = self.map.iter().collect::<Vec<_>>();
+        entries.sort();
+
+        for (old, new) in entries {
+            writeln!(f, "{}={}", old.to_hyphenated(), new.to_hyphenated())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remap::UuidRemap;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn uuid(x: u128) -> Uuid {
+        Uuid::from_u128(x)
+    }
+
+    #[test]
+    fn parse_and_format_roundtrip() {
+        let text = "# legacy uuids\n\
+            00000000-0000-0000-0000-000000000001=00000000-0000-0000-0000-00000000000a\n\
+            \n\
+            00000000-0000-0000-0000-000000000002 = 00000000-0000-0000-0000-00000000000b\n";
+
+        let remap = UuidRemap::parse(text).unwrap();
+        assert_eq!(remap.len(), 2);
+        assert_eq!(remap.resolve(uuid(1)), uuid(10));
+        assert_eq!(remap.resolve(uuid(3)), uuid(3));
+        assert_eq!(UuidRemap::parse(&remap.to_string()).unwrap(), remap);
+
+        assert!(UuidRemap::parse("00000000-0000-0000-0000-000000000001").is_err());
+        assert!(UuidRemap::parse("a=b").is_err());
+    }
+
+    #[test]
+    fn extend_follows_chains() {
+        let mut remap = UuidRemap::default();
+        remap.extend(
+            &vec![(uuid(1), uuid(2)), (uuid(5), uuid(6))]
+                .into_iter()
+                .collect(),
+        );
+        let rekeyed: HashMap<_, _> = vec![(uuid(2), uuid(3)), (uuid(6), uuid(5))]
+            .into_iter()
+            .collect();
+        remap.extend(&rekeyed);
+
+        assert_eq!(remap.resolve(uuid(1)), uuid(3));
+        assert_eq!(remap.resolve(uuid(2)), uuid(3));
+        // the asset got its first uuid back
+        assert_eq!(remap.resolve(uuid(5)), uuid(5));
+        assert_eq!(remap.resolve(uuid(6)), uuid(5));
+        assert_eq!(remap.len(), 3);
+    }
+}
@@ -0,0 +1,107 @@
+//! Keyframed sequences (camera paths) used for benchmark flythroughs, trailers
+//! and scripted cutscenes.
+//!
+//! Each animated property of the camera has its own track, so position,
+//! view direction and field of view may be keyed at different times. Objects
+//! of the scene are shown and hidden by visibility keys.
+
+use serde::{Deserialize, Serialize};
+
+/// Value of a property at specified time (in seconds from the start of the sequence).
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Key<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+/// Keys of a single property sorted by time.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Track<T> {
+    pub keys: Vec<Key<T>>,
+}
+
+impl<T> Track<T> {
+    /// Inserts a key into the track while keeping the keys sorted by time.
+    pub fn insert(&mut self, time: f32, value: T) {
+        let idx = self.keys.partition_point(|k| k.time <= time);
+        self.keys.insert(idx, Key { time, value });
+    }
+
+    /// Returns time of the last key in the track (or zero if it is empty).
+    pub fn end(&self) -> f32 {
+        self.keys.last().map_or(0.0, |k| k.time)
+    }
+}
+
+/// Shows or hides an object at specified time. The object is identified by
+/// its index in the scene the sequence was created for.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct VisibilityKey {
+    pub time: f32,
+    pub object: u32,
+    pub visible: bool,
+}
+
+/// Sequence of keyframed camera movement and object visibility changes.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Sequence {
+    /// Position of the camera.
+    pub position: Track<[f32; 3]>,
+    /// Rotation of the camera as the (normalized) direction it looks in.
+    pub forward: Track<[f32; 3]>,
+    /// Vertical field of view of the camera in degrees.
+    pub fov: Track<f32>,
+    /// Visibility changes sorted by time.
+    pub visibility: Vec<VisibilityKey>,
+}
+
+impl Sequence {
+    /// Returns the time of the last key in the sequence.
+    pub fn duration(&self) -> f32 {
+        let visibility = self.visibility.last().map_or(0.0, |k| k.time);
+
+        self.position
+            .end()
+            .max(self.forward.end())
+            .max(self.fov.end())
+            .max(visibility)
+    }
+
+    /// Inserts a visibility change while keeping the changes sorted by time.
+    pub fn set_visible(&mut self, time: f32, object: u32, visible: bool) {
+        let idx = self.visibility.partition_point(|k| k.time <= time);
+        self.visibility.insert(
+            idx,
+            VisibilityKey {
+                time,
+                object,
+                visible,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequence::Sequence;
+
+    #[test]
+    fn keys_are_sorted() {
+        let mut seq = Sequence::default();
+        seq.fov.insert(2.0, 60.0);
+        seq.fov.insert(0.0, 90.0);
+        seq.fov.insert(1.0, 75.0);
+        seq.set_visible(3.0, 1, false);
+        seq.set_visible(0.5, 0, true);
+
+        let times = seq.fov.keys.iter().map(|k| k.time).collect::<Vec<_>>();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+        assert_eq!(seq.visibility[0].object, 0);
+        assert_eq!(seq.duration(), 3.0);
+    }
+
+    #[test]
+    fn empty_sequence_has_zero_duration() {
+        assert_eq!(Sequence::default().duration(), 0.0);
+    }
+}
@@ -0,0 +1,105 @@
+//! Shaders compiled to SPIR-V ahead of time.
+//!
+//! One shader asset is compiled from a single GLSL source into multiple
+//! variants that differ in preprocessor definitions (permutations).
+
+use serde::{Deserialize, Serialize};
+
+/// Pipeline stage the shader is compiled for.
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// Determines the stage from file name of the shader source using the naming
+    /// conventions of the engine (`vs_` / `fs_` / `cs_` prefix, `_vert` / `_frag` /
+    /// `_comp` suffix or `.vert` / `.frag` / `.comp` extension).
+    pub fn from_file_name(file_name: &str) -> Option<ShaderStage> {
+        let name = file_name.to_lowercase();
+        let stem = name.split('.').next().unwrap_or_default();
+        let has = |prefix: &str, suffix: &str| {
+            stem.starts_with(prefix) || stem.ends_with(suffix) || name.ends_with(suffix)
+        };
+
+        if has("vs_", "vert") {
+            Some(ShaderStage::Vertex)
+        } else if has("fs_", "frag") {
+            Some(ShaderStage::Fragment)
+        } else if has("cs_", "comp") {
+            Some(ShaderStage::Compute)
+        } else {
+            None
+        }
+    }
+}
+
+/// SPIR-V code of the shader compiled with a set of preprocessor definitions.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Variant {
+    /// Definitions in form of `NAME` or `NAME=VALUE`.
+    pub defines: Vec<String>,
+    /// SPIR-V words in little-endian byte order.
+    pub spirv: Vec<u8>,
+}
+
+/// Shader with all its compiled variants.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Shader {
+    /// Name of the shader (file name of the source without extension).
+    pub name: String,
+    pub stage: ShaderStage,
+    pub variants: Vec<Variant>,
+}
+
+impl Shader {
+    /// Returns the variant compiled with exactly the specified definitions
+    /// (in any order) or `None` if there is no such variant.
+    pub fn variant(&self, defines: &[&str]) -> Option<&Variant> {
+        self.variants.iter().find(|v| {
+            v.defines.len() == defines.len()
+                && defines.iter().all(|d| v.defines.iter().any(|x| x == d))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shader::{Shader, ShaderStage, Variant};
+
+    fn variant(defines: &[&str]) -> Variant {
+        Variant {
+            defines: defines.iter().map(|x| x.to_string()).collect(),
+            spirv: vec![],
+        }
+    }
+
+    #[test]
+    fn finds_variants() {
+        let shader = Shader {
+            name: "fs_test".into(),
+            stage: ShaderStage::Fragment,
+            variants: vec![variant(&[]), variant(&["MASKED", "LIGHTS=4"])],
+        };
+
+        assert_eq!(shader.variant(&[]), Some(&shader.variants[0]));
+        assert_eq!(
+            shader.variant(&["LIGHTS=4", "MASKED"]),
+            Some(&shader.variants[1])
+        );
+        assert_eq!(shader.variant(&["MASKED"]), None);
+        assert_eq!(shader.variant(&["MASKED", "LIGHTS=8"]), None);
+    }
+
+    #[test]
+    fn stage_from_file_name() {
+        let stage = ShaderStage::from_file_name;
+
+        assert_eq!(stage("vs_passtrough.glsl"), Some(ShaderStage::Vertex));
+        assert_eq!(stage("sky_hosek_frag.glsl"), Some(ShaderStage::Fragment));
+        assert_eq!(stage("blur.comp"), Some(ShaderStage::Compute));
+        assert_eq!(stage("inc_brdf.glsl"), None);
+    }
+}
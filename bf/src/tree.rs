@@ -70,7 +70,7 @@ pub enum Component {
 /// Single entry in the `Tree`. Each node can have multiple (or zero)
 /// children nodes. It also contains a `Vec` of `Component`s attached
 /// to this node.
-#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Node {
     children: Vec<Handle>,
     components: Vec<Component>,
@@ -0,0 +1,302 @@
+//! Very large textures split into pages (tiles) for virtual texturing.
+//!
+//! The texture is square with a power of two size. Each mip-map level is split
+//! into square pages of `page_size` pixels which are stored one after another, so
+//! a single page can be read without touching the rest of the texture. When the
+//! file is uncompressed and memory-mapped (see [`load_bf_shared`](../fn.load_bf_shared.html)),
+//! only the pages that are read are loaded from the disk.
+//!
+//! Pages contain `border` pixels of their neighbours on each side, so they can be
+//! filtered independently of the neighbouring pages (which are usually not next
+//! to each other in the page cache on the GPU). Levels smaller than one page are
+//! not stored.
+
+use crate::blob::Blob;
+use crate::image::Format;
+use serde::{Deserialize, Serialize};
+
+/// Identifier of one page of a [`VirtualTexture`](struct.VirtualTexture.html).
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug)]
+pub struct PageId {
+    pub mip: u8,
+    /// Column of the page in the mip-map level.
+    pub x: u32,
+    /// Row of the page in the mip-map level.
+    pub y: u32,
+}
+
+impl PageId {
+    pub fn new(mip: u8, x: u32, y: u32) -> Self {
+        Self { mip, x, y }
+    }
+
+    /// Returns the page of the next (smaller) level that contains this page.
+    pub fn parent(self) -> PageId {
+        PageId::new(self.mip + 1, self.x / 2, self.y / 2)
+    }
+}
+
+/// Errors that can happen when creating a [`VirtualTexture`](struct.VirtualTexture.html).
+#[derive(Debug, PartialEq)]
+pub enum VirtualTextureError {
+    /// Pages of block compressed formats can't be created from pixels.
+    CompressedFormat(Format),
+    /// Mip-maps can only be generated for formats with 8-bit channels.
+    UnsupportedFormat(Format),
+    /// Size of the texture and of the page must be powers of two and the page
+    /// must not be bigger than the texture.
+    InvalidSize { size: u32, page_size: u32 },
+    /// Border must not be wider than the page.
+    InvalidBorder(u32),
+    /// Length of the pixel data does not match the size of the texture.
+    InvalidDataLength { expected: usize, actual: usize },
+}
+
+/// Square texture with mip-maps stored as pages of the same size.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VirtualTexture {
+    pub format: Format,
+    /// Width and height of the biggest mip-map level in pixels.
+    pub size: u32,
+    /// Width and height of one page in pixels (without the border).
+    pub page_size: u32,
+    /// Number of pixels of the neighbouring pages on each side of the page.
+    pub border: u32,
+    /// Bytes of all pages ordered from the biggest level to the smallest one,
+    /// rows of pages from the top. All pages have `page_bytes()` bytes.
+    pub page_data: Blob,
+}
+
+impl VirtualTexture {
+    /// Returns the number of stored mip-map levels. The smallest level is one page.
+    pub fn mip_count(&self) -> u32 {
+        (self.size / self.page_size).trailing_zeros() + 1
+    }
+
+    /// Returns the number of pages in one row (and column) of the level `mip`.
+    pub fn pages_per_side(&self, mip: u32) -> u32 {
+        (self.size / self.page_size) >> mip
+    }
+
+    /// Returns the total number of pages in all levels.
+    pub fn page_count(&self) -> usize {
+        (0..self.mip_count())
+            .map(|mip| (self.pages_per_side(mip) as usize).pow(2))
+            .sum()
+    }
+
+    /// Returns width and height of a page including the border on both sides.
+    pub fn padded_page_size(&self) -> u32 {
+        self.page_size + 2 * self.border
+    }
+
+    /// Returns the number of bytes of one page.
+    pub fn page_bytes(&self) -> usize {
+        (self.padded_page_size() as usize).pow(2) * self.format.bits_per_pixel() as usize / 8
+    }
+
+    /// Returns the index of the `page` in `page_data` or `None` if there is
+    /// no such page.
+    pub fn page_index(&self, page: PageId) -> Option<usize> {
+        let mip = page.mip as u32;
+        if mip >= self.mip_count() {
+            return None;
+        }
+
+        let side = self.pages_per_side(mip);
+        if page.x >= side || page.y >= side {
+            return None;
+        }
+
+        let previous = (0..mip)
+            .map(|m| (self.pages_per_side(m) as usize).pow(2))
+            .sum::<usize>();
+        Some(previous + page.y as usize * side as usize + page.x as usize)
+    }
+
+    /// Returns bytes of the `page` (rows of padded pixels) or `None` if there
+    /// is no such page or it is not stored.
+    pub fn page(&self, page: PageId) -> Option<&[u8]> {
+        let start = self.page_index(page)? * self.page_bytes();
+        self.page_data.get(start..start + self.page_bytes())
+    }
+
+    /// Splits the texture of `size` x `size` `pixels` (rows of pixels in uncompressed
+    /// `format`) into pages. Smaller mip-map levels are generated with a box filter.
+    pub fn from_pixels(
+        format: Format,
+        size: u32,
+        page_size: u32,
+        border: u32,
+        pixels: &[u8],
+    ) -> Result<Self, VirtualTextureError> {
+        if format.compressed() {
+            return Err(VirtualTextureError::CompressedFormat(format));
+        }
+        if format.bits_per_pixel() != format.channels() as u16 * 8 {
+            return Err(VirtualTextureError::UnsupportedFormat(format));
+        }
+        if !size.is_power_of_two() || !page_size.is_power_of_two() || page_size > size {
+            return Err(VirtualTextureError::InvalidSize { size, page_size });
+        }
+        if border > page_size {
+            return Err(VirtualTextureError::InvalidBorder(border));
+        }
+
+        let pixel_bytes = format.bits_per_pixel() as usize / 8;
+        let expected = (size as usize).pow(2) * pixel_bytes;
+        if pixels.len() != expected {
+            return Err(VirtualTextureError::InvalidDataLength {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+
+        let mut texture = VirtualTexture {
+            format,
+            size,
+            page_size,
+            border,
+            page_data: Blob::default(),
+        };
+        let mut page_data = Vec::with_capacity(texture.page_count() * texture.page_bytes());
+
+        let mut level = pixels.to_vec();
+        for mip in 0..texture.mip_count() {
+            let level_size = size >> mip;
+            if mip > 0 {
+                level = downsample(&level, level_size * 2, pixel_bytes);
+            }
+
+            let side = texture.pages_per_side(mip);
+            for page_y in 0..side {
+                for page_x in 0..side {
+                    // pixels of the border outside of the level are clamped to its edge
+                    let coord = |page: u32, i: u32| {
+                        let x = (page * page_size + i) as i64 - border as i64;
+                        x.max(0).min(level_size as i64 - 1) as usize
+                    };
+
+                    for i in 0..texture.padded_page_size() {
+                        let y = coord(page_y, i);
+                        for j in 0..texture.padded_page_size() {
+                            let x = coord(page_x, j);
+                            let start = (y * level_size as usize + x) * pixel_bytes;
+                            page_data.extend_from_slice(&level[start..start + pixel_bytes]);
+                        }
+                    }
+                }
+            }
+        }
+
+        texture.page_data = page_data.into();
+        Ok(texture)
+    }
+}
+
+/// Returns the level of half the `size` with each pixel being an average
+/// of 2x2 pixels of the `level`.
+fn downsample(level: &[u8], size: u32, pixel_bytes: usize) -> Vec<u8> {
+    let size = size as usize;
+    let half = size / 2;
+    let mut result = Vec::with_capacity(half * half * pixel_bytes);
+
+    for y in 0..half {
+        for x in 0..half {
+            for c in 0..pixel_bytes {
+                let texel = |x: usize, y: usize| level[(y * size + x) * pixel_bytes + c] as u32;
+                let sum = texel(2 * x, 2 * y)
+                    + texel(2 * x + 1, 2 * y)
+                    + texel(2 * x, 2 * y + 1)
+                    + texel(2 * x + 1, 2 * y + 1);
+                result.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::Format;
+    use crate::virtual_texture::{PageId, VirtualTexture, VirtualTextureError};
+
+    fn texture(size: u32, page_size: u32, border: u32) -> VirtualTexture {
+        let pixels = (0..size * size).map(|x| x as u8).collect::<Vec<_>>();
+        VirtualTexture::from_pixels(Format::R8, size, page_size, border, &pixels).unwrap()
+    }
+
+    #[test]
+    fn counts_pages() {
+        let texture = texture(16, 4, 0);
+
+        assert_eq!(texture.mip_count(), 3);
+        assert_eq!(texture.pages_per_side(0), 4);
+        assert_eq!(texture.pages_per_side(2), 1);
+        assert_eq!(texture.page_count(), 16 + 4 + 1);
+        assert_eq!(texture.page_data.len(), 21 * 16);
+        assert_eq!(texture.page_index(PageId::new(1, 1, 1)), Some(16 + 3));
+        assert_eq!(texture.page_index(PageId::new(1, 2, 0)), None);
+        assert_eq!(texture.page_index(PageId::new(3, 0, 0)), None);
+        assert_eq!(PageId::new(0, 3, 2).parent(), PageId::new(1, 1, 1));
+    }
+
+    #[test]
+    fn pages_contain_clamped_borders() {
+        let texture = texture(4, 2, 1);
+
+        // pixel values are their indices in the 4x4 level
+        assert_eq!(texture.page_bytes(), 16);
+        assert_eq!(
+            texture.page(PageId::new(0, 0, 0)).unwrap(),
+            [0, 0, 1, 2, 0, 0, 1, 2, 4, 4, 5, 6, 8, 8, 9, 10]
+        );
+        assert_eq!(
+            texture.page(PageId::new(0, 1, 1)).unwrap(),
+            [5, 6, 7, 7, 9, 10, 11, 11, 13, 14, 15, 15, 13, 14, 15, 15]
+        );
+    }
+
+    #[test]
+    fn generates_smaller_levels() {
+        let texture = texture(4, 2, 0);
+
+        // averages of 2x2 pixels
+        assert_eq!(texture.page(PageId::new(1, 0, 0)).unwrap(), [3, 5, 11, 13]);
+    }
+
+    #[test]
+    fn rejects_invalid_textures() {
+        let from_pixels = |format, size, page_size, border, len| {
+            VirtualTexture::from_pixels(format, size, page_size, border, &vec![0; len]).unwrap_err()
+        };
+
+        assert_eq!(
+            from_pixels(Format::BC7, 8, 4, 0, 64),
+            VirtualTextureError::CompressedFormat(Format::BC7)
+        );
+        assert_eq!(
+            from_pixels(Format::R16, 8, 4, 0, 128),
+            VirtualTextureError::UnsupportedFormat(Format::R16)
+        );
+        assert_eq!(
+            from_pixels(Format::R8, 12, 4, 0, 144),
+            VirtualTextureError::InvalidSize {
+                size: 12,
+                page_size: 4
+            }
+        );
+        assert_eq!(
+            from_pixels(Format::R8, 8, 4, 5, 64),
+            VirtualTextureError::InvalidBorder(5)
+        );
+        assert_eq!(
+            from_pixels(Format::Rgba8, 8, 4, 0, 64),
+            VirtualTextureError::InvalidDataLength {
+                expected: 256,
+                actual: 64
+            }
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Helper module for integration of `zstd` compressed parts of struct into
+//! `serde`. It is an alternative to the [`lz4`](../lz4/index.html) module that
+//! is slower, but produces noticeably smaller files (especially for meshes).
+//!
+//! The codec is available only when the `bf` crate is built with the `zstd`
+//! feature. Without it files compressed with `zstd` can't be written nor read
+//! and (de)serialization of [`Compressed`](struct.Compressed.html) fails.
+//!
+//! Data is split into chunks of [`lz4::CHUNK_SIZE`](../lz4/constant.CHUNK_SIZE.html)
+//! bytes that are compressed independently into `zstd` frames (which store
+//! their uncompressed size), so they can be (de)compressed in parallel.
+
+use crate::layout::bincode_options;
+#[cfg(feature = "zstd")]
+use crate::lz4::CHUNK_SIZE;
+use bincode::Options;
+#[cfg(feature = "zstd")]
+use rayon::prelude::*;
+use serde::de::{DeserializeOwned, Error as DeError};
+use serde::ser::Error as SerError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::{ByteBuf, Bytes};
+use std::io;
+
+/// Compression level used when no level is specified.
+pub const DEFAULT_LEVEL: i32 = 19;
+
+/// Wrapper struct that causes the wrapped type to be converted to bytes
+/// using `bincode` crate and compressed using `zstd` when this struct is
+/// serialized. The reverse happens when this struct is deserialized.
+#[derive(Copy, Clone, Debug)]
+pub struct Compressed<T>(T, i32);
+
+impl<T> Compressed<T> {
+    /// Creates a new `Compressed` wrapper with specified data and default
+    /// compression level.
+    pub fn new(t: T) -> Self {
+        Self::new_with_level(t, DEFAULT_LEVEL)
+    }
+
+    /// Creates a new `Compressed` wrapper with specified data and `zstd`
+    /// compression level (`1` to `22`).
+    pub fn new_with_level(t: T, level: i32) -> Self {
+        Self(t, level)
+    }
+
+    /// Converts this struct into `T`.
+    pub fn into(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Serialize for Compressed<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        let serialized = bincode_options()
+            .serialize(&self.0)
+            .map_err(S::Error::custom)?;
+        let chunks = compress_chunks(&serialized, self.1).map_err(S::Error::custom)?;
+
+        serializer.collect_seq(chunks.iter().map(|x| Bytes::new(x)))
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Compressed<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let chunks: Vec<ByteBuf> = Deserialize::deserialize(deserializer)?;
+        let decompressed = decompress_chunks(&chunks).map_err(D::Error::custom)?;
+        let deserialized: T = bincode_options()
+            .deserialize(decompressed.as_slice())
+            .map_err(D::Error::custom)?;
+
+        Ok(Compressed(deserialized, DEFAULT_LEVEL))
+    }
+}
+
+/// Splits the `bytes` into chunks and compresses each of them (in parallel)
+/// into a `zstd` frame.
+#[cfg(feature = "zstd")]
+fn compress_chunks(bytes: &[u8], level: i32) -> io::Result<Vec<Vec<u8>>> {
+    bytes
+        .par_chunks(CHUNK_SIZE)
+        .map(|chunk| zstd::block::compress(chunk, level))
+        .collect()
+}
+
+/// Decompresses chunks created by `compress_chunks` (in parallel) and returns
+/// the concatenated data.
+#[cfg(feature = "zstd")]
+fn decompress_chunks(chunks: &[ByteBuf]) -> io::Result<Vec<u8>> {
+    let decompressed = chunks
+        .par_iter()
+        .map(|chunk| zstd::block::decompress(chunk, CHUNK_SIZE))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(decompressed.concat())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_chunks(_: &[u8], _: i32) -> io::Result<Vec<Vec<u8>>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_chunks(_: &[ByteBuf]) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "bf was built without `zstd` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zstd::Compressed;
+    use bincode::{deserialize, serialize};
+    #[cfg(not(feature = "zstd"))]
+    use serde_bytes::ByteBuf;
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_roundtrip() {
+        let data: Vec<u8> = (0..100_000u32).map(|x| (x % 7) as u8).collect();
+
+        let serialized = serialize(&Compressed::new_with_level(data.clone(), 3)).unwrap();
+        assert!(serialized.len() < data.len() / 10);
+
+        let deserialized: Compressed<Vec<u8>> = deserialize(serialized.as_slice()).unwrap();
+        assert_eq!(deserialized.into(), data);
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn test_unsupported() {
+        assert!(serialize(&Compressed::new(vec![1u8, 2, 3])).is_err());
+        let chunks = serialize(&vec![ByteBuf::from(vec![1u8, 2, 3])]).unwrap();
+        assert!(deserialize::<Compressed<Vec<u8>>>(&chunks).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Export of images into standard DDS and KTX2 containers, so they can be
+//! inspected by other tools (RenderDoc, NVIDIA Texture Tools, ...). Payloads of
+//! mip-maps are copied without any conversion.
+//!
+//! BF stores mip-maps of compressed formats down to 1x1 pixel with sizes computed
+//! from bits per pixel, so levels smaller than one block don't have the size the
+//! containers expect. The exported mip-chain ends before the first such level.
+
+use bf::image::{Format, Image};
+use std::str::FromStr;
+
+/// Container the image is exported to.
+#[derive(Copy, Clone, Debug)]
+pub enum ExportFormat {
+    Dds,
+    Ktx2,
+}
+
+impl ExportFormat {
+    /// Returns the extension of files of this container.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Dds => "dds",
+            ExportFormat::Ktx2 => "ktx2",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dds" => Ok(ExportFormat::Dds),
+            "ktx2" => Ok(ExportFormat::Ktx2),
+            _ => Err(format!(
+                "invalid export format {:?} (expected dds or ktx2)",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the size in bytes of one block (4x4 pixels) of compressed formats
+/// or one pixel of uncompressed formats.
+fn block_size(format: Format) -> usize {
+    match format {
+        Format::Dxt1 | Format::SrgbDxt1 => 8,
+        Format::Dxt3 | Format::SrgbDxt3 | Format::Dxt5 | Format::SrgbDxt5 => 16,
+        Format::BC5 | Format::BC6H | Format::BC7 | Format::SrgbBC7 => 16,
+        Format::R8 => 1,
+        Format::Rgb8 | Format::Srgb8 => 3,
+        Format::Rgba8 | Format::Srgb8A8 => 4,
+        Format::R16 => 2,
+        Format::Rg16 => 4,
+        Format::Rgba16F => 8,
+        Format::Rgba32F => 16,
+    }
+}
+
+/// Returns the size of a mip-map of `width` x `height` pixels as expected by
+/// the containers.
+fn level_size(format: Format, width: usize, height: usize) -> usize {
+    if format.compressed() {
+        ((width + 3) / 4).max(1) * ((height + 3) / 4).max(1) * block_size(format)
+    } else {
+        width * height * block_size(format)
+    }
+}
+
+/// Returns data of mip-maps of the `image` that can be stored in the containers.
+fn levels(image: &Image) -> Vec<&[u8]> {
+    // dimensions reported by `MipMaps` are wrong for odd sizes, so they are computed here
+    let (width, height) = (image.width as usize, image.height as usize);
+    image
+        .mipmaps()
+        .enumerate()
+        .take_while(|(idx, x)| {
+            let (width, height) = (width >> idx, height >> idx);
+            width > 0 && height > 0 && x.data.len() == level_size(image.format, width, height)
+        })
+        .map(|(_, x)| x.data)
+        .collect()
+}
+
+/// Serializes the `image` into the `container`.
+pub fn export(image: &Image, container: ExportFormat) -> Vec<u8> {
+    match container {
+        ExportFormat::Dds => export_dds(image),
+        ExportFormat::Ktx2 => export_ktx2(image),
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+// flags of the dds header and pixel format
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Returns the `DXGI_FORMAT` of the `format` or `None` for formats that
+/// have no DXGI equivalent.
+fn dxgi_format(format: Format) -> Option<u32> {
+    match format {
+        Format::Dxt1 => Some(71),
+        Format::SrgbDxt1 => Some(72),
+        Format::Dxt3 => Some(74),
+        Format::SrgbDxt3 => Some(75),
+        Format::Dxt5 => Some(77),
+        Format::SrgbDxt5 => Some(78),
+        Format::BC5 => Some(83),
+        Format::BC6H => Some(95),
+        Format::BC7 => Some(98),
+        Format::SrgbBC7 => Some(99),
+        Format::Rgba8 => Some(28),
+        Format::Srgb8A8 => Some(29),
+        Format::R8 => Some(61),
+        Format::R16 => Some(56),
+        Format::Rg16 => Some(35),
+        Format::Rgba16F => Some(10),
+        Format::Rgba32F => Some(2),
+        Format::Rgb8 | Format::Srgb8 => None,
+    }
+}
+
+/// Writes the image into DDS container. Formats known to DXGI use the `DX10`
+/// extended header, 24-bit RGB formats are stored as legacy RGB pixel format
+/// (which has no notion of sRGB).
+fn export_dds(image: &Image) -> Vec<u8> {
+    let levels = levels(image);
+    let format = image.format;
+    let (width, height) = (image.width as u32, image.height as u32);
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+    let pitch = if format.compressed() {
+        flags |= DDSD_LINEARSIZE;
+        level_size(format, width as usize, height as usize) as u32
+    } else {
+        flags |= DDSD_PITCH;
+        width * block_size(format) as u32
+    };
+    let mut caps = DDSCAPS_TEXTURE;
+    if levels.len() > 1 {
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"DDS ");
+    put_u32(&mut out, 124);
+    put_u32(&mut out, flags);
+    put_u32(&mut out, height);
+    put_u32(&mut out, width);
+    put_u32(&mut out, pitch);
+    put_u32(&mut out, 0); // depth
+    put_u32(&mut out, levels.len() as u32);
+    out.extend_from_slice(&[0; 11 * 4]);
+
+    // pixel format
+    put_u32(&mut out, 32);
+    match dxgi_format(format) {
+        Some(_) => {
+            put_u32(&mut out, DDPF_FOURCC);
+            out.extend_from_slice(b"DX10");
+            out.extend_from_slice(&[0; 5 * 4]);
+        }
+        None => {
+            put_u32(&mut out, DDPF_RGB);
+            put_u32(&mut out, 0);
+            put_u32(&mut out, 24);
+            put_u32(&mut out, 0x0000ff);
+            put_u32(&mut out, 0x00ff00);
+            put_u32(&mut out, 0xff0000);
+            put_u32(&mut out, 0);
+        }
+    }
+
+    put_u32(&mut out, caps);
+    out.extend_from_slice(&[0; 4 * 4]); // caps2, caps3, caps4, reserved
+
+    if let Some(dxgi) = dxgi_format(format) {
+        put_u32(&mut out, dxgi);
+        put_u32(&mut out, D3D10_RESOURCE_DIMENSION_TEXTURE2D);
+        put_u32(&mut out, 0); // misc flags
+        put_u32(&mut out, 1); // array size
+        put_u32(&mut out, 0); // alpha mode
+    }
+
+    for level in levels {
+        out.extend_from_slice(level);
+    }
+    out
+}
+
+// constants of the khronos data format descriptor
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_MODEL_BC2: u8 = 129;
+const KHR_DF_MODEL_BC3: u8 = 130;
+const KHR_DF_MODEL_BC5: u8 = 132;
+const KHR_DF_MODEL_BC6H: u8 = 133;
+const KHR_DF_MODEL_BC7: u8 = 134;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_CHANNEL_ALPHA: u8 = 15;
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u8 = 0x10;
+const KHR_DF_SAMPLE_DATATYPE_SIGNED: u8 = 0x40;
+const KHR_DF_SAMPLE_DATATYPE_FLOAT: u8 = 0x80;
+
+/// Returns the `VkFormat` of the `format`.
+fn vk_format(format: Format) -> u32 {
+    match format {
+        Format::R8 => 9,
+        Format::Rgb8 => 23,
+        Format::Srgb8 => 29,
+        Format::Rgba8 => 37,
+        Format::Srgb8A8 => 43,
+        Format::Dxt1 => 131,
+        Format::SrgbDxt1 => 132,
+        Format::Dxt3 => 135,
+        Format::SrgbDxt3 => 136,
+        Format::Dxt5 => 137,
+        Format::SrgbDxt5 => 138,
+        Format::BC5 => 141,
+        Format::BC6H => 143,
+        Format::BC7 => 145,
+        Format::SrgbBC7 => 146,
+        Format::R16 => 70,
+        Format::Rg16 => 77,
+        Format::Rgba16F => 97,
+        Format::Rgba32F => 109,
+    }
+}
+
+fn is_srgb(format: Format) -> bool {
+    matches!(
+        format,
+        Format::SrgbDxt1
+            | Format::SrgbDxt3
+            | Format::SrgbDxt5
+            | Format::Srgb8
+            | Format::Srgb8A8
+            | Format::SrgbBC7
+    )
+}
+
+/// Sample of the data format descriptor: channel with qualifiers, bit offset,
+/// bit length and the range of values.
+struct Sample(u8, u16, u16, u32, u32);
+
+/// Returns the data format descriptor (basic descriptor block) of the `format`.
+fn data_format_descriptor(format: Format) -> Vec<u8> {
+    let srgb = is_srgb(format);
+    // alpha is always linear, even in sRGB formats
+    let alpha = if srgb {
+        KHR_DF_CHANNEL_ALPHA | KHR_DF_SAMPLE_DATATYPE_LINEAR
+    } else {
+        KHR_DF_CHANNEL_ALPHA
+    };
+
+    // signed floats in range -1.0 to 1.0
+    let float = |channel, offset, length| {
+        let qualifiers = KHR_DF_SAMPLE_DATATYPE_FLOAT | KHR_DF_SAMPLE_DATATYPE_SIGNED;
+        Sample(
+            channel | qualifiers,
+            offset,
+            length,
+            0xBF80_0000,
+            0x3F80_0000,
+        )
+    };
+
+    let (model, samples) = match format {
+        Format::Dxt1 | Format::SrgbDxt1 => (KHR_DF_MODEL_BC1A, vec![Sample(0, 0, 64, 0, !0)]),
+        Format::Dxt3 | Format::SrgbDxt3 => (
+            KHR_DF_MODEL_BC2,
+            vec![Sample(alpha, 0, 64, 0, !0), Sample(0, 64, 64, 0, !0)],
+        ),
+        Format::Dxt5 | Format::SrgbDxt5 => (
+            KHR_DF_MODEL_BC3,
+            vec![Sample(alpha, 0, 64, 0, !0), Sample(0, 64, 64, 0, !0)],
+        ),
+        Format::BC5 => (
+            KHR_DF_MODEL_BC5,
+            vec![Sample(0, 0, 64, 0, !0), Sample(1, 64, 64, 0, !0)],
+        ),
+        // unsigned half floats in range 0.0 to 1.0
+        Format::BC6H => (
+            KHR_DF_MODEL_BC6H,
+            vec![Sample(KHR_DF_SAMPLE_DATATYPE_FLOAT, 0, 128, 0, 0x3F80_0000)],
+        ),
+        Format::BC7 | Format::SrgbBC7 => (KHR_DF_MODEL_BC7, vec![Sample(0, 0, 128, 0, !0)]),
+        Format::R8 => (KHR_DF_MODEL_RGBSDA, vec![Sample(0, 0, 8, 0, 255)]),
+        Format::Rgb8 | Format::Srgb8 => (
+            KHR_DF_MODEL_RGBSDA,
+            vec![
+                Sample(0, 0, 8, 0, 255),
+                Sample(1, 8, 8, 0, 255),
+                Sample(2, 16, 8, 0, 255),
+            ],
+        ),
+        Format::Rgba8 | Format::Srgb8A8 => (
+            KHR_DF_MODEL_RGBSDA,
+            vec![
+                Sample(0, 0, 8, 0, 255),
+                Sample(1, 8, 8, 0, 255),
+                Sample(2, 16, 8, 0, 255),
+                Sample(alpha, 24, 8, 0, 255),
+            ],
+        ),
+        Format::R16 => (KHR_DF_MODEL_RGBSDA, vec![Sample(0, 0, 16, 0, 65535)]),
+        Format::Rg16 => (
+            KHR_DF_MODEL_RGBSDA,
+            vec![Sample(0, 0, 16, 0, 65535), Sample(1, 16, 16, 0, 65535)],
+        ),
+        Format::Rgba16F => (
+            KHR_DF_MODEL_RGBSDA,
+            vec![
+                float(0, 0, 16),
+                float(1, 16, 16),
+                float(2, 32, 16),
+                float(KHR_DF_CHANNEL_ALPHA, 48, 16),
+            ],
+        ),
+        Format::Rgba32F => (
+            KHR_DF_MODEL_RGBSDA,
+            vec![
+                float(0, 0, 32),
+                float(1, 32, 32),
+                float(2, 64, 32),
+                float(KHR_DF_CHANNEL_ALPHA, 96, 32),
+            ],
+        ),
+    };
+
+    let descriptor_size = 24 + 16 * samples.len();
+    let mut out = Vec::new();
+    put_u32(&mut out, (descriptor_size + 4) as u32); // total size
+    put_u32(&mut out, 0); // vendor (khronos) and descriptor type (basic)
+    out.extend_from_slice(&2u16.to_le_bytes()); // version
+    out.extend_from_slice(&(descriptor_size as u16).to_le_bytes());
+    out.push(model);
+    out.push(KHR_DF_PRIMARIES_BT709);
+    out.push(if srgb {
+        KHR_DF_TRANSFER_SRGB
+    } else {
+        KHR_DF_TRANSFER_LINEAR
+    });
+    out.push(0); // flags (straight alpha)
+
+    // texel block dimensions (minus one) and bytes per plane
+    if format.compressed() {
+        out.extend_from_slice(&[3, 3, 0, 0]);
+    } else {
+        out.extend_from_slice(&[0, 0, 0, 0]);
+    }
+    out.push(block_size(format) as u8);
+    out.extend_from_slice(&[0; 7]);
+
+    for Sample(channel, offset, length, lower, upper) in samples {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.push((length - 1) as u8);
+        out.push(channel);
+        out.extend_from_slice(&[0; 4]); // sample position
+        put_u32(&mut out, lower);
+        put_u32(&mut out, upper);
+    }
+    out
+}
+
+fn align(out: &mut Vec<u8>, alignment: usize) {
+    while out.len() % alignment != 0 {
+        out.push(0);
+    }
+}
+
+/// Writes the image into KTX2 container without supercompression. Data of
+/// mip-maps is stored from the smallest to the largest as the format requires.
+fn export_ktx2(image: &Image) -> Vec<u8> {
+    const HEADER_SIZE: usize = 80;
+    const LEVEL_INDEX_ENTRY: usize = 24;
+
+    let levels = levels(image);
+    let format = image.format;
+    let dfd = data_format_descriptor(format);
+    let mut kvd = Vec::new();
+    let writer = b"KTXwriter\0bfinfo\0";
+    put_u32(&mut kvd, writer.len() as u32);
+    kvd.extend_from_slice(writer);
+    align(&mut kvd, 4);
+
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_ENTRY * levels.len();
+    let kvd_offset = dfd_offset + dfd.len();
+
+    // levels are aligned to the least common multiple of the block size and 4
+    let block = block_size(format);
+    let alignment = (1..=4).map(|x| block * x).find(|x| x % 4 == 0).unwrap();
+
+    let mut data = Vec::new();
+    let mut offsets = vec![0; levels.len()];
+    let mut data_offset = kvd_offset + kvd.len();
+    while data_offset % alignment != 0 {
+        data_offset += 1;
+    }
+    for (idx, level) in levels.iter().enumerate().rev() {
+        align(&mut data, alignment);
+        offsets[idx] = data_offset + data.len();
+        data.extend_from_slice(level);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&[
+        0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+    ]);
+    put_u32(&mut out, vk_format(format));
+    put_u32(&mut out, 1); // type size
+    put_u32(&mut out, image.width as u32);
+    put_u32(&mut out, image.height as u32);
+    put_u32(&mut out, 0); // depth
+    put_u32(&mut out, 0); // layers
+    put_u32(&mut out, 1); // faces
+    put_u32(&mut out, levels.len() as u32);
+    put_u32(&mut out, 0); // no supercompression
+
+    put_u32(&mut out, dfd_offset as u32);
+    put_u32(&mut out, dfd.len() as u32);
+    put_u32(&mut out, kvd_offset as u32);
+    put_u32(&mut out, kvd.len() as u32);
+    put_u64(&mut out, 0); // supercompression global data
+    put_u64(&mut out, 0);
+
+    for (level, offset) in levels.iter().zip(offsets) {
+        put_u64(&mut out, offset as u64);
+        put_u64(&mut out, level.len() as u64);
+        put_u64(&mut out, level.len() as u64);
+    }
+
+    out.extend_from_slice(&dfd);
+    out.extend_from_slice(&kvd);
+    align(&mut out, alignment);
+    out.extend_from_slice(&data);
+    out
+}
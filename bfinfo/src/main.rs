@@ -1,13 +1,23 @@
-use bf::image::{Format, Image};
+use crate::export::{export, ExportFormat};
+use bf::image::{f16_to_f32, Format, Image};
 use bf::material::Material;
 use bf::mesh::Mesh;
+use bf::sequence::Sequence;
+use bf::shader::Shader;
 use bf::tree::Tree;
+use bf::virtual_texture::VirtualTexture;
 use bf::{load_bf_from_bytes, Container};
 use image::dxt::{DXTVariant, DxtDecoder};
 use image::{DynamicImage, ImageBuffer, ImageDecoder, ImageFormat};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+mod export;
+mod preview;
+
+/// Size of the mesh preview in pixels.
+const PREVIEW_SIZE: u32 = 256;
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "bfinfo")]
 struct Opt {
@@ -17,13 +27,22 @@ struct Opt {
     #[structopt(short, long)]
     unpack_normal_map: bool,
 
+    /// Renders a thumbnail of the mesh into `preview.png`.
+    #[structopt(short, long)]
+    preview: bool,
+
+    /// Writes the image with all its mip-maps into `<input>.dds` or `<input>.ktx2`
+    /// in the current directory. Compressed data is copied without re-encoding.
+    #[structopt(short, long)]
+    export: Option<ExportFormat>,
+
     #[structopt(short, long, parse(from_os_str))]
     input: PathBuf,
 }
 
 fn main() {
     let opt = Opt::from_args();
-    let bytes = std::fs::read(opt.input).unwrap();
+    let bytes = std::fs::read(&opt.input).unwrap();
     let file = load_bf_from_bytes(bytes.as_slice()).unwrap();
 
     println!("magic={:.4} (ok)", file.magic());
@@ -33,14 +52,24 @@ fn main() {
     let container = file.into_container();
 
     match container {
-        Container::Image(i) => handle_image(i, opt.dump, opt.unpack_normal_map),
-        Container::Mesh(g) => handle_mesh(g, opt.dump),
+        Container::Image(i) => {
+            handle_image(&i, opt.dump, opt.unpack_normal_map);
+            if let Some(format) = opt.export {
+                let path = opt.input.with_extension(format.extension());
+                let path = path.file_name().unwrap();
+                std::fs::write(path, export(&i, format)).expect("cannot write exported file");
+            }
+        }
+        Container::Mesh(g) => handle_mesh(g, opt.dump, opt.preview),
         Container::Material(m) => handle_material(m),
         Container::Tree(t) => handle_tree(t),
+        Container::Sequence(s) => handle_sequence(s),
+        Container::Shader(s) => handle_shader(s),
+        Container::VirtualTexture(t) => handle_virtual_texture(t),
     }
 }
 
-fn handle_image(image: Image, dump: bool, unpack: bool) {
+fn handle_image(image: &Image, dump: bool, unpack: bool) {
     println!("image");
     println!("format={:?}", image.format);
     println!("mipmaps={:.4}", image.mipmap_count());
@@ -66,18 +95,22 @@ fn handle_image(image: Image, dump: bool, unpack: bool) {
                 raw
             };
 
-            let raw = match image.format {
-                Format::SrgbDxt1 | Format::Dxt1 => dxt(DXTVariant::DXT1),
-                Format::SrgbDxt3 | Format::Dxt3 => dxt(DXTVariant::DXT3),
-                Format::SrgbDxt5 | Format::Dxt5 => dxt(DXTVariant::DXT5),
-                _ => Vec::from(mipmap.data),
+            let (raw, channels) = match image.format {
+                Format::SrgbDxt1 | Format::Dxt1 => (dxt(DXTVariant::DXT1), 3),
+                Format::SrgbDxt3 | Format::Dxt3 => (dxt(DXTVariant::DXT3), 4),
+                Format::SrgbDxt5 | Format::Dxt5 => (dxt(DXTVariant::DXT5), 4),
+                Format::BC5 => (decode_bc5(mipmap.data, mipmap.width, mipmap.height), 3),
+                Format::R16 => (unorm16_to_8(mipmap.data, 1, 1), 1),
+                Format::Rg16 => (unorm16_to_8(mipmap.data, 2, 3), 3),
+                Format::Rgba16F | Format::Rgba32F => (float_to_8(image.format, mipmap.data), 4),
+                _ => (Vec::from(mipmap.data), image.format.channels()),
             };
 
-            let img = match image.format.channels() {
+            let img = match channels {
                 1 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, raw).unwrap()),
                 3 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, raw).unwrap()),
                 4 => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, raw).unwrap()),
-                _ => panic!("cannot dump with {:.4} channels", image.format.channels()),
+                _ => panic!("cannot dump with {:.4} channels", channels),
             };
 
             // unpack dxt5nm
@@ -116,24 +149,123 @@ fn handle_image(image: Image, dump: bool, unpack: bool) {
     }
 }
 
-fn handle_mesh(geo: Mesh, dump: bool) {
+/// Decodes single BC4 block (8 bytes) into 16 single channel values.
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let e0 = block[0] as u32;
+    let e1 = block[1] as u32;
+
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 255];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = ((7 - i) * e0 + i * e1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = ((5 - i) * e0 + i * e1) / 5;
+        }
+    }
+
+    // 16 3-bit indices are stored in remaining 48 bits
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let bits = u64::from_le_bytes(bits);
+
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((bits >> (3 * i)) & 7) as usize] as u8;
+    }
+    values
+}
+
+/// Converts pixels with `channels` 16-bit channels to pixels with `output_channels`
+/// 8-bit channels (missing channels are zero).
+fn unorm16_to_8(data: &[u8], channels: usize, output_channels: usize) -> Vec<u8> {
+    data.chunks_exact(channels * 2)
+        .flat_map(|px| (0..output_channels).map(move |c| px.get(c * 2 + 1).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Converts floating point channels to 8-bit channels, values outside of the 0.0
+/// to 1.0 range are clamped.
+fn float_to_8(format: Format, data: &[u8]) -> Vec<u8> {
+    let values = match format {
+        Format::Rgba16F => data
+            .chunks_exact(2)
+            .map(|x| f16_to_f32(u16::from_le_bytes([x[0], x[1]])))
+            .collect::<Vec<_>>(),
+        _ => data
+            .chunks_exact(4)
+            .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect(),
+    };
+    values
+        .iter()
+        .map(|x| (x.max(0.0).min(1.0) * 255.0).round() as u8)
+        .collect()
+}
+
+/// Decodes BC5 compressed data into RGB pixels. The blue channel (Z) of the
+/// normal is reconstructed from the red (X) and green (Y) channels.
+fn decode_bc5(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut raw = vec![0; width * height * 3];
+    let blocks_x = (width + 3) / 4;
+
+    for (idx, block) in data.chunks_exact(16).enumerate() {
+        let red = decode_bc4_block(&block[0..8]);
+        let green = decode_bc4_block(&block[8..16]);
+
+        for i in 0..16 {
+            let x = (idx % blocks_x) * 4 + i % 4;
+            let y = (idx / blocks_x) * 4 + i / 4;
+            if x >= width || y >= height {
+                continue;
+            }
+
+            let nx = red[i] as f32 / 255.0 * 2.0 - 1.0;
+            let ny = green[i] as f32 / 255.0 * 2.0 - 1.0;
+            let nz = (1.0 - (nx * nx + ny * ny).min(1.0)).sqrt();
+
+            let offset = (y * width + x) * 3;
+            raw[offset] = red[i];
+            raw[offset + 1] = green[i];
+            raw[offset + 2] = ((nz * 0.5 + 0.5) * 255.0) as u8;
+        }
+    }
+
+    raw
+}
+
+fn handle_mesh(geo: Mesh, dump: bool, preview: bool) {
     println!("mesh");
 
+    let (vertex_data, index_data) = geo.decoded_data().expect("cannot decode mesh data");
+
     println!("vertex_data_format={:?}", geo.vertex_format);
     println!("index_type={:?}", geo.index_type);
+    println!("encoding={:?}", geo.encoding);
     println!(
         "vertices={:.4}",
-        geo.vertex_data.len() / geo.vertex_format.size_of_one_vertex()
+        vertex_data.len() / geo.vertex_format.size_of_one_vertex()
     );
     println!(
         "indices={:.4}",
-        geo.index_data.len() / geo.index_type.size_of_one_index()
+        index_data.len() / geo.index_type.size_of_one_index()
     );
+    for (idx, lod) in geo.lods.iter().enumerate() {
+        println!(
+            "lod{}=first_index={} index_count={} min_coverage={}",
+            idx, lod.first_index, lod.index_count, lod.min_coverage
+        );
+    }
+
+    if preview {
+        preview::render_mesh(&geo, PREVIEW_SIZE)
+            .save_with_format("preview.png", ImageFormat::Png)
+            .expect("cannot save preview");
+    }
 
     if dump {
-        for (idx, vertex) in geo
-            .vertex_data
-            .as_slice()
+        for (idx, vertex) in vertex_data
             .chunks(geo.vertex_format.size_of_one_vertex())
             .enumerate()
         {
@@ -184,3 +316,47 @@ fn handle_tree(tree: Tree) {
 
     println!("{:?}", tree);
 }
+
+fn handle_sequence(sequence: Sequence) {
+    println!("sequence");
+    println!("duration={:.2}s", sequence.duration());
+    println!("position_keys={}", sequence.position.keys.len());
+    println!("forward_keys={}", sequence.forward.keys.len());
+    println!("fov_keys={}", sequence.fov.keys.len());
+    println!("visibility_keys={}", sequence.visibility.len());
+}
+
+fn handle_shader(shader: Shader) {
+    println!("shader");
+    println!("name={}", shader.name);
+    println!("stage={:?}", shader.stage);
+    println!("variants={}", shader.variants.len());
+
+    for variant in shader.variants.iter() {
+        println!(
+            "variant defines={:?} size={}",
+            variant.defines.join(","),
+            variant.spirv.len()
+        );
+    }
+}
+
+fn handle_virtual_texture(texture: VirtualTexture) {
+    println!("virtual_texture");
+    println!("format={:?}", texture.format);
+    println!("size={}", texture.size);
+    println!("page_size={}", texture.page_size);
+    println!("border={}", texture.border);
+    println!("page_bytes={}", texture.page_bytes());
+
+    for mip in 0..texture.mip_count() {
+        let pages = texture.pages_per_side(mip);
+        println!(
+            "mipmap level={} size={} pages={}x{}",
+            mip,
+            texture.size >> mip,
+            pages,
+            pages
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Software rasterizer of mesh thumbnails.
+//!
+//! The mesh is rendered with orthographic projection from the usual turntable
+//! angle (from the front-right and above), flat shaded with a single light
+//! and a light gray default material. The background is transparent.
+
+use bf::mesh::{IndexType, Mesh};
+use image::{Rgba, RgbaImage};
+
+/// Horizontal angle of the camera around the mesh.
+const AZIMUTH: f32 = 45.0;
+/// Vertical angle of the camera above the horizon.
+const ELEVATION: f32 = 30.0;
+/// Fraction of the image covered by the bounding sphere of the mesh.
+const FILL: f32 = 0.9;
+const ALBEDO: [f32; 3] = [0.8, 0.8, 0.8];
+const AMBIENT: f32 = 0.15;
+
+type Vec3 = [f32; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: Vec3) -> Vec3 {
+    let len = dot(a, a).sqrt();
+    if len == 0.0 {
+        a
+    } else {
+        [a[0] / len, a[1] / len, a[2] / len]
+    }
+}
+
+/// Returns positions of all vertices in decoded `vertex_data` of the `mesh`. Position is the first
+/// attribute in all vertex formats.
+fn positions(mesh: &Mesh, vertex_data: &[u8]) -> Vec<Vec3> {
+    let f = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+
+    vertex_data
+        .chunks_exact(mesh.vertex_format.size_of_one_vertex())
+        .map(|v| [f(&v[0..4]), f(&v[4..8]), f(&v[8..12])])
+        .collect()
+}
+
+/// Returns indices of the most detailed level of detail of the `mesh`.
+fn indices(mesh: &Mesh, index_data: &[u8]) -> Vec<usize> {
+    let all = match mesh.index_type {
+        IndexType::U16 => index_data
+            .chunks_exact(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]) as usize)
+            .collect::<Vec<_>>(),
+        IndexType::U32 => index_data
+            .chunks_exact(4)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize)
+            .collect::<Vec<_>>(),
+    };
+
+    match mesh.lods.first() {
+        None => all,
+        Some(lod) => {
+            let first = (lod.first_index as usize).min(all.len());
+            let last = (first + lod.index_count as usize).min(all.len());
+            all[first..last].to_vec()
+        }
+    }
+}
+
+/// Renders a `size`x`size` pixels thumbnail of the `mesh`.
+pub fn render_mesh(mesh: &Mesh, size: u32) -> RgbaImage {
+    let mut image = RgbaImage::new(size, size);
+    let (vertex_data, index_data) = mesh.decoded_data().expect("cannot decode mesh data");
+    let positions = positions(mesh, &vertex_data);
+    let indices = indices(mesh, &index_data);
+
+    if positions.is_empty() {
+        return image;
+    }
+
+    // fit the bounding sphere of the bounding box into the image
+    let mut min = positions[0];
+    let mut max = positions[0];
+    for p in positions.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    let center = [
+        (min[0] + max[0]) * 0.5,
+        (min[1] + max[1]) * 0.5,
+        (min[2] + max[2]) * 0.5,
+    ];
+    let radius = (dot(sub(max, min), sub(max, min)).sqrt() * 0.5).max(f32::EPSILON);
+
+    let (azimuth, elevation) = (AZIMUTH.to_radians(), ELEVATION.to_radians());
+    let to_camera = [
+        elevation.cos() * azimuth.cos(),
+        elevation.sin(),
+        elevation.cos() * azimuth.sin(),
+    ];
+    let right = normalize(cross([0.0, 1.0, 0.0], to_camera));
+    let up = cross(to_camera, right);
+    let light = normalize([
+        to_camera[0] + right[0] * -0.5 + up[0],
+        to_camera[1] + right[1] * -0.5 + up[1],
+        to_camera[2] + right[2] * -0.5 + up[2],
+    ]);
+
+    // vertices in pixels, z is the distance towards the camera
+    let half = size as f32 * 0.5;
+    let scale = half * FILL / radius;
+    let projected = positions
+        .iter()
+        .map(|p| {
+            let d = sub(*p, center);
+            [
+                half + dot(d, right) * scale,
+                half - dot(d, up) * scale,
+                dot(d, to_camera),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut depth = vec![f32::NEG_INFINITY; (size * size) as usize];
+
+    for triangle in indices.chunks_exact(3) {
+        if triangle.iter().any(|x| *x >= positions.len()) {
+            continue;
+        }
+
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+        let normal = normalize(cross(
+            sub(positions[b], positions[a]),
+            sub(positions[c], positions[a]),
+        ));
+        // winding of imported meshes is not consistent, so shade both sides
+        let intensity = AMBIENT + (1.0 - AMBIENT) * dot(normal, light).abs();
+        let color = Rgba([
+            (ALBEDO[0] * intensity * 255.0) as u8,
+            (ALBEDO[1] * intensity * 255.0) as u8,
+            (ALBEDO[2] * intensity * 255.0) as u8,
+            255,
+        ]);
+
+        let (p0, p1, p2) = (projected[a], projected[b], projected[c]);
+        let area = (p1[0] - p0[0]) * (p2[1] - p0[1]) - (p1[1] - p0[1]) * (p2[0] - p0[0]);
+        if area.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let x0 = p0[0].min(p1[0]).min(p2[0]).floor().max(0.0) as u32;
+        let x1 = (p0[0].max(p1[0]).max(p2[0]).ceil() as u32).min(size);
+        let y0 = p0[1].min(p1[1]).min(p2[1]).floor().max(0.0) as u32;
+        let y1 = (p0[1].max(p1[1]).max(p2[1]).ceil() as u32).min(size);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: Vec3, b: Vec3| {
+                    ((b[0] - a[0]) * (py - a[1]) - (b[1] - a[1]) * (px - a[0])) / area
+                };
+                let (w0, w1, w2) = (edge(p1, p2), edge(p2, p0), edge(p0, p1));
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let z = w0 * p0[2] + w1 * p1[2] + w2 * p2[2];
+                let idx = (y * size + x) as usize;
+                if z > depth[idx] {
+                    depth[idx] = z;
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    image
+}
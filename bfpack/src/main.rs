@@ -0,0 +1,89 @@
+use bf::pack::{write_pack, Pack};
+use bf::uuid::Uuid;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "bfpack")]
+struct Opt {
+    /// Directory with `<uuid>.bf` files to pack (or the pack file with `--list`).
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+
+    /// Output pack file (.bfpack). Defaults to the input directory with `.bfpack` extension.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Lists files of the input pack instead of creating a new one.
+    #[structopt(short, long)]
+    list: bool,
+}
+
+/// Memory maps the file at specified path.
+fn map(path: &Path) -> Mmap {
+    let file = File::open(path).expect("cannot open file");
+    // safety: files are not expected to be modified while they are being packed
+    unsafe { Mmap::map(&file) }.expect("cannot map file")
+}
+
+/// Returns all files in the `dir` named `<uuid>.bf` sorted by the uuid.
+fn find_files(dir: &Path) -> Vec<(Uuid, PathBuf)> {
+    let mut files = std::fs::read_dir(dir)
+        .expect("cannot read input directory")
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().map_or(false, |e| e == "bf"))
+        .filter_map(|x| {
+            let uuid = Uuid::parse_str(x.file_stem()?.to_str()?).ok()?;
+            Some((uuid, x))
+        })
+        .collect::<Vec<_>>();
+
+    files.sort_by_key(|x| x.0);
+    files
+}
+
+fn list(path: &Path) {
+    let pack = Pack::new(map(path)).expect("cannot read pack");
+    let mut entries = pack.entries().collect::<Vec<_>>();
+    entries.sort_by_key(|x| x.offset);
+
+    println!("files={}", pack.len());
+    for entry in entries {
+        println!(
+            "{} offset={} length={} codec={:?}",
+            entry.uuid.to_hyphenated(),
+            entry.offset,
+            entry.length,
+            entry.codec
+        );
+    }
+}
+
+fn pack(input: &Path, output: &Path) {
+    let files = find_files(input)
+        .into_iter()
+        .map(|(uuid, path)| (uuid, map(&path)))
+        .collect::<Vec<_>>();
+
+    let mut writer = BufWriter::new(File::create(output).expect("cannot create output file"));
+    write_pack(&mut writer, &files).expect("cannot write pack");
+
+    let size = files.iter().map(|x| x.1.len()).sum::<usize>();
+    println!("files={} size={}", files.len(), size);
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    if opt.list {
+        list(&opt.input);
+    } else {
+        let input = opt.input;
+        let output = opt.output.unwrap_or_else(|| input.with_extension("bfpack"));
+        pack(&input, &output);
+    }
+}
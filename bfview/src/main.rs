@@ -0,0 +1,469 @@
+//! Viewer of a single mesh, material or image asset.
+//!
+//! Usage: `bfview <file.bf | uuid | lookup name> [--set <name>=<value>]...`
+//!
+//! Meshes are shown with a neutral gray material, materials on a sphere and
+//! images on a plane. The camera orbits around the asset (mouse rotates, wheel
+//! zooms). Lighting and the debug view can be changed in the viewer window
+//! while the GUI is enabled (`toggle_gui` action). Turntable lighting replaces
+//! the lights with the standardized rig of the material review mode and spins
+//! the asset.
+
+use bf::uuid::Uuid;
+use cgmath::{vec3, Deg, EuclideanSpace, InnerSpace, Point3, Vector3};
+use core::settings::{parse_set_args, Overrides};
+use engine::assets::{lookup, Content};
+use engine::camera::{ActiveCamera, OrthographicCamera, PerspectiveCamera};
+use engine::config::{config_file, ENV_PREFIX};
+use engine::egui;
+use engine::movement::{Movement, OrbitMovement};
+use engine::render::debug_view::DebugView;
+use engine::render::hierarchy::Hierarchy;
+use engine::render::object::Object;
+use engine::render::objects::Objects;
+use engine::render::transform::Transform;
+use engine::render::ubo::{AmbientLight, DirectionalLight, Wind};
+use engine::render::vertex::NormalMappedVertex;
+use engine::resources::material::{create_default_fallback_maps, StaticMaterial};
+use engine::resources::mesh::{create_mesh_cached, DynamicIndexedMesh, IndexedMesh};
+use engine::{Engine, Game, GameState, RendererConfiguration};
+use log::{info, warn, LevelFilter};
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, ImmutableBuffer};
+use vulkano::sync::GpuFuture;
+use winit::event_loop::EventLoop;
+
+#[cfg(windows)]
+use winit::platform::windows::EventLoopExtWindows;
+
+#[cfg(unix)]
+use winit::platform::unix::EventLoopExtUnix;
+
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Asset specified on the command line.
+enum Source {
+    /// BF file that may not be in any content root.
+    File(PathBuf),
+    Uuid(Uuid),
+}
+
+impl Source {
+    /// Parses the argument as a path to an existing file, uuid or a name known
+    /// to the lookup database (in this order).
+    fn parse(arg: &str) -> Self {
+        if Path::new(arg).is_file() {
+            return Source::File(PathBuf::from(arg));
+        }
+
+        match Uuid::parse_str(arg) {
+            Ok(uuid) => Source::Uuid(uuid),
+            Err(_) => Source::Uuid(lookup(arg)),
+        }
+    }
+}
+
+/// Types of assets the viewer can show.
+#[derive(Copy, Clone, Debug)]
+enum Kind {
+    Mesh,
+    Material,
+    Image,
+}
+
+/// State of the viewer that is not part of the engine.
+struct Viewer {
+    kind: Kind,
+    /// Camera controller the camera returns to when the view is reset.
+    initial_orbit: OrbitMovement,
+}
+
+impl Game for Viewer {
+    fn update(&mut self, engine: &mut Engine) {
+        let ctx = engine.egui.ctx().clone();
+
+        egui::Window::new("Viewer").show(&ctx, |ui| {
+            ui.label(format!("Showing {:?}", self.kind));
+
+            let ambient = &mut engine.game_state.ambient_light;
+            ui.add(egui::Slider::new(&mut ambient.intensity, 0.0..=2.0).text("ambient intensity"));
+            if let Some(light) = engine.game_state.directional_lights.first_mut() {
+                ui.add(egui::Slider::new(&mut light.intensity, 0.0..=5.0).text("key light"));
+            }
+
+            ui.separator();
+            let mut turntable = engine.turntable.is_enabled();
+            if ui.checkbox(&mut turntable, "Turntable lighting").changed() {
+                if turntable {
+                    engine.turntable.enable(&mut engine.game_state);
+                } else {
+                    engine.turntable.disable(&mut engine.game_state);
+                }
+            }
+            if turntable {
+                let t = &mut engine.turntable;
+                ui.add(egui::Slider::new(&mut t.luminance, 0.0..=10.0).text("rig luminance"));
+                ui.add(egui::Slider::new(&mut t.speed, -90.0..=90.0).text("spin speed"));
+            }
+
+            ui.separator();
+            let view = &mut engine.renderer_state.render_path.debug_view.view;
+            for x in DebugView::ALL.iter() {
+                ui.radio_value(view, *x, x.name());
+            }
+
+            ui.separator();
+            if ui.button("Reset camera").clicked() {
+                engine.movement = Movement::Orbit(self.initial_orbit);
+            }
+        });
+    }
+}
+
+fn main() {
+    // increase default stack size to 8MB
+    let child = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(boot)
+        .unwrap();
+
+    // Wait for thread to join
+    child.join().unwrap();
+}
+
+fn boot() {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let source = match args.first() {
+        Some(x) if !x.starts_with("--") => Source::parse(&args.remove(0)),
+        _ => {
+            eprintln!("Usage: bfview <file.bf | uuid | name> [--set <name>=<value>]...");
+            std::process::exit(1);
+        }
+    };
+
+    // load configuration from `renderer.toml` (overridden by `--set name=value`
+    // and environment variables)
+    let overrides = match parse_set_args(args) {
+        Ok(t) => Overrides::from_env(ENV_PREFIX, t),
+        Err(e) => panic!("Cannot parse arguments: {}", e),
+    };
+    let mut conf = match RendererConfiguration::load(&config_file(), &overrides) {
+        Ok(t) => t,
+        Err(e) => panic!("Invalid configuration: {}", e),
+    };
+    for name in overrides.unused() {
+        warn!("Unknown setting {:?} was ignored", name);
+    }
+
+    // assets referenced by the file are looked for next to it
+    if let Source::File(path) = &source {
+        if let Some(dir) = path.parent() {
+            conf.content_roots.insert(0, dir.to_path_buf());
+        }
+    }
+
+    let aspect_ratio = conf.resolution[0] as f32 / conf.resolution[1] as f32;
+    let mut engine = Engine::new(
+        GameState {
+            start: Instant::now(),
+            camera: PerspectiveCamera {
+                position: Point3::new(0.0, 0.0, 3.0),
+                forward: vec3(0.0, 0.0, -1.0),
+                up: vec3(0.0, -1.0, 0.0),
+                fov: Deg(60.0).into(),
+                aspect_ratio,
+                near: 0.01,
+                far: 100.0,
+            },
+            camera_rig: None,
+            // front view of the asset
+            orthographic_camera: OrthographicCamera {
+                position: Point3::new(0.0, 0.0, 50.0),
+                forward: vec3(0.0, 0.0, -1.0),
+                up: vec3(0.0, -1.0, 0.0),
+                height: 3.0,
+                aspect_ratio,
+                near: 0.01,
+                far: 100.0,
+            },
+            active_camera: ActiveCamera::Perspective,
+            objects: Objects::default(),
+            // white key light from the front top and a neutral ambient light
+            directional_lights: vec![DirectionalLight {
+                direction: vec3(1.0, 2.0, 2.0).normalize(),
+                intensity: 2.0,
+                color: vec3(1.0, 1.0, 1.0),
+            }],
+            spot_lights: vec![],
+            decals: vec![],
+            ambient_light: AmbientLight {
+                sky_color: vec3(1.0, 1.0, 1.0),
+                ground_color: vec3(0.5, 0.5, 0.5),
+                intensity: 0.5,
+            },
+            wind: Wind::default(),
+            origin: vec3(0.0, 0.0, 0.0),
+            hierarchy: Hierarchy::default(),
+        },
+        &conf,
+        EventLoop::new_any_thread(),
+    );
+
+    let uuid = match source {
+        Source::File(path) => {
+            // files that are not named by their uuid are mounted as nil uuid
+            let uuid = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| Uuid::parse_str(x).ok())
+                .unwrap_or_else(Uuid::nil);
+            engine.content.mount_file(uuid, path);
+            uuid
+        }
+        Source::Uuid(uuid) => uuid,
+    };
+
+    let kind = match asset_kind(&engine.content, uuid) {
+        Some(t) => t,
+        None => {
+            eprintln!("Asset {} is not a mesh, material or image.", uuid);
+            std::process::exit(1);
+        }
+    };
+    info!("Viewing {:?} {}", kind, uuid);
+
+    let (center, radius) = spawn(&mut engine, kind, uuid);
+    let camera = &mut engine.game_state.camera;
+    let distance = radius / (camera.fov.0 * 0.5).sin();
+    camera.near = distance * 0.01;
+    camera.far = distance * 10.0;
+
+    let initial_orbit = OrbitMovement::new(Point3::from_vec(center), distance);
+    engine.movement = Movement::Orbit(initial_orbit);
+
+    engine.run_forever(Viewer {
+        kind,
+        initial_orbit,
+    });
+}
+
+/// Loads the asset and returns which kind of asset it is.
+fn asset_kind(content: &Content, uuid: Uuid) -> Option<Kind> {
+    content.request_load(uuid);
+
+    if content.is_of_type::<bf::mesh::Mesh>(&uuid) {
+        Some(Kind::Mesh)
+    } else if content.is_of_type::<bf::material::Material>(&uuid) {
+        Some(Kind::Material)
+    } else if content.is_of_type::<bf::image::Image>(&uuid) {
+        Some(Kind::Image)
+    } else {
+        None
+    }
+}
+
+/// Creates the object that shows the asset and returns the center and radius
+/// of its bounding sphere.
+fn spawn(engine: &mut Engine, kind: Kind, uuid: Uuid) -> (Vector3<f32>, f32) {
+    let device = engine.vulkan_state.device();
+    let content = &engine.content;
+    let path = &engine.renderer_state.render_path;
+    let queue = content.transfer_queue.clone();
+
+    let (fallback_maps, f) = create_default_fallback_maps(queue.clone());
+    content.uploads.schedule(f, 0);
+
+    let (mesh, material, center, radius) = match kind {
+        Kind::Mesh => {
+            let mesh = create_mesh_cached(uuid, content).expect("cannot create mesh");
+            let (center, radius) = mesh_bounds(&content.get_blocking::<bf::mesh::Mesh>(&uuid));
+            let material = bf::material::Material {
+                albedo_color: [0.8, 0.8, 0.8],
+                ..Default::default()
+            };
+            (mesh, material, center, radius)
+        }
+        Kind::Material => {
+            let material = *content.get_blocking::<bf::material::Material>(&uuid);
+            (
+                uv_sphere(content, 48, 24),
+                material,
+                Vector3::new(0.0, 0.0, 0.0),
+                1.0,
+            )
+        }
+        Kind::Image => {
+            let (width, height) = {
+                let image = content.get_blocking::<bf::image::Image>(&uuid);
+                (image.width as f32, image.height as f32)
+            };
+            let aspect = width / height;
+            let material = bf::material::Material {
+                albedo_color: [1.0, 1.0, 1.0],
+                albedo_map: Some(uuid),
+                roughness: 1.0,
+                ..Default::default()
+            };
+            let radius = (aspect * aspect + 1.0).sqrt();
+            (
+                plane(content, aspect),
+                material,
+                Vector3::new(0.0, 0.0, 0.0),
+                radius,
+            )
+        }
+    };
+
+    let (material, f) = StaticMaterial::from_material(
+        &material,
+        content,
+        path.buffers.geometry_pipeline.clone(),
+        path.samplers.aniso_repeat.clone(),
+        queue,
+        fallback_maps,
+    )
+    .expect("cannot create material");
+    content.uploads.schedule(f, 0);
+
+    let mut object = Object::new(
+        mesh,
+        material,
+        device,
+        path.buffers.geometry_pipeline.clone(),
+        Transform::from_position(vec3(0.0, 0.0, 0.0)),
+    );
+    object.bounding_radius = center.magnitude() + radius;
+    let id = engine.game_state.objects.insert(object);
+    engine.turntable.object = Some(id);
+
+    (center, radius)
+}
+
+/// Returns the center and radius of the sphere around the vertices of the mesh.
+fn mesh_bounds(mesh: &bf::mesh::Mesh) -> (Vector3<f32>, f32) {
+    let vertex_data = match mesh.decoded_data() {
+        Ok((vertex_data, _)) => vertex_data,
+        Err(e) => panic!("cannot decode mesh: {:?}", e),
+    };
+
+    // all vertex formats start with the position
+    let positions = vertex_data
+        .chunks_exact(mesh.vertex_format.size_of_one_vertex())
+        .map(|x| {
+            let f = |i: usize| f32::from_le_bytes([x[i], x[i + 1], x[i + 2], x[i + 3]]);
+            Vector3::new(f(0), f(4), f(8))
+        })
+        .collect::<Vec<_>>();
+
+    if positions.is_empty() {
+        return (Vector3::new(0.0, 0.0, 0.0), 1.0);
+    }
+
+    let (min, max) = positions.iter().fold(
+        (Vector3::from([f32::MAX; 3]), Vector3::from([f32::MIN; 3])),
+        |(min, max), p| {
+            (
+                vec3(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                vec3(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        },
+    );
+    let center = (min + max) * 0.5;
+    let radius = positions
+        .iter()
+        .map(|p| (p - center).magnitude())
+        .fold(0.0, f32::max);
+
+    (center, radius.max(1e-3))
+}
+
+/// Creates a unit sphere with `segments` vertical slices and `rings` horizontal ones.
+fn uv_sphere(
+    content: &Content,
+    segments: u16,
+    rings: u16,
+) -> Arc<DynamicIndexedMesh<NormalMappedVertex>> {
+    let mut vertices = vec![];
+    for r in 0..=rings {
+        for s in 0..=segments {
+            let (u, v) = (s as f32 / segments as f32, r as f32 / rings as f32);
+            let (theta, phi) = (v * PI, u * 2.0 * PI);
+            let normal = [
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ];
+
+            vertices.push(NormalMappedVertex {
+                position: normal,
+                normal,
+                uv: [u, v],
+                tangent: [-phi.sin(), 0.0, phi.cos(), 0.0],
+            });
+        }
+    }
+
+    let index = |r: u16, s: u16| r * (segments + 1) + s;
+    let mut indices = vec![];
+    for r in 0..rings {
+        for s in 0..segments {
+            let (a, b) = (index(r, s), index(r, s + 1));
+            let (c, d) = (index(r + 1, s), index(r + 1, s + 1));
+            indices.extend_from_slice(&[a, b, c, c, b, d]);
+        }
+    }
+
+    upload(content, vertices, indices)
+}
+
+/// Creates a plane facing up that is `aspect` units wide and one unit deep.
+fn plane(content: &Content, aspect: f32) -> Arc<DynamicIndexedMesh<NormalMappedVertex>> {
+    let vertex = |x: f32, z: f32| NormalMappedVertex {
+        position: [x * aspect, 0.0, z],
+        normal: [0.0, 1.0, 0.0],
+        uv: [x * 0.5 + 0.5, z * 0.5 + 0.5],
+        tangent: [1.0, 0.0, 0.0, 0.0],
+    };
+
+    let vertices = vec![
+        vertex(-1.0, -1.0),
+        vertex(-1.0, 1.0),
+        vertex(1.0, -1.0),
+        vertex(1.0, 1.0),
+    ];
+    upload(content, vertices, vec![0, 1, 2, 2, 1, 3])
+}
+
+fn upload(
+    content: &Content,
+    vertices: Vec<NormalMappedVertex>,
+    indices: Vec<u16>,
+) -> Arc<DynamicIndexedMesh<NormalMappedVertex>> {
+    let bytes = vertices.len() * std::mem::size_of::<NormalMappedVertex>() + indices.len() * 2;
+    let queue = content.transfer_queue.clone();
+    let (vertex_buffer, f1) = ImmutableBuffer::from_iter(
+        vertices.into_iter(),
+        BufferUsage::vertex_buffer(),
+        queue.clone(),
+    )
+    .expect("cannot create vertex buffer");
+    let (index_buffer, f2) =
+        ImmutableBuffer::from_iter(indices.into_iter(), BufferUsage::index_buffer(), queue)
+            .expect("cannot create index buffer");
+    content.uploads.schedule(f1.join(f2), bytes);
+
+    let mesh = IndexedMesh::<NormalMappedVertex, u16>::new(vertex_buffer, index_buffer);
+    Arc::new(DynamicIndexedMesh::U16(match Arc::try_unwrap(mesh) {
+        Ok(t) => t,
+        Err(_) => unreachable!(),
+    }))
+}
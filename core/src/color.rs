@@ -0,0 +1,173 @@
+//! Conversions between color spaces.
+//!
+//! Colors are `[r, g, b]` arrays of floats. Linear colors are in the Rec. 709
+//! primaries (same as sRGB) unless stated otherwise. ACEScg colors use the AP1
+//! primaries with D60 white point, conversions to and from it include the
+//! Bradford chromatic adaptation between D65 and D60.
+
+use std::str::FromStr;
+
+/// Rec. 709 (linear sRGB) to ACEScg matrix (rows).
+const REC709_TO_ACESCG: [[f32; 3]; 3] = [
+    [0.6130974, 0.3395231, 0.0473793],
+    [0.0701942, 0.9163556, 0.0134526],
+    [0.0206156, 0.1095698, 0.8697926],
+];
+
+/// ACEScg to Rec. 709 (linear sRGB) matrix (rows).
+const ACESCG_TO_REC709: [[f32; 3]; 3] = [
+    [1.7048587, -0.621716, -0.0832584],
+    [-0.1300768, 1.1407357, -0.0105598],
+    [-0.023964, -0.1289755, 1.153014],
+];
+
+/// Color space of color values (eg. pixels of an image or a color constant).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorSpace {
+    /// Rec. 709 primaries with the sRGB transfer function (most 8-bit images).
+    Srgb,
+    /// Rec. 709 primaries without any transfer function (shading, HDR images).
+    LinearRec709,
+    /// Linear AP1 primaries of the ACES (common for EXR renders and textures).
+    AcesCg,
+}
+
+impl FromStr for ColorSpace {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "linear" | "rec709" => Ok(ColorSpace::LinearRec709),
+            "acescg" => Ok(ColorSpace::AcesCg),
+            _ => Err("unknown color space"),
+        }
+    }
+}
+
+impl ColorSpace {
+    /// Converts the `color` from this color space to the `target` color space.
+    pub fn convert(self, color: [f32; 3], target: ColorSpace) -> [f32; 3] {
+        if self == target {
+            return color;
+        }
+
+        let linear = match self {
+            ColorSpace::Srgb => srgb_to_linear_rgb(color),
+            ColorSpace::LinearRec709 => color,
+            ColorSpace::AcesCg => mul(&ACESCG_TO_REC709, color),
+        };
+
+        match target {
+            ColorSpace::Srgb => linear_to_srgb_rgb(linear),
+            ColorSpace::LinearRec709 => linear,
+            ColorSpace::AcesCg => mul(&REC709_TO_ACESCG, linear),
+        }
+    }
+}
+
+fn mul(m: &[[f32; 3]; 3], c: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * c[0] + m[0][1] * c[1] + m[0][2] * c[2],
+        m[1][0] * c[0] + m[1][1] * c[1] + m[1][2] * c[2],
+        m[2][0] * c[0] + m[2][1] * c[1] + m[2][2] * c[2],
+    ]
+}
+
+/// Decodes one channel of sRGB color to linear value (exact piecewise sRGB
+/// transfer function, not the `pow(x, 2.2)` approximation).
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes one channel of linear color to sRGB. Negative values are clamped to zero.
+pub fn linear_to_srgb(x: f32) -> f32 {
+    let x = x.max(0.0);
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes sRGB color to linear Rec. 709 color.
+pub fn srgb_to_linear_rgb(c: [f32; 3]) -> [f32; 3] {
+    [
+        srgb_to_linear(c[0]),
+        srgb_to_linear(c[1]),
+        srgb_to_linear(c[2]),
+    ]
+}
+
+/// Encodes linear Rec. 709 color to sRGB.
+pub fn linear_to_srgb_rgb(c: [f32; 3]) -> [f32; 3] {
+    [
+        linear_to_srgb(c[0]),
+        linear_to_srgb(c[1]),
+        linear_to_srgb(c[2]),
+    ]
+}
+
+/// Returns linear Rec. 709 color of 8-bit sRGB color (as picked in image editors),
+/// eg. for light colors and albedo tints specified in code.
+pub fn srgb8(r: u8, g: u8, b: u8) -> [f32; 3] {
+    srgb_to_linear_rgb([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0])
+}
+
+/// Relative luminance of linear Rec. 709 color.
+pub fn luminance(c: [f32; 3]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{linear_to_srgb, luminance, srgb8, srgb_to_linear, ColorSpace};
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn srgb_roundtrip() {
+        for i in 0..=255 {
+            let x = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(x)) - x).abs() < 1e-5);
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // middle gray
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        assert_close(srgb8(255, 255, 255), [1.0; 3]);
+    }
+
+    #[test]
+    fn acescg_roundtrip_keeps_white_and_luminance() {
+        let white = ColorSpace::LinearRec709.convert([1.0; 3], ColorSpace::AcesCg);
+        assert_close(white, [1.0; 3]);
+
+        let color = [0.8, 0.3, 0.1];
+        let aces = ColorSpace::LinearRec709.convert(color, ColorSpace::AcesCg);
+        assert_close(
+            ColorSpace::AcesCg.convert(aces, ColorSpace::LinearRec709),
+            color,
+        );
+        // primaries of ACEScg are wider, so saturated colors get less saturated
+        assert!(aces[0] < color[0] && aces[2] > color[2]);
+        assert!((luminance(color) - 0.3919).abs() < 1e-3);
+    }
+
+    #[test]
+    fn converts_through_linear() {
+        let srgb = [0.5, 0.25, 1.0];
+        let aces = ColorSpace::Srgb.convert(srgb, ColorSpace::AcesCg);
+        assert_close(ColorSpace::AcesCg.convert(aces, ColorSpace::Srgb), srgb);
+        assert_eq!("ACEScg".parse(), Ok(ColorSpace::AcesCg));
+        assert!("xyz".parse::<ColorSpace>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Resolution of `#include "file"` directives of GLSL shaders.
+//!
+//! Shared code of shaders (BRDF terms, color conversions, ...) lives in files
+//! prefixed with `inc_` next to the shaders. Includes are resolved relative to
+//! the including file both by the shader compiler (`glsl2bf`) and by the asset
+//! server, which recompiles every shader that includes a changed file.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Returns names of files included by the GLSL `source` in order of the
+/// `#include` directives.
+pub fn parse_includes(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix('#'))
+        .filter_map(|line| line.trim_start().strip_prefix("include"))
+        .filter_map(|rest| {
+            let rest = rest.trim().strip_prefix('"')?;
+            rest.split('"').next()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Returns the path of file `name` included by the file at `including` path.
+/// Parent directory components (`..`) are resolved, so the same file is always
+/// referred to by the same path.
+pub fn include_path(including: &Path, name: &str) -> PathBuf {
+    let path = including
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(name);
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            x => normalized.push(x),
+        }
+    }
+    normalized
+}
+
+/// Returns paths of all files included by the shader at `path`, including
+/// files included by other included files. Files that can't be read are
+/// returned, but not searched for further includes.
+pub fn collect_includes(path: &Path) -> Vec<PathBuf> {
+    let mut includes = Vec::new();
+    let mut stack = vec![path.to_path_buf()];
+
+    while let Some(file) = stack.pop() {
+        let source = match std::fs::read_to_string(&file) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+
+        for name in parse_includes(&source) {
+            let include = include_path(&file, name);
+            if include != path && !includes.contains(&include) {
+                includes.push(include.clone());
+                stack.push(include);
+            }
+        }
+    }
+
+    includes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glsl::{collect_includes, include_path, parse_includes};
+    use std::path::Path;
+
+    #[test]
+    fn parses_include_directives() {
+        let source = "#version 450\n#include \"inc_a.glsl\"\n  # include  \"b/inc_b.glsl\"\n\
+                      // #include \"commented.glsl\"\n#include <system.glsl>\n#include \"\"\n";
+
+        assert_eq!(parse_includes(source), vec!["inc_a.glsl", "b/inc_b.glsl"]);
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let shader = Path::new("shaders/fs_tonemap.glsl");
+
+        assert_eq!(
+            include_path(shader, "inc_color.glsl"),
+            Path::new("shaders/inc_color.glsl")
+        );
+        assert_eq!(
+            include_path(shader, "./lib/../../common/inc_math.glsl"),
+            Path::new("common/inc_math.glsl")
+        );
+    }
+
+    #[test]
+    fn collects_nested_includes() {
+        let dir = std::env::temp_dir().join(format!("glsl_includes_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        let write = |name: &str, source: &str| std::fs::write(dir.join(name), source).unwrap();
+
+        write(
+            "shader.frag",
+            "#include \"lib/inc_a.glsl\"\n#include \"inc_c.glsl\"\n",
+        );
+        // cycles through the shader itself are not followed
+        write(
+            "lib/inc_a.glsl",
+            "#include \"inc_b.glsl\"\n#include \"../shader.frag\"\n",
+        );
+        write("lib/inc_b.glsl", "#include \"inc_a.glsl\"\n");
+
+        let includes = collect_includes(&dir.join("shader.frag"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            includes,
+            vec![
+                dir.join("lib/inc_a.glsl"),
+                dir.join("inc_c.glsl"),
+                dir.join("lib/inc_b.glsl"),
+            ]
+        );
+    }
+}
@@ -2,8 +2,11 @@
 
 use std::ops::{Add, Mul, Sub};
 
+pub mod color;
+pub mod glsl;
 pub mod notification;
 pub mod perf;
+pub mod settings;
 pub mod tool;
 
 /// Statically asserts that the alignment of specified type is
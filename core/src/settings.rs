@@ -0,0 +1,250 @@
+//! Override layer of settings shared by the renderer and the asset server.
+//!
+//! Every setting has a `snake_case` name (eg. `bloom_intensity`) and its value
+//! is taken from the first of these sources that specifies it:
+//!
+//! 1. command line argument `--set <name>=<value>` (can be repeated),
+//! 2. environment variable named by the setting in upper case with application
+//!    specific prefix (eg. `RENDERER_BLOOM_INTENSITY` for renderer, `ASSET_SERVER_PORT`
+//!    for asset server),
+//! 3. settings file or default value of the application.
+//!
+//! Optional settings are unset by an empty value or `none`.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Values of settings overridden by the command line and environment variables.
+pub struct Overrides {
+    prefix: String,
+    args: HashMap<String, String>,
+    vars: HashMap<String, String>,
+    /// Names of settings that were looked up (to detect misspelled arguments).
+    used: RefCell<HashSet<String>>,
+}
+
+impl Overrides {
+    /// Creates overrides from the environment of the process and specified
+    /// values from the command line.
+    pub fn from_env<I>(prefix: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        Self::new(prefix, args, std::env::vars())
+    }
+
+    /// Creates overrides from specified values from the command line and
+    /// environment variables. Variables without the `prefix` are ignored.
+    pub fn new<I, V>(prefix: &str, args: I, vars: V) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+        V: IntoIterator<Item = (String, String)>,
+    {
+        Self {
+            prefix: prefix.to_string(),
+            args: args.into_iter().collect(),
+            vars: vars
+                .into_iter()
+                .filter(|(k, _)| k.starts_with(prefix))
+                .collect(),
+            used: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Returns the name of environment variable of specified setting.
+    pub fn var_name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name.to_uppercase())
+    }
+
+    /// Returns the overridden value of specified setting.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.used.borrow_mut().insert(name.to_string());
+        self.args
+            .get(name)
+            .or_else(|| self.vars.get(&self.var_name(name)))
+            .map(|x| x.as_str())
+    }
+
+    /// Returns all overridden settings whose name starts with `prefix` as pairs
+    /// of the rest of the name and the value (eg. `action_bindings_` returns
+    /// values of all action bindings).
+    pub fn get_prefixed(&self, prefix: &str) -> Vec<(String, &str)> {
+        let var_prefix = self.var_name(prefix);
+        let mut values = self
+            .vars
+            .iter()
+            .filter_map(|(k, v)| Some((k.strip_prefix(&var_prefix)?.to_lowercase(), v)))
+            .collect::<HashMap<_, _>>();
+
+        for (k, v) in self.args.iter() {
+            if let Some(rest) = k.strip_prefix(prefix) {
+                self.used.borrow_mut().insert(k.clone());
+                values.insert(rest.to_string(), v);
+            }
+        }
+
+        values.into_iter().map(|(k, v)| (k, v.as_str())).collect()
+    }
+
+    /// Replaces the `target` with the parsed value of the setting if it is
+    /// overridden.
+    pub fn apply<T>(&self, name: &str, target: &mut T) -> Result<(), String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(value) = self.get(name) {
+            *target = parse(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Same as [`apply`](#method.apply), but for optional settings.
+    pub fn apply_option<T>(&self, name: &str, target: &mut Option<T>) -> Result<(), String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.get(name) {
+            None => {}
+            Some("") | Some("none") => *target = None,
+            Some(value) => *target = Some(parse(name, value)?),
+        }
+        Ok(())
+    }
+
+    /// Returns names of settings that were specified on the command line, but
+    /// were never looked up (most likely misspelled).
+    pub fn unused(&self) -> Vec<&str> {
+        let used = self.used.borrow();
+        let mut unused = self
+            .args
+            .keys()
+            .filter(|x| !used.contains(*x))
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>();
+        unused.sort_unstable();
+        unused
+    }
+}
+
+fn parse<T>(name: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid value {:?} of setting {}: {}", value, name, e))
+}
+
+/// Parses a single `<name>=<value>` value of `--set` argument (to be used with
+/// `structopt`).
+pub fn parse_set_arg(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("invalid setting {:?} (expected name=value)", arg)),
+    }
+}
+
+/// Parses `--set <name>=<value>` (or `--set=<name>=<value>`) arguments for
+/// applications without a command line parser. Any other argument is an error.
+pub fn parse_set_args<I>(args: I) -> Result<Vec<(String, String)>, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut values = vec![];
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let set = match arg.strip_prefix("--set") {
+            Some("") => args
+                .next()
+                .ok_or_else(|| "missing value of --set".to_string())?,
+            Some(x) if x.starts_with('=') => x[1..].to_string(),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        };
+        values.push(parse_set_arg(&set)?);
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::{parse_set_args, Overrides};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn overrides(args: &[&str], vars: &[(&str, &str)]) -> Overrides {
+        Overrides::new(
+            "APP_",
+            parse_set_args(self::args(args)).unwrap(),
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+    }
+
+    #[test]
+    fn arguments_take_precedence_over_variables() {
+        let o = overrides(
+            &["--set", "port=80", "--set=gpu=1"],
+            &[
+                ("APP_PORT", "90"),
+                ("APP_WATCH", "true"),
+                ("WATCH", "false"),
+            ],
+        );
+
+        let (mut port, mut watch, mut gpu, mut other) = (8000u16, false, 0usize, 5u8);
+        o.apply("port", &mut port).unwrap();
+        o.apply("watch", &mut watch).unwrap();
+        o.apply("gpu", &mut gpu).unwrap();
+        o.apply("other", &mut other).unwrap();
+
+        assert_eq!((port, watch, gpu, other), (80, true, 1, 5));
+        assert!(o.unused().is_empty());
+    }
+
+    #[test]
+    fn optional_and_prefixed_settings() {
+        let o = overrides(
+            &[
+                "--set",
+                "cache=",
+                "--set",
+                "bind_jump=Key:Space",
+                "--set",
+                "typo=1",
+            ],
+            &[("APP_LIMIT", "3"), ("APP_BIND_FIRE", "Key:E")],
+        );
+
+        let (mut cache, mut limit) = (Some(1u32), None);
+        o.apply_option("cache", &mut cache).unwrap();
+        o.apply_option("limit", &mut limit).unwrap();
+        assert_eq!((cache, limit), (None, Some(3u32)));
+
+        let mut binds = o.get_prefixed("bind_");
+        binds.sort_unstable();
+        assert_eq!(
+            binds,
+            vec![("fire".into(), "Key:E"), ("jump".into(), "Key:Space")]
+        );
+
+        assert_eq!(o.unused(), vec!["typo"]);
+        assert!(o.apply("limit", &mut 0u8).is_ok());
+        assert!(o.apply("typo", &mut true).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert!(parse_set_args(args(&["--set"])).is_err());
+        assert!(parse_set_args(args(&["--set", "=1"])).is_err());
+        assert!(parse_set_args(args(&["--set", "gpu"])).is_err());
+        assert!(parse_set_args(args(&["--fullscreen"])).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+#version 450
+
+#include "inc_color.glsl"
+
+// must match `HISTOGRAM_BINS`
+#define BINS 64
+
+layout(local_size_x = 16, local_size_y = 16) in;
+
+layout(set = 0, binding = 0) uniform sampler2D hdr;
+
+layout(set = 0, binding = 1) buffer Histogram {
+    uint bins[BINS];
+} histogram;
+
+layout(push_constant) uniform PushConstants {
+    uvec2 resolution;
+    // range of exposure values covered by the bins
+    float min_ev;
+    float max_ev;
+} push_constants;
+
+shared uint local_bins[BINS];
+
+void main() {
+    uint index = gl_LocalInvocationIndex;
+    if (index < BINS) {
+        local_bins[index] = 0u;
+    }
+    barrier();
+
+    // pixels are counted in shared memory first, so only one atomic
+    // operation per bin and work group goes to the buffer
+    uvec2 pixel = gl_GlobalInvocationID.xy;
+    if (all(lessThan(pixel, push_constants.resolution))) {
+        vec3 color = texelFetch(hdr, ivec2(pixel), 0).rgb;
+        float ev = log2(max(luminance(color), 1e-6) / 0.18);
+        float t = (ev - push_constants.min_ev) / (push_constants.max_ev - push_constants.min_ev);
+        uint bin = uint(clamp(t * BINS, 0.0, BINS - 1.0));
+        atomicAdd(local_bins[bin], 1u);
+    }
+    barrier();
+
+    if (index < BINS && local_bins[index] > 0u) {
+        atomicAdd(histogram.bins[index], local_bins[index]);
+    }
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 texel_size;
+    vec2 target_size;
+} push_constants;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / push_constants.target_size;
+    vec2 d = push_constants.texel_size;
+
+    vec3 color = texture(source, uv + vec2(-d.x, -d.y)).rgb
+               + texture(source, uv + vec2( d.x, -d.y)).rgb
+               + texture(source, uv + vec2(-d.x,  d.y)).rgb
+               + texture(source, uv + vec2( d.x,  d.y)).rgb;
+
+    f_color = vec4(color * 0.25, 1.0);
+}
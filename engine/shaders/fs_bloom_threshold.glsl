@@ -0,0 +1,35 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 texel_size;
+    vec2 target_size;
+    float threshold;
+} push_constants;
+
+layout(location = 0) out vec4 f_color;
+
+// fraction of the threshold used as soft transition so bright areas do not pop in
+const float KNEE = 0.5;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / push_constants.target_size;
+    vec2 d = push_constants.texel_size;
+
+    // four bilinear taps average 4x4 texels which removes most of the flickering
+    vec3 color = texture(source, uv + vec2(-d.x, -d.y)).rgb
+               + texture(source, uv + vec2( d.x, -d.y)).rgb
+               + texture(source, uv + vec2(-d.x,  d.y)).rgb
+               + texture(source, uv + vec2( d.x,  d.y)).rgb;
+    color *= 0.25;
+
+    float threshold = push_constants.threshold;
+    float knee = threshold * KNEE;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.00001);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+
+    f_color = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 texel_size;
+    vec2 target_size;
+} push_constants;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / push_constants.target_size;
+    vec2 d = push_constants.texel_size;
+
+    // 3x3 tent filter, result is added to the target by blending
+    vec3 color = texture(source, uv).rgb * 4.0;
+    color += (texture(source, uv + vec2(-d.x, 0.0)).rgb
+            + texture(source, uv + vec2( d.x, 0.0)).rgb
+            + texture(source, uv + vec2(0.0, -d.y)).rgb
+            + texture(source, uv + vec2(0.0,  d.y)).rgb) * 2.0;
+    color += texture(source, uv + vec2(-d.x, -d.y)).rgb
+           + texture(source, uv + vec2( d.x, -d.y)).rgb
+           + texture(source, uv + vec2(-d.x,  d.y)).rgb
+           + texture(source, uv + vec2( d.x,  d.y)).rgb;
+
+    f_color = vec4(color / 16.0, 1.0);
+}
@@ -0,0 +1,60 @@
+#version 450
+
+#include "inc_color.glsl"
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 resolution;
+    // how to decode the source (see `DebugView::shader_mode`)
+    uint mode;
+    float near;
+    float far;
+} push_constants;
+
+const uint MODE_COLOR = 0;
+const uint MODE_DEPTH = 1;
+const uint MODE_SINGLE_CHANNEL = 2;
+const uint MODE_EXPOSURE = 3;
+const uint MODE_SKY_VISIBILITY = 4;
+
+// false colors of exposure zones, must match `render::histogram::zone_color`
+vec3 exposure_zone(float ev) {
+    if (ev < -6.0) return vec3(0.4, 0.0, 0.6);  // crushed shadows
+    if (ev < -3.0) return vec3(0.0, 0.3, 1.0);  // underexposed
+    if (ev < -0.5) return vec3(0.3);            // shadows
+    if (ev < 0.5) return vec3(0.2, 0.8, 0.2);   // middle gray
+    if (ev < 3.0) return vec3(0.7);             // highlights
+    if (ev < 6.0) return vec3(1.0, 0.8, 0.0);   // bright
+    return vec3(1.0, 0.0, 0.0);                 // overexposed
+}
+
+void main() {
+    vec4 value = texture(source, gl_FragCoord.xy / push_constants.resolution);
+    float n = push_constants.near;
+    float f = push_constants.far;
+
+    switch (push_constants.mode) {
+        case MODE_DEPTH:
+            // depth buffer contains z in NDC of the projection matrix
+            float z = 2.0 * f * n / ((f + n) - value.r * (f - n));
+            f_color = vec4(vec3((z - n) / (f - n)), 1.0);
+            break;
+        case MODE_SINGLE_CHANNEL:
+            f_color = vec4(value.rrr, 1.0);
+            break;
+        case MODE_SKY_VISIBILITY:
+            f_color = vec4(value.bbb, 1.0);
+            break;
+        case MODE_EXPOSURE:
+            // exposure value relative to middle gray (18 % reflectance)
+            float ev = log2(max(luminance(value.rgb), 1e-6) / 0.18);
+            f_color = vec4(exposure_zone(ev), 1.0);
+            break;
+        default:
+            f_color = vec4(clamp(value.rgb, 0.0, 1.0), 1.0);
+            break;
+    }
+}
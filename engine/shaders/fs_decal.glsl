@@ -0,0 +1,93 @@
+#version 450
+#include "inc_structs.glsl"
+#include "inc_normal.glsl"
+
+layout(location = 0) out vec4 normal_l_model;
+layout(location = 1) out vec4 albedo_occlusion;
+layout(location = 2) out vec4 roughness_metallic;
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+} frame_matrix_data;
+
+// material textures (same layout as the geometry pass)
+layout(set = 1, binding = 0) uniform sampler2D albedo_map;
+layout(set = 1, binding = 1) uniform sampler2D normal_map;
+layout(set = 1, binding = 2) uniform sampler2D displacement_map;
+layout(set = 1, binding = 3) uniform sampler2D roughness_map;
+layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
+layout(set = 1, binding = 5) uniform sampler2D metallic_map;
+layout(std140, set = 1, binding = 6) uniform TheBlock {
+    MaterialData material_data;
+};
+layout(set = 1, binding = 7) uniform sampler2D opacity_map;
+layout(set = 1, binding = 8) uniform sampler2D emissive_map;
+
+layout(std140, set = 2, binding = 0) uniform DecalData {
+    mat4 model;
+    mat4 inv_model;
+    float opacity;
+} decal_data;
+
+layout(set = 3, binding = 0, input_attachment_index = 0) uniform subpassInput depth;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 resolution;
+} push_constants;
+
+// extract position from depth value
+vec3 PositionFromDepth(float depth) {
+    vec2 coord = gl_FragCoord.xy / push_constants.resolution;
+
+    vec4 clipSpacePosition = vec4(coord * 2.0 - 1.0, depth, 1.0);
+    vec4 viewSpacePosition = frame_matrix_data.invProjection * clipSpacePosition;
+    viewSpacePosition /= viewSpacePosition.w;
+    vec4 worldSpacePosition = frame_matrix_data.invView * viewSpacePosition;
+    return worldSpacePosition.xyz;
+}
+
+void main() {
+    float d = subpassLoad(depth).r;
+    vec3 position = PositionFromDepth(d);
+
+    // the decal is projected along the y axis of its box
+    vec3 T = normalize(decal_data.model[0].xyz);
+    vec3 N = normalize(decal_data.model[1].xyz);
+    vec3 B = cross(N, T);
+
+    // surfaces perpendicular to the projection would stretch the decal
+    vec3 surface = normalize(cross(dFdx(position), dFdy(position)));
+    float facing = smoothstep(0.2, 0.5, abs(dot(surface, N)));
+
+    vec3 local = (decal_data.inv_model * vec4(position, 1.0)).xyz;
+    if (d >= 1.0 || any(greaterThan(abs(local), vec3(0.5)))) {
+        discard;
+    }
+
+    vec2 uv = local.xz + 0.5;
+    vec4 albedo = texture(albedo_map, uv);
+    vec3 normal = unpack_normal(texture(normal_map, uv), (material_data.flags & MATERIAL_NORMAL_MAP_AG) != 0u);
+    float roughness = material_data.roughness * texture(roughness_map, uv).r;
+    float metallic = material_data.metallic * texture(metallic_map, uv).r;
+    float displacement = texture(displacement_map, uv).r; // todo: remove when vulkano-shaders is fixed
+    float occlusion = texture(occlusion_map, uv).r; // todo: remove when vulkano-shaders is fixed
+    vec3 emissive = texture(emissive_map, uv).rgb; // todo: remove when vulkano-shaders is fixed
+
+    float alpha = albedo.a * texture(opacity_map, uv).r * material_data.opacity;
+    if (alpha < material_data.alpha_cutoff) {
+        discard;
+    }
+    // fade out towards the ends of the projection
+    alpha *= decal_data.opacity * facing * (1.0 - smoothstep(0.4, 0.5, abs(local.y)));
+
+    vec3 n = mat3(T, B, N) * normalize(normal);
+
+    // the alpha is only used for blending, channels not written by decals are masked
+    normal_l_model = vec4(n * 0.5 + 0.5, alpha);
+    albedo_occlusion = vec4(material_data.albedo_color * albedo.rgb, alpha);
+    roughness_metallic = vec4(roughness, metallic, 0.0, alpha);
+}
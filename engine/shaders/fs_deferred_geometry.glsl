@@ -0,0 +1,82 @@
+#version 450
+#include "inc_structs.glsl"
+#include "inc_normal.glsl"
+#include "inc_dither.glsl"
+#include "inc_object_id.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in mat3 in_tbn;
+layout(location = 4) in vec4 in_sky;
+layout(location = 5) flat in vec2 in_fade;
+
+layout(location = 0) out vec4 normal_l_model;
+layout(location = 1) out vec4 albedo_occlusion;
+layout(location = 2) out vec4 roughness_metallic;
+layout(location = 3) out vec4 emission;
+layout(location = 4) out vec4 coat_anisotropy;
+
+// material textures
+layout(set = 1, binding = 0) uniform sampler2D albedo_map;
+layout(set = 1, binding = 1) uniform sampler2D normal_map;
+layout(set = 1, binding = 2) uniform sampler2D displacement_map;
+layout(set = 1, binding = 3) uniform sampler2D roughness_map;
+layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
+layout(set = 1, binding = 5) uniform sampler2D metallic_map;
+layout(std140, set = 1, binding = 6) uniform TheBlock {
+    MaterialData material_data;
+};
+layout(set = 1, binding = 7) uniform sampler2D opacity_map;
+layout(set = 1, binding = 8) uniform sampler2D emissive_map;
+
+void main() {
+    if (dither_discard(gl_FragCoord.xy, in_fade)) {
+        discard;
+    }
+
+    vec3 albedo = material_data.albedo_color * texture(albedo_map, in_uv).xyz;
+    vec3 normal = unpack_normal(texture(normal_map, in_uv), (material_data.flags & MATERIAL_NORMAL_MAP_AG) != 0u);
+    float roughness = material_data.roughness * texture(roughness_map, in_uv).r;
+    float metallic = material_data.metallic * texture(metallic_map, in_uv).r;
+    float occlusion = texture(occlusion_map, in_uv).r;
+    float opacity = texture(opacity_map, in_uv).r; // used only by the MASKED variant
+    float displacement = texture(displacement_map, in_uv).r; // todo: remove when vulkano-shaders is fixed
+    vec3 emissive = material_data.emissive_color * texture(emissive_map, in_uv).rgb;
+
+#ifdef MASKED
+    if (opacity < material_data.alpha_cutoff) {
+        discard;
+    }
+#endif
+
+    vec3 n = in_tbn * normalize(normal);
+    // back faces are drawn only by double-sided pipelines and face the other way
+    if (!gl_FrontFacing) {
+        n = -n;
+    }
+
+    // the lighting pass evaluates the extra lobes only for surfaces that need them
+    uint model = 0u;
+    if ((material_data.flags & MATERIAL_CLEAR_COAT) != 0u) {
+        model |= LIGHTING_CLEAR_COAT;
+    }
+    if ((material_data.flags & MATERIAL_ANISOTROPIC) != 0u) {
+        model |= LIGHTING_ANISOTROPIC;
+    }
+    // direction of the anisotropy is the tangent of the mesh projected to the shading plane
+    vec3 t = in_tbn[0] - n * dot(in_tbn[0], n);
+    t = dot(t, t) > 0.0 ? normalize(t) : in_tbn[0];
+
+    normal_l_model = vec4(n * 0.5 + 0.5, float(model) / 3.0);
+    albedo_occlusion = vec4(albedo, occlusion);
+    // sky visibility and the up component of the bent normal for the sky ambient term
+    roughness_metallic = vec4(roughness, metallic, in_sky.w, normalize(in_sky.xyz).y * 0.5 + 0.5);
+    // the lighting pass adds the lit color to the emission
+    emission = vec4(min(emissive, vec3(HALF_MAX)), 1.0);
+    coat_anisotropy = vec4(
+        material_data.clear_coat,
+        material_data.clear_coat_roughness,
+        material_data.anisotropy * 0.5 + 0.5,
+        pack_tangent(n, t)
+    );
+    write_object_id();
+}
\ No newline at end of file
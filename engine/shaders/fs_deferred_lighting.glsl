@@ -0,0 +1,136 @@
+#version 450
+#include "inc_structs.glsl"
+#include "inc_normal.glsl"
+#include "inc_brdf.glsl"
+
+layout(set = 1, binding = 0, input_attachment_index = 0) uniform subpassInput normal_l_model;
+layout(set = 1, binding = 1, input_attachment_index = 1) uniform subpassInput albedo_occlusion;
+layout(set = 1, binding = 2, input_attachment_index = 2) uniform subpassInput roughness_metallic;
+layout(set = 1, binding = 3, input_attachment_index = 3) uniform subpassInput depth;
+layout(set = 1, binding = 4, input_attachment_index = 4) uniform subpassInput coat_anisotropy;
+
+layout(location = 0) out vec4 hdr;
+
+layout(std140, set = 2, binding = 0) uniform Lights {
+    DirectionalLight lights[MAX_LIGHTS];
+} lights_ubo;
+
+layout(std140, set = 3, binding = 0) uniform SpotLights {
+    SpotLight lights[MAX_SPOT_LIGHTS];
+} spot_lights_ubo;
+
+layout(set = 3, binding = 1) uniform sampler2D gobos[MAX_GOBOS];
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+} frame_matrix_data;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 resolution;
+    uint light_count;
+    uint spot_light_count;
+    // hemispherical sky ambient light (color multiplied by intensity)
+    vec4 sky_ambient;
+    vec4 ground_ambient;
+} push_constants;
+
+// extract position from depth value
+vec3 PositionFromDepth(float depth) {
+    vec2 coord = gl_FragCoord.xy / push_constants.resolution;
+
+    vec4 clipSpacePosition = vec4(coord * 2.0 - 1.0, depth, 1.0);
+    vec4 viewSpacePosition = frame_matrix_data.invProjection * clipSpacePosition;
+    viewSpacePosition /= viewSpacePosition.w;
+    vec4 worldSpacePosition = frame_matrix_data.invView * viewSpacePosition;
+    return worldSpacePosition.xyz;
+}
+
+// color of the gobo projected by spot light to specified position (relative to the light)
+vec3 Gobo(SpotLight spot, vec3 local) {
+    if (spot.gobo < 0) {
+        return vec3(1.0);
+    }
+
+    vec3 up = abs(spot.direction.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(spot.direction, up));
+    up = cross(right, spot.direction);
+
+    float size = dot(local, spot.direction) * spot.tan_outer;
+    vec2 uv = vec2(dot(local, right), -dot(local, up)) / size * 0.5 + 0.5;
+
+    // indices must be constant without dynamic indexing of sampler arrays
+    switch (spot.gobo) {
+        case 0: return texture(gobos[0], uv).rgb;
+        case 1: return texture(gobos[1], uv).rgb;
+        case 2: return texture(gobos[2], uv).rgb;
+        default: return texture(gobos[3], uv).rgb;
+    }
+}
+
+// radiance of spot light arriving to specified position
+vec3 SpotLightRadiance(SpotLight spot, vec3 position, out vec3 L) {
+    vec3 local = position - spot.position;
+    float dist = length(local);
+    L = -local / dist;
+
+    // smooth cone falloff between inner and outer cone
+    float cosAngle = dot(-L, spot.direction);
+    float cone = smoothstep(spot.cos_outer, max(spot.cos_inner, spot.cos_outer + 0.0001), cosAngle);
+
+    // inverse square falloff windowed to reach zero at the range
+    float window = clamp(1.0 - pow(dist / spot.range, 4.0), 0.0, 1.0);
+    float attenuation = window * window / max(dist * dist, 0.0001);
+
+    return spot.color * Gobo(spot, local) * spot.intensity * cone * attenuation;
+}
+
+void main() {
+    /* load data from buffers */
+    vec4 b1 = subpassLoad(normal_l_model);
+    vec4 b2 = subpassLoad(albedo_occlusion);
+    vec4 b3 = subpassLoad(roughness_metallic);
+    float depth = subpassLoad(depth).x;
+    vec4 b4 = subpassLoad(coat_anisotropy);
+
+    /* unpack the individual components */
+    vec3 normal = b1.rgb * 2 - 1.0;
+    vec3 albedo = b2.rgb;
+    float occlusion = b2.a;
+    float roughness = clamp(b3.r, 0.0001, 1.0);// dissalow non-sensical 0 roughness
+    float metallic = b3.g;
+    float sky_visibility = b3.b;
+    float bent_normal_up = b3.a;
+    uint model = uint(b1.a * 3.0 + 0.5);
+    float clear_coat_roughness = clamp(b4.g, 0.045, 1.0);
+    vec3 position = PositionFromDepth(depth);
+
+    /* remap roughness */
+    roughness = roughness * roughness;
+    clear_coat_roughness = clear_coat_roughness * clear_coat_roughness;
+
+    vec3 N = normalize(normal);
+    vec3 V = normalize(frame_matrix_data.cameraPosition.xyz - position);
+    vec3 T = unpack_tangent(N, b4.a);
+
+    Surface surface = Surface(N, T, cross(N, T), roughness, albedo, metallic, model, b4.b * 2.0 - 1.0, b4.r, clear_coat_roughness);
+
+    vec3 result = vec3(0.0);
+    for (uint i = 0; i < push_constants.light_count; i++) {
+        result += (light_surface(surface, lights_ubo.lights[i].direction, V, lights_ubo.lights[i].color) * lights_ubo.lights[i].intensity * occlusion);
+    }
+    for (uint i = 0; i < push_constants.spot_light_count; i++) {
+        vec3 L;
+        vec3 radiance = SpotLightRadiance(spot_lights_ubo.lights[i], position, L);
+        result += light_surface(surface, L, V, radiance) * occlusion;
+    }
+
+    // sky ambient arriving from the unoccluded directions around the bent normal
+    vec3 ambient = mix(push_constants.ground_ambient.rgb, push_constants.sky_ambient.rgb, bent_normal_up);
+    result += ambient * albedo * (1.0 - metallic) * sky_visibility * occlusion;
+
+    hdr = vec4(min(result, vec3(HALF_MAX)), 1.0);
+}
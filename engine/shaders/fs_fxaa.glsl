@@ -9,15 +9,13 @@ layout(std140, push_constant) uniform PushConstants {
 } push_constants;
 
 
-#ifndef FXAA_REDUCE_MIN
+// quality preset, specialized when the pipeline is created (see `FxaaQuality`)
+layout(constant_id = 0) const float span_max = 8.0;
+layout(constant_id = 1) const float reduce_mul = 0.125;
+
 #define FXAA_REDUCE_MIN   (1.0/ 128.0)
-#endif
-#ifndef FXAA_REDUCE_MUL
-#define FXAA_REDUCE_MUL   (1.0 / 8.0)
-#endif
-#ifndef FXAA_SPAN_MAX
-#define FXAA_SPAN_MAX     8.0// 8.0
-#endif
+#define FXAA_REDUCE_MUL   reduce_mul
+#define FXAA_SPAN_MAX     span_max
 
 void texcoords(vec2 fragCoord, vec2 resolution, out vec2 v_rgbNW, out vec2 v_rgbNE, out vec2 v_rgbSW, out vec2 v_rgbSE, out vec2 v_rgbM) {
     vec2 inverseVP = 1.0 / resolution.xy;
@@ -87,6 +85,11 @@ vec4 fxaa_apply(sampler2D tex, vec2 fragCoord, vec2 resolution) {
 
 void main() {
     vec2 resolution = push_constants.resolution;
+    // zero span disables the anti-aliasing, the branch is removed by specialization
+    if (span_max == 0.0) {
+        f_color = vec4(texture(tex, gl_FragCoord.xy / resolution).rgb, 1);
+        return;
+    }
     vec3 color = fxaa_apply(tex, gl_FragCoord.xy, resolution).rgb;
     f_color = vec4(color, 1);
     // f_color = vec4(texture(tex, gl_FragCoord.xy / resolution).xyz, 1);
@@ -0,0 +1,35 @@
+#version 450
+#include "inc_color.glsl"
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D tex;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 resolution;
+    float time;
+    float intensity;
+} push_constants;
+
+// film grain changes 24 times per second regardless of the frame rate
+const float GRAIN_FPS = 24.0;
+
+float hash(vec3 p) {
+    p = fract(p * vec3(443.897, 441.423, 437.195));
+    p += dot(p, p.yzx + 19.19);
+    return fract((p.x + p.y) * p.z);
+}
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / push_constants.resolution;
+    vec3 color = texture(tex, uv).rgb;
+
+    float frame = floor(push_constants.time * GRAIN_FPS);
+    float noise = hash(vec3(gl_FragCoord.xy, frame)) - 0.5;
+
+    // grain is most visible in mid-tones, like on film
+    float response = 1.0 - abs(luminance(color) * 2.0 - 1.0);
+    color += noise * push_constants.intensity * response;
+
+    f_color = vec4(max(color, vec3(0.0)), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+
+layout(set = 0, binding = 0) uniform sampler2D font_texture;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    // color has premultiplied alpha, the font texture contains only coverage
+    f_color = color * texture(font_texture, uv).r;
+}
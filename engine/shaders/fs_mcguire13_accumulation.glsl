@@ -1,5 +1,6 @@
 #version 450
 #include "inc_structs.glsl"
+#include "inc_normal.glsl"
 #include "inc_brdf.glsl"
 
 layout(location = 0) in vec2 in_uv;
@@ -35,19 +36,12 @@ layout(set = 1, binding = 3) uniform sampler2D roughness_map;
 layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
 layout(set = 1, binding = 5) uniform sampler2D metallic_map;
 layout(set = 1, binding = 7) uniform sampler2D opacity_map;
+layout(set = 1, binding = 8) uniform sampler2D emissive_map;
 
 layout(std140, set = 1, binding = 6) uniform TheBlock {
     MaterialData material_data;
 };
 
-// unpacks normal from DXT5nm format
-vec3 unpack_normal(vec4 packednormal) {
-    vec3 normal;
-    normal.xy = packednormal.wy * 2 - 1;
-    normal.z = sqrt(1.0 - clamp(dot(normal.xy, normal.xy), 0.0, 1.0));
-    return normal;
-}
-
 float w7(float z, float alpha) {
     float n1 = abs(z) / 5;
     float n1_2 = n1 * n1;
@@ -95,6 +89,7 @@ void main() {
     float occlusion = texture(occlusion_map, in_uv).r;
     float opacity = material_data.opacity * texture(opacity_map, in_uv).r;
     float displacement = texture(displacement_map, in_uv).r;// todo: remove when vulkano-shaders is fixed
+    vec3 emissive = material_data.emissive_color * texture(emissive_map, in_uv).rgb;
     vec3 position = in_wsPosition;
 
     /* normal mapping */
@@ -118,10 +113,10 @@ void main() {
         lighting += diffuse(roughness, albedo) + specular(roughness, albedo, metallic, H, NdotV, NdotL, NdotH, LdotH) * lights_ubo.lights[i].color * NdotL;
     }
 
-    vec3 Ci = lighting * opacity;
+    vec3 Ci = (lighting + emissive) * opacity;
     float ai = opacity;
     float zi = gl_FragCoord.z;
 
-    accum = vec4(Ci, ai) * w10(zi, ai);
+    accum = min(vec4(Ci, ai) * w10(zi, ai), vec4(HALF_MAX));
     reveal = vec4(ai);
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+#version 450
+#include "inc_normal.glsl"
+#include "inc_dither.glsl"
+#include "inc_object_id.glsl"
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in mat3 in_tbn;
+layout(location = 4) flat in vec2 in_fade;
+
+layout(location = 0) out vec4 normal_l_model;
+layout(location = 1) out vec4 albedo_occlusion;
+layout(location = 2) out vec4 roughness_metallic;
+layout(location = 3) out vec4 emission;
+layout(location = 4) out vec4 coat_anisotropy;
+
+// weights of the layers in the channels
+layout(set = 1, binding = 1) uniform sampler2D splat_map;
+layout(set = 1, binding = 2) uniform sampler2D albedo_map0;
+layout(set = 1, binding = 3) uniform sampler2D albedo_map1;
+layout(set = 1, binding = 4) uniform sampler2D albedo_map2;
+layout(set = 1, binding = 5) uniform sampler2D albedo_map3;
+layout(set = 1, binding = 6) uniform sampler2D normal_map0;
+layout(set = 1, binding = 7) uniform sampler2D normal_map1;
+layout(set = 1, binding = 8) uniform sampler2D normal_map2;
+layout(set = 1, binding = 9) uniform sampler2D normal_map3;
+layout(std140, set = 1, binding = 10) uniform TerrainData {
+    // tiling, roughness, metallic, normal map in AG (DXT5nm)
+    vec4 layers[4];
+    float height_scale;
+    uint gpu_displacement;
+    float size;
+} terrain;
+
+vec3 albedo = vec3(0.0);
+vec3 normal = vec3(0.0);
+float roughness = 0.0;
+float metallic = 0.0;
+
+// adds the layer with the `weight` to the blended material
+void blend(int layer, float weight, sampler2D albedo_map, sampler2D normal_map) {
+    vec2 uv = in_uv * terrain.layers[layer].x;
+    albedo += weight * texture(albedo_map, uv).rgb;
+    normal += weight * unpack_normal(texture(normal_map, uv), terrain.layers[layer].w > 0.0);
+    roughness += weight * terrain.layers[layer].y;
+    metallic += weight * terrain.layers[layer].z;
+}
+
+void main() {
+    if (dither_discard(gl_FragCoord.xy, in_fade)) {
+        discard;
+    }
+
+    vec2 dims = vec2(textureSize(splat_map, 0));
+    vec4 weights = texture(splat_map, (in_uv * (dims - 1.0) + 0.5) / dims);
+    float total = dot(weights, vec4(1.0));
+    weights = total > 0.0 ? weights / total : vec4(1.0, 0.0, 0.0, 0.0);
+
+    blend(0, weights.x, albedo_map0, normal_map0);
+    blend(1, weights.y, albedo_map1, normal_map1);
+    blend(2, weights.z, albedo_map2, normal_map2);
+    blend(3, weights.w, albedo_map3, normal_map3);
+
+    vec3 n = in_tbn * normalize(normal);
+
+    normal_l_model = vec4(n * 0.5 + 0.5, 0);
+    albedo_occlusion = vec4(albedo, 1.0);
+    // the terrain has no baked sky occlusion, its bent normal is the normal
+    roughness_metallic = vec4(roughness, metallic, 1.0, normalize(in_tbn[2]).y * 0.5 + 0.5);
+    emission = vec4(0.0, 0.0, 0.0, 1.0);
+    coat_anisotropy = vec4(0.0);
+    write_object_id();
+}
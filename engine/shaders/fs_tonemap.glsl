@@ -0,0 +1,23 @@
+#version 450
+#include "inc_color.glsl"
+
+layout(set = 0, binding = 0) uniform sampler2D hdr_buffer;
+layout(set = 0, binding = 1) uniform sampler2D bloom_buffer;
+
+layout(std140, push_constant) uniform PushConstants {
+    vec2 resolution;
+    float bloom_intensity;
+} push_constants;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / push_constants.resolution;
+    vec3 hdr = texture(hdr_buffer, uv).rgb;
+    // bloom buffer is not rendered when bloom is disabled
+    if (push_constants.bloom_intensity > 0.0) {
+        hdr += texture(bloom_buffer, uv).rgb * push_constants.bloom_intensity;
+    }
+    vec3 ldr = ACESFilm(hdr);
+    f_color = vec4(ldr, 1.0);
+}
\ No newline at end of file
@@ -1,5 +1,6 @@
 #version 450
 #include "inc_structs.glsl"
+#include "inc_normal.glsl"
 #include "inc_brdf.glsl"
 
 layout(location = 0) in vec2 in_uv;
@@ -34,19 +35,12 @@ layout(set = 1, binding = 3) uniform sampler2D roughness_map;
 layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
 layout(set = 1, binding = 5) uniform sampler2D metallic_map;
 layout(set = 1, binding = 7) uniform sampler2D opacity_map;
+layout(set = 1, binding = 8) uniform sampler2D emissive_map;
 
 layout(std140, set = 1, binding = 6) uniform TheBlock {
     MaterialData material_data;
 };
 
-// unpacks normal from DXT5nm format
-vec3 unpack_normal(vec4 packednormal) {
-    vec3 normal;
-    normal.xy = packednormal.wy * 2 - 1;
-    normal.z = sqrt(1.0 - clamp(dot(normal.xy, normal.xy), 0.0, 1.0));
-    return normal;
-}
-
 void main() {
     vec3 albedo = material_data.albedo_color * texture(albedo_map, in_uv).xyz;
     //vec3 normal = texture(normal_map, in_uv).xyz;
@@ -55,6 +49,7 @@ void main() {
     float occlusion = texture(occlusion_map, in_uv).r;
     float opacity = material_data.opacity * texture(opacity_map, in_uv).r;
     float displacement = texture(displacement_map, in_uv).r;// todo: remove when vulkano-shaders is fixed
+    vec3 emissive = material_data.emissive_color * texture(emissive_map, in_uv).rgb;
     vec3 position = in_wsPosition;
 
     /* normal mapping */
@@ -79,5 +74,5 @@ void main() {
     }
 
     // todo: toto je zle, lebo to zoslabi aj odlesky svetla, ktore by nemali byt priesvitne
-    hdr = vec4(lighting, opacity);
+    hdr = vec4(lighting + emissive, opacity);
 }
\ No newline at end of file
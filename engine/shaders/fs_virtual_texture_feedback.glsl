@@ -0,0 +1,27 @@
+#version 450
+#include "inc_virtual_texture.glsl"
+
+layout(location = 0) in vec2 uv0;
+
+layout(location = 0) out vec4 feedback;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model_view_projection;
+    float size;
+    float page_size;
+    float border;
+    float cache_size;
+    float mip_count;
+    float mip_bias;
+} push_constants;
+
+void main() {
+    VirtualTextureParams vt = VirtualTextureParams(
+        push_constants.size,
+        push_constants.page_size,
+        push_constants.border,
+        push_constants.cache_size,
+        push_constants.mip_count
+    );
+    feedback = vt_feedback(uv0, vt, push_constants.mip_bias);
+}
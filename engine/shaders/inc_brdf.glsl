@@ -0,0 +1,141 @@
+// ggx distribution term
+float D_GGX(float roughness, float NdotH, const vec3 H) {
+    float oneMinusNoHSquared = 1.0 - NdotH * NdotH;
+    float a = NdotH * roughness;
+    float k = roughness / (oneMinusNoHSquared + a * a);
+    float d = k * k * (1.0 / 3.14159);
+    return d;
+}
+
+float distribution(float roughness, float NdotH, const vec3 H) {
+    return D_GGX(roughness, NdotH, H);
+}
+
+float V_SmithGGXCorrelated(float roughness, float NdotV, float NdotL) {
+    float a2 = roughness * roughness;
+    float GGXV = NdotL * sqrt((NdotV - a2 * NdotV) * NdotV + a2);
+    float GGXL = NdotV * sqrt((NdotL - a2 * NdotL) * NdotL + a2);
+    return 0.5 / (GGXV + GGXL);
+}
+
+float visibility(float roughness, float NoV, float NoL) {
+    return V_SmithGGXCorrelated(roughness, NoV, NoL);
+}
+
+// anisotropic ggx distribution term with roughness `at` along the tangent and `ab`
+// along the bitangent
+float D_GGX_Anisotropic(float at, float ab, float TdotH, float BdotH, float NdotH) {
+    float a2 = at * ab;
+    vec3 d = vec3(ab * TdotH, at * BdotH, a2 * NdotH);
+    float d2 = dot(d, d);
+    float b2 = a2 / d2;
+    return a2 * b2 * b2 * (1.0 / 3.14159);
+}
+
+float V_SmithGGXCorrelated_Anisotropic(float at, float ab, float TdotV, float BdotV, float TdotL, float BdotL, float NdotV, float NdotL) {
+    float lambdaV = NdotL * length(vec3(at * TdotV, ab * BdotV, NdotV));
+    float lambdaL = NdotV * length(vec3(at * TdotL, ab * BdotL, NdotL));
+    return 0.5 / (lambdaV + lambdaL);
+}
+
+// visibility term of the clear coat layer (Kelemen 2001)
+float V_Kelemen(float LdotH) {
+    return 0.25 / max(LdotH * LdotH, 0.0001);
+}
+
+vec3 F_Schlick(const vec3 F0, float F90, float VdotH) {
+    return F0 + (F90 - F0) * pow(1.0 - VdotH, 5);
+}
+
+vec3 fresnel(const vec3 F0, float LdotH) {
+    float f90 = clamp(dot(F0, vec3(50.0 * 0.33)), 0.0, 1.0);// todo: replace with material property
+    return F_Schlick(F0, f90, LdotH);
+}
+
+vec3 specular(float roughness, vec3 albedo, float metallic, const vec3 h, float NdotV, float NdotL, float NdotH, float LdotH) {
+    const vec3 dielectricSpecular = vec3(0.04, 0.04, 0.04);
+    vec3 F0 = mix(dielectricSpecular, albedo, metallic);
+
+    float D = distribution(roughness, NdotH, h);
+    float V = visibility(roughness, NdotV, NdotL);
+    vec3  F = fresnel(F0, LdotH);
+
+    return (D * V) * F;
+}
+
+vec3 diffuse(float roughness, vec3 albedo) {
+    return albedo / 3.14159;
+}
+
+vec3 light(vec3 N, vec3 L, vec3 V, vec3 lightColor, float roughness, vec3 albedo, float metallic) {
+    vec3 H = normalize(L + V);
+
+    float NdotV = clamp(dot(N, V), 0.0001, 1.0);
+    float NdotL = clamp(dot(N, L), 0.0, 1.0);
+    float NdotH = clamp(dot(N, H), 0.0, 1.0);
+    float LdotH = clamp(dot(L, H), 0.0, 1.0);
+
+    vec3 specular = specular(roughness, albedo, metallic, H, NdotV, NdotL, NdotH, LdotH);
+    vec3 diffuse = diffuse(roughness, albedo);
+
+    vec3 color = diffuse * (1 - metallic) + mix(specular, specular * albedo, metallic);
+
+    return (color * lightColor) * NdotL;
+}
+
+// surface read from the g-buffer by the deferred lighting
+struct Surface {
+    vec3 N;
+    // tangent and bitangent (used by the anisotropic model)
+    vec3 T;
+    vec3 B;
+    float roughness;
+    vec3 albedo;
+    float metallic;
+    // bits of the lighting model (LIGHTING_CLEAR_COAT, LIGHTING_ANISOTROPIC)
+    uint model;
+    float anisotropy;
+    float clear_coat;
+    float clear_coat_roughness;
+};
+
+// light with optional anisotropic specular lobe and clear coat layer
+vec3 light_surface(Surface s, vec3 L, vec3 V, vec3 lightColor) {
+    if (s.model == 0u) {
+        return light(s.N, L, V, lightColor, s.roughness, s.albedo, s.metallic);
+    }
+
+    vec3 H = normalize(L + V);
+
+    float NdotV = clamp(dot(s.N, V), 0.0001, 1.0);
+    float NdotL = clamp(dot(s.N, L), 0.0, 1.0);
+    float NdotH = clamp(dot(s.N, H), 0.0, 1.0);
+    float LdotH = clamp(dot(L, H), 0.0, 1.0);
+
+    const vec3 dielectricSpecular = vec3(0.04, 0.04, 0.04);
+    vec3 F0 = mix(dielectricSpecular, s.albedo, s.metallic);
+
+    float DV;
+    if ((s.model & LIGHTING_ANISOTROPIC) != 0u) {
+        float at = max(s.roughness * (1.0 + s.anisotropy), 0.0001);
+        float ab = max(s.roughness * (1.0 - s.anisotropy), 0.0001);
+        float D = D_GGX_Anisotropic(at, ab, dot(s.T, H), dot(s.B, H), NdotH);
+        float Vis = V_SmithGGXCorrelated_Anisotropic(at, ab, dot(s.T, V), dot(s.B, V), dot(s.T, L), dot(s.B, L), NdotV, NdotL);
+        DV = D * Vis;
+    } else {
+        DV = distribution(s.roughness, NdotH, H) * visibility(s.roughness, NdotV, NdotL);
+    }
+
+    vec3 specular = DV * fresnel(F0, LdotH);
+    vec3 diffuse = diffuse(s.roughness, s.albedo);
+    vec3 color = diffuse * (1 - s.metallic) + mix(specular, specular * s.albedo, s.metallic);
+
+    if ((s.model & LIGHTING_CLEAR_COAT) != 0u) {
+        // energy reflected by the coat does not reach the base layer
+        float Fc = F_Schlick(dielectricSpecular, 1.0, LdotH).x * s.clear_coat;
+        float Dc = distribution(s.clear_coat_roughness, NdotH, H);
+        color = color * (1.0 - Fc) + vec3(Dc * V_Kelemen(LdotH) * Fc);
+    }
+
+    return (color * lightColor) * NdotL;
+}
@@ -0,0 +1,18 @@
+// ordered 4x4 (bayer) dither thresholds from 0 to 1
+float dither_threshold(vec2 frag_coord) {
+    const float bayer[16] = float[16](
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    ivec2 p = ivec2(frag_coord) & 3;
+    return (bayer[p.y * 4 + p.x] + 0.5) / 16.0;
+}
+
+// whether the pixel should be discarded by the screen-door fade which keeps only
+// pixels with dither threshold in the range [fade.x, fade.y) (see `render::lod_fade`)
+bool dither_discard(vec2 frag_coord, vec2 fade) {
+    float threshold = dither_threshold(frag_coord);
+    return threshold < fade.x || threshold >= fade.y;
+}
@@ -0,0 +1,51 @@
+// unpacks normal from BC5 (RG) or DXT5nm (AG) format, the format is known
+// from the image asset (`MATERIAL_NORMAL_MAP_AG`), not from the texel
+vec3 unpack_normal(vec4 packednormal, bool ag) {
+    vec3 normal;
+    normal.xy = (ag ? packednormal.ag : packednormal.rg) * 2 - 1;
+    normal.z = sqrt(1.0 - clamp(dot(normal.xy, normal.xy), 0.0, 1.0));
+    return normal;
+}
+
+// unpacks sky visibility and bent normal baked into the last component of the
+// tangent (integer packed by `bf::mesh::pack_sky_occlusion`); zero means the
+// vertex is not occluded and its bent normal is the vertex normal
+float unpack_sky_occlusion(float packed, vec3 normal, out vec3 bent_normal) {
+    uint bits = uint(packed);
+    uint occlusion = bits & 63u;
+    if (occlusion == 0u) {
+        bent_normal = normal;
+        return 1.0;
+    }
+
+    vec2 p = vec2((bits >> 6) & 511u, (bits >> 15) & 511u) / 511.0 * 2.0 - 1.0;
+    vec3 n = vec3(p, 1.0 - abs(p.x) - abs(p.y));
+    float t = max(-n.z, 0.0);
+    n.xy += vec2(n.x >= 0.0 ? -t : t, n.y >= 0.0 ? -t : t);
+    bent_normal = normalize(n);
+    return 1.0 - float(occlusion) / 63.0;
+}
+
+// orthonormal basis of the plane perpendicular to the normal (Duff et al. 2017)
+void tangent_basis(vec3 n, out vec3 b1, out vec3 b2) {
+    float s = n.z >= 0.0 ? 1.0 : -1.0;
+    float a = -1.0 / (s + n.z);
+    float b = n.x * n.y * a;
+    b1 = vec3(1.0 + s * n.x * n.x * a, s * b, -s * n.x);
+    b2 = vec3(b, s + n.y * n.y * a, -n.y);
+}
+
+// packs direction of the tangent `t` perpendicular to the normal `n` into [0, 1]
+// as its angle in `tangent_basis`; the sign of the tangent is not preserved
+float pack_tangent(vec3 n, vec3 t) {
+    vec3 b1, b2;
+    tangent_basis(n, b1, b2);
+    return fract(atan(dot(t, b2), dot(t, b1)) / 3.14159265);
+}
+
+vec3 unpack_tangent(vec3 n, float packed) {
+    vec3 b1, b2;
+    tangent_basis(n, b1, b2);
+    float angle = packed * 3.14159265;
+    return cos(angle) * b1 + sin(angle) * b2;
+}
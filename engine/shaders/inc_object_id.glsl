@@ -0,0 +1,15 @@
+// object ID buffer of editor builds (see `render::object_id`), the ID of the
+// drawn object is passed by the draw call and zero means that no object is there
+#ifdef OBJECT_ID
+layout(location = 5) out uint out_object_id;
+
+layout(push_constant) uniform PushConstants {
+    uint object_id;
+} push_constants;
+#endif
+
+void write_object_id() {
+#ifdef OBJECT_ID
+    out_object_id = push_constants.object_id;
+#endif
+}
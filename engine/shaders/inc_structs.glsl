@@ -0,0 +1,47 @@
+const uint MAX_LIGHTS = 100;
+const uint MAX_SPOT_LIGHTS = 32;
+const int MAX_GOBOS = 4;
+// largest finite value of 16-bit float render targets
+const float HALF_MAX = 65504.0;
+
+struct MaterialData {
+    vec3 albedo_color;
+    float alpha_cutoff;
+    float roughness;
+    float metallic;
+    float opacity;
+    float ior;
+    vec3 emissive_color;
+    float clear_coat;
+    float clear_coat_roughness;
+    float anisotropy;
+    uint flags;
+};
+
+// bits of `MaterialData::flags` (see `bf::material::MaterialFlags`)
+const uint MATERIAL_CLEAR_COAT = 4u;
+const uint MATERIAL_ANISOTROPIC = 8u;
+// set by the engine when the normal map is DXT5nm (see `render::ubo`)
+const uint MATERIAL_NORMAL_MAP_AG = 0x80000000u;
+
+// bits of the lighting model stored in the alpha of GBuffer 1 (two bits)
+const uint LIGHTING_CLEAR_COAT = 1u;
+const uint LIGHTING_ANISOTROPIC = 2u;
+
+struct DirectionalLight {
+    vec3 direction;
+    float intensity;
+    vec3 color;
+};
+
+struct SpotLight {
+    vec3 position;
+    float range;
+    vec3 direction;
+    float intensity;
+    vec3 color;
+    float cos_inner;
+    float cos_outer;
+    float tan_outer;
+    int gobo;
+};
@@ -0,0 +1,58 @@
+// virtual texturing: sampling through the page table and encoding of page
+// requests for the feedback pass (see `render::virtual_texture` module)
+
+struct VirtualTextureParams {
+    // size of the biggest level and of one page (without border) in pixels
+    float size;
+    float page_size;
+    // pixels of neighbouring pages on each side of the page
+    float border;
+    // size of the physical page cache texture in pixels
+    float cache_size;
+    float mip_count;
+};
+
+// level of the virtual texture with texels of the size of one pixel at `uv`
+float vt_mip(vec2 uv, VirtualTextureParams vt) {
+    vec2 dx = dFdx(uv * vt.size);
+    vec2 dy = dFdy(uv * vt.size);
+    return 0.5 * log2(max(dot(dx, dx), dot(dy, dy)));
+}
+
+// number of pages in one row of the level `mip`
+float vt_pages(float mip, VirtualTextureParams vt) {
+    return vt.size / vt.page_size / exp2(mip);
+}
+
+// samples the virtual texture at `uv` from the page of the needed level or the
+// closest resident coarser page (the page table must use nearest filtering)
+vec4 vt_sample(sampler2D page_table, sampler2D cache, vec2 uv, VirtualTextureParams vt) {
+    uv = clamp(uv, 0.0, 1.0);
+    float mip = clamp(floor(vt_mip(uv, vt)), 0.0, vt.mip_count - 1.0);
+    vec4 entry = floor(textureLod(page_table, uv, mip) * 255.0 + 0.5);
+    if (entry.a == 0.0) {
+        return vec4(0.0);
+    }
+
+    // entry contains position of the slot in the cache and level of the resident page
+    vec2 in_page = fract(uv * vt_pages(entry.b, vt));
+    float padded = vt.page_size + 2.0 * vt.border;
+    vec2 texel = entry.rg * padded + vt.border + in_page * vt.page_size;
+    return textureLod(cache, texel / vt.cache_size, 0.0);
+}
+
+// encodes the page needed at `uv` into the feedback buffer, `mip_bias` compensates
+// lower resolution of the feedback buffer (see `decode_feedback`)
+vec4 vt_feedback(vec2 uv, VirtualTextureParams vt, float mip_bias) {
+    uv = clamp(uv, 0.0, 1.0);
+    float mip = clamp(floor(vt_mip(uv, vt) + mip_bias), 0.0, vt.mip_count - 1.0);
+    float pages = vt_pages(mip, vt);
+    uvec2 page = uvec2(min(uv * pages, vec2(pages - 1.0)));
+    uvec4 encoded = uvec4(
+        page.x & 255u,
+        page.y & 255u,
+        (page.x >> 8u) | ((page.y >> 8u) << 4u),
+        uint(mip) + 1u
+    );
+    return vec4(encoded) / 255.0;
+}
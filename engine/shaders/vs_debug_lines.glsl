@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 out_color;
+
+// positions are relative to the camera, so the view contains only its rotation
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+} push_constants;
+
+void main() {
+    out_color = color;
+    gl_Position = push_constants.view_projection * vec4(position, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec4 position;
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+} frame_matrix_data;
+
+layout(std140, set = 2, binding = 0) uniform DecalData {
+    mat4 model;
+    mat4 inv_model;
+    float opacity;
+} decal_data;
+
+void main() {
+    gl_Position = frame_matrix_data.projection * frame_matrix_data.view * decal_data.model * vec4(position.xyz, 1.0);
+}
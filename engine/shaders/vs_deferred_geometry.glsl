@@ -1,4 +1,5 @@
 #version 450
+#include "inc_normal.glsl"
 
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec3 normal;
@@ -7,6 +8,10 @@ layout(location = 3) in vec4 tangent;
 
 layout(location = 0) out vec2 uv0;
 layout(location = 1) out mat3 tbn0;
+// world-space bent normal (xyz) and sky visibility (w)
+layout(location = 4) out vec4 sky0;
+// range of dither thresholds of the cross-fade
+layout(location = 5) flat out vec2 fade0;
 
 layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
     mat4 view;
@@ -18,14 +23,20 @@ layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
 
 layout(std140, set = 2, binding = 0) uniform ObjectMatrixData {
     mat4 model;
+    mat4 normal;
+    vec2 fade;
 } object_matrix_data;
 
 void main() {
     vec3 T = normalize((object_matrix_data.model * vec4(tangent.xyz, 0.0)).xyz);
-    vec3 N = normalize((object_matrix_data.model * vec4(normal, 0.0)).xyz);
+    vec3 N = normalize(mat3(object_matrix_data.normal) * normal);
     T = normalize(T - dot(T, N) * N);
     vec3 B = cross(N, T);
     tbn0 = mat3(T, B, N);
+    vec3 bent;
+    float visibility = unpack_sky_occlusion(tangent.w, normal, bent);
+    sky0 = vec4(normalize(mat3(object_matrix_data.normal) * bent), visibility);
     uv0 = uv;
+    fade0 = object_matrix_data.fade;
     gl_Position = frame_matrix_data.projection * frame_matrix_data.view * object_matrix_data.model * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,63 @@
+#version 450
+#include "inc_normal.glsl"
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 tangent;
+// 0.0 = moved by the wind the most, 1.0 = rigid
+layout(location = 4) in float stiffness;
+
+layout(location = 0) out vec2 uv0;
+layout(location = 1) out mat3 tbn0;
+// world-space bent normal (xyz) and sky visibility (w)
+layout(location = 4) out vec4 sky0;
+// range of dither thresholds of the cross-fade
+layout(location = 5) flat out vec2 fade0;
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+    float time;
+    // horizontal direction (xy), strength (z) and frequency (w) of the wind
+    vec4 wind;
+} frame_matrix_data;
+
+layout(std140, set = 2, binding = 0) uniform ObjectMatrixData {
+    mat4 model;
+    mat4 normal;
+    vec2 fade;
+} object_matrix_data;
+
+const float TAU = 6.28318530718;
+
+// Displacement of the vertex by the wind. The phase depends on the local position
+// so the parts of the plant do not move in sync, tips also flutter faster.
+vec3 wind_displacement(vec3 local_position) {
+    vec4 wind = frame_matrix_data.wind;
+    float sway = 1.0 - clamp(stiffness, 0.0, 1.0);
+    float phase = dot(local_position, vec3(0.7, 0.3, 0.5));
+    float t = TAU * wind.w * frame_matrix_data.time;
+    float wave = 0.6 + 0.4 * sin(t + phase) + 0.15 * sin(3.7 * t + 4.0 * phase) * sway;
+    // quadratic falloff bends the plant instead of shearing it
+    return vec3(wind.x, 0.0, wind.y) * wind.z * wave * sway * sway;
+}
+
+void main() {
+    vec3 T = normalize((object_matrix_data.model * vec4(tangent.xyz, 0.0)).xyz);
+    vec3 N = normalize(mat3(object_matrix_data.normal) * normal);
+    T = normalize(T - dot(T, N) * N);
+    vec3 B = cross(N, T);
+    tbn0 = mat3(T, B, N);
+    vec3 bent;
+    float visibility = unpack_sky_occlusion(tangent.w, normal, bent);
+    sky0 = vec4(normalize(mat3(object_matrix_data.normal) * bent), visibility);
+    uv0 = uv;
+    fade0 = object_matrix_data.fade;
+    vec4 world = object_matrix_data.model * vec4(position, 1.0);
+    world.xyz += wind_displacement(position);
+    gl_Position = frame_matrix_data.projection * frame_matrix_data.view * world;
+}
@@ -0,0 +1,48 @@
+#version 450
+#include "inc_normal.glsl"
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 tangent;
+
+// per-instance model matrix (columns)
+layout(location = 4) in vec4 model_x;
+layout(location = 5) in vec4 model_y;
+layout(location = 6) in vec4 model_z;
+layout(location = 7) in vec4 model_w;
+// per-instance normal matrix (columns)
+layout(location = 8) in vec3 normal_x;
+layout(location = 9) in vec3 normal_y;
+layout(location = 10) in vec3 normal_z;
+// per-instance range of dither thresholds of the cross-fade
+layout(location = 11) in vec2 fade;
+
+layout(location = 0) out vec2 uv0;
+layout(location = 1) out mat3 tbn0;
+// world-space bent normal (xyz) and sky visibility (w)
+layout(location = 4) out vec4 sky0;
+layout(location = 5) flat out vec2 fade0;
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+} frame_matrix_data;
+
+void main() {
+    mat4 model = mat4(model_x, model_y, model_z, model_w);
+    vec3 T = normalize((model * vec4(tangent.xyz, 0.0)).xyz);
+    vec3 N = normalize(mat3(normal_x, normal_y, normal_z) * normal);
+    T = normalize(T - dot(T, N) * N);
+    vec3 B = cross(N, T);
+    tbn0 = mat3(T, B, N);
+    vec3 bent;
+    float visibility = unpack_sky_occlusion(tangent.w, normal, bent);
+    sky0 = vec4(normalize(mat3(normal_x, normal_y, normal_z) * bent), visibility);
+    uv0 = uv;
+    fade0 = fade;
+    gl_Position = frame_matrix_data.projection * frame_matrix_data.view * model * vec4(position, 1.0);
+}
\ No newline at end of file
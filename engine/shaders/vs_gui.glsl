@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push_constants;
+
+void main() {
+    out_uv = uv;
+    out_color = color;
+    // position is in points with origin in the top-left corner
+    gl_Position = vec4(2.0 * position / push_constants.screen_size - 1.0, 0.0, 1.0);
+}
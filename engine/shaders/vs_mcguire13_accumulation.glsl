@@ -20,11 +20,12 @@ layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
 
 layout(std140, set = 2, binding = 0) uniform ObjectMatrixData {
     mat4 model;
+    mat4 normal;
 } object_matrix_data;
 
 void main() {
     vec3 T = normalize((object_matrix_data.model * vec4(tangent.xyz, 0.0)).xyz);
-    vec3 N = normalize((object_matrix_data.model * vec4(normal, 0.0)).xyz);
+    vec3 N = normalize(mat3(object_matrix_data.normal) * normal);
     T = normalize(T - dot(T, N) * N);
     vec3 B = cross(N, T);
     tbn0 = mat3(T, B, N);
@@ -0,0 +1,63 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 tangent;
+
+// terrain coordinates (0 to 1 across the whole terrain)
+layout(location = 0) out vec2 uv0;
+layout(location = 1) out mat3 tbn0;
+// range of dither thresholds of the cross-fade
+layout(location = 4) flat out vec2 fade0;
+
+layout(std140, set = 0, binding = 0) uniform FrameMatrixData {
+    mat4 view;
+    mat4 projection;
+    mat4 invProjection;
+    mat4 invView;
+    vec3 cameraPosition;
+} frame_matrix_data;
+
+layout(set = 1, binding = 0) uniform sampler2D heightmap;
+layout(std140, set = 1, binding = 10) uniform TerrainData {
+    vec4 layers[4];
+    float height_scale;
+    uint gpu_displacement;
+    float size;
+} terrain;
+
+layout(std140, set = 2, binding = 0) uniform ObjectMatrixData {
+    mat4 model;
+    mat4 normal;
+    vec2 fade;
+} object_matrix_data;
+
+// height at the terrain coordinates, texels are at the corners of the grid
+float height(vec2 uv) {
+    vec2 dims = vec2(textureSize(heightmap, 0));
+    vec2 st = (clamp(uv, 0.0, 1.0) * (dims - 1.0) + 0.5) / dims;
+    return textureLod(heightmap, st, 0.0).r * terrain.height_scale;
+}
+
+void main() {
+    vec3 p = position;
+    vec3 n = normal;
+
+    if (terrain.gpu_displacement != 0u) {
+        vec2 texel = 1.0 / max(vec2(textureSize(heightmap, 0)) - 1.0, vec2(1.0));
+        float dx = (height(uv + vec2(texel.x, 0.0)) - height(uv - vec2(texel.x, 0.0))) / (2.0 * texel.x * terrain.size);
+        float dz = (height(uv + vec2(0.0, texel.y)) - height(uv - vec2(0.0, texel.y))) / (2.0 * texel.y * terrain.size);
+        p.y += height(uv);
+        n = normalize(vec3(-dx, 1.0, -dz));
+    }
+
+    vec3 T = normalize((object_matrix_data.model * vec4(tangent.xyz, 0.0)).xyz);
+    vec3 N = normalize(mat3(object_matrix_data.normal) * n);
+    T = normalize(T - dot(T, N) * N);
+    vec3 B = cross(N, T);
+    tbn0 = mat3(T, B, N);
+    uv0 = uv;
+    fade0 = object_matrix_data.fade;
+    gl_Position = frame_matrix_data.projection * frame_matrix_data.view * object_matrix_data.model * vec4(p, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec2 uv;
+
+layout(location = 0) out vec2 uv0;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model_view_projection;
+    float size;
+    float page_size;
+    float border;
+    float cache_size;
+    float mip_count;
+    float mip_bias;
+} push_constants;
+
+void main() {
+    uv0 = uv;
+    gl_Position = push_constants.model_view_projection * vec4(position, 1.0);
+}
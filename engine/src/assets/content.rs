@@ -0,0 +1,1025 @@
+//! Storage for assets, loading of asset, waiting for asset load and worker threads.
+
+use crate::assets::gc::{Collector, MemoryPressure, BUCKET_COUNT};
+use crate::assets::http::{is_url, url_template, HttpRoot};
+use crate::assets::Asset as BfAsset;
+use crate::frame_stats::{self, FrameEvent};
+use crate::resources::transfer::UploadScheduler;
+use bf::blob::SharedBytes;
+use bf::pack::Pack;
+use bf::remap::UuidRemap;
+use bf::uuid::Uuid;
+use bf::{load_bf_from_bytes, load_bf_shared, Container, LoadError};
+use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
+use log::{error, info, trace};
+use memmap2::Mmap;
+use once_cell::sync::Lazy;
+use parking_lot::lock_api::MappedRwLockReadGuard;
+use parking_lot::{Condvar, Mutex, RawRwLock, RwLock, RwLockReadGuard};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::device::Queue;
+
+// some helper types
+type Map<A> = HashMap<Uuid, AssetSlot<A>>;
+type Storage<A> = RwLock<Map<A>>;
+type BoxedAsset = Box<dyn BfAsset>;
+
+type SignalRx = Receiver<()>;
+type SignalTx = Sender<()>;
+type MappedPack = Pack<Mmap>;
+
+/// State of single asset in the storage internal structure.
+pub struct AssetSlot<A> {
+    /// Possibly loaded asset.
+    asset: Option<A>,
+    revision: u64,
+    rx: Option<SignalRx>,
+    /// Whether load of the asset was requested and has not finished yet.
+    loading: bool,
+    /// Whether the asset was evicted by the garbage collector (see `gc` module).
+    evicted: bool,
+    /// Approximate size of the loaded asset in bytes.
+    size: usize,
+    /// Frame in which the asset was used for the last time.
+    last_used: AtomicU64,
+    /// Why the last load of the asset failed.
+    error: Option<String>,
+}
+
+impl<A> AssetSlot<A> {
+    pub fn new_empty(rx: SignalRx) -> Self {
+        Self {
+            asset: Option::None,
+            revision: 0,
+            rx: Some(rx),
+            loading: true,
+            evicted: false,
+            size: 0,
+            last_used: AtomicU64::new(0),
+            error: None,
+        }
+    }
+}
+
+/// Evicts loaded assets of the `map` that were last used before frame `before`.
+/// Returns the number of freed bytes.
+fn evict_unused<A>(map: &mut Map<A>, before: u64) -> usize {
+    let mut freed = 0;
+
+    for slot in map.values_mut() {
+        let unused = *slot.last_used.get_mut() < before;
+        if slot.asset.is_some() && !slot.loading && unused {
+            slot.asset = None;
+            slot.evicted = true;
+            freed += std::mem::take(&mut slot.size);
+        }
+    }
+
+    freed
+}
+
+// note: maybe we can refactor Load to contain a reference to
+// a storage that the asset should be loaded, then we can get
+// rid of `static` from the storage. this way we can simply
+// replace the whole storage before loading another scene.
+
+/// Content root (place where assets are looked for).
+enum Root {
+    /// Directory containing `<uuid>.bf` files. Assets are only written to
+    /// directories that are not read-only (see `Content::writable_path`).
+    Directory { path: PathBuf, read_only: bool },
+    /// Memory-mapped pack of BF files (see `bf::pack`) mounted from the path.
+    Pack(PathBuf, Arc<MappedPack>),
+    /// Single BF file providing the asset with the uuid.
+    File(Uuid, PathBuf),
+    /// HTTP server providing BF files of all assets (see `assets::http`).
+    Http(Arc<HttpRoot>),
+}
+
+impl Root {
+    /// Returns whether the root was mounted from the `path` (url template of
+    /// HTTP roots).
+    fn is_mounted_from(&self, path: &Path) -> bool {
+        match self {
+            Root::Directory { path: p, .. } | Root::Pack(p, _) | Root::File(_, p) => p == path,
+            Root::Http(root) => path
+                .to_str()
+                .map_or(false, |x| url_template(x) == root.template()),
+        }
+    }
+}
+
+impl Display for Root {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Root::Directory { path, read_only } => {
+                let access = if *read_only { "read-only" } else { "writable" };
+                write!(f, "directory {:?} ({})", path, access)
+            }
+            Root::Pack(path, _) => write!(f, "pack {:?}", path),
+            Root::File(uuid, path) => write!(f, "file {:?} (asset {})", path, uuid),
+            Root::Http(root) => write!(f, "http {}", root.template()),
+        }
+    }
+}
+
+/// Position of a mounted root in the search order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SearchOrder {
+    /// Searched before all other roots (eg. project folder opened by the
+    /// editor that overrides assets of the library).
+    First,
+    /// Searched after all other roots.
+    Last,
+}
+
+/// Returns the name of the BF file of the asset with the `uuid`.
+fn file_name(uuid: &Uuid) -> String {
+    format!("{}.bf", uuid.to_hyphenated().to_string().to_lowercase())
+}
+
+/// Returns the source of the asset from the first of the `roots` that contains it.
+fn find_in_roots(roots: &[Root], uuid: &Uuid) -> Option<AssetSource> {
+    let file_name = file_name(uuid);
+
+    for root in roots.iter() {
+        match root {
+            Root::Directory { path, .. } => {
+                let path = path.join(&file_name);
+                if path.exists() {
+                    return Some(AssetSource::File(path));
+                }
+            }
+            Root::Pack(_, pack) => {
+                if pack.contains(uuid) {
+                    return Some(AssetSource::Pack(pack.clone(), *uuid));
+                }
+            }
+            Root::File(file_uuid, path) => {
+                if file_uuid == uuid {
+                    return Some(AssetSource::File(path.clone()));
+                }
+            }
+            Root::Http(root) => return Some(AssetSource::Http(root.clone(), *uuid)),
+        }
+    }
+
+    None
+}
+
+/// Returns the first directory of the `roots` that is not read-only.
+fn writable_directory(roots: &[Root]) -> Option<&Path> {
+    roots.iter().find_map(|x| match x {
+        Root::Directory {
+            path,
+            read_only: false,
+        } => Some(path.as_path()),
+        _ => None,
+    })
+}
+
+/// Location of the BF file of an asset. Packs and HTTP roots store the file
+/// under the current uuid of the asset (see `Content::set_uuid_remap`).
+enum AssetSource {
+    File(PathBuf),
+    Pack(Arc<MappedPack>, Uuid),
+    Http(Arc<HttpRoot>, Uuid),
+}
+
+/// Error that happened while mounting a pack.
+#[derive(Debug)]
+pub enum MountError {
+    /// The pack file could not be opened or mapped.
+    Io(std::io::Error),
+    /// The file is not a valid pack.
+    InvalidPack(LoadError),
+}
+
+/// Opens the pack at specified path and maps it into memory.
+fn open_pack(path: &Path) -> Result<MappedPack, MountError> {
+    let file = std::fs::File::open(path).map_err(MountError::Io)?;
+    // safety: packs are not expected to be modified while the engine is running
+    let mmap = unsafe { Mmap::map(&file) }.map_err(MountError::Io)?;
+
+    Pack::new(mmap).map_err(MountError::InvalidPack)
+}
+
+/// Bytes of a single file in a mounted pack (shared with the loaded asset).
+struct PackFile {
+    pack: Arc<MappedPack>,
+    uuid: Uuid,
+}
+
+impl AsRef<[u8]> for PackFile {
+    fn as_ref(&self) -> &[u8] {
+        self.pack.get(&self.uuid).expect("asset not found in pack")
+    }
+}
+
+/// Maps the BF file of an asset into memory so the payloads of the loaded
+/// asset can borrow from it.
+fn map_source(source: &AssetSource) -> Result<SharedBytes, String> {
+    match source {
+        AssetSource::File(path) => {
+            let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+            // safety: files of loaded assets must not be modified while the
+            // assets are in memory (that's why mapping is opt-in)
+            let mmap = unsafe { Mmap::map(&file) }.map_err(|e| e.to_string())?;
+            Ok(Arc::new(mmap))
+        }
+        AssetSource::Pack(pack, uuid) if pack.contains(uuid) => Ok(Arc::new(PackFile {
+            pack: pack.clone(),
+            uuid: *uuid,
+        })),
+        AssetSource::Pack(..) => Err("asset not found in pack".to_string()),
+        // downloaded files are kept in memory
+        AssetSource::Http(root, uuid) => match root.fetch(*uuid) {
+            Ok(t) => Ok(Arc::new(t)),
+            Err(e) => Err(format!("{:?}", e)),
+        },
+    }
+}
+
+/// Request to load an asset.
+struct Load {
+    uuid: Uuid,
+    source: AssetSource,
+    tx: SignalTx,
+    /// Sequence number of the request. Requests with same priority
+    /// are processed in the order they were made.
+    seq: u64,
+}
+
+/// Queue of pending load requests shared by all worker threads. Workers always
+/// pick the request with highest priority. Priorities can be changed while the
+/// requests are waiting in the queue (eg. by visibility feedback from renderer).
+#[derive(Default)]
+struct LoadQueue {
+    pending: Mutex<Vec<Load>>,
+    available: Condvar,
+    priorities: RwLock<HashMap<Uuid, f32>>,
+    closed: AtomicBool,
+    counter: AtomicU64,
+    /// Whether payloads of assets are borrowed from memory-mapped files.
+    memory_mapped: AtomicBool,
+}
+
+impl LoadQueue {
+    fn push(&self, uuid: Uuid, source: AssetSource, tx: SignalTx) {
+        let seq = self.counter.fetch_add(1, Ordering::SeqCst);
+        self.pending.lock().push(Load {
+            uuid,
+            source,
+            tx,
+            seq,
+        });
+        self.available.notify_one();
+    }
+
+    /// Blocks until there is a request to process and returns the one with the
+    /// highest priority. Returns `None` when the queue was closed.
+    fn pop(&self) -> Option<Load> {
+        let mut pending = self.pending.lock();
+        loop {
+            if self.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            if !pending.is_empty() {
+                let priorities = self.priorities.read();
+                let priority = |l: &Load| priorities.get(&l.uuid).copied().unwrap_or(0.0);
+                let (idx, _) = pending
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| {
+                        priority(a)
+                            .partial_cmp(&priority(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                            .then(b.seq.cmp(&a.seq))
+                    })
+                    .unwrap();
+                return Some(pending.remove(idx));
+            }
+
+            self.available.wait(&mut pending);
+        }
+    }
+
+    fn close(&self) {
+        // hold the lock so no worker can miss the notification
+        let _guard = self.pending.lock();
+        self.closed.store(true, Ordering::SeqCst);
+        self.available.notify_all();
+    }
+}
+
+/// Actual internal storage. Split into buckets so the garbage collector can
+/// scan it incrementally.
+static STORAGE: Lazy<Vec<Storage<BoxedAsset>>> =
+    Lazy::new(|| (0..BUCKET_COUNT).map(|_| RwLock::default()).collect());
+static COLLECTOR: Lazy<Collector> = Lazy::new(Collector::default);
+
+/// Returns the bucket of the storage that contains the asset.
+fn bucket(uuid: &Uuid) -> &'static Storage<BoxedAsset> {
+    &STORAGE[uuid.as_u128() as usize % BUCKET_COUNT]
+}
+static WORKER_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Function that drives single worker thread.
+fn spawn_worker_thread(queue: Arc<LoadQueue>) {
+    std::thread::Builder::new()
+        .name(format!(
+            "ContentWorker-{}",
+            WORKER_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ))
+        .spawn(move || {
+            while let Some(item) = queue.pop() {
+                load(item, queue.memory_mapped.load(Ordering::Relaxed));
+            }
+            info!("Worker thread exited!");
+        })
+        .expect("cannot start worker thread");
+}
+
+/// Marks the asset as no longer loading after its load failed because of the `error`.
+fn mark_failed(uuid: &Uuid, error: String) {
+    if let Some(slot) = bucket(uuid).write().get_mut(uuid) {
+        slot.loading = false;
+        slot.evicted = false;
+        slot.error = Some(error);
+    }
+}
+
+/// Function that actually loads an asset into storage.
+fn load(work: Load, memory_mapped: bool) {
+    // helper macro to skip processing current item in the loop
+    // mark it as errored and move to next item in the queue
+    macro_rules! give_up_with_error {
+        ($err: expr) => {{
+            let _err = $err;
+            error!(
+                "Cannot load asset {:?} due to {:?}",
+                work.uuid.to_hyphenated().to_string(),
+                &_err
+            );
+            mark_failed(&work.uuid, format!("{:?}", &_err));
+            work.tx.send(()).ok();
+            return;
+        }};
+    }
+
+    let start = Instant::now();
+
+    if memory_mapped {
+        trace!(" Mapping asset {:?}", work.uuid);
+        let bf_file = match map_source(&work.source).map(load_bf_shared) {
+            Err(e) => give_up_with_error!(e),
+            Ok(Err(e)) => give_up_with_error!(e),
+            Ok(Ok(t)) => t,
+        };
+        return store(work, bf_file.into_container(), start);
+    }
+
+    // files in packs are borrowed directly from the mapped memory
+    let bytes: Cow<[u8]> = match &work.source {
+        AssetSource::File(path) => {
+            trace!(" Loading file {:?} as asset {:?}", path, work.uuid);
+            match std::fs::read(path) {
+                Err(e) => give_up_with_error!(e),
+                Ok(t) => Cow::Owned(t),
+            }
+        }
+        AssetSource::Pack(pack, uuid) => {
+            trace!(" Loading asset {:?} from pack", work.uuid);
+            match pack.get(uuid) {
+                None => give_up_with_error!("asset not found in pack"),
+                Some(t) => Cow::Borrowed(t),
+            }
+        }
+        AssetSource::Http(root, uuid) => {
+            trace!(
+                " Downloading asset {:?} from {}",
+                work.uuid,
+                root.url(*uuid)
+            );
+            match root.fetch(*uuid) {
+                Err(e) => give_up_with_error!(e),
+                Ok(t) => Cow::Owned(t),
+            }
+        }
+    };
+
+    let bf_file = match load_bf_from_bytes(&bytes) {
+        Err(e) => give_up_with_error!(e),
+        Ok(t) => t,
+    };
+
+    store(work, bf_file.into_container(), start);
+}
+
+/// Stores the loaded asset into storage and notifies the waiting threads.
+fn store(work: Load, container: Container, start: Instant) {
+    let asset: BoxedAsset = match container {
+        Container::Image(t) => Box::new(t),
+        Container::Mesh(t) => Box::new(t),
+        Container::Material(t) => Box::new(t),
+        Container::Tree(t) => Box::new(t),
+        Container::Sequence(t) => Box::new(t),
+        Container::Shader(t) => Box::new(t),
+        Container::VirtualTexture(t) => Box::new(t),
+    };
+
+    // update the storage
+    {
+        trace!(" Updating the storage of {:?}", work.uuid);
+        trace!(
+            "[{:?}] Acquiring WRITE lock to store loaded asset",
+            std::thread::current().name()
+        );
+        let size = asset.memory_size();
+        let mut guard = bucket(&work.uuid).write();
+        match guard.get_mut(&work.uuid) {
+            None => panic!("loaded asset that was not found in storage map"),
+            Some(slot) => {
+                // evicted asset is loaded again from the same file, so the
+                // resources created from it are still valid
+                if !slot.evicted {
+                    slot.revision += 1;
+                }
+                COLLECTOR.remove_used(slot.size);
+                COLLECTOR.add_used(size);
+                slot.asset = Some(asset);
+                slot.loading = false;
+                slot.evicted = false;
+                slot.error = None;
+                slot.size = size;
+                *slot.last_used.get_mut() = COLLECTOR.frame();
+            }
+        }
+        trace!("[{:?}] Dropping WRITE lock", std::thread::current().name())
+    }
+
+    frame_stats::record(FrameEvent::AssetLoaded);
+    trace!(
+        " Asset {:?} completely loaded in {}ms! ",
+        work.uuid,
+        start.elapsed().as_millis()
+    );
+    // send notification (we don't care if it arrives)
+    work.tx.send(()).ok();
+}
+
+pub struct Content {
+    // todo: remove transfer queue from content
+    pub transfer_queue: Arc<Queue>,
+    /// Scheduler of uploads of resources created from the assets.
+    pub uploads: UploadScheduler,
+    roots: Vec<Root>,
+    /// Current uuids of assets referenced by legacy uuids.
+    remap: UuidRemap,
+    load_queue: Arc<LoadQueue>,
+}
+
+impl Content {
+    /// Constructs a new `Content` and starts a specified amount of worker (loading)
+    /// threads. Roots that are `.bfpack` files are mounted as packs and roots
+    /// that are `http://` or `https://` urls as HTTP roots.
+    pub fn new(worker_count: usize, transfer_queue: Arc<Queue>, roots: Vec<PathBuf>) -> Self {
+        info!("Creating a Content with {} worker threads.", worker_count);
+        info!("Using following content roots: ");
+
+        roots.iter().for_each(|x| info!(" - {:?}", x));
+
+        let queue = Arc::new(LoadQueue::default());
+
+        let mut content = Self {
+            load_queue: queue.clone(),
+            uploads: UploadScheduler::new(transfer_queue.clone()),
+            transfer_queue,
+            roots: Vec::with_capacity(roots.len()),
+            remap: UuidRemap::default(),
+        };
+
+        for root in roots {
+            if root.extension().map_or(false, |x| x == "bfpack") && root.is_file() {
+                if let Err(e) = content.mount_pack(&root) {
+                    error!("Cannot mount pack {:?} due to {:?}", root, e);
+                }
+            } else if is_url(&root) {
+                content.mount_http(&root.to_string_lossy());
+            } else {
+                content.mount_directory(root, true, SearchOrder::Last);
+            }
+        }
+
+        for _ in 0..worker_count {
+            spawn_worker_thread(queue.clone());
+        }
+
+        content
+    }
+
+    /// Sets whether image and mesh payloads of loaded assets borrow from
+    /// memory-mapped BF files instead of being copied into memory (only
+    /// uncompressed files can be borrowed from). Mapped files must not be
+    /// modified or truncated while their assets are loaded.
+    pub fn set_memory_mapped(&self, enabled: bool) {
+        self.load_queue
+            .memory_mapped
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Mounts the pack at specified path as another content root. Assets are
+    /// looked for in the roots in the order they were added.
+    pub fn mount_pack(&mut self, path: impl AsRef<Path>) -> Result<(), MountError> {
+        let pack = open_pack(path.as_ref())?;
+        info!(
+            "Mounted pack {:?} with {} assets",
+            path.as_ref(),
+            pack.len()
+        );
+
+        self.roots
+            .push(Root::Pack(path.as_ref().to_path_buf(), Arc::new(pack)));
+        Ok(())
+    }
+
+    /// Mounts a directory with `<uuid>.bf` files as another content root searched
+    /// in the specified `order`. Assets can be written to the directory (see
+    /// [`writable_path`](#method.writable_path)) unless it is `read_only`.
+    pub fn mount_directory(
+        &mut self,
+        path: impl Into<PathBuf>,
+        read_only: bool,
+        order: SearchOrder,
+    ) {
+        let root = Root::Directory {
+            path: path.into(),
+            read_only,
+        };
+        info!("Mounted {}", root);
+
+        match order {
+            SearchOrder::First => self.roots.insert(0, root),
+            SearchOrder::Last => self.roots.push(root),
+        }
+    }
+
+    /// Removes all roots mounted from the `path` (url template of HTTP roots).
+    /// Assets that were already loaded from them stay loaded. Returns whether
+    /// any root was removed.
+    pub fn unmount(&mut self, path: impl AsRef<Path>) -> bool {
+        let count = self.roots.len();
+        self.roots.retain(|x| !x.is_mounted_from(path.as_ref()));
+        if self.roots.len() == count {
+            return false;
+        }
+
+        info!("Unmounted {:?}", path.as_ref());
+        true
+    }
+
+    /// Sets whether assets can be written to the directory root mounted from the `path`.
+    pub fn set_read_only(&mut self, path: impl AsRef<Path>, read_only: bool) -> Result<(), String> {
+        let path = path.as_ref();
+        let flags = self
+            .roots
+            .iter_mut()
+            .filter_map(|x| match x {
+                Root::Directory {
+                    path: p,
+                    read_only: flag,
+                } if p == path => Some(flag),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if flags.is_empty() {
+            return Err(format!("{:?} is not a mounted directory", path));
+        }
+
+        for flag in flags {
+            *flag = read_only;
+        }
+        Ok(())
+    }
+
+    /// Returns the path in the first writable directory root where the BF file
+    /// of the asset with the `uuid` should be written (eg. by the editor).
+    pub fn writable_path(&self, uuid: &Uuid) -> Result<PathBuf, String> {
+        writable_directory(&self.roots)
+            .map(|x| x.join(file_name(uuid)))
+            .ok_or_else(|| {
+                "no content root is writable (mount a directory that is not read-only \
+                 or add it to `writable_roots`)"
+                    .to_string()
+            })
+    }
+
+    /// Returns descriptions of the content roots in the order they are searched.
+    pub fn roots(&self) -> Vec<String> {
+        self.roots.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Mounts an HTTP server as another content root. The `template` is the url
+    /// of BF files with `{uuid}` placeholder (eg. `http://server:8000/library/{uuid}.bf`)
+    /// or the url of the directory containing them. The server is expected to
+    /// provide all assets, so roots mounted after it are never searched.
+    pub fn mount_http(&mut self, template: &str) {
+        info!("Mounted HTTP root {}", template);
+        self.roots
+            .push(Root::Http(Arc::new(HttpRoot::new(template))));
+    }
+
+    /// Mounts a single BF file as the asset with specified uuid. Useful for
+    /// files that are not named by the uuid of their asset.
+    pub fn mount_file(&mut self, uuid: Uuid, path: impl Into<PathBuf>) {
+        self.roots.push(Root::File(uuid, path.into()));
+    }
+
+    /// Sets the table of current uuids of assets that were re-keyed by the asset
+    /// server. Assets requested by their legacy uuids are loaded from the files
+    /// of their current uuids (they are still stored under the requested uuid).
+    pub fn set_uuid_remap(&mut self, remap: UuidRemap) {
+        info!("Using uuid remap table with {} entries", remap.len());
+        self.remap = remap;
+    }
+
+    /// Returns the source of the asset or an error listing the searched roots.
+    fn find_asset(&self, uuid: &Uuid) -> Result<AssetSource, String> {
+        let current = self.remap.resolve(*uuid);
+        if let Some(source) = find_in_roots(&self.roots, &current) {
+            return Ok(source);
+        }
+
+        let name = if current == *uuid {
+            file_name(uuid)
+        } else {
+            format!("{} (remapped from {})", file_name(&current), uuid)
+        };
+        if self.roots.is_empty() {
+            return Err(format!("{} not found, no content roots are mounted", name));
+        }
+        Err(format!(
+            "{} not found in any content root (searched {})",
+            name,
+            self.roots().join(", ")
+        ))
+    }
+
+    pub fn request_load(&self, uuid: Uuid) -> LoadRequest {
+        self.request(uuid, false);
+
+        LoadRequest {
+            content: &self,
+            uuid,
+        }
+    }
+
+    /// Requests load of the asset. Load of evicted asset can `keep_revision`.
+    fn request(&self, uuid: Uuid, keep_revision: bool) {
+        let (tx, rx) = bounded(1);
+        let source = match self.find_asset(&uuid) {
+            Ok(t) => t,
+            Err(e) => {
+                // the failed slot makes `get_blocking` report the error
+                error!("Cannot load asset: {}", e);
+                let mut guard = bucket(&uuid).write();
+                let slot = guard
+                    .entry(uuid)
+                    .or_insert_with(|| AssetSlot::new_empty(rx));
+                slot.rx = None;
+                slot.loading = false;
+                slot.evicted = false;
+                slot.error = Some(e);
+                return;
+            }
+        };
+
+        trace!("Load request {:?}...", uuid.to_hyphenated().to_string());
+
+        // create initial entry or update existing entry in the storage
+        {
+            trace!(
+                "[{:?}] Acquiring WRITE lock to request load",
+                std::thread::current().name()
+            );
+            let mut guard = bucket(&uuid).write();
+            match guard.entry(uuid) {
+                Entry::Occupied(mut t) => {
+                    let slot = t.get_mut();
+                    slot.rx = Some(rx.clone());
+                    slot.loading = true;
+                    slot.evicted &= keep_revision;
+                    slot.error = None;
+                }
+                Entry::Vacant(t) => {
+                    t.insert(AssetSlot::new_empty(rx.clone()));
+                }
+            }
+            trace!("[{:?}] Dropping WRITE lock", std::thread::current().name())
+        }
+
+        // push item to the load queue
+        self.load_queue.push(uuid, source, tx);
+    }
+
+    pub fn get<A: BfAsset>(&self, uuid: &Uuid) -> Option<MappedRwLockReadGuard<RawRwLock, A>> {
+        trace!(
+            "[{:?}] Acquiring READ lock to read asset",
+            std::thread::current().name()
+        );
+        let guard = bucket(uuid).read();
+
+        match guard.get(uuid) {
+            Some(slot) if slot.asset.is_some() => {
+                slot.last_used.store(COLLECTOR.frame(), Ordering::Relaxed)
+            }
+            Some(slot) if slot.evicted && !slot.loading => {
+                // evicted assets are loaded again when they are needed
+                drop(guard);
+                self.request(*uuid, true);
+                return None;
+            }
+            _ => {
+                trace!("[{:?}] Dropping READ lock", std::thread::current().name());
+                return None;
+            }
+        }
+
+        Some(RwLockReadGuard::map(guard, |g| {
+            // we can safely unwrap as we verified that both options
+            // are `Some(t)` and we still hold a lock to storage
+            let x = g.get(uuid).unwrap().asset.as_ref().unwrap();
+
+            assert!(x.is::<A>());
+            x.downcast_ref::<A>().unwrap()
+        }))
+    }
+
+    pub fn get_blocking<A: BfAsset>(&self, uuid: &Uuid) -> MappedRwLockReadGuard<RawRwLock, A> {
+        loop {
+            if let Some(t) = self.wait_and_get(uuid) {
+                return t;
+            }
+
+            // the asset was evicted before we got it (`get` requested it again)
+            match bucket(uuid).read().get(uuid) {
+                Some(slot) if slot.evicted => {}
+                Some(slot) => panic!(
+                    "Cannot load asset {}: {}",
+                    uuid,
+                    slot.error.as_deref().unwrap_or("unknown error")
+                ),
+                None => panic!("Asset {} was never requested!", uuid),
+            }
+        }
+    }
+
+    /// Waits until the requested asset is loaded and returns whether it is of
+    /// type `A`. Unlike [`get_blocking`](#method.get_blocking) this does not panic
+    /// when the asset has other type or could not be loaded.
+    pub fn is_of_type<A: BfAsset>(&self, uuid: &Uuid) -> bool {
+        self.wait(uuid);
+
+        bucket(uuid)
+            .read()
+            .get(uuid)
+            .and_then(|x| x.asset.as_ref())
+            .map_or(false, |x| x.is::<A>())
+    }
+
+    fn wait_and_get<A: BfAsset>(&self, uuid: &Uuid) -> Option<MappedRwLockReadGuard<RawRwLock, A>> {
+        self.wait(uuid);
+        self.get(uuid)
+    }
+
+    /// Blocks until the pending load of the asset (if any) finishes.
+    fn wait(&self, uuid: &Uuid) {
+        let rx = {
+            trace!(
+                "[{:?}] Acquiring READ lock to wait for asset",
+                std::thread::current().name()
+            );
+            let guard = bucket(uuid).read();
+            let x = match guard.get(uuid) {
+                None => None,
+                Some(slot) => match slot.rx {
+                    None => None, // nothing to do, asset is already loaded
+                    Some(ref rx) => match rx.try_recv() {
+                        Ok(_) => None, // item is loaded, but recv was never called
+                        Err(e) => match e {
+                            TryRecvError::Empty => Some(rx.clone()), // item is not yet loaded, wait
+                            TryRecvError::Disconnected => None, // item is loaded and recv was called
+                        },
+                    },
+                },
+            };
+            trace!("[{:?}] Dropping READ lock", std::thread::current().name());
+            x
+        };
+
+        if let Some(rx) = rx {
+            rx.recv().ok();
+        }
+    }
+
+    /// Requests load of the asset unless it was already requested, waits until it
+    /// is loaded and returns its revision. Unlike [`request_load`](#method.request_load)
+    /// this never loads already loaded asset again.
+    pub fn ensure_loaded<A: BfAsset>(&self, uuid: Uuid) -> u64 {
+        let requested = bucket(&uuid).read().contains_key(&uuid);
+        if !requested {
+            self.request_load(uuid);
+        }
+
+        drop(self.get_blocking::<A>(&uuid));
+        self.revision(&uuid)
+    }
+
+    /// Sets the priority of loading specified asset. Pending requests with higher
+    /// priority are loaded first. Assets without priority have priority `0.0`.
+    pub fn set_priority(&self, uuid: Uuid, priority: f32) {
+        self.load_queue.priorities.write().insert(uuid, priority);
+    }
+
+    /// Replaces priorities of all assets with specified ones.
+    pub fn set_priorities(&self, priorities: HashMap<Uuid, f32>) {
+        *self.load_queue.priorities.write() = priorities;
+    }
+
+    /// Returns the current loading priority of specified asset.
+    pub fn priority(&self, uuid: &Uuid) -> f32 {
+        self.load_queue
+            .priorities
+            .read()
+            .get(uuid)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Returns why the last load of the asset failed (eg. it was not found in
+    /// any root) or `None` if it did not fail.
+    pub fn error(&self, uuid: &Uuid) -> Option<String> {
+        bucket(uuid).read().get(uuid).and_then(|x| x.error.clone())
+    }
+
+    /// Returns the number of load requests waiting for a worker thread.
+    pub fn pending_loads(&self) -> usize {
+        self.load_queue.pending.lock().len()
+    }
+
+    /// Returns the number of times the asset with specified `uuid` was loaded. Requesting
+    /// load of already loaded asset loads it again, GPU resources created from the
+    /// previous revision should then be replaced using `resources::swap::Swap`.
+    pub fn revision(&self, uuid: &Uuid) -> u64 {
+        bucket(uuid).read().get(uuid).map_or(0, |x| x.revision)
+    }
+
+    /// Advances the garbage collector of loaded assets to the next frame and
+    /// evicts unused assets from a few buckets of the storage if there is
+    /// memory pressure. Called by the engine once per frame.
+    pub fn next_frame(&self) {
+        let scan = match COLLECTOR.next_frame() {
+            None => return,
+            Some(t) => t,
+        };
+
+        let freed = scan
+            .buckets
+            .iter()
+            .map(|x| evict_unused(&mut STORAGE[*x].write(), scan.evict_before))
+            .sum::<usize>();
+
+        if freed > 0 {
+            COLLECTOR.remove_used(freed);
+            trace!("Evicted unused assets of {} bytes", freed);
+        }
+    }
+
+    /// Registers a callback that reports the memory pressure. It is called once
+    /// per frame and the highest reported pressure drives the garbage collector.
+    pub fn on_memory_pressure<F>(&self, callback: F)
+    where
+        F: Fn() -> MemoryPressure + Send + Sync + 'static,
+    {
+        COLLECTOR.on_memory_pressure(Box::new(callback));
+    }
+
+    /// Sets the memory budget of loaded assets in bytes. Unused assets are
+    /// evicted when the loaded assets get close to the budget. Evicted assets
+    /// are loaded again (with the same revision) when they are needed.
+    pub fn set_memory_budget(&self, budget: Option<usize>) {
+        COLLECTOR.set_budget(budget);
+    }
+
+    /// Returns the approximate size of all loaded assets in bytes.
+    pub fn memory_used(&self) -> usize {
+        COLLECTOR.used()
+    }
+
+    // todo: add hot-reloading
+}
+
+impl Drop for Content {
+    fn drop(&mut self) {
+        // stop the worker threads
+        self.load_queue.close();
+    }
+}
+
+pub struct LoadRequest<'a> {
+    uuid: Uuid,
+    content: &'a Content,
+}
+
+impl<'a> LoadRequest<'a> {
+    pub fn wait<A: BfAsset>(&self) -> MappedRwLockReadGuard<RawRwLock, A> {
+        self.content.get_blocking(&self.uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::content::{
+        evict_unused, file_name, find_in_roots, writable_directory, AssetSlot, AssetSource, Map,
+        Root,
+    };
+    use bf::uuid::Uuid;
+    use crossbeam::channel::bounded;
+    use std::path::PathBuf;
+
+    fn slot(asset: Option<u32>, loading: bool, last_used: u64) -> AssetSlot<u32> {
+        let mut slot = AssetSlot::new_empty(bounded(1).1);
+        slot.asset = asset;
+        slot.loading = loading;
+        slot.size = 100;
+        *slot.last_used.get_mut() = last_used;
+        slot
+    }
+
+    #[test]
+    fn evicts_only_unused_loaded_assets() {
+        let mut map: Map<u32> = vec![
+            (1, slot(Some(1), false, 10)),
+            (2, slot(Some(2), false, 50)),
+            (3, slot(Some(3), true, 10)),
+            (4, slot(None, true, 0)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (Uuid::from_u128(k), v))
+        .collect();
+
+        assert_eq!(evict_unused(&mut map, 20), 100);
+
+        let evicted = map.get(&Uuid::from_u128(1)).unwrap();
+        assert!(evicted.evicted && evicted.asset.is_none() && evicted.size == 0);
+        assert!(map.values().filter(|x| x.evicted).count() == 1);
+        assert_eq!(evict_unused(&mut map, 20), 0);
+    }
+
+    #[test]
+    fn roots_are_searched_in_order() {
+        let dir = std::env::temp_dir().join(format!("engine-content-roots-{}", std::process::id()));
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+
+        let (both, only_second) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        std::fs::write(first.join(file_name(&both)), b"").unwrap();
+        std::fs::write(second.join(file_name(&both)), b"").unwrap();
+        std::fs::write(second.join(file_name(&only_second)), b"").unwrap();
+
+        let roots = vec![
+            Root::Directory {
+                path: first.clone(),
+                read_only: true,
+            },
+            Root::Directory {
+                path: second.clone(),
+                read_only: false,
+            },
+        ];
+        let found = |uuid| match find_in_roots(&roots, &uuid) {
+            Some(AssetSource::File(path)) => Some(path),
+            _ => None,
+        };
+
+        assert_eq!(found(both), Some(first.join(file_name(&both))));
+        assert_eq!(
+            found(only_second),
+            Some(second.join(file_name(&only_second)))
+        );
+        assert_eq!(found(Uuid::from_u128(3)), None::<PathBuf>);
+        assert_eq!(writable_directory(&roots), Some(second.as_path()));
+        assert_eq!(writable_directory(&roots[..1]), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,172 @@
+//! Incremental garbage collection of loaded assets.
+//!
+//! Loaded assets stay in memory so GPU resources can be created from them
+//! again (and streamed images can upload more mip-maps later). When there is
+//! memory pressure the collector evicts assets that were not used for a while.
+//! The storage of assets is split into buckets and each frame only a few of
+//! them are scanned, so the collection never stalls a frame. Higher pressure
+//! means more buckets per frame and shorter time an asset can stay unused.
+//!
+//! Pressure is the highest of the pressure of the memory budget of assets and
+//! of registered callbacks (eg. the amount of free memory of the system).
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Number of buckets the storage of assets is split into.
+pub const BUCKET_COUNT: usize = 64;
+
+/// How much memory is needed by the rest of the application.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    /// Assets are never evicted.
+    None,
+    /// Assets that were not used for a long time are evicted.
+    Moderate,
+    /// Assets that were not used in the last few frames are evicted.
+    High,
+}
+
+impl MemoryPressure {
+    /// Returns the pressure of having `used` bytes loaded with specified budget
+    /// (pressure starts at three quarters of the budget).
+    pub fn from_usage(used: usize, budget: usize) -> Self {
+        if used >= budget {
+            MemoryPressure::High
+        } else if used >= budget / 4 * 3 {
+            MemoryPressure::Moderate
+        } else {
+            MemoryPressure::None
+        }
+    }
+
+    /// Returns the number of buckets scanned per frame and the number of
+    /// frames an asset must be unused to be evicted.
+    fn work(self) -> Option<(usize, u64)> {
+        match self {
+            MemoryPressure::None => None,
+            MemoryPressure::Moderate => Some((2, 600)),
+            MemoryPressure::High => Some((8, 60)),
+        }
+    }
+}
+
+type PressureCallback = Box<dyn Fn() -> MemoryPressure + Send + Sync>;
+
+/// Buckets of the storage that should be scanned in the current frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scan {
+    pub buckets: Vec<usize>,
+    /// Assets last used before this frame are evicted.
+    pub evict_before: u64,
+}
+
+/// State of the collector shared by all users of the storage.
+#[derive(Default)]
+pub struct Collector {
+    callbacks: Mutex<Vec<PressureCallback>>,
+    /// Memory budget of loaded assets in bytes (zero when unlimited).
+    budget: AtomicUsize,
+    /// Approximate size of loaded assets in bytes.
+    used: AtomicUsize,
+    frame: AtomicU64,
+    /// Next bucket to scan.
+    cursor: AtomicUsize,
+}
+
+impl Collector {
+    /// Registers a callback that is asked for the memory pressure every frame.
+    pub fn on_memory_pressure(&self, callback: PressureCallback) {
+        self.callbacks.lock().push(callback);
+    }
+
+    /// Sets the memory budget of loaded assets in bytes (`None` for unlimited).
+    pub fn set_budget(&self, budget: Option<usize>) {
+        self.budget.store(budget.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn add_used(&self, bytes: usize) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn remove_used(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the approximate size of loaded assets in bytes.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of the current frame.
+    pub fn frame(&self) -> u64 {
+        self.frame.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current memory pressure.
+    pub fn pressure(&self) -> MemoryPressure {
+        let budget = match self.budget.load(Ordering::Relaxed) {
+            0 => MemoryPressure::None,
+            budget => MemoryPressure::from_usage(self.used(), budget),
+        };
+
+        self.callbacks
+            .lock()
+            .iter()
+            .map(|x| x())
+            .fold(budget, MemoryPressure::max)
+    }
+
+    /// Advances to the next frame and returns the buckets that should be
+    /// scanned in it (if there is any memory pressure).
+    pub fn next_frame(&self) -> Option<Scan> {
+        let frame = self.frame.fetch_add(1, Ordering::Relaxed) + 1;
+        let (buckets, max_idle) = self.pressure().work()?;
+        let first = self.cursor.fetch_add(buckets, Ordering::Relaxed);
+
+        Some(Scan {
+            buckets: (first..first + buckets).map(|x| x % BUCKET_COUNT).collect(),
+            evict_before: frame.saturating_sub(max_idle),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::gc::{Collector, MemoryPressure, BUCKET_COUNT};
+
+    #[test]
+    fn pressure_from_budget_and_callbacks() {
+        let collector = Collector::default();
+        collector.add_used(800);
+        assert_eq!(collector.pressure(), MemoryPressure::None);
+
+        collector.set_budget(Some(1000));
+        assert_eq!(collector.pressure(), MemoryPressure::Moderate);
+        collector.remove_used(100);
+        assert_eq!(collector.pressure(), MemoryPressure::None);
+
+        collector.on_memory_pressure(Box::new(|| MemoryPressure::High));
+        assert_eq!(collector.pressure(), MemoryPressure::High);
+    }
+
+    #[test]
+    fn scans_all_buckets_incrementally() {
+        let collector = Collector::default();
+        assert_eq!(collector.next_frame(), None);
+
+        collector.set_budget(Some(1));
+        collector.add_used(1);
+
+        let mut scanned = vec![0; BUCKET_COUNT];
+        for _ in 0..BUCKET_COUNT / 8 {
+            let scan = collector.next_frame().unwrap();
+            assert_eq!(scan.buckets.len(), 8);
+            scan.buckets.iter().for_each(|x| scanned[*x] += 1);
+        }
+
+        assert!(scanned.iter().all(|x| *x == 1));
+        assert_eq!(collector.frame(), 1 + BUCKET_COUNT as u64 / 8);
+        assert_eq!(collector.next_frame().unwrap().evict_before, 0);
+    }
+}
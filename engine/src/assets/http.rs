@@ -0,0 +1,282 @@
+//! Content roots that download assets from an HTTP server (eg. the library of
+//! the asset server) and cache them on the local disk.
+
+use bf::uuid::Uuid;
+use log::{trace, warn};
+use once_cell::sync::OnceCell;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Lazily configured on-disk cache of downloaded assets.
+static HTTP_CACHE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Placeholder in the url template that is replaced by the uuid of the asset.
+const UUID_PLACEHOLDER: &str = "{uuid}";
+
+/// Sets the directory where assets downloaded from HTTP roots will be cached.
+/// Downloaded assets are not cached if this function is never called.
+pub fn set_http_cache_dir(dir: PathBuf) {
+    if HTTP_CACHE.set(dir).is_err() {
+        warn!("HTTP cache directory can be set only once!");
+    }
+}
+
+/// Errors that may happen while downloading an asset.
+#[derive(Debug)]
+pub enum HttpError {
+    /// The request failed or the server responded with error status code.
+    Http(Box<ureq::Error>),
+    /// The response body couldn't be read.
+    Io(std::io::Error),
+}
+
+impl From<ureq::Error> for HttpError {
+    fn from(e: ureq::Error) -> Self {
+        HttpError::Http(Box::new(e))
+    }
+}
+
+impl From<std::io::Error> for HttpError {
+    fn from(e: std::io::Error) -> Self {
+        HttpError::Io(e)
+    }
+}
+
+/// Returns whether the content root is an url instead of a path.
+pub fn is_url(root: &Path) -> bool {
+    root.to_str().map_or(false, |x| {
+        x.starts_with("http://") || x.starts_with("https://")
+    })
+}
+
+/// Returns the url template of compiled assets served by the asset server
+/// with base `url` (eg. `http://server:8000`).
+pub fn asset_server_template(url: &str) -> String {
+    format!(
+        "{}/assets/{}/compiled",
+        url.trim_end_matches('/'),
+        UUID_PLACEHOLDER
+    )
+}
+
+/// Returns the url template of a root specified as the template or as the url
+/// of the directory with BF files.
+pub fn url_template(root: &str) -> String {
+    if root.contains(UUID_PLACEHOLDER) {
+        root.to_string()
+    } else {
+        format!("{}/{}.bf", root.trim_end_matches('/'), UUID_PLACEHOLDER)
+    }
+}
+
+/// Content root that downloads BF files from urls created by replacing `{uuid}`
+/// in a template (eg. `http://server:8000/library/{uuid}.bf`).
+///
+/// Downloaded files are stored in the cache directory under their uuid and
+/// revision (the `ETag`). Cached files are revalidated by conditional requests, so unchanged
+/// assets are not downloaded again, and they are used as they are when the
+/// server can't be reached.
+pub struct HttpRoot {
+    template: String,
+    agent: ureq::Agent,
+}
+
+impl HttpRoot {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: url_template(template),
+            agent: ureq::agent(),
+        }
+    }
+
+    /// Returns the url template of the BF files (with the `{uuid}` placeholder).
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the url of the BF file of the asset.
+    pub fn url(&self, uuid: Uuid) -> String {
+        self.template
+            .replace(UUID_PLACEHOLDER, &uuid.to_hyphenated().to_string())
+    }
+
+    /// Downloads the BF file of the asset (or reads it from the cache if it
+    /// did not change since it was cached) and returns its bytes.
+    pub fn fetch(&self, uuid: Uuid) -> Result<Vec<u8>, HttpError> {
+        let cache = HTTP_CACHE.get().map(|dir| CachedFile::new(dir, uuid));
+        let cached = cache.as_ref().and_then(|x| x.etag());
+
+        let url = self.url(uuid);
+        let mut request = self.agent.get(&url);
+        if let Some(etag) = &cached {
+            request = request.set("If-None-Match", etag);
+        }
+
+        let response = match request.call() {
+            Ok(t) => t,
+            // server is unreachable, the cached file is better than nothing
+            Err(ureq::Error::Transport(e)) => match (&cache, &cached) {
+                (Some(cache), Some(etag)) => {
+                    warn!("Cannot download {} ({}), using cached file", url, e);
+                    return Ok(cache.read(etag)?);
+                }
+                _ => return Err(ureq::Error::Transport(e).into()),
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        if response.status() == 304 {
+            if let (Some(cache), Some(etag)) = (&cache, &cached) {
+                trace!(" Cached file of {:?} is up to date", uuid);
+                return Ok(cache.read(etag)?);
+            }
+        }
+
+        let etag = response.header("ETag").map(String::from);
+        let mut bytes = Vec::new();
+        response.into_reader().read_to_end(&mut bytes)?;
+        trace!(" Downloaded {} bytes from {}", bytes.len(), url);
+
+        if let (Some(cache), Some(etag)) = (&cache, etag) {
+            if let Err(e) = cache.write(&bytes, &etag) {
+                warn!("Cannot cache downloaded asset {:?}: {:?}", uuid, e);
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Returns the revision of a downloaded file usable in file names (the `ETag`
+/// without quotes and other special characters).
+fn revision(etag: &str) -> String {
+    etag.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(64)
+        .collect()
+}
+
+/// Downloaded BF files of an asset stored in the cache directory as
+/// `<uuid>.<revision>.bf` and the `ETag` of the current revision stored
+/// in `<uuid>.etag`.
+struct CachedFile {
+    dir: PathBuf,
+    name: String,
+}
+
+impl CachedFile {
+    fn new(dir: &Path, uuid: Uuid) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            name: uuid.to_hyphenated().to_string(),
+        }
+    }
+
+    fn path(&self, etag: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}.bf", self.name, revision(etag)))
+    }
+
+    fn etag_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.etag", self.name))
+    }
+
+    /// Returns the `ETag` of the current cached revision or `None` if the
+    /// asset is not cached.
+    fn etag(&self) -> Option<String> {
+        let etag = std::fs::read_to_string(self.etag_path()).ok()?;
+        if self.path(&etag).is_file() {
+            Some(etag)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the cached revision with the `etag`.
+    fn read(&self, etag: &str) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self.path(etag))
+    }
+
+    /// Stores a new revision of the asset and removes the previous one.
+    fn write(&self, bytes: &[u8], etag: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let previous = self.etag();
+        std::fs::write(self.path(etag), bytes)?;
+
+        // the etag is replaced at once, so it is always paired with a whole file
+        let tmp = self.dir.join(format!("{}.etag.tmp", self.name));
+        std::fs::write(&tmp, etag)?;
+        std::fs::rename(&tmp, self.etag_path())?;
+
+        if let Some(previous) = previous {
+            if revision(&previous) != revision(etag) {
+                let _ = std::fs::remove_file(self.path(&previous));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::http::{asset_server_template, is_url, CachedFile, HttpRoot};
+    use bf::uuid::Uuid;
+    use std::path::Path;
+
+    #[test]
+    fn url_from_template() {
+        let uuid = Uuid::from_u128(0x1234);
+        let expected = "http://server:8000/library/00000000-0000-0000-0000-000000001234.bf";
+
+        assert_eq!(
+            HttpRoot::new("http://server:8000/library/{uuid}.bf").url(uuid),
+            expected
+        );
+        assert_eq!(
+            HttpRoot::new("http://server:8000/library/").url(uuid),
+            expected
+        );
+    }
+
+    #[test]
+    fn asset_server_url() {
+        let uuid = Uuid::from_u128(0x1234);
+        let root = HttpRoot::new(&asset_server_template("http://server:8000/"));
+
+        assert_eq!(
+            root.url(uuid),
+            "http://server:8000/assets/00000000-0000-0000-0000-000000001234/compiled"
+        );
+    }
+
+    #[test]
+    fn detects_urls() {
+        assert!(is_url(Path::new("http://localhost:8000/library/{uuid}.bf")));
+        assert!(is_url(Path::new("https://example.com/assets")));
+        assert!(!is_url(Path::new("/home/user/assets")));
+        assert!(!is_url(Path::new("C:\\assets\\target")));
+    }
+
+    #[test]
+    fn cached_file_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("engine-http-cache-{}", std::process::id()));
+        let cached = CachedFile::new(&dir, Uuid::from_u128(7));
+
+        assert_eq!(cached.etag(), None);
+        cached.write(b"BF", "\"1a-2b\"").unwrap();
+        assert_eq!(cached.etag().as_deref(), Some("\"1a-2b\""));
+        assert_eq!(cached.read("\"1a-2b\"").unwrap(), b"BF");
+
+        // new revision replaces the previous one
+        cached.write(b"BF2", "\"1b-3c\"").unwrap();
+        assert_eq!(cached.etag().as_deref(), Some("\"1b-3c\""));
+        assert_eq!(cached.read("\"1b-3c\"").unwrap(), b"BF2");
+        assert!(!cached.path("\"1a-2b\"").exists());
+
+        // etag without the file is not valid
+        std::fs::remove_file(cached.path("\"1b-3c\"")).unwrap();
+        assert_eq!(cached.etag(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
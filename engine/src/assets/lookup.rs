@@ -20,8 +20,8 @@ use std::collections::HashMap;
 /// Read-only lazily created translation `HashMap`.
 static LOOKUP_MAP: OnceCell<HashMap<String, Uuid>> = OnceCell::new();
 
-// default path searched when no env variable is provided
-const DEFAULT_LOOKUP_DB: &str = "C:\\Users\\dobra\\CLionProjects\\renderer\\assets\\input2uuid.dat";
+// default path (relative to the working directory) searched when no env variable is provided
+const DEFAULT_LOOKUP_DB: &str = "assets/input2uuid.dat";
 
 /// Creates a `HashMap<String, Uuid>` from translation file defined
 /// in `LOOKUP_DB` environment variable and returns it.
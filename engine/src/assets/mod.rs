@@ -0,0 +1,51 @@
+//! Functionality related to loading assets & managing their memory.
+
+use downcast_rs::{impl_downcast, Downcast};
+
+mod content;
+pub mod gc;
+mod http;
+mod lookup;
+
+pub use content::{Content, MountError, SearchOrder};
+pub use gc::MemoryPressure;
+pub use http::{asset_server_template, is_url, set_http_cache_dir};
+pub use lookup::lookup;
+
+/// Marker trait that specifies some struct as an "asset" meaning it
+/// can be deserialized from a slice of bytes, stored and loaded using
+/// a `Storage`.
+pub trait Asset: Downcast + Send + Sync + 'static {
+    /// Returns the approximate number of bytes the asset occupies in memory
+    /// (used to decide when unused assets should be evicted).
+    fn memory_size(&self) -> usize {
+        0
+    }
+}
+
+impl_downcast!(Asset);
+
+impl Asset for bf::material::Material {}
+impl Asset for bf::mesh::Mesh {
+    fn memory_size(&self) -> usize {
+        self.vertex_data.len() + self.index_data.len()
+    }
+}
+impl Asset for bf::image::Image {
+    fn memory_size(&self) -> usize {
+        self.mipmap_data.len()
+    }
+}
+impl Asset for bf::tree::Tree {}
+impl Asset for bf::sequence::Sequence {}
+impl Asset for bf::shader::Shader {}
+impl Asset for bf::virtual_texture::VirtualTexture {
+    fn memory_size(&self) -> usize {
+        // pages of memory-mapped files are only loaded when they are read
+        if self.page_data.is_shared() {
+            0
+        } else {
+            self.page_data.len()
+        }
+    }
+}
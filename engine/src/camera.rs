@@ -0,0 +1,263 @@
+//! Contains code related to cameras.
+
+use cgmath::{
+    vec3, BaseFloat, EuclideanSpace, InnerSpace, Matrix, Matrix4, Ortho, PerspectiveFov, Point3,
+    Rad, Transform, Vector3, Vector4,
+};
+
+/// Object that can provide *view* and *projection matrices*.
+pub trait Camera<T: BaseFloat> {
+    /// Returns the current *projection matrix*.
+    fn projection_matrix(&self) -> Matrix4<T>;
+
+    /// Returns the current *view matrix*.
+    fn view_matrix(&self) -> Matrix4<T>;
+
+    /// Returns the position of the camera.
+    fn position(&self) -> Point3<T>;
+
+    /// Returns the distance of the near clipping plane.
+    fn near(&self) -> T;
+
+    /// Returns the distance of the far clipping plane.
+    fn far(&self) -> T;
+
+    /// Returns the volume visible by the camera.
+    fn frustum(&self) -> Frustum<T> {
+        Frustum::from_matrix(self.projection_matrix() * self.view_matrix())
+    }
+}
+
+/// Volume bounded by six planes (left, right, bottom, top, near, far) with
+/// normals pointing inside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum<T> {
+    /// Planes as `(normal, distance)` with normalized normals.
+    pub planes: [Vector4<T>; 6],
+}
+
+impl<T: BaseFloat> Frustum<T> {
+    /// Extracts the planes from a *view-projection matrix* (Gribb-Hartmann method).
+    pub fn from_matrix(matrix: Matrix4<T>) -> Self {
+        let (x, y, z, w) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        let normalize = |plane: Vector4<T>| plane / plane.truncate().magnitude();
+
+        Self {
+            planes: [
+                normalize(w + x),
+                normalize(w - x),
+                normalize(w + y),
+                normalize(w - y),
+                normalize(w + z),
+                normalize(w - z),
+            ],
+        }
+    }
+
+    /// Returns whether the `point` is inside the frustum.
+    pub fn contains(&self, point: Point3<T>) -> bool {
+        self.intersects_sphere(point, T::zero())
+    }
+
+    /// Returns whether any part of the sphere with specified `center` and
+    /// `radius` is inside the frustum.
+    pub fn intersects_sphere(&self, center: Point3<T>, radius: T) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+    }
+}
+
+/// Camera used to render the world (see `GameState::active_camera`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActiveCamera {
+    Perspective,
+    Orthographic,
+}
+
+// todo: use quaternion for camera rotation
+
+/// First person perspective camera that is controlled by mouse and WASD keys.
+#[derive(Copy, Clone, Debug)]
+pub struct PerspectiveCamera {
+    pub position: Point3<f32>,
+    pub forward: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub fov: Rad<f32>,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PerspectiveCamera {
+    #[inline]
+    pub fn move_forward(&mut self, amount: f32) {
+        self.position += self.forward * amount;
+    }
+
+    #[inline]
+    pub fn move_backward(&mut self, amount: f32) {
+        self.move_forward(-amount);
+    }
+
+    #[inline]
+    pub fn move_right(&mut self, amount: f32) {
+        self.move_left(-amount);
+    }
+
+    #[inline]
+    pub fn move_left(&mut self, amount: f32) {
+        let left = self.up.cross(self.forward);
+        self.position += left * amount;
+    }
+
+    #[inline]
+    pub fn move_up(&mut self, amount: f32) {
+        self.position += Vector3::new(0.0, amount, 0.0);
+    }
+
+    #[inline]
+    pub fn move_down(&mut self, amount: f32) {
+        self.move_up(-amount);
+    }
+
+    #[inline]
+    pub fn rotate(&mut self, dx: Rad<f32>, dy: Rad<f32>) {
+        let rx = Matrix4::from_angle_y(dx);
+        self.forward = rx.transform_vector(self.forward).normalize();
+
+        let right = self.forward.cross(self.up).normalize();
+        let old_forward = self.forward;
+        let ry = Matrix4::from_axis_angle(right, dy);
+        self.forward = ry.transform_vector(self.forward).normalize();
+        let angle = self.forward.dot(vec3(0.0, 1.0, 0.0));
+        if angle.abs() > 0.999 {
+            self.forward = old_forward;
+        }
+    }
+}
+
+impl Camera<f32> for PerspectiveCamera {
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        PerspectiveFov {
+            fovy: self.fov,
+            aspect: self.aspect_ratio,
+            near: self.near,
+            far: self.far,
+        }
+        .into()
+    }
+
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward, self.up)
+    }
+
+    fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    fn near(&self) -> f32 {
+        self.near
+    }
+
+    fn far(&self) -> f32 {
+        self.far
+    }
+}
+
+/// Camera with parallel projection, used for top-down views. Objects keep
+/// their size regardless of the distance from the camera.
+pub struct OrthographicCamera {
+    pub position: Point3<f32>,
+    pub forward: Vector3<f32>,
+    pub up: Vector3<f32>,
+    /// Height of the visible area in world units.
+    pub height: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera<f32> for OrthographicCamera {
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        let half_height = self.height * 0.5;
+        let half_width = half_height * self.aspect_ratio;
+
+        Ortho {
+            left: -half_width,
+            right: half_width,
+            bottom: -half_height,
+            top: half_height,
+            near: self.near,
+            far: self.far,
+        }
+        .into()
+    }
+
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward, self.up)
+    }
+
+    fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    fn near(&self) -> f32 {
+        self.near
+    }
+
+    fn far(&self) -> f32 {
+        self.far
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::{Camera, OrthographicCamera, PerspectiveCamera};
+    use cgmath::{vec3, Deg, Point3};
+
+    fn perspective() -> PerspectiveCamera {
+        PerspectiveCamera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            forward: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            fov: Deg(90.0).into(),
+            aspect_ratio: 1.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    #[test]
+    fn perspective_frustum() {
+        let frustum = perspective().frustum();
+
+        assert!(frustum.contains(Point3::new(0.0, 0.0, -10.0)));
+        assert!(frustum.contains(Point3::new(9.0, -9.0, -10.0)));
+        assert!(!frustum.contains(Point3::new(11.0, 0.0, -10.0)));
+        assert!(!frustum.contains(Point3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains(Point3::new(0.0, 0.0, -101.0)));
+        assert!(frustum.intersects_sphere(Point3::new(11.0, 0.0, -10.0), 2.0));
+    }
+
+    #[test]
+    fn orthographic_frustum() {
+        let camera = OrthographicCamera {
+            position: Point3::new(0.0, 50.0, 0.0),
+            forward: vec3(0.0, -1.0, 0.0),
+            up: vec3(0.0, 0.0, -1.0),
+            height: 20.0,
+            aspect_ratio: 2.0,
+            near: 0.0,
+            far: 100.0,
+        };
+        let frustum = camera.frustum();
+
+        // the visible area does not grow with distance
+        assert!(frustum.contains(Point3::new(19.0, 49.0, 9.0)));
+        assert!(frustum.contains(Point3::new(19.0, -49.0, 9.0)));
+        assert!(!frustum.contains(Point3::new(21.0, 0.0, 0.0)));
+        assert!(!frustum.contains(Point3::new(0.0, 0.0, 11.0)));
+        assert!(!frustum.contains(Point3::new(0.0, 51.0, 0.0)));
+    }
+}
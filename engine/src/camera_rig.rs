@@ -0,0 +1,375 @@
+//! Camera rig that drives a [`PerspectiveCamera`](../camera/struct.PerspectiveCamera.html)
+//! like a physical camera: the field of view is derived from the sensor and the
+//! focal length of the lens, zooms are animated and trauma makes the camera shake.
+
+use crate::camera::PerspectiveCamera;
+use cgmath::{InnerSpace, Matrix3, Rad, Vector3};
+
+/// Size of the camera sensor (film back) in millimeters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sensor {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Sensor {
+    /// 35 mm full frame sensor.
+    pub const FULL_FRAME: Sensor = Sensor {
+        width: 36.0,
+        height: 24.0,
+    };
+
+    /// Super 35 motion picture film.
+    pub const SUPER_35: Sensor = Sensor {
+        width: 24.89,
+        height: 18.66,
+    };
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width / self.height
+    }
+}
+
+/// How the sensor is fitted into a viewport with a different aspect ratio.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GateFit {
+    /// The height of the sensor fills the viewport.
+    Vertical,
+    /// The width of the sensor fills the viewport.
+    Horizontal,
+    /// The sensor covers the whole viewport, parts of it are cropped.
+    Fill,
+    /// The whole sensor is visible, the viewport shows more around it.
+    Overscan,
+}
+
+/// Parameters of the trauma-based camera shake. The shake is the square of the
+/// trauma multiplied by the maximum offsets, so small amounts of trauma are
+/// subtle and big amounts are violent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shake {
+    pub max_yaw: Rad<f32>,
+    pub max_pitch: Rad<f32>,
+    pub max_roll: Rad<f32>,
+    /// Maximum translation in world units.
+    pub max_offset: f32,
+    /// How fast the camera shakes (changes of direction per second).
+    pub frequency: f32,
+    /// Trauma removed per second.
+    pub decay: f32,
+}
+
+impl Default for Shake {
+    fn default() -> Self {
+        Self {
+            max_yaw: Rad(0.05),
+            max_pitch: Rad(0.05),
+            max_roll: Rad(0.08),
+            max_offset: 0.0,
+            frequency: 15.0,
+            decay: 1.0,
+        }
+    }
+}
+
+/// Animation of the focal length.
+#[derive(Copy, Clone, Debug)]
+struct Zoom {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+/// Drives the field of view and aspect ratio of a camera and renders it with
+/// procedural shake. The camera itself is never shaken, so controllers and
+/// sequences can keep moving it; the shaken camera is returned by
+/// [`view`](#method.view).
+#[derive(Clone, Debug)]
+pub struct CameraRig {
+    pub sensor: Sensor,
+    pub gate_fit: GateFit,
+    /// Aspect ratio of the rendered image, follows the viewport when `None`.
+    pub aspect_ratio: Option<f32>,
+    pub shake: Shake,
+    /// Focal length of the lens in millimeters.
+    focal_length: f32,
+    zoom: Option<Zoom>,
+    trauma: f32,
+    time: f32,
+    view: PerspectiveCamera,
+}
+
+impl CameraRig {
+    /// Creates a rig with a full frame sensor, vertical gate fit and the focal
+    /// length matching the field of view of the `camera`.
+    pub fn new(camera: &PerspectiveCamera) -> Self {
+        let mut rig = Self {
+            sensor: Sensor::FULL_FRAME,
+            gate_fit: GateFit::Vertical,
+            aspect_ratio: None,
+            shake: Shake::default(),
+            focal_length: 0.0,
+            zoom: None,
+            trauma: 0.0,
+            time: 0.0,
+            view: *camera,
+        };
+        rig.focal_length = rig.focal_length_for(camera.fov, camera.aspect_ratio);
+        rig
+    }
+
+    /// Returns the current focal length in millimeters.
+    pub fn focal_length(&self) -> f32 {
+        self.focal_length
+    }
+
+    /// Returns the vertical field of view of the rendered image.
+    pub fn fov(&self) -> Rad<f32> {
+        self.view.fov
+    }
+
+    /// Smoothly changes the focal length to `target` (in millimeters) over `duration`
+    /// seconds. Zero duration changes it immediately.
+    pub fn zoom_to(&mut self, target: f32, duration: f32) {
+        self.zoom = if duration > 0.0 {
+            Some(Zoom {
+                from: self.focal_length,
+                to: target,
+                duration,
+                elapsed: 0.0,
+            })
+        } else {
+            self.focal_length = target;
+            None
+        };
+    }
+
+    /// Smoothly changes the vertical field of view to `fov` over `duration` seconds.
+    pub fn animate_fov(&mut self, fov: Rad<f32>, duration: f32) {
+        let target = self.focal_length_for(fov, self.view.aspect_ratio);
+        self.zoom_to(target, duration);
+    }
+
+    /// Returns whether the focal length is being animated.
+    pub fn is_zooming(&self) -> bool {
+        self.zoom.is_some()
+    }
+
+    /// Adds `amount` of trauma (eg. `0.3` for a nearby explosion). Trauma is
+    /// clamped to `0.0` - `1.0` and decays over time.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Returns the shaken camera that should be rendered.
+    pub fn view(&self) -> &PerspectiveCamera {
+        &self.view
+    }
+
+    /// Half of the height of the sensor (in millimeters) that is fitted into
+    /// a viewport with `aspect_ratio`.
+    fn fitted_half_height(&self, aspect_ratio: f32) -> f32 {
+        let vertical = self.sensor.height * 0.5;
+        let horizontal = self.sensor.width * 0.5 / aspect_ratio;
+
+        match self.gate_fit {
+            GateFit::Vertical => vertical,
+            GateFit::Horizontal => horizontal,
+            GateFit::Fill => vertical.min(horizontal),
+            GateFit::Overscan => vertical.max(horizontal),
+        }
+    }
+
+    /// Returns the vertical field of view of an image with `aspect_ratio` taken
+    /// with the `focal_length`.
+    pub fn fov_for(&self, focal_length: f32, aspect_ratio: f32) -> Rad<f32> {
+        Rad(2.0 * (self.fitted_half_height(aspect_ratio) / focal_length).atan())
+    }
+
+    /// Returns the focal length that results in the vertical field of view `fov`
+    /// of an image with `aspect_ratio`.
+    pub fn focal_length_for(&self, fov: Rad<f32>, aspect_ratio: f32) -> f32 {
+        self.fitted_half_height(aspect_ratio) / (fov.0 * 0.5).tan()
+    }
+
+    /// Advances the zoom and the shake by `dt` seconds and sets the field of view
+    /// and aspect ratio of the `camera` (whose aspect ratio is the aspect ratio of
+    /// the viewport). Called once per update.
+    pub fn update(&mut self, camera: &mut PerspectiveCamera, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.shake.decay * dt).max(0.0);
+
+        if let Some(zoom) = self.zoom.as_mut() {
+            zoom.elapsed += dt;
+            let t = (zoom.elapsed / zoom.duration).min(1.0);
+            self.focal_length = zoom.from + (zoom.to - zoom.from) * smoothstep(t);
+            if t >= 1.0 {
+                self.zoom = None;
+            }
+        }
+
+        let aspect_ratio = self.aspect_ratio.unwrap_or(camera.aspect_ratio);
+        camera.fov = self.fov_for(self.focal_length, aspect_ratio);
+        self.view = PerspectiveCamera {
+            aspect_ratio,
+            ..*camera
+        };
+
+        self.apply_shake();
+    }
+
+    /// Rotates and offsets the view by the current shake.
+    fn apply_shake(&mut self) {
+        let shake = self.trauma * self.trauma;
+        if shake == 0.0 {
+            return;
+        }
+
+        let t = self.time * self.shake.frequency;
+        let view = &mut self.view;
+        let right = view.forward.cross(view.up).normalize();
+        let up = right.cross(view.forward).normalize();
+
+        let yaw = Matrix3::from_axis_angle(up, self.shake.max_yaw * shake * noise(0, t));
+        let pitch = Matrix3::from_axis_angle(right, self.shake.max_pitch * shake * noise(1, t));
+        let roll =
+            Matrix3::from_axis_angle(view.forward, self.shake.max_roll * shake * noise(2, t));
+
+        view.forward = (yaw * pitch * view.forward).normalize();
+        view.up = (roll * view.up).normalize();
+        view.position += (right * noise(3, t) + up * noise(4, t) + view.forward * noise(5, t))
+            * self.shake.max_offset
+            * shake;
+    }
+
+    /// Moves the shaken view by `-offset` (see `GameState::shift_origin`).
+    pub fn shift_origin(&mut self, offset: Vector3<f32>) {
+        self.view.position -= offset;
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Smooth one dimensional value noise in range `-1.0` to `1.0`. Different
+/// `seed`s produce independent noises.
+fn noise(seed: u32, t: f32) -> f32 {
+    let lattice = |i: i64| {
+        let mut x = (i as u32) ^ seed.wrapping_mul(0x9e37_79b9);
+        x = (x ^ (x >> 16)).wrapping_mul(0x7feb_352d);
+        x = (x ^ (x >> 15)).wrapping_mul(0x846c_a68b);
+        x ^= x >> 16;
+        x as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    let i = t.floor();
+    let a = lattice(i as i64);
+    let b = lattice(i as i64 + 1);
+    a + (b - a) * smoothstep(t - i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::PerspectiveCamera;
+    use crate::camera_rig::{noise, CameraRig, GateFit, Sensor};
+    use cgmath::{vec3, Deg, InnerSpace, Point3, Rad};
+
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            forward: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            fov: Deg(90.0).into(),
+            aspect_ratio: 1.5,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn derives_fov_from_lens() {
+        let mut rig = CameraRig::new(&camera());
+        assert_close(rig.focal_length(), 12.0);
+
+        // 50 mm lens on a full frame sensor has ~27 degrees vertical field of view
+        let fov = rig.fov_for(50.0, Sensor::FULL_FRAME.aspect_ratio());
+        assert_close(Deg::from(fov).0, 26.991);
+
+        // narrower viewport than the sensor
+        rig.gate_fit = GateFit::Horizontal;
+        let horizontal = rig.fov_for(50.0, 1.0);
+        assert_close(Deg::from(horizontal).0, 39.598);
+        rig.gate_fit = GateFit::Fill;
+        assert_eq!(rig.fov_for(50.0, 1.0), fov);
+        rig.gate_fit = GateFit::Overscan;
+        assert_eq!(rig.fov_for(50.0, 1.0), horizontal);
+
+        let focal_length = rig.focal_length_for(horizontal, 1.0);
+        assert_close(focal_length, 50.0);
+    }
+
+    #[test]
+    fn animates_zoom() {
+        let mut camera = camera();
+        let mut rig = CameraRig::new(&camera);
+        rig.zoom_to(50.0, 1.0);
+
+        rig.update(&mut camera, 0.5);
+        assert_close(rig.focal_length(), 31.0);
+        assert!(rig.is_zooming());
+
+        rig.update(&mut camera, 0.6);
+        assert_eq!(rig.focal_length(), 50.0);
+        assert!(!rig.is_zooming());
+        assert_eq!(camera.fov, rig.fov());
+
+        rig.animate_fov(Deg(90.0).into(), 0.0);
+        rig.update(&mut camera, 0.1);
+        assert_close(Deg::from(camera.fov).0, 90.0);
+    }
+
+    #[test]
+    fn shakes_with_trauma() {
+        let mut camera = camera();
+        let mut rig = CameraRig::new(&camera);
+        rig.aspect_ratio = Some(2.0);
+
+        rig.update(&mut camera, 0.1);
+        assert_eq!(rig.view().forward, camera.forward);
+        assert_eq!(rig.view().aspect_ratio, 2.0);
+        assert_eq!(camera.aspect_ratio, 1.5);
+
+        rig.add_trauma(2.0);
+        assert_eq!(rig.trauma(), 1.0);
+        rig.update(&mut camera, 0.25);
+        assert_eq!(rig.trauma(), 0.75);
+        let angle = Rad(rig.view().forward.dot(camera.forward).min(1.0).acos());
+        assert!(angle.0 > 0.0 && angle < rig.shake.max_yaw + rig.shake.max_pitch);
+        // the camera itself is not shaken
+        assert_eq!(camera.forward, vec3(0.0, 0.0, -1.0));
+
+        rig.update(&mut camera, 1.0);
+        assert_eq!(rig.trauma(), 0.0);
+        assert_eq!(rig.view().forward, camera.forward);
+    }
+
+    #[test]
+    fn noise_is_smooth() {
+        for i in 0..100 {
+            let t = i as f32 * 0.137;
+            assert!(noise(0, t).abs() <= 1.0);
+            assert!((noise(0, t) - noise(0, t + 0.01)).abs() < 0.05);
+        }
+        assert_ne!(noise(0, 0.5), noise(1, 0.5));
+    }
+}
@@ -0,0 +1,590 @@
+//! Configuration related structs and functions for renderer.
+
+use crate::assets::is_url;
+use crate::input::actions::ActionBinding;
+use crate::render::bloom::BloomSettings;
+use crate::render::fxaa::FxaaQuality;
+use crate::render::precision::TargetPrecision;
+use crate::render::renderer::PresentModePreference;
+use crate::window::WindowMode;
+use core::settings::Overrides;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use winit::dpi::{LogicalSize, Size};
+
+/// Default path of the configuration file (see `RendererConfiguration::load`).
+pub const CONFIG_FILE: &str = "renderer.toml";
+
+/// Prefix of environment variables overriding the configuration (see
+/// `core::settings::Overrides::from_env`).
+pub const ENV_PREFIX: &str = "RENDERER_";
+
+/// Configuration of content system, rendering and other aspects of the renderer.
+#[derive(Clone)]
+pub struct RendererConfiguration {
+    /// Whether the window is windowed, borderless or exclusive fullscreen.
+    pub window_mode: WindowMode,
+    /// Size of the window in logical pixels (physical pixels of the video
+    /// mode of exclusive fullscreen).
+    pub resolution: [u16; 2],
+    pub gpu: usize,
+    /// Preferred present mode of the swapchain (eg. `fifo` for vsync).
+    pub present_mode: PresentModePreference,
+    /// Directories, `.bfpack` files and HTTP urls (see `Content::mount_http`)
+    /// searched for assets in this order.
+    pub content_roots: Vec<PathBuf>,
+    /// Directories of `content_roots` that assets can be written to (eg. by the
+    /// editor, see `Content::writable_path`). Other roots are read-only.
+    pub writable_roots: Vec<PathBuf>,
+    /// Whether payloads of images and meshes are borrowed from memory-mapped
+    /// files instead of being read into memory (see `Content::set_memory_mapped`).
+    pub mmap_assets: bool,
+    /// Memory budget of loaded assets in megabytes. Assets that were not used
+    /// for a while are evicted when it is exceeded. Unlimited when `None`.
+    pub asset_memory_budget: Option<usize>,
+    /// Maximum size of resource uploads submitted in one frame in megabytes.
+    pub upload_budget: usize,
+    /// Address the remote control channel should listen on. Remote control
+    /// is disabled when `None`. Addresses other than loopback require `remote_token`.
+    pub remote_control: Option<SocketAddr>,
+    /// Directory that paths of remote control commands (eg. `screenshot`) are
+    /// relative to. Clients can't write files outside of it.
+    pub remote_output: PathBuf,
+    /// Token remote control clients must send (`auth <token>`) before other
+    /// commands. Clients are not authenticated when `None`.
+    pub remote_token: Option<String>,
+    /// Bindings of gameplay actions to physical inputs (see `input::actions` module
+    /// for the binding format).
+    pub action_bindings: HashMap<String, Vec<String>>,
+    /// Directory where images transcoded to formats supported by the GPU
+    /// are cached. Transcoded images are not cached when `None`.
+    pub transcode_cache: Option<PathBuf>,
+    /// Base url of the asset server (eg. `http://server:8000`) whose compiled
+    /// assets are downloaded when they are not found in `content_roots`.
+    pub asset_server: Option<String>,
+    /// Directory where assets downloaded from HTTP content roots are cached.
+    /// Downloaded assets are not cached when `None`.
+    pub http_cache: Option<PathBuf>,
+    /// Table of current uuids of assets re-keyed by the asset server (see
+    /// `bf::remap`), so scenes referencing legacy uuids still load.
+    pub uuid_remap: Option<PathBuf>,
+    /// Directory with shaders compiled by the asset server that replace the
+    /// shaders embedded in the engine. Embedded shaders are used when `None`.
+    pub shader_cache: Option<PathBuf>,
+    /// Directory with GLSL sources of the shaders that are compiled at run-time
+    /// and recompiled when they change (requires the `shader-compiler` feature).
+    /// Shaders that fail to compile fall back to the `shader_cache` and the
+    /// embedded shaders.
+    pub shader_source: Option<PathBuf>,
+    /// Directory where pipelines compiled by the driver are cached between
+    /// runs (one file per GPU). Pipelines are compiled on each run when `None`.
+    pub pipeline_cache: Option<PathBuf>,
+    /// Distance of the camera from the origin at which the origin is moved to
+    /// the camera (floating origin). The origin never moves when `None`.
+    pub floating_origin: Option<f32>,
+    /// Intensity and threshold of the bloom. Intensity of zero disables the bloom.
+    pub bloom: BloomSettings,
+    /// Precision of floating point render targets.
+    pub precision: TargetPrecision,
+    /// Quality preset of the anti-aliasing.
+    pub fxaa_quality: FxaaQuality,
+    /// Number of fixed updates of the game per second (see `timestep` module).
+    pub fixed_update_rate: f32,
+    /// Whether frames are rendered without a window into `headless_output`
+    /// (see `render::headless`).
+    pub headless: bool,
+    /// Number of frames rendered in the headless mode before the last one is saved.
+    pub headless_frames: usize,
+    /// File the last frame of the headless mode is saved to. EXR files contain
+    /// the HDR buffer, other files (eg. PNG) the tonemapped image.
+    pub headless_output: PathBuf,
+}
+
+impl<'a> Into<Size> for &'a RendererConfiguration {
+    fn into(self) -> Size {
+        Size::Logical(LogicalSize::new(
+            self.resolution[0] as f64,
+            self.resolution[1] as f64,
+        ))
+    }
+}
+
+// default development configuration
+impl Default for RendererConfiguration {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowMode::default(),
+            resolution: [1920, 1080],
+            gpu: 0,
+            present_mode: PresentModePreference::default(),
+            // relative to the working directory (the root of the repository)
+            content_roots: vec![PathBuf::from("assets/target")],
+            writable_roots: vec![],
+            mmap_assets: false,
+            asset_memory_budget: None,
+            upload_budget: 16,
+            remote_control: None,
+            remote_output: PathBuf::from("captures"),
+            remote_token: None,
+            action_bindings: vec![
+                ("cycle_floor_material", vec!["Key:F", "Gamepad:North"]),
+                ("spawn_light", vec!["Key:L", "Gamepad:West"]),
+                ("toggle_gui", vec!["Key:F1"]),
+                ("toggle_hud", vec!["Key:F2"]),
+                ("cycle_debug_view", vec!["Key:F3"]),
+                ("toggle_vsync", vec!["Key:F10"]),
+                ("cycle_window_mode", vec!["Key:F11"]),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+            .collect(),
+            transcode_cache: None,
+            asset_server: None,
+            http_cache: None,
+            uuid_remap: None,
+            shader_cache: None,
+            shader_source: None,
+            pipeline_cache: None,
+            floating_origin: None,
+            bloom: BloomSettings::default(),
+            precision: TargetPrecision::default(),
+            fxaa_quality: FxaaQuality::default(),
+            headless: false,
+            fixed_update_rate: 60.0,
+            headless_frames: 8,
+            headless_output: PathBuf::from("frame.png"),
+        }
+    }
+}
+
+impl RendererConfiguration {
+    /// Overrides the fields of this configuration by command line arguments and
+    /// environment variables (see `core::settings` module). Environment variables
+    /// are prefixed with `ENV_PREFIX` (eg. `RENDERER_BLOOM_INTENSITY`).
+    ///
+    /// The resolution is specified as `<width>x<height>`, content roots are
+    /// separated the same way as in `PATH` and bindings of each action are
+    /// specified by `action_bindings_<action>` setting as comma separated list.
+    pub fn apply_overrides(&mut self, overrides: &Overrides) -> Result<(), String> {
+        overrides.apply("window_mode", &mut self.window_mode)?;
+        overrides.apply("gpu", &mut self.gpu)?;
+        overrides.apply("present_mode", &mut self.present_mode)?;
+        if let Some(value) = overrides.get("resolution") {
+            self.resolution = parse_resolution(value)?;
+        }
+        if let Some(value) = overrides.get("content_roots") {
+            self.content_roots = parse_content_roots(value);
+        }
+        if let Some(value) = overrides.get("writable_roots") {
+            self.writable_roots = std::env::split_paths(value).collect();
+        }
+        overrides.apply("mmap_assets", &mut self.mmap_assets)?;
+        overrides.apply_option("asset_memory_budget", &mut self.asset_memory_budget)?;
+        overrides.apply("upload_budget", &mut self.upload_budget)?;
+        overrides.apply_option("remote_control", &mut self.remote_control)?;
+        overrides.apply("remote_output", &mut self.remote_output)?;
+        overrides.apply_option("remote_token", &mut self.remote_token)?;
+        for (action, value) in overrides.get_prefixed("action_bindings_") {
+            let bindings = value.split(',').map(|x| x.trim().to_string()).collect();
+            self.action_bindings.insert(action, bindings);
+        }
+        overrides.apply_option("transcode_cache", &mut self.transcode_cache)?;
+        overrides.apply_option("asset_server", &mut self.asset_server)?;
+        overrides.apply_option("http_cache", &mut self.http_cache)?;
+        overrides.apply_option("uuid_remap", &mut self.uuid_remap)?;
+        overrides.apply_option("shader_cache", &mut self.shader_cache)?;
+        overrides.apply_option("shader_source", &mut self.shader_source)?;
+        overrides.apply_option("pipeline_cache", &mut self.pipeline_cache)?;
+        overrides.apply_option("floating_origin", &mut self.floating_origin)?;
+        overrides.apply("bloom_intensity", &mut self.bloom.intensity)?;
+        overrides.apply("bloom_threshold", &mut self.bloom.threshold)?;
+        overrides.apply("hdr_precision", &mut self.precision.hdr)?;
+        overrides.apply("bloom_precision", &mut self.precision.bloom)?;
+        overrides.apply("transparency_precision", &mut self.precision.transparency)?;
+        overrides.apply("fxaa_quality", &mut self.fxaa_quality)?;
+        overrides.apply("fixed_update_rate", &mut self.fixed_update_rate)?;
+        overrides.apply("headless", &mut self.headless)?;
+        overrides.apply("headless_frames", &mut self.headless_frames)?;
+        overrides.apply("headless_output", &mut self.headless_output)?;
+        Ok(())
+    }
+
+    /// Loads the configuration from the TOML file at `path` (the defaults are
+    /// used when it does not exist), overrides it by command line arguments and
+    /// environment variables and validates the result.
+    pub fn load(path: &Path, overrides: &Overrides) -> Result<Self, String> {
+        let mut conf = Self::default();
+        match std::fs::read_to_string(path) {
+            Ok(text) => conf
+                .apply_toml(&text)
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+        conf.apply_overrides(overrides)?;
+        conf.validate()?;
+        Ok(conf)
+    }
+
+    /// Replaces the fields of this configuration by the settings in the TOML
+    /// document `text`. Keys are the names of the settings (the same as in
+    /// [`apply_overrides`](#method.apply_overrides)), but the values have TOML
+    /// types (eg. `resolution = [1280, 720]`, `content_roots` is an array and
+    /// action bindings are in the `[action_bindings]` table). Unknown keys and
+    /// values of wrong types are errors.
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), String> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        parse_value("window_mode", file.window_mode, &mut self.window_mode)?;
+        set(&mut self.resolution, file.resolution);
+        set(&mut self.gpu, file.gpu);
+        parse_value("present_mode", file.present_mode, &mut self.present_mode)?;
+        set(&mut self.content_roots, file.content_roots);
+        set(&mut self.writable_roots, file.writable_roots);
+        set(&mut self.mmap_assets, file.mmap_assets);
+        set_option(&mut self.asset_memory_budget, file.asset_memory_budget);
+        set(&mut self.upload_budget, file.upload_budget);
+        set_option(&mut self.remote_control, file.remote_control);
+        set(&mut self.remote_output, file.remote_output);
+        set_option(&mut self.remote_token, file.remote_token);
+        self.action_bindings
+            .extend(file.action_bindings.unwrap_or_default());
+        set_option(&mut self.transcode_cache, file.transcode_cache);
+        set_option(&mut self.asset_server, file.asset_server);
+        set_option(&mut self.http_cache, file.http_cache);
+        set_option(&mut self.uuid_remap, file.uuid_remap);
+        set_option(&mut self.shader_cache, file.shader_cache);
+        set_option(&mut self.shader_source, file.shader_source);
+        set_option(&mut self.pipeline_cache, file.pipeline_cache);
+        set_option(&mut self.floating_origin, file.floating_origin);
+        set(&mut self.bloom.intensity, file.bloom_intensity);
+        set(&mut self.bloom.threshold, file.bloom_threshold);
+        parse_value("hdr_precision", file.hdr_precision, &mut self.precision.hdr)?;
+        parse_value(
+            "bloom_precision",
+            file.bloom_precision,
+            &mut self.precision.bloom,
+        )?;
+        parse_value(
+            "transparency_precision",
+            file.transparency_precision,
+            &mut self.precision.transparency,
+        )?;
+        parse_value("fxaa_quality", file.fxaa_quality, &mut self.fxaa_quality)?;
+        set(&mut self.fixed_update_rate, file.fixed_update_rate);
+        set(&mut self.headless, file.headless);
+        set(&mut self.headless_frames, file.headless_frames);
+        set(&mut self.headless_output, file.headless_output);
+        Ok(())
+    }
+
+    /// Checks values that have the right type, but can't be used (eg. zero
+    /// resolution). Returns an error describing every invalid setting.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = vec![];
+
+        if self.resolution.contains(&0) {
+            errors.push(format!(
+                "resolution {}x{} must be at least 1x1",
+                self.resolution[0], self.resolution[1]
+            ));
+        }
+        if self.content_roots.is_empty() {
+            errors.push("content_roots must contain at least one directory, pack or url".into());
+        }
+        for root in self.writable_roots.iter() {
+            if !self.content_roots.contains(root) {
+                errors.push(format!("writable root {:?} is not in content_roots", root));
+            } else if is_url(root) || root.extension().map_or(false, |x| x == "bfpack") {
+                errors.push(format!("writable root {:?} is not a directory", root));
+            }
+        }
+        if let Some(url) = &self.asset_server {
+            if !is_url(Path::new(url)) {
+                errors.push(format!("asset_server {:?} is not an http(s) url", url));
+            }
+        }
+        if self.asset_memory_budget == Some(0) {
+            errors.push("asset_memory_budget must be positive (unset it for no budget)".into());
+        }
+        if self.upload_budget == 0 {
+            errors.push("upload_budget must be at least 1 MB".into());
+        }
+        if let Some(addr) = self.remote_control {
+            if !addr.ip().is_loopback() && self.remote_token.is_none() {
+                errors.push(format!(
+                    "remote_control {} is reachable from other machines, set remote_token \
+                     to require authentication (or use 127.0.0.1)",
+                    addr
+                ));
+            }
+        }
+        if self
+            .remote_token
+            .as_deref()
+            .map_or(false, |x| x.trim().is_empty())
+        {
+            errors.push("remote_token must not be empty (unset it for no authentication)".into());
+        }
+
+        let mut actions = self.action_bindings.iter().collect::<Vec<_>>();
+        actions.sort_by_key(|x| x.0);
+        for (action, bindings) in actions {
+            for binding in bindings {
+                if let Err(e) = binding.parse::<ActionBinding>() {
+                    errors.push(format!("action_bindings_{}: {}", action, e));
+                }
+            }
+        }
+
+        if self
+            .floating_origin
+            .map_or(false, |x| x.is_nan() || x <= 0.0)
+        {
+            errors.push("floating_origin must be positive (unset it to never move)".into());
+        }
+        if self.bloom.intensity.is_nan() || self.bloom.intensity < 0.0 {
+            errors.push("bloom_intensity must not be negative (zero disables bloom)".into());
+        }
+        if self.bloom.threshold.is_nan() || self.bloom.threshold < 0.0 {
+            errors.push("bloom_threshold must not be negative".into());
+        }
+        if !(self.fixed_update_rate > 0.0 && self.fixed_update_rate.is_finite()) {
+            errors.push("fixed_update_rate must be a positive number of updates per second".into());
+        }
+        if self.headless_frames == 0 {
+            errors.push("headless_frames must be at least 1".into());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// Returns the path of the configuration file, which is the value of the
+/// `RENDERER_CONFIG` environment variable or `renderer.toml` in the working
+/// directory.
+pub fn config_file() -> PathBuf {
+    std::env::var_os("RENDERER_CONFIG").map_or_else(|| PathBuf::from(CONFIG_FILE), PathBuf::from)
+}
+
+/// Settings of the configuration file. Settings with values that are parsed
+/// from text (eg. `fxaa_quality`) are kept as TOML values, so they can be
+/// written as strings or numbers (eg. `hdr_precision = 16`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    window_mode: Option<toml::Value>,
+    resolution: Option<[u16; 2]>,
+    gpu: Option<usize>,
+    present_mode: Option<toml::Value>,
+    content_roots: Option<Vec<PathBuf>>,
+    writable_roots: Option<Vec<PathBuf>>,
+    mmap_assets: Option<bool>,
+    asset_memory_budget: Option<usize>,
+    upload_budget: Option<usize>,
+    remote_control: Option<SocketAddr>,
+    remote_output: Option<PathBuf>,
+    remote_token: Option<String>,
+    action_bindings: Option<HashMap<String, Vec<String>>>,
+    transcode_cache: Option<PathBuf>,
+    asset_server: Option<String>,
+    http_cache: Option<PathBuf>,
+    uuid_remap: Option<PathBuf>,
+    shader_cache: Option<PathBuf>,
+    shader_source: Option<PathBuf>,
+    pipeline_cache: Option<PathBuf>,
+    floating_origin: Option<f32>,
+    bloom_intensity: Option<f32>,
+    bloom_threshold: Option<f32>,
+    hdr_precision: Option<toml::Value>,
+    bloom_precision: Option<toml::Value>,
+    transparency_precision: Option<toml::Value>,
+    fxaa_quality: Option<toml::Value>,
+    fixed_update_rate: Option<f32>,
+    headless: Option<bool>,
+    headless_frames: Option<usize>,
+    headless_output: Option<PathBuf>,
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+fn set_option<T>(target: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *target = value;
+    }
+}
+
+/// Parses the text of the `value` (strings without quotes) of the setting `name`.
+fn parse_value<T>(name: &str, value: Option<toml::Value>, target: &mut T) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = match value {
+        None => return Ok(()),
+        Some(toml::Value::String(s)) => s,
+        Some(value) => value.to_string(),
+    };
+    *target = text
+        .parse()
+        .map_err(|e| format!("invalid value {:?} of setting {}: {}", text, name, e))?;
+    Ok(())
+}
+
+/// Parses resolution in `<width>x<height>` format.
+pub(crate) fn parse_resolution(value: &str) -> Result<[u16; 2], String> {
+    let invalid = || format!("invalid resolution {:?} (expected eg. 1920x1080)", value);
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+
+    Ok([
+        width.parse().map_err(|_| invalid())?,
+        height.parse().map_err(|_| invalid())?,
+    ])
+}
+
+/// Parses content roots separated as in `PATH`. Urls are kept whole even though
+/// `:` separates paths on unix.
+fn parse_content_roots(value: &str) -> Vec<PathBuf> {
+    // whether the url ends with the host (the port was split off)
+    let ends_with_host = |url: &str| match url.find("://") {
+        Some(idx) => !url[idx + 3..].contains(|c| c == ':' || c == '/'),
+        None => false,
+    };
+
+    let mut roots: Vec<String> = vec![];
+    for part in std::env::split_paths(value) {
+        let part = part.to_string_lossy().into_owned();
+        match roots.last_mut() {
+            Some(last) if (last == "http" || last == "https") && part.starts_with("//") => {
+                last.push(':');
+                last.push_str(&part);
+            }
+            Some(last)
+                if ends_with_host(last) && part.starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                last.push(':');
+                last.push_str(&part);
+            }
+            _ => roots.push(part),
+        }
+    }
+
+    roots.into_iter().map(PathBuf::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::parse_content_roots;
+    use crate::render::fxaa::FxaaQuality;
+    use crate::render::precision::Precision;
+    use crate::render::renderer::PresentModePreference;
+    use crate::window::WindowMode;
+    use crate::RendererConfiguration;
+    use std::path::PathBuf;
+
+    #[test]
+    fn toml_overrides_defaults() {
+        let mut conf = RendererConfiguration::default();
+        conf.apply_toml(
+            r#"
+            resolution = [1280, 720]
+            gpu = 1
+            present_mode = "fifo"
+            window_mode = "borderless"
+            content_roots = ["assets", "http://server:8000/library/{uuid}.bf"]
+            hdr_precision = 32
+            fxaa_quality = "low"
+
+            [action_bindings]
+            spawn_light = ["Key:K"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(conf.resolution, [1280, 720]);
+        assert_eq!(conf.gpu, 1);
+        assert_eq!(conf.present_mode, PresentModePreference::Fifo);
+        assert_eq!(conf.window_mode, WindowMode::Borderless);
+        assert_eq!(
+            conf.content_roots,
+            vec![
+                PathBuf::from("assets"),
+                PathBuf::from("http://server:8000/library/{uuid}.bf")
+            ]
+        );
+        assert_eq!(conf.precision.hdr, Precision::Full);
+        assert_eq!(conf.fxaa_quality, FxaaQuality::Low);
+        assert_eq!(conf.action_bindings["spawn_light"], vec!["Key:K"]);
+        // bindings of other actions are kept
+        assert!(conf.action_bindings.contains_key("toggle_gui"));
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn toml_errors_name_the_setting() {
+        let error = |text| {
+            RendererConfiguration::default()
+                .apply_toml(text)
+                .unwrap_err()
+        };
+
+        assert!(error("resolutoin = [1280, 720]").contains("unknown field `resolutoin`"));
+        assert!(error("gpu = \"first\"").contains("`gpu`"));
+        assert!(error("fxaa_quality = \"ultra\"").contains("setting fxaa_quality"));
+    }
+
+    #[test]
+    fn validation_reports_all_invalid_settings() {
+        let mut conf = RendererConfiguration::default();
+        conf.resolution = [0, 720];
+        conf.upload_budget = 0;
+        conf.asset_server = Some("server:8000".to_string());
+        conf.action_bindings
+            .insert("jump".to_string(), vec!["Key:Nope".to_string()]);
+
+        let error = conf.validate().unwrap_err();
+        assert!(error.contains("resolution 0x720"));
+        assert!(error.contains("upload_budget"));
+        assert!(error.contains("asset_server"));
+        assert!(error.contains("action_bindings_jump"));
+    }
+
+    #[test]
+    fn remote_control_from_other_machines_requires_token() {
+        let mut conf = RendererConfiguration::default();
+        conf.remote_control = Some("127.0.0.1:9000".parse().unwrap());
+        assert!(conf.validate().is_ok());
+
+        conf.remote_control = Some("0.0.0.0:9000".parse().unwrap());
+        assert!(conf.validate().unwrap_err().contains("remote_token"));
+
+        conf.remote_token = Some("secret".to_string());
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn content_roots_keep_urls_whole() {
+        assert_eq!(
+            parse_content_roots("/data/assets:http://server:8000/library/{uuid}.bf:https://cdn"),
+            vec![
+                PathBuf::from("/data/assets"),
+                PathBuf::from("http://server:8000/library/{uuid}.bf"),
+                PathBuf::from("https://cdn"),
+            ]
+        );
+    }
+}
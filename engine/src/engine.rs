@@ -0,0 +1,1027 @@
+use crate::assets::{asset_server_template, set_http_cache_dir, Content};
+use crate::camera::ActiveCamera;
+use crate::config::parse_resolution;
+use crate::frame_stats::{self, FrameHistory};
+use crate::input::actions::ActionMap;
+use crate::input::Input;
+use crate::movement::Movement;
+use crate::picking::{pick, Hit, Ray};
+use crate::remote::{Command, RemoteControl};
+use crate::render::debug_draw;
+use crate::render::debug_view::DebugView;
+use crate::render::feedback::texture_priorities;
+use crate::render::fxaa::FXAA;
+use crate::render::grain::Grain;
+use crate::render::gui::EguiContext;
+use crate::render::histogram::{zone_color, Histogram, HISTOGRAM_BINS, MAX_EV, MIN_EV};
+use crate::render::overlay::font;
+use crate::render::renderer::{PresentModePreference, RendererState};
+use crate::render::shader_cache::{
+    invalidate_changed_shaders, set_shader_cache_dir, set_shader_source_dir,
+};
+use crate::render::vulkan::VulkanState;
+use crate::resources::image::TextureStreamer;
+use crate::resources::transcode::set_transcode_cache_dir;
+use crate::sequencer::Sequencer;
+use crate::timestep::FixedTimestep;
+use crate::turntable::Turntable;
+use crate::viewport::{Viewport, ViewportId};
+use crate::window::{WindowMode, WindowSize};
+use crate::{GameState, RendererConfiguration};
+use bf::remap::UuidRemap;
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Point3, Vector3};
+use core::perf::CPUProfiler;
+use log::{error, info};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+use winit::window::WindowId;
+
+/// How often shader sources are checked for changes.
+const SHADER_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Game specific logic that is driven by the [`Engine`](struct.Engine.html).
+pub trait Game {
+    /// Called once per frame after the engine updated its own state and after
+    /// the fixed updates of the frame. `Engine::delta` is the time of the frame.
+    fn update(&mut self, _engine: &mut Engine) {}
+
+    /// Called at the fixed rate (`fixed_update_rate` setting) with the length
+    /// of the step `dt` in seconds, so it may be called several times in one
+    /// frame or not at all. Movement and physics done here don't depend on the
+    /// frame rate. Objects moved here should have interpolated transforms
+    /// (see `Transform::set_interpolated`).
+    fn fixed_update(&mut self, _engine: &mut Engine, _dt: f32) {}
+
+    /// Replaces current scene with the scene with specified name. This
+    /// is used by the `load_scene` remote control command.
+    fn load_scene(&mut self, _engine: &mut Engine, name: &str) -> Result<(), String> {
+        Err(format!("unknown scene {:?}", name))
+    }
+}
+
+/// main struct containing everything
+pub struct Engine {
+    pub game_state: GameState,
+    pub vulkan_state: VulkanState,
+    pub renderer_state: RendererState,
+    pub input_state: Input,
+    pub content: Content,
+    /// Sequence that controls the camera instead of the user while it is playing.
+    pub sequencer: Option<Sequencer>,
+    /// GUI the game can draw windows into during its update. It is shown and
+    /// hidden by the `toggle_gui` action.
+    pub egui: EguiContext,
+    /// Controller of the perspective camera used while the GUI is hidden.
+    pub movement: Movement,
+    /// Material review mode that replaces lighting of the scene.
+    pub turntable: Turntable,
+    /// Additional windows with their own world (see `open_viewport`).
+    pub viewports: Vec<Viewport>,
+    next_viewport: u64,
+    texture_streamer: TextureStreamer,
+    floating_origin: Option<f32>,
+    remote: Option<RemoteControl>,
+    window_mode: WindowMode,
+    /// Size of the window when it is windowed and of the video mode of
+    /// exclusive fullscreen.
+    resolution: [u16; 2],
+    /// Present mode used when vsync is turned off by the `toggle_vsync` action.
+    unsynced_present_mode: PresentModePreference,
+    /// Whether the GUI is displayed (the cursor is released and the camera
+    /// doesn't move while it is).
+    gui: bool,
+    /// Whether the performance HUD is displayed.
+    hud: bool,
+    frame_count: u64,
+    frame_time: Duration,
+    last_frame: Instant,
+    timestep: FixedTimestep,
+    /// When the shader sources were last checked for changes (`None` when
+    /// the shaders are not compiled from sources).
+    last_shader_check: Option<Instant>,
+    /// Recent frames with events that happened during them (shown in the HUD).
+    frame_history: FrameHistory,
+    render_scope: CPUProfiler<'static>,
+    update_scope: CPUProfiler<'static>,
+    event_loop: Option<EventLoop<()>>,
+}
+
+impl Engine {
+    pub fn new(
+        initial_state: GameState,
+        conf: &RendererConfiguration,
+        event_loop: EventLoop<()>,
+    ) -> Self {
+        if let Some(dir) = &conf.transcode_cache {
+            set_transcode_cache_dir(dir.clone());
+        }
+        if let Some(dir) = &conf.shader_cache {
+            set_shader_cache_dir(dir.clone());
+        }
+        if let Some(dir) = &conf.shader_source {
+            set_shader_source_dir(dir.clone());
+        }
+        if let Some(dir) = &conf.http_cache {
+            set_http_cache_dir(dir.clone());
+        }
+        let vulkan_state = VulkanState::new(conf, &event_loop).expect("cannot create VulkanState");
+        let mut content =
+            Content::new(8, vulkan_state.transfer_queue(), conf.content_roots.clone());
+        for root in conf.writable_roots.iter() {
+            if let Err(e) = content.set_read_only(root, false) {
+                error!("Cannot make content root writable: {}", e);
+            }
+        }
+        if let Some(url) = &conf.asset_server {
+            // searched last, only for assets missing on the local disk
+            content.mount_http(&asset_server_template(url));
+        }
+        if let Some(path) = &conf.uuid_remap {
+            match std::fs::read_to_string(path).map_err(|e| e.to_string()) {
+                Ok(text) => match UuidRemap::parse(&text) {
+                    Ok(remap) => content.set_uuid_remap(remap),
+                    Err(e) => error!("Cannot parse uuid remap table {:?}: {}", path, e),
+                },
+                Err(e) => error!("Cannot read uuid remap table {:?}: {}", path, e),
+            }
+        }
+        content.set_memory_mapped(conf.mmap_assets);
+        content.set_memory_budget(conf.asset_memory_budget.map(|x| x * 1024 * 1024));
+        content.uploads.set_budget(conf.upload_budget * 1024 * 1024);
+        let texture_streamer = TextureStreamer::new(vulkan_state.transfer_queue());
+        let renderer_state =
+            RendererState::new(&vulkan_state, conf).expect("cannot create RendererState");
+        let input_state = Input::new(
+            vulkan_state.surface(),
+            ActionMap::from_config(&conf.action_bindings),
+        );
+        let remote = conf.remote_control.and_then(|addr| {
+            let output = conf.remote_output.clone();
+            match RemoteControl::listen(addr, output, conf.remote_token.clone()) {
+                Ok(t) => Some(t),
+                Err(e) => {
+                    error!("Cannot start remote control on {}: {:?}", addr, e);
+                    None
+                }
+            }
+        });
+        Self {
+            game_state: initial_state,
+            renderer_state,
+            vulkan_state,
+            content,
+            sequencer: None,
+            egui: EguiContext::default(),
+            movement: Movement::Fps,
+            turntable: Turntable::default(),
+            viewports: Vec::new(),
+            next_viewport: 0,
+            texture_streamer,
+            floating_origin: conf.floating_origin,
+            input_state,
+            remote,
+            window_mode: conf.window_mode,
+            resolution: conf.resolution,
+            unsynced_present_mode: match conf.present_mode {
+                PresentModePreference::Fifo => PresentModePreference::Mailbox,
+                mode => mode,
+            },
+            gui: false,
+            hud: false,
+            frame_count: 0,
+            frame_time: Duration::default(),
+            last_frame: Instant::now(),
+            timestep: FixedTimestep::new(conf.fixed_update_rate),
+            last_shader_check: conf.shader_source.as_ref().map(|_| Instant::now()),
+            frame_history: FrameHistory::default(),
+            render_scope: CPUProfiler::new("render"),
+            update_scope: CPUProfiler::new("update"),
+            event_loop: Some(event_loop),
+        }
+    }
+
+    pub fn update(&mut self, game: &mut dyn Game) {
+        self.frame_count += 1;
+        self.frame_time = self.last_frame.elapsed();
+        self.last_frame = Instant::now();
+        self.record_frame();
+
+        self.handle_remote_requests(game);
+
+        if let Some(last) = self.last_shader_check {
+            if last.elapsed() >= SHADER_CHECK_INTERVAL {
+                self.last_shader_check = Some(Instant::now());
+                if invalidate_changed_shaders() > 0 {
+                    self.reload_shaders();
+                }
+            }
+        }
+
+        if self.input_state.is_action_pressed("cycle_debug_view") {
+            let debug_view = &mut self.renderer_state.render_path.debug_view;
+            debug_view.view = debug_view.view.next();
+            info!("Debug view: {}", debug_view.view.name());
+        }
+
+        if self.input_state.is_action_pressed("toggle_gui") {
+            self.gui = !self.gui;
+            let input = &mut self.input_state;
+            input.gui.set_enabled(self.gui);
+            input.mouse.set_cursor_grabbed(!self.gui);
+            input.mouse.set_cursor_visible(self.gui);
+        }
+
+        if self.input_state.is_action_pressed("toggle_hud") {
+            self.hud = !self.hud;
+        }
+
+        if self.input_state.is_action_pressed("cycle_window_mode") {
+            self.set_window_mode(self.window_mode.next());
+        }
+
+        if self.input_state.is_action_pressed("toggle_vsync") {
+            let vsync = self.renderer_state.present_mode() == PresentModePreference::Fifo;
+            self.set_vsync(!vsync);
+        }
+        let scale_factor = self.window_size().scale_factor as f32;
+        self.renderer_state
+            .render_path
+            .overlay
+            .set_scale_factor(scale_factor);
+        if self.hud {
+            self.draw_hud();
+        }
+        if self.renderer_state.render_path.debug_view.view == DebugView::Exposure {
+            self.draw_histogram();
+        }
+
+        match &mut self.sequencer {
+            Some(seq) if seq.is_playing() => {
+                seq.advance(self.frame_time);
+                seq.apply(&mut self.game_state);
+            }
+            _ if self.gui => {}
+            // the orthographic camera is positioned by the game
+            _ if self.game_state.active_camera == ActiveCamera::Orthographic => {}
+            _ => self.movement.update(
+                &mut self.game_state.camera,
+                &self.input_state,
+                self.frame_time.as_secs_f32(),
+            ),
+        }
+
+        if let Some(rig) = self.game_state.camera_rig.as_mut() {
+            rig.update(&mut self.game_state.camera, self.frame_time.as_secs_f32());
+        }
+
+        if let Some(distance) = self.floating_origin {
+            let camera = self.game_state.render_camera().position().to_vec();
+            if camera.magnitude() > distance {
+                self.game_state.shift_origin(camera);
+            }
+        }
+
+        // textures that cover most of the screen should be loaded first
+        self.content.set_priorities(texture_priorities(
+            self.game_state.render_camera(),
+            &self.game_state.objects,
+        ));
+        let screen_height = self.window_size().physical.height;
+        self.texture_streamer.update(&self.content, screen_height);
+        self.renderer_state
+            .render_path
+            .spot_lights
+            .update_gobos(&self.game_state.spot_lights, &self.content);
+        self.content.next_frame();
+
+        self.vulkan_state.surface().window().set_title(&format!(
+            "{:?}",
+            self.game_state
+                .world_position(self.game_state.render_camera().position())
+        ));
+
+        let window = self.vulkan_state.surface();
+        self.egui
+            .begin_frame(&mut self.input_state.gui, window.window());
+        let dt = self.timestep.step().as_secs_f32();
+        for _ in 0..self.timestep.advance(self.frame_time) {
+            self.game_state.save_previous_transforms();
+            game.fixed_update(self, dt);
+        }
+        game.update(self);
+        let meshes = self.egui.end_frame();
+        if self.gui {
+            let painter = &mut self.renderer_state.render_path.gui;
+            painter.set_meshes(self.egui.ctx(), meshes);
+        }
+
+        // after the game, so the review lighting wins over lights set by the game
+        self.turntable
+            .update(&mut self.game_state, self.frame_time.as_secs_f32());
+    }
+
+    /// Returns the time of the last frame in seconds.
+    pub fn delta(&self) -> f32 {
+        self.frame_time.as_secs_f32()
+    }
+
+    /// Returns the length of the fixed update step in seconds.
+    pub fn fixed_delta(&self) -> f32 {
+        self.timestep.step().as_secs_f32()
+    }
+
+    /// Returns the fraction (0 to 1) of the next fixed update step that already
+    /// elapsed. Interpolated transforms are rendered at this fraction between
+    /// the last two fixed updates.
+    pub fn alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+
+    /// Rebuilds all pipelines of the render path (with shaders compiled from the
+    /// changed sources) and replaces the pipelines of the objects that used the
+    /// previous ones. Objects with custom pipelines keep them.
+    pub fn reload_shaders(&mut self) {
+        let start = Instant::now();
+        let previous = self.renderer_state.recreate_render_path();
+
+        let render_path = &self.renderer_state.render_path;
+        let viewports = self.viewports.iter_mut();
+        for state in
+            std::iter::once(&mut self.game_state).chain(viewports.map(|x| &mut x.game_state))
+        {
+            for object in state.objects.iter_mut() {
+                if let Some(pipeline) =
+                    render_path.replacement_pipeline(&previous, &object.pipeline, object.variant)
+                {
+                    object.pipeline = pipeline;
+                }
+            }
+        }
+        for renderer in self
+            .viewports
+            .iter_mut()
+            .flat_map(|x| x.renderer_state.as_mut())
+        {
+            renderer.recreate_render_path();
+        }
+
+        info!("Shaders reloaded in {:?}", start.elapsed());
+    }
+
+    /// Adds the frame that just finished (the interval between two updates) to
+    /// the frame history together with the events that happened during it.
+    fn record_frame(&mut self) {
+        let scopes = vec![
+            (self.render_scope.name(), self.render_scope.last_time()),
+            (self.update_scope.name(), self.update_scope.last_time()),
+        ];
+        let record = self.frame_history.push(
+            self.frame_count,
+            self.frame_time,
+            scopes,
+            frame_stats::take_events(),
+        );
+
+        if record.spike {
+            info!(
+                "Frame {} took {:.1} ms: {}",
+                record.frame,
+                record.time.as_secs_f64() * 1000.0,
+                record.annotation()
+            );
+        }
+    }
+
+    /// Adds frame statistics to the overlay of the next frame.
+    fn draw_hud(&mut self) {
+        let frame_ms = self.frame_time.as_secs_f64() * 1000.0;
+        let text = format!(
+            "FPS {:.0}\nCPU {:.2} MS\nDRAW CALLS {}\nLOAD QUEUE {}\nASSETS {} MB",
+            1000.0 / frame_ms.max(0.001),
+            frame_ms,
+            self.renderer_state.render_path.draw_calls,
+            self.content.pending_loads(),
+            self.content.memory_used() / (1024 * 1024),
+        );
+
+        let scale = 2.0;
+        let size = font::measure(&text);
+        let overlay = &mut self.renderer_state.render_path.overlay;
+        overlay.rect(
+            [0.0, 0.0],
+            [(size[0] + 4) as f32 * scale, (size[1] + 4) as f32 * scale],
+            [0.0, 0.0, 0.0, 0.6],
+        );
+        overlay.text([2.0 * scale, 2.0 * scale], scale, [1.0; 4], &text);
+
+        self.draw_frame_graph([0.0, (size[1] + 8) as f32 * scale], scale);
+    }
+
+    /// Draws the luminance histogram of the exposure debug view into the bottom
+    /// left corner. Bars are colored the same way as exposure zones of the view.
+    fn draw_histogram(&mut self) {
+        const HEIGHT: f32 = 96.0;
+
+        let scale = 2.0;
+        let bar_width = 2.0 * scale;
+        let screen_height = self.window_size().logical().height as f32;
+        let path = &mut self.renderer_state.render_path;
+        let histogram = match path.histogram.latest() {
+            Some(t) => t.clone(),
+            None => return,
+        };
+
+        let max = histogram.bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let text = match histogram.average_ev() {
+            Some(ev) => format!("EV {} TO +{} AVERAGE {:.1}", MIN_EV, MAX_EV, ev),
+            None => format!("EV {} TO +{}", MIN_EV, MAX_EV),
+        };
+        let text_height = (font::measure(&text)[1] + 4) as f32 * scale;
+        let position = [0.0, screen_height - HEIGHT - text_height];
+
+        path.overlay.rect(
+            position,
+            [HISTOGRAM_BINS as f32 * bar_width, HEIGHT + text_height],
+            [0.0, 0.0, 0.0, 0.6],
+        );
+        for (i, count) in histogram.bins.iter().enumerate() {
+            let height = HEIGHT * *count as f32 / max;
+            path.overlay.rect(
+                [
+                    position[0] + i as f32 * bar_width,
+                    position[1] + HEIGHT - height,
+                ],
+                [bar_width, height],
+                zone_color(Histogram::bin_ev(i)),
+            );
+        }
+        path.overlay.text(
+            [2.0 * scale, position[1] + HEIGHT + 2.0 * scale],
+            scale,
+            [1.0; 4],
+            &text,
+        );
+    }
+
+    /// Draws bars of frame times in the history (spikes are red) and the
+    /// annotations of the most recent spikes below them.
+    fn draw_frame_graph(&mut self, position: [f32; 2], scale: f32) {
+        const HEIGHT: f32 = 64.0;
+        const MAX_ANNOTATIONS: usize = 4;
+
+        let history = &self.frame_history;
+        let overlay = &mut self.renderer_state.render_path.overlay;
+        let width = frame_stats::HISTORY_LEN as f32;
+        // at least 33 ms fit into the graph so the bars of a smooth frame rate stay low
+        let max = history.max().as_secs_f32().max(0.033);
+
+        overlay.rect(position, [width * scale, HEIGHT], [0.0, 0.0, 0.0, 0.6]);
+        for (i, frame) in history.frames().enumerate() {
+            let height = HEIGHT * frame.time.as_secs_f32() / max;
+            let color = if frame.spike {
+                [1.0, 0.2, 0.2, 1.0]
+            } else {
+                [0.2, 1.0, 0.2, 1.0]
+            };
+            overlay.rect(
+                [
+                    position[0] + i as f32 * scale,
+                    position[1] + HEIGHT - height,
+                ],
+                [scale, height],
+                color,
+            );
+        }
+
+        let text = history
+            .spikes()
+            .take(MAX_ANNOTATIONS)
+            .map(|x| {
+                format!(
+                    "FRAME {} {:.1} MS: {}",
+                    x.frame,
+                    x.time.as_secs_f64() * 1000.0,
+                    x.annotation()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if !text.is_empty() {
+            let size = font::measure(&text);
+            let top = position[1] + HEIGHT;
+            overlay.rect(
+                [position[0], top],
+                [(size[0] + 4) as f32 * scale, (size[1] + 4) as f32 * scale],
+                [0.0, 0.0, 0.0, 0.6],
+            );
+            overlay.text(
+                [position[0] + 2.0 * scale, top + 2.0 * scale],
+                scale,
+                [1.0; 4],
+                &text,
+            );
+        }
+    }
+
+    /// Executes all requests received over the remote control channel.
+    fn handle_remote_requests(&mut self, game: &mut dyn Game) {
+        let requests: Vec<_> = match &self.remote {
+            None => return,
+            Some(remote) => remote.poll().collect(),
+        };
+
+        for request in requests {
+            match request.command.clone() {
+                Command::Screenshot(path) => {
+                    let rx = self.renderer_state.request_screenshot();
+
+                    // encoding the png is slow so we do not want to block the main thread
+                    std::thread::spawn(move || {
+                        let result = rx
+                            .recv()
+                            .map_err(|e| e.to_string())
+                            .and_then(|img| {
+                                create_parent_dir(&path)?;
+                                img.save(&path).map_err(|e| e.to_string())
+                            })
+                            .map(|_| path.display().to_string());
+                        request.reply(result);
+                    });
+                }
+                cmd => {
+                    let result = self.execute_command(cmd, game);
+                    request.reply(result);
+                }
+            }
+        }
+    }
+
+    fn execute_command(&mut self, cmd: Command, game: &mut dyn Game) -> Result<String, String> {
+        match cmd {
+            Command::LoadScene(name) => game.load_scene(self, &name).map(|_| String::new()),
+            Command::Set(name, value) => self.set_cvar(&name, &value).map(|_| String::new()),
+            Command::Get(name) => self.get_cvar(&name),
+            Command::Stats => Ok(format!(
+                "frames={} frame_time_ms={:.3} uptime_s={:.1} objects={} lights={} spot_lights={}",
+                self.frame_count,
+                self.frame_time.as_secs_f64() * 1000.0,
+                self.game_state.start.elapsed().as_secs_f64(),
+                self.game_state.objects.len(),
+                self.game_state.directional_lights.len(),
+                self.game_state.spot_lights.len(),
+            )),
+            Command::Sequence(action) => self.control_sequence(&action).map(|_| String::new()),
+            Command::Graph(path) => self.export_graph(&path).map(|_| String::new()),
+            Command::Screenshot(_) => unreachable!("screenshots are handled asynchronously"),
+        }
+    }
+
+    /// Saves the main render graph to `path` as JSON or GraphViz DOT file.
+    fn export_graph(&self, path: &Path) -> Result<(), String> {
+        let graph = &self.renderer_state.render_path.main_graph.graph;
+        let contents = match path.extension().and_then(|x| x.to_str()) {
+            Some("json") => graph.to_json(),
+            _ => graph.to_dot(),
+        };
+
+        create_parent_dir(path)?;
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+
+    /// Controls playback of the current sequence.
+    fn control_sequence(&mut self, action: &str) -> Result<(), String> {
+        let seq = self.sequencer.as_mut().ok_or("no sequence is loaded")?;
+        let (name, arg) = action
+            .split_once(char::is_whitespace)
+            .unwrap_or((action, ""));
+        let float = || arg.trim().parse::<f32>().map_err(|e| e.to_string());
+
+        match name {
+            "play" => seq.play(),
+            "pause" => seq.pause(),
+            "stop" => seq.stop(),
+            "seek" => seq.seek(float()?),
+            "speed" => seq.set_speed(float()?),
+            "loop" => seq.set_looping(arg.trim() == "on"),
+            _ => return Err(format!("unknown sequence action {:?}", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Sets value of console variable with specified name.
+    fn set_cvar(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let float = || value.parse::<f32>().map_err(|e| e.to_string());
+        let camera = &mut self.game_state.camera;
+        let path = &mut self.renderer_state.render_path;
+
+        match name {
+            "camera.active" => {
+                self.game_state.active_camera = match value {
+                    "perspective" => ActiveCamera::Perspective,
+                    "orthographic" => ActiveCamera::Orthographic,
+                    _ => return Err(format!("unknown camera {:?}", value)),
+                }
+            }
+            "camera.fov" => camera.fov = Deg(float()?).into(),
+            "camera.near" => camera.near = float()?,
+            "camera.far" => camera.far = float()?,
+            "camera.position" => camera.position = Point3::from(parse_vec3(value)?),
+            "sky.turbidity" => path.sky.turbidity = float()?,
+            "sky.ground_albedo" => path.sky.ground_albedo = Vector3::from(parse_vec3(value)?),
+            "render.debug_view" => path.debug_view.view = value.parse()?,
+            "render.fxaa_quality" => path
+                .post
+                .effect_mut::<FXAA>()
+                .ok_or("fxaa is not in the post-processing chain")?
+                .set_quality(value.parse()?),
+            "render.grain_intensity" => {
+                path.post
+                    .effect_mut::<Grain>()
+                    .ok_or("grain is not in the post-processing chain")?
+                    .intensity = float()?
+            }
+            "turntable.enabled" => match value {
+                "on" => self.turntable.enable(&mut self.game_state),
+                "off" => self.turntable.disable(&mut self.game_state),
+                _ => return Err(format!("expected on or off, got {:?}", value)),
+            },
+            "turntable.object" => {
+                self.turntable.object = match value {
+                    "none" => None,
+                    _ => {
+                        let index = value.parse::<usize>().map_err(|e| e.to_string())?;
+                        let objects = &self.game_state.objects;
+                        let id = objects.iter_with_ids().nth(index).map(|x| x.0);
+                        Some(id.ok_or_else(|| format!("no object with index {}", index))?)
+                    }
+                }
+            }
+            "window.mode" => self.set_window_mode(value.parse()?),
+            "window.resolution" => self.set_resolution(parse_resolution(value)?)?,
+            "render.present_mode" => self.set_present_mode(value.parse()?),
+            "turntable.speed" => self.turntable.speed = float()?,
+            "turntable.luminance" => self.turntable.luminance = float()?,
+            "turntable.ambient" => self.turntable.ambient = float()?,
+            "render.post_effects" => path.post.configure(
+                &value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                    .collect::<Vec<_>>(),
+            )?,
+            _ => return Err(format!("unknown cvar {:?}", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Returns value of console variable with specified name.
+    fn get_cvar(&self, name: &str) -> Result<String, String> {
+        let camera = &self.game_state.camera;
+        let path = &self.renderer_state.render_path;
+
+        Ok(match name {
+            "camera.active" => match self.game_state.active_camera {
+                ActiveCamera::Perspective => "perspective".to_string(),
+                ActiveCamera::Orthographic => "orthographic".to_string(),
+            },
+            "camera.fov" => Deg::from(camera.fov).0.to_string(),
+            "camera.near" => camera.near.to_string(),
+            "camera.far" => camera.far.to_string(),
+            "camera.position" => format_vec3(camera.position.into()),
+            "sky.turbidity" => path.sky.turbidity.to_string(),
+            "sky.ground_albedo" => format_vec3(path.sky.ground_albedo.into()),
+            "render.debug_view" => path.debug_view.view.name().to_string(),
+            "render.fxaa_quality" => path
+                .post
+                .effect::<FXAA>()
+                .ok_or("fxaa is not in the post-processing chain")?
+                .quality()
+                .name()
+                .to_string(),
+            "render.grain_intensity" => path
+                .post
+                .effect::<Grain>()
+                .ok_or("grain is not in the post-processing chain")?
+                .intensity
+                .to_string(),
+            "render.post_effects" => path.post.enabled().join(","),
+            "turntable.enabled" => {
+                if self.turntable.is_enabled() {
+                    "on".to_string()
+                } else {
+                    "off".to_string()
+                }
+            }
+            "turntable.object" => self
+                .turntable
+                .object
+                .and_then(|id| {
+                    self.game_state
+                        .objects
+                        .iter_with_ids()
+                        .position(|x| x.0 == id)
+                })
+                .map_or("none".to_string(), |x| x.to_string()),
+            "window.mode" => self.window_mode.name().to_string(),
+            "window.resolution" => format!("{}x{}", self.resolution[0], self.resolution[1]),
+            "render.present_mode" => self.renderer_state.present_mode().name().to_string(),
+            "turntable.speed" => self.turntable.speed.to_string(),
+            "turntable.luminance" => self.turntable.luminance.to_string(),
+            "turntable.ambient" => self.turntable.ambient.to_string(),
+            _ => return Err(format!("unknown cvar {:?}", name)),
+        })
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.window_mode
+    }
+
+    /// Switches the window to the `mode`. The swapchain and render targets are
+    /// recreated when the window reports its new size.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        let surface = self.vulkan_state.surface();
+        let window = surface.window();
+        window.set_fullscreen(mode.fullscreen(window.current_monitor(), self.resolution));
+        if mode == WindowMode::Windowed {
+            window.set_inner_size(self.logical_resolution());
+        }
+
+        self.window_mode = mode;
+        info!("Window mode: {}", mode.name());
+    }
+
+    /// Returns the size of the windowed window (in logical pixels) and of the
+    /// video mode of exclusive fullscreen.
+    pub fn resolution(&self) -> [u16; 2] {
+        self.resolution
+    }
+
+    /// Changes the size of the windowed window and the video mode of exclusive
+    /// fullscreen. Borderless window keeps the size of the monitor and the
+    /// resolution is used when it is switched back to windowed.
+    pub fn set_resolution(&mut self, resolution: [u16; 2]) -> Result<(), String> {
+        if resolution.contains(&0) {
+            return Err(format!(
+                "resolution {}x{} must be at least 1x1",
+                resolution[0], resolution[1]
+            ));
+        }
+
+        self.resolution = resolution;
+        match self.window_mode {
+            WindowMode::Windowed => {
+                let size = self.logical_resolution();
+                self.vulkan_state.surface().window().set_inner_size(size);
+            }
+            WindowMode::Fullscreen => self.set_window_mode(WindowMode::Fullscreen),
+            WindowMode::Borderless => {}
+        }
+        Ok(())
+    }
+
+    fn logical_resolution(&self) -> LogicalSize<f64> {
+        LogicalSize::new(self.resolution[0] as f64, self.resolution[1] as f64)
+    }
+
+    /// Changes the preferred present mode of the swapchain without restarting.
+    pub fn set_present_mode(&mut self, present_mode: PresentModePreference) {
+        if present_mode != PresentModePreference::Fifo {
+            self.unsynced_present_mode = present_mode;
+        }
+        self.renderer_state.set_present_mode(present_mode);
+        info!("Present mode: {}", present_mode.name());
+    }
+
+    /// Turns vsync on (`Fifo` present mode) or off (the last used or configured
+    /// other present mode, `Mailbox` by default).
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.set_present_mode(if vsync {
+            PresentModePreference::Fifo
+        } else {
+            self.unsynced_present_mode
+        });
+    }
+
+    /// Opens a window with the `title` and `size` (in logical pixels) rendering
+    /// the `game_state`. The window is created before the next frame.
+    pub fn open_viewport(
+        &mut self,
+        title: &str,
+        size: [u16; 2],
+        game_state: GameState,
+    ) -> ViewportId {
+        let id = ViewportId(self.next_viewport);
+        self.next_viewport += 1;
+        self.viewports
+            .push(Viewport::new(id, title, size, game_state));
+        id
+    }
+
+    /// Closes the window of the viewport. Returns whether the viewport was open.
+    pub fn close_viewport(&mut self, id: ViewportId) -> bool {
+        let count = self.viewports.len();
+        self.viewports.retain(|x| x.id() != id);
+        self.viewports.len() != count
+    }
+
+    pub fn viewport(&self, id: ViewportId) -> Option<&Viewport> {
+        self.viewports.iter().find(|x| x.id() == id)
+    }
+
+    pub fn viewport_mut(&mut self, id: ViewportId) -> Option<&mut Viewport> {
+        self.viewports.iter_mut().find(|x| x.id() == id)
+    }
+
+    /// Creates windows and renderers of viewports opened since the last frame.
+    /// Viewports whose window can't be created are closed.
+    fn create_viewport_windows(&mut self, target: &EventLoopWindowTarget<()>) {
+        let vulkan = &self.vulkan_state;
+        let main = &self.renderer_state;
+        let mut failed = vec![];
+
+        for viewport in self
+            .viewports
+            .iter_mut()
+            .filter(|x| x.renderer_state.is_none())
+        {
+            let renderer = vulkan
+                .create_window(target, viewport.title(), viewport.size())
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|surface| {
+                    main.for_window(vulkan, surface)
+                        .map_err(|e| format!("{:?}", e))
+                });
+            match renderer {
+                Ok(renderer) => viewport.renderer_state = Some(renderer),
+                Err(e) => {
+                    error!("Cannot open viewport {:?}: {}", viewport.title(), e);
+                    failed.push(viewport.id());
+                }
+            }
+        }
+
+        self.viewports.retain(|x| !failed.contains(&x.id()));
+    }
+
+    /// Renders the worlds of all viewports into their windows. Their frames
+    /// don't wait for uploads of this frame (only the main window does), so
+    /// viewports should show assets that are already shown in the main window
+    /// or were loaded a few frames earlier.
+    fn render_viewports(&mut self) {
+        for viewport in self.viewports.iter_mut() {
+            if let Some(renderer) = viewport.renderer_state.as_mut() {
+                viewport.game_state.update_transforms();
+                viewport.game_state.update_lods();
+                renderer.render_frame(&viewport.game_state, None);
+            }
+        }
+    }
+
+    /// Handles an event of the window of a viewport.
+    fn handle_viewport_event(&mut self, window_id: WindowId, event: WindowEvent) {
+        let idx = match self
+            .viewports
+            .iter()
+            .position(|x| x.window_id() == Some(window_id))
+        {
+            Some(t) => t,
+            None => return,
+        };
+
+        let new_size = match event {
+            WindowEvent::CloseRequested => {
+                self.viewports.remove(idx);
+                return;
+            }
+            WindowEvent::Resized(new_size) => new_size,
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => *new_inner_size,
+            _ => return,
+        };
+
+        let viewport = &mut self.viewports[idx];
+        if let Some(aspect_ratio) = WindowSize::new(new_size, 1.0).aspect_ratio() {
+            viewport.game_state.camera.aspect_ratio = aspect_ratio;
+            viewport.game_state.orthographic_camera.aspect_ratio = aspect_ratio;
+        }
+        if let Some(renderer) = viewport.renderer_state.as_mut() {
+            renderer.window_resized();
+        }
+    }
+
+    /// Returns the size of the window in physical pixels (the resolution the
+    /// frames are rendered at) and its scale factor.
+    pub fn window_size(&self) -> WindowSize {
+        WindowSize::of(self.vulkan_state.surface().window())
+    }
+
+    /// Returns the closest visible object under the cursor in the main window
+    /// (see `picking` module).
+    pub fn pick_under_cursor(&self) -> Option<Hit> {
+        let (x, y) = self.input_state.mouse.position();
+        let ray = Ray::from_cursor(
+            self.game_state.render_camera(),
+            PhysicalPosition::new(x, y),
+            self.window_size().physical,
+        )?;
+        pick(&self.game_state, &ray)
+    }
+
+    /// Updates aspect ratios of cameras and recreates the swapchain when the
+    /// window is resized or moved to a display with a different scale factor.
+    fn window_resized(&mut self, new_size: PhysicalSize<u32>) {
+        let size = WindowSize::new(new_size, self.window_size().scale_factor);
+        if let Some(aspect_ratio) = size.aspect_ratio() {
+            self.game_state.camera.aspect_ratio = aspect_ratio;
+            self.game_state.orthographic_camera.aspect_ratio = aspect_ratio;
+        }
+        self.renderer_state.window_resized();
+    }
+
+    /// Runs the event loop and renders frames until the main window is closed.
+    /// The `game` is updated once per frame and at the fixed rate. Windows of
+    /// viewports are rendered after the main window.
+    pub fn run_forever<G: Game + 'static>(mut self, mut game: G) -> ! {
+        let main_window = self.vulkan_state.surface().window().id();
+        self.event_loop
+            .take()
+            .unwrap()
+            .run(move |ev, target, flow| match ev {
+                Event::WindowEvent { window_id, event } if window_id != main_window => {
+                    self.handle_viewport_event(window_id, event)
+                }
+                Event::WindowEvent { event, .. } => {
+                    self.input_state.handle_window_event(&event);
+                    match event {
+                        WindowEvent::CloseRequested => *flow = ControlFlow::Exit,
+                        WindowEvent::Focused(focus) => self.input_state.set_enabled(focus),
+                        WindowEvent::Resized(new_size) => self.window_resized(new_size),
+                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                            self.window_resized(*new_inner_size)
+                        }
+                        _ => {}
+                    }
+                }
+                Event::DeviceEvent { event, .. } => self.input_state.handle_device_event(&event),
+                Event::LoopDestroyed => {
+                    if let Err(e) = self.vulkan_state.pipeline_cache().save() {
+                        error!("Cannot save pipeline cache: {}", e);
+                    }
+                }
+                Event::RedrawEventsCleared => {
+                    self.create_viewport_windows(target);
+                    let alpha = self.timestep.alpha();
+                    self.game_state.update_transforms_interpolated(alpha);
+                    self.game_state.update_lods();
+                    self.render_scope.start();
+                    let uploads = self.content.uploads.take_submitted();
+                    self.renderer_state
+                        .render_path
+                        .debug_lines
+                        .set_lines(debug_draw::take());
+                    self.renderer_state.render_frame(&self.game_state, uploads);
+                    self.render_viewports();
+                    self.render_scope.end();
+                    self.update_scope.start();
+                    self.update(&mut game);
+                    self.content.uploads.submit();
+                    self.update_scope.end();
+                    self.input_state.frame_finished();
+                }
+                _ => {}
+            });
+    }
+}
+
+/// Parses a vector in `x,y,z` format.
+fn parse_vec3(value: &str) -> Result<[f32; 3], String> {
+    let parts = value
+        .split(',')
+        .map(|x| x.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match parts.as_slice() {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(format!("expected three components, got {:?}", value)),
+    }
+}
+
+fn format_vec3(v: [f32; 3]) -> String {
+    format!("{},{},{}", v[0], v[1], v[2])
+}
+
+/// Creates the directory of the file at `path` (eg. the output directory of the
+/// remote control) if it does not exist.
+fn create_parent_dir(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())
+        }
+        _ => Ok(()),
+    }
+}
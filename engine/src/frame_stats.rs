@@ -0,0 +1,263 @@
+//! History of frame times with events that happened during each frame.
+//!
+//! Systems that may cause a hitch (loading and uploading of assets, building
+//! of pipelines, recreation of the swapchain) [`record`](fn.record.html) an
+//! event when they do the work. Events recorded on any thread are attributed
+//! to the frame that is being processed when they happen. The engine collects
+//! them at the end of each frame together with timings of its scopes, so
+//! frame-time spikes can be annotated with their probable cause.
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Number of frames kept in the history.
+pub const HISTORY_LEN: usize = 240;
+/// Frame is a spike when it takes this many times longer than the average
+/// of the history.
+const SPIKE_FACTOR: f64 = 2.0;
+/// Frames shorter than this are never considered spikes (an idle frame of
+/// very fast scene is not a hitch).
+const MIN_SPIKE_TIME: Duration = Duration::from_millis(8);
+
+/// Kind of work that can make a frame take longer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameEvent {
+    /// Asset was loaded from disk by a content worker.
+    AssetLoaded = 0,
+    /// Mesh or image (or its mip-maps) was uploaded to the GPU.
+    AssetUploaded = 1,
+    /// Graphics pipeline was built.
+    PipelineBuilt = 2,
+    /// Swapchain and framebuffers were recreated.
+    SwapchainRecreated = 3,
+}
+
+const EVENT_COUNT: usize = 4;
+const EVENTS: [FrameEvent; EVENT_COUNT] = [
+    FrameEvent::AssetLoaded,
+    FrameEvent::AssetUploaded,
+    FrameEvent::PipelineBuilt,
+    FrameEvent::SwapchainRecreated,
+];
+
+impl FrameEvent {
+    fn describe(self, count: u32) -> String {
+        let (one, many) = match self {
+            FrameEvent::AssetLoaded => ("asset loaded", "assets loaded"),
+            FrameEvent::AssetUploaded => ("asset uploaded", "assets uploaded"),
+            FrameEvent::PipelineBuilt => ("pipeline built", "pipelines built"),
+            FrameEvent::SwapchainRecreated => ("swapchain recreated", "swapchain recreated"),
+        };
+
+        match count {
+            1 => one.to_string(),
+            _ => format!("{} {}", count, many),
+        }
+    }
+}
+
+/// Events recorded since the last call of `take_events`.
+static PENDING: [AtomicU32; EVENT_COUNT] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+/// Records that the `event` happened during the current frame. Can be called
+/// from any thread.
+pub fn record(event: FrameEvent) {
+    PENDING[event as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns events recorded since the last call and resets them.
+pub fn take_events() -> EventCounts {
+    let mut counts = EventCounts::default();
+    for (count, pending) in counts.0.iter_mut().zip(PENDING.iter()) {
+        *count = pending.swap(0, Ordering::Relaxed);
+    }
+    counts
+}
+
+/// Number of events of each kind that happened during one frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EventCounts([u32; EVENT_COUNT]);
+
+impl EventCounts {
+    pub fn get(&self, event: FrameEvent) -> u32 {
+        self.0[event as usize]
+    }
+
+    pub fn add(&mut self, event: FrameEvent, count: u32) {
+        self.0[event as usize] += count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|x| *x == 0)
+    }
+}
+
+impl Display for EventCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts = EVENTS
+            .iter()
+            .filter(|e| self.get(**e) > 0)
+            .map(|e| e.describe(self.get(*e)))
+            .collect::<Vec<_>>();
+
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Timing and events of a single frame.
+#[derive(Clone, Debug)]
+pub struct FrameRecord {
+    /// Sequence number of the frame.
+    pub frame: u64,
+    pub time: Duration,
+    /// Durations of named perf scopes measured during the frame.
+    pub scopes: Vec<(&'static str, Duration)>,
+    pub events: EventCounts,
+    /// Whether the frame took much longer than the frames before it.
+    pub spike: bool,
+}
+
+impl FrameRecord {
+    /// Returns human readable description of what (probably) caused the
+    /// frame to take long: the events of the frame and its slowest scope.
+    pub fn annotation(&self) -> String {
+        let slowest = self.scopes.iter().max_by_key(|(_, time)| *time);
+        let scope =
+            slowest.map(|(name, time)| format!("{} {:.1} ms", name, time.as_secs_f64() * 1000.0));
+
+        match (self.events.is_empty(), scope) {
+            (false, Some(scope)) => format!("{} ({})", self.events, scope),
+            (false, None) => self.events.to_string(),
+            (true, Some(scope)) => scope,
+            (true, None) => String::new(),
+        }
+    }
+}
+
+/// Ring buffer of the most recent frames.
+pub struct FrameHistory {
+    frames: VecDeque<FrameRecord>,
+}
+
+impl Default for FrameHistory {
+    fn default() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+}
+
+impl FrameHistory {
+    /// Adds the frame to the history (dropping the oldest frame if the history
+    /// is full) and returns the record of it.
+    pub fn push(
+        &mut self,
+        frame: u64,
+        time: Duration,
+        scopes: Vec<(&'static str, Duration)>,
+        events: EventCounts,
+    ) -> &FrameRecord {
+        let spike = time >= MIN_SPIKE_TIME
+            && !self.frames.is_empty()
+            && time.as_secs_f64() > self.average().as_secs_f64() * SPIKE_FACTOR;
+
+        if self.frames.len() == HISTORY_LEN {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(FrameRecord {
+            frame,
+            time,
+            scopes,
+            events,
+            spike,
+        });
+        self.frames.back().unwrap()
+    }
+
+    /// Returns the average frame time of the history.
+    pub fn average(&self) -> Duration {
+        match self.frames.len() {
+            0 => Duration::default(),
+            n => self.frames.iter().map(|x| x.time).sum::<Duration>() / n as u32,
+        }
+    }
+
+    /// Returns the longest frame time of the history.
+    pub fn max(&self) -> Duration {
+        self.frames.iter().map(|x| x.time).max().unwrap_or_default()
+    }
+
+    /// Returns iterator over the frames from the oldest one.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameRecord> {
+        self.frames.iter()
+    }
+
+    /// Returns iterator over the spikes in the history from the newest one.
+    pub fn spikes(&self) -> impl Iterator<Item = &FrameRecord> {
+        self.frames.iter().rev().filter(|x| x.spike)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_stats::{record, take_events, EventCounts, FrameEvent, FrameHistory};
+    use std::time::Duration;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn recorded_events_are_taken_once() {
+        record(FrameEvent::SwapchainRecreated);
+        record(FrameEvent::SwapchainRecreated);
+
+        assert_eq!(take_events().get(FrameEvent::SwapchainRecreated), 2);
+        assert_eq!(take_events().get(FrameEvent::SwapchainRecreated), 0);
+    }
+
+    #[test]
+    fn spikes_are_annotated() {
+        let mut history = FrameHistory::default();
+        for frame in 0..10 {
+            history.push(frame, ms(10), vec![], EventCounts::default());
+        }
+
+        let mut events = EventCounts::default();
+        events.add(FrameEvent::AssetUploaded, 3);
+        events.add(FrameEvent::PipelineBuilt, 1);
+        let scopes = vec![("render", ms(35)), ("update", ms(5))];
+        let spike = history.push(10, ms(40), scopes, events);
+
+        assert!(spike.spike);
+        assert_eq!(
+            spike.annotation(),
+            "3 assets uploaded, pipeline built (render 35.0 ms)"
+        );
+
+        history.push(11, ms(12), vec![], EventCounts::default());
+        assert_eq!(history.spikes().map(|x| x.frame).collect::<Vec<_>>(), [10]);
+        assert_eq!(history.max(), ms(40));
+    }
+
+    #[test]
+    fn short_frames_are_not_spikes() {
+        let mut history = FrameHistory::default();
+        history.push(0, ms(1), vec![], EventCounts::default());
+
+        assert!(!history.push(1, ms(5), vec![], EventCounts::default()).spike);
+        assert_eq!(
+            history
+                .push(2, ms(1), vec![], EventCounts::default())
+                .annotation(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Mapping of named gameplay actions (eg. `"move_forward"`) to physical keys & buttons.
+//!
+//! Bindings are specified as strings in `[-]Device:Name` format where device
+//! is one of `Key`, `Gamepad` (button) or `GamepadAxis`. The optional minus
+//! sign inverts the value of the binding when the action is queried as an axis.
+//!
+//! ```ignore
+//! let mut actions = ActionMap::default();
+//! actions.bind_str("move_forward", "Key:W")?;
+//! actions.bind_str("move_forward", "-Key:S")?;
+//! actions.bind_str("move_forward", "GamepadAxis:LeftStickY")?;
+//! ```
+
+use crate::input::gamepad::{Gamepad, GamepadAxis, GamepadButton};
+use crate::input::keyboard::Keyboard;
+use log::error;
+use std::collections::HashMap;
+use std::str::FromStr;
+use winit::event::VirtualKeyCode;
+
+/// Physical input that can be bound to an action.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PhysicalInput {
+    Key(VirtualKeyCode),
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis),
+}
+
+/// Single physical input bound to an action with a scale that is applied
+/// to its value when the action is queried as an axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ActionBinding {
+    pub input: PhysicalInput,
+    pub scale: f32,
+}
+
+/// Generates a function that converts name of enum variant to the variant.
+macro_rules! parse_by_name {
+    ($fn_name: ident, $type: ident; $($variant: ident),+) => {
+        fn $fn_name(name: &str) -> Option<$type> {
+            match name {
+                $(stringify!($variant) => Some($type::$variant),)+
+                _ => None,
+            }
+        }
+    };
+}
+
+parse_by_name!(parse_key, VirtualKeyCode;
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down,
+    Back, Return, Space, Tab, Grave, Minus, Equals, Comma, Period,
+    LAlt, LControl, LShift, RAlt, RControl, RShift
+);
+
+parse_by_name!(parse_gamepad_button, GamepadButton;
+    South, East, North, West, C, Z, LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+    Select, Start, Mode, LeftThumb, RightThumb, DPadUp, DPadDown, DPadLeft, DPadRight
+);
+
+parse_by_name!(parse_gamepad_axis, GamepadAxis;
+    LeftStickX, LeftStickY, LeftZ, RightStickX, RightStickY, RightZ, DPadX, DPadY
+);
+
+impl FromStr for ActionBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scale, s) = match s.strip_prefix('-') {
+            Some(rest) => (-1.0, rest),
+            None => (1.0, s),
+        };
+
+        let (device, name) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid binding {:?}: expected Device:Name", s))?;
+
+        let input = match device {
+            "Key" => parse_key(name).map(PhysicalInput::Key),
+            "Gamepad" => parse_gamepad_button(name).map(PhysicalInput::GamepadButton),
+            "GamepadAxis" => parse_gamepad_axis(name).map(PhysicalInput::GamepadAxis),
+            _ => return Err(format!("invalid binding {:?}: unknown device", s)),
+        }
+        .ok_or_else(|| format!("invalid binding {:?}: unknown {} {:?}", s, device, name))?;
+
+        Ok(ActionBinding { input, scale })
+    }
+}
+
+/// Resolves named actions to physical inputs. The bindings can be changed
+/// at runtime.
+#[derive(Default)]
+pub struct ActionMap {
+    bindings: HashMap<String, Vec<ActionBinding>>,
+}
+
+impl ActionMap {
+    /// Creates a new `ActionMap` from configuration map of action names to
+    /// binding strings. Invalid bindings are reported and skipped.
+    pub fn from_config(config: &HashMap<String, Vec<String>>) -> Self {
+        let mut map = Self::default();
+
+        for (action, bindings) in config {
+            for binding in bindings {
+                if let Err(e) = map.bind_str(action, binding) {
+                    error!("Cannot bind action {:?}: {}", action, e);
+                }
+            }
+        }
+
+        map
+    }
+
+    /// Adds a binding to specified action.
+    pub fn bind(&mut self, action: &str, binding: ActionBinding) {
+        self.bindings
+            .entry(action.to_string())
+            .or_default()
+            .push(binding);
+    }
+
+    /// Parses the binding string and adds it to specified action.
+    pub fn bind_str(&mut self, action: &str, binding: &str) -> Result<(), String> {
+        self.bind(action, binding.parse()?);
+        Ok(())
+    }
+
+    /// Replaces all bindings of specified action with provided ones.
+    pub fn rebind(&mut self, action: &str, bindings: Vec<ActionBinding>) {
+        self.bindings.insert(action.to_string(), bindings);
+    }
+
+    /// Removes all bindings of specified action.
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    /// Returns all bindings of specified action.
+    pub fn bindings(&self, action: &str) -> &[ActionBinding] {
+        self.bindings.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns whether any input bound to the action is currently held down.
+    pub fn is_action_down(&self, action: &str, keyboard: &Keyboard, gamepad: &Gamepad) -> bool {
+        self.bindings(action).iter().any(|b| match b.input {
+            PhysicalInput::Key(k) => keyboard.is_key_down(k),
+            PhysicalInput::GamepadButton(btn) => gamepad.is_button_down(btn),
+            PhysicalInput::GamepadAxis(axis) => gamepad.axis(axis) * b.scale > 0.5,
+        })
+    }
+
+    /// Returns whether any input bound to the action was pressed in this frame.
+    pub fn is_action_pressed(&self, action: &str, keyboard: &Keyboard, gamepad: &Gamepad) -> bool {
+        self.bindings(action).iter().any(|b| match b.input {
+            PhysicalInput::Key(k) => keyboard.was_key_pressed(k),
+            PhysicalInput::GamepadButton(btn) => gamepad.was_button_pressed(btn),
+            PhysicalInput::GamepadAxis(_) => false,
+        })
+    }
+
+    /// Returns the value of the action as an axis. The value is sum of values of
+    /// all bound inputs (multiplied by their scale) clamped to `-1.0..=1.0`.
+    pub fn action_axis(&self, action: &str, keyboard: &Keyboard, gamepad: &Gamepad) -> f32 {
+        self.bindings(action)
+            .iter()
+            .map(|b| {
+                b.scale
+                    * match b.input {
+                        PhysicalInput::Key(k) => keyboard.is_key_down(k) as u8 as f32,
+                        PhysicalInput::GamepadButton(btn) => gamepad
+                            .button_value(btn)
+                            .max(gamepad.is_button_down(btn) as u8 as f32),
+                        PhysicalInput::GamepadAxis(axis) => gamepad.axis(axis),
+                    }
+            })
+            .sum::<f32>()
+            .max(-1.0)
+            .min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::actions::{ActionBinding, PhysicalInput};
+    use crate::input::gamepad::GamepadButton;
+    use winit::event::VirtualKeyCode;
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(
+            "Key:S".parse::<ActionBinding>(),
+            Ok(ActionBinding {
+                input: PhysicalInput::Key(VirtualKeyCode::S),
+                scale: 1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn minus_sign_inverts_the_axis() {
+        assert_eq!(
+            "-Key:S".parse::<ActionBinding>(),
+            Ok(ActionBinding {
+                input: PhysicalInput::Key(VirtualKeyCode::S),
+                scale: -1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_gamepad_buttons() {
+        assert_eq!(
+            "Gamepad:North".parse::<ActionBinding>(),
+            Ok(ActionBinding {
+                input: PhysicalInput::GamepadButton(GamepadButton::North),
+                scale: 1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_devices_and_names() {
+        assert_eq!(
+            "Mouse:Left".parse::<ActionBinding>(),
+            Err("invalid binding \"Mouse:Left\": unknown device".to_string())
+        );
+        assert_eq!(
+            "Key:Foo".parse::<ActionBinding>(),
+            Err("invalid binding \"Key:Foo\": unknown Key \"Foo\"".to_string())
+        );
+        assert!("Key".parse::<ActionBinding>().is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Functionality related to handling gamepad (controller) input.
+
+use gilrs::{EventType, Gilrs};
+use log::{error, info};
+use std::collections::{HashMap, HashSet};
+
+pub use gilrs::{Axis as GamepadAxis, Button as GamepadButton};
+
+/// Gamepad input and state. Input from all connected gamepads is merged
+/// together as if there was only one gamepad.
+///
+/// Unlike keyboard and mouse, gamepad events are not delivered by `winit`.
+/// They are polled from the operating system in `frame_finished` method.
+pub struct Gamepad {
+    gilrs: Option<Gilrs>,
+    axes: HashMap<GamepadAxis, f32>,
+    button_values: HashMap<GamepadButton, f32>,
+    current_button_state: HashSet<GamepadButton>,
+    previous_button_state: HashSet<GamepadButton>,
+    input_enabled: bool,
+    /// Axis values with smaller magnitude than this are reported as zero.
+    pub dead_zone: f32,
+}
+
+// need this because we want input to be enabled from start
+impl Default for Gamepad {
+    fn default() -> Self {
+        // gamepad support is optional, we can live without it
+        let gilrs = match Gilrs::new() {
+            Ok(t) => Some(t),
+            Err(e) => {
+                error!("Cannot initialize gamepad support: {:?}", e);
+                None
+            }
+        };
+
+        if let Some(g) = &gilrs {
+            for (_, gamepad) in g.gamepads() {
+                info!("Found gamepad {:?}", gamepad.name());
+            }
+        }
+
+        Self {
+            gilrs,
+            axes: HashMap::new(),
+            button_values: HashMap::new(),
+            current_button_state: HashSet::new(),
+            previous_button_state: HashSet::new(),
+            input_enabled: true,
+            dead_zone: 0.15,
+        }
+    }
+}
+
+impl Gamepad {
+    /// Returns whether is the gamepad input currently responding
+    /// to incoming gamepad events.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    /// Enables or disables the handling of gamepad events.
+    pub fn set_enabled(&mut self, input_enabled: bool) {
+        // when we lose focus we trigger release all pressed buttons to prevent bugs
+        if !input_enabled {
+            self.reset();
+        }
+
+        self.input_enabled = input_enabled;
+    }
+
+    fn reset(&mut self) {
+        self.axes.clear();
+        self.button_values.clear();
+        self.current_button_state.clear();
+    }
+
+    /// Returns the value of specified axis (usually between `-1.0` and `1.0`)
+    /// with dead zone applied. The value is rescaled so it continuously starts
+    /// from zero at the edge of the dead zone.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        let value = self.axis_raw(axis);
+
+        if value.abs() < self.dead_zone {
+            return 0.0;
+        }
+
+        value.signum() * (value.abs() - self.dead_zone) / (1.0 - self.dead_zone)
+    }
+
+    /// Returns the value of specified axis without dead zone applied.
+    pub fn axis_raw(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// Returns how much is the specified button pressed (from `0.0` to `1.0`).
+    /// Only analog buttons (such as triggers) report values in between.
+    pub fn button_value(&self, button: GamepadButton) -> f32 {
+        self.button_values.get(&button).copied().unwrap_or(0.0)
+    }
+
+    /// Returns whether the user is currently (in this frame)
+    /// holding down the specified button.
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        self.current_button_state.contains(&button)
+    }
+
+    /// Returns whether the user is currently (in this frame)
+    /// not holding down the specified button.
+    pub fn is_button_up(&self, button: GamepadButton) -> bool {
+        !self.is_button_down(button)
+    }
+
+    /// Returns whether the user started pressing the specified
+    /// button in this frame.
+    pub fn was_button_pressed(&self, button: GamepadButton) -> bool {
+        !self.previous_button_state.contains(&button) && self.is_button_down(button)
+    }
+
+    /// Returns whether the user released the specified button
+    /// in this frame.
+    pub fn was_button_released(&self, button: GamepadButton) -> bool {
+        self.previous_button_state.contains(&button) && self.is_button_up(button)
+    }
+
+    /// Should be called once per frame to maintain internal state. This method
+    /// also polls pending gamepad events that will be visible in the next frame.
+    pub fn frame_finished(&mut self) {
+        self.previous_button_state = self.current_button_state.clone();
+
+        let mut events = vec![];
+        if let Some(gilrs) = self.gilrs.as_mut() {
+            while let Some(event) = gilrs.next_event() {
+                events.push(event.event);
+            }
+        }
+
+        for event in events {
+            self.handle_event(event);
+        }
+    }
+
+    /// Modifies the internal state according to specified `gilrs` event.
+    fn handle_event(&mut self, event: EventType) {
+        match event {
+            EventType::Connected => info!("Gamepad connected"),
+            EventType::Disconnected => {
+                info!("Gamepad disconnected");
+                self.reset();
+            }
+            _ if !self.input_enabled => {}
+            EventType::ButtonPressed(button, _) => {
+                self.current_button_state.insert(button);
+            }
+            EventType::ButtonReleased(button, _) => {
+                self.current_button_state.remove(&button);
+            }
+            EventType::ButtonChanged(button, value, _) => {
+                self.button_values.insert(button, value);
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                self.axes.insert(axis, value);
+            }
+            _ => {}
+        }
+    }
+}
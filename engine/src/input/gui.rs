@@ -0,0 +1,296 @@
+//! Translation of `winit` window events into `egui` input.
+
+use egui::{Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect, Vec2};
+use winit::dpi::PhysicalSize;
+use winit::event::{
+    ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// How many points is scrolled by one line of the mouse wheel.
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+
+/// Collects input for the GUI between two frames.
+pub struct GuiInput {
+    input_enabled: bool,
+    pixels_per_point: f32,
+    pointer: Option<Pos2>,
+    modifiers: Modifiers,
+    scroll_delta: Vec2,
+    events: Vec<Event>,
+}
+
+impl Default for GuiInput {
+    fn default() -> Self {
+        Self {
+            input_enabled: false,
+            pixels_per_point: 1.0,
+            pointer: None,
+            modifiers: Modifiers::default(),
+            scroll_delta: Vec2::ZERO,
+            events: vec![],
+        }
+    }
+}
+
+impl GuiInput {
+    /// Enables or disables collecting of the input. The GUI is hidden by
+    /// default, so the input is disabled until it is shown.
+    pub fn set_enabled(&mut self, input_enabled: bool) {
+        self.input_enabled = input_enabled;
+    }
+
+    /// Handles window events related to mouse and keyboard. Other events are
+    /// silently ignored.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        // positions received before the next frame already use the new scale
+        if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = event {
+            self.pixels_per_point = *scale_factor as f32;
+        }
+
+        if !self.input_enabled {
+            return;
+        }
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = Pos2::new(
+                    position.x as f32 / self.pixels_per_point,
+                    position.y as f32 / self.pixels_per_point,
+                );
+                self.pointer = Some(pos);
+                self.events.push(Event::PointerMoved(pos));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer = None;
+                self.events.push(Event::PointerGone);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let (Some(pos), Some(button)) = (self.pointer, pointer_button(*button)) {
+                    self.events.push(Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y) * POINTS_PER_SCROLL_LINE,
+                    MouseScrollDelta::PixelDelta(p) => {
+                        Vec2::new(p.x as f32, p.y as f32) / self.pixels_per_point
+                    }
+                };
+            }
+            WindowEvent::ModifiersChanged(state) => self.modifiers = modifiers(*state),
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => {
+                self.events.push(Event::Text(c.to_string()));
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let pressed = input.state == ElementState::Pressed;
+
+                match input.virtual_keycode {
+                    Some(VirtualKeyCode::C) if pressed && self.modifiers.command => {
+                        self.events.push(Event::Copy)
+                    }
+                    Some(VirtualKeyCode::X) if pressed && self.modifiers.command => {
+                        self.events.push(Event::Cut)
+                    }
+                    _ => {}
+                }
+
+                if let Some(key) = input.virtual_keycode.and_then(key) {
+                    self.events.push(Event::Key {
+                        key,
+                        pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the input collected since the last call. The `screen_size` is
+    /// the size of the window in physical pixels and `time` is the time since
+    /// start in seconds.
+    pub fn take_raw_input(
+        &mut self,
+        screen_size: PhysicalSize<u32>,
+        pixels_per_point: f32,
+        time: f64,
+    ) -> RawInput {
+        self.pixels_per_point = pixels_per_point;
+
+        RawInput {
+            scroll_delta: std::mem::replace(&mut self.scroll_delta, Vec2::ZERO),
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                Vec2::new(screen_size.width as f32, screen_size.height as f32) / pixels_per_point,
+            )),
+            pixels_per_point: Some(pixels_per_point),
+            time: Some(time),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..RawInput::default()
+        }
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: cfg!(target_os = "macos") && state.logo(),
+        command: if cfg!(target_os = "macos") {
+            state.logo()
+        } else {
+            state.ctrl()
+        },
+    }
+}
+
+fn key(key: VirtualKeyCode) -> Option<Key> {
+    use VirtualKeyCode::*;
+
+    Some(match key {
+        Down => Key::ArrowDown,
+        Left => Key::ArrowLeft,
+        Right => Key::ArrowRight,
+        Up => Key::ArrowUp,
+        Escape => Key::Escape,
+        Tab => Key::Tab,
+        Back => Key::Backspace,
+        Return => Key::Enter,
+        Space => Key::Space,
+        Insert => Key::Insert,
+        Delete => Key::Delete,
+        Home => Key::Home,
+        End => Key::End,
+        PageUp => Key::PageUp,
+        PageDown => Key::PageDown,
+        Key0 | Numpad0 => Key::Num0,
+        Key1 | Numpad1 => Key::Num1,
+        Key2 | Numpad2 => Key::Num2,
+        Key3 | Numpad3 => Key::Num3,
+        Key4 | Numpad4 => Key::Num4,
+        Key5 | Numpad5 => Key::Num5,
+        Key6 | Numpad6 => Key::Num6,
+        Key7 | Numpad7 => Key::Num7,
+        Key8 | Numpad8 => Key::Num8,
+        Key9 | Numpad9 => Key::Num9,
+        A => Key::A,
+        B => Key::B,
+        C => Key::C,
+        D => Key::D,
+        E => Key::E,
+        F => Key::F,
+        G => Key::G,
+        H => Key::H,
+        I => Key::I,
+        J => Key::J,
+        K => Key::K,
+        L => Key::L,
+        M => Key::M,
+        N => Key::N,
+        O => Key::O,
+        P => Key::P,
+        Q => Key::Q,
+        R => Key::R,
+        S => Key::S,
+        T => Key::T,
+        U => Key::U,
+        V => Key::V,
+        W => Key::W,
+        X => Key::X,
+        Y => Key::Y,
+        Z => Key::Z,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::gui::GuiInput;
+    use egui::{Event, PointerButton, Pos2};
+    use winit::dpi::{PhysicalPosition, PhysicalSize};
+    use winit::event::{DeviceId, ElementState, ModifiersState, MouseButton, WindowEvent};
+
+    #[allow(deprecated)]
+    fn cursor_moved(x: f64, y: f64) -> WindowEvent<'static> {
+        WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(x, y),
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn converts_pixels_to_points() {
+        let mut input = GuiInput::default();
+        let size = PhysicalSize::new(800, 600);
+        input.take_raw_input(size, 2.0, 0.0);
+
+        input.handle_event(&cursor_moved(10.0, 10.0));
+        assert!(input.take_raw_input(size, 2.0, 0.5).events.is_empty());
+        input.set_enabled(true);
+
+        input.handle_event(&cursor_moved(100.0, 50.0));
+        input.handle_event(&WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        });
+        input.handle_event(&WindowEvent::ReceivedCharacter('a'));
+        input.handle_event(&WindowEvent::ReceivedCharacter('\u{8}'));
+
+        let raw = input.take_raw_input(size, 2.0, 1.0);
+        assert_eq!(raw.screen_rect.unwrap().max, Pos2::new(400.0, 300.0));
+        assert_eq!(raw.events.len(), 3);
+        assert_eq!(raw.events[0], Event::PointerMoved(Pos2::new(50.0, 25.0)));
+        assert!(matches!(
+            raw.events[1],
+            Event::PointerButton {
+                button: PointerButton::Primary,
+                pressed: true,
+                ..
+            }
+        ));
+        assert_eq!(raw.events[2], Event::Text("a".to_string()));
+        assert!(input.take_raw_input(size, 2.0, 2.0).events.is_empty());
+    }
+
+    #[test]
+    fn uses_changed_scale_factor_immediately() {
+        let mut input = GuiInput::default();
+        input.set_enabled(true);
+        input.take_raw_input(PhysicalSize::new(1920, 1080), 1.0, 0.0);
+
+        // the window moved to a display with 150% scaling
+        let mut new_size = PhysicalSize::new(2880, 1620);
+        input.handle_event(&WindowEvent::ScaleFactorChanged {
+            scale_factor: 1.5,
+            new_inner_size: &mut new_size,
+        });
+        input.handle_event(&cursor_moved(300.0, 150.0));
+
+        let raw = input.take_raw_input(new_size, 1.5, 1.0);
+        assert_eq!(raw.screen_rect.unwrap().max, Pos2::new(1920.0, 1080.0));
+        assert_eq!(
+            raw.events,
+            vec![Event::PointerMoved(Pos2::new(200.0, 100.0))]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Keyboard, mouse, gamepad & virtual input (keybindings).
+
+use crate::input::actions::ActionMap;
+use crate::input::gamepad::Gamepad;
+use crate::input::gui::GuiInput;
+use crate::input::keyboard::Keyboard;
+use crate::input::mouse::Mouse;
+use crate::input::universal::Universal;
+use std::sync::Arc;
+use vulkano::swapchain::Surface;
+use winit::event::{DeviceEvent, WindowEvent};
+use winit::window::Window;
+
+pub mod actions;
+pub mod gamepad;
+pub mod gui;
+mod keyboard;
+mod mouse;
+mod universal;
+
+/// Provides access to keyboard, mouse & gamepad input.
+pub struct Input {
+    pub keyboard: Keyboard,
+    pub mouse: Mouse,
+    pub gamepad: Gamepad,
+    pub universal: Universal,
+    pub actions: ActionMap,
+    /// Input collected for the GUI (see `render::gui`).
+    pub gui: GuiInput,
+}
+
+impl Input {
+    pub fn new(window: Arc<Surface<Window>>, actions: ActionMap) -> Self {
+        Self {
+            keyboard: Keyboard::default(),
+            mouse: Mouse::new(window),
+            gamepad: Gamepad::default(),
+            universal: Universal::default(), // todo: load bindings from configuration
+            actions,
+            gui: GuiInput::default(),
+        }
+    }
+
+    /// Returns whether any input bound to specified action is currently held down.
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.actions
+            .is_action_down(action, &self.keyboard, &self.gamepad)
+    }
+
+    /// Returns whether any input bound to specified action was pressed in this frame.
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.actions
+            .is_action_pressed(action, &self.keyboard, &self.gamepad)
+    }
+
+    /// Returns the value of specified action as an axis (from `-1.0` to `1.0`).
+    pub fn action_axis(&self, action: &str) -> f32 {
+        self.actions
+            .action_axis(action, &self.keyboard, &self.gamepad)
+    }
+
+    /// Enables or disables the handling of input events on all
+    /// input types (mouse, keyboard, gamepad, universal).
+    pub fn set_enabled(&mut self, input_enabled: bool) {
+        self.keyboard.set_enabled(input_enabled);
+        self.mouse.set_enabled(input_enabled);
+        self.gamepad.set_enabled(input_enabled);
+        self.universal.set_enabled(input_enabled);
+    }
+
+    /// Should be called once per frame to maintain internal state.
+    pub fn frame_finished(&mut self) {
+        self.universal.frame_finished();
+        self.keyboard.frame_finished();
+        self.mouse.frame_finished();
+        self.gamepad.frame_finished();
+    }
+
+    /// Handles mouse & keyboard related `winit` events. Other events are silently ignored.
+    ///
+    /// Gamepad events are not delivered by `winit`, they are polled in `frame_finished`.
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::Key(k) = event {
+            self.keyboard.handle_event(*k);
+        }
+
+        if let DeviceEvent::MouseMotion { .. } | DeviceEvent::MouseWheel { .. } = event {
+            self.mouse.handle_device_event(event);
+        }
+
+        self.universal.handle_event(event);
+    }
+
+    /// Handles `winit` window events. They carry the cursor position and text
+    /// input for the GUI, other input is read from device events.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        self.mouse.handle_mouse_event(event);
+        self.gui.handle_event(event);
+    }
+}
@@ -1,8 +1,10 @@
 //! Functionality related to handling mouse input.
 
+use crate::window::WindowSize;
 use log::error;
 use std::sync::Arc;
 use vulkano::swapchain::Surface;
+use winit::dpi::PhysicalPosition;
 use winit::event::{DeviceEvent, MouseScrollDelta, WindowEvent};
 use winit::window::Window;
 
@@ -15,8 +17,6 @@ pub struct Mouse {
     window: Arc<Surface<Window>>,
 }
 
-// todo: decide when to use logical and physical position & deltas
-
 impl Mouse {
     /// Creates a new Mouse input tracker tied to specified `Window` instance.
     pub fn new(window: Arc<Surface<Window>>) -> Self {
@@ -60,7 +60,16 @@ impl Mouse {
         self.position
     }
 
-    /// Returns the cursor delta (x, y) between last frame and this frame.
+    /// Returns the current cursor position in logical pixels (the coordinates of
+    /// the overlay and the GUI, see `window` module).
+    pub fn logical_position(&self) -> (f64, f64) {
+        let size = WindowSize::of(self.window.window());
+        let position = size.to_logical(PhysicalPosition::new(self.position.0, self.position.1));
+        (position.x, position.y)
+    }
+
+    /// Returns the cursor delta (x, y) between last frame and this frame. The
+    /// delta is raw motion of the mouse, not scaled by the DPI of the display.
     pub fn delta(&self) -> (f64, f64) {
         self.move_delta
     }
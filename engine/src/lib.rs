@@ -0,0 +1,193 @@
+//! Vulkan-based rendering engine. Contains the renderer itself, loading of assets,
+//! input handling and the main loop. Game specific logic is provided by implementing
+//! the [`Game`](engine/trait.Game.html) trait.
+
+use crate::camera::{ActiveCamera, Camera, OrthographicCamera, PerspectiveCamera};
+use crate::camera_rig::CameraRig;
+use crate::render::decals::Decal;
+use crate::render::feedback::screen_coverage;
+use crate::render::hierarchy::Hierarchy;
+use crate::render::object::Object;
+use crate::render::objects::{ObjectId, Objects};
+use crate::render::ubo::{AmbientLight, DirectionalLight, SpotLight, Wind};
+use crate::render::vertex::NormalMappedVertex;
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use std::time::Instant;
+
+pub mod assets;
+pub mod camera;
+pub mod camera_rig;
+pub mod config;
+pub mod engine;
+pub mod frame_stats;
+pub mod input;
+pub mod movement;
+pub mod picking;
+pub mod remote;
+pub mod render;
+pub mod resources;
+pub mod self_test;
+pub mod sequencer;
+pub mod timestep;
+pub mod turntable;
+pub mod viewport;
+pub mod window;
+
+pub use crate::config::RendererConfiguration;
+pub use crate::engine::{Engine, Game};
+pub use egui;
+
+/// State of the rendered world.
+pub struct GameState {
+    pub start: Instant,
+    /// First person camera controlled by the user and sequences.
+    pub camera: PerspectiveCamera,
+    /// Rig that animates the field of view of the `camera` and shakes it. The
+    /// `camera` is rendered as it is when there is no rig.
+    pub camera_rig: Option<CameraRig>,
+    /// Camera with parallel projection (eg. top view).
+    pub orthographic_camera: OrthographicCamera,
+    /// Camera the world is rendered with.
+    pub active_camera: ActiveCamera,
+    /// Objects of the world. Keep their `ObjectId` handles to refer to them
+    /// across frames.
+    pub objects: Objects<Object<NormalMappedVertex>>,
+    pub directional_lights: Vec<DirectionalLight>,
+    /// Spot lights positioned in the same space as the camera.
+    pub spot_lights: Vec<SpotLight>,
+    /// Decals projected onto opaque objects.
+    pub decals: Vec<Decal>,
+    /// Ambient light of the sky attenuated by sky occlusion of meshes.
+    pub ambient_light: AmbientLight,
+    /// Wind that sways foliage.
+    pub wind: Wind,
+    /// Parent / child relationships of transforms. Objects are attached
+    /// to its nodes by `Object::parent`.
+    pub hierarchy: Hierarchy,
+    /// World-space position of the origin of the local space the camera and
+    /// objects are positioned in (see [`shift_origin`](#method.shift_origin)).
+    pub origin: Vector3<f64>,
+}
+
+impl GameState {
+    /// Moves the origin of the local space by `offset` (floating origin). The
+    /// camera and all objects are moved by `-offset`, so their world-space
+    /// positions do not change but stay close to the origin, where `f32`
+    /// coordinates are precise.
+    pub fn shift_origin(&mut self, offset: Vector3<f32>) {
+        self.camera.position -= offset;
+        self.orthographic_camera.position -= offset;
+        if let Some(rig) = self.camera_rig.as_mut() {
+            rig.shift_origin(offset);
+        }
+        for object in self.objects.iter_mut().filter(|x| x.parent.is_none()) {
+            object.transform.shift(-offset);
+        }
+        for light in self.spot_lights.iter_mut() {
+            light.position -= offset;
+        }
+        for decal in self.decals.iter_mut() {
+            decal.transform.shift(-offset);
+        }
+        self.hierarchy.shift_roots(offset);
+        self.origin += offset.cast().unwrap();
+    }
+
+    /// Returns the camera the world is rendered with.
+    pub fn render_camera(&self) -> &dyn Camera<f32> {
+        match self.active_camera {
+            ActiveCamera::Perspective => match &self.camera_rig {
+                Some(rig) => rig.view(),
+                None => &self.camera,
+            },
+            ActiveCamera::Orthographic => &self.orthographic_camera,
+        }
+    }
+
+    /// Recomputes cached matrices of changed transforms of objects, decals and world
+    /// matrices of changed nodes of the `hierarchy` and passes them to objects
+    /// attached to the nodes. Called before each frame is rendered.
+    pub fn update_transforms(&mut self) {
+        self.update_transforms_interpolated(1.0);
+    }
+
+    /// Same as [`update_transforms`](#method.update_transforms), but interpolated
+    /// transforms are rendered at the fraction `alpha` between the last two fixed
+    /// updates (see `timestep` module). Nodes of the `hierarchy` are not interpolated.
+    pub fn update_transforms_interpolated(&mut self, alpha: f32) {
+        self.hierarchy.update();
+
+        for object in self.objects.iter_mut() {
+            object.transform.update_interpolated(alpha);
+            if let Some(parent) = object.parent {
+                object.set_parent_world(self.hierarchy.world(parent));
+            }
+        }
+        for decal in self.decals.iter_mut() {
+            decal.transform.update_interpolated(alpha);
+        }
+    }
+
+    /// Stores current components of interpolated transforms as their previous
+    /// ones. Called before each fixed update.
+    pub fn save_previous_transforms(&mut self) {
+        for object in self.objects.iter_mut() {
+            object.transform.save_previous();
+        }
+        for decal in self.decals.iter_mut() {
+            decal.transform.save_previous();
+        }
+    }
+
+    /// Selects levels of detail of objects for the render camera and advances their
+    /// cross-fades (see [`LodFade`](render/lod_fade/struct.LodFade.html)) by one
+    /// frame. Objects that finished fading out are removed. Called before each
+    /// frame is rendered, after `update_transforms`.
+    pub fn update_lods(&mut self) {
+        let camera = self.render_camera();
+        let lods = self
+            .objects
+            .iter()
+            .map(|x| {
+                let (center, radius) = x.bounding_sphere();
+                x.mesh
+                    .get()
+                    .select_lod(screen_coverage(camera, center, radius))
+            })
+            .collect::<Vec<_>>();
+
+        for (object, lod) in self.objects.iter_mut().zip(lods) {
+            object.lod_fade.update(lod);
+        }
+
+        let faded_out = self
+            .objects
+            .iter_with_ids()
+            .filter(|(_, x)| x.lod_fade.is_faded_out())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in faded_out {
+            self.objects.remove(id);
+        }
+    }
+
+    /// Adds the `object` to the world. It is faded in over a few frames.
+    pub fn spawn(&mut self, mut object: Object<NormalMappedVertex>) -> ObjectId {
+        object.lod_fade.fade_in();
+        self.objects.insert(object)
+    }
+
+    /// Starts fading out the object, which is removed once it is invisible
+    /// (see [`update_lods`](#method.update_lods)). Use `objects.remove` to
+    /// remove the object immediately.
+    pub fn despawn(&mut self, id: ObjectId) {
+        if let Some(object) = self.objects.get_mut(id) {
+            object.lod_fade.fade_out();
+        }
+    }
+
+    /// Converts the `local` position to world-space position.
+    pub fn world_position(&self, local: Point3<f32>) -> Point3<f64> {
+        Point3::from_vec(self.origin + local.to_vec().cast().unwrap())
+    }
+}
@@ -0,0 +1,258 @@
+//! Various input handling movement controllers.
+//!
+//! Held inputs (keys, sticks and triggers) move the camera by a speed per
+//! second scaled by the time of the frame, so the camera moves the same
+//! distance at any frame rate. Mouse movement and the wheel are already
+//! distances, so they are applied as they are.
+
+use crate::camera::PerspectiveCamera;
+use crate::input::gamepad::{GamepadAxis, GamepadButton};
+use crate::input::Input;
+use cgmath::{InnerSpace, Point3, Rad, Vector3};
+
+/// Speed of the free camera in units per second.
+const MOVE_SPEED: f32 = 0.3;
+/// Speed of the free camera while sprinting in units per second.
+const SPRINT_SPEED: f32 = 1.2;
+/// Radians per pixel of the mouse movement of the free camera.
+const MOUSE_LOOK: f32 = 0.001;
+/// Radians per second of the right stick of the free camera.
+const STICK_LOOK_SPEED: f32 = 1.2;
+
+/// Radians per pixel of the mouse movement of the orbit camera.
+const ORBIT_MOUSE: f32 = 0.005;
+/// Radians per second of the right stick of the orbit camera.
+const ORBIT_STICK_SPEED: f32 = 1.8;
+/// Fraction of the distance to the target removed by one step of the wheel.
+const ORBIT_WHEEL_ZOOM: f32 = 0.1;
+/// Rate of the exponential change of the distance while the forward axis is held.
+const ORBIT_ZOOM_SPEED: f32 = 1.2;
+
+/// Input of the free camera in one frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FpsInput {
+    /// Movement along the right, up and forward axes of the camera (-1 to 1).
+    pub direction: [f32; 3],
+    pub sprint: bool,
+    /// Movement of the mouse in this frame (pixels).
+    pub mouse: [f32; 2],
+    /// Position of the right stick (-1 to 1, up is positive).
+    pub stick: [f32; 2],
+}
+
+impl FpsInput {
+    pub fn read(input: &Input) -> Self {
+        let pad = &input.gamepad;
+
+        // gamepad: left stick moves, triggers move up & down
+        Self {
+            direction: [
+                input.universal.axis("MoveRight") + pad.axis(GamepadAxis::LeftStickX),
+                input.universal.axis("MoveUp") + pad.button_value(GamepadButton::RightTrigger2)
+                    - pad.button_value(GamepadButton::LeftTrigger2),
+                input.universal.axis("MoveForward") + pad.axis(GamepadAxis::LeftStickY),
+            ],
+            sprint: input.universal.is_button_down("Sprint")
+                || pad.is_button_down(GamepadButton::LeftThumb),
+            mouse: [
+                input.universal.axis_raw("Mouse X"),
+                input.universal.axis_raw("Mouse Y"),
+            ],
+            stick: [
+                pad.axis(GamepadAxis::RightStickX),
+                pad.axis(GamepadAxis::RightStickY),
+            ],
+        }
+    }
+}
+
+/// Provides simple FPS-like free movement controller for camera.
+pub struct FpsMovement;
+
+impl FpsMovement {
+    /// Moves the `camera` by the `input` during the frame of `dt` seconds.
+    pub fn update(camera: &mut PerspectiveCamera, input: &Input, dt: f32) {
+        Self::apply(camera, &FpsInput::read(input), dt)
+    }
+
+    pub fn apply(camera: &mut PerspectiveCamera, input: &FpsInput, dt: f32) {
+        let speed = if input.sprint {
+            SPRINT_SPEED
+        } else {
+            MOVE_SPEED
+        } * dt;
+
+        camera.move_right(speed * input.direction[0]);
+        camera.move_up(speed * input.direction[1]);
+        camera.move_forward(speed * input.direction[2]);
+
+        // gamepad: right stick looks around (stick up means look up)
+        camera.rotate(
+            Rad(input.mouse[0] * MOUSE_LOOK + input.stick[0] * STICK_LOOK_SPEED * dt),
+            Rad(input.mouse[1] * MOUSE_LOOK - input.stick[1] * STICK_LOOK_SPEED * dt),
+        );
+    }
+}
+
+/// Input of the orbit camera in one frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OrbitInput {
+    /// Movement of the mouse in this frame (pixels).
+    pub mouse: [f32; 2],
+    /// Position of the right stick (-1 to 1, up is positive).
+    pub stick: [f32; 2],
+    /// Steps of the mouse wheel in this frame.
+    pub wheel: f32,
+    /// Movement towards the target (-1 to 1).
+    pub zoom: f32,
+}
+
+impl OrbitInput {
+    pub fn read(input: &Input) -> Self {
+        let pad = &input.gamepad;
+
+        Self {
+            mouse: [
+                input.universal.axis_raw("Mouse X"),
+                input.universal.axis_raw("Mouse Y"),
+            ],
+            stick: [
+                pad.axis(GamepadAxis::RightStickX),
+                pad.axis(GamepadAxis::RightStickY),
+            ],
+            wheel: input.mouse.wheel_delta().1 as f32,
+            zoom: input.universal.axis("MoveForward") + pad.axis(GamepadAxis::LeftStickY),
+        }
+    }
+}
+
+/// Controller that orbits the camera around a target point. Mouse (or the right
+/// stick) rotates the camera around the target and the wheel (or the forward
+/// axis) changes the distance to it.
+#[derive(Copy, Clone, Debug)]
+pub struct OrbitMovement {
+    /// Point the camera looks at.
+    pub target: Point3<f32>,
+    /// Distance of the camera from the `target`.
+    pub distance: f32,
+    /// Rotation around the vertical axis.
+    pub yaw: Rad<f32>,
+    /// Elevation of the camera above the horizontal plane of the `target`.
+    pub pitch: Rad<f32>,
+}
+
+impl OrbitMovement {
+    /// Creates a controller looking at `target` from `distance` slightly from above.
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: Rad(std::f32::consts::FRAC_PI_4),
+            pitch: Rad(0.4),
+        }
+    }
+
+    /// Moves the `camera` by the `input` during the frame of `dt` seconds.
+    pub fn update(&mut self, camera: &mut PerspectiveCamera, input: &Input, dt: f32) {
+        self.apply(camera, &OrbitInput::read(input), dt)
+    }
+
+    pub fn apply(&mut self, camera: &mut PerspectiveCamera, input: &OrbitInput, dt: f32) {
+        self.yaw += Rad(input.mouse[0] * ORBIT_MOUSE + input.stick[0] * ORBIT_STICK_SPEED * dt);
+        self.pitch += Rad(input.mouse[1] * ORBIT_MOUSE - input.stick[1] * ORBIT_STICK_SPEED * dt);
+        self.pitch = Rad(self.pitch.0.clamp(-1.5, 1.5));
+
+        // each step of the wheel moves the camera by a tenth of the distance,
+        // held zoom changes the distance exponentially (independent of the frame rate)
+        let wheel = (input.wheel * ORBIT_WHEEL_ZOOM).clamp(-0.5, 0.5);
+        let zoom = (-input.zoom * ORBIT_ZOOM_SPEED * dt).exp();
+        self.distance = (self.distance * (1.0 - wheel) * zoom).max(camera.near * 2.0);
+
+        let (yaw, pitch) = (self.yaw.0, self.pitch.0);
+        let direction = Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        camera.position = self.target + direction * self.distance;
+        camera.forward = -direction.normalize();
+    }
+}
+
+/// Controller of the camera driven by the user.
+#[derive(Copy, Clone, Debug)]
+pub enum Movement {
+    Fps,
+    Orbit(OrbitMovement),
+}
+
+impl Movement {
+    /// Moves the `camera` by the `input` during the frame of `dt` seconds.
+    pub fn update(&mut self, camera: &mut PerspectiveCamera, input: &Input, dt: f32) {
+        match self {
+            Movement::Fps => FpsMovement::update(camera, input, dt),
+            Movement::Orbit(orbit) => orbit.update(camera, input, dt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::PerspectiveCamera;
+    use crate::movement::{FpsInput, FpsMovement, OrbitInput, OrbitMovement};
+    use cgmath::{vec3, Deg, MetricSpace, Point3};
+
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            forward: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            fov: Deg(90.0).into(),
+            aspect_ratio: 1.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    #[test]
+    fn fps_movement_does_not_depend_on_frame_rate() {
+        let input = FpsInput {
+            direction: [1.0, 0.5, 1.0],
+            sprint: true,
+            ..FpsInput::default()
+        };
+        let (mut fast, mut slow) = (camera(), camera());
+
+        for _ in 0..120 {
+            FpsMovement::apply(&mut fast, &input, 1.0 / 120.0);
+        }
+        for _ in 0..60 {
+            FpsMovement::apply(&mut slow, &input, 1.0 / 60.0);
+        }
+
+        assert!(fast.position.distance(slow.position) < 1e-4);
+        assert!(fast.position.distance(Point3::new(0.0, 0.0, 0.0)) > 1.0);
+    }
+
+    #[test]
+    fn orbit_movement_does_not_depend_on_frame_rate() {
+        let input = OrbitInput {
+            stick: [0.5, -0.25],
+            zoom: 1.0,
+            ..OrbitInput::default()
+        };
+        let (mut fast, mut slow) = (camera(), camera());
+        let mut fast_orbit = OrbitMovement::new(Point3::new(0.0, 0.0, 0.0), 10.0);
+        let mut slow_orbit = fast_orbit;
+
+        for _ in 0..120 {
+            fast_orbit.apply(&mut fast, &input, 1.0 / 120.0);
+        }
+        for _ in 0..60 {
+            slow_orbit.apply(&mut slow, &input, 1.0 / 60.0);
+        }
+
+        assert!(fast.position.distance(slow.position) < 1e-3);
+        assert!((fast_orbit.distance - 10.0 * (-1.2f32).exp()).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,428 @@
+//! Finding objects under the cursor (eg. to select them in an editor).
+//!
+//! The cursor position is converted to a [`Ray`](struct.Ray.html) through the
+//! inverse of the view-projection matrix of the camera. The ray is in the local
+//! space of the camera and objects (see `GameState::shift_origin`). Each visible
+//! object is tested in its own space: meshes created from assets retain their
+//! positions and indices on the CPU (see [`PickMesh`](struct.PickMesh.html)) and
+//! are tested triangle by triangle after a test of their bounding box. Other
+//! meshes (eg. generated ones) are tested only by the box that contains their
+//! bounding sphere. The closest hit is returned.
+
+use crate::camera::Camera;
+use crate::render::objects::ObjectId;
+use crate::GameState;
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3, Vector4,
+};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Half-line starting at the `origin`. Distances along the ray are measured in
+/// lengths of the `direction`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self { origin, direction }
+    }
+
+    /// Creates a ray from the `camera` through the `cursor` position in a window
+    /// of specified `size`. The ray starts at the near plane and its direction
+    /// is normalized. Returns `None` when the window is minimized.
+    pub fn from_cursor(
+        camera: &dyn Camera<f32>,
+        cursor: PhysicalPosition<f64>,
+        size: PhysicalSize<u32>,
+    ) -> Option<Self> {
+        if size.width == 0 || size.height == 0 {
+            return None;
+        }
+
+        // vulkan viewport maps -1 of normalized device coordinates to the top
+        // left corner, cgmath projections map the near plane to depth -1
+        let x = (2.0 * cursor.x / size.width as f64 - 1.0) as f32;
+        let y = (2.0 * cursor.y / size.height as f64 - 1.0) as f32;
+        let inverse = (camera.projection_matrix() * camera.view_matrix()).invert()?;
+        let unproject = |depth: f32| {
+            let point = inverse * Vector4::new(x, y, depth, 1.0);
+            Point3::from_homogeneous(point)
+        };
+
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        Some(Self::new(near, (far - near).normalize()))
+    }
+
+    /// Returns the point at `distance` along the ray.
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Returns the ray transformed by the `matrix`. The direction is not
+    /// normalized, so distances along the transformed ray stay the same.
+    pub fn transform(&self, matrix: Matrix4<f32>) -> Self {
+        Self::new(
+            matrix.transform_point(self.origin),
+            matrix.transform_vector(self.direction),
+        )
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// Returns the box centered at the origin that contains a sphere of
+    /// specified `radius`.
+    pub fn from_radius(radius: f32) -> Self {
+        Self {
+            min: Point3::new(-radius, -radius, -radius),
+            max: Point3::new(radius, radius, radius),
+        }
+    }
+
+    /// Returns the smallest box containing all `points` or `None` if there are
+    /// no points.
+    pub fn from_points(points: &[Point3<f32>]) -> Option<Self> {
+        let first = *points.first()?;
+        Some(points.iter().fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: Point3::new(
+                    aabb.min.x.min(p.x),
+                    aabb.min.y.min(p.y),
+                    aabb.min.z.min(p.z),
+                ),
+                max: Point3::new(
+                    aabb.max.x.max(p.x),
+                    aabb.max.y.max(p.y),
+                    aabb.max.z.max(p.z),
+                ),
+            },
+        ))
+    }
+
+    /// Returns the distance along the `ray` where it enters the box (zero when
+    /// the ray starts inside) or `None` if it misses the box.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let origin = ray.origin[axis];
+            let direction = ray.direction[axis];
+
+            if direction.abs() < f32::EPSILON {
+                // parallel with the slab, misses it unless it starts inside
+                if origin < self.min[axis] || origin > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (self.min[axis] - origin) / direction;
+            let t2 = (self.max[axis] - origin) / direction;
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+}
+
+/// Returns the distance along the `ray` where it hits the `triangle` or `None`
+/// if it misses it. Both sides of the triangle are hit (Möller-Trumbore).
+pub fn intersect_triangle(ray: &Ray, triangle: [Point3<f32>; 3]) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let inv_determinant = 1.0 / determinant;
+    let s = ray.origin - triangle[0];
+    let u = s.dot(p) * inv_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inv_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inv_determinant;
+    if distance >= 0.0 {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Positions and indices of triangles of a mesh retained on the CPU for picking.
+/// Only the most detailed level of detail is retained.
+pub struct PickMesh {
+    positions: Vec<Point3<f32>>,
+    indices: Vec<u32>,
+    bounds: Aabb,
+    /// Distance of the farthest position from the origin.
+    radius: f32,
+}
+
+impl PickMesh {
+    /// Creates a mesh from `positions` and triangle list `indices`.
+    pub fn new(positions: Vec<Point3<f32>>, indices: Vec<u32>) -> Self {
+        let bounds = Aabb::from_points(&positions).unwrap_or_else(|| Aabb::from_radius(0.0));
+        let radius = positions
+            .iter()
+            .map(|p| p.to_vec().magnitude())
+            .fold(0.0, f32::max);
+        Self {
+            positions,
+            indices,
+            bounds,
+            radius,
+        }
+    }
+
+    /// Creates a mesh from decoded vertex and index data of a mesh asset. The
+    /// position is the first `vec3` of each vertex of `vertex_size` bytes and
+    /// indices are `u16` or `u32` depending on `index_size`.
+    pub fn from_bytes(
+        vertex_data: &[u8],
+        vertex_size: usize,
+        index_data: &[u8],
+        index_size: usize,
+    ) -> Self {
+        let float = |bytes: &[u8], i: usize| {
+            f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        let positions = vertex_data
+            .chunks_exact(vertex_size)
+            .map(|v| Point3::new(float(v, 0), float(v, 4), float(v, 8)))
+            .collect();
+        let indices = index_data
+            .chunks_exact(index_size)
+            .map(|i| match index_size {
+                2 => u16::from_le_bytes([i[0], i[1]]) as u32,
+                _ => u32::from_le_bytes([i[0], i[1], i[2], i[3]]),
+            })
+            .collect();
+
+        Self::new(positions, indices)
+    }
+
+    /// Returns the bounding box of all positions.
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    /// Returns the radius of the sphere centered at the origin that contains
+    /// all positions.
+    pub fn bounding_radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Returns the number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Returns the distance along the `ray` to the closest triangle or `None`
+    /// if it misses all of them. Triangles with invalid indices are skipped.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        self.bounds.intersect(ray)?;
+
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                let a = *self.positions.get(t[0] as usize)?;
+                let b = *self.positions.get(t[1] as usize)?;
+                let c = *self.positions.get(t[2] as usize)?;
+                intersect_triangle(ray, [a, b, c])
+            })
+            .fold(None, |closest: Option<f32>, t| {
+                Some(closest.map_or(t, |c| c.min(t)))
+            })
+    }
+}
+
+/// Object hit by a ray.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub object: ObjectId,
+    /// Distance along the ray.
+    pub distance: f32,
+    /// Position of the hit in the local space.
+    pub position: Point3<f32>,
+}
+
+/// Returns the closest visible object of the `state` hit by the `ray`.
+pub fn pick(state: &GameState, ray: &Ray) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+
+    for (id, object) in state.objects.iter_with_ids() {
+        if !object.visible {
+            continue;
+        }
+
+        // objects with zero scale can't be hit
+        let to_object = match object.model_matrix(Point3::origin()).invert() {
+            Some(t) => t,
+            None => continue,
+        };
+        let local = ray.transform(to_object);
+        let distance = match object.mesh.get().pick_mesh() {
+            Some(mesh) => mesh.intersect(&local),
+            None => Aabb::from_radius(object.bounding_radius).intersect(&local),
+        };
+
+        if let Some(distance) = distance {
+            if closest.map_or(true, |c| distance < c.distance) {
+                closest = Some(Hit {
+                    object: id,
+                    distance,
+                    position: ray.at(distance),
+                });
+            }
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::PerspectiveCamera;
+    use crate::picking::{intersect_triangle, Aabb, PickMesh, Ray};
+    use cgmath::{vec3, Deg, InnerSpace, Matrix4, Point3, SquareMatrix};
+    use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera {
+            position: Point3::new(0.0, 0.0, 5.0),
+            forward: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            fov: Deg(90.0).into(),
+            aspect_ratio: 2.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    #[test]
+    fn cursor_ray_goes_through_the_center_of_the_screen() {
+        let size = PhysicalSize::new(200, 100);
+        let ray = Ray::from_cursor(&camera(), PhysicalPosition::new(100.0, 50.0), size).unwrap();
+
+        assert!((ray.origin - Point3::new(0.0, 0.0, 4.9)).magnitude() < 1e-4);
+        assert!((ray.direction - vec3(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+
+        // right edge of the screen is at 45 degrees times the aspect ratio
+        let right = Ray::from_cursor(&camera(), PhysicalPosition::new(200.0, 50.0), size).unwrap();
+        assert!((right.direction - vec3(2.0, 0.0, -1.0).normalize()).magnitude() < 1e-4);
+
+        assert!(Ray::from_cursor(
+            &camera(),
+            PhysicalPosition::new(0.0, 0.0),
+            PhysicalSize::new(0, 0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn ray_box_intersection() {
+        let aabb = Aabb::from_radius(1.0);
+
+        let hit = Ray::new(Point3::new(0.0, 0.0, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(aabb.intersect(&hit), Some(4.0));
+
+        let inside = Ray::new(Point3::new(0.5, 0.0, 0.0), vec3(1.0, 0.0, 0.0));
+        assert_eq!(aabb.intersect(&inside), Some(0.0));
+
+        let miss = Ray::new(Point3::new(0.0, 2.0, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(aabb.intersect(&miss), None);
+
+        let behind = Ray::new(Point3::new(0.0, 0.0, 5.0), vec3(0.0, 0.0, 1.0));
+        assert_eq!(aabb.intersect(&behind), None);
+    }
+
+    #[test]
+    fn ray_triangle_intersection() {
+        let triangle = [
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+
+        let front = Ray::new(Point3::new(0.0, 0.0, 2.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(intersect_triangle(&front, triangle), Some(2.0));
+
+        let back = Ray::new(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 1.0));
+        assert_eq!(intersect_triangle(&back, triangle), Some(3.0));
+
+        let outside = Ray::new(Point3::new(0.9, 0.9, 2.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(intersect_triangle(&outside, triangle), None);
+
+        let parallel = Ray::new(Point3::new(0.0, 0.0, 2.0), vec3(1.0, 0.0, 0.0));
+        assert_eq!(intersect_triangle(&parallel, triangle), None);
+    }
+
+    #[test]
+    fn pick_mesh_returns_closest_triangle() {
+        // two quads at z = 0 and z = -1 sharing the bounds on x and y
+        let mut vertex_data = Vec::new();
+        for z in [0.0f32, -1.0].iter() {
+            for (x, y) in [(-1.0f32, -1.0f32), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+                for v in [*x, *y, *z, 0.0].iter() {
+                    vertex_data.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        let index_data = [0u16, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]
+            .iter()
+            .flat_map(|i| i.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let mesh = PickMesh::from_bytes(&vertex_data, 16, &index_data, 2);
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_eq!(mesh.bounds().min, Point3::new(-1.0, -1.0, -1.0));
+        assert!((mesh.bounding_radius() - 3.0f32.sqrt()).abs() < 1e-6);
+
+        let from_front = Ray::new(Point3::new(0.5, 0.5, 3.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(mesh.intersect(&from_front), Some(3.0));
+
+        let from_back = Ray::new(Point3::new(0.5, 0.5, -3.0), vec3(0.0, 0.0, 1.0));
+        assert_eq!(mesh.intersect(&from_back), Some(2.0));
+
+        let miss = Ray::new(Point3::new(2.0, 0.5, 3.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(mesh.intersect(&miss), None);
+    }
+
+    #[test]
+    fn transformed_ray_keeps_distances() {
+        let model = Matrix4::from_translation(vec3(0.0, 0.0, -10.0)) * Matrix4::from_scale(2.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0));
+        let local = ray.transform(model.invert().unwrap());
+
+        // unit box scaled to 2 in front of the ray ends at z = -8
+        assert_eq!(Aabb::from_radius(1.0).intersect(&local), Some(8.0));
+    }
+}
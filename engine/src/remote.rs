@@ -0,0 +1,299 @@
+//! Optional TCP control channel that allows external tools (asset-server UI,
+//! test harnesses) to drive a running renderer instance.
+//!
+//! The protocol is line-based. Each request is a single line containing
+//! whitespace separated command name and its arguments. Each request is
+//! answered with a single line starting with `ok` or `err` followed by
+//! the result or the error message.
+//!
+//! | Request                | Description                                         |
+//! |------------------------|-----------------------------------------------------|
+//! | `load_scene <name>`    | replaces current scene (see `Game::load_scene`)     |
+//! | `set <cvar> <value>`   | sets value of a console variable                    |
+//! | `get <cvar>`           | returns value of a console variable                 |
+//! | `auth <token>`         | authenticates the client (see below)                |
+//! | `screenshot <path>`    | saves next rendered frame as PNG to `path`          |
+//! | `stats`                | returns frame statistics as `key=value` pairs       |
+//! | `sequence <action>`    | controls playback of the sequence (`play`, `pause`, |
+//! |                        | `stop`, `seek <s>`, `speed <x>`, `loop <on\|off>`)  |
+//! | `graph <path>`         | saves the render graph to `path` as JSON (`.json`)  |
+//! |                        | or GraphViz DOT (any other extension)               |
+//!
+//! Commands are executed on the main thread between frames.
+//!
+//! Paths sent by clients are relative to the output directory of the channel
+//! (`remote_output` setting). Absolute paths and paths with `..` are rejected,
+//! so clients can't write files anywhere else. When the channel has a token
+//! (`remote_token` setting) the first request of each client must be `auth <token>`,
+//! clients that send anything else are disconnected.
+
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
+use log::{error, info, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Command that can be sent to the renderer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    LoadScene(String),
+    Set(String, String),
+    Get(String),
+    Screenshot(PathBuf),
+    Stats,
+    Sequence(String),
+    Graph(PathBuf),
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let args = parts.next().unwrap_or("").trim();
+
+        let require = |what: &str| {
+            if args.is_empty() {
+                Err(format!("missing argument {} for {:?}", what, name))
+            } else {
+                Ok(args.to_string())
+            }
+        };
+
+        match name {
+            "load_scene" => Ok(Command::LoadScene(require("<name>")?)),
+            "set" => {
+                let args = require("<cvar> <value>")?;
+                match args.split_once(char::is_whitespace) {
+                    Some((k, v)) => Ok(Command::Set(k.to_string(), v.trim().to_string())),
+                    None => Err("missing argument <value> for \"set\"".into()),
+                }
+            }
+            "get" => Ok(Command::Get(require("<cvar>")?)),
+            "screenshot" => Ok(Command::Screenshot(PathBuf::from(require("<path>")?))),
+            "stats" => Ok(Command::Stats),
+            "sequence" => Ok(Command::Sequence(require("<action>")?)),
+            "graph" => Ok(Command::Graph(PathBuf::from(require("<path>")?))),
+            _ => Err(format!("unknown command {:?}", name)),
+        }
+    }
+}
+
+impl Command {
+    /// Replaces the paths of this command by the paths under the `output` directory.
+    fn resolve_paths(self, output: &Path) -> Result<Self, String> {
+        match self {
+            Command::Screenshot(path) => Ok(Command::Screenshot(resolve_output(output, &path)?)),
+            Command::Graph(path) => Ok(Command::Graph(resolve_output(output, &path)?)),
+            cmd => Ok(cmd),
+        }
+    }
+}
+
+/// Returns the path of the file at relative `path` under the `output` directory.
+/// Absolute paths and paths leaving the directory are errors.
+pub fn resolve_output(output: &Path, path: &Path) -> Result<PathBuf, String> {
+    let mut has_file = false;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => has_file = true,
+            Component::CurDir => {}
+            Component::ParentDir => return Err(format!("path {:?} must not contain ..", path)),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("path {:?} must be relative", path))
+            }
+        }
+    }
+
+    if has_file {
+        Ok(output.join(path))
+    } else {
+        Err(format!("path {:?} does not name a file", path))
+    }
+}
+
+/// Command received from a remote client together with the channel
+/// the result should be sent to.
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Result<String, String>>,
+}
+
+impl Request {
+    /// Sends the result of the command back to the client. The result
+    /// may be sent from any thread.
+    pub fn reply(self, result: Result<String, String>) {
+        // client might have disconnected in meantime
+        self.reply.send(result).ok();
+    }
+}
+
+/// Listener of the remote control channel. Incoming requests are queued and
+/// should be processed by calling [`poll`](struct.RemoteControl.html#method.poll)
+/// once per frame.
+pub struct RemoteControl {
+    requests: Receiver<Request>,
+}
+
+impl RemoteControl {
+    /// Starts listening on specified address. Each connected client is handled
+    /// by its own thread. Paths of the commands are resolved under the `output`
+    /// directory and clients must authenticate with the `token` when it is set.
+    pub fn listen(
+        addr: SocketAddr,
+        output: PathBuf,
+        token: Option<String>,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (tx, rx) = unbounded();
+        let client = Arc::new(ClientSettings { output, token });
+
+        info!("Remote control is listening on {}", addr);
+
+        std::thread::Builder::new()
+            .name("RemoteControl".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => spawn_client_thread(stream, tx.clone(), client.clone()),
+                        Err(e) => warn!("Cannot accept remote control client: {:?}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self { requests: rx })
+    }
+
+    /// Returns iterator over all requests received since the last call.
+    pub fn poll(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+/// Settings shared by the threads of all clients.
+struct ClientSettings {
+    output: PathBuf,
+    token: Option<String>,
+}
+
+fn spawn_client_thread(
+    stream: TcpStream,
+    requests: Sender<Request>,
+    settings: Arc<ClientSettings>,
+) {
+    let peer = stream.peer_addr().ok();
+    info!("Remote control client {:?} connected", peer);
+
+    let result = std::thread::Builder::new()
+        .name(format!("RemoteControl-{:?}", peer))
+        .spawn(move || {
+            if let Err(e) = handle_client(stream, requests, &settings) {
+                warn!("Remote control client {:?} failed: {:?}", peer, e);
+            }
+            info!("Remote control client {:?} disconnected", peer);
+        });
+
+    if let Err(e) = result {
+        error!("Cannot start remote control client thread: {:?}", e);
+    }
+}
+
+fn handle_client(
+    stream: TcpStream,
+    requests: Sender<Request>,
+    settings: &ClientSettings,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut authenticated = settings.token.is_none();
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !authenticated {
+            let (name, token) = line
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or(("", ""));
+            if name != "auth" || Some(token.trim()) != settings.token.as_deref() {
+                writeln!(writer, "err authentication required")?;
+                return Ok(());
+            }
+            authenticated = true;
+            writeln!(writer, "ok")?;
+            continue;
+        }
+
+        let command = line
+            .parse::<Command>()
+            .and_then(|cmd| cmd.resolve_paths(&settings.output));
+        let result = match command {
+            Err(e) => Err(e),
+            Ok(command) => {
+                let (tx, rx) = bounded(1);
+                requests
+                    .send(Request { command, reply: tx })
+                    .map_err(|_| "renderer is shutting down".to_string())
+                    .and_then(|_| rx.recv().map_err(|_| "no reply".to_string()))
+                    .and_then(|r| r)
+            }
+        };
+
+        match result {
+            Ok(t) if t.is_empty() => writeln!(writer, "ok")?,
+            Ok(t) => writeln!(writer, "ok {}", t)?,
+            Err(e) => writeln!(writer, "err {}", e)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remote::{resolve_output, Command};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn relative_paths_are_resolved_under_output() {
+        let output = Path::new("captures");
+        assert_eq!(
+            resolve_output(output, Path::new("shot.png")),
+            Ok(PathBuf::from("captures/shot.png"))
+        );
+        assert_eq!(
+            resolve_output(output, Path::new("./scenes/graph.json")),
+            Ok(PathBuf::from("captures/./scenes/graph.json"))
+        );
+    }
+
+    #[test]
+    fn paths_outside_of_output_are_rejected() {
+        let output = Path::new("captures");
+        for path in &[
+            "/etc/passwd",
+            "../shot.png",
+            "scenes/../../shot.png",
+            ".",
+            "",
+        ] {
+            assert!(resolve_output(output, Path::new(path)).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn command_paths_are_resolved() {
+        let cmd = "graph frame.dot".parse::<Command>().unwrap();
+        assert_eq!(
+            cmd.resolve_paths(Path::new("captures")),
+            Ok(Command::Graph(PathBuf::from("captures/frame.dot")))
+        );
+        let cmd = "screenshot ../frame.png".parse::<Command>().unwrap();
+        assert!(cmd.resolve_paths(Path::new("captures")).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+//! Bloom of bright areas of the HDR buffer.
+//!
+//! Bright parts of the HDR buffer are extracted (with a soft threshold) into
+//! a half resolution buffer which is then progressively downsampled into a chain
+//! of smaller buffers. The chain is then upsampled back with a tent filter where
+//! each level is added to the bigger one. The biggest level is composited with
+//! the HDR buffer by the tonemapping (the next effect of the post-processing chain).
+
+use crate::render::descriptor_set_layout;
+use crate::render::graph::{AttachmentId, PassId, RenderGraph};
+use crate::render::post::{PostEffect, PostImage, PostInput, PostOutput};
+use crate::render::precision::Precision;
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use crate::resources::mesh::IndexedMesh;
+use std::sync::Arc;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents,
+};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::format::ClearValue;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{Framebuffer, FramebufferAbstract, LoadOp, RenderPass, StoreOp};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+pub mod shaders {
+    pub mod threshold {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_bloom_threshold.glsl"
+        }
+    }
+
+    pub mod downsample {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_bloom_downsample.glsl"
+        }
+    }
+
+    pub mod upsample {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_bloom_upsample.glsl"
+        }
+    }
+}
+
+/// Maximum number of buffers in the downsample chain (first one has half resolution).
+pub const MAX_BLOOM_LEVELS: usize = 6;
+
+const BLOOM_DESCRIPTOR_SET: usize = 0;
+
+/// Parameters of the bloom effect.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomSettings {
+    /// Multiplier of the bloom added to the HDR color. Bloom is disabled when zero.
+    pub intensity: f32,
+    /// Brightness in the HDR buffer above which pixels start to bloom.
+    pub threshold: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.05,
+            threshold: 1.0,
+        }
+    }
+}
+
+/// Returns dimensions of all levels of the downsample chain for the specified
+/// resolution. Levels smaller than 2x2 pixels are not used.
+pub fn level_dimensions(dims: [u32; 2]) -> Vec<[u32; 2]> {
+    let mut levels = Vec::with_capacity(MAX_BLOOM_LEVELS);
+    let mut level = [dims[0] / 2, dims[1] / 2];
+
+    while levels.len() < MAX_BLOOM_LEVELS && level[0] >= 2 && level[1] >= 2 {
+        levels.push(level);
+        level = [level[0] / 2, level[1] / 2];
+    }
+
+    levels
+}
+
+/// One buffer of the downsample chain.
+struct Level {
+    dims: [u32; 2],
+    buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    // framebuffers used to render into this level
+    downsample_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    upsample_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    // descriptor sets used to read from this level
+    downsample_ds: Arc<dyn DescriptorSet + Send + Sync>,
+    upsample_ds: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+pub struct Bloom {
+    pub settings: BloomSettings,
+    sampler: Arc<Sampler>,
+    // render passes rendering into one level (overwriting or adding to it)
+    level_graph: RenderGraph,
+    level_buffer: AttachmentId,
+    downsample_render_pass: Arc<RenderPass>,
+    upsample_render_pass: Arc<RenderPass>,
+    threshold_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    downsample_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    upsample_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    // descriptor set and dimensions of the hdr buffer
+    threshold_ds: Arc<dyn DescriptorSet + Send + Sync>,
+    hdr_dims: [u32; 2],
+    levels: Vec<Level>,
+}
+
+impl Bloom {
+    pub fn new(
+        device: Arc<Device>,
+        settings: BloomSettings,
+        precision: Precision,
+        hdr_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+        dims: [u32; 2],
+    ) -> Self {
+        let (level_graph, level_buffer, level_pass) =
+            declare_level_graph(LoadOp::DontCare, precision);
+        let downsample_render_pass = level_graph
+            .create_render_pass(device.clone())
+            .expect("cannot create render pass for bloom");
+
+        // upsampling adds to the existing contents of the level
+        let (upsample_graph, _, _) = declare_level_graph(LoadOp::Load, precision);
+        let upsample_render_pass = upsample_graph
+            .create_render_pass(device.clone())
+            .expect("cannot create render pass for bloom");
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .expect("cannot create sampler for bloom");
+
+        let vs = crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
+        let threshold_fs = shaders::threshold::Shader::load(device.clone()).unwrap();
+        let downsample_fs = shaders::downsample::Shader::load(device.clone()).unwrap();
+        let upsample_fs = shaders::upsample::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_threshold_fs = CachedShader::load(device.clone(), "fs_bloom_threshold");
+        let cached_downsample_fs = CachedShader::load(device.clone(), "fs_bloom_downsample");
+        let cached_upsample_fs = CachedShader::load(device.clone(), "fs_bloom_upsample");
+
+        let threshold_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(
+                    cached_threshold_fs.entry_point(threshold_fs.main_entry_point()),
+                    (),
+                )
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .render_pass(level_graph.subpass(&downsample_render_pass, level_pass))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create bloom threshold pipeline"),
+        );
+
+        let downsample_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(
+                    cached_downsample_fs.entry_point(downsample_fs.main_entry_point()),
+                    (),
+                )
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .render_pass(level_graph.subpass(&downsample_render_pass, level_pass))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create bloom downsample pipeline"),
+        );
+
+        let upsample_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(
+                    cached_upsample_fs.entry_point(upsample_fs.main_entry_point()),
+                    (),
+                )
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .blend_collective(AttachmentBlend {
+                    enabled: true,
+                    color_op: BlendOp::Add,
+                    color_source: BlendFactor::One,
+                    color_destination: BlendFactor::One,
+                    alpha_op: BlendOp::Add,
+                    alpha_source: BlendFactor::One,
+                    alpha_destination: BlendFactor::One,
+                    mask_red: true,
+                    mask_green: true,
+                    mask_blue: true,
+                    mask_alpha: true,
+                })
+                .render_pass(upsample_graph.subpass(&upsample_render_pass, level_pass))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create bloom upsample pipeline"),
+        );
+
+        let threshold_ds = sampled_ds(threshold_pipeline.as_ref(), hdr_buffer, sampler.clone());
+
+        let mut bloom = Self {
+            settings,
+            sampler,
+            level_graph,
+            level_buffer,
+            downsample_render_pass,
+            upsample_render_pass,
+            threshold_pipeline: threshold_pipeline as Arc<_>,
+            downsample_pipeline: downsample_pipeline as Arc<_>,
+            upsample_pipeline: upsample_pipeline as Arc<_>,
+            threshold_ds,
+            hdr_dims: dims,
+            levels: vec![],
+        };
+        bloom.levels = bloom.create_levels(device, dims);
+        bloom
+    }
+
+    fn create_levels(&self, device: Arc<Device>, dims: [u32; 2]) -> Vec<Level> {
+        level_dimensions(dims)
+            .into_iter()
+            .map(|dims| {
+                let buffer = self
+                    .level_graph
+                    .create_images(device.clone(), dims)
+                    .expect("cannot create bloom buffer")
+                    .get(self.level_buffer);
+
+                Level {
+                    dims,
+                    downsample_framebuffer: framebuffer(
+                        self.downsample_render_pass.clone(),
+                        buffer.clone(),
+                    ),
+                    upsample_framebuffer: framebuffer(
+                        self.upsample_render_pass.clone(),
+                        buffer.clone(),
+                    ),
+                    downsample_ds: sampled_ds(
+                        self.downsample_pipeline.as_ref(),
+                        buffer.clone(),
+                        self.sampler.clone(),
+                    ),
+                    upsample_ds: sampled_ds(
+                        self.upsample_pipeline.as_ref(),
+                        buffer.clone(),
+                        self.sampler.clone(),
+                    ),
+                    buffer,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns whether the bloom is rendered.
+    pub fn enabled(&self) -> bool {
+        self.settings.intensity > 0.0 && !self.levels.is_empty()
+    }
+
+    /// Returns the buffer with the final bloom (with half resolution of the
+    /// HDR buffer).
+    pub fn output(&self) -> Option<Arc<ImageView<Arc<AttachmentImage>>>> {
+        self.levels.first().map(|x| x.buffer.clone())
+    }
+
+    /// Returns the sampler that should be used to read the output.
+    pub fn sampler(&self) -> Arc<Sampler> {
+        self.sampler.clone()
+    }
+
+    /// Records all passes of the bloom into the command buffer. Must be called
+    /// outside of a render pass after the HDR buffer is rendered.
+    pub fn draw(
+        &self,
+        fst: &IndexedMesh<PositionOnlyVertex, u16>,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        if !self.enabled() {
+            return;
+        }
+
+        // extract bright areas into the first level
+        let first = &self.levels[0];
+        pass(
+            cmd,
+            fst,
+            &first.downsample_framebuffer,
+            &self.threshold_pipeline,
+            &self.threshold_ds,
+            first.dims,
+            shaders::threshold::ty::PushConstants {
+                texel_size: texel_size(self.hdr_dims),
+                target_size: target_size(first.dims),
+                threshold: self.settings.threshold,
+            },
+        );
+
+        // downsample each level into the next smaller one
+        for (source, target) in self.levels.iter().zip(self.levels.iter().skip(1)) {
+            pass(
+                cmd,
+                fst,
+                &target.downsample_framebuffer,
+                &self.downsample_pipeline,
+                &source.downsample_ds,
+                target.dims,
+                shaders::downsample::ty::PushConstants {
+                    texel_size: texel_size(source.dims),
+                    target_size: target_size(target.dims),
+                },
+            );
+        }
+
+        // add each level to the next bigger one, starting with the smallest one
+        for (target, source) in self.levels.iter().zip(self.levels.iter().skip(1)).rev() {
+            pass(
+                cmd,
+                fst,
+                &target.upsample_framebuffer,
+                &self.upsample_pipeline,
+                &source.upsample_ds,
+                target.dims,
+                shaders::upsample::ty::PushConstants {
+                    texel_size: texel_size(source.dims),
+                    target_size: target_size(target.dims),
+                },
+            );
+        }
+    }
+}
+
+impl PostEffect for Bloom {
+    fn name(&self) -> &'static str {
+        "bloom"
+    }
+
+    fn draws(&self) -> bool {
+        false
+    }
+
+    /// Recreates the downsample chain for the new HDR buffer.
+    fn dimensions_changed(&mut self, hdr: &PostImage, dims: [u32; 2]) {
+        let device = self.downsample_render_pass.device().clone();
+
+        self.threshold_ds = sampled_ds(
+            self.threshold_pipeline.as_ref(),
+            hdr.clone(),
+            self.sampler.clone(),
+        );
+        self.hdr_dims = dims;
+        self.levels = self.create_levels(device, dims);
+    }
+
+    fn record(
+        &self,
+        input: &mut PostInput,
+        _: PostOutput,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        if !self.enabled() {
+            return;
+        }
+
+        self.draw(input.frame.fst, cmd);
+        input.bloom = self.output().map(|x| (x, self.settings.intensity));
+    }
+}
+
+/// Declares a render pass rendering into one level of the chain.
+fn declare_level_graph(load: LoadOp, precision: Precision) -> (RenderGraph, AttachmentId, PassId) {
+    let mut graph = RenderGraph::new();
+    // alpha is not needed, but three component float formats are not renderable
+    let buffer = graph.attachment("Bloom buffer", precision.rgba(), load, StoreOp::Store);
+    graph.sampled(buffer);
+    let pass = graph.pass("Bloom").color(buffer).add();
+
+    (graph, buffer, pass)
+}
+
+fn texel_size(dims: [u32; 2]) -> [f32; 2] {
+    [1.0 / dims[0] as f32, 1.0 / dims[1] as f32]
+}
+
+fn target_size(dims: [u32; 2]) -> [f32; 2] {
+    [dims[0] as f32, dims[1] as f32]
+}
+
+fn framebuffer(
+    render_pass: Arc<RenderPass>,
+    buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+    Arc::new(
+        Framebuffer::start(render_pass)
+            .add(buffer)
+            .expect("cannot add attachment to framebuffer")
+            .build()
+            .expect("cannot build framebuffer"),
+    )
+}
+
+fn sampled_ds(
+    pipeline: &(dyn GraphicsPipelineAbstract + Send + Sync),
+    image: Arc<ImageView<Arc<AttachmentImage>>>,
+    sampler: Arc<Sampler>,
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    Arc::new(
+        PersistentDescriptorSet::start(descriptor_set_layout(
+            pipeline.layout(),
+            BLOOM_DESCRIPTOR_SET,
+        ))
+        .add_sampled_image(image, sampler)
+        .unwrap()
+        .build()
+        .unwrap(),
+    )
+}
+
+/// Records one full screen pass rendering into the `framebuffer`.
+fn pass<Pc>(
+    cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    fst: &IndexedMesh<PositionOnlyVertex, u16>,
+    framebuffer: &Arc<dyn FramebufferAbstract + Send + Sync>,
+    pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    ds: &Arc<dyn DescriptorSet + Send + Sync>,
+    dims: [u32; 2],
+    push_constants: Pc,
+) {
+    let dynamic_state = DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dims[0] as f32, dims[1] as f32],
+            depth_range: 0.0..1.0,
+        }]),
+        ..DynamicState::none()
+    };
+
+    cmd.begin_render_pass(
+        framebuffer.clone(),
+        SubpassContents::Inline,
+        vec![ClearValue::None],
+    )
+    .unwrap();
+    cmd.draw_indexed(
+        pipeline.clone(),
+        &dynamic_state,
+        vec![fst.vertex_buffer().clone()],
+        fst.index_buffer().clone(),
+        ds.clone(),
+        push_constants,
+    )
+    .expect("cannot do bloom pass");
+    cmd.end_render_pass().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::bloom::{level_dimensions, MAX_BLOOM_LEVELS};
+
+    #[test]
+    fn levels_halve_resolution() {
+        assert_eq!(
+            level_dimensions([1920, 1080]),
+            vec![
+                [960, 540],
+                [480, 270],
+                [240, 135],
+                [120, 67],
+                [60, 33],
+                [30, 16]
+            ]
+        );
+        assert_eq!(level_dimensions([7680, 4320]).len(), MAX_BLOOM_LEVELS);
+    }
+
+    #[test]
+    fn skips_tiny_levels() {
+        assert_eq!(level_dimensions([16, 6]), vec![[8, 3]]);
+        assert!(level_dimensions([3, 3]).is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+//! Immediate-mode drawing of lines in the world for debugging (eg. light
+//! directions, bounding boxes or the frustum of a camera while debugging culling).
+//!
+//! Lines can be added from anywhere (any thread) with [`line`](fn.line.html),
+//! [`aabb`](fn.aabb.html), [`sphere`](fn.sphere.html), [`axis`](fn.axis.html)
+//! and [`frustum`](fn.frustum.html) in world-space. The engine takes the lines
+//! collected since the last frame before rendering the main window and the
+//! [`DebugLines`](struct.DebugLines.html) painter draws them into the swapchain
+//! image after tonemapping (before the overlay and the GUI). Lines are unlit and
+//! not occluded by the geometry (there is no depth buffer in that pass).
+
+use crate::camera::Camera;
+use crate::render::overlay::shaders::fragment;
+use crate::render::shader_cache::CachedShader;
+use crate::render::ubo::FrameMatrixData;
+use crate::render::vertex::DebugLineVertex;
+use crate::render::vulkan::pipeline_cache;
+use cgmath::{Matrix4, Point3, Quaternion, SquareMatrix, Vector3, Vector4};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{RenderPass, Subpass};
+
+/// Number of segments of each circle of a sphere.
+const SPHERE_SEGMENTS: usize = 24;
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../shaders/vs_debug_lines.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_debug_lines.glsl"
+        }
+    }
+}
+
+/// Line segment in world-space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Line {
+    pub from: Point3<f32>,
+    pub to: Point3<f32>,
+    pub color: [f32; 4],
+}
+
+/// Lines added since the last call of `take`.
+static LINES: Lazy<Mutex<Vec<Line>>> = Lazy::new(Default::default);
+
+/// Adds a line from `from` to `to`.
+pub fn line(from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+    LINES.lock().push(Line { from, to, color });
+}
+
+/// Adds the edges of the axis aligned box between `min` and `max`.
+pub fn aabb(min: Point3<f32>, max: Point3<f32>, color: [f32; 4]) {
+    LINES.lock().extend(box_edges(
+        |x, y, z| {
+            Point3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        },
+        color,
+    ));
+}
+
+/// Adds three circles (one in each axis plane) of the sphere at `center`.
+pub fn sphere(center: Point3<f32>, radius: f32, color: [f32; 4]) {
+    let point = |i: usize, plane: usize| {
+        let angle = i as f32 / SPHERE_SEGMENTS as f32 * 2.0 * PI;
+        let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+        center
+            + match plane {
+                0 => Vector3::new(cos, sin, 0.0),
+                1 => Vector3::new(0.0, cos, sin),
+                _ => Vector3::new(sin, 0.0, cos),
+            }
+    };
+
+    let mut lines = LINES.lock();
+    for plane in 0..3 {
+        lines.extend((0..SPHERE_SEGMENTS).map(|i| Line {
+            from: point(i, plane),
+            to: point(i + 1, plane),
+            color,
+        }));
+    }
+}
+
+/// Adds the axes of the `rotation` at `position` with `length` (X red, Y green
+/// and Z blue).
+pub fn axis(position: Point3<f32>, rotation: Quaternion<f32>, length: f32) {
+    let mut lines = LINES.lock();
+    for (axis, color) in [
+        (Vector3::unit_x(), [1.0, 0.0, 0.0, 1.0]),
+        (Vector3::unit_y(), [0.0, 1.0, 0.0, 1.0]),
+        (Vector3::unit_z(), [0.0, 0.0, 1.0, 1.0]),
+    ]
+    .iter()
+    {
+        lines.push(Line {
+            from: position,
+            to: position + rotation * axis * length,
+            color: *color,
+        });
+    }
+}
+
+/// Adds the edges of the frustum of the `camera` (eg. a copy of the camera
+/// before it was moved to see what it culls).
+pub fn frustum(camera: &dyn Camera<f32>, color: [f32; 4]) {
+    let inverse = match (camera.projection_matrix() * camera.view_matrix()).invert() {
+        Some(t) => t,
+        None => return,
+    };
+
+    // corners of the clip space (the near plane is at -1 in cgmath projections)
+    let corner = |x: bool, y: bool, z: bool| {
+        let sign = |b: bool| if b { 1.0 } else { -1.0 };
+        let p = inverse * Vector4::new(sign(x), sign(y), sign(z), 1.0);
+        Point3::from_homogeneous(p)
+    };
+    LINES.lock().extend(box_edges(corner, color));
+}
+
+/// Returns the 12 edges of a box with corners returned by `corner` for each
+/// combination of the minimum (false) and maximum (true) along the axes.
+fn box_edges<F>(corner: F, color: [f32; 4]) -> Vec<Line>
+where
+    F: Fn(bool, bool, bool) -> Point3<f32>,
+{
+    let mut lines = Vec::with_capacity(12);
+    for a in [false, true].iter().copied() {
+        for b in [false, true].iter().copied() {
+            lines.push(Line {
+                from: corner(false, a, b),
+                to: corner(true, a, b),
+                color,
+            });
+            lines.push(Line {
+                from: corner(a, false, b),
+                to: corner(a, true, b),
+                color,
+            });
+            lines.push(Line {
+                from: corner(a, b, false),
+                to: corner(a, b, true),
+                color,
+            });
+        }
+    }
+    lines
+}
+
+/// Returns the lines added since the last call and removes them.
+pub fn take() -> Vec<Line> {
+    std::mem::take(&mut *LINES.lock())
+}
+
+/// Draws the debug lines of the frame.
+pub struct DebugLines {
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    vertex_pool: CpuBufferPool<DebugLineVertex>,
+    lines: Vec<Line>,
+}
+
+impl DebugLines {
+    /// Creates the painter drawing into the first subpass of the `render_pass`
+    /// (`PostPasses::present` that writes into the swapchain image).
+    pub fn new(device: Arc<Device>, render_pass: Arc<RenderPass>) -> Self {
+        let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+        let fs = fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_debug_lines");
+        let cached_fs = CachedShader::load(device.clone(), "fs_overlay");
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<DebugLineVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .line_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::disabled())
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline for debug lines"),
+        );
+
+        Self {
+            pipeline: pipeline as Arc<_>,
+            vertex_pool: CpuBufferPool::new(device, BufferUsage::vertex_buffer()),
+            lines: vec![],
+        }
+    }
+
+    /// Replaces the lines drawn in the next frame (see `take`).
+    pub fn set_lines(&mut self, lines: Vec<Line>) {
+        self.lines = lines;
+    }
+
+    /// Records drawing of the lines as seen by the `camera` into the current
+    /// subpass and clears them.
+    pub fn draw(
+        &mut self,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        dynamic_state: &DynamicState,
+        camera: &dyn Camera<f32>,
+    ) {
+        if self.lines.is_empty() {
+            return;
+        }
+
+        // vertices are relative to the camera like the rest of the frame
+        let fmd = FrameMatrixData::new(camera);
+        let view_projection: Matrix4<f32> = fmd.projection * fmd.view;
+        let camera_position = camera.position();
+        let vertices = self
+            .lines
+            .drain(..)
+            .flat_map(|l| {
+                let vertex = |p: Point3<f32>| DebugLineVertex {
+                    position: (p - camera_position).into(),
+                    color: l.color,
+                };
+                vec![vertex(l.from), vertex(l.to)]
+            })
+            .collect::<Vec<_>>();
+        let vertex_buffer = self
+            .vertex_pool
+            .chunk(vertices)
+            .expect("cannot create vertex buffer for debug lines");
+
+        cmd.draw(
+            self.pipeline.clone(),
+            dynamic_state,
+            vec![Arc::new(vertex_buffer)],
+            (),
+            shaders::vertex::ty::PushConstants {
+                view_projection: view_projection.into(),
+            },
+        )
+        .expect("cannot draw debug lines");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::debug_draw::{aabb, box_edges, line, sphere, take, SPHERE_SEGMENTS};
+    use cgmath::Point3;
+
+    #[test]
+    fn box_has_twelve_axis_aligned_edges() {
+        let edges = box_edges(
+            |x, y, z| Point3::new(x as u8 as f32, y as u8 as f32, z as u8 as f32),
+            [1.0; 4],
+        );
+
+        assert_eq!(edges.len(), 12);
+        for edge in edges {
+            let d = edge.to - edge.from;
+            assert_eq!(d.x.abs() + d.y.abs() + d.z.abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn lines_are_taken_once() {
+        // the only test using the global lines (tests run in parallel)
+        line(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            [1.0; 4],
+        );
+        aabb(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 2.0, 3.0),
+            [1.0; 4],
+        );
+        sphere(Point3::new(0.0, 0.0, 0.0), 2.0, [1.0; 4]);
+
+        let lines = take();
+        assert_eq!(lines.len(), 1 + 12 + 3 * SPHERE_SEGMENTS);
+        assert!(lines[13..].iter().all(|l| ((l.from.x.powi(2)
+            + l.from.y.powi(2)
+            + l.from.z.powi(2))
+        .sqrt()
+            - 2.0)
+            .abs()
+            < 1e-4));
+        assert!(take().is_empty());
+    }
+}
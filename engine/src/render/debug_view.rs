@@ -0,0 +1,254 @@
+//! Debug visualization of intermediate buffers of the frame.
+//!
+//! When a [`DebugView`](enum.DebugView.html) other than `Final` is selected, the
+//! selected buffer is copied directly to the swapchain image over the output of
+//! the post-processing chain.
+
+use crate::render::descriptor_set_layout;
+use crate::render::pbr::Buffers;
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use std::str::FromStr;
+use std::sync::Arc;
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Device;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+pub mod shaders {
+    pub mod fragment {
+        const X: &str = include_str!("../../shaders/fs_debug_view.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_debug_view.glsl"
+        }
+    }
+}
+
+const DEBUG_VIEW_DESCRIPTOR_SET: usize = 0;
+
+/// Buffer that is presented to the screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    /// Tonemapped and anti-aliased image (no debug visualization).
+    Final,
+    /// Normals from `GBuffer 1`.
+    Normals,
+    /// Albedo from `GBuffer 2`.
+    Albedo,
+    /// Roughness (red) and metallic (green) from `GBuffer 3`.
+    RoughnessMetallic,
+    /// Sky visibility baked into vertices of meshes from `GBuffer 3`.
+    SkyVisibility,
+    /// Depth buffer linearized between the near and far plane of the camera.
+    Depth,
+    /// Revealage of transparent objects (white where nothing transparent is drawn).
+    Revealage,
+    /// HDR buffer before bloom and tonemapping clamped to `[0, 1]`.
+    Hdr,
+    /// Exposure zones of the HDR buffer in false colors (see
+    /// `render::histogram::zone_color`) with a luminance histogram.
+    Exposure,
+}
+
+impl DebugView {
+    /// All views in the order they are cycled through.
+    pub const ALL: [DebugView; 9] = [
+        DebugView::Final,
+        DebugView::Normals,
+        DebugView::Albedo,
+        DebugView::RoughnessMetallic,
+        DebugView::SkyVisibility,
+        DebugView::Depth,
+        DebugView::Revealage,
+        DebugView::Hdr,
+        DebugView::Exposure,
+    ];
+
+    /// Returns the view that follows this one (wraps around to `Final`).
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Returns the name used by the `render.debug_view` cvar.
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Final => "final",
+            DebugView::Normals => "normals",
+            DebugView::Albedo => "albedo",
+            DebugView::RoughnessMetallic => "roughness_metallic",
+            DebugView::SkyVisibility => "sky_visibility",
+            DebugView::Depth => "depth",
+            DebugView::Revealage => "revealage",
+            DebugView::Hdr => "hdr",
+            DebugView::Exposure => "exposure",
+        }
+    }
+
+    /// Returns how `fs_debug_view.glsl` decodes the sampled value.
+    fn shader_mode(self) -> u32 {
+        match self {
+            DebugView::Depth => 1,
+            DebugView::Revealage => 2,
+            DebugView::Exposure => 3,
+            DebugView::SkyVisibility => 4,
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for DebugView {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| format!("unknown debug view {:?}", s))
+    }
+}
+
+/// Returns the buffer displayed by the `view` or `None` for `DebugView::Final`.
+fn source(buffers: &Buffers, view: DebugView) -> Option<Arc<ImageView<Arc<AttachmentImage>>>> {
+    Some(match view {
+        DebugView::Final => return None,
+        DebugView::Normals => buffers.gbuffer1.clone(),
+        DebugView::Albedo => buffers.gbuffer2.clone(),
+        DebugView::RoughnessMetallic | DebugView::SkyVisibility => buffers.gbuffer3.clone(),
+        DebugView::Depth => buffers.depth_buffer.clone(),
+        DebugView::Revealage => buffers.revealage_buffer.clone(),
+        DebugView::Hdr | DebugView::Exposure => buffers.hdr_buffer.clone(),
+    })
+}
+
+/// Pipeline that draws the selected debug view into the final render pass.
+pub struct DebugViewer {
+    /// Currently displayed view.
+    pub view: DebugView,
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    sampler: Arc<Sampler>,
+    /// Descriptor sets for each view in `DebugView::ALL` order (`None` for `Final`).
+    descriptor_sets: Vec<Option<Arc<dyn DescriptorSet + Send + Sync>>>,
+}
+
+impl DebugViewer {
+    /// Creates the viewer drawing into the first subpass of the `render_pass`
+    /// (`PostPasses::present` that writes into the swapchain image).
+    pub fn new(device: Arc<Device>, render_pass: Arc<RenderPass>, buffers: &Buffers) -> Self {
+        let vs = crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_fs = CachedShader::load(device.clone(), "fs_debug_view");
+
+        // nearest filtering so individual texels of the buffers can be inspected
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .expect("cannot create sampler for debug view");
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::disabled())
+                .cull_mode_back()
+                .front_face_clockwise()
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build_with_cache(pipeline_cache(&device))
+                .build(device)
+                .expect("cannot create graphics pipeline"),
+        );
+
+        let mut viewer = Self {
+            view: DebugView::Final,
+            pipeline: pipeline as Arc<_>,
+            sampler,
+            descriptor_sets: vec![],
+        };
+        viewer.recreate_descriptors(buffers);
+        viewer
+    }
+
+    /// Recreates descriptor sets after the `buffers` were recreated.
+    pub fn recreate_descriptors(&mut self, buffers: &Buffers) {
+        let layout = descriptor_set_layout(self.pipeline.layout(), DEBUG_VIEW_DESCRIPTOR_SET);
+
+        self.descriptor_sets = DebugView::ALL
+            .iter()
+            .map(|view| {
+                source(buffers, *view).map(|image| {
+                    Arc::new(
+                        PersistentDescriptorSet::start(layout.clone())
+                            .add_sampled_image(image, self.sampler.clone())
+                            .unwrap()
+                            .build()
+                            .unwrap(),
+                    ) as Arc<_>
+                })
+            })
+            .collect();
+    }
+
+    /// Returns the descriptor set of the current view or `None` if the final
+    /// image should be displayed.
+    pub fn descriptor_set(&self) -> Option<Arc<dyn DescriptorSet + Send + Sync>> {
+        self.descriptor_sets[self.view as usize].clone()
+    }
+
+    /// Returns push constants for the current view.
+    pub fn push_constants(
+        &self,
+        resolution: [f32; 2],
+        near: f32,
+        far: f32,
+    ) -> shaders::fragment::ty::PushConstants {
+        shaders::fragment::ty::PushConstants {
+            resolution,
+            mode: self.view.shader_mode(),
+            near,
+            far,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::debug_view::DebugView;
+
+    #[test]
+    fn cycles_through_all_views() {
+        let mut view = DebugView::Final;
+        for expected in DebugView::ALL.iter().skip(1) {
+            view = view.next();
+            assert_eq!(view, *expected);
+        }
+        assert_eq!(view.next(), DebugView::Final);
+    }
+
+    #[test]
+    fn parses_view_names() {
+        for view in DebugView::ALL.iter() {
+            assert_eq!(view.name().parse::<DebugView>(), Ok(*view));
+        }
+        assert!("gbuffer".parse::<DebugView>().is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Screen-space decals projected onto opaque geometry.
+//!
+//! Decals are drawn in their own subpass between the geometry and the lighting
+//! subpass. Each decal is a box that is rasterized, the position of the surface
+//! under each of its pixels is reconstructed from the depth buffer and pixels
+//! inside the box are blended into the G-buffer. The material is projected along
+//! the local y axis of the box (from the top).
+
+use crate::assets::Content;
+use crate::camera::Camera;
+use crate::render::pbr::PBRDeffered;
+use crate::render::pools::UniformBufferPool;
+use crate::render::shader_cache::CachedShader;
+use crate::render::transform::Transform;
+use crate::render::ubo::{DecalData, FrameMatrixData};
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use crate::render::{descriptor_set_layout, FrameMatrixPool, FRAME_DATA_UBO_DESCRIPTOR_SET};
+use crate::resources::material::{FallbackMaps, Material, StaticMaterial, StaticMaterialError};
+use crate::resources::mesh::{create_cube, IndexedMesh};
+use bf::uuid::Uuid;
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::Subpass;
+use vulkano::sync::GpuFuture;
+
+/// Descriptor set index used for data of the decal.
+pub const DECAL_DATA_UBO_DESCRIPTOR_SET: usize = 2;
+/// Descriptor set index used for the depth buffer.
+pub const DECAL_DEPTH_DESCRIPTOR_SET: usize = 3;
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../shaders/vs_decal.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_decal.glsl"
+        }
+    }
+
+    pub mod fragment {
+        const X: &str = include_str!("../../shaders/fs_decal.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_decal.glsl"
+        }
+    }
+}
+
+/// Box (unit cube scaled by the `transform`) that projects a material onto the
+/// surfaces inside it.
+///
+/// The albedo, normal, roughness and metallic of the material replace the ones
+/// of the surface weighted by the opacity of the material (alpha of the albedo
+/// map, opacity map and opacity). Other maps are ignored.
+pub struct Decal {
+    pub transform: Transform,
+    /// Material created for the decal pipeline (see [`Decal::new`](#method.new)).
+    pub material: Arc<dyn Material>,
+    /// Strength of the decal from `0.0` (invisible) to `1.0`.
+    pub opacity: f32,
+}
+
+impl Decal {
+    /// Creates a decal that projects the material asset with specified `uuid`.
+    pub fn new(
+        material: Uuid,
+        transform: Transform,
+        path: &PBRDeffered,
+        content: &Content,
+        fallback: Arc<FallbackMaps>,
+    ) -> Result<(Self, impl GpuFuture), StaticMaterialError> {
+        let asset = *content
+            .request_load(material)
+            .wait::<bf::material::Material>();
+        let (material, future) = StaticMaterial::from_material(
+            &asset,
+            content,
+            path.decals.pipeline.clone(),
+            path.samplers.aniso_repeat.clone(),
+            content.transfer_queue.clone(),
+            fallback,
+        )?;
+
+        Ok((
+            Self {
+                transform,
+                material,
+                opacity: 1.0,
+            },
+            future,
+        ))
+    }
+
+    /// Returns the center and radius of the sphere that contains the box.
+    pub fn bounding_sphere(&self) -> (Vector3<f32>, f32) {
+        let scale = self.transform.scale();
+        (self.transform.position(), scale.magnitude() * 0.5)
+    }
+}
+
+/// Pipeline of the decal subpass and resources used to draw decals.
+pub struct Decals {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    cube: Arc<IndexedMesh<PositionOnlyVertex, u16>>,
+    frame_matrix_data_pool: FrameMatrixPool,
+    decal_data_pool: UniformBufferPool<DecalData>,
+    /// Depth buffer the positions of surfaces are reconstructed from.
+    depth_ds: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl Decals {
+    pub fn new(
+        queue: Arc<Queue>,
+        subpass: Subpass,
+        depth_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Self {
+        let device = queue.device().clone();
+        let (cube, _) = create_cube(queue).expect("cannot create cube for decals");
+
+        let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_decal");
+        let cached_fs = CachedShader::load(device.clone(), "fs_decal");
+
+        // decals replace albedo, normal, roughness and metallic, the lighting
+        // model, occlusion and sky visibility of the surface are kept
+        let blend = |mask_blue| AttachmentBlend {
+            enabled: true,
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::SrcAlpha,
+            color_destination: BlendFactor::OneMinusSrcAlpha,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::Zero,
+            alpha_destination: BlendFactor::One,
+            mask_red: true,
+            mask_green: true,
+            mask_blue,
+            mask_alpha: false,
+        };
+
+        // back faces are drawn, so the decal is visible with the camera inside its box
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .blend_individual(vec![blend(true), blend(true), blend(false)])
+                .cull_mode_front()
+                .front_face_clockwise()
+                .render_pass(subpass)
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create decal pipeline"),
+        );
+
+        Self {
+            frame_matrix_data_pool: FrameMatrixPool::new(
+                device.clone(),
+                descriptor_set_layout(pipeline.layout(), FRAME_DATA_UBO_DESCRIPTOR_SET),
+            ),
+            decal_data_pool: UniformBufferPool::new(
+                device,
+                descriptor_set_layout(pipeline.layout(), DECAL_DATA_UBO_DESCRIPTOR_SET),
+            ),
+            depth_ds: depth_ds(pipeline.as_ref(), depth_buffer),
+            pipeline: pipeline as Arc<_>,
+            cube,
+        }
+    }
+
+    /// Replaces the depth buffer with a new one (with different dimensions).
+    pub fn dimensions_changed(&mut self, depth_buffer: Arc<ImageView<Arc<AttachmentImage>>>) {
+        self.depth_ds = depth_ds(self.pipeline.as_ref(), depth_buffer);
+    }
+
+    /// Records draw commands of `decals` that are in the view of the `camera`
+    /// into specified *command buffer*. Returns the number of draw calls.
+    pub fn draw(
+        &self,
+        decals: &[Decal],
+        camera: &dyn Camera<f32>,
+        dynamic_state: &DynamicState,
+        resolution: [f32; 2],
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> usize {
+        let frustum = camera.frustum();
+        let visible = decals
+            .iter()
+            .filter(|x| x.opacity > 0.0)
+            .filter(|x| {
+                let (center, radius) = x.bounding_sphere();
+                frustum.intersects_sphere(Point3::from_vec(center), radius)
+            })
+            .collect::<Vec<_>>();
+
+        if visible.is_empty() {
+            return 0;
+        }
+
+        let frame_matrix_data = Arc::new(
+            self.frame_matrix_data_pool
+                .next(FrameMatrixData::new(camera))
+                .expect("cannot create FrameMatrixData for this frame"),
+        );
+
+        let mut draw_calls = 0;
+        for decal in visible {
+            // boxes with zero size can't contain any surface
+            let data = match DecalData::new(
+                decal.transform.relative_to(camera.position()),
+                decal.opacity,
+            ) {
+                Some(t) => t,
+                None => continue,
+            };
+            let decal_data = self
+                .decal_data_pool
+                .next(data)
+                .expect("cannot create DecalData for this frame");
+
+            cmd.draw_indexed(
+                self.pipeline.clone(),
+                dynamic_state,
+                vec![self.cube.vertex_buffer().clone()],
+                self.cube.index_buffer().clone(),
+                (
+                    frame_matrix_data.clone(),
+                    decal.material.descriptor_set(),
+                    decal_data,
+                    self.depth_ds.clone(),
+                ),
+                shaders::fragment::ty::PushConstants { resolution },
+            )
+            .expect("cannot draw decal");
+            draw_calls += 1;
+        }
+
+        draw_calls
+    }
+}
+
+fn depth_ds(
+    pipeline: &(dyn GraphicsPipelineAbstract + Send + Sync),
+    depth_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    Arc::new(
+        PersistentDescriptorSet::start(descriptor_set_layout(
+            pipeline.layout(),
+            DECAL_DEPTH_DESCRIPTOR_SET,
+        ))
+        .add_image(depth_buffer)
+        .unwrap()
+        .build()
+        .unwrap(),
+    )
+}
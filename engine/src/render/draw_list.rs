@@ -0,0 +1,101 @@
+//! Assignment of objects to passes they are rendered in.
+
+use bf::material::BlendMode;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Indices of objects rendered in individual passes of a frame.
+#[derive(Debug, Default, PartialEq)]
+pub struct DrawList {
+    /// Objects rendered into the G-Buffer (opaque and masked).
+    pub geometry: Vec<usize>,
+    /// Objects rendered in the transparency accumulation pass.
+    pub transparent: Vec<usize>,
+}
+
+impl DrawList {
+    /// Creates a draw list from blend modes of all objects in the scene. The
+    /// indices in the list refer to the order of `blend_modes`. Hidden objects
+    /// have no blend mode (`None`) and are not rendered at all.
+    pub fn new<I: IntoIterator<Item = Option<BlendMode>>>(blend_modes: I) -> Self {
+        let mut list = DrawList::default();
+
+        for (idx, blend_mode) in blend_modes.into_iter().enumerate() {
+            match blend_mode {
+                // masked materials are discarded in geometry fragment shader
+                Some(BlendMode::Opaque) | Some(BlendMode::Masked) => list.geometry.push(idx),
+                Some(BlendMode::Translucent) => list.transparent.push(idx),
+                None => {}
+            }
+        }
+
+        list
+    }
+}
+
+/// Groups objects with equal keys (eg. the same mesh, material and level of
+/// detail) that can be drawn with one instanced draw call. Groups are ordered
+/// by the first occurrence of their key and objects keep their order.
+pub fn group_instances<K, I>(objects: I) -> Vec<Vec<usize>>
+where
+    K: Eq + Hash,
+    I: IntoIterator<Item = (usize, K)>,
+{
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut group_of_key = HashMap::new();
+
+    for (idx, key) in objects {
+        let group = *group_of_key.entry(key).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(idx);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::draw_list::{group_instances, DrawList};
+    use bf::material::BlendMode;
+
+    #[test]
+    fn empty_scene() {
+        assert_eq!(DrawList::new(vec![]), DrawList::default());
+    }
+
+    #[test]
+    fn assigns_objects_to_passes() {
+        let list = DrawList::new(vec![
+            Some(BlendMode::Translucent),
+            Some(BlendMode::Opaque),
+            Some(BlendMode::Masked),
+            Some(BlendMode::Translucent),
+        ]);
+
+        assert_eq!(list.geometry, vec![1, 2]);
+        assert_eq!(list.transparent, vec![0, 3]);
+    }
+
+    #[test]
+    fn skips_hidden_objects() {
+        let list = DrawList::new(vec![
+            None,
+            Some(BlendMode::Opaque),
+            None,
+            Some(BlendMode::Translucent),
+        ]);
+
+        assert_eq!(list.geometry, vec![1]);
+        assert_eq!(list.transparent, vec![3]);
+    }
+
+    #[test]
+    fn groups_instances_by_key() {
+        let groups = group_instances(vec![(0, "rock"), (2, "tree"), (3, "rock"), (5, "rock")]);
+
+        assert_eq!(groups, vec![vec![0, 3, 5], vec![2]]);
+        assert!(group_instances::<&str, _>(vec![]).is_empty());
+    }
+}
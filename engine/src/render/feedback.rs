@@ -0,0 +1,66 @@
+//! CPU estimate of how much of the screen is covered by individual textures.
+//!
+//! The estimate is computed from bounding spheres of objects and is used to
+//! prioritize loading (and streaming) of textures, so the textures that are
+//! most visible are loaded first.
+
+use crate::camera::Camera;
+use crate::render::object::Object;
+use bf::uuid::Uuid;
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+use std::collections::HashMap;
+use vulkano::pipeline::vertex::Vertex;
+
+/// Returns the approximate fraction (`0.0` to `1.0`) of the screen covered by the
+/// sphere with specified `center` and `radius` when viewed by `camera`.
+pub fn screen_coverage(camera: &dyn Camera<f32>, center: Vector3<f32>, radius: f32) -> f32 {
+    let to_center = center - camera.position().to_vec();
+    let distance = to_center.magnitude();
+
+    // camera is inside the sphere
+    if distance <= radius {
+        return 1.0;
+    }
+
+    // sphere is completely behind the camera
+    let view = camera.view_matrix();
+    let forward = -Vector3::new(view.x.z, view.y.z, view.z.z);
+    if to_center.dot(forward) < -radius {
+        return 0.0;
+    }
+
+    // radius of projected sphere relative to the half of the screen height,
+    // perspective projection divides it by the distance, orthographic does not
+    let projection = camera.projection_matrix();
+    let w = projection.z.w.abs() * distance + projection.w.w;
+    let projected = radius * projection.y.y.abs() / w;
+    let screen_area = 4.0 * projection.y.y.abs() / projection.x.x.abs();
+
+    (std::f32::consts::PI * projected * projected / screen_area).min(1.0)
+}
+
+/// Computes priorities for all textures used by specified objects. The priority
+/// of a texture is the largest screen coverage of all objects using it.
+pub fn texture_priorities<V: Vertex>(
+    camera: &dyn Camera<f32>,
+    objects: &[Object<V>],
+) -> HashMap<Uuid, f32> {
+    let mut priorities = HashMap::new();
+
+    for object in objects {
+        let textures = object.material.textures();
+        if textures.is_empty() {
+            continue;
+        }
+
+        let (center, radius) = object.bounding_sphere();
+        let coverage = screen_coverage(camera, center, radius);
+
+        for uuid in textures {
+            let priority = priorities.entry(*uuid).or_insert(0.0f32);
+            *priority = priority.max(coverage);
+        }
+    }
+
+    priorities
+}
@@ -0,0 +1,242 @@
+//! Fast approximate anti-aliasing (usually the last effect of the post-processing chain).
+
+use crate::render::descriptor_set_layout;
+use crate::render::post::{
+    InputSets, PostEffect, PostImage, PostInput, PostOutput, PostPasses, PostPipelines,
+};
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use std::str::FromStr;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+pub mod shaders {
+    pub mod fragment {
+        const X: &str = include_str!("../../../shaders/fs_fxaa.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_fxaa.glsl"
+        }
+    }
+}
+
+const FXAA_DESCRIPTOR_SET: usize = 0;
+
+/// Quality preset of the anti-aliasing. Presets are specialization constants
+/// of the fragment shader, so changing the preset rebuilds the pipeline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FxaaQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl FxaaQuality {
+    /// Returns the values of specialization constants of this preset.
+    pub fn specialization_constants(self) -> shaders::fragment::SpecializationConstants {
+        // longer span finds longer edges, lower multiplier blurs more of them
+        let (span_max, reduce_mul) = match self {
+            FxaaQuality::Off => (0.0, 0.0),
+            FxaaQuality::Low => (4.0, 1.0 / 4.0),
+            FxaaQuality::Medium => (8.0, 1.0 / 8.0),
+            FxaaQuality::High => (16.0, 1.0 / 16.0),
+        };
+
+        shaders::fragment::SpecializationConstants {
+            span_max,
+            reduce_mul,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FxaaQuality::Off => "off",
+            FxaaQuality::Low => "low",
+            FxaaQuality::Medium => "medium",
+            FxaaQuality::High => "high",
+        }
+    }
+}
+
+impl Default for FxaaQuality {
+    fn default() -> Self {
+        FxaaQuality::Medium
+    }
+}
+
+impl FromStr for FxaaQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(FxaaQuality::Off),
+            "low" => Ok(FxaaQuality::Low),
+            "medium" => Ok(FxaaQuality::Medium),
+            "high" => Ok(FxaaQuality::High),
+            _ => Err(format!(
+                "invalid fxaa quality {:?} (expected off, low, medium or high)",
+                s
+            )),
+        }
+    }
+}
+
+pub struct FXAA {
+    pipelines: PostPipelines,
+    passes: PostPasses,
+    sets: InputSets,
+    sampler: Arc<Sampler>,
+    device: Arc<Device>,
+    quality: FxaaQuality,
+}
+
+impl FXAA {
+    pub fn new(device: Arc<Device>, passes: &PostPasses, quality: FxaaQuality) -> Self {
+        // create sampler that does not repeat the texture so we don't anti-alias bottom with top
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            1000.0,
+        )
+        .expect("cannot create sampler for fxaa (reading ldr_buffer)");
+
+        Self {
+            pipelines: create_pipelines(device.clone(), passes, quality),
+            passes: passes.clone(),
+            sets: InputSets::default(),
+            sampler,
+            device,
+            quality,
+        }
+    }
+
+    pub fn quality(&self) -> FxaaQuality {
+        self.quality
+    }
+
+    /// Changes the quality preset. The pipelines are rebuilt when the preset
+    /// is different from the current one.
+    pub fn set_quality(&mut self, quality: FxaaQuality) {
+        if quality == self.quality {
+            return;
+        }
+
+        self.quality = quality;
+        self.pipelines = create_pipelines(self.device.clone(), &self.passes, quality);
+    }
+}
+
+impl PostEffect for FXAA {
+    fn name(&self) -> &'static str {
+        "fxaa"
+    }
+
+    fn dimensions_changed(&mut self, _: &PostImage, _: [u32; 2]) {
+        self.sets.clear();
+    }
+
+    fn record(
+        &self,
+        input: &mut PostInput,
+        output: PostOutput,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        let ds = self.sets.get(&[&input.color], || {
+            Arc::new(
+                PersistentDescriptorSet::start(descriptor_set_layout(
+                    self.pipelines.any().layout(),
+                    FXAA_DESCRIPTOR_SET,
+                ))
+                .add_sampled_image(input.color.clone(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+            )
+        });
+
+        let frame = input.frame;
+        cmd.draw_indexed(
+            self.pipelines.get(output).clone(),
+            frame.dynamic_state,
+            vec![frame.fst.vertex_buffer().clone()],
+            frame.fst.index_buffer().clone(),
+            ds,
+            shaders::fragment::ty::PushConstants {
+                resolution: frame.resolution,
+            },
+        )
+        .expect("cannot do fxaa pass");
+    }
+}
+
+fn create_pipelines(
+    device: Arc<Device>,
+    passes: &PostPasses,
+    quality: FxaaQuality,
+) -> PostPipelines {
+    let vs = crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
+    let fs = crate::render::fxaa::shaders::fragment::Shader::load(device.clone()).unwrap();
+    let cached_vs = CachedShader::load(device.clone(), "vs_passtrough");
+    let cached_fs = CachedShader::load(device.clone(), "fs_fxaa");
+
+    PostPipelines::new(passes, |subpass| {
+        Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(
+                    cached_fs.entry_point(fs.main_entry_point()),
+                    quality.specialization_constants(),
+                )
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::disabled())
+                .cull_mode_back()
+                .front_face_clockwise()
+                .render_pass(subpass)
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::fxaa::FxaaQuality;
+
+    #[test]
+    fn parses_quality_presets() {
+        for quality in [
+            FxaaQuality::Off,
+            FxaaQuality::Low,
+            FxaaQuality::Medium,
+            FxaaQuality::High,
+        ] {
+            assert_eq!(quality.name().parse(), Ok(quality));
+        }
+        assert!("ultra".parse::<FxaaQuality>().is_err());
+    }
+
+    #[test]
+    fn off_preset_disables_anti_aliasing() {
+        // the shader skips the anti-aliasing when the span is zero
+        assert_eq!(FxaaQuality::Off.specialization_constants().span_max, 0.0);
+        assert!(FxaaQuality::Low.specialization_constants().span_max > 0.0);
+    }
+}
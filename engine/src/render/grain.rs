@@ -0,0 +1,111 @@
+//! Animated film grain added to the tonemapped image.
+
+use crate::render::descriptor_set_layout;
+use crate::render::post::{
+    InputSets, PostEffect, PostImage, PostInput, PostOutput, PostPasses, PostPipelines,
+};
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::Sampler;
+
+pub mod shaders {
+    pub mod fragment {
+        const X: &str = include_str!("../../shaders/fs_grain.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_grain.glsl"
+        }
+    }
+}
+
+const GRAIN_DESCRIPTOR_SET: usize = 0;
+
+pub struct Grain {
+    /// Amplitude of the noise added to mid-tones of the image.
+    pub intensity: f32,
+    pipelines: PostPipelines,
+    sets: InputSets,
+    sampler: Arc<Sampler>,
+}
+
+impl Grain {
+    pub fn new(device: Arc<Device>, passes: &PostPasses) -> Self {
+        let vs = crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_fs = CachedShader::load(device.clone(), "fs_grain");
+
+        let pipelines = PostPipelines::new(passes, |subpass| {
+            Arc::new(
+                GraphicsPipeline::start()
+                    .vertex_input_single_buffer::<PositionOnlyVertex>()
+                    .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                    .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                    .triangle_list()
+                    .viewports_dynamic_scissors_irrelevant(1)
+                    .render_pass(subpass)
+                    .build_with_cache(pipeline_cache(&device))
+                    .build(device.clone())
+                    .expect("cannot build grain graphics pipeline"),
+            )
+        });
+
+        Self {
+            intensity: 0.04,
+            pipelines,
+            sets: InputSets::default(),
+            sampler: Sampler::simple_repeat_linear_no_mipmap(device),
+        }
+    }
+}
+
+impl PostEffect for Grain {
+    fn name(&self) -> &'static str {
+        "grain"
+    }
+
+    fn dimensions_changed(&mut self, _: &PostImage, _: [u32; 2]) {
+        self.sets.clear();
+    }
+
+    fn record(
+        &self,
+        input: &mut PostInput,
+        output: PostOutput,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        let ds = self.sets.get(&[&input.color], || {
+            Arc::new(
+                PersistentDescriptorSet::start(descriptor_set_layout(
+                    self.pipelines.any().layout(),
+                    GRAIN_DESCRIPTOR_SET,
+                ))
+                .add_sampled_image(input.color.clone(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+            )
+        });
+
+        let frame = input.frame;
+        cmd.draw_indexed(
+            self.pipelines.get(output).clone(),
+            frame.dynamic_state,
+            vec![frame.fst.vertex_buffer().clone()],
+            frame.fst.index_buffer().clone(),
+            ds,
+            shaders::fragment::ty::PushConstants {
+                resolution: frame.resolution,
+                time: frame.time,
+                intensity: self.intensity,
+            },
+        )
+        .expect("cannot do grain pass");
+    }
+}
@@ -0,0 +1,637 @@
+//! Small render graph that describes passes of a render pass together with
+//! attachments they read and write.
+//!
+//! The Vulkan render pass (attachment layouts, subpasses, preserved attachments
+//! and dependencies between subpasses) and images of all attachments are derived
+//! from these declarations. When resolution changes only the images need to be
+//! created again with [`RenderGraph::create_images`](struct.RenderGraph.html#method.create_images).
+//!
+//! The graph can be exported to GraphViz ([`to_dot`](struct.RenderGraph.html#method.to_dot))
+//! or JSON ([`to_json`](struct.RenderGraph.html#method.to_json)) to inspect the order of
+//! passes, dependencies between them and lifetimes of attachments.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageCreationError, ImageLayout, ImageUsage};
+use vulkano::render_pass::{
+    AttachmentDesc, LoadOp, RenderPass, RenderPassCreationError, RenderPassDesc, StoreOp, Subpass,
+    SubpassDependencyDesc, SubpassDesc,
+};
+use vulkano::sync::{AccessFlags, PipelineStages};
+
+/// Identifier of an attachment in the [`RenderGraph`](struct.RenderGraph.html).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AttachmentId(usize);
+
+/// Identifier of a pass (subpass) in the [`RenderGraph`](struct.RenderGraph.html).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct PassId(usize);
+
+/// Attachment (image with resolution of the render target) declared in the graph.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub name: &'static str,
+    pub format: Format,
+    pub load: LoadOp,
+    pub store: StoreOp,
+    /// Value the attachment is cleared with when `load` is `LoadOp::Clear`.
+    pub clear_value: ClearValue,
+    /// Whether the image is also sampled outside of the render pass.
+    pub sampled: bool,
+}
+
+/// The way a pass uses an attachment.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Usage {
+    Color,
+    DepthStencil,
+    Input,
+}
+
+impl Usage {
+    /// Returns the layout the attachment has to be in for this usage.
+    pub fn layout(self) -> ImageLayout {
+        match self {
+            Usage::Color => ImageLayout::ColorAttachmentOptimal,
+            Usage::DepthStencil => ImageLayout::DepthStencilAttachmentOptimal,
+            Usage::Input => ImageLayout::ShaderReadOnlyOptimal,
+        }
+    }
+
+    /// Returns whether the pass may write into the attachment.
+    pub fn writes(self) -> bool {
+        match self {
+            Usage::Color | Usage::DepthStencil => true,
+            Usage::Input => false,
+        }
+    }
+}
+
+/// Pass declared in the graph.
+#[derive(Clone, Debug)]
+struct Pass {
+    name: &'static str,
+    color: Vec<AttachmentId>,
+    depth_stencil: Option<AttachmentId>,
+    input: Vec<AttachmentId>,
+}
+
+impl Pass {
+    /// Returns the usage of the `attachment` in this pass (if it is used).
+    fn usage(&self, attachment: AttachmentId) -> Option<Usage> {
+        if self.color.contains(&attachment) {
+            Some(Usage::Color)
+        } else if self.depth_stencil == Some(attachment) {
+            Some(Usage::DepthStencil)
+        } else if self.input.contains(&attachment) {
+            Some(Usage::Input)
+        } else {
+            None
+        }
+    }
+}
+
+/// Builder of a single pass returned by [`RenderGraph::pass`](struct.RenderGraph.html#method.pass).
+pub struct PassBuilder<'a> {
+    graph: &'a mut RenderGraph,
+    pass: Pass,
+}
+
+impl<'a> PassBuilder<'a> {
+    /// Pass writes into the `attachment` as color attachment.
+    pub fn color(mut self, attachment: AttachmentId) -> Self {
+        self.pass.color.push(attachment);
+        self
+    }
+
+    /// Pass uses the `attachment` as depth (stencil) attachment.
+    pub fn depth_stencil(mut self, attachment: AttachmentId) -> Self {
+        self.pass.depth_stencil = Some(attachment);
+        self
+    }
+
+    /// Pass reads the `attachment` as input attachment.
+    pub fn input(mut self, attachment: AttachmentId) -> Self {
+        self.pass.input.push(attachment);
+        self
+    }
+
+    /// Adds the pass to the graph. Passes are executed in the order they were added.
+    pub fn add(self) -> PassId {
+        self.graph.passes.push(self.pass);
+        PassId(self.graph.passes.len() - 1)
+    }
+}
+
+/// Declarations of attachments and passes of one render pass.
+#[derive(Clone, Debug, Default)]
+pub struct RenderGraph {
+    attachments: Vec<Attachment>,
+    passes: Vec<Pass>,
+}
+
+/// Images created for all attachments of a [`RenderGraph`](struct.RenderGraph.html).
+pub struct GraphImages(Vec<Arc<ImageView<Arc<AttachmentImage>>>>);
+
+impl GraphImages {
+    /// Returns the image of specified attachment.
+    pub fn get(&self, attachment: AttachmentId) -> Arc<ImageView<Arc<AttachmentImage>>> {
+        self.0[attachment.0].clone()
+    }
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a new attachment. Attachments are in the framebuffer in the order
+    /// they were declared.
+    pub fn attachment(
+        &mut self,
+        name: &'static str,
+        format: Format,
+        load: LoadOp,
+        store: StoreOp,
+    ) -> AttachmentId {
+        self.attachments.push(Attachment {
+            name,
+            format,
+            load,
+            store,
+            clear_value: ClearValue::None,
+            sampled: false,
+        });
+        AttachmentId(self.attachments.len() - 1)
+    }
+
+    /// Sets the value the `attachment` is cleared with at the start of the render pass.
+    pub fn clear_value(&mut self, attachment: AttachmentId, value: ClearValue) {
+        self.attachments[attachment.0].clear_value = value;
+    }
+
+    /// Marks the `attachment` as sampled outside of the render pass.
+    pub fn sampled(&mut self, attachment: AttachmentId) {
+        self.attachments[attachment.0].sampled = true;
+    }
+
+    /// Starts declaration of a new pass.
+    pub fn pass(&mut self, name: &'static str) -> PassBuilder {
+        PassBuilder {
+            graph: self,
+            pass: Pass {
+                name,
+                color: vec![],
+                depth_stencil: None,
+                input: vec![],
+            },
+        }
+    }
+
+    /// Returns all declared attachments.
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    /// Returns clear values of all attachments in order required by `begin_render_pass`.
+    pub fn clear_values(&self) -> Vec<ClearValue> {
+        self.attachments.iter().map(|a| a.clear_value).collect()
+    }
+
+    /// Returns the name of the specified pass.
+    pub fn pass_name(&self, pass: PassId) -> &'static str {
+        self.passes[pass.0].name
+    }
+
+    /// Returns indices of passes using the `attachment` together with the usage.
+    fn usages(&self, attachment: AttachmentId) -> impl Iterator<Item = (usize, Usage)> + '_ {
+        self.passes
+            .iter()
+            .enumerate()
+            .filter_map(move |(idx, p)| p.usage(attachment).map(|u| (idx, u)))
+    }
+
+    /// Returns the layout of the `attachment` at the start of the render pass (layout
+    /// of the first usage).
+    pub fn initial_layout(&self, attachment: AttachmentId) -> ImageLayout {
+        self.usages(attachment)
+            .next()
+            .map(|(_, u)| u.layout())
+            .unwrap_or(ImageLayout::General)
+    }
+
+    /// Returns the layout of the `attachment` at the end of the render pass (layout
+    /// of the last usage).
+    pub fn final_layout(&self, attachment: AttachmentId) -> ImageLayout {
+        self.usages(attachment)
+            .last()
+            .map(|(_, u)| u.layout())
+            .unwrap_or(ImageLayout::General)
+    }
+
+    /// Returns attachments that must be preserved by the `pass` because they are used
+    /// both before and after it, but not by the pass itself.
+    pub fn preserved(&self, pass: PassId) -> Vec<AttachmentId> {
+        (0..self.attachments.len())
+            .map(AttachmentId)
+            .filter(|a| {
+                self.passes[pass.0].usage(*a).is_none()
+                    && self.usages(*a).any(|(idx, _)| idx < pass.0)
+                    && self.usages(*a).any(|(idx, _)| idx > pass.0)
+            })
+            .collect()
+    }
+
+    /// Returns pairs of passes `(source, destination)` where the destination pass uses
+    /// an attachment that was used by the source pass and at least one of them writes
+    /// into it. The destination pass has to wait for the source pass.
+    pub fn dependencies(&self) -> Vec<(PassId, PassId)> {
+        let mut dependencies = BTreeSet::new();
+
+        for attachment in (0..self.attachments.len()).map(AttachmentId) {
+            let usages: Vec<_> = self.usages(attachment).collect();
+
+            for (i, (dst, dst_usage)) in usages.iter().enumerate() {
+                let src = usages[..i]
+                    .iter()
+                    .rev()
+                    .find(|(_, src_usage)| src_usage.writes() || dst_usage.writes());
+
+                if let Some((src, _)) = src {
+                    dependencies.insert((PassId(*src), PassId(*dst)));
+                }
+            }
+        }
+
+        dependencies.into_iter().collect()
+    }
+
+    /// Returns the first and the last pass using the `attachment` or `None` if
+    /// the attachment is not used by any pass.
+    pub fn lifetime(&self, attachment: AttachmentId) -> Option<(PassId, PassId)> {
+        let first = self.usages(attachment).next()?.0;
+        let last = self.usages(attachment).last()?.0;
+
+        Some((PassId(first), PassId(last)))
+    }
+
+    /// Exports the graph in GraphViz DOT format. Passes are boxes connected by
+    /// dashed edges of dependencies. Attachments are ellipses (dotted when they
+    /// are not sampled and thus transient) with edges to passes that read them
+    /// and from passes that write them.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+
+        for (idx, p) in self.passes.iter().enumerate() {
+            writeln!(
+                dot,
+                "    p{} [shape=box, label=\"{}. {}\"];",
+                idx, idx, p.name
+            )
+            .unwrap();
+        }
+
+        for (idx, a) in self.attachments.iter().enumerate() {
+            let lifetime = match self.lifetime(AttachmentId(idx)) {
+                Some((first, last)) => format!("passes {}-{}", first.0, last.0),
+                None => "unused".to_string(),
+            };
+            writeln!(
+                dot,
+                "    a{} [shape=ellipse, style={}, label=\"{}\\n{:?}\\n{:?}/{:?}, {}\"];",
+                idx,
+                if a.sampled { "solid" } else { "dotted" },
+                a.name,
+                a.format,
+                a.load,
+                a.store,
+                lifetime
+            )
+            .unwrap();
+
+            for (pass, usage) in self.usages(AttachmentId(idx)) {
+                let edge = match usage {
+                    Usage::Input => format!("a{} -> p{} [label=input]", idx, pass),
+                    Usage::Color => format!("p{} -> a{} [label=color]", pass, idx),
+                    Usage::DepthStencil => format!("p{} -> a{} [label=depth]", pass, idx),
+                };
+                writeln!(dot, "    {};", edge).unwrap();
+            }
+        }
+
+        for (src, dst) in self.dependencies() {
+            writeln!(dot, "    p{} -> p{} [style=dashed];", src.0, dst.0).unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Exports the graph as JSON object with `attachments` (including their layouts
+    /// and lifetimes), `passes` (with names of used and preserved attachments) and
+    /// `dependencies` (pairs of pass indices).
+    pub fn to_json(&self) -> String {
+        let name = |a: &AttachmentId| json_string(self.attachments[a.0].name);
+        let names = |ids: &[AttachmentId]| ids.iter().map(name).collect::<Vec<_>>().join(",");
+
+        let attachments = self
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(idx, a)| {
+                let lifetime = match self.lifetime(AttachmentId(idx)) {
+                    Some((first, last)) => format!("[{},{}]", first.0, last.0),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"name\":{},\"format\":\"{:?}\",\"load\":\"{:?}\",\"store\":\"{:?}\",\
+                     \"sampled\":{},\"initial_layout\":\"{:?}\",\"final_layout\":\"{:?}\",\
+                     \"lifetime\":{}}}",
+                    json_string(a.name),
+                    a.format,
+                    a.load,
+                    a.store,
+                    a.sampled,
+                    self.initial_layout(AttachmentId(idx)),
+                    self.final_layout(AttachmentId(idx)),
+                    lifetime
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let passes = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                format!(
+                    "{{\"name\":{},\"color\":[{}],\"depth_stencil\":{},\"input\":[{}],\
+                     \"preserve\":[{}]}}",
+                    json_string(p.name),
+                    names(&p.color),
+                    p.depth_stencil.as_ref().map_or("null".to_string(), name),
+                    names(&p.input),
+                    names(&self.preserved(PassId(idx)))
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let dependencies = self
+            .dependencies()
+            .iter()
+            .map(|(src, dst)| format!("[{},{}]", src.0, dst.0))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"attachments\":[{}],\"passes\":[{}],\"dependencies\":[{}]}}",
+            attachments.join(","),
+            passes.join(","),
+            dependencies.join(",")
+        )
+    }
+
+    /// Derives the description of Vulkan render pass from the graph.
+    pub fn render_pass_desc(&self) -> RenderPassDesc {
+        let attachments = self
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(idx, a)| AttachmentDesc {
+                format: a.format,
+                samples: 1,
+                load: a.load,
+                store: a.store,
+                stencil_load: a.load,
+                stencil_store: a.store,
+                initial_layout: self.initial_layout(AttachmentId(idx)),
+                final_layout: self.final_layout(AttachmentId(idx)),
+            })
+            .collect();
+
+        let layout = |a: &AttachmentId, u: Usage| (a.0, u.layout());
+        let subpasses = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| SubpassDesc {
+                color_attachments: p.color.iter().map(|a| layout(a, Usage::Color)).collect(),
+                depth_stencil: p.depth_stencil.map(|a| layout(&a, Usage::DepthStencil)),
+                input_attachments: p.input.iter().map(|a| layout(a, Usage::Input)).collect(),
+                resolve_attachments: vec![],
+                preserve_attachments: self.preserved(PassId(idx)).iter().map(|a| a.0).collect(),
+            })
+            .collect();
+
+        let dependencies = self
+            .dependencies()
+            .into_iter()
+            .map(|(src, dst)| SubpassDependencyDesc {
+                source_subpass: src.0,
+                destination_subpass: dst.0,
+                source_stages: PipelineStages {
+                    all_graphics: true,
+                    ..PipelineStages::none()
+                },
+                destination_stages: PipelineStages {
+                    all_graphics: true,
+                    ..PipelineStages::none()
+                },
+                source_access: AccessFlags::all(),
+                destination_access: AccessFlags::all(),
+                by_region: true,
+            })
+            .collect();
+
+        RenderPassDesc::new(attachments, subpasses, dependencies)
+    }
+
+    /// Creates the Vulkan render pass described by this graph.
+    pub fn create_render_pass(
+        &self,
+        device: Arc<Device>,
+    ) -> Result<Arc<RenderPass>, RenderPassCreationError> {
+        RenderPass::new(device, self.render_pass_desc()).map(Arc::new)
+    }
+
+    /// Returns the subpass of `render_pass` (created from this graph) that corresponds
+    /// to the specified `pass`.
+    pub fn subpass(&self, render_pass: &Arc<RenderPass>, pass: PassId) -> Subpass {
+        Subpass::from(render_pass.clone(), pass.0 as u32).expect("pass is not in the render pass")
+    }
+
+    /// Creates images with specified dimensions for all attachments in the graph.
+    pub fn create_images(
+        &self,
+        device: Arc<Device>,
+        dims: [u32; 2],
+    ) -> Result<GraphImages, ImageCreationError> {
+        let mut images = Vec::with_capacity(self.attachments.len());
+
+        for (idx, attachment) in self.attachments.iter().enumerate() {
+            let mut usage = ImageUsage {
+                sampled: attachment.sampled,
+                // images only used during the render pass do not need backing memory on tilers
+                transient_attachment: !attachment.sampled,
+                // sampled images can be copied to the cpu (eg. by the headless renderer)
+                transfer_source: attachment.sampled,
+                ..ImageUsage::none()
+            };
+            for (_, u) in self.usages(AttachmentId(idx)) {
+                match u {
+                    Usage::Color => usage.color_attachment = true,
+                    Usage::DepthStencil => usage.depth_stencil_attachment = true,
+                    Usage::Input => usage.input_attachment = true,
+                }
+            }
+
+            let image =
+                AttachmentImage::with_usage(device.clone(), dims, attachment.format, usage)?;
+            images.push(ImageView::new(image).expect("cannot create image view"));
+        }
+
+        Ok(GraphImages(images))
+    }
+}
+
+/// Returns the `value` as quoted and escaped JSON string.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::graph::{AttachmentId, PassId, RenderGraph};
+    use vulkano::format::Format;
+    use vulkano::image::ImageLayout;
+    use vulkano::render_pass::{LoadOp, StoreOp};
+
+    /// Simplified deferred renderer: geometry, lighting, tonemap.
+    fn deferred() -> (RenderGraph, [AttachmentId; 4]) {
+        let mut graph = RenderGraph::new();
+        let gbuffer = graph.attachment(
+            "gbuffer",
+            Format::R8G8B8A8Unorm,
+            LoadOp::Clear,
+            StoreOp::DontCare,
+        );
+        let depth = graph.attachment("depth", Format::D32Sfloat, LoadOp::Clear, StoreOp::DontCare);
+        let hdr = graph.attachment(
+            "hdr",
+            Format::R16G16B16A16Sfloat,
+            LoadOp::Clear,
+            StoreOp::DontCare,
+        );
+        let ldr = graph.attachment(
+            "ldr",
+            Format::R8G8B8A8Unorm,
+            LoadOp::DontCare,
+            StoreOp::Store,
+        );
+        graph.sampled(ldr);
+
+        graph
+            .pass("geometry")
+            .color(gbuffer)
+            .depth_stencil(depth)
+            .add();
+        graph
+            .pass("lighting")
+            .color(hdr)
+            .input(gbuffer)
+            .input(depth)
+            .add();
+        graph.pass("sky").color(hdr).depth_stencil(depth).add();
+        graph.pass("tonemap").color(ldr).input(hdr).add();
+
+        (graph, [gbuffer, depth, hdr, ldr])
+    }
+
+    #[test]
+    fn layouts_follow_first_and_last_usage() {
+        let (graph, [gbuffer, depth, hdr, ldr]) = deferred();
+
+        assert_eq!(
+            graph.initial_layout(gbuffer),
+            ImageLayout::ColorAttachmentOptimal
+        );
+        assert_eq!(
+            graph.final_layout(gbuffer),
+            ImageLayout::ShaderReadOnlyOptimal
+        );
+        assert_eq!(
+            graph.initial_layout(depth),
+            ImageLayout::DepthStencilAttachmentOptimal
+        );
+        assert_eq!(
+            graph.final_layout(depth),
+            ImageLayout::DepthStencilAttachmentOptimal
+        );
+        assert_eq!(graph.final_layout(hdr), ImageLayout::ShaderReadOnlyOptimal);
+        assert_eq!(graph.final_layout(ldr), ImageLayout::ColorAttachmentOptimal);
+    }
+
+    #[test]
+    fn dependencies_between_writers_and_readers() {
+        let (graph, _) = deferred();
+
+        assert_eq!(
+            graph.dependencies(),
+            vec![
+                (PassId(0), PassId(1)), // gbuffer, depth
+                (PassId(1), PassId(2)), // hdr, depth read by lighting
+                (PassId(2), PassId(3)), // hdr
+            ]
+        );
+    }
+
+    #[test]
+    fn preserves_attachments_used_around_pass() {
+        let (graph, [_, depth, hdr, _]) = deferred();
+
+        assert!(graph.preserved(PassId(0)).is_empty());
+        assert!(graph.preserved(PassId(1)).is_empty());
+        assert!(graph.preserved(PassId(2)).is_empty());
+        assert!(graph.preserved(PassId(3)).is_empty());
+
+        let mut graph = graph;
+        let post = graph.pass("post").color(hdr).input(depth).add();
+        assert_eq!(graph.pass_name(post), "post");
+        assert_eq!(graph.preserved(PassId(3)), vec![depth]);
+    }
+
+    #[test]
+    fn exports_graph() {
+        let (graph, [gbuffer, _, hdr, _]) = deferred();
+        assert_eq!(graph.lifetime(gbuffer), Some((PassId(0), PassId(1))));
+        assert_eq!(graph.lifetime(hdr), Some((PassId(1), PassId(3))));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph render_graph {"));
+        assert!(dot.contains("p0 [shape=box, label=\"0. geometry\"];"));
+        assert!(dot.contains("a0 -> p1 [label=input];"));
+        assert!(dot.contains("p2 -> p3 [style=dashed];"));
+
+        let json = graph.to_json();
+        assert!(json.contains("\"dependencies\":[[0,1],[1,2],[2,3]]"));
+        assert!(json.contains(
+            "{\"name\":\"tonemap\",\"color\":[\"ldr\"],\"depth_stencil\":null,\
+             \"input\":[\"hdr\"],\"preserve\":[]}"
+        ));
+        assert!(json.contains("\"lifetime\":[1,3]"));
+    }
+}
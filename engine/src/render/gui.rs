@@ -0,0 +1,273 @@
+//! Integration of the [`egui`](https://docs.rs/egui) immediate-mode GUI.
+//!
+//! The game draws its windows into the [`EguiContext`] during its update. The
+//! tessellated meshes are handed to the [`GuiPainter`] which draws them in the
+//! render pass of the last post-processing effect on top of the final image
+//! (and the overlay) in the next frame.
+
+use crate::input::gui::GuiInput;
+use crate::render::descriptor_set_layout;
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::GuiVertex;
+use crate::render::vulkan::pipeline_cache;
+use egui::{ClippedMesh, CtxRef, Rgba, TextureId};
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor};
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::viewport::Scissor;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+use winit::window::Window;
+
+const FONT_TEXTURE_DESCRIPTOR_SET: usize = 0;
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../shaders/vs_gui.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_gui.glsl"
+        }
+    }
+
+    pub mod fragment {
+        const X: &str = include_str!("../../shaders/fs_gui.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_gui.glsl"
+        }
+    }
+}
+
+/// Context the game draws GUI windows into. It is valid to use the context
+/// only during `Game::update`.
+pub struct EguiContext {
+    ctx: CtxRef,
+    start: Instant,
+}
+
+impl Default for EguiContext {
+    fn default() -> Self {
+        Self {
+            ctx: CtxRef::default(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl EguiContext {
+    /// Returns the `egui` context to draw windows and widgets with.
+    pub fn ctx(&self) -> &CtxRef {
+        &self.ctx
+    }
+
+    /// Starts a new GUI frame with the `input` collected since the last frame.
+    pub fn begin_frame(&mut self, input: &mut GuiInput, window: &Window) {
+        let raw_input = input.take_raw_input(
+            window.inner_size(),
+            window.scale_factor() as f32,
+            self.start.elapsed().as_secs_f64(),
+        );
+        self.ctx.begin_frame(raw_input);
+    }
+
+    /// Ends the GUI frame and returns meshes of everything drawn during it.
+    pub fn end_frame(&mut self) -> Vec<ClippedMesh> {
+        let (_, shapes) = self.ctx.end_frame();
+        self.ctx.tessellate(shapes)
+    }
+}
+
+/// Draws meshes produced by `egui`.
+pub struct GuiPainter {
+    queue: Arc<Queue>,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    sampler: Arc<Sampler>,
+    vertex_pool: CpuBufferPool<GuiVertex>,
+    index_pool: CpuBufferPool<u32>,
+    /// Version of the uploaded font texture and its descriptor set.
+    font_texture: Option<(u64, Arc<dyn DescriptorSet + Send + Sync>)>,
+    meshes: Vec<ClippedMesh>,
+    pixels_per_point: f32,
+}
+
+impl GuiPainter {
+    /// Creates the painter drawing into the first subpass of the `render_pass`
+    /// (`PostPasses::present` that writes into the swapchain image).
+    pub fn new(queue: Arc<Queue>, render_pass: Arc<RenderPass>) -> Self {
+        let device = queue.device().clone();
+        let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_gui");
+        let cached_fs = CachedShader::load(device.clone(), "fs_gui");
+
+        // egui outputs colors with premultiplied alpha
+        let blend = AttachmentBlend {
+            color_source: BlendFactor::One,
+            alpha_source: BlendFactor::One,
+            ..AttachmentBlend::alpha_blending()
+        };
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<GuiVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_scissors_dynamic(1)
+                .depth_stencil(DepthStencil::disabled())
+                .blend_collective(blend)
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline for gui"),
+        );
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .expect("cannot create sampler for gui");
+
+        Self {
+            queue,
+            pipeline: pipeline as Arc<_>,
+            sampler,
+            vertex_pool: CpuBufferPool::new(device.clone(), BufferUsage::vertex_buffer()),
+            index_pool: CpuBufferPool::new(device, BufferUsage::index_buffer()),
+            font_texture: None,
+            meshes: vec![],
+            pixels_per_point: 1.0,
+        }
+    }
+
+    /// Sets the meshes that will be drawn in the next frame and uploads the
+    /// font texture of the `ctx` if it has changed.
+    pub fn set_meshes(&mut self, ctx: &CtxRef, meshes: Vec<ClippedMesh>) {
+        let texture = ctx.texture();
+
+        if self.font_texture.as_ref().map(|x| x.0) != Some(texture.version) {
+            let (image, future) = ImmutableImage::from_iter(
+                texture.pixels.iter().cloned(),
+                ImageDimensions::Dim2d {
+                    width: texture.width as u32,
+                    height: texture.height as u32,
+                    array_layers: 1,
+                },
+                MipmapsCount::One,
+                Format::R8Unorm,
+                self.queue.clone(),
+            )
+            .expect("cannot create gui font texture");
+
+            // the font texture changes only when new glyphs are rasterized
+            future
+                .then_signal_fence_and_flush()
+                .and_then(|f| f.wait(None))
+                .expect("cannot upload gui font texture");
+
+            let layout = descriptor_set_layout(self.pipeline.layout(), FONT_TEXTURE_DESCRIPTOR_SET);
+            let descriptor_set = PersistentDescriptorSet::start(layout)
+                .add_sampled_image(ImageView::new(image).unwrap(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap();
+
+            self.font_texture = Some((texture.version, Arc::new(descriptor_set)));
+        }
+
+        self.meshes = meshes;
+        self.pixels_per_point = ctx.pixels_per_point();
+    }
+
+    /// Records drawing of the meshes set since the last call into the current
+    /// subpass. The `dims` are dimensions of the framebuffer in pixels.
+    pub fn draw(
+        &mut self,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        dynamic_state: &DynamicState,
+        dims: [f32; 2],
+    ) {
+        let font_texture = match &self.font_texture {
+            Some((_, ds)) => ds.clone(),
+            None => return,
+        };
+        let ppp = self.pixels_per_point;
+
+        for ClippedMesh(clip, mesh) in self.meshes.drain(..) {
+            // user textures are not supported yet
+            if mesh.is_empty() || mesh.texture_id != TextureId::Egui {
+                continue;
+            }
+
+            let min = [
+                (clip.min.x * ppp).round().clamp(0.0, dims[0]),
+                (clip.min.y * ppp).round().clamp(0.0, dims[1]),
+            ];
+            let max = [
+                (clip.max.x * ppp).round().clamp(min[0], dims[0]),
+                (clip.max.y * ppp).round().clamp(min[1], dims[1]),
+            ];
+            if max[0] <= min[0] || max[1] <= min[1] {
+                continue;
+            }
+
+            let dynamic_state = DynamicState {
+                scissors: Some(vec![Scissor {
+                    origin: [min[0] as i32, min[1] as i32],
+                    dimensions: [(max[0] - min[0]) as u32, (max[1] - min[1]) as u32],
+                }]),
+                ..dynamic_state.clone()
+            };
+
+            let vertices = mesh
+                .vertices
+                .iter()
+                .map(|v| GuiVertex {
+                    position: [v.pos.x, v.pos.y],
+                    uv: [v.uv.x, v.uv.y],
+                    color: Rgba::from(v.color).to_array(),
+                })
+                .collect::<Vec<_>>();
+            let vertex_buffer = self
+                .vertex_pool
+                .chunk(vertices)
+                .expect("cannot create vertex buffer for gui");
+            let index_buffer = self
+                .index_pool
+                .chunk(mesh.indices)
+                .expect("cannot create index buffer for gui");
+
+            cmd.draw_indexed(
+                self.pipeline.clone(),
+                &dynamic_state,
+                vec![Arc::new(vertex_buffer)],
+                index_buffer,
+                font_texture.clone(),
+                shaders::vertex::ty::PushConstants {
+                    screen_size: [dims[0] / ppp, dims[1] / ppp],
+                },
+            )
+            .expect("cannot draw gui");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Rendering without a window (eg. automated image tests and thumbnails).
+//!
+//! [`HeadlessRenderer`](struct.HeadlessRenderer.html) renders frames with the
+//! same render path as the window, but the final image is drawn into an
+//! offscreen image instead of a swapchain image, so no surface or swapchain is
+//! created (see `HeadlessVulkanState`). Each frame is waited for before the next
+//! one is recorded, so rendered images can be copied to the CPU right away and
+//! saved as PNG (the tonemapped image) or EXR (the HDR buffer before tonemapping).
+
+use crate::render::pbr::PBRDeffered;
+use crate::render::vulkan::HeadlessVulkanState;
+use crate::render::Frame;
+use crate::resources::swap;
+use crate::{GameState, RendererConfiguration};
+use bf::image::f16_to_f32;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageAccess, ImageUsage};
+use vulkano::render_pass::FramebufferAbstract;
+use vulkano::sync::GpuFuture;
+
+/// Format of the offscreen image (the same channel order as RGBA PNG files).
+const OUTPUT_FORMAT: Format = Format::R8G8B8A8Srgb;
+
+/// Image with linear floating point colors (the HDR buffer).
+pub type HdrImage = ImageBuffer<Rgba<f32>, Vec<f32>>;
+
+/// Renders frames into an offscreen image with the resolution of the configuration.
+pub struct HeadlessRenderer {
+    device: Arc<Device>,
+    graphical_queue: Arc<Queue>,
+    /// Image the final (tonemapped) frame is drawn into.
+    image: Arc<AttachmentImage>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    /// Number of rendered frames.
+    frames: u64,
+    pub render_path: PBRDeffered,
+}
+
+impl HeadlessRenderer {
+    /// Creates the render path and the offscreen image with the resolution and
+    /// settings of the render targets from `conf`.
+    pub fn new(vulkan: &HeadlessVulkanState, conf: &RendererConfiguration) -> Result<Self, String> {
+        let device = vulkan.device();
+        let dimensions = [conf.resolution[0] as u32, conf.resolution[1] as u32];
+
+        let image = AttachmentImage::with_usage(
+            device.clone(),
+            dimensions,
+            OUTPUT_FORMAT,
+            ImageUsage {
+                color_attachment: true,
+                transfer_source: true,
+                ..ImageUsage::none()
+            },
+        )
+        .map_err(|e| format!("cannot create offscreen image: {:?}", e))?;
+
+        let mut render_path = PBRDeffered::new(
+            vulkan.graphical_queue(),
+            device.clone(),
+            OUTPUT_FORMAT,
+            dimensions,
+            conf.bloom,
+            conf.precision,
+            conf.fxaa_quality,
+        );
+        render_path.dimensions_changed(dimensions);
+
+        let view = ImageView::new(image.clone())
+            .map_err(|e| format!("cannot create offscreen image view: {:?}", e))?;
+        let framebuffer = render_path
+            .create_framebuffer(view)
+            .map_err(|e| format!("cannot create framebuffer: {}", e))?;
+
+        Ok(Self {
+            device,
+            graphical_queue: vulkan.graphical_queue(),
+            image,
+            framebuffer,
+            frames: 0,
+            render_path,
+        })
+    }
+
+    /// Returns the resolution of rendered images.
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.image.dimensions().width_height()
+    }
+
+    /// Returns the number of frames rendered so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Renders a single frame of the `game_state` and waits until it is finished.
+    /// The frame waits for `uploads` (see `UploadScheduler::take_submitted`).
+    pub fn render_frame(
+        &mut self,
+        game_state: &GameState,
+        uploads: Option<Box<dyn GpuFuture + Send>>,
+    ) -> Result<(), String> {
+        // previous frames are finished, so their retired resources can be dropped
+        swap::next_frame(0);
+
+        let mut frame = Frame {
+            render_path: &mut self.render_path,
+            game_state,
+            framebuffer: self.framebuffer.clone(),
+            builder: Some(
+                AutoCommandBufferBuilder::primary(
+                    self.device.clone(),
+                    self.graphical_queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                .map_err(|e| format!("cannot create command buffer: {:?}", e))?,
+            ),
+        };
+        let primary_cb = frame.build();
+
+        let future = vulkano::sync::now(self.device.clone()).boxed();
+        let future = match uploads {
+            Some(uploads) => future.join(uploads).boxed(),
+            None => future,
+        };
+        future
+            .then_execute(self.graphical_queue.clone(), primary_cb)
+            .map_err(|e| format!("cannot execute frame: {:?}", e))?
+            .then_signal_fence_and_flush()
+            .map_err(|e| format!("cannot flush frame: {:?}", e))?
+            .wait(None)
+            .map_err(|e| format!("cannot wait for frame: {:?}", e))?;
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Renders `frames` frames of the `game_state`. The first frame waits for
+    /// the `uploads`.
+    pub fn render_frames(
+        &mut self,
+        game_state: &GameState,
+        uploads: Option<Box<dyn GpuFuture + Send>>,
+        frames: usize,
+    ) -> Result<(), String> {
+        let mut uploads = uploads;
+        for _ in 0..frames {
+            self.render_frame(game_state, uploads.take())?;
+        }
+        Ok(())
+    }
+
+    /// Copies the last rendered (tonemapped) frame to the CPU.
+    pub fn capture(&self) -> Result<RgbaImage, String> {
+        let [width, height] = self.dimensions();
+        let data = self.read_image(self.image.clone(), (width * height * 4) as usize)?;
+        RgbaImage::from_raw(width, height, data).ok_or_else(|| "invalid image size".to_string())
+    }
+
+    /// Copies the HDR buffer of the last rendered frame (linear colors before
+    /// the post-processing) to the CPU.
+    pub fn capture_hdr(&self) -> Result<HdrImage, String> {
+        let image = self.render_path.buffers.hdr_buffer.image().clone();
+        let [width, height] = image.dimensions().width_height();
+        let pixels = (width * height) as usize;
+
+        let floats = match image.format() {
+            Format::R16G16B16A16Sfloat => self
+                .read_image(image, pixels * 8)?
+                .chunks_exact(2)
+                .map(|x| f16_to_f32(u16::from_le_bytes([x[0], x[1]])))
+                .collect(),
+            Format::R32G32B32A32Sfloat => self
+                .read_image(image, pixels * 16)?
+                .chunks_exact(4)
+                .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+                .collect(),
+            f => return Err(format!("unsupported format of the hdr buffer {:?}", f)),
+        };
+
+        HdrImage::from_raw(width, height, floats).ok_or_else(|| "invalid image size".to_string())
+    }
+
+    /// Saves the last rendered frame to `path`. Files with `exr` extension
+    /// contain the HDR buffer, other files the tonemapped image in the format
+    /// determined by the extension (eg. PNG).
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let is_exr = path
+            .extension()
+            .map_or(false, |x| x.eq_ignore_ascii_case("exr"));
+
+        if is_exr {
+            save_exr(&self.capture_hdr()?, path)
+        } else {
+            self.capture()?
+                .save(path)
+                .map_err(|e| format!("cannot save {:?}: {}", path, e))
+        }
+    }
+
+    /// Copies the whole `image` (`size` bytes) into a CPU accessible buffer and
+    /// waits for the copy.
+    fn read_image<I>(&self, image: I, size: usize) -> Result<Vec<u8>, String>
+    where
+        I: ImageAccess + Send + Sync + 'static,
+    {
+        let buffer = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::transfer_destination(),
+            true,
+            (0..size).map(|_| 0u8),
+        )
+        .map_err(|e| format!("cannot create buffer: {:?}", e))?;
+
+        let mut b = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.graphical_queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(|e| format!("cannot create command buffer: {:?}", e))?;
+        b.copy_image_to_buffer(image, buffer.clone())
+            .map_err(|e| format!("cannot record copy: {:?}", e))?;
+        let cb = b
+            .build()
+            .map_err(|e| format!("cannot build command buffer: {:?}", e))?;
+
+        vulkano::sync::now(self.device.clone())
+            .then_execute(self.graphical_queue.clone(), cb)
+            .map_err(|e| format!("cannot execute copy: {:?}", e))?
+            .then_signal_fence_and_flush()
+            .map_err(|e| format!("cannot flush copy: {:?}", e))?
+            .wait(None)
+            .map_err(|e| format!("cannot wait for copy: {:?}", e))?;
+
+        let data = buffer
+            .read()
+            .map_err(|e| format!("cannot read buffer: {:?}", e))?;
+        Ok(data.to_vec())
+    }
+}
+
+/// Saves the `image` as an OpenEXR file with 32-bit float channels.
+pub fn save_exr(image: &HdrImage, path: &Path) -> Result<(), String> {
+    exr::prelude::write_rgba_file(
+        path,
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| {
+            let [r, g, b, a] = image.get_pixel(x as u32, y as u32).0;
+            (r, g, b, a)
+        },
+    )
+    .map_err(|e| format!("cannot save {:?}: {}", path, e))
+}
+
+/// Renders `conf.headless_frames` frames of the `game_state` (the first one
+/// waits for the `uploads`) and saves the last one to `conf.headless_output`.
+pub fn render_headless(
+    renderer: &mut HeadlessRenderer,
+    conf: &RendererConfiguration,
+    game_state: &GameState,
+    uploads: Option<Box<dyn GpuFuture + Send>>,
+) -> Result<(), String> {
+    renderer.render_frames(game_state, uploads, conf.headless_frames.max(1))?;
+    renderer.save(&conf.headless_output)
+}
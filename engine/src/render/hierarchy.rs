@@ -0,0 +1,205 @@
+//! Parent / child relationships between transforms (scene graph).
+//!
+//! Each node has a local [`Transform`](../transform/struct.Transform.html) relative
+//! to its parent. World matrices are computed in double precision by
+//! [`Hierarchy::update`](struct.Hierarchy.html#method.update) only for nodes whose
+//! local transform (or a transform of any of their ancestors) changed.
+
+use crate::render::transform::Transform;
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+
+/// Handle of a single node in the [`Hierarchy`](struct.Hierarchy.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Errors that can happen when changing the structure of the hierarchy.
+#[derive(Debug, PartialEq)]
+pub enum HierarchyError {
+    /// The new parent is the node itself or one of its descendants.
+    WouldCreateCycle,
+}
+
+struct Node {
+    local: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f64>,
+    dirty: bool,
+}
+
+/// Tree of transforms. Nodes can't be removed, but they can be re-parented.
+#[derive(Default)]
+pub struct Hierarchy {
+    nodes: Vec<Node>,
+}
+
+impl Hierarchy {
+    /// Adds a new node with specified local transform as a child of `parent`
+    /// (or as a root when `parent` is `None`).
+    pub fn add(&mut self, local: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            local,
+            parent,
+            children: vec![],
+            world: Matrix4::identity(),
+            dirty: true,
+        });
+        if let Some(p) = parent {
+            self.nodes[p.0].children.push(id);
+        }
+        id
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    /// Returns the transform of the node relative to its parent.
+    pub fn local(&self, node: NodeId) -> &Transform {
+        &self.nodes[node.0].local
+    }
+
+    /// Changes the transform of the node relative to its parent. World matrices
+    /// of the node and its descendants are recomputed during the next `update`.
+    pub fn set_local(&mut self, node: NodeId, local: Transform) {
+        let n = &mut self.nodes[node.0];
+        n.local = local;
+        n.dirty = true;
+    }
+
+    /// Moves the node (with all its descendants) under a new parent. The local
+    /// transform is kept, so the world position of the node may change.
+    pub fn set_parent(
+        &mut self,
+        node: NodeId,
+        parent: Option<NodeId>,
+    ) -> Result<(), HierarchyError> {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a == node {
+                return Err(HierarchyError::WouldCreateCycle);
+            }
+            ancestor = self.nodes[a.0].parent;
+        }
+
+        if let Some(old) = self.nodes[node.0].parent {
+            self.nodes[old.0].children.retain(|x| *x != node);
+        }
+        if let Some(new) = parent {
+            self.nodes[new.0].children.push(node);
+        }
+
+        let n = &mut self.nodes[node.0];
+        n.parent = parent;
+        n.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the world matrix of the node computed by the last `update`.
+    pub fn world(&self, node: NodeId) -> Matrix4<f64> {
+        self.nodes[node.0].world
+    }
+
+    /// Moves all root nodes by `offset` (see `GameState::shift_origin`).
+    pub fn shift_roots(&mut self, offset: Vector3<f32>) {
+        for node in self.nodes.iter_mut().filter(|x| x.parent.is_none()) {
+            node.local.translate(-offset);
+            node.dirty = true;
+        }
+    }
+
+    /// Recomputes world matrices of all changed nodes and their descendants.
+    /// Returns the number of recomputed nodes.
+    pub fn update(&mut self) -> usize {
+        let mut stack = (0..self.nodes.len())
+            .filter(|x| self.nodes[*x].parent.is_none())
+            .map(|x| (x, false))
+            .collect::<Vec<_>>();
+        let mut updated = 0;
+
+        while let Some((idx, parent_changed)) = stack.pop() {
+            let changed = parent_changed || self.nodes[idx].dirty;
+
+            if changed {
+                let parent = match self.nodes[idx].parent {
+                    Some(p) => self.nodes[p.0].world,
+                    None => Matrix4::identity(),
+                };
+                let node = &mut self.nodes[idx];
+                node.world = parent * node.local.matrix_f64();
+                node.dirty = false;
+                updated += 1;
+            }
+
+            stack.extend(self.nodes[idx].children.iter().map(|x| (x.0, changed)));
+        }
+
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::hierarchy::{Hierarchy, HierarchyError};
+    use crate::render::transform::Transform;
+    use cgmath::{vec3, vec4, Vector4};
+
+    fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform::from_position(vec3(x, y, z))
+    }
+
+    fn origin() -> Vector4<f64> {
+        vec4(0.0, 0.0, 0.0, 1.0)
+    }
+
+    #[test]
+    fn combines_parent_transforms() {
+        let mut h = Hierarchy::default();
+        let root = h.add(at(1.0, 0.0, 0.0), None);
+        let child = h.add(at(0.0, 2.0, 0.0), Some(root));
+        let grandchild = h.add(at(0.0, 0.0, 3.0), Some(child));
+
+        assert_eq!(h.update(), 3);
+        assert_eq!(h.world(grandchild) * origin(), vec4(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(h.children(root), [child]);
+    }
+
+    #[test]
+    fn recomputes_only_changed_subtrees() {
+        let mut h = Hierarchy::default();
+        let a = h.add(at(1.0, 0.0, 0.0), None);
+        let a_child = h.add(at(1.0, 0.0, 0.0), Some(a));
+        let b = h.add(at(5.0, 0.0, 0.0), None);
+        h.update();
+        assert_eq!(h.update(), 0);
+
+        h.set_local(a, at(2.0, 0.0, 0.0));
+        assert_eq!(h.update(), 2);
+        assert_eq!(h.world(a_child) * origin(), vec4(3.0, 0.0, 0.0, 1.0));
+        assert_eq!(h.world(b) * origin(), vec4(5.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reparents_nodes() {
+        let mut h = Hierarchy::default();
+        let a = h.add(at(1.0, 0.0, 0.0), None);
+        let b = h.add(at(0.0, 1.0, 0.0), None);
+        let child = h.add(at(0.0, 0.0, 1.0), Some(a));
+
+        assert_eq!(
+            h.set_parent(a, Some(child)),
+            Err(HierarchyError::WouldCreateCycle)
+        );
+        assert_eq!(h.set_parent(child, Some(b)), Ok(()));
+        h.update();
+
+        assert_eq!(h.parent(child), Some(b));
+        assert!(h.children(a).is_empty());
+        assert_eq!(h.world(child) * origin(), vec4(0.0, 1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,274 @@
+//! Luminance histogram of the HDR buffer read back to the CPU.
+//!
+//! A compute shader counts pixels of the HDR buffer into bins by their exposure
+//! value (EV, stops relative to middle gray). Counts of each frame are written
+//! into one of a few CPU accessible buffers, which is read when it is about to
+//! be used again. By then the frame that wrote it has finished, so reading the
+//! histogram never waits for the GPU; the displayed histogram is a few frames old.
+
+use crate::render::descriptor_set_layout;
+use crate::render::vulkan::pipeline_cache;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Device;
+use vulkano::image::view::ImageView;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::sampler::Sampler;
+
+pub mod shaders {
+    pub mod compute {
+        const X: &str = include_str!("../../shaders/cs_luminance_histogram.glsl");
+        vulkano_shaders::shader! {
+            ty: "compute",
+            path: "shaders/cs_luminance_histogram.glsl"
+        }
+    }
+}
+
+/// Number of bins of the histogram. Must match `BINS` in the compute shader.
+pub const HISTOGRAM_BINS: usize = 64;
+
+/// Exposure value of the lower bound of the first bin. Darker pixels are
+/// counted into the first bin.
+pub const MIN_EV: f32 = -10.0;
+
+/// Exposure value of the upper bound of the last bin. Brighter pixels are
+/// counted into the last bin.
+pub const MAX_EV: f32 = 10.0;
+
+/// Number of buffers the histogram is written into in turns.
+const READBACK_BUFFERS: usize = 3;
+
+const HISTOGRAM_DESCRIPTOR_SET: usize = 0;
+
+/// Numbers of pixels in bins of exposure values between [`MIN_EV`](constant.MIN_EV.html)
+/// and [`MAX_EV`](constant.MAX_EV.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub bins: [u32; HISTOGRAM_BINS],
+}
+
+impl Histogram {
+    /// Returns the index of the bin that counts pixels with exposure value `ev`
+    /// (the same way the compute shader does).
+    pub fn bin(ev: f32) -> usize {
+        let t = (ev - MIN_EV) / (MAX_EV - MIN_EV);
+        (t * HISTOGRAM_BINS as f32).clamp(0.0, HISTOGRAM_BINS as f32 - 1.0) as usize
+    }
+
+    /// Returns the exposure value in the middle of the bin with `index`.
+    pub fn bin_ev(index: usize) -> f32 {
+        MIN_EV + (index as f32 + 0.5) * (MAX_EV - MIN_EV) / HISTOGRAM_BINS as f32
+    }
+
+    /// Returns the number of counted pixels.
+    pub fn total(&self) -> u64 {
+        self.bins.iter().map(|x| *x as u64).sum()
+    }
+
+    /// Returns the average exposure value of the pixels or `None` if the
+    /// histogram is empty.
+    pub fn average_ev(&self) -> Option<f32> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+
+        let sum: f64 = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(i, count)| Self::bin_ev(i) as f64 * *count as f64)
+            .sum();
+        Some((sum / total as f64) as f32)
+    }
+}
+
+/// Returns the false color of the exposure zone of the exposure value `ev`.
+/// Must match `exposure_zone` in `fs_debug_view.glsl`.
+pub fn zone_color(ev: f32) -> [f32; 4] {
+    match ev {
+        x if x < -6.0 => [0.4, 0.0, 0.6, 1.0],
+        x if x < -3.0 => [0.0, 0.3, 1.0, 1.0],
+        x if x < -0.5 => [0.3, 0.3, 0.3, 1.0],
+        x if x < 0.5 => [0.2, 0.8, 0.2, 1.0],
+        x if x < 3.0 => [0.7, 0.7, 0.7, 1.0],
+        x if x < 6.0 => [1.0, 0.8, 0.0, 1.0],
+        _ => [1.0, 0.0, 0.0, 1.0],
+    }
+}
+
+struct Readback {
+    buffer: Arc<CpuAccessibleBuffer<[u32; HISTOGRAM_BINS]>>,
+    descriptor_set: Arc<dyn DescriptorSet + Send + Sync>,
+    /// Whether a frame wrote the histogram into the buffer.
+    written: bool,
+}
+
+/// Computes the luminance histogram of the HDR buffer and reads it back.
+pub struct LuminanceHistogram {
+    pipeline: Arc<ComputePipeline>,
+    sampler: Arc<Sampler>,
+    readbacks: Vec<Readback>,
+    next: usize,
+    hdr_dims: [u32; 2],
+    latest: Option<Histogram>,
+}
+
+impl LuminanceHistogram {
+    pub fn new(
+        device: Arc<Device>,
+        hdr_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+        dims: [u32; 2],
+    ) -> Self {
+        // compute shaders are not replaced by the shader cache
+        let cs = shaders::compute::Shader::load(device.clone()).unwrap();
+        let pipeline = Arc::new(
+            ComputePipeline::new(
+                device.clone(),
+                &cs.main_entry_point(),
+                &(),
+                Some(pipeline_cache(&device)),
+            )
+            .expect("cannot create histogram pipeline"),
+        );
+        let sampler = Sampler::simple_repeat_linear_no_mipmap(device.clone());
+
+        let mut histogram = Self {
+            pipeline,
+            sampler,
+            readbacks: vec![],
+            next: 0,
+            hdr_dims: dims,
+            latest: None,
+        };
+        histogram.readbacks = (0..READBACK_BUFFERS)
+            .map(|_| {
+                let buffer = CpuAccessibleBuffer::from_data(
+                    device.clone(),
+                    BufferUsage {
+                        storage_buffer: true,
+                        ..BufferUsage::none()
+                    },
+                    true,
+                    [0; HISTOGRAM_BINS],
+                )
+                .expect("cannot create histogram buffer");
+
+                Readback {
+                    descriptor_set: histogram.descriptor_set(hdr_buffer.clone(), buffer.clone()),
+                    buffer,
+                    written: false,
+                }
+            })
+            .collect();
+        histogram
+    }
+
+    fn descriptor_set(
+        &self,
+        hdr_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+        buffer: Arc<CpuAccessibleBuffer<[u32; HISTOGRAM_BINS]>>,
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        Arc::new(
+            PersistentDescriptorSet::start(descriptor_set_layout(
+                self.pipeline.layout(),
+                HISTOGRAM_DESCRIPTOR_SET,
+            ))
+            .add_sampled_image(hdr_buffer, self.sampler.clone())
+            .unwrap()
+            .add_buffer(buffer)
+            .unwrap()
+            .build()
+            .unwrap(),
+        )
+    }
+
+    /// Recreates descriptor sets for the new HDR buffer.
+    pub fn dimensions_changed(
+        &mut self,
+        hdr_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+        dims: [u32; 2],
+    ) {
+        self.hdr_dims = dims;
+        for i in 0..self.readbacks.len() {
+            let buffer = self.readbacks[i].buffer.clone();
+            self.readbacks[i].descriptor_set = self.descriptor_set(hdr_buffer.clone(), buffer);
+        }
+    }
+
+    /// Returns the most recent histogram that was read back.
+    pub fn latest(&self) -> Option<&Histogram> {
+        self.latest.as_ref()
+    }
+
+    /// Reads back the histogram of an older frame from the next buffer and
+    /// records computation of the histogram of this frame into it. Nothing is
+    /// recorded when the buffer is still used by the GPU. Must be called outside
+    /// of a render pass after the HDR buffer is rendered.
+    pub fn record(&mut self, cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let readback = &mut self.readbacks[self.next];
+
+        match readback.buffer.write() {
+            Ok(mut bins) => {
+                if readback.written {
+                    self.latest = Some(Histogram { bins: *bins });
+                }
+                *bins = [0; HISTOGRAM_BINS];
+            }
+            Err(_) => return,
+        }
+
+        let [width, height] = self.hdr_dims;
+        cmd.dispatch(
+            [(width + 15) / 16, (height + 15) / 16, 1],
+            self.pipeline.clone(),
+            readback.descriptor_set.clone(),
+            shaders::compute::ty::PushConstants {
+                resolution: self.hdr_dims,
+                min_ev: MIN_EV,
+                max_ev: MAX_EV,
+            },
+        )
+        .expect("cannot dispatch luminance histogram");
+
+        readback.written = true;
+        self.next = (self.next + 1) % self.readbacks.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::histogram::{zone_color, Histogram, HISTOGRAM_BINS, MAX_EV, MIN_EV};
+
+    #[test]
+    fn exposure_values_map_to_bins() {
+        assert_eq!(Histogram::bin(MIN_EV - 5.0), 0);
+        assert_eq!(Histogram::bin(MAX_EV + 5.0), HISTOGRAM_BINS - 1);
+        for i in 0..HISTOGRAM_BINS {
+            assert_eq!(Histogram::bin(Histogram::bin_ev(i)), i);
+        }
+    }
+
+    #[test]
+    fn computes_average_exposure() {
+        let mut histogram = Histogram {
+            bins: [0; HISTOGRAM_BINS],
+        };
+        assert_eq!(histogram.average_ev(), None);
+
+        histogram.bins[Histogram::bin(-2.0)] = 1;
+        histogram.bins[Histogram::bin(2.0)] = 1;
+        assert_eq!(histogram.total(), 2);
+        assert!(histogram.average_ev().unwrap().abs() < 1e-4);
+    }
+
+    #[test]
+    fn middle_gray_is_green() {
+        assert_eq!(zone_color(0.0), [0.2, 0.8, 0.2, 1.0]);
+        assert_ne!(zone_color(-8.0), zone_color(8.0));
+    }
+}
@@ -5,8 +5,10 @@
 use crate::render::hosek::dataset::{DATASETS_RGB, DATASETS_RGB_RAD};
 use crate::render::hosek::shaders::{get_or_load_fragment_shader, get_or_load_vertex_shader};
 use crate::render::pools::{UniformBufferPool, UniformBufferPoolError};
+use crate::render::shader_cache::CachedShader;
 use crate::render::ubo::FrameMatrixData;
 use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
 use crate::render::{descriptor_set_layout, FrameMatrixPool, FRAME_DATA_UBO_DESCRIPTOR_SET};
 use crate::resources::mesh::{create_icosphere, IndexedMesh};
 use cgmath::Vector3;
@@ -17,7 +19,7 @@ use vulkano::device::{Device, Queue};
 use vulkano::pipeline::depth_stencil::{Compare, DepthBounds, DepthStencil};
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::pipeline::GraphicsPipelineAbstract;
-use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::render_pass::Subpass;
 
 mod dataset;
 mod shaders;
@@ -43,18 +45,20 @@ pub struct HosekSky {
 impl HosekSky {
     /// Creates a new `Sky` with specified parameters. Provided pipeline should be the one
     /// that will be used to render the sky.
-    pub fn new(queue: Arc<Queue>, render_pass: Arc<RenderPass>, device: Arc<Device>) -> Self {
+    pub fn new(queue: Arc<Queue>, subpass: Subpass, device: Arc<Device>) -> Self {
         // todo: decide with to do with `expect` and with future
         let (mesh, _) = create_icosphere(queue, 0).expect("cannot generate icosphere for Sky");
 
         let sky_vs = get_or_load_vertex_shader(device.clone());
         let sky_fs = get_or_load_fragment_shader(device.clone());
+        let cached_vs = CachedShader::load(device.clone(), "sky_hosek_vert");
+        let cached_fs = CachedShader::load(device.clone(), "sky_hosek_frag");
 
         let pipeline = Arc::new(
             GraphicsPipeline::start()
                 .vertex_input_single_buffer::<PositionOnlyVertex>()
-                .vertex_shader(sky_vs.main_entry_point(), ())
-                .fragment_shader(sky_fs.main_entry_point(), ())
+                .vertex_shader(cached_vs.entry_point(sky_vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(sky_fs.main_entry_point()), ())
                 .triangle_list()
                 .viewports_dynamic_scissors_irrelevant(1)
                 .depth_stencil(DepthStencil {
@@ -64,7 +68,8 @@ impl HosekSky {
                     stencil_front: Default::default(),
                     stencil_back: Default::default(),
                 })
-                .render_pass(Subpass::from(render_pass.clone(), 2).unwrap())
+                .render_pass(subpass)
+                .build_with_cache(pipeline_cache(&device))
                 .build(device.clone())
                 .expect("cannot create aky pipeline"),
         );
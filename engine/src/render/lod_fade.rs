@@ -0,0 +1,157 @@
+//! Dithered (screen-door) cross-fades that hide popping when an object switches
+//! its level of detail, is spawned or is despawned.
+//!
+//! During a transition both levels are drawn. Each pixel has a dither threshold
+//! (from an ordered 4x4 pattern) and every draw keeps only the pixels whose
+//! threshold is in its range, so the ranges of the two levels never overlap and
+//! together cover the visibility of the object. Must match `dither_discard` in
+//! `inc_dither.glsl`.
+
+/// Number of frames one transition takes.
+pub const FADE_FRAMES: u32 = 8;
+
+/// Range of dither thresholds of fully visible objects.
+pub const VISIBLE: [f32; 2] = [0.0, 1.0];
+
+/// State of the transitions of one object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodFade {
+    lod: usize,
+    /// Level that is fading out and the progress of the transition to `lod`.
+    previous: Option<(usize, f32)>,
+    /// Visibility of the whole object (`0.0` to `1.0`).
+    visibility: f32,
+    /// Visibility the object is fading to.
+    target: f32,
+}
+
+impl Default for LodFade {
+    fn default() -> Self {
+        Self {
+            lod: 0,
+            previous: None,
+            visibility: 1.0,
+            target: 1.0,
+        }
+    }
+}
+
+impl LodFade {
+    /// Returns the level the object is transitioning to (or is drawn with).
+    pub fn lod(&self) -> usize {
+        self.lod
+    }
+
+    /// Makes the object invisible and starts fading it in.
+    pub fn fade_in(&mut self) {
+        self.visibility = 0.0;
+        self.target = 1.0;
+    }
+
+    /// Starts fading out the object.
+    pub fn fade_out(&mut self) {
+        self.target = 0.0;
+    }
+
+    /// Returns whether the object finished fading out.
+    pub fn is_faded_out(&self) -> bool {
+        self.target == 0.0 && self.visibility == 0.0
+    }
+
+    /// Advances the transitions by one frame in which the level `lod` was selected.
+    /// Changing the level during a transition back to the fading out level reverses
+    /// it, switching to a third level restarts it.
+    pub fn update(&mut self, lod: usize) {
+        let step = 1.0 / FADE_FRAMES as f32;
+
+        self.previous = match self.previous {
+            Some((previous, progress)) if previous == lod => Some((self.lod, 1.0 - progress)),
+            _ if lod != self.lod => Some((self.lod, 0.0)),
+            Some((previous, progress)) if progress + step < 1.0 => {
+                Some((previous, progress + step))
+            }
+            _ => None,
+        };
+        self.lod = lod;
+
+        self.visibility = if self.target > self.visibility {
+            (self.visibility + step).min(self.target)
+        } else {
+            (self.visibility - step).max(self.target)
+        };
+    }
+
+    /// Returns the levels that should be drawn in this frame with their ranges
+    /// of dither thresholds. Levels with empty ranges are not returned.
+    pub fn draws(&self) -> impl Iterator<Item = (usize, [f32; 2])> {
+        let progress = self.previous.map_or(1.0, |(_, x)| x);
+        let split = self.visibility * progress;
+
+        std::iter::once((self.lod, [0.0, split]))
+            .chain(
+                self.previous
+                    .map(|(previous, _)| (previous, [split, self.visibility])),
+            )
+            .filter(|(_, [from, to])| from < to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::lod_fade::{LodFade, FADE_FRAMES, VISIBLE};
+
+    #[test]
+    fn cross_fades_levels() {
+        let mut fade = LodFade::default();
+        fade.update(0);
+        assert_eq!(fade.draws().collect::<Vec<_>>(), vec![(0, VISIBLE)]);
+
+        fade.update(1);
+        assert_eq!(fade.draws().collect::<Vec<_>>(), vec![(0, VISIBLE)]);
+
+        fade.update(1);
+        let draws = fade.draws().collect::<Vec<_>>();
+        let split = 1.0 / FADE_FRAMES as f32;
+        assert_eq!(draws, vec![(1, [0.0, split]), (0, [split, 1.0])]);
+
+        for _ in 0..FADE_FRAMES {
+            fade.update(1);
+        }
+        assert_eq!(fade.draws().collect::<Vec<_>>(), vec![(1, VISIBLE)]);
+    }
+
+    #[test]
+    fn reverses_interrupted_transitions() {
+        let mut fade = LodFade::default();
+        fade.update(1);
+        fade.update(1);
+        fade.update(1);
+
+        // back to the first level, which is mostly visible
+        fade.update(0);
+        let draws = fade.draws().collect::<Vec<_>>();
+        assert_eq!(draws[0].0, 0);
+        assert_eq!(draws[0].1[1], 1.0 - 2.0 / FADE_FRAMES as f32);
+
+        // switching to another level starts from the current one
+        fade.update(2);
+        assert_eq!(fade.draws().collect::<Vec<_>>(), vec![(0, VISIBLE)]);
+    }
+
+    #[test]
+    fn fades_in_and_out() {
+        let mut fade = LodFade::default();
+        fade.fade_in();
+        assert_eq!(fade.draws().count(), 0);
+
+        fade.update(0);
+        let step = 1.0 / FADE_FRAMES as f32;
+        assert_eq!(fade.draws().collect::<Vec<_>>(), vec![(0, [0.0, step])]);
+
+        fade.fade_out();
+        assert!(!fade.is_faded_out());
+        fade.update(0);
+        assert!(fade.is_faded_out());
+        assert_eq!(fade.draws().count(), 0);
+    }
+}
@@ -3,14 +3,15 @@ use crate::render::mcguire13::shaders::{
     get_or_load_acc_fragment_shader, get_or_load_acc_vertex_shader,
     get_or_load_resolve_fragment_shader,
 };
+use crate::render::shader_cache::CachedShader;
 use crate::render::vertex::{NormalMappedVertex, PositionOnlyVertex};
+use crate::render::vulkan::pipeline_cache;
 use std::sync::Arc;
 use vulkano::descriptor_set::DescriptorSet;
 use vulkano::descriptor_set::PersistentDescriptorSet;
 use vulkano::device::Device;
-use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::image::AttachmentImage;
 use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
 use vulkano::pipeline::depth_stencil::{Compare, DepthBounds, DepthStencil};
 use vulkano::pipeline::GraphicsPipeline;
@@ -19,12 +20,9 @@ use vulkano::render_pass::Subpass;
 
 pub mod shaders;
 
-pub const ACCUMULATION_BUFFER_FORMAT: Format = Format::R16G16B16A16Sfloat;
-pub const REVEALAGE_BUFFER_FORMAT: Format = Format::R16Sfloat;
-
-// Integrate to you render pass
+// Integrate to you render pass. Buffers with four channel (accumulation) and
+// single channel (revealage) float format are attachments of the render pass.
 pub struct McGuire13 {
-    device: Arc<Device>,
     // buffers
     pub accumulation: Arc<ImageView<Arc<AttachmentImage>>>,
     pub revealage: Arc<ImageView<Arc<AttachmentImage>>>,
@@ -41,18 +39,18 @@ impl McGuire13 {
         device: Arc<Device>,
         accum_subpass: Subpass,
         resolve_subpass: Subpass,
-        dims: [u32; 2],
+        accumulation: Arc<ImageView<Arc<AttachmentImage>>>,
+        revealage: Arc<ImageView<Arc<AttachmentImage>>>,
     ) -> Self {
-        let accumulation = make_buffer(device.clone(), ACCUMULATION_BUFFER_FORMAT, dims);
-        let revealage = make_buffer(device.clone(), REVEALAGE_BUFFER_FORMAT, dims);
-
         let accum_vs = get_or_load_acc_vertex_shader(device.clone());
         let accum_fs = get_or_load_acc_fragment_shader(device.clone());
+        let cached_accum_vs = CachedShader::load(device.clone(), "vs_mcguire13_accumulation");
+        let cached_accum_fs = CachedShader::load(device.clone(), "fs_mcguire13_accumulation");
 
         let accumulation_pipeline = GraphicsPipeline::start()
             .vertex_input_single_buffer::<NormalMappedVertex>()
-            .vertex_shader(accum_vs.main_entry_point(), ())
-            .fragment_shader(accum_fs.main_entry_point(), ())
+            .vertex_shader(cached_accum_vs.entry_point(accum_vs.main_entry_point()), ())
+            .fragment_shader(cached_accum_fs.entry_point(accum_fs.main_entry_point()), ())
             .triangle_list()
             .blend_individual(vec![
                 AttachmentBlend {
@@ -93,17 +91,26 @@ impl McGuire13 {
                 stencil_back: Default::default(),
             })
             .render_pass(accum_subpass)
+            .build_with_cache(pipeline_cache(&device))
             .build(device.clone())
             .expect("cannot build transparency graphics pipeline");
 
         let resolve_vs =
             crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
         let resolve_fs = get_or_load_resolve_fragment_shader(device.clone());
+        let cached_resolve_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_resolve_fs = CachedShader::load(device.clone(), "fs_mcguire13_resolve");
 
         let resolve_pipeline = GraphicsPipeline::start()
             .vertex_input_single_buffer::<PositionOnlyVertex>()
-            .vertex_shader(resolve_vs.main_entry_point(), ())
-            .fragment_shader(resolve_fs.main_entry_point(), ())
+            .vertex_shader(
+                cached_resolve_vs.entry_point(resolve_vs.main_entry_point()),
+                (),
+            )
+            .fragment_shader(
+                cached_resolve_fs.entry_point(resolve_fs.main_entry_point()),
+                (),
+            )
             .triangle_list()
             .blend_collective(AttachmentBlend {
                 enabled: true,
@@ -120,6 +127,7 @@ impl McGuire13 {
             })
             .viewports_dynamic_scissors_irrelevant(1)
             .render_pass(resolve_subpass)
+            .build_with_cache(pipeline_cache(&device))
             .build(device.clone())
             .expect("cannot build transparency graphics pipeline");
 
@@ -133,7 +141,6 @@ impl McGuire13 {
                 .unwrap();
 
         Self {
-            device,
             accumulation,
             revealage,
             resolve_ds: Arc::new(resolve_ds),
@@ -142,13 +149,14 @@ impl McGuire13 {
         }
     }
 
-    pub fn dimensions_changed(&mut self, new_dimensions: [u32; 2]) {
-        self.accumulation = make_buffer(
-            self.device.clone(),
-            ACCUMULATION_BUFFER_FORMAT,
-            new_dimensions,
-        );
-        self.revealage = make_buffer(self.device.clone(), REVEALAGE_BUFFER_FORMAT, new_dimensions);
+    /// Replaces the buffers with new ones (with different dimensions).
+    pub fn dimensions_changed(
+        &mut self,
+        accumulation: Arc<ImageView<Arc<AttachmentImage>>>,
+        revealage: Arc<ImageView<Arc<AttachmentImage>>>,
+    ) {
+        self.accumulation = accumulation;
+        self.revealage = revealage;
 
         self.resolve_ds = Arc::new(
             PersistentDescriptorSet::start(descriptor_set_layout(
@@ -164,22 +172,3 @@ impl McGuire13 {
         );
     }
 }
-
-// creates a new float buffer for transparency
-fn make_buffer(
-    device: Arc<Device>,
-    format: Format,
-    dims: [u32; 2],
-) -> Arc<ImageView<Arc<AttachmentImage>>> {
-    let buffer = AttachmentImage::with_usage(
-        device.clone(),
-        dims,
-        format,
-        ImageUsage {
-            input_attachment: true,
-            ..ImageUsage::none()
-        },
-    )
-    .expect(&format!("cannot create buffer {}", stringify!($format)));
-    ImageView::new(buffer).expect("cannot create image view")
-}
@@ -0,0 +1,455 @@
+//! Objects & procedures related to rendering.
+
+use crate::render::debug_view::DebugView;
+use crate::render::draw_list::{group_instances, DrawList};
+use crate::render::lod_fade::VISIBLE;
+use crate::render::object_id::{geometry_push_constants, OBJECT_IDS};
+use crate::render::pbr::PBRDeffered;
+use crate::render::pools::UniformBufferPool;
+use crate::render::post::PostFrame;
+use crate::render::ubo::{pack_directional_lights, FrameMatrixData};
+use crate::render::vertex::InstanceData;
+use crate::resources::mesh::DynamicIndexedMesh;
+use crate::GameState;
+use cstr::cstr;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents,
+};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::device::{Device, Queue};
+use vulkano::image::SwapchainImage;
+use vulkano::pipeline::layout::PipelineLayout;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::render_pass::FramebufferAbstract;
+use winit::window::Window;
+
+// consts to descriptor set binding indices
+pub const FRAME_DATA_UBO_DESCRIPTOR_SET: usize = 0;
+pub const OBJECT_DATA_UBO_DESCRIPTOR_SET: usize = 2;
+pub const SUBPASS_UBO_DESCRIPTOR_SET: usize = 1;
+pub const LIGHTS_UBO_DESCRIPTOR_SET: usize = 2;
+pub const SPOT_LIGHTS_DESCRIPTOR_SET: usize = 3;
+
+/// Minimal number of objects with the same mesh, material and level of detail
+/// that are drawn with one instanced draw call instead of one call per object.
+pub const MIN_INSTANCES: usize = 2;
+
+pub mod bloom;
+pub mod debug_draw;
+pub mod debug_view;
+pub mod decals;
+pub mod draw_list;
+pub mod feedback;
+pub mod fxaa;
+pub mod grain;
+pub mod graph;
+pub mod gui;
+pub mod headless;
+pub mod hierarchy;
+pub mod histogram;
+pub mod hosek;
+pub mod lod_fade;
+pub mod mcguire13;
+pub mod object;
+pub mod object_id;
+pub mod objects;
+pub mod overlay;
+pub mod pbr;
+pub mod pools;
+pub mod post;
+pub mod precision;
+pub mod readback;
+pub mod renderer;
+pub mod samplers;
+pub mod shader_cache;
+mod shaders;
+pub mod spot_lights;
+pub mod terrain;
+pub mod tonemap;
+pub mod transform;
+pub mod ubo;
+pub mod variants;
+pub mod vertex;
+pub mod virtual_texture;
+pub mod vulkan;
+
+pub type FrameMatrixPool = UniformBufferPool<FrameMatrixData>;
+
+/// Series of operations related to lighting and shading.
+pub trait RenderPath {
+    fn new(graphical_queue: Arc<Queue>, device: Arc<Device>) -> Box<Self>;
+    /// Creates a *Framebuffer* with given `final_image` as final render target.
+    fn create_framebuffer(&self, final_image: Arc<SwapchainImage<Window>>);
+    /// Recreates internal state & buffers to support the new resolution.
+    fn recreate_buffers(&self, new_dimensions: [u32; 2]);
+}
+
+/// Helper function to retrieve `DescriptorSetLayout` from pipeline
+/// by specifying the index of the layout.
+///
+/// # Panics
+///
+/// This function panics if `index` is invalid set index for provided pipeline.
+///
+pub fn descriptor_set_layout(pipeline: &PipelineLayout, index: usize) -> Arc<DescriptorSetLayout> {
+    pipeline
+        .descriptor_set_layouts()
+        .get(index)
+        .expect("cannot get descriptor set layout")
+        .clone()
+}
+
+pub struct Frame<'r, 's> {
+    render_path: &'r mut PBRDeffered,
+    game_state: &'s GameState,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    builder: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+}
+
+impl<'r, 's> Frame<'r, 's> {
+    pub fn build(&mut self) -> PrimaryAutoCommandBuffer {
+        let dims = [
+            self.framebuffer.dimensions()[0] as f32,
+            self.framebuffer.dimensions()[1] as f32,
+        ];
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dims[0] as f32, dims[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+        let path = &mut self.render_path;
+        let state = self.game_state;
+        let camera = state.render_camera();
+
+        /* create FrameMatrixData (set=2) for this frame. */
+        let fmd = FrameMatrixData {
+            time: path
+                .fixed_time
+                .unwrap_or_else(|| state.start.elapsed().as_secs_f32()),
+            wind: state.wind.packed(),
+            ..FrameMatrixData::new(camera)
+        };
+        let draw_list = DrawList::new(
+            state
+                .objects
+                .iter()
+                .map(|x| x.visible.then(|| x.material.blend_mode())),
+        );
+        let frame_matrix_data = Arc::new(
+            path.buffers
+                .geometry_frame_matrix_pool
+                .next(fmd)
+                .expect("cannot take next buffer"),
+        );
+        let lights_frame_matrix_data = path
+            .buffers
+            .lights_frame_matrix_pool
+            .next(fmd)
+            .expect("cannot take next buffer");
+        let transparency_frame_matrix_data = Arc::new(
+            path.buffers
+                .transparency_frame_matrix_pool
+                .next(fmd)
+                .expect("cannot take next buffer"),
+        );
+
+        let mut b = self.builder.take().unwrap();
+
+        b.begin_render_pass(
+            path.buffers.main_framebuffer.clone(),
+            SubpassContents::Inline,
+            path.main_graph.graph.clear_values(),
+        )
+        .unwrap();
+
+        // 1.1. SUBPASS - Opaque Geometry
+        b.debug_marker_begin(cstr!("Geometry Pass"), [1.0, 0.0, 0.0, 1.0])
+            .unwrap();
+        // objects in the middle of a cross-fade are drawn with both levels
+        let geometry = draw_list
+            .geometry
+            .iter()
+            .flat_map(|idx| {
+                let index = *idx;
+                let x = &state.objects[index];
+                let mesh = x.mesh.get();
+                x.lod_fade
+                    .draws()
+                    .map(move |(lod, fade)| (index, x, mesh.clone(), lod, fade))
+            })
+            .collect::<Vec<_>>();
+        let instanced_pipeline = Arc::as_ptr(&path.buffers.geometry_pipeline) as *const ();
+        let batches = group_instances(geometry.iter().enumerate().map(
+            |(idx, (_, x, mesh, lod, _))| {
+                let key = (
+                    Arc::as_ptr(mesh),
+                    Arc::as_ptr(&x.material) as *const (),
+                    Arc::as_ptr(&x.pipeline) as *const (),
+                    *lod,
+                );
+                (idx, key)
+            },
+        ));
+
+        let mut draw_calls = 0;
+        for batch in batches {
+            let (_, x, mesh, lod, _) = &geometry[batch[0]];
+
+            // objects with custom pipelines can't be drawn by the instanced pipeline
+            // and objects with their own ids (see `object_id`) are drawn one by one
+            if batch.len() >= MIN_INSTANCES
+                && Arc::as_ptr(&x.pipeline) as *const () == instanced_pipeline
+                && !OBJECT_IDS
+            {
+                let instances = path
+                    .instance_buffer_pool
+                    .chunk(batch.iter().map(|idx| {
+                        let (_, object, _, _, fade) = &geometry[*idx];
+                        InstanceData::new(
+                            object.model_matrix(camera.position()),
+                            object.normal_matrix(),
+                            *fade,
+                        )
+                    }))
+                    .expect("cannot create instance buffer for this frame");
+                let instances = Arc::new(instances) as Arc<dyn BufferAccess + Send + Sync>;
+
+                // todo: get rid of this dispatch somehow
+                match &**mesh {
+                    DynamicIndexedMesh::U16(m) => b
+                        .draw_indexed(
+                            path.buffers.instanced_geometry_pipeline.clone(),
+                            &dynamic_state,
+                            vec![m.vertex_buffer().clone() as Arc<_>, instances],
+                            m.lod_index_buffer(*lod),
+                            (frame_matrix_data.clone(), x.material.descriptor_set()),
+                            (),
+                        )
+                        .expect("cannot DrawIndexed these instances"),
+                    DynamicIndexedMesh::U32(m) => b
+                        .draw_indexed(
+                            path.buffers.instanced_geometry_pipeline.clone(),
+                            &dynamic_state,
+                            vec![m.vertex_buffer().clone() as Arc<_>, instances],
+                            m.lod_index_buffer(*lod),
+                            (frame_matrix_data.clone(), x.material.descriptor_set()),
+                            (),
+                        )
+                        .expect("cannot DrawIndexed these instances"),
+                };
+                draw_calls += 1;
+                continue;
+            }
+
+            for (index, x, mesh, lod, fade) in batch.iter().map(|idx| &geometry[*idx]) {
+                draw_calls += 1;
+                let object_matrix_data = x
+                    .object_matrix_data(camera.position(), *fade)
+                    .expect("cannot create ObjectMatrixData for this frame");
+                let push_constants = geometry_push_constants(*index);
+
+                // todo: get rid of this dispatch somehow
+                match &**mesh {
+                    DynamicIndexedMesh::U16(m) => b
+                        .draw_indexed(
+                            x.pipeline.clone(),
+                            &dynamic_state,
+                            m.vertex_buffers(x.variant.foliage),
+                            m.lod_index_buffer(*lod),
+                            (
+                                frame_matrix_data.clone(),
+                                x.material.descriptor_set(),
+                                object_matrix_data,
+                            ),
+                            push_constants,
+                        )
+                        .expect("cannot DrawIndexed this mesh"),
+                    DynamicIndexedMesh::U32(m) => b
+                        .draw_indexed(
+                            x.pipeline.clone(),
+                            &dynamic_state,
+                            m.vertex_buffers(x.variant.foliage),
+                            m.lod_index_buffer(*lod),
+                            (
+                                frame_matrix_data.clone(),
+                                x.material.descriptor_set(),
+                                object_matrix_data,
+                            ),
+                            push_constants,
+                        )
+                        .expect("cannot DrawIndexed this mesh"),
+                };
+            }
+        }
+        b.next_subpass(SubpassContents::Inline).unwrap();
+        b.debug_marker_end().unwrap();
+
+        // 1.2. SUBPASS - Decals
+        b.debug_marker_begin(cstr!("Decals"), [1.0, 0.5, 0.0, 1.0])
+            .unwrap();
+        draw_calls += path
+            .decals
+            .draw(&state.decals, camera, &dynamic_state, dims, &mut b);
+        b.next_subpass(SubpassContents::Inline).unwrap();
+        b.debug_marker_end().unwrap();
+
+        // 1.3. SUBPASS - Lighting
+        b.debug_marker_begin(cstr!("Lighting Pass"), [1.0, 1.0, 0.0, 1.0])
+            .unwrap();
+        let (lights, light_count) = pack_directional_lights(&state.directional_lights);
+        let lighting_lights_ds = Arc::new(path.lights_buffer_pool.next(lights).unwrap());
+        let (sky_ambient, ground_ambient) = state.ambient_light.packed();
+        let (spot_lights_ds, spot_light_count) = path
+            .spot_lights
+            .next(&state.spot_lights, camera.position())
+            .unwrap();
+        b.draw_indexed(
+            path.buffers.lighting_pipeline.clone(),
+            &dynamic_state,
+            vec![path.fst.vertex_buffer().clone()],
+            path.fst.index_buffer().clone(),
+            (
+                lights_frame_matrix_data,
+                path.buffers.lighting_gbuffer_ds.clone(),
+                lighting_lights_ds.clone(),
+                spot_lights_ds,
+            ),
+            shaders::fs_deferred_lighting::ty::PushConstants {
+                resolution: dims,
+                light_count,
+                spot_light_count,
+                sky_ambient,
+                ground_ambient,
+            },
+        )
+        .expect("cannot do lighting pass")
+        .next_subpass(SubpassContents::Inline)
+        .unwrap();
+        b.debug_marker_end().unwrap();
+
+        // 1.4. SUBPASS - Skybox
+        b.debug_marker_begin(cstr!("Skybox"), [0.0, 0.0, 1.0, 1.0])
+            .unwrap();
+        path.sky.draw(&dynamic_state, fmd, &mut b);
+        b.next_subpass(SubpassContents::Inline).unwrap();
+        b.debug_marker_end().unwrap();
+
+        // 1.5. SUBPASS - Transparent Geometry
+        b.debug_marker_begin(cstr!("Accumulate Transparency Pass"), [1.0, 0.2, 0.5, 1.0])
+            .unwrap();
+        for x in draw_list.transparent.iter().map(|idx| &state.objects[*idx]) {
+            draw_calls += 1;
+            // transparent objects are not dithered, they switch levels immediately
+            let object_matrix_data = x
+                .object_matrix_data(camera.position(), VISIBLE)
+                .expect("cannot create ObjectMatrixData for this frame");
+            let mesh = x.mesh.get();
+            let lod = x.lod_fade.lod();
+
+            // todo: get rid of this dispatch somehow
+            match &*mesh {
+                DynamicIndexedMesh::U16(m) => b
+                    .draw_indexed(
+                        path.buffers.transparency.accumulation_pipeline.clone(),
+                        &dynamic_state,
+                        vec![m.vertex_buffer().clone()],
+                        m.lod_index_buffer(lod),
+                        (
+                            transparency_frame_matrix_data.clone(),
+                            x.material.descriptor_set(),
+                            object_matrix_data,
+                            lighting_lights_ds.clone(),
+                        ),
+                        mcguire13::shaders::accumulation_fs::ty::PushConstants {
+                            resolution: dims,
+                            light_count,
+                        },
+                    )
+                    .expect("cannot DrawIndexed this mesh"),
+                DynamicIndexedMesh::U32(m) => b
+                    .draw_indexed(
+                        path.buffers.transparency.accumulation_pipeline.clone(),
+                        &dynamic_state,
+                        vec![m.vertex_buffer().clone()],
+                        m.lod_index_buffer(lod),
+                        (
+                            transparency_frame_matrix_data.clone(),
+                            x.material.descriptor_set(),
+                            object_matrix_data,
+                            lighting_lights_ds.clone(),
+                        ),
+                        mcguire13::shaders::accumulation_fs::ty::PushConstants {
+                            resolution: dims,
+                            light_count,
+                        },
+                    )
+                    .expect("cannot DrawIndexed this mesh"),
+            };
+        }
+        b.next_subpass(SubpassContents::Inline).unwrap();
+        b.debug_marker_end().unwrap();
+        b.debug_marker_begin(cstr!("Resolve Transparency Pass"), [1.0, 0.2, 0.5, 1.0])
+            .unwrap();
+        b.draw_indexed(
+            path.buffers.transparency.resolve_pipeline.clone(),
+            &dynamic_state,
+            vec![path.fst.vertex_buffer().clone()],
+            path.fst.index_buffer().clone(),
+            path.buffers.transparency.resolve_ds.clone(),
+            (),
+        )
+        .expect("cannot do transparency resolve pass");
+        b.end_render_pass().unwrap();
+        b.debug_marker_end().unwrap();
+
+        // 2.0 Luminance histogram (only for the exposure debug view)
+        if path.debug_view.view == DebugView::Exposure {
+            b.debug_marker_begin(cstr!("Luminance Histogram"), [0.3, 1.0, 0.3, 1.0])
+                .unwrap();
+            path.histogram.record(&mut b);
+            b.debug_marker_end().unwrap();
+        }
+
+        // 2.1 Post-processing effects (bloom, tonemap, ..., FXAA into the swapchain image)
+        let post_frame = PostFrame {
+            fst: &path.fst,
+            dynamic_state: &dynamic_state,
+            resolution: dims,
+            time: fmd.time,
+        };
+        path.post.record(
+            path.buffers.hdr_buffer.clone(),
+            self.framebuffer.clone(),
+            &post_frame,
+            &mut b,
+        );
+
+        // 2.2 Debug view (one of the intermediate buffers drawn over the final image)
+        if let Some(ds) = path.debug_view.descriptor_set() {
+            b.draw_indexed(
+                path.debug_view.pipeline.clone(),
+                &dynamic_state,
+                vec![path.fst.vertex_buffer().clone()],
+                path.fst.index_buffer().clone(),
+                ds,
+                path.debug_view
+                    .push_constants(dims, camera.near(), camera.far()),
+            )
+            .expect("cannot do debug view pass");
+        }
+
+        // 2.3 Debug lines, overlay and GUI
+        path.debug_lines.draw(&mut b, &dynamic_state, camera);
+        path.overlay.draw(&mut b, &dynamic_state, dims);
+        path.gui.draw(&mut b, &dynamic_state, dims);
+        b.end_render_pass().unwrap();
+
+        path.draw_calls = draw_calls;
+        b.build().unwrap()
+    }
+}
@@ -0,0 +1,168 @@
+//! Temporary helper struct to allow rendering of meshes with materials.
+
+use crate::render::hierarchy::NodeId;
+use crate::render::lod_fade::LodFade;
+use crate::render::pools::{UniformBufferPool, UniformBufferPoolError};
+use crate::render::transform::{normal_matrix, relative_to_eye, Transform};
+use crate::render::ubo::ObjectMatrixData;
+use crate::render::variants::{select_variant, PipelineVariant};
+use crate::render::{descriptor_set_layout, OBJECT_DATA_UBO_DESCRIPTOR_SET};
+use crate::resources::material::Material;
+use crate::resources::mesh::DynamicIndexedMesh;
+use crate::resources::swap::Swap;
+use cgmath::{InnerSpace, Matrix3, Matrix4, Point3, SquareMatrix, Vector3};
+use std::sync::Arc;
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::vertex::Vertex;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+/// Bounding radius of objects whose meshes have no positions on the CPU (eg.
+/// meshes not created from assets).
+pub const DEFAULT_BOUNDING_RADIUS: f32 = 1.0;
+
+/// Uniform buffer pool for object data.
+pub type ObjectDataPool = UniformBufferPool<ObjectMatrixData>;
+
+/// Struct that simplifies rendering of meshes with materials.
+pub struct Object<V: Vertex> {
+    pool: ObjectDataPool,
+
+    /// Pipeline that is used for this object.
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Variant of the pipeline selected by the material and mesh (see
+    /// `render::variants`).
+    pub variant: PipelineVariant,
+    /// Transform of this object. When the object has a `parent` the transform
+    /// is relative to the parent node.
+    pub transform: Transform,
+    /// Node of `GameState::hierarchy` this object is attached to.
+    pub parent: Option<NodeId>,
+    /// World matrix of the `parent` node (see `GameState::update_transforms`).
+    parent_world: Matrix4<f64>,
+    /// Mesh that is currently being rendered. It can be replaced (eg. by
+    /// a reloaded version) while the previous one is still in use.
+    pub mesh: Swap<DynamicIndexedMesh<V>>,
+    /// Material that is currently used for rendering.
+    pub material: Arc<dyn Material>,
+    /// Radius of the sphere (in local space, centered at origin) that
+    /// contains the whole mesh. Derived from the mesh when the object is created.
+    pub bounding_radius: f32,
+    /// Whether this object is rendered.
+    pub visible: bool,
+    /// Selected level of detail and the state of the cross-fades between levels
+    /// (see `GameState::update_lods`).
+    pub lod_fade: LodFade,
+}
+
+impl<V: Vertex> Object<V> {
+    /// Creates a new `Object` from specified mesh, material. The device and pipeline
+    /// parameters are needed to initialize internal object data pool.
+    ///
+    /// The `pipeline` is replaced by its variant required by the material and the
+    /// mesh (eg. the wind-animated variant for foliage materials on meshes with
+    /// stiffness of vertices or the variant without culling for double-sided
+    /// materials). Meshes that replace the `mesh` later must have the same
+    /// properties.
+    ///
+    /// Once created, this object can only be used with the pipeline it was created with.
+    pub fn new(
+        mesh: Arc<DynamicIndexedMesh<V>>,
+        material: Arc<dyn Material>,
+        device: Arc<Device>,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        transform: Transform,
+    ) -> Self {
+        let variant = PipelineVariant::new(
+            material.blend_mode(),
+            material.flags(),
+            mesh.has_stiffness(),
+        );
+        let pipeline = select_variant(pipeline, variant);
+        let bounding_radius = mesh.bounding_radius().unwrap_or(DEFAULT_BOUNDING_RADIUS);
+
+        Self {
+            pool: ObjectDataPool::new(
+                device,
+                descriptor_set_layout(pipeline.layout(), OBJECT_DATA_UBO_DESCRIPTOR_SET),
+            ),
+            transform,
+            parent: None,
+            parent_world: Matrix4::identity(),
+            pipeline,
+            variant,
+            mesh: Swap::new(mesh),
+            material,
+            bounding_radius,
+            visible: true,
+            lod_fade: LodFade::default(),
+        }
+    }
+
+    /// Returns the center and radius of the bounding sphere of this object
+    /// in world space.
+    pub fn bounding_sphere(&self) -> (Vector3<f32>, f32) {
+        if self.parent.is_some() {
+            let world = self.world_matrix();
+            let max_scale = [world.x, world.y, world.z]
+                .iter()
+                .map(|axis| axis.truncate().magnitude())
+                .fold(0.0, f64::max);
+
+            return (
+                world.w.truncate().cast().unwrap(),
+                self.bounding_radius * max_scale as f32,
+            );
+        }
+
+        let scale = self.transform.scale();
+        let max_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+
+        (self.transform.position(), self.bounding_radius * max_scale)
+    }
+
+    /// Returns descriptor set that can be used for rendering in this frame. The model
+    /// matrix is relative to the `eye` (camera position) and only pixels with dither
+    /// thresholds in the `fade` range are drawn. Returned `DescriptorSet` may or may
+    /// not be cached from previous frame(s).
+    pub fn object_matrix_data(
+        &self,
+        eye: Point3<f32>,
+        fade: [f32; 2],
+    ) -> Result<impl DescriptorSet + Send + Sync, UniformBufferPoolError> {
+        // todo: implement caching
+        let data = ObjectMatrixData {
+            model: self.model_matrix(eye),
+            normal: self.normal_matrix().into(),
+            fade,
+        };
+        self.pool.next(data)
+    }
+
+    /// Returns the model matrix of this object relative to the `eye` (camera position).
+    pub fn model_matrix(&self, eye: Point3<f32>) -> Matrix4<f32> {
+        match self.parent {
+            None => self.transform.relative_to(eye),
+            Some(_) => relative_to_eye(self.world_matrix(), eye),
+        }
+    }
+
+    /// Returns the matrix that transforms normals of this object (see
+    /// [`Transform::normal_matrix`](../transform/struct.Transform.html#method.normal_matrix)).
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        match self.parent {
+            None => self.transform.normal_matrix(),
+            Some(_) => normal_matrix(self.world_matrix()),
+        }
+    }
+
+    /// Returns the world matrix of this object in double precision.
+    pub fn world_matrix(&self) -> Matrix4<f64> {
+        self.parent_world * self.transform.matrix_f64()
+    }
+
+    /// Sets the world matrix of the parent node. Called by `GameState::update_transforms`.
+    pub(crate) fn set_parent_world(&mut self, world: Matrix4<f64>) {
+        self.parent_world = world;
+    }
+}
@@ -0,0 +1,153 @@
+//! Buffer with IDs of objects drawn into each pixel for pixel-perfect picking.
+//!
+//! Only builds with the `editor` feature pay for it. The geometry pass of these
+//! builds has an additional `R32Uint` attachment and the fragment shaders of
+//! the geometry pass (the geometry pipeline, its variants and the terrain) write
+//! the ID of the drawn object passed in push constants into it. The ID is the
+//! index of the object in `GameState::objects` plus one (zero means no object).
+//! Objects are not drawn instanced in these builds, so each object has its own ID.
+//!
+//! `RendererState::pick` copies one pixel of the buffer to the CPU after the
+//! frame (see `readback` module) and translates the ID to the `ObjectId` of
+//! the object of that frame. Custom pipelines drawing objects in the geometry
+//! pass must declare the same push constants (see `inc_object_id.glsl`).
+
+use crate::render::objects::ObjectId;
+use crate::render::readback::ReadbackCallback;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use vulkano::format::Format;
+
+/// Whether the geometry pass writes IDs of objects (`editor` feature).
+pub const OBJECT_IDS: bool = cfg!(feature = "editor");
+
+/// Format of the object ID buffer.
+pub const OBJECT_ID_FORMAT: Format = Format::R32Uint;
+
+/// ID of pixels without any object.
+pub const NO_OBJECT: u32 = 0;
+
+/// Defines of the variants of the geometry fragment shaders used by this build.
+#[cfg(feature = "editor")]
+pub const GEOMETRY_DEFINES: &[&str] = &["OBJECT_ID"];
+#[cfg(not(feature = "editor"))]
+pub const GEOMETRY_DEFINES: &[&str] = &[];
+
+/// Returns the ID written by the object at `index` in `GameState::objects`.
+pub fn encode(index: usize) -> u32 {
+    index as u32 + 1
+}
+
+/// Push constants of draws of objects in the geometry pass.
+#[cfg(feature = "editor")]
+pub type GeometryPushConstants = crate::render::shaders::fs_deferred_geometry::ty::PushConstants;
+
+/// Push constants of draws of objects in the geometry pass (there are none
+/// without the `editor` feature).
+#[cfg(not(feature = "editor"))]
+#[derive(Copy, Clone, Debug)]
+pub struct GeometryPushConstants;
+
+/// Returns the push constants of the draw of the object at `index` in
+/// `GameState::objects`.
+#[cfg(feature = "editor")]
+pub fn geometry_push_constants(index: usize) -> GeometryPushConstants {
+    GeometryPushConstants {
+        object_id: encode(index),
+    }
+}
+
+/// Returns the push constants of the draw of the object at `index` in
+/// `GameState::objects`.
+#[cfg(not(feature = "editor"))]
+pub fn geometry_push_constants(_index: usize) -> GeometryPushConstants {
+    GeometryPushConstants
+}
+
+/// Returns the object of the ID read from the buffer (`data`) in a frame that
+/// drew objects with handles `ids` (in the order of the objects).
+pub fn decode(data: &[u8], ids: &[ObjectId]) -> Option<ObjectId> {
+    let bytes = data.get(0..4)?;
+    match u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+        NO_OBJECT => None,
+        id => ids.get(id as usize - 1).copied(),
+    }
+}
+
+/// Pixel of the object ID buffer requested for the next frame and the result
+/// of the last finished readback.
+#[derive(Default)]
+pub struct ObjectIdPicker {
+    requested: Option<[u32; 2]>,
+    /// Pixel and the object in it.
+    result: Arc<Mutex<Option<([u32; 2], Option<ObjectId>)>>>,
+}
+
+impl ObjectIdPicker {
+    /// Requests a readback of the `pixel` after the next frame and returns the
+    /// object in the pixel according to the last finished readback of it.
+    /// Returns `None` when there is no object or the pixel was not read yet.
+    pub fn pick(&mut self, pixel: [u32; 2]) -> Option<ObjectId> {
+        self.requested = Some(pixel);
+        match *self.result.lock() {
+            Some((read, object)) if read == pixel => object,
+            _ => None,
+        }
+    }
+
+    /// Takes the pixel requested since the last frame.
+    pub fn take_request(&mut self) -> Option<[u32; 2]> {
+        self.requested.take()
+    }
+
+    /// Returns the callback of the readback of the `pixel` in a frame that drew
+    /// objects with handles `ids`.
+    pub fn callback(&self, pixel: [u32; 2], ids: Vec<ObjectId>) -> ReadbackCallback {
+        let result = self.result.clone();
+        Box::new(move |readback| {
+            *result.lock() = Some((pixel, decode(readback.data, &ids)));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::object_id::{decode, encode, ObjectIdPicker, NO_OBJECT};
+    use crate::render::objects::Objects;
+    use crate::render::readback::ReadbackResult;
+
+    #[test]
+    fn ids_are_decoded_to_handles_of_the_frame() {
+        let mut objects = Objects::default();
+        let a = objects.insert("a");
+        let b = objects.insert("b");
+
+        let id = |x: u32| x.to_ne_bytes();
+        assert_eq!(decode(&id(encode(0)), objects.ids()), Some(a));
+        assert_eq!(decode(&id(encode(1)), objects.ids()), Some(b));
+        assert_eq!(decode(&id(NO_OBJECT), objects.ids()), None);
+        assert_eq!(decode(&id(encode(2)), objects.ids()), None);
+        assert_eq!(decode(&[], objects.ids()), None);
+    }
+
+    #[test]
+    fn picks_result_of_the_same_pixel() {
+        let mut objects = Objects::default();
+        let a = objects.insert("a");
+
+        let mut picker = ObjectIdPicker::default();
+        assert_eq!(picker.pick([10, 20]), None);
+        assert_eq!(picker.take_request(), Some([10, 20]));
+        assert_eq!(picker.take_request(), None);
+
+        let callback = picker.callback([10, 20], objects.ids().to_vec());
+        callback(ReadbackResult {
+            frame: 0,
+            latency: 2,
+            data: &encode(0).to_ne_bytes(),
+        });
+
+        assert_eq!(picker.pick([10, 20]), Some(a));
+        assert_eq!(picker.pick([11, 20]), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Storage of objects with stable generational handles.
+//!
+//! Objects are stored densely in insertion order, so the render paths can
+//! iterate over them (and refer to them by index within one frame) as over
+//! a slice. Systems that need to refer to an object across frames (history
+//! of the object, selection, scripting) should keep its [`ObjectId`] instead,
+//! which stays valid until the object is removed and is never reused for
+//! a different object.
+
+use std::ops::{Deref, DerefMut};
+
+/// Stable handle of an object stored in [`Objects`](struct.Objects.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId {
+    slot: u32,
+    generation: u32,
+}
+
+struct Slot {
+    /// Incremented each time the object in the slot is removed.
+    generation: u32,
+    /// Index of the object in the dense storage, `None` when the slot is free.
+    index: Option<usize>,
+}
+
+/// Collection of objects addressed by [`ObjectId`s](struct.ObjectId.html).
+///
+/// Dereferences to a slice of the objects in insertion order. Indices into
+/// the slice change when an object is removed, so they should not be kept
+/// longer than a frame.
+pub struct Objects<T> {
+    items: Vec<T>,
+    /// Handle of each item in `items`.
+    ids: Vec<ObjectId>,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl<T> Default for Objects<T> {
+    fn default() -> Self {
+        Self {
+            items: vec![],
+            ids: vec![],
+            slots: vec![],
+            free: vec![],
+        }
+    }
+}
+
+impl<T> Objects<T> {
+    /// Adds the object at the end and returns its handle.
+    pub fn insert(&mut self, object: T) -> ObjectId {
+        let index = Some(self.items.len());
+        let id = match self.free.pop() {
+            Some(slot) => {
+                let s = &mut self.slots[slot as usize];
+                s.index = index;
+                ObjectId {
+                    slot,
+                    generation: s.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    index,
+                });
+                ObjectId {
+                    slot: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.items.push(object);
+        self.ids.push(id);
+        id
+    }
+
+    /// Removes the object and returns it. Objects after it keep their order
+    /// and move one index down. Returns `None` if the object was already removed.
+    pub fn remove(&mut self, id: ObjectId) -> Option<T> {
+        let index = self.index_of(id)?;
+
+        let slot = &mut self.slots[id.slot as usize];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.slot);
+
+        self.ids.remove(index);
+        for moved in self.ids[index..].iter() {
+            if let Some(x) = self.slots[moved.slot as usize].index.as_mut() {
+                *x -= 1;
+            }
+        }
+        Some(self.items.remove(index))
+    }
+
+    /// Removes all objects. Handles of the removed objects are invalidated.
+    pub fn clear(&mut self) {
+        for id in self.ids.drain(..) {
+            let slot = &mut self.slots[id.slot as usize];
+            slot.index = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(id.slot);
+        }
+        self.items.clear();
+    }
+
+    /// Returns whether the object with specified handle exists.
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.index_of(id).is_some()
+    }
+
+    /// Returns the current index of the object in the slice of all objects.
+    pub fn index_of(&self, id: ObjectId) -> Option<usize> {
+        match self.slots.get(id.slot as usize) {
+            Some(slot) if slot.generation == id.generation => slot.index,
+            _ => None,
+        }
+    }
+
+    /// Returns the handle of the object at specified index.
+    pub fn id_of(&self, index: usize) -> Option<ObjectId> {
+        self.ids.get(index).copied()
+    }
+
+    /// Returns handles of all objects in the same order as the objects.
+    pub fn ids(&self) -> &[ObjectId] {
+        &self.ids
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&T> {
+        self.index_of(id).map(move |x| &self.items[x])
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut T> {
+        let index = self.index_of(id)?;
+        Some(&mut self.items[index])
+    }
+
+    /// Returns iterator over all objects together with their handles.
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (ObjectId, &T)> {
+        self.ids.iter().copied().zip(self.items.iter())
+    }
+}
+
+impl<T> Deref for Objects<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> DerefMut for Objects<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items
+    }
+}
+
+impl<T> Extend<T> for Objects<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for object in iter {
+            self.insert(object);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::objects::Objects;
+
+    #[test]
+    fn handles_stay_valid_after_removal() {
+        let mut objects = Objects::default();
+        let a = objects.insert("a");
+        let b = objects.insert("b");
+        let c = objects.insert("c");
+
+        assert_eq!(objects.remove(a), Some("a"));
+        assert_eq!(objects.remove(a), None);
+        assert!(!objects.contains(a));
+
+        assert_eq!(&*objects, &["b", "c"]);
+        assert_eq!(objects.index_of(b), Some(0));
+        assert_eq!(objects.index_of(c), Some(1));
+        assert_eq!(objects.get(c), Some(&"c"));
+        assert_eq!(objects.ids(), &[b, c]);
+    }
+
+    #[test]
+    fn reused_slots_get_new_handles() {
+        let mut objects = Objects::default();
+        let a = objects.insert(1);
+        objects.remove(a);
+        let b = objects.insert(2);
+
+        assert_ne!(a, b);
+        assert_eq!(objects.get(a), None);
+        assert_eq!(objects.get(b), Some(&2));
+
+        objects.clear();
+        assert!(objects.is_empty());
+        assert!(!objects.contains(b));
+
+        objects.extend(vec![3, 4]);
+        let last = objects.id_of(1).unwrap();
+        *objects.get_mut(last).unwrap() += 1;
+        assert_eq!(&*objects, &[3, 5]);
+        assert_eq!(objects.iter_with_ids().count(), 2);
+    }
+}
@@ -0,0 +1,131 @@
+//! Tiny built-in 3x5 pixel font.
+//!
+//! Only digits, uppercase letters and a few punctuation characters are
+//! available. Lowercase letters are drawn as uppercase and unknown characters
+//! as `?`.
+
+/// Width of a glyph in font pixels.
+pub const GLYPH_WIDTH: u32 = 3;
+/// Height of a glyph in font pixels.
+pub const GLYPH_HEIGHT: u32 = 5;
+/// Horizontal distance between starts of two characters in font pixels.
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between starts of two lines in font pixels.
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Rows of the glyph from top to bottom, the most significant of three bits
+/// is the leftmost pixel.
+type Glyph = [u8; GLYPH_HEIGHT as usize];
+
+const UNKNOWN: Glyph = [0b111, 0b001, 0b010, 0b000, 0b010];
+
+#[rustfmt::skip]
+fn glyph(c: char) -> Glyph {
+    match c.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        _ => UNKNOWN,
+    }
+}
+
+/// Returns positions (column and row in font pixels relative to the top-left
+/// corner of the text) of all set pixels of the `text`. Lines are separated
+/// by `\n`.
+pub fn layout(text: &str) -> Vec<[u32; 2]> {
+    let mut pixels = vec![];
+
+    for (line_idx, line) in text.lines().enumerate() {
+        for (char_idx, c) in line.chars().enumerate() {
+            let origin = [char_idx as u32 * ADVANCE, line_idx as u32 * LINE_HEIGHT];
+
+            for (y, row) in glyph(c).iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        pixels.push([origin[0] + x, origin[1] + y as u32]);
+                    }
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Returns the size of the `text` in font pixels.
+pub fn measure(text: &str) -> [u32; 2] {
+    let columns = text.lines().map(|x| x.chars().count()).max().unwrap_or(0) as u32;
+    let lines = text.lines().count() as u32;
+
+    [
+        (columns * ADVANCE).saturating_sub(1),
+        (lines * LINE_HEIGHT).saturating_sub(LINE_HEIGHT - GLYPH_HEIGHT),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::overlay::font::{layout, measure, ADVANCE, LINE_HEIGHT};
+
+    #[test]
+    fn lays_out_lines_and_characters() {
+        assert_eq!(layout("-"), vec![[0, 2], [1, 2], [2, 2]]);
+        assert_eq!(layout(" ."), vec![[ADVANCE + 1, 4]]);
+        assert_eq!(layout("\n."), vec![[1, LINE_HEIGHT + 4]]);
+        assert_eq!(layout("fps"), layout("FPS"));
+    }
+
+    #[test]
+    fn measures_text() {
+        assert_eq!(measure(""), [0, 0]);
+        assert_eq!(measure("A"), [3, 5]);
+        assert_eq!(measure("AB\nC"), [7, 12]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Immediate-mode overlay of text and colored rectangles drawn on top of the
+//! final image (eg. performance HUD).
+//!
+//! Shapes are collected during the frame in logical pixel coordinates (origin in
+//! the top-left corner, see `window` module) and drawn as quads after FXAA into
+//! the swapchain image, so they have the same size on high-DPI displays.
+//! Collected shapes are cleared after each frame.
+
+use crate::render::shader_cache::CachedShader;
+use crate::render::vertex::OverlayVertex;
+use crate::render::vulkan::pipeline_cache;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{RenderPass, Subpass};
+
+pub mod font;
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../../shaders/vs_overlay.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_overlay.glsl"
+        }
+    }
+
+    pub mod fragment {
+        const X: &str = include_str!("../../../shaders/fs_overlay.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_overlay.glsl"
+        }
+    }
+}
+
+/// Axis aligned rectangle in logical pixels.
+struct Quad {
+    min: [f32; 2],
+    max: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct Overlay {
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    vertex_pool: CpuBufferPool<OverlayVertex>,
+    quads: Vec<Quad>,
+    /// Physical pixels per logical pixel.
+    scale_factor: f32,
+}
+
+impl Overlay {
+    /// Creates the overlay drawing into the first subpass of the `render_pass`
+    /// (`PostPasses::present` that writes into the swapchain image).
+    pub fn new(device: Arc<Device>, render_pass: Arc<RenderPass>) -> Self {
+        let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_overlay");
+        let cached_fs = CachedShader::load(device.clone(), "fs_overlay");
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<OverlayVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::disabled())
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline for overlay"),
+        );
+
+        Self {
+            pipeline: pipeline as Arc<_>,
+            vertex_pool: CpuBufferPool::new(device, BufferUsage::vertex_buffer()),
+            quads: vec![],
+            scale_factor: 1.0,
+        }
+    }
+
+    /// Sets the scale factor of the window the overlay is drawn into.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// Adds a rectangle with top-left corner at `position`.
+    pub fn rect(&mut self, position: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+        self.quads.push(Quad {
+            min: position,
+            max: [position[0] + size[0], position[1] + size[1]],
+            color,
+        });
+    }
+
+    /// Adds the `text` with top-left corner at `position`. Each pixel of the
+    /// built-in font (see `font` module) is `scale` logical pixels big.
+    pub fn text(&mut self, position: [f32; 2], scale: f32, color: [f32; 4], text: &str) {
+        for [x, y] in font::layout(text) {
+            self.rect(
+                [
+                    position[0] + x as f32 * scale,
+                    position[1] + y as f32 * scale,
+                ],
+                [scale, scale],
+                color,
+            );
+        }
+    }
+
+    /// Records drawing of all shapes added since the last call into the
+    /// current subpass and clears them. The `dims` are dimensions of the
+    /// framebuffer in physical pixels.
+    pub fn draw(
+        &mut self,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        dynamic_state: &DynamicState,
+        dims: [f32; 2],
+    ) {
+        if self.quads.is_empty() {
+            return;
+        }
+
+        // logical pixels to normalized device coordinates (y points down in vulkan)
+        let dims = [dims[0] / self.scale_factor, dims[1] / self.scale_factor];
+        let ndc = |p: [f32; 2]| [p[0] / dims[0] * 2.0 - 1.0, p[1] / dims[1] * 2.0 - 1.0];
+        let vertices = self
+            .quads
+            .drain(..)
+            .flat_map(|q| {
+                let (min, max) = (ndc(q.min), ndc(q.max));
+                let vertex = |x: f32, y: f32| OverlayVertex {
+                    position: [x, y],
+                    color: q.color,
+                };
+
+                vec![
+                    vertex(min[0], min[1]),
+                    vertex(max[0], min[1]),
+                    vertex(max[0], max[1]),
+                    vertex(min[0], min[1]),
+                    vertex(max[0], max[1]),
+                    vertex(min[0], max[1]),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let vertex_buffer = self
+            .vertex_pool
+            .chunk(vertices)
+            .expect("cannot create vertex buffer for overlay");
+
+        cmd.draw(
+            self.pipeline.clone(),
+            dynamic_state,
+            vec![Arc::new(vertex_buffer)],
+            (),
+            (),
+        )
+        .expect("cannot draw overlay");
+    }
+}
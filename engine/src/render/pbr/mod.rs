@@ -0,0 +1,759 @@
+//! Module containing all logic for PHR deferred rendering pipeline.
+
+use crate::render::bloom::{Bloom, BloomSettings};
+use crate::render::debug_draw::DebugLines;
+use crate::render::debug_view::DebugViewer;
+use crate::render::decals::Decals;
+use crate::render::fxaa::{FxaaQuality, FXAA};
+use crate::render::grain::Grain;
+use crate::render::graph::{AttachmentId, GraphImages, PassId, RenderGraph};
+use crate::render::gui::GuiPainter;
+use crate::render::histogram::LuminanceHistogram;
+use crate::render::hosek::HosekSky;
+use crate::render::mcguire13::McGuire13;
+use crate::render::object_id::GEOMETRY_DEFINES;
+#[cfg(feature = "editor")]
+use crate::render::object_id::{NO_OBJECT, OBJECT_ID_FORMAT};
+use crate::render::overlay::Overlay;
+use crate::render::pools::UniformBufferPool;
+use crate::render::post::PostChain;
+use crate::render::precision::TargetPrecision;
+use crate::render::samplers::Samplers;
+use crate::render::shader_cache::CachedShader;
+use crate::render::spot_lights::SpotLights;
+use crate::render::tonemap::Tonemap;
+use crate::render::ubo::{DirectionalLight, MAX_DIRECTIONAL_LIGHTS};
+use crate::render::variants::{register_variant, select_variant, PipelineVariant};
+use crate::render::vertex::{InstanceData, NormalMappedVertex, PositionOnlyVertex, WindVertex};
+use crate::render::vulkan::pipeline_cache;
+use crate::render::{
+    descriptor_set_layout, FrameMatrixPool, FRAME_DATA_UBO_DESCRIPTOR_SET,
+    LIGHTS_UBO_DESCRIPTOR_SET, SPOT_LIGHTS_DESCRIPTOR_SET, SUBPASS_UBO_DESCRIPTOR_SET,
+};
+use crate::resources::mesh::{create_full_screen_triangle, IndexedMesh};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::{ImageView, ImageViewAbstract};
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::vertex::{OneVertexOneInstanceDefinition, TwoBuffersDefinition};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::render_pass::{Framebuffer, RenderPass};
+use vulkano::render_pass::{FramebufferAbstract, FramebufferCreationError, LoadOp, StoreOp};
+
+const DEPTH_BUFFER_FORMAT: Format = Format::D32Sfloat;
+
+/// Uniform buffer poll for light data.
+pub type LightDataPool = UniformBufferPool<[DirectionalLight; MAX_DIRECTIONAL_LIGHTS]>;
+
+/// Long-lived objects & buffers that **do not** change when resolution
+/// changes.
+pub struct PBRDeffered {
+    pub main_graph: MainGraph,
+    pub render_pass: Arc<RenderPass>,
+    pub samplers: Samplers,
+    pub lights_buffer_pool: LightDataPool,
+    pub spot_lights: SpotLights,
+    pub decals: Decals,
+    /// Pool of per-instance vertex buffers for instanced geometry.
+    pub instance_buffer_pool: CpuBufferPool<InstanceData>,
+    pub fst: Arc<IndexedMesh<PositionOnlyVertex, u16>>,
+    pub buffers: Buffers,
+    pub sky: HosekSky,
+    /// Post-processing effects (bloom, tonemap, grain and FXAA by default).
+    pub post: PostChain,
+    pub debug_view: DebugViewer,
+    /// Histogram of the HDR buffer shown by the exposure debug view.
+    pub histogram: LuminanceHistogram,
+    /// Lines of `debug_draw` drawn after tonemapping.
+    pub debug_lines: DebugLines,
+    pub overlay: Overlay,
+    pub gui: GuiPainter,
+    /// Number of draw calls of scene objects recorded in the last frame.
+    pub draw_calls: usize,
+    /// Time passed to the shaders instead of the time since `GameState::start`.
+    /// Freezes animated effects (wind, grain), so rendered images are deterministic.
+    pub fixed_time: Option<f32>,
+}
+
+/// Long-lived objects & buffers that **do** change when resolution changes.
+pub struct Buffers {
+    pub transparency: McGuire13,
+
+    pub hdr_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub gbuffer1: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub gbuffer2: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub gbuffer3: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub depth_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub revealage_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    /// IDs of objects drawn into each pixel (see `render::object_id`).
+    #[cfg(feature = "editor")]
+    pub object_id_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub main_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+
+    pub geometry_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Variant of `geometry_pipeline` that takes model matrices from a per-instance
+    /// vertex buffer (`InstanceData`) instead of the object UBO.
+    pub instanced_geometry_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Variants of `geometry_pipeline` for foliage (vertices displaced by the wind
+    /// according to their stiffness in a second vertex buffer of `WindVertex`),
+    /// masked and double-sided materials (see `render::variants`).
+    pub geometry_variants:
+        HashMap<PipelineVariant, Arc<dyn GraphicsPipelineAbstract + Send + Sync>>,
+    pub lighting_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    // subpass descriptor sets dependant on buffers
+    pub lighting_gbuffer_ds: Arc<dyn DescriptorSet + Send + Sync>,
+
+    pub geometry_frame_matrix_pool: FrameMatrixPool,
+    pub lights_frame_matrix_pool: FrameMatrixPool,
+    pub transparency_frame_matrix_pool: FrameMatrixPool,
+}
+
+/// Render graph of the main render pass together with its attachments and passes.
+pub struct MainGraph {
+    pub graph: RenderGraph,
+
+    pub gbuffer1: AttachmentId,
+    pub gbuffer2: AttachmentId,
+    pub gbuffer3: AttachmentId,
+    /// Clear coat and anisotropy of surfaces with `MaterialFlags::CLEAR_COAT`
+    /// or `MaterialFlags::ANISOTROPIC` materials.
+    pub gbuffer4: AttachmentId,
+    pub depth: AttachmentId,
+    pub hdr: AttachmentId,
+    pub trans_accum: AttachmentId,
+    pub trans_reveal: AttachmentId,
+    /// IDs of objects drawn by the geometry pass (see `render::object_id`).
+    #[cfg(feature = "editor")]
+    pub object_id: AttachmentId,
+
+    pub geometry: PassId,
+    pub decals: PassId,
+    pub lighting: PassId,
+    pub sky: PassId,
+    pub transparency_accumulation: PassId,
+    pub transparency_resolve: PassId,
+}
+
+impl MainGraph {
+    /// Declares the graph that renders all geometry, projects decals onto it,
+    /// lights it and draws the sky and transparent objects on top into the HDR
+    /// buffer.
+    fn new(precision: &TargetPrecision) -> Self {
+        let mut graph = RenderGraph::new();
+
+        let gbuffer1 = graph.attachment(
+            "GBuffer 1",
+            Format::A2B10G10R10UnormPack32,
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let gbuffer2 = graph.attachment(
+            "GBuffer 2",
+            Format::R8G8B8A8Unorm,
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let gbuffer3 = graph.attachment(
+            "GBuffer 3",
+            Format::R8G8B8A8Unorm,
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let gbuffer4 = graph.attachment(
+            "GBuffer 4",
+            Format::R8G8B8A8Unorm,
+            LoadOp::Clear,
+            StoreOp::DontCare,
+        );
+        let depth = graph.attachment(
+            "Depth buffer",
+            DEPTH_BUFFER_FORMAT,
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let hdr = graph.attachment(
+            "HDR Buffer",
+            precision.hdr.rgba(),
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let trans_accum = graph.attachment(
+            "Transparency accumulation",
+            precision.transparency.rgba(),
+            LoadOp::Clear,
+            StoreOp::DontCare,
+        );
+        let trans_reveal = graph.attachment(
+            "Transparency revealage",
+            precision.transparency.r(),
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+
+        // ids of objects are copied to the cpu by picking
+        #[cfg(feature = "editor")]
+        let object_id = {
+            let object_id = graph.attachment(
+                "Object IDs",
+                OBJECT_ID_FORMAT,
+                LoadOp::Clear,
+                StoreOp::Store,
+            );
+            graph.clear_value(object_id, ClearValue::Uint([NO_OBJECT; 4]));
+            graph.sampled(object_id);
+            object_id
+        };
+
+        for x in &[gbuffer1, gbuffer2, gbuffer3, gbuffer4, trans_accum] {
+            graph.clear_value(*x, ClearValue::Float([0.0, 0.0, 0.0, 0.0]));
+        }
+        graph.clear_value(depth, ClearValue::Depth(1.0));
+        graph.clear_value(hdr, ClearValue::Float([0.0, 0.0, 0.0, 1.0]));
+        graph.clear_value(trans_reveal, ClearValue::Float([1.0, 0.0, 0.0, 0.0]));
+        // hdr is sampled by bloom and tonemapping after the render pass
+        graph.sampled(hdr);
+        // the rest can be displayed by the debug view
+        for x in &[gbuffer1, gbuffer2, gbuffer3, depth, trans_reveal] {
+            graph.sampled(*x);
+        }
+
+        // emissive materials write their emission directly into the hdr buffer
+        let geometry = graph
+            .pass("Geometry")
+            .color(gbuffer1)
+            .color(gbuffer2)
+            .color(gbuffer3)
+            .color(hdr)
+            .color(gbuffer4);
+        #[cfg(feature = "editor")]
+        let geometry = geometry.color(object_id);
+        let geometry = geometry.depth_stencil(depth).add();
+        let decals = graph
+            .pass("Decals")
+            .color(gbuffer1)
+            .color(gbuffer2)
+            .color(gbuffer3)
+            .input(depth)
+            .add();
+        let lighting = graph
+            .pass("Lighting")
+            .color(hdr)
+            .input(gbuffer1)
+            .input(gbuffer2)
+            .input(gbuffer3)
+            .input(depth)
+            .input(gbuffer4)
+            .add();
+        let sky = graph.pass("Sky").color(hdr).depth_stencil(depth).add();
+        let transparency_accumulation = graph
+            .pass("Transparency accumulation")
+            .color(trans_accum)
+            .color(trans_reveal)
+            .depth_stencil(depth)
+            .add();
+        let transparency_resolve = graph
+            .pass("Transparency resolve")
+            .color(hdr)
+            .depth_stencil(depth)
+            .input(trans_accum)
+            .input(trans_reveal)
+            .add();
+
+        Self {
+            graph,
+            gbuffer1,
+            gbuffer2,
+            gbuffer3,
+            gbuffer4,
+            depth,
+            hdr,
+            trans_accum,
+            trans_reveal,
+            #[cfg(feature = "editor")]
+            object_id,
+            geometry,
+            decals,
+            lighting,
+            sky,
+            transparency_accumulation,
+            transparency_resolve,
+        }
+    }
+
+    /// Creates framebuffer of the main render pass from the images of the graph.
+    fn framebuffer(
+        &self,
+        render_pass: Arc<RenderPass>,
+        images: &GraphImages,
+    ) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+        // attachments have to be added in the order they were declared in the graph
+        let framebuffer = Framebuffer::start(render_pass)
+            .add(images.get(self.gbuffer1))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.gbuffer2))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.gbuffer3))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.gbuffer4))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.depth))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.hdr))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.trans_accum))
+            .expect("cannot add attachment to framebuffer")
+            .add(images.get(self.trans_reveal))
+            .expect("cannot add attachment to framebuffer");
+        #[cfg(feature = "editor")]
+        let framebuffer = framebuffer
+            .add(images.get(self.object_id))
+            .expect("cannot add attachment to framebuffer");
+
+        Arc::new(framebuffer.build().expect("cannot build framebuffer"))
+    }
+}
+
+impl Buffers {
+    fn new(
+        main: &MainGraph,
+        render_pass: Arc<RenderPass>,
+        device: Arc<Device>,
+        dims: [u32; 2],
+    ) -> Self {
+        // we create required shaders for all graphical pipelines we use in this
+        // render pass from precompiled (embedded) spri-v binary data from soruces.
+        let vs =
+            crate::render::shaders::vs_deferred_geometry::Shader::load(device.clone()).unwrap();
+        let fs =
+            crate::render::shaders::fs_deferred_geometry::Shader::load(device.clone()).unwrap();
+        let instanced_vs =
+            crate::render::shaders::vs_deferred_geometry_instanced::Shader::load(device.clone())
+                .unwrap();
+        let foliage_vs =
+            crate::render::shaders::vs_deferred_geometry_foliage::Shader::load(device.clone())
+                .unwrap();
+        let masked_fs =
+            crate::render::shaders::fs_deferred_geometry_masked::Shader::load(device.clone())
+                .unwrap();
+        let pt_vs = crate::render::shaders::vs_passtrough::Shader::load(device.clone()).unwrap();
+        let dl_fs =
+            crate::render::shaders::fs_deferred_lighting::Shader::load(device.clone()).unwrap();
+
+        // shaders compiled by the asset server replace the embedded ones when available
+        let cached_vs = CachedShader::load(device.clone(), "vs_deferred_geometry");
+        let cached_fs =
+            CachedShader::load_variant(device.clone(), "fs_deferred_geometry", GEOMETRY_DEFINES);
+        let cached_instanced_vs =
+            CachedShader::load(device.clone(), "vs_deferred_geometry_instanced");
+        let cached_foliage_vs = CachedShader::load(device.clone(), "vs_deferred_geometry_foliage");
+        let cached_masked_fs = CachedShader::load_variant(
+            device.clone(),
+            "fs_deferred_geometry",
+            &[&["MASKED"][..], GEOMETRY_DEFINES].concat(),
+        );
+        let cached_pt_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_dl_fs = CachedShader::load(device.clone(), "fs_deferred_lighting");
+
+        // create basic pipeline for drawing
+        let geometry_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<NormalMappedVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::simple_depth_test())
+                .cull_mode_back()
+                .front_face_clockwise()
+                .render_pass(main.graph.subpass(&render_pass, main.geometry))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline"),
+        );
+
+        let instanced_geometry_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(OneVertexOneInstanceDefinition::<
+                    NormalMappedVertex,
+                    InstanceData,
+                >::new())
+                .vertex_shader(
+                    cached_instanced_vs.entry_point(instanced_vs.main_entry_point()),
+                    (),
+                )
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::simple_depth_test())
+                .cull_mode_back()
+                .front_face_clockwise()
+                .render_pass(main.graph.subpass(&render_pass, main.geometry))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create instanced graphics pipeline"),
+        );
+
+        // variants of the geometry pipeline selected by `Object::new`
+        let geometry_variants = PipelineVariant::all()
+            .map(|variant| {
+                let vertex = if variant.foliage {
+                    cached_foliage_vs.entry_point(foliage_vs.main_entry_point())
+                } else {
+                    cached_vs.entry_point(vs.main_entry_point())
+                };
+                let fragment = if variant.masked {
+                    cached_masked_fs.entry_point(masked_fs.main_entry_point())
+                } else {
+                    cached_fs.entry_point(fs.main_entry_point())
+                };
+
+                let builder = GraphicsPipeline::start()
+                    .vertex_shader(vertex, ())
+                    .fragment_shader(fragment, ())
+                    .triangle_list()
+                    .viewports_dynamic_scissors_irrelevant(1)
+                    .depth_stencil(DepthStencil::simple_depth_test())
+                    .front_face_clockwise()
+                    .render_pass(main.graph.subpass(&render_pass, main.geometry));
+                let builder = if variant.double_sided {
+                    builder.cull_mode_disabled()
+                } else {
+                    builder.cull_mode_back()
+                };
+
+                let pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync> = if variant.foliage {
+                    Arc::new(
+                        builder
+                            .vertex_input(
+                                TwoBuffersDefinition::<NormalMappedVertex, WindVertex>::new(),
+                            )
+                            .build_with_cache(pipeline_cache(&device))
+                            .build(device.clone())
+                            .expect("cannot create variant of geometry pipeline"),
+                    )
+                } else {
+                    Arc::new(
+                        builder
+                            .vertex_input_single_buffer::<NormalMappedVertex>()
+                            .build_with_cache(pipeline_cache(&device))
+                            .build(device.clone())
+                            .expect("cannot create variant of geometry pipeline"),
+                    )
+                };
+                (variant, pipeline)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let lighting_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<PositionOnlyVertex>()
+                .vertex_shader(cached_pt_vs.entry_point(pt_vs.main_entry_point()), ())
+                .fragment_shader(cached_dl_fs.entry_point(dl_fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                // lighting is added to the emission written by the geometry pass
+                .blend_collective(AttachmentBlend {
+                    enabled: true,
+                    color_op: BlendOp::Add,
+                    color_source: BlendFactor::One,
+                    color_destination: BlendFactor::One,
+                    alpha_op: BlendOp::Add,
+                    alpha_source: BlendFactor::Zero,
+                    alpha_destination: BlendFactor::One,
+                    mask_red: true,
+                    mask_green: true,
+                    mask_blue: true,
+                    mask_alpha: true,
+                })
+                .render_pass(main.graph.subpass(&render_pass, main.lighting))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot build lighting graphics pipeline"),
+        );
+
+        let images = main
+            .graph
+            .create_images(device.clone(), dims)
+            .expect("cannot create buffers");
+
+        // create transparency pipelines
+        let transparency = McGuire13::new(
+            device.clone(),
+            main.graph
+                .subpass(&render_pass, main.transparency_accumulation),
+            main.graph.subpass(&render_pass, main.transparency_resolve),
+            images.get(main.trans_accum),
+            images.get(main.trans_reveal),
+        );
+
+        let geometry_pipeline = geometry_pipeline as Arc<_>;
+        for (variant, pipeline) in geometry_variants.iter() {
+            register_variant(&geometry_pipeline, *variant, pipeline);
+        }
+
+        Self {
+            geometry_frame_matrix_pool: FrameMatrixPool::new(
+                device.clone(),
+                descriptor_set_layout(geometry_pipeline.layout(), FRAME_DATA_UBO_DESCRIPTOR_SET),
+            ),
+            lights_frame_matrix_pool: FrameMatrixPool::new(
+                device.clone(),
+                descriptor_set_layout(lighting_pipeline.layout(), FRAME_DATA_UBO_DESCRIPTOR_SET),
+            ),
+            transparency_frame_matrix_pool: FrameMatrixPool::new(
+                device,
+                descriptor_set_layout(
+                    transparency.accumulation_pipeline.layout(),
+                    FRAME_DATA_UBO_DESCRIPTOR_SET,
+                ),
+            ),
+            lighting_gbuffer_ds: lighting_gbuffer_ds(lighting_pipeline.as_ref(), main, &images),
+            geometry_pipeline,
+            instanced_geometry_pipeline: instanced_geometry_pipeline as Arc<_>,
+            geometry_variants,
+            lighting_pipeline: lighting_pipeline as Arc<_>,
+            main_framebuffer: main.framebuffer(render_pass, &images),
+            transparency,
+            depth_buffer: images.get(main.depth),
+            revealage_buffer: images.get(main.trans_reveal),
+            #[cfg(feature = "editor")]
+            object_id_buffer: images.get(main.object_id),
+            gbuffer1: images.get(main.gbuffer1),
+            gbuffer2: images.get(main.gbuffer2),
+            gbuffer3: images.get(main.gbuffer3),
+            hdr_buffer: images.get(main.hdr),
+        }
+    }
+
+    pub fn dimensions_changed(
+        &mut self,
+        main: &MainGraph,
+        render_pass: Arc<RenderPass>,
+        dims: [u32; 2],
+    ) {
+        info!("Dimensions changed to {:?}. Recreating buffers.", dims);
+        let device = render_pass.device().clone();
+        let images = main
+            .graph
+            .create_images(device, dims)
+            .expect("cannot create buffers");
+
+        self.depth_buffer = images.get(main.depth);
+        self.revealage_buffer = images.get(main.trans_reveal);
+        #[cfg(feature = "editor")]
+        {
+            self.object_id_buffer = images.get(main.object_id);
+        }
+        self.hdr_buffer = images.get(main.hdr);
+        self.gbuffer1 = images.get(main.gbuffer1);
+        self.gbuffer2 = images.get(main.gbuffer2);
+        self.gbuffer3 = images.get(main.gbuffer3);
+
+        self.transparency
+            .dimensions_changed(images.get(main.trans_accum), images.get(main.trans_reveal));
+
+        self.lighting_gbuffer_ds =
+            lighting_gbuffer_ds(self.lighting_pipeline.as_ref(), main, &images);
+        self.main_framebuffer = main.framebuffer(render_pass, &images);
+    }
+}
+
+fn lighting_gbuffer_ds(
+    pipeline: &(dyn GraphicsPipelineAbstract + Send + Sync),
+    main: &MainGraph,
+    images: &GraphImages,
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    Arc::new(
+        PersistentDescriptorSet::start(descriptor_set_layout(
+            pipeline.layout(),
+            SUBPASS_UBO_DESCRIPTOR_SET,
+        ))
+        .add_image(images.get(main.gbuffer1))
+        .unwrap()
+        .add_image(images.get(main.gbuffer2))
+        .unwrap()
+        .add_image(images.get(main.gbuffer3))
+        .unwrap()
+        .add_image(images.get(main.depth))
+        .unwrap()
+        .add_image(images.get(main.gbuffer4))
+        .unwrap()
+        .build()
+        .unwrap(),
+    )
+}
+
+impl PBRDeffered {
+    /// Creates the render path drawing the final image in `output_format`
+    /// (format of the swapchain images) with `dimensions`.
+    pub fn new(
+        queue: Arc<Queue>,
+        device: Arc<Device>,
+        output_format: Format,
+        dimensions: [u32; 2],
+        bloom: BloomSettings,
+        precision: TargetPrecision,
+        fxaa_quality: FxaaQuality,
+    ) -> Self {
+        // first we generate some useful resources on the fly
+        let (fst, _) = create_full_screen_triangle(queue.clone()).expect("cannot create fst");
+
+        // this example render path uses one render pass which renders all geometry and then
+        // the skybox with one directional light without any shadows. the hdr result is
+        // then processed by the chain of post-processing effects.
+        let main_graph = MainGraph::new(&precision);
+        let render_pass = main_graph
+            .graph
+            .create_render_pass(device.clone())
+            .expect("cannot create render pass");
+
+        let samplers = Samplers::new(device.clone()).unwrap();
+        let buffers = Buffers::new(&main_graph, render_pass.clone(), device.clone(), dimensions);
+        let decals = Decals::new(
+            queue.clone(),
+            main_graph.graph.subpass(&render_pass, main_graph.decals),
+            buffers.depth_buffer.clone(),
+        );
+        let sky = HosekSky::new(
+            queue.clone(),
+            main_graph.graph.subpass(&render_pass, main_graph.sky),
+            device.clone(),
+        );
+
+        let mut post = PostChain::new(device.clone(), output_format, dimensions);
+        let passes = post.passes().clone();
+        post.push(Box::new(Bloom::new(
+            device.clone(),
+            bloom,
+            precision.bloom,
+            buffers.hdr_buffer.clone(),
+            dimensions,
+        )));
+        post.push(Box::new(Tonemap::new(device.clone(), &passes)));
+        post.push(Box::new(Grain::new(device.clone(), &passes)));
+        post.push(Box::new(FXAA::new(device.clone(), &passes, fxaa_quality)));
+        post.set_enabled("grain", false)
+            .expect("cannot disable grain");
+
+        let debug_view = DebugViewer::new(device.clone(), passes.present.clone(), &buffers);
+        let histogram =
+            LuminanceHistogram::new(device.clone(), buffers.hdr_buffer.clone(), dimensions);
+        let debug_lines = DebugLines::new(device.clone(), passes.present.clone());
+        let overlay = Overlay::new(device.clone(), passes.present.clone());
+        let gui = GuiPainter::new(queue.clone(), passes.present);
+
+        Self {
+            fst,
+            render_pass: render_pass as Arc<_>,
+            lights_buffer_pool: LightDataPool::new(
+                device.clone(),
+                buffers
+                    .lighting_pipeline
+                    .layout()
+                    .descriptor_set_layouts()
+                    .get(LIGHTS_UBO_DESCRIPTOR_SET)
+                    .unwrap()
+                    .clone(),
+            ),
+            spot_lights: SpotLights::new(
+                queue.clone(),
+                descriptor_set_layout(
+                    buffers.lighting_pipeline.layout(),
+                    SPOT_LIGHTS_DESCRIPTOR_SET,
+                ),
+            ),
+            decals,
+            instance_buffer_pool: CpuBufferPool::new(device.clone(), BufferUsage::vertex_buffer()),
+            post,
+            debug_view,
+            histogram,
+            debug_lines,
+            overlay,
+            gui,
+            draw_calls: 0,
+            fixed_time: None,
+            buffers,
+            sky,
+            samplers,
+            main_graph,
+        }
+    }
+
+    pub fn create_framebuffer<I>(
+        &self,
+        final_image: I,
+    ) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, FramebufferCreationError>
+    where
+        I: ImageViewAbstract + Send + Sync + 'static,
+    {
+        self.post.create_framebuffer(final_image)
+    }
+
+    pub fn dimensions_changed(&mut self, dimensions: [u32; 2]) {
+        self.buffers
+            .dimensions_changed(&self.main_graph, self.render_pass.clone(), dimensions);
+        self.post
+            .dimensions_changed(&self.buffers.hdr_buffer, dimensions);
+        self.decals
+            .dimensions_changed(self.buffers.depth_buffer.clone());
+        self.debug_view.recreate_descriptors(&self.buffers);
+        self.histogram
+            .dimensions_changed(self.buffers.hdr_buffer.clone(), dimensions);
+    }
+
+    /// Copies the settings changed at run-time (sky, debug view, fixed time and
+    /// enabled post-processing effects) from the `previous` render path this one replaces.
+    pub fn copy_settings(&mut self, previous: &PBRDeffered) {
+        self.sky.sun_dir = previous.sky.sun_dir;
+        self.sky.turbidity = previous.sky.turbidity;
+        self.sky.ground_albedo = previous.sky.ground_albedo;
+        self.debug_view.view = previous.debug_view.view;
+        self.fixed_time = previous.fixed_time;
+
+        if let (Some(grain), Some(previous)) = (
+            self.post.effect_mut::<Grain>(),
+            previous.post.effect::<Grain>(),
+        ) {
+            grain.intensity = previous.intensity;
+        }
+        if let Err(e) = self.post.configure(&previous.post.enabled()) {
+            warn!("Cannot configure post-processing effects: {}", e);
+        }
+    }
+
+    /// Returns the pipeline of this render path that replaces the `pipeline` of
+    /// the `previous` render path (the geometry pipeline or its variants) or
+    /// `None` when the `pipeline` is not one of them.
+    pub fn replacement_pipeline(
+        &self,
+        previous: &PBRDeffered,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        variant: PipelineVariant,
+    ) -> Option<Arc<dyn GraphicsPipelineAbstract + Send + Sync>> {
+        let address = |x: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>| {
+            Arc::as_ptr(x) as *const () as usize
+        };
+
+        let previous_buffers = &previous.buffers;
+        let is_geometry = std::iter::once(&previous_buffers.geometry_pipeline)
+            .chain(previous_buffers.geometry_variants.values())
+            .any(|x| address(x) == address(pipeline));
+        if !is_geometry {
+            return None;
+        }
+
+        Some(select_variant(
+            self.buffers.geometry_pipeline.clone(),
+            variant,
+        ))
+    }
+}
@@ -0,0 +1,575 @@
+//! Chain of post-processing effects applied to the HDR buffer.
+//!
+//! Effects implement [`PostEffect`](trait.PostEffect.html) and are recorded by
+//! [`PostChain`](struct.PostChain.html) in order (bloom, tonemap, grain and FXAA
+//! by default). An effect that draws an image reads the image drawn by the
+//! previous one (the first one reads the HDR buffer) and draws into one of two
+//! intermediate buffers that are used in turns. The last effect that draws an
+//! image draws into the swapchain image. Effects that don't draw an image (eg.
+//! bloom) only pass data to the following effects.
+//!
+//! Effects can be enabled, disabled and reordered at runtime (see
+//! `PostChain::configure`) and projects can add their own effects with
+//! `PostChain::insert`.
+
+use crate::render::graph::{AttachmentId, RenderGraph};
+use crate::render::vertex::PositionOnlyVertex;
+use crate::resources::mesh::IndexedMesh;
+use downcast_rs::{impl_downcast, Downcast};
+use parking_lot::Mutex;
+use std::ffi::{CStr, CString};
+use std::sync::Arc;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents,
+};
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::{ImageView, ImageViewAbstract};
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::render_pass::{
+    Framebuffer, FramebufferAbstract, FramebufferCreationError, LoadOp, RenderPass, StoreOp,
+    Subpass,
+};
+
+/// Image read by the effects.
+pub type PostImage = Arc<ImageView<Arc<AttachmentImage>>>;
+
+/// Format of the intermediate buffers between the effects.
+pub const LDR_FORMAT: Format = Format::B10G11R11UfloatPack32;
+
+/// Data of the frame shared by all effects.
+pub struct PostFrame<'a> {
+    /// Full screen triangle the effects draw.
+    pub fst: &'a IndexedMesh<PositionOnlyVertex, u16>,
+    pub dynamic_state: &'a DynamicState,
+    /// Resolution of the images in pixels.
+    pub resolution: [f32; 2],
+    /// Time since the start of the game in seconds (eg. to animate noise).
+    pub time: f32,
+}
+
+/// Images an effect reads.
+pub struct PostInput<'a> {
+    /// Image drawn by the previous effect (the HDR buffer for the first one).
+    pub color: PostImage,
+    /// Bloom buffer and its intensity when the bloom was recorded before the effect.
+    pub bloom: Option<(PostImage, f32)>,
+    pub frame: &'a PostFrame<'a>,
+}
+
+/// Where an effect draws.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PostOutput {
+    /// The effect does not draw an image and is recorded outside of a render pass.
+    None,
+    /// One of the intermediate buffers (subpass of `PostPasses::intermediate`).
+    Intermediate,
+    /// The swapchain image (subpass of `PostPasses::present`).
+    Present,
+}
+
+/// Post-processing effect recorded by the `PostChain`.
+pub trait PostEffect: Downcast {
+    /// Unique name of the effect used to configure the chain.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the effect draws an image. Effects that don't (eg. bloom)
+    /// only add data to the input of the following effects.
+    fn draws(&self) -> bool {
+        true
+    }
+
+    /// Called when the resolution changed with the new HDR buffer.
+    fn dimensions_changed(&mut self, _hdr: &PostImage, _dims: [u32; 2]) {}
+
+    /// Records the effect. Effects that draw are recorded inside the render pass
+    /// selected by the `output`, other effects outside of any render pass.
+    fn record(
+        &self,
+        input: &mut PostInput,
+        output: PostOutput,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    );
+}
+
+impl_downcast!(PostEffect);
+
+/// Render passes the effects draw in. Both have one subpass with one color
+/// attachment.
+#[derive(Clone)]
+pub struct PostPasses {
+    /// Render pass drawing into an intermediate buffer (`LDR_FORMAT`).
+    pub intermediate: Arc<RenderPass>,
+    /// Render pass drawing into the swapchain image. The overlay, GUI and debug
+    /// views are drawn in it too.
+    pub present: Arc<RenderPass>,
+}
+
+/// Pipelines of an effect for both of its possible outputs, as any effect may
+/// be the last one in the chain.
+pub struct PostPipelines {
+    intermediate: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    present: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+impl PostPipelines {
+    /// Creates the pipelines by calling `create` with subpasses of both render passes.
+    pub fn new<F>(passes: &PostPasses, create: F) -> Self
+    where
+        F: Fn(Subpass) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    {
+        Self {
+            intermediate: create(Subpass::from(passes.intermediate.clone(), 0).unwrap()),
+            present: create(Subpass::from(passes.present.clone(), 0).unwrap()),
+        }
+    }
+
+    /// Returns the pipeline drawing into the `output`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the `output` is `PostOutput::None`.
+    pub fn get(&self, output: PostOutput) -> &Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        match output {
+            PostOutput::None => panic!("effect without output has no pipeline"),
+            PostOutput::Intermediate => &self.intermediate,
+            PostOutput::Present => &self.present,
+        }
+    }
+
+    /// Returns one of the pipelines. Both pipelines have the same layout, so
+    /// descriptor sets created from it can be used with either of them.
+    pub fn any(&self) -> &Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        &self.intermediate
+    }
+}
+
+/// Descriptor sets of an effect for its input images. Inputs change only when
+/// the chain is resized or reconfigured, so the sets are created once and reused.
+#[derive(Default)]
+pub struct InputSets {
+    sets: Mutex<Vec<InputSet>>,
+}
+
+struct InputSet {
+    /// Addresses of the input images.
+    images: Vec<usize>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl InputSets {
+    /// Returns the descriptor set for the `images` or creates it with `create`.
+    pub fn get<F>(&self, images: &[&PostImage], create: F) -> Arc<dyn DescriptorSet + Send + Sync>
+    where
+        F: FnOnce() -> Arc<dyn DescriptorSet + Send + Sync>,
+    {
+        // the sets keep the images alive, so their addresses are not reused
+        let key = images
+            .iter()
+            .map(|x| Arc::as_ptr(x) as usize)
+            .collect::<Vec<_>>();
+        let mut sets = self.sets.lock();
+
+        if let Some(x) = sets.iter().find(|x| x.images == key) {
+            return x.set.clone();
+        }
+        let set = create();
+        sets.push(InputSet {
+            images: key,
+            set: set.clone(),
+        });
+        set
+    }
+
+    /// Releases all sets (and the images they reference).
+    pub fn clear(&mut self) {
+        self.sets.get_mut().clear();
+    }
+}
+
+/// Effect in the chain.
+struct Entry {
+    effect: Box<dyn PostEffect>,
+    enabled: bool,
+    /// Name of the debug marker around the commands of the effect.
+    marker: &'static CStr,
+}
+
+impl Entry {
+    fn new(effect: Box<dyn PostEffect>) -> Self {
+        // markers must outlive the command buffers. effects are added a few times
+        // per run, so the names are leaked instead of tracking their lifetime.
+        let marker = CString::new(effect.name()).expect("invalid name of post effect");
+
+        Self {
+            effect,
+            enabled: true,
+            marker: Box::leak(marker.into_boxed_c_str()),
+        }
+    }
+}
+
+/// Intermediate buffer with its framebuffer.
+struct LdrBuffer {
+    image: PostImage,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+}
+
+/// Ordered chain of post-processing effects.
+pub struct PostChain {
+    passes: PostPasses,
+    graph: RenderGraph,
+    ldr: AttachmentId,
+    /// Intermediate buffers used in turns.
+    buffers: Vec<LdrBuffer>,
+    effects: Vec<Entry>,
+}
+
+impl PostChain {
+    /// Creates an empty chain drawing into swapchain images with the `swapchain_format`.
+    pub fn new(device: Arc<Device>, swapchain_format: Format, dims: [u32; 2]) -> Self {
+        let mut graph = RenderGraph::new();
+        let ldr = graph.attachment("LDR Buffer", LDR_FORMAT, LoadOp::DontCare, StoreOp::Store);
+        // the buffer is read by the next effect
+        graph.sampled(ldr);
+        graph.pass("Post effect").color(ldr).add();
+        let intermediate = graph
+            .create_render_pass(device.clone())
+            .expect("cannot create render pass for post effects");
+
+        let present = Arc::new(
+            vulkano::ordered_passes_renderpass!(
+                device.clone(),
+                attachments: {
+                    final_color: {
+                        load: DontCare,
+                        store: Store,
+                        format: swapchain_format,
+                        samples: 1,
+                    }
+                },
+                passes: [
+                    {
+                         color: [final_color],
+                         depth_stencil: {},
+                         input: []
+                    }
+                ]
+            )
+            .expect("cannot create render pass for post effects"),
+        );
+
+        let mut chain = Self {
+            passes: PostPasses {
+                intermediate,
+                present,
+            },
+            graph,
+            ldr,
+            buffers: vec![],
+            effects: vec![],
+        };
+        chain.buffers = chain.create_buffers(device, dims);
+        chain
+    }
+
+    fn create_buffers(&self, device: Arc<Device>, dims: [u32; 2]) -> Vec<LdrBuffer> {
+        (0..2)
+            .map(|_| {
+                let image = self
+                    .graph
+                    .create_images(device.clone(), dims)
+                    .expect("cannot create buffers")
+                    .get(self.ldr);
+                let framebuffer = Arc::new(
+                    Framebuffer::start(self.passes.intermediate.clone())
+                        .add(image.clone())
+                        .expect("cannot add attachment to framebuffer")
+                        .build()
+                        .expect("cannot build framebuffer"),
+                );
+                LdrBuffer {
+                    image,
+                    framebuffer: framebuffer as Arc<_>,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the render passes effects of this chain draw in.
+    pub fn passes(&self) -> &PostPasses {
+        &self.passes
+    }
+
+    /// Adds an enabled `effect` to the end of the chain.
+    pub fn push(&mut self, effect: Box<dyn PostEffect>) {
+        self.insert(self.effects.len(), effect);
+    }
+
+    /// Inserts an enabled `effect` at the `index` of the chain.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if an effect with the same name is already in the chain.
+    pub fn insert(&mut self, index: usize, effect: Box<dyn PostEffect>) {
+        assert!(
+            self.position(effect.name()).is_none(),
+            "post effect {:?} is already in the chain",
+            effect.name()
+        );
+        self.effects.insert(index, Entry::new(effect));
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.effects.iter().position(|x| x.effect.name() == name)
+    }
+
+    /// Returns names of the enabled effects in order.
+    pub fn enabled(&self) -> Vec<&'static str> {
+        enabled_names(&self.effects)
+    }
+
+    /// Enables or disables the effect with the `name`.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        let idx = self
+            .position(name)
+            .ok_or_else(|| format!("unknown post effect {:?}", name))?;
+        let was = std::mem::replace(&mut self.effects[idx].enabled, enabled);
+
+        if last_drawing(&self.effects).is_none() {
+            self.effects[idx].enabled = was;
+            return Err("no enabled effect would draw the final image".to_string());
+        }
+        Ok(())
+    }
+
+    /// Enables the effects with the `names` in the specified order and disables
+    /// the other ones (which are moved after them).
+    pub fn configure(&mut self, names: &[&str]) -> Result<(), String> {
+        configure(&mut self.effects, names)
+    }
+
+    /// Returns the effect of type `T` if it is in the chain.
+    pub fn effect<T: PostEffect>(&self) -> Option<&T> {
+        self.effects
+            .iter()
+            .find_map(|x| x.effect.downcast_ref::<T>())
+    }
+
+    /// Returns the effect of type `T` if it is in the chain.
+    pub fn effect_mut<T: PostEffect>(&mut self) -> Option<&mut T> {
+        self.effects
+            .iter_mut()
+            .find_map(|x| x.effect.downcast_mut::<T>())
+    }
+
+    /// Recreates the intermediate buffers and notifies all effects (including
+    /// the disabled ones) about the new HDR buffer.
+    pub fn dimensions_changed(&mut self, hdr: &PostImage, dims: [u32; 2]) {
+        let device = self.passes.intermediate.device().clone();
+        self.buffers = self.create_buffers(device, dims);
+
+        for entry in self.effects.iter_mut() {
+            entry.effect.dimensions_changed(hdr, dims);
+        }
+    }
+
+    /// Creates a framebuffer drawing into the swapchain image (or the offscreen
+    /// image of the headless renderer).
+    pub fn create_framebuffer<I>(
+        &self,
+        final_image: I,
+    ) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, FramebufferCreationError>
+    where
+        I: ImageViewAbstract + Send + Sync + 'static,
+    {
+        Ok(Arc::new(
+            Framebuffer::start(self.passes.present.clone())
+                .add(final_image)?
+                .build()?,
+        ))
+    }
+
+    /// Records all enabled effects. The last effect that draws an image draws
+    /// into the `present` framebuffer (created by `create_framebuffer`). Its
+    /// render pass is left open, so overlays can be drawn over the final image,
+    /// and must be ended by the caller.
+    pub fn record(
+        &self,
+        hdr: PostImage,
+        present: Arc<dyn FramebufferAbstract + Send + Sync>,
+        frame: &PostFrame,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        let last = last_drawing(&self.effects).expect("no post effect draws the final image");
+        let mut input = PostInput {
+            color: hdr,
+            bloom: None,
+            frame,
+        };
+        let mut buffer = 0;
+
+        for (idx, entry) in self.effects.iter().enumerate().take(last + 1) {
+            if !entry.enabled {
+                continue;
+            }
+
+            let effect = &entry.effect;
+            let name = entry.marker;
+
+            if idx == last {
+                cmd.begin_render_pass(
+                    present.clone(),
+                    SubpassContents::Inline,
+                    vec![ClearValue::None],
+                )
+                .unwrap();
+                cmd.debug_marker_begin(name, [1.0, 0.3, 0.0, 1.0]).unwrap();
+                effect.record(&mut input, PostOutput::Present, cmd);
+                cmd.debug_marker_end().unwrap();
+            } else if effect.draws() {
+                let target = &self.buffers[buffer];
+
+                cmd.debug_marker_begin(name, [0.5, 0.5, 1.0, 1.0]).unwrap();
+                cmd.begin_render_pass(
+                    target.framebuffer.clone(),
+                    SubpassContents::Inline,
+                    self.graph.clear_values(),
+                )
+                .unwrap();
+                effect.record(&mut input, PostOutput::Intermediate, cmd);
+                cmd.end_render_pass().unwrap();
+                cmd.debug_marker_end().unwrap();
+
+                // the next effect reads this buffer and draws into the other one
+                input.color = target.image.clone();
+                buffer = 1 - buffer;
+            } else {
+                cmd.debug_marker_begin(name, [1.0, 0.8, 0.3, 1.0]).unwrap();
+                effect.record(&mut input, PostOutput::None, cmd);
+                cmd.debug_marker_end().unwrap();
+            }
+        }
+    }
+}
+
+fn enabled_names(effects: &[Entry]) -> Vec<&'static str> {
+    effects
+        .iter()
+        .filter(|x| x.enabled)
+        .map(|x| x.effect.name())
+        .collect()
+}
+
+/// Returns the index of the last enabled effect that draws an image.
+fn last_drawing(effects: &[Entry]) -> Option<usize> {
+    effects.iter().rposition(|x| x.enabled && x.effect.draws())
+}
+
+fn configure(effects: &mut Vec<Entry>, names: &[&str]) -> Result<(), String> {
+    // indices of the effects in the new order, the listed ones first
+    let mut order = Vec::with_capacity(effects.len());
+    for name in names {
+        let idx = effects
+            .iter()
+            .position(|x| x.effect.name() == *name)
+            .ok_or_else(|| format!("unknown post effect {:?}", name))?;
+        if order.contains(&idx) {
+            return Err(format!("post effect {:?} is listed twice", name));
+        }
+        order.push(idx);
+    }
+    if !order.iter().any(|x| effects[*x].effect.draws()) {
+        return Err("no listed effect draws the final image".to_string());
+    }
+
+    let listed = order.len();
+    let rest = (0..effects.len())
+        .filter(|x| !order.contains(x))
+        .collect::<Vec<_>>();
+    order.extend(rest);
+
+    let mut entries = std::mem::take(effects)
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    for (position, idx) in order.into_iter().enumerate() {
+        effects.push(Entry {
+            enabled: position < listed,
+            ..entries[idx].take().unwrap()
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::post::{configure, enabled_names, last_drawing, Entry};
+    use crate::render::post::{PostEffect, PostInput, PostOutput};
+    use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+
+    struct Dummy(&'static str, bool);
+
+    impl PostEffect for Dummy {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn draws(&self) -> bool {
+            self.1
+        }
+
+        fn record(
+            &self,
+            _: &mut PostInput,
+            _: PostOutput,
+            _: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        ) {
+        }
+    }
+
+    fn chain() -> Vec<Entry> {
+        [
+            ("bloom", false),
+            ("tonemap", true),
+            ("grain", true),
+            ("fxaa", true),
+        ]
+        .iter()
+        .map(|(name, draws)| Entry::new(Box::new(Dummy(name, *draws))))
+        .collect()
+    }
+
+    #[test]
+    fn configure_reorders_and_disables() {
+        let mut effects = chain();
+        configure(&mut effects, &["bloom", "tonemap", "fxaa"]).unwrap();
+        assert_eq!(enabled_names(&effects), ["bloom", "tonemap", "fxaa"]);
+        assert_eq!(last_drawing(&effects), Some(2));
+
+        configure(&mut effects, &["tonemap", "grain"]).unwrap();
+        assert_eq!(enabled_names(&effects), ["tonemap", "grain"]);
+        assert_eq!(last_drawing(&effects), Some(1));
+        assert_eq!(effects.len(), 4);
+    }
+
+    #[test]
+    fn configure_rejects_invalid_chains() {
+        let mut effects = chain();
+        assert!(configure(&mut effects, &["tonemap", "sharpen"]).is_err());
+        assert!(configure(&mut effects, &["fxaa", "fxaa"]).is_err());
+        assert_eq!(
+            enabled_names(&effects),
+            ["bloom", "tonemap", "grain", "fxaa"]
+        );
+
+        // bloom alone does not draw the final image
+        assert!(configure(&mut effects, &["bloom"]).is_err());
+        assert_eq!(
+            enabled_names(&effects),
+            ["bloom", "tonemap", "grain", "fxaa"]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Precision of floating point render targets.
+//!
+//! All floating point targets use 16-bit floats by default, which is enough
+//! for HDR colors and halves the bandwidth compared to 32-bit floats. Shaders
+//! writing into these targets clamp their outputs to `HALF_MAX` so that very
+//! bright pixels don't overflow to infinity. 32-bit floats are useful when
+//! debugging precision problems.
+
+use std::str::FromStr;
+use vulkano::format::Format;
+
+/// Bits per channel of a floating point render target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Precision {
+    Half,
+    Full,
+}
+
+impl Precision {
+    /// Returns the four channel format with this precision (three component
+    /// float formats are not renderable on most GPUs).
+    pub fn rgba(self) -> Format {
+        match self {
+            Precision::Half => Format::R16G16B16A16Sfloat,
+            Precision::Full => Format::R32G32B32A32Sfloat,
+        }
+    }
+
+    /// Returns the single channel format with this precision.
+    pub fn r(self) -> Format {
+        match self {
+            Precision::Half => Format::R16Sfloat,
+            Precision::Full => Format::R32Sfloat,
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    /// Parses the number of bits per channel (`16` or `32`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "16" => Ok(Precision::Half),
+            "32" => Ok(Precision::Full),
+            _ => Err(format!("invalid precision {:?} (expected 16 or 32)", s)),
+        }
+    }
+}
+
+/// Precision of each floating point render target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TargetPrecision {
+    /// Lit scene before tonemapping.
+    pub hdr: Precision,
+    /// Downsample chain of the bloom.
+    pub bloom: Precision,
+    /// Accumulation and revealage buffers of transparent objects.
+    pub transparency: Precision,
+}
+
+impl Default for TargetPrecision {
+    fn default() -> Self {
+        Self {
+            hdr: Precision::Half,
+            bloom: Precision::Half,
+            transparency: Precision::Half,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::precision::Precision;
+    use vulkano::format::Format;
+
+    #[test]
+    fn parses_bits_per_channel() {
+        assert_eq!("16".parse(), Ok(Precision::Half));
+        assert_eq!("32".parse(), Ok(Precision::Full));
+        assert!("64".parse::<Precision>().is_err());
+        assert_eq!(Precision::Full.rgba(), Format::R32G32B32A32Sfloat);
+        assert_eq!(Precision::Half.r(), Format::R16Sfloat);
+    }
+}
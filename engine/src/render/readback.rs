@@ -0,0 +1,271 @@
+//! Asynchronous copies of GPU resources to the CPU.
+//!
+//! Features that need data rendered by the GPU (screenshots, depth queries,
+//! picking) schedule a copy into a host visible buffer while recording a frame
+//! and provide a callback. The callback is invoked at the start of a later frame,
+//! once the frame that recorded the copy was submitted at least `latency` frames
+//! ago and its fence has signaled, so reading the data never stalls the
+//! render-loop. Buffers of finished readbacks are kept and reused for readbacks
+//! of the same size.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::image::ImageAccess;
+
+/// Maximal number of unused buffers kept for future readbacks.
+const MAX_FREE_BUFFERS: usize = 8;
+
+/// Data of a finished readback passed to its callback.
+pub struct ReadbackResult<'a> {
+    /// Index of the frame that recorded the copy.
+    pub frame: u64,
+    /// Number of frames between recording of the copy and the callback.
+    pub latency: u64,
+    /// Copied bytes.
+    pub data: &'a [u8],
+}
+
+/// Callback invoked with the data of a finished readback.
+pub type ReadbackCallback = Box<dyn FnOnce(ReadbackResult) + Send>;
+
+/// Items recorded in frames waiting until they can be completed, oldest first.
+struct FrameQueue<T> {
+    items: VecDeque<(u64, T)>,
+}
+
+impl<T> FrameQueue<T> {
+    fn new() -> Self {
+        Self {
+            items: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, frame: u64, item: T) {
+        self.items.push_back((frame, item));
+    }
+
+    /// Removes items recorded at least `latency` frames before the `frame` for
+    /// which `ready` returns `true`. Frames finish in order, so the search stops
+    /// at the first item that is not ready.
+    fn pop_ready(
+        &mut self,
+        frame: u64,
+        latency: u64,
+        mut ready: impl FnMut(&T) -> bool,
+    ) -> Vec<(u64, T)> {
+        let mut result = Vec::new();
+        while let Some((recorded, item)) = self.items.front() {
+            if frame - recorded < latency || !ready(item) {
+                break;
+            }
+            result.extend(self.items.pop_front());
+        }
+        result
+    }
+
+    /// Removes all items recorded in the `frame`.
+    fn remove_frame(&mut self, frame: u64) -> usize {
+        let before = self.items.len();
+        self.items.retain(|(recorded, _)| *recorded != frame);
+        before - self.items.len()
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+struct Pending {
+    size: usize,
+    buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    callback: ReadbackCallback,
+}
+
+/// Schedules copies of GPU resources into host visible buffers and invokes
+/// callbacks with the copied data when the copies are finished.
+pub struct ReadbackManager {
+    device: Arc<Device>,
+    /// Minimal number of frames between recording of a copy and its callback.
+    latency: u64,
+    /// Index of the frame that is being recorded.
+    frame: u64,
+    pending: FrameQueue<Pending>,
+    /// Unused buffers with their sizes.
+    free: Vec<(usize, Arc<CpuAccessibleBuffer<[u8]>>)>,
+}
+
+impl ReadbackManager {
+    /// Creates a manager that invokes callbacks at least `latency` frames after
+    /// the copy was recorded (usually the number of frames in flight).
+    pub fn new(device: Arc<Device>, latency: u64) -> Self {
+        Self {
+            device,
+            latency: latency.max(1),
+            frame: 0,
+            pending: FrameQueue::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the frame that is being recorded.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns the number of readbacks waiting for their frames to finish.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns an unused buffer of `size` bytes.
+    fn buffer(&mut self, size: usize) -> Arc<CpuAccessibleBuffer<[u8]>> {
+        match self.free.iter().position(|(x, _)| *x == size) {
+            Some(i) => self.free.swap_remove(i).1,
+            None => CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::transfer_destination(),
+                true,
+                (0..size).map(|_| 0u8),
+            )
+            .expect("cannot create readback buffer"),
+        }
+    }
+
+    /// Records a copy of the `image` (`size` bytes) into `cmd` and schedules the
+    /// `callback` to be invoked with the copied data once the frame finishes.
+    pub fn read_image<I>(
+        &mut self,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: I,
+        size: usize,
+        callback: ReadbackCallback,
+    ) where
+        I: ImageAccess + Send + Sync + 'static,
+    {
+        let buffer = self.buffer(size);
+        cmd.copy_image_to_buffer(image, buffer.clone())
+            .expect("cannot record readback copy");
+
+        self.push(size, buffer, callback);
+    }
+
+    /// Records a copy of the region of the first layer and mip level of `image`
+    /// starting at `offset` with `extent` pixels into a buffer of `size` bytes.
+    /// The `callback` is invoked when the frame of the command buffer finishes.
+    pub fn read_image_region<I>(
+        &mut self,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: I,
+        offset: [u32; 2],
+        extent: [u32; 2],
+        size: usize,
+        callback: ReadbackCallback,
+    ) where
+        I: ImageAccess + Send + Sync + 'static,
+    {
+        let buffer = self.buffer(size);
+        cmd.copy_image_to_buffer_dimensions(
+            image,
+            buffer.clone(),
+            [offset[0], offset[1], 0],
+            [extent[0], extent[1], 1],
+            0,
+            1,
+            0,
+        )
+        .expect("cannot record readback copy");
+
+        self.push(size, buffer, callback);
+    }
+
+    fn push(
+        &mut self,
+        size: usize,
+        buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+        callback: ReadbackCallback,
+    ) {
+        self.pending.push(
+            self.frame,
+            Pending {
+                size,
+                buffer,
+                callback,
+            },
+        );
+    }
+
+    /// Starts a new frame and invokes callbacks of finished readbacks. Called by
+    /// the renderer at the start of each frame after finished frames were cleaned up.
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+
+        // buffers are locked by the GPU until the fence of their frame signals
+        let finished = self
+            .pending
+            .pop_ready(self.frame, self.latency, |x| x.buffer.read().is_ok());
+
+        for (recorded, pending) in finished {
+            {
+                let data = pending.buffer.read().expect("cannot read readback buffer");
+                (pending.callback)(ReadbackResult {
+                    frame: recorded,
+                    latency: self.frame - recorded,
+                    data: &data,
+                });
+            }
+
+            if self.free.len() < MAX_FREE_BUFFERS {
+                self.free.push((pending.size, pending.buffer));
+            }
+        }
+    }
+
+    /// Drops readbacks recorded in the current frame without invoking their
+    /// callbacks. Called when the frame could not be submitted.
+    pub fn cancel_frame(&mut self) -> usize {
+        self.pending.remove_frame(self.frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::readback::FrameQueue;
+
+    #[test]
+    fn pops_items_after_latency() {
+        let mut queue = FrameQueue::new();
+        queue.push(0, "a");
+        queue.push(0, "b");
+        queue.push(1, "c");
+
+        assert!(queue.pop_ready(1, 2, |_| true).is_empty());
+        assert_eq!(queue.pop_ready(2, 2, |_| true), vec![(0, "a"), (0, "b")]);
+        assert_eq!(queue.pop_ready(3, 2, |_| true), vec![(1, "c")]);
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn waits_for_unfinished_items_in_order() {
+        let mut queue = FrameQueue::new();
+        queue.push(0, 1);
+        queue.push(1, 2);
+
+        // the older item is still used by the GPU, so the newer one must wait
+        assert!(queue.pop_ready(5, 1, |x| *x == 2).is_empty());
+        assert_eq!(queue.pop_ready(5, 1, |_| true), vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn removes_items_of_cancelled_frame() {
+        let mut queue = FrameQueue::new();
+        queue.push(0, 1);
+        queue.push(1, 2);
+        queue.push(1, 3);
+
+        assert_eq!(queue.remove_frame(1), 2);
+        assert_eq!(queue.pop_ready(2, 1, |_| true), vec![(0, 1)]);
+    }
+}
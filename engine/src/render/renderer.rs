@@ -0,0 +1,671 @@
+//! *Swapchain* creation & render-loop.
+
+use crate::frame_stats::{self, FrameEvent};
+use crate::render::bloom::{Bloom, BloomSettings};
+use crate::render::fxaa::{FxaaQuality, FXAA};
+#[cfg(feature = "editor")]
+use crate::render::object_id::ObjectIdPicker;
+#[cfg(feature = "editor")]
+use crate::render::objects::ObjectId;
+use crate::render::pbr::PBRDeffered;
+use crate::render::precision::TargetPrecision;
+use crate::render::readback::ReadbackManager;
+use crate::render::vulkan::VulkanState;
+use crate::render::Frame;
+use crate::resources::swap;
+use crate::{GameState, RendererConfiguration};
+use crossbeam::channel::{bounded, Receiver, Sender};
+use image::RgbaImage;
+use log::debug;
+use log::error;
+use log::trace;
+use log::warn;
+use smallvec::SmallVec;
+use std::str::FromStr;
+use std::sync::Arc;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::render_pass::FramebufferAbstract;
+use vulkano::swapchain;
+use vulkano::swapchain::{
+    Capabilities, CapabilitiesError, ColorSpace, FullscreenExclusive, PresentMode,
+    SupportedPresentModes, Surface, Swapchain, SwapchainCreationError,
+};
+use vulkano::sync::{FlushError, GpuFuture, SharingMode};
+use winit::window::{Window, WindowId};
+
+/// Preferred present mode of the swapchain. Modes that are not supported by
+/// the surface fall back to `Fifo`, which is supported everywhere.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PresentModePreference {
+    /// Frames are presented on vertical blank and a newer frame replaces the
+    /// one waiting in the queue (low latency without tearing).
+    Mailbox,
+    /// Frames are presented on vertical blank in the order they were rendered
+    /// (vsync, the frame rate is limited by the display).
+    Fifo,
+    /// Frames are presented immediately (lowest latency, may tear).
+    Immediate,
+}
+
+impl Default for PresentModePreference {
+    fn default() -> Self {
+        PresentModePreference::Mailbox
+    }
+}
+
+impl FromStr for PresentModePreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mailbox" => Ok(PresentModePreference::Mailbox),
+            "fifo" | "vsync" => Ok(PresentModePreference::Fifo),
+            "immediate" => Ok(PresentModePreference::Immediate),
+            _ => Err(format!(
+                "invalid present mode {:?} (expected mailbox, fifo or immediate)",
+                s
+            )),
+        }
+    }
+}
+
+impl PresentModePreference {
+    /// Returns the name used by the `present_mode` setting and cvar.
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentModePreference::Mailbox => "mailbox",
+            PresentModePreference::Fifo => "fifo",
+            PresentModePreference::Immediate => "immediate",
+        }
+    }
+
+    /// Returns the preferred mode if it is `supported`, otherwise `Fifo`.
+    fn select(self, supported: SupportedPresentModes) -> PresentMode {
+        match self {
+            PresentModePreference::Mailbox if supported.mailbox => PresentMode::Mailbox,
+            PresentModePreference::Immediate if supported.immediate => PresentMode::Immediate,
+            _ => PresentMode::Fifo,
+        }
+    }
+}
+
+/// All possible errors that can happen while creating [`RendererState`](struct.RendererState.html).
+#[derive(Debug)]
+pub enum RendererStateError {
+    CapabilitiesError(CapabilitiesError),
+    CannotFindFormat,
+    CannotCreateSwapchain(SwapchainCreationError),
+}
+
+/// Struct that manages the process of rendering. It contains functions related
+/// to render-loop processing, reactions to incoming system messages such as
+/// *swapchain* recreation caused by resolution change.
+///
+/// This class does not perform any rendering or command buffer recording, it only
+/// provides low-level wrapper around render-loop.
+pub struct RendererState {
+    /// The `Device` that is used for rendering.
+    device: Arc<Device>,
+    /// The `Queue` that will the recorded primary command buffer be submitted to.
+    graphical_queue: Arc<Queue>,
+    /// Current `Swapchain` object.
+    swapchain: Arc<Swapchain<Window>>,
+    /// Vector of *swapchain* images.
+    swapchain_images: Vec<Arc<ImageView<Arc<SwapchainImage<Window>>>>>,
+    /// Vector of current framebuffers.
+    framebuffers: SmallVec<[Arc<dyn FramebufferAbstract + Send + Sync>; 4]>,
+    /// Whether the vector of framebuffers is out-of-date. Framebuffers may become out-of-date
+    /// when resolution of the application changes and need to be recreated before rendering
+    /// can continue. They are also out-of-date the first time this object is constructed.
+    should_recreate_swapchain: bool,
+    /// Future of when the last frame finished rendering & is presented on the screen.
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+    /// Current rendering path.
+    pub render_path: PBRDeffered,
+    /// Senders waiting for the next rendered frame to be captured.
+    screenshot_requests: Vec<Sender<RgbaImage>>,
+    /// Copies of GPU resources to the CPU waiting for their frames to finish.
+    pub readbacks: ReadbackManager,
+    /// Pixel of the object ID buffer to read after the next frame.
+    #[cfg(feature = "editor")]
+    picker: ObjectIdPicker,
+    /// Precision of render targets the render path was created with.
+    precision: TargetPrecision,
+    /// Preferred present mode of the swapchain.
+    present_mode: PresentModePreference,
+    /// Whether this renderer draws into the main window. Only the main renderer
+    /// releases retired resources (once per iteration of the event loop).
+    primary: bool,
+}
+
+impl RendererState {
+    /// Creates a new renderer from provided vulkan state struct.
+    pub fn new(
+        vulkan: &VulkanState,
+        conf: &RendererConfiguration,
+    ) -> Result<Self, RendererStateError> {
+        Self::with_surface(
+            vulkan,
+            vulkan.surface(),
+            conf.bloom,
+            conf.precision,
+            conf.fxaa_quality,
+            conf.present_mode,
+            true,
+        )
+    }
+
+    /// Creates a renderer of another window (see `VulkanState::create_window`)
+    /// with the settings of this renderer. Its render passes are compatible with
+    /// the render passes of this renderer, so objects can use the same pipelines.
+    pub fn for_window(
+        &self,
+        vulkan: &VulkanState,
+        surface: Arc<Surface<Window>>,
+    ) -> Result<Self, RendererStateError> {
+        let post = &self.render_path.post;
+        let mut renderer = Self::with_surface(
+            vulkan,
+            surface,
+            post.effect::<Bloom>()
+                .map_or_else(BloomSettings::default, |x| x.settings),
+            self.precision,
+            post.effect::<FXAA>()
+                .map_or_else(FxaaQuality::default, |x| x.quality()),
+            self.present_mode,
+            false,
+        )?;
+        renderer.render_path.copy_settings(&self.render_path);
+        Ok(renderer)
+    }
+
+    fn with_surface(
+        vulkan: &VulkanState,
+        surface: Arc<Surface<Window>>,
+        bloom: BloomSettings,
+        precision: TargetPrecision,
+        fxaa_quality: FxaaQuality,
+        present_mode: PresentModePreference,
+        primary: bool,
+    ) -> Result<Self, RendererStateError> {
+        let device = vulkan.device();
+        let graphical_queue = vulkan.graphical_queue();
+
+        let caps: Capabilities = surface
+            .capabilities(device.physical_device())
+            .map_err(RendererStateError::CapabilitiesError)?;
+
+        let dimensions = caps.current_extent.unwrap_or(caps.max_image_extent);
+        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+
+        // to render color correctly and compute in linear color space we must
+        // request the vulkan explicitly. here we choose a first swapchain format
+        // that has sRGB non-linear color space.
+        let format = caps
+            .supported_formats
+            .iter()
+            .find(|(f, _)| *f == Format::B8G8R8A8Srgb)
+            .map(|(f, _)| *f)
+            .ok_or(RendererStateError::CannotFindFormat)?;
+
+        debug!("Chosen {:?} format for swapchain buffers.", format);
+
+        // fifo is the fallback as it should be supported on all configurations
+        let preference = present_mode;
+        let present_mode = preference.select(caps.present_modes);
+        debug!("Chosen {:?} present mode for swapchain.", present_mode);
+
+        // lets create a swapchain and vector of created swapchain images
+        let (swapchain, swapchain_images) = Swapchain::start(device.clone(), surface)
+            .num_images(caps.min_image_count)
+            .format(format)
+            .dimensions(dimensions)
+            .layers(1)
+            // transfer source is needed to capture screenshots
+            .usage(ImageUsage {
+                transfer_source: true,
+                ..ImageUsage::color_attachment()
+            })
+            .sharing_mode(SharingMode::Exclusive)
+            .transform(caps.current_transform)
+            .composite_alpha(alpha)
+            .present_mode(present_mode)
+            .fullscreen_exclusive(FullscreenExclusive::Default)
+            .clipped(true)
+            .color_space(ColorSpace::SrgbNonLinear)
+            .build()
+            .map_err(RendererStateError::CannotCreateSwapchain)?;
+
+        let render_path = PBRDeffered::new(
+            graphical_queue.clone(),
+            device.clone(),
+            swapchain.format(),
+            swapchain.dimensions(),
+            bloom,
+            precision,
+            fxaa_quality,
+        );
+
+        let swapchain_images = swapchain_imgs_to_views(swapchain_images);
+        let framebuffers = match swapchain_images
+            .iter()
+            .map(|it| render_path.create_framebuffer(it.clone()))
+            .collect()
+        {
+            Ok(t) => t,
+            Err(e) => panic!("cannot (re)create framebuffers: {}", e),
+        };
+
+        // readbacks are completed once the frames that may still be in flight finish
+        let readbacks = ReadbackManager::new(device.clone(), swapchain_images.len() as u64);
+
+        // todo: move RenderPath creation to constructor params, or something
+        Ok(RendererState {
+            previous_frame_end: now(device.clone()),
+            should_recreate_swapchain: true,
+            framebuffers,
+            render_path,
+            screenshot_requests: Vec::new(),
+            readbacks,
+            #[cfg(feature = "editor")]
+            picker: ObjectIdPicker::default(),
+            swapchain_images,
+            swapchain,
+            device,
+            graphical_queue,
+            precision,
+            present_mode: preference,
+            primary,
+        })
+    }
+
+    /// Replaces the render path with a new one with all pipelines rebuilt (eg.
+    /// after shaders changed) and returns the previous one. Settings changed at
+    /// run-time are copied to the new render path, but pipelines of the objects
+    /// must be replaced by the caller (see `PBRDeffered::replacement_pipeline`).
+    pub fn recreate_render_path(&mut self) -> PBRDeffered {
+        // resources of the previous render path may still be used by the gpu
+        if let Some(previous) = self.previous_frame_end.take() {
+            match previous.then_signal_fence_and_flush() {
+                Ok(f) => {
+                    if let Err(e) = f.wait(None) {
+                        error!("Cannot wait for the previous frame {:?}", e);
+                    }
+                }
+                Err(e) => error!("Cannot flush the previous frame {:?}", e),
+            }
+        }
+        self.previous_frame_end = now(self.device.clone());
+
+        let previous = &self.render_path;
+        let bloom = previous
+            .post
+            .effect::<Bloom>()
+            .map_or_else(BloomSettings::default, |x| x.settings);
+        let fxaa_quality = previous
+            .post
+            .effect::<FXAA>()
+            .map_or_else(FxaaQuality::default, |x| x.quality());
+
+        let mut render_path = PBRDeffered::new(
+            self.graphical_queue.clone(),
+            self.device.clone(),
+            self.swapchain.format(),
+            self.swapchain.dimensions(),
+            bloom,
+            self.precision,
+            fxaa_quality,
+        );
+        render_path.copy_settings(previous);
+
+        // framebuffers are created from the render passes of the render path
+        self.should_recreate_swapchain = true;
+        std::mem::replace(&mut self.render_path, render_path)
+    }
+
+    /// Renders single frame. This function is called from render-loop.
+    ///
+    /// This function updates internal state of this struct, it is responsible
+    /// for freeing unused resources from previous frames. The frame waits for
+    /// `uploads` (submitted by `resources::transfer::UploadScheduler`) before
+    /// it is rendered.
+    pub fn render_frame(
+        &mut self,
+        game_state: &GameState,
+        uploads: Option<Box<dyn GpuFuture + Send>>,
+    ) {
+        // clean-up all resources from the previous frame
+        if let Some(t) = self.previous_frame_end.as_mut() {
+            t.cleanup_finished();
+        }
+
+        // drop replaced resources that can no longer be used by frames in flight
+        // (windows are rendered one after another, so once per all of them)
+        if self.primary {
+            let released = swap::next_frame(self.swapchain_images.len());
+            if released > 0 {
+                trace!("Released {} retired resources", released);
+            }
+        }
+
+        // invoke callbacks of readbacks whose frames have finished
+        self.readbacks.next_frame();
+
+        // minimized window has no area to render into
+        let size = self.swapchain.surface().window().inner_size();
+        if size.width == 0 || size.height == 0 {
+            self.wait_for_uploads(uploads);
+            return;
+        }
+
+        // if framebuffers are out-of date, we need to recreate them.
+        if self.should_recreate_swapchain {
+            self.recreate_swapchain();
+            self.recreate_framebuffers();
+
+            // force recreation of internal buffers and state of the current
+            // render path
+            self.render_path
+                .dimensions_changed(self.swapchain.dimensions());
+
+            self.should_recreate_swapchain = false;
+        }
+
+        // acquire next image from swapchain that will be used for rendering. if the
+        // suboptimal flag is true we try to recreate the swapchain after this frame.
+        //
+        // if the acquire operation fails, we recreate swapchain right away and skip
+        // rendering of this frame
+        let (idx, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("Cannot acquire next image {:?}. Recreating swapchain...", e);
+                    // framebuffers must be recreated together with the swapchain
+                    self.should_recreate_swapchain = true;
+                    self.wait_for_uploads(uploads);
+                    return;
+                }
+            };
+
+        if suboptimal {
+            self.should_recreate_swapchain = true;
+        }
+
+        // build primary command buffer by distributing command buffer
+        // recording into multiple threads as parallel job
+        let mut frame = Frame {
+            render_path: &mut self.render_path,
+            game_state,
+            framebuffer: self.framebuffers[idx].clone(),
+            builder: Some(
+                AutoCommandBufferBuilder::primary(
+                    self.device.clone(),
+                    self.graphical_queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
+                )
+                .unwrap(),
+            ),
+        };
+
+        // let frame create and records it's command buffer(s).
+        let primary_cb = frame.build();
+
+        // wait for image to be available and then present drawn the image
+        // to screen.
+        let previous = self.previous_frame_end.take().unwrap();
+        let previous = match uploads {
+            Some(uploads) => previous.join(uploads).boxed(),
+            None => previous,
+        };
+        let future = previous
+            .join(acquire_future)
+            .then_execute(self.graphical_queue.clone(), primary_cb)
+            .unwrap()
+            .boxed();
+
+        // if someone requested a screenshot we copy the rendered image
+        // to cpu accessible buffer before presenting it
+        let future = if self.screenshot_requests.is_empty() {
+            future
+        } else {
+            let copy_cb = self.copy_swapchain_image(idx);
+            future
+                .then_execute(self.graphical_queue.clone(), copy_cb)
+                .unwrap()
+                .boxed()
+        };
+
+        // if someone picked an object we copy the pixel of the object ID buffer
+        // (it has the dimensions of the swapchain)
+        #[cfg(feature = "editor")]
+        let future = match self.picker.take_request() {
+            Some(pixel) if self.inside_swapchain(pixel) => {
+                let copy_cb = self.copy_object_id(pixel, game_state);
+                future
+                    .then_execute(self.graphical_queue.clone(), copy_cb)
+                    .unwrap()
+                    .boxed()
+            }
+            _ => future,
+        };
+
+        let future = future
+            .then_swapchain_present(self.graphical_queue.clone(), self.swapchain.clone(), idx)
+            .then_signal_fence_and_flush();
+
+        // depending on the completion state of the submitted command buffer either
+        // return to continue to next frame, or report and error
+        match future {
+            Ok(f) => {
+                self.previous_frame_end = Some(f.boxed());
+            }
+            Err(FlushError::OutOfDate) => {
+                self.should_recreate_swapchain = true;
+                self.previous_frame_end = now(self.device.clone());
+                self.readbacks.cancel_frame();
+            }
+            Err(e) => {
+                error!("Error occurred during rendering a frame {:?}", e);
+                self.previous_frame_end = now(self.device.clone());
+                self.readbacks.cancel_frame();
+            }
+        }
+    }
+
+    /// Returns the id of the window this renderer draws into.
+    pub fn window_id(&self) -> WindowId {
+        self.swapchain.surface().window().id()
+    }
+
+    /// Returns the preferred present mode of the swapchain.
+    pub fn present_mode(&self) -> PresentModePreference {
+        self.present_mode
+    }
+
+    /// Changes the preferred present mode (eg. `Fifo` to enable vsync). The
+    /// swapchain is recreated with the mode before the next frame.
+    pub fn set_present_mode(&mut self, present_mode: PresentModePreference) {
+        self.present_mode = present_mode;
+        self.should_recreate_swapchain = true;
+    }
+
+    /// Recreates the swapchain (in the physical size of the window) before the
+    /// next frame. Called when the size or the scale factor of the window changes.
+    pub fn window_resized(&mut self) {
+        self.should_recreate_swapchain = true;
+    }
+
+    /// Makes the next frame wait for `uploads` when the current frame could not
+    /// be rendered.
+    fn wait_for_uploads(&mut self, uploads: Option<Box<dyn GpuFuture + Send>>) {
+        if let Some(uploads) = uploads {
+            let previous = self.previous_frame_end.take().unwrap();
+            self.previous_frame_end = Some(previous.join(uploads).boxed());
+        }
+    }
+
+    /// Requests capture of the next rendered frame. The captured image will be
+    /// sent to returned `Receiver` a few frames later, when the frame is finished.
+    pub fn request_screenshot(&mut self) -> Receiver<RgbaImage> {
+        let (tx, rx) = bounded(1);
+        self.screenshot_requests.push(tx);
+        rx
+    }
+
+    /// Returns the object drawn into the pixel at `x`, `y` (in physical pixels
+    /// of the window) and requests the pixel to be read after the next frame.
+    ///
+    /// The object is known a few frames later, when the frame is finished, so
+    /// this should be called each frame while the cursor is over the pixel
+    /// (eg. on hover or while the button is held). Returns `None` when there is
+    /// no object in the pixel or the pixel was not read yet.
+    #[cfg(feature = "editor")]
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<ObjectId> {
+        self.picker.pick([x, y])
+    }
+
+    /// Returns whether the `pixel` is inside the images of the swapchain.
+    #[cfg(feature = "editor")]
+    fn inside_swapchain(&self, pixel: [u32; 2]) -> bool {
+        let [width, height] = self.swapchain.dimensions();
+        pixel[0] < width && pixel[1] < height
+    }
+
+    /// Records a command buffer that copies the `pixel` of the object ID buffer
+    /// to the cpu. The object in it is resolved using objects of `game_state`
+    /// when the frame is finished.
+    #[cfg(feature = "editor")]
+    fn copy_object_id(
+        &mut self,
+        pixel: [u32; 2],
+        game_state: &GameState,
+    ) -> PrimaryAutoCommandBuffer {
+        let callback = self
+            .picker
+            .callback(pixel, game_state.objects.ids().to_vec());
+
+        let mut b = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.graphical_queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        self.readbacks.read_image_region(
+            &mut b,
+            self.render_path.buffers.object_id_buffer.image().clone(),
+            pixel,
+            [1, 1],
+            4,
+            callback,
+        );
+        b.build().unwrap()
+    }
+
+    /// Records a command buffer that copies the *swapchain* image with index `idx`
+    /// to the cpu. The copy is sent to all waiting screenshot receivers when the
+    /// frame is finished.
+    fn copy_swapchain_image(&mut self, idx: usize) -> PrimaryAutoCommandBuffer {
+        let [width, height] = self.swapchain.dimensions();
+        let requests = std::mem::take(&mut self.screenshot_requests);
+
+        let mut b = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.graphical_queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        self.readbacks.read_image(
+            &mut b,
+            self.swapchain_images[idx].image().clone(),
+            (width * height * 4) as usize,
+            Box::new(move |result| {
+                let mut pixels = result.data.to_vec();
+
+                // swapchain images are in BGRA format
+                for px in pixels.chunks_exact_mut(4) {
+                    px.swap(0, 2);
+                }
+
+                let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+                for tx in requests {
+                    tx.send(image.clone()).ok();
+                }
+            }),
+        );
+
+        b.build().unwrap()
+    }
+
+    /// Forces recreation of *swapchain* and it's images. Transitively the *framebuffers*   
+    /// and internal buffers of current render path will be also recreated.
+    pub fn recreate_swapchain(&mut self) {
+        // new dimensions of the swapchain
+        let new_dimensions = self.swapchain.surface().window().inner_size().into();
+        let present_mode = match self
+            .swapchain
+            .surface()
+            .capabilities(self.device.physical_device())
+        {
+            Ok(caps) => self.present_mode.select(caps.present_modes),
+            Err(e) => {
+                warn!("Cannot query present modes {:?}", e);
+                PresentMode::Fifo
+            }
+        };
+
+        let (swapchain, imgs) = match Swapchain::recreate(&self.swapchain)
+            .dimensions(new_dimensions)
+            .present_mode(present_mode)
+            .build()
+        {
+            Ok(r) => r,
+            // This error tends to happen when the user is manually resizing the window.
+            // Simply restarting the loop is the easiest way to fix this issue.
+            Err(SwapchainCreationError::UnsupportedDimensions) => return,
+            Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+        };
+
+        self.swapchain = swapchain;
+        self.swapchain_images = swapchain_imgs_to_views(imgs);
+        frame_stats::record(FrameEvent::SwapchainRecreated);
+    }
+
+    /// Recreates current *framebuffers* by calling `create_framebuffer` method
+    /// on current render path with current *swapchain images*.
+    ///
+    /// This method is called when current *framebuffers* become out-of-date.
+    fn recreate_framebuffers(&mut self) {
+        self.framebuffers = match self
+            .swapchain_images
+            .iter()
+            .map(|it| self.render_path.create_framebuffer(it.clone()))
+            .collect()
+        {
+            Ok(t) => t,
+            Err(e) => panic!("cannot (re)create framebuffers: {}", e),
+        };
+    }
+}
+
+/// Converts a `Vec<SwapchainImage>` to `Vec<ImageView>` without double Arc-ing the
+/// image resource.
+fn swapchain_imgs_to_views(
+    imgs: Vec<Arc<SwapchainImage<Window>>>,
+) -> Vec<Arc<ImageView<Arc<SwapchainImage<Window>>>>> {
+    imgs.into_iter()
+        .map(|x| ImageView::new(x).ok().unwrap())
+        .collect()
+}
+
+/// Creates a **now** GpuFuture wrapped in `Box` and `Option`.
+#[inline]
+fn now(device: Arc<Device>) -> Option<Box<dyn GpuFuture>> {
+    Some(vulkano::sync::now(device).boxed())
+}
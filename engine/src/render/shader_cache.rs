@@ -0,0 +1,313 @@
+//! Shaders compiled ahead of time by the asset server (`glsl2bf`) or at runtime
+//! from their sources.
+//!
+//! Pipelines are built from SPIR-V embedded by `vulkano_shaders` at compile
+//! time. When a shader cache directory is configured, compiled shader assets
+//! found in it are used instead of the embedded code, so shaders can be
+//! changed without recompiling the engine. The interface of the shader
+//! (inputs, outputs, descriptor sets and push constants) is still reflected
+//! from the embedded shader and the cached code must not change it.
+//!
+//! When a shader source directory is configured (and the engine is built with
+//! the `shader-compiler` feature), shaders are compiled from the GLSL sources in
+//! it with `shaderc` instead. Sources that fail to compile fall back to the
+//! shader assets and the embedded code. [`invalidate_changed_shaders`] finds
+//! shaders whose sources (or included files) changed since they were compiled,
+//! so the pipelines using them can be rebuilt (hot-reload).
+
+use crate::frame_stats::{self, FrameEvent};
+use bf::load_bf_from_bytes;
+use bf::shader::Shader;
+use cstr::cstr;
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::{
+    EntryPointAbstract, GraphicsEntryPoint, GraphicsEntryPointAbstract, GraphicsShaderType,
+    ShaderModule,
+};
+
+/// Directory with compiled shader assets.
+static SHADER_CACHE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Shaders from the cache directory by their name, loaded on first use.
+static SHADERS: OnceCell<HashMap<String, Shader>> = OnceCell::new();
+
+/// Directory with GLSL sources of the shaders compiled at runtime.
+static SHADER_SOURCE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Shaders compiled from the sources by their name and definitions.
+static COMPILED: Lazy<Mutex<HashMap<(String, Vec<String>), Compiled>>> =
+    Lazy::new(Default::default);
+
+/// Shader compiled from the sources at runtime.
+struct Compiled {
+    /// Compiled code, `None` if the compilation failed.
+    spirv: Option<Vec<u8>>,
+    /// The source and included files with their modification times at the
+    /// time of the compilation.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Sets the directory where compiled shader assets are looked up. Embedded
+/// shaders are used if this function is never called.
+pub fn set_shader_cache_dir(dir: PathBuf) {
+    if SHADER_CACHE_DIR.set(dir).is_err() {
+        warn!("Shader cache directory can be set only once!");
+    }
+}
+
+/// Sets the directory with GLSL sources (`<name>.glsl`) of the shaders that are
+/// compiled at runtime and take precedence over shader assets and the embedded
+/// shaders.
+pub fn set_shader_source_dir(dir: PathBuf) {
+    if !cfg!(feature = "shader-compiler") {
+        warn!("Engine was built without the shader-compiler feature, shader sources are ignored");
+        return;
+    }
+    if SHADER_SOURCE_DIR.set(dir).is_err() {
+        warn!("Shader source directory can be set only once!");
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+/// Forgets shaders compiled from sources that changed since their compilation
+/// (including sources that failed to compile), so they are compiled again when
+/// the pipelines using them are rebuilt. Returns the number of such shaders.
+pub fn invalidate_changed_shaders() -> usize {
+    let mut compiled = COMPILED.lock();
+    let before = compiled.len();
+    compiled.retain(|_, x| x.files.iter().all(|(path, time)| modified(path) == *time));
+
+    let changed = before - compiled.len();
+    if changed > 0 {
+        info!("{} shaders changed and will be recompiled", changed);
+    }
+    changed
+}
+
+/// Returns the code of the variant of shader `name` with `defines` compiled
+/// from the source directory or `None` when the directory is not set or the
+/// source can't be compiled.
+fn compiled_variant(name: &str, defines: &[&str]) -> Option<Vec<u8>> {
+    let dir = SHADER_SOURCE_DIR.get()?;
+    let key = (
+        name.to_string(),
+        defines.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+    );
+
+    let mut compiled = COMPILED.lock();
+    if let Some(x) = compiled.get(&key) {
+        return x.spirv.clone();
+    }
+
+    // times are taken before the compilation so changes made during it are not missed
+    let path = dir.join(format!("{}.glsl", name));
+    let files = std::iter::once(path.clone())
+        .chain(core::glsl::collect_includes(&path))
+        .map(|x| {
+            let time = modified(&x);
+            (x, time)
+        })
+        .collect();
+
+    let spirv = match compile(&path, defines) {
+        Ok(t) => {
+            info!("Compiled shader {:?} {:?}", name, defines);
+            Some(t)
+        }
+        Err(e) => {
+            error!("Cannot compile shader {:?} {:?}: {}", name, defines, e);
+            None
+        }
+    };
+
+    compiled.insert(
+        key,
+        Compiled {
+            spirv: spirv.clone(),
+            files,
+        },
+    );
+    spirv
+}
+
+/// Compiles the shader at `path` with `defines` to SPIR-V. The stage is
+/// determined from the file name.
+#[cfg(feature = "shader-compiler")]
+fn compile(path: &Path, defines: &[&str]) -> Result<Vec<u8>, String> {
+    use shaderc::{CompileOptions, Compiler, ShaderKind};
+
+    let file_name = path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+    let kind = match bf::shader::ShaderStage::from_file_name(file_name) {
+        Some(bf::shader::ShaderStage::Vertex) => ShaderKind::Vertex,
+        Some(bf::shader::ShaderStage::Fragment) => ShaderKind::Fragment,
+        Some(bf::shader::ShaderStage::Compute) => ShaderKind::Compute,
+        None => return Err("cannot determine shader stage".to_string()),
+    };
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    let mut options = CompileOptions::new().ok_or("cannot create compile options")?;
+    options.set_include_callback(|name, _, including, _| {
+        let path = core::glsl::include_path(Path::new(including), name);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read include {:?}: {}", path, e))?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: path.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+    for define in defines {
+        match define.split_once('=') {
+            Some((name, value)) => options.add_macro_definition(name, Some(value)),
+            None => options.add_macro_definition(define, None),
+        }
+    }
+
+    let mut compiler = Compiler::new().ok_or("cannot create shader compiler")?;
+    let artifact = compiler
+        .compile_into_spirv(
+            &source,
+            kind,
+            &path.to_string_lossy(),
+            "main",
+            Some(&options),
+        )
+        .map_err(|e| e.to_string())?;
+    if artifact.get_num_warnings() > 0 {
+        warn!("{}", artifact.get_warning_messages());
+    }
+
+    Ok(artifact.as_binary_u8().to_vec())
+}
+
+#[cfg(not(feature = "shader-compiler"))]
+fn compile(_: &Path, _: &[&str]) -> Result<Vec<u8>, String> {
+    Err("engine was built without the shader-compiler feature".to_string())
+}
+
+/// Loads all shader assets from the cache directory. Files that are not
+/// shader assets are skipped, so the directory can be the content root.
+fn load_shaders() -> HashMap<String, Shader> {
+    let mut shaders = HashMap::new();
+    let dir = match SHADER_CACHE_DIR.get() {
+        Some(t) => t,
+        None => return shaders,
+    };
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Cannot read shader cache {:?}: {:?}", dir, e);
+            return shaders;
+        }
+    };
+
+    for path in entries
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().map_or(false, |e| e == "bf"))
+    {
+        let shader = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| load_bf_from_bytes(&bytes).ok())
+            .and_then(|file| file.try_to_shader().ok());
+
+        if let Some(shader) = shader {
+            shaders.insert(shader.name.clone(), shader);
+        }
+    }
+
+    info!("Loaded {} shaders from cache {:?}", shaders.len(), dir);
+    shaders
+}
+
+/// Shader module created from the shader cache that replaces an embedded shader.
+pub struct CachedShader {
+    module: Option<Arc<ShaderModule>>,
+}
+
+impl CachedShader {
+    /// Creates the module from the variant without definitions of the cached
+    /// shader `name` (file name of the source without extension). The embedded
+    /// shader is used when the shader is not in the cache.
+    pub fn load(device: Arc<Device>, name: &str) -> Self {
+        Self::load_variant(device, name, &[])
+    }
+
+    /// Creates the module from the variant of the cached shader `name` compiled
+    /// with `defines` (eg. `MASKED`). The shader is compiled from its source
+    /// when the source directory is set. The embedded shader is used when the
+    /// shader or its variant is neither compiled nor in the cache.
+    pub fn load_variant(device: Arc<Device>, name: &str, defines: &[&str]) -> Self {
+        let spirv = compiled_variant(name, defines).or_else(|| {
+            let variant = SHADERS
+                .get_or_init(load_shaders)
+                .get(name)
+                .and_then(|s| s.variant(defines))?;
+            info!("Using cached shader {:?} {:?}", name, defines);
+            Some(variant.spirv.clone())
+        });
+
+        let module = spirv.and_then(|spirv| {
+            // safety: the code was validated by the shader compiler
+            match unsafe { ShaderModule::new(device, &spirv) } {
+                Ok(t) => Some(t),
+                Err(e) => {
+                    warn!("Cannot create module of cached shader {:?}: {:?}", name, e);
+                    None
+                }
+            }
+        });
+
+        Self { module }
+    }
+
+    /// Returns the entry point of the cached module with interface of the
+    /// `embedded` entry point, or the `embedded` entry point itself if the
+    /// shader is not cached.
+    pub fn entry_point<'a>(&'a self, embedded: GraphicsEntryPoint<'a>) -> GraphicsEntryPoint<'a> {
+        // all pipelines get their vertex shader from here, so this is where
+        // the builds of pipelines are counted
+        if let GraphicsShaderType::Vertex = embedded.ty() {
+            frame_stats::record(FrameEvent::PipelineBuilt);
+        }
+
+        let module = match &self.module {
+            Some(t) => t,
+            None => return embedded,
+        };
+
+        // specialization constants of the embedded shader are `'static` only
+        // in the generated code, so they can't be moved to the cached module
+        if !embedded.spec_constants().is_empty() {
+            warn!("Cached shaders with specialization constants are not supported");
+            return embedded;
+        }
+
+        // safety: the cached shader is compiled from the same source as the
+        // embedded one so their interfaces match
+        unsafe {
+            module.graphics_entry_point(
+                cstr!("main"),
+                embedded.descriptor_set_layout_descs().iter().cloned(),
+                *embedded.push_constant_range(),
+                &[],
+                embedded.input().clone(),
+                embedded.output().clone(),
+                embedded.ty(),
+            )
+        }
+    }
+}
@@ -0,0 +1,77 @@
+pub mod vs_deferred_geometry {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs_deferred_geometry.glsl"
+    }
+}
+
+pub mod vs_deferred_geometry_instanced {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs_deferred_geometry_instanced.glsl"
+    }
+}
+
+pub mod vs_deferred_geometry_foliage {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs_deferred_geometry_foliage.glsl"
+    }
+}
+
+#[cfg(not(feature = "editor"))]
+pub mod fs_deferred_geometry {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_deferred_geometry.glsl"
+    }
+}
+
+#[cfg(not(feature = "editor"))]
+pub mod fs_deferred_geometry_masked {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_deferred_geometry.glsl",
+        define: [("MASKED", "")]
+    }
+}
+
+// editor builds write IDs of objects into the object ID buffer
+#[cfg(feature = "editor")]
+pub mod fs_deferred_geometry {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_deferred_geometry.glsl",
+        define: [("OBJECT_ID", "")]
+    }
+}
+
+#[cfg(feature = "editor")]
+pub mod fs_deferred_geometry_masked {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_deferred_geometry.glsl",
+        define: [("MASKED", ""), ("OBJECT_ID", "")]
+    }
+}
+
+pub mod fs_deferred_lighting {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_deferred_lighting.glsl"
+    }
+}
+
+pub mod vs_passtrough {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/vs_passtrough.glsl"
+    }
+}
+
+pub mod fs_tonemap {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/fs_tonemap.glsl"
+    }
+}
@@ -0,0 +1,119 @@
+//! Spot lights of the lighting pass together with their gobo textures.
+
+use crate::assets::Content;
+use crate::render::pools::UniformBufferPoolError;
+use crate::render::ubo::{pack_spot_lights, SpotLight, SpotLightData, MAX_GOBOS, MAX_SPOT_LIGHTS};
+use crate::resources::image::{
+    create_single_pixel_image, create_streamed_image_cached, StreamedImage,
+};
+use bf::uuid::Uuid;
+use cgmath::Point3;
+use log::{error, warn};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::image::view::ImageView;
+use vulkano::image::ImmutableImage;
+use vulkano::sampler::{BorderColor, Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+/// Uniform buffer of spot lights and gobo textures bound to the lighting pass.
+pub struct SpotLights {
+    buffer_pool: CpuBufferPool<[SpotLightData; MAX_SPOT_LIGHTS]>,
+    layout: Arc<DescriptorSetLayout>,
+    sampler: Arc<Sampler>,
+    /// Image bound to gobo slots that are not used.
+    white: Arc<ImageView<Arc<ImmutableImage>>>,
+    /// Gobo textures in order of their indices.
+    gobos: Vec<(Uuid, Arc<StreamedImage>)>,
+}
+
+impl SpotLights {
+    /// Creates the spot lights for descriptor sets with specified `layout`.
+    pub fn new(queue: Arc<Queue>, layout: Arc<DescriptorSetLayout>) -> Self {
+        let device = queue.device().clone();
+        let (white, _) =
+            create_single_pixel_image(queue, [255; 4]).expect("cannot create gobo image");
+        // everything outside of the projected texture is black
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Linear,
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatOpaqueBlack),
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatOpaqueBlack),
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatOpaqueBlack),
+            0.0,
+            1.0,
+            0.0,
+            1000.0,
+        )
+        .expect("cannot create gobo sampler");
+
+        Self {
+            buffer_pool: CpuBufferPool::new(device, BufferUsage::uniform_buffer()),
+            layout,
+            sampler,
+            white: ImageView::new(white).expect("cannot create view from image"),
+            gobos: Vec::new(),
+        }
+    }
+
+    /// Creates gobo textures of the `lights` that were not used before. Gobos
+    /// over the [`MAX_GOBOS`](../ubo/constant.MAX_GOBOS.html) limit are ignored.
+    pub fn update_gobos(&mut self, lights: &[SpotLight], content: &Content) {
+        for uuid in lights.iter().filter_map(|x| x.gobo) {
+            if self.gobo_index(&uuid).is_some() {
+                continue;
+            }
+
+            if self.gobos.len() == MAX_GOBOS {
+                warn!("Too many gobo textures, gobo {} is ignored", uuid);
+                continue;
+            }
+
+            match create_streamed_image_cached(uuid, content) {
+                Ok(image) => self.gobos.push((uuid, image)),
+                Err(e) => error!("Cannot create gobo {}: {:?}", uuid, e),
+            }
+        }
+    }
+
+    fn gobo_index(&self, uuid: &Uuid) -> Option<usize> {
+        self.gobos.iter().position(|(x, _)| x == uuid)
+    }
+
+    /// Creates a descriptor set with the `lights` relative to the `camera`
+    /// position. Returns the set and number of lights in it.
+    pub fn next(
+        &self,
+        lights: &[SpotLight],
+        camera: Point3<f32>,
+    ) -> Result<(Arc<dyn DescriptorSet + Send + Sync>, u32), UniformBufferPoolError> {
+        let (data, count) = pack_spot_lights(lights, camera, |x| self.gobo_index(x));
+        let buffer = self
+            .buffer_pool
+            .next(data)
+            .map_err(UniformBufferPoolError::CannotAllocateBuffer)?;
+
+        // the array is unrolled as the type of the builder changes with each image
+        let gobo = |index: usize| match self.gobos.get(index) {
+            Some((_, image)) => image.view(),
+            None => self.white.clone(),
+        };
+        let set = PersistentDescriptorSet::start(self.layout.clone())
+            .add_buffer(buffer)
+            .and_then(|x| x.enter_array())
+            .and_then(|x| x.add_sampled_image(gobo(0), self.sampler.clone()))
+            .and_then(|x| x.add_sampled_image(gobo(1), self.sampler.clone()))
+            .and_then(|x| x.add_sampled_image(gobo(2), self.sampler.clone()))
+            .and_then(|x| x.add_sampled_image(gobo(3), self.sampler.clone()))
+            .and_then(|x| x.leave_array())
+            .map_err(UniformBufferPoolError::CannotCreateDescriptorSet)?
+            .build()
+            .map_err(UniformBufferPoolError::CannotBuildDescriptorSet)?;
+
+        Ok((Arc::new(set), count))
+    }
+}
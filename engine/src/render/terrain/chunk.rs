@@ -0,0 +1,261 @@
+//! Generation of the grid meshes of terrain chunks.
+
+use crate::render::terrain::heightmap::Heightmap;
+use crate::render::terrain::{Displacement, TerrainSettings};
+use crate::render::vertex::NormalMappedVertex;
+use bf::mesh::Lod;
+use cgmath::Vector3;
+
+/// Vertices and indices of one chunk with all its levels of detail.
+pub struct ChunkGeometry {
+    /// Grid vertices (row by row along the x axis) followed by skirt vertices.
+    /// Positions are relative to the `center`.
+    pub vertices: Vec<NormalMappedVertex>,
+    pub indices: Vec<u16>,
+    pub lods: Vec<Lod>,
+    /// Center of the chunk in the local space of the terrain.
+    pub center: Vector3<f32>,
+    /// Radius of the sphere around the `center` that contains the whole chunk.
+    pub bounding_radius: f32,
+}
+
+/// Generates the mesh of the chunk at `coords` (in chunks along the x and z axis).
+///
+/// Level `n` skips every `2^n`-th row and column of the grid (geo-mipmapping).
+/// Edges of neighbouring chunks with different levels do not match, so each
+/// level has a skirt hanging `skirt_depth` down from its border that hides the
+/// cracks between them.
+pub fn chunk_geometry(
+    heightmap: &Heightmap,
+    settings: &TerrainSettings,
+    coords: [u32; 2],
+) -> ChunkGeometry {
+    let quads = settings.chunk_quads;
+    let grid_quads = (settings.chunks * quads) as f32;
+    let chunk_size = settings.chunk_size();
+    let spacing = settings.size / grid_quads;
+
+    // terrain coordinates of the grid vertex
+    let uv = |i: u32, j: u32| {
+        [
+            (coords[0] * quads + i) as f32 / grid_quads,
+            (coords[1] * quads + j) as f32 / grid_quads,
+        ]
+    };
+
+    let uv_min = uv(0, 0);
+    let uv_max = uv(quads, quads);
+    let (low, high) = heightmap.range(uv_min, uv_max);
+    let mid = (low + high) * 0.5 * settings.height_scale;
+    let center = Vector3::new(
+        (coords[0] as f32 + 0.5) * chunk_size,
+        mid,
+        (coords[1] as f32 + 0.5) * chunk_size,
+    );
+
+    let vertex = |i: u32, j: u32, depth: f32| {
+        let [u, v] = uv(i, j);
+        let (y, normal) = match settings.displacement {
+            Displacement::Cpu => (
+                heightmap.sample(u, v) * settings.height_scale - mid,
+                heightmap.normal(u, v, settings.size, settings.height_scale),
+            ),
+            // the vertex shader adds the height and computes the normal
+            Displacement::Gpu => (-mid, Vector3::unit_y()),
+        };
+
+        NormalMappedVertex {
+            position: [
+                (i as f32 - quads as f32 * 0.5) * spacing,
+                y - depth,
+                (j as f32 - quads as f32 * 0.5) * spacing,
+            ],
+            normal: normal.into(),
+            uv: [u, v],
+            tangent: [1.0, 0.0, 0.0, 0.0],
+        }
+    };
+
+    let index = |i: u32, j: u32| (j * (quads + 1) + i) as u16;
+    let border = border_loop(quads);
+    let skirt_start = (quads + 1) * (quads + 1);
+
+    let mut vertices = (0..=quads)
+        .flat_map(|j| (0..=quads).map(move |i| (i, j)))
+        .map(|(i, j)| vertex(i, j, 0.0))
+        .collect::<Vec<_>>();
+    vertices.extend(
+        border
+            .iter()
+            .map(|&(i, j)| vertex(i, j, settings.skirt_depth)),
+    );
+
+    let mut indices = vec![];
+    let mut lods = vec![];
+    for level in 0..settings.lod_count {
+        let step = 1 << level;
+        let first_index = indices.len() as u32;
+
+        for j in (0..quads).step_by(step as usize) {
+            for i in (0..quads).step_by(step as usize) {
+                let a = index(i, j);
+                let b = index(i, j + step);
+                let c = index(i + step, j);
+                let d = index(i + step, j + step);
+                indices.extend_from_slice(&[a, b, c, c, b, d]);
+            }
+        }
+
+        // the border is walked so that the skirt faces outwards
+        for k in (0..border.len()).step_by(step as usize) {
+            let next = (k + step as usize) % border.len();
+            let (a, b) = (
+                index(border[k].0, border[k].1),
+                index(border[next].0, border[next].1),
+            );
+            let (skirt_a, skirt_b) = (
+                (skirt_start + k as u32) as u16,
+                (skirt_start + next as u32) as u16,
+            );
+            indices.extend_from_slice(&[a, b, skirt_a, b, skirt_b, skirt_a]);
+        }
+
+        lods.push(Lod {
+            first_index,
+            index_count: indices.len() as u32 - first_index,
+            min_coverage: if level + 1 == settings.lod_count {
+                0.0
+            } else {
+                settings.lod_coverage / step as f32
+            },
+        });
+    }
+
+    let half_height = (high - low) * 0.5 * settings.height_scale + settings.skirt_depth;
+    let half_diagonal = chunk_size * std::f32::consts::FRAC_1_SQRT_2;
+
+    ChunkGeometry {
+        vertices,
+        indices,
+        lods,
+        center,
+        bounding_radius: (half_diagonal * half_diagonal + half_height * half_height).sqrt(),
+    }
+}
+
+/// Returns the grid coordinates of the border vertices of a chunk with `quads`
+/// quads per side, counter-clockwise when viewed from above.
+fn border_loop(quads: u32) -> Vec<(u32, u32)> {
+    (0..quads)
+        .map(|i| (i, 0))
+        .chain((0..quads).map(|j| (quads, j)))
+        .chain((0..quads).map(|i| (quads - i, quads)))
+        .chain((0..quads).map(|j| (0, quads - j)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::terrain::chunk::chunk_geometry;
+    use crate::render::terrain::heightmap::Heightmap;
+    use crate::render::terrain::{Displacement, TerrainSettings};
+    use bf::image::{Format, Image};
+    use cgmath::{InnerSpace, Vector3};
+
+    fn heightmap(values: &[u16]) -> Heightmap {
+        let side = (values.len() as f32).sqrt() as u16;
+        Heightmap::from_image(&Image {
+            format: Format::R16,
+            width: side,
+            height: side,
+            mipmap_data: values
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<_>>()
+                .into(),
+        })
+        .unwrap()
+    }
+
+    fn settings(displacement: Displacement) -> TerrainSettings {
+        TerrainSettings {
+            size: 64.0,
+            height_scale: 8.0,
+            chunks: 2,
+            chunk_quads: 8,
+            lod_count: 3,
+            lod_coverage: 0.4,
+            skirt_depth: 1.0,
+            displacement,
+        }
+    }
+
+    #[test]
+    fn generates_levels_with_skirts() {
+        let settings = settings(Displacement::Cpu);
+        let chunk = chunk_geometry(&heightmap(&[0; 4]), &settings, [1, 0]);
+
+        assert_eq!(chunk.vertices.len(), 9 * 9 + 4 * 8);
+        assert_eq!(chunk.center, Vector3::new(48.0, 0.0, 16.0));
+
+        let counts = chunk.lods.iter().map(|x| x.index_count).collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            vec![8 * 8 * 6 + 32 * 6, 4 * 4 * 6 + 16 * 6, 2 * 2 * 6 + 8 * 6]
+        );
+        assert_eq!(chunk.lods[1].first_index, counts[0]);
+
+        let coverages = chunk
+            .lods
+            .iter()
+            .map(|x| x.min_coverage)
+            .collect::<Vec<_>>();
+        assert_eq!(coverages, vec![0.4, 0.2, 0.0]);
+    }
+
+    #[test]
+    fn triangles_face_outwards() {
+        let settings = settings(Displacement::Cpu);
+        let chunk = chunk_geometry(&heightmap(&[0; 4]), &settings, [0, 0]);
+        let position = |i: u16| Vector3::from(chunk.vertices[i as usize].position);
+
+        for triangle in chunk.indices.chunks(3) {
+            let (a, b, c) = (
+                position(triangle[0]),
+                position(triangle[1]),
+                position(triangle[2]),
+            );
+            let normal = (b - a).cross(c - a).normalize();
+            let centroid = (a + b + c) / 3.0;
+
+            if a.y == b.y && b.y == c.y {
+                assert!(normal.y > 0.99, "top faces up: {:?}", normal);
+            } else {
+                // skirts face away from the center of the chunk
+                assert!(normal.y.abs() < 1e-6);
+                assert!(normal.dot(centroid) > 0.0, "skirt faces out: {:?}", normal);
+            }
+        }
+    }
+
+    #[test]
+    fn bounding_sphere_contains_displaced_vertices() {
+        let values = [0, 65535, 16384, 0, 32768, 65535, 0, 0, 65535];
+
+        for displacement in [Displacement::Cpu, Displacement::Gpu].iter() {
+            let settings = settings(*displacement);
+            let map = heightmap(&values);
+            let chunk = chunk_geometry(&map, &settings, [0, 1]);
+            // the highest texel of the chunk is in the middle of the heightmap
+            assert!((chunk.center.y - 2.0).abs() < 1e-3);
+
+            for vertex in chunk.vertices.iter() {
+                let mut position = Vector3::from(vertex.position);
+                if *displacement == Displacement::Gpu {
+                    position.y += map.sample(vertex.uv[0], vertex.uv[1]) * settings.height_scale;
+                }
+                assert!(position.magnitude() <= chunk.bounding_radius + 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Heights of the terrain read from an image asset.
+
+use bf::image::{Format, Image};
+use cgmath::{InnerSpace, Vector3};
+
+/// Errors that may happen when reading a heightmap.
+#[derive(Debug)]
+pub enum HeightmapError {
+    /// The image is not in the `R16` format.
+    UnsupportedFormat(Format),
+    /// The image has less data than its dimensions require.
+    InvalidLength,
+}
+
+/// Heights sampled from the highest resolution mip-map of an `R16` image. The
+/// texels are placed at the corners of the grid, so the corners of the image
+/// are the corners of the terrain.
+pub struct Heightmap {
+    width: u32,
+    height: u32,
+    /// Heights normalized to `0.0` to `1.0` in row-major order.
+    values: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Reads the heights from the first mip-map of the `image`.
+    pub fn from_image(image: &Image) -> Result<Self, HeightmapError> {
+        if image.format != Format::R16 {
+            return Err(HeightmapError::UnsupportedFormat(image.format));
+        }
+
+        let (width, height) = (image.width as u32, image.height as u32);
+        let count = (width * height) as usize;
+        let data = image
+            .mipmap_data
+            .get(..count * 2)
+            .ok_or(HeightmapError::InvalidLength)?;
+
+        Ok(Self {
+            width,
+            height,
+            values: data
+                .chunks_exact(2)
+                .map(|x| u16::from_le_bytes([x[0], x[1]]) as f32 / 65535.0)
+                .collect(),
+        })
+    }
+
+    /// Returns the dimensions of the heightmap in texels.
+    pub fn dimensions(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+
+    /// Returns the bilinearly interpolated height (`0.0` to `1.0`) at the terrain
+    /// coordinates `u` and `v` (`0.0` to `1.0`, clamped).
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        let x = u.clamp(0.0, 1.0) * (self.width - 1) as f32;
+        let y = v.clamp(0.0, 1.0) * (self.height - 1) as f32;
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+        let texel = |x: u32, y: u32| self.values[(y * self.width + x) as usize];
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+        let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Returns the normal of the terrain at `u` and `v` when it is `size` units
+    /// large and `height_scale` units high.
+    pub fn normal(&self, u: f32, v: f32, size: f32, height_scale: f32) -> Vector3<f32> {
+        let du = 1.0 / (self.width - 1).max(1) as f32;
+        let dv = 1.0 / (self.height - 1).max(1) as f32;
+        let dx =
+            (self.sample(u + du, v) - self.sample(u - du, v)) * height_scale / (2.0 * du * size);
+        let dz =
+            (self.sample(u, v + dv) - self.sample(u, v - dv)) * height_scale / (2.0 * dv * size);
+
+        Vector3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Returns the lowest and the highest height (`0.0` to `1.0`) in the rectangle
+    /// of terrain coordinates from `min` to `max`.
+    pub fn range(&self, min: [f32; 2], max: [f32; 2]) -> (f32, f32) {
+        let to_texel = |x: f32, size: u32| x.clamp(0.0, 1.0) * (size - 1) as f32;
+        let (x0, x1) = (
+            to_texel(min[0], self.width).floor() as u32,
+            to_texel(max[0], self.width).ceil() as u32,
+        );
+        let (y0, y1) = (
+            to_texel(min[1], self.height).floor() as u32,
+            to_texel(max[1], self.height).ceil() as u32,
+        );
+
+        (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .map(|(x, y)| self.values[(y * self.width + x) as usize])
+            .fold((f32::MAX, f32::MIN), |(lo, hi), x| (lo.min(x), hi.max(x)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::terrain::heightmap::{Heightmap, HeightmapError};
+    use bf::image::{Format, Image};
+    use cgmath::InnerSpace;
+
+    fn image(width: u16, height: u16, values: &[u16]) -> Image {
+        Image {
+            format: Format::R16,
+            width,
+            height,
+            mipmap_data: values
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<_>>()
+                .into(),
+        }
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let mut img = image(1, 1, &[0]);
+        img.format = Format::R8;
+        assert!(matches!(
+            Heightmap::from_image(&img),
+            Err(HeightmapError::UnsupportedFormat(Format::R8))
+        ));
+
+        let img = image(2, 2, &[0, 0, 0]);
+        assert!(matches!(
+            Heightmap::from_image(&img),
+            Err(HeightmapError::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn samples_corners_and_interpolates() {
+        let map = Heightmap::from_image(&image(2, 2, &[0, 65535, 65535, 65535])).unwrap();
+
+        assert_eq!(map.sample(0.0, 0.0), 0.0);
+        assert_eq!(map.sample(1.0, 0.0), 1.0);
+        assert_eq!(map.sample(0.5, 0.0), 0.5);
+        assert_eq!(map.sample(0.5, 0.5), 0.75);
+        assert_eq!(map.sample(-1.0, 2.0), 1.0);
+        assert_eq!(map.range([0.0, 0.0], [1.0, 1.0]), (0.0, 1.0));
+    }
+
+    #[test]
+    fn computes_normals_of_slopes() {
+        // height rises along x by the size of the terrain
+        let map = Heightmap::from_image(&image(3, 3, &[0, 32768, 65535].repeat(3))).unwrap();
+
+        let flat = map.normal(0.5, 0.5, 10.0, 0.0);
+        assert!((flat.y - 1.0).abs() < 1e-6);
+
+        let slope = map.normal(0.5, 0.5, 10.0, 10.0);
+        assert!((slope.dot(cgmath::vec3(1.0, 1.0, 0.0).normalize()) - 0.0).abs() < 1e-3);
+        assert!(slope.x < 0.0 && slope.z.abs() < 1e-6);
+    }
+}
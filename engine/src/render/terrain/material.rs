@@ -0,0 +1,234 @@
+//! Material blending up to four layers of textures by a splat map.
+
+use crate::assets::Content;
+use crate::render::ubo::TerrainData;
+use crate::resources::image::{create_streamed_image_cached, is_dxt5nm, StreamedImage};
+use crate::resources::material::{FallbackMaps, Material, MATERIAL_UBO_DESCRIPTOR_SET};
+use bf::material::BlendMode;
+use bf::uuid::Uuid;
+use log::error;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, ImmutableBuffer};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::descriptor_set::{
+    PersistentDescriptorSet, PersistentDescriptorSetBuildError, PersistentDescriptorSetError,
+};
+use vulkano::image::view::ImageView;
+use vulkano::image::ImmutableImage;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+/// Errors that may happen when creating a terrain material.
+#[derive(Debug)]
+pub enum TerrainMaterialError {
+    /// Uniform Buffer couldn't be created because of allocation error.
+    CannotCreateUniformBuffer(DeviceMemoryAllocError),
+    /// Descriptor set has invalid number.
+    InvalidDescriptorSetNumber,
+    /// Persistent descriptor set could be created.
+    CannotCreateDescriptorSet(PersistentDescriptorSetError),
+    /// Persistent descriptor set could be built.
+    CannotBuildDescriptorSet(PersistentDescriptorSetBuildError),
+}
+
+/// One of the materials blended on the terrain. Its weight is stored in one
+/// channel of the splat map (the first layer in red, the second in green...).
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainLayer {
+    pub albedo_map: Option<Uuid>,
+    pub normal_map: Option<Uuid>,
+    /// Number of repetitions of the maps across the whole terrain.
+    pub tiling: f32,
+    pub roughness: f32,
+    pub metallic: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            albedo_map: None,
+            normal_map: None,
+            tiling: 1.0,
+            roughness: 1.0,
+            metallic: 0.0,
+        }
+    }
+}
+
+/// Textures painted on the terrain.
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainTextures {
+    /// Image with weights of the `layers` in its channels. The weights are
+    /// normalized, so they do not need to add up to one.
+    pub splat_map: Uuid,
+    pub layers: [TerrainLayer; 4],
+}
+
+/// Maps of the material in order: splat, albedo of the layers, normal of the layers.
+type Maps = [Arc<StreamedImage>; 9];
+
+/// Material of terrain chunks. Besides the layers it contains the heightmap
+/// sampled by the vertex shader when the vertices are displaced on the gpu.
+pub struct TerrainMaterial {
+    textures: Vec<Uuid>,
+    heightmap: Arc<ImageView<Arc<ImmutableImage>>>,
+    maps: Maps,
+    buffer: Arc<ImmutableBuffer<TerrainData>>,
+    layout: Arc<DescriptorSetLayout>,
+    sampler: Arc<Sampler>,
+    /// Current descriptor set and the revision of maps it was built with.
+    descriptor_set: Mutex<(u64, Arc<dyn DescriptorSet + Send + Sync>)>,
+}
+
+impl TerrainMaterial {
+    pub fn new(
+        heightmap: Arc<ImageView<Arc<ImmutableImage>>>,
+        textures: &TerrainTextures,
+        mut parameters: TerrainData,
+        content: &Content,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        sampler: Arc<Sampler>,
+        fallback: &FallbackMaps,
+    ) -> Result<(Arc<Self>, impl GpuFuture), TerrainMaterialError> {
+        let load = |uuid: Option<Uuid>, default: &Arc<ImageView<Arc<ImmutableImage>>>| match uuid {
+            None => StreamedImage::resident(default.clone()),
+            Some(uuid) => create_streamed_image_cached(uuid, content)
+                .unwrap_or_else(|_| panic!("cannot create image for: {}", uuid)),
+        };
+
+        let TerrainTextures { splat_map, layers } = textures;
+        let maps = [
+            load(Some(*splat_map), &fallback.fallback_white),
+            load(layers[0].albedo_map, &fallback.fallback_white),
+            load(layers[1].albedo_map, &fallback.fallback_white),
+            load(layers[2].albedo_map, &fallback.fallback_white),
+            load(layers[3].albedo_map, &fallback.fallback_white),
+            load(layers[0].normal_map, &fallback.fallback_normal),
+            load(layers[1].normal_map, &fallback.fallback_normal),
+            load(layers[2].normal_map, &fallback.fallback_normal),
+            load(layers[3].normal_map, &fallback.fallback_normal),
+        ];
+        let textures = std::iter::once(*splat_map)
+            .chain(layers.iter().flat_map(|x| x.albedo_map))
+            .chain(layers.iter().flat_map(|x| x.normal_map))
+            .collect();
+
+        // the shader reads X of DXT5nm normal maps from alpha
+        for (data, normal_map) in parameters.layers.iter_mut().zip(&maps[5..]) {
+            data[3] = if normal_map.format().map_or(false, is_dxt5nm) {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let (buffer, future) = ImmutableBuffer::from_data(
+            parameters,
+            BufferUsage::uniform_buffer(),
+            content.transfer_queue.clone(),
+        )
+        .map_err(TerrainMaterialError::CannotCreateUniformBuffer)?;
+
+        let layout = pipeline
+            .layout()
+            .descriptor_set_layouts()
+            .get(MATERIAL_UBO_DESCRIPTOR_SET)
+            .ok_or(TerrainMaterialError::InvalidDescriptorSetNumber)?
+            .clone();
+        let set = build_descriptor_set(&layout, &sampler, &heightmap, &maps, &buffer)?;
+
+        Ok((
+            Arc::new(Self {
+                descriptor_set: Mutex::new((revision(&maps), set)),
+                textures,
+                heightmap,
+                maps,
+                buffer,
+                layout,
+                sampler,
+            }),
+            future,
+        ))
+    }
+}
+
+/// Returns the number that changes when any of the maps changes.
+fn revision(maps: &Maps) -> u64 {
+    maps.iter().map(|x| x.revision()).sum()
+}
+
+fn build_descriptor_set(
+    layout: &Arc<DescriptorSetLayout>,
+    sampler: &Arc<Sampler>,
+    heightmap: &Arc<ImageView<Arc<ImmutableImage>>>,
+    maps: &Maps,
+    buffer: &Arc<ImmutableBuffer<TerrainData>>,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>, TerrainMaterialError> {
+    let [splat, albedo0, albedo1, albedo2, albedo3, normal0, normal1, normal2, normal3] = maps;
+
+    let set = PersistentDescriptorSet::start(layout.clone())
+        .add_sampled_image(heightmap.clone(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(splat.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(albedo0.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(albedo1.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(albedo2.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(albedo3.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(normal0.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(normal1.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(normal2.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(normal3.view(), sampler.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .add_buffer(buffer.clone())
+        .map_err(TerrainMaterialError::CannotCreateDescriptorSet)?
+        .build()
+        .map_err(TerrainMaterialError::CannotBuildDescriptorSet)?;
+
+    Ok(Arc::new(set))
+}
+
+impl Material for TerrainMaterial {
+    fn descriptor_set(&self) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let mut current = self.descriptor_set.lock();
+        let revision = revision(&self.maps);
+
+        // some of the streamed maps were replaced
+        if current.0 != revision {
+            match build_descriptor_set(
+                &self.layout,
+                &self.sampler,
+                &self.heightmap,
+                &self.maps,
+                &self.buffer,
+            ) {
+                Ok(set) => *current = (revision, set),
+                Err(e) => {
+                    error!("Cannot rebuild descriptor set of terrain material: {:?}", e);
+                    // keep using the old descriptor set
+                    current.0 = revision;
+                }
+            }
+        }
+
+        current.1.clone()
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Opaque
+    }
+
+    fn textures(&self) -> &[Uuid] {
+        &self.textures
+    }
+}
@@ -0,0 +1,351 @@
+//! Terrain rendered from a heightmap image asset.
+//!
+//! The terrain is split into a grid of square chunks. Each chunk is an `Object`
+//! drawn in the geometry subpass with the terrain pipeline, so it gets levels of
+//! detail and their cross-fades from `GameState::update_lods` like other meshes.
+//! The levels are generated by skipping rows and columns of the grid (see
+//! [`chunk_geometry`](chunk/fn.chunk_geometry.html)). Chunks outside of the view
+//! frustum are hidden by [`Terrain::cull`](struct.Terrain.html#method.cull).
+//!
+//! The surface blends up to four layers of textures with weights from a splat map
+//! (see [`TerrainMaterial`](material/struct.TerrainMaterial.html)).
+
+use crate::assets::Content;
+use crate::camera::Camera;
+use crate::render::object::Object;
+use crate::render::object_id::GEOMETRY_DEFINES;
+use crate::render::objects::{ObjectId, Objects};
+use crate::render::pbr::PBRDeffered;
+use crate::render::shader_cache::CachedShader;
+use crate::render::terrain::chunk::chunk_geometry;
+use crate::render::terrain::heightmap::{Heightmap, HeightmapError};
+use crate::render::terrain::material::{TerrainMaterial, TerrainMaterialError, TerrainTextures};
+use crate::render::transform::Transform;
+use crate::render::ubo::TerrainData;
+use crate::render::vertex::NormalMappedVertex;
+use crate::render::vulkan::pipeline_cache;
+use crate::resources::image::{create_image, CreateImageError};
+use crate::resources::material::FallbackMaps;
+use crate::resources::mesh::{DynamicIndexedMesh, IndexedMesh};
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, ImmutableBuffer};
+use vulkano::device::Device;
+use vulkano::image::view::ImageView;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::sync::GpuFuture;
+
+pub mod chunk;
+pub mod heightmap;
+pub mod material;
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../../shaders/vs_terrain.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_terrain.glsl"
+        }
+    }
+
+    #[cfg(not(feature = "editor"))]
+    pub mod fragment {
+        const X: &str = include_str!("../../../shaders/fs_terrain.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_terrain.glsl"
+        }
+    }
+
+    #[cfg(feature = "editor")]
+    pub mod fragment {
+        const X: &str = include_str!("../../../shaders/fs_terrain.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_terrain.glsl",
+            define: [("OBJECT_ID", "")]
+        }
+    }
+}
+
+/// Where the heights are added to the vertices of the grid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Displacement {
+    /// Heights (and normals) are stored in the vertex buffers of chunks.
+    Cpu,
+    /// Vertex buffers contain a flat grid and the vertex shader samples the
+    /// heightmap texture.
+    Gpu,
+}
+
+/// Parameters of the terrain geometry.
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainSettings {
+    /// Size of the terrain along the x and z axis.
+    pub size: f32,
+    /// Height of the terrain at the highest value of the heightmap.
+    pub height_scale: f32,
+    /// Number of chunks along each axis.
+    pub chunks: u32,
+    /// Number of quads along each side of a chunk in the most detailed level.
+    /// Must be a power of two not larger than 128.
+    pub chunk_quads: u32,
+    /// Number of levels of detail, each of them halves the number of quads
+    /// along each side.
+    pub lod_count: u32,
+    /// Fraction of the screen covered by a chunk above which the most detailed
+    /// level is used. Each following level is used from half of the coverage
+    /// of the previous one.
+    pub lod_coverage: f32,
+    /// Depth of the skirts that hide cracks between chunks with different levels.
+    pub skirt_depth: f32,
+    pub displacement: Displacement,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            size: 512.0,
+            height_scale: 64.0,
+            chunks: 8,
+            chunk_quads: 64,
+            lod_count: 4,
+            lod_coverage: 0.5,
+            skirt_depth: 2.0,
+            displacement: Displacement::Cpu,
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Returns the size of one chunk.
+    pub fn chunk_size(&self) -> f32 {
+        self.size / self.chunks as f32
+    }
+
+    fn validate(&self) -> Result<(), TerrainError> {
+        if !self.chunk_quads.is_power_of_two() || self.chunk_quads > 128 {
+            return Err(TerrainError::InvalidSettings(
+                "chunk_quads must be a power of two not larger than 128",
+            ));
+        }
+        if self.lod_count == 0 || self.chunk_quads >> (self.lod_count - 1) == 0 {
+            return Err(TerrainError::InvalidSettings(
+                "lod_count must be between one and log2(chunk_quads) + 1",
+            ));
+        }
+        if self.chunks == 0 {
+            return Err(TerrainError::InvalidSettings("chunks must not be zero"));
+        }
+        Ok(())
+    }
+}
+
+/// Errors that may happen when creating a terrain.
+#[derive(Debug)]
+pub enum TerrainError {
+    InvalidSettings(&'static str),
+    InvalidHeightmap(HeightmapError),
+    CannotCreateHeightmapImage(CreateImageError),
+    CannotCreateBuffer(DeviceMemoryAllocError),
+    CannotCreateMaterial(TerrainMaterialError),
+}
+
+/// Chunk of the terrain with its mesh.
+struct Chunk {
+    mesh: Arc<DynamicIndexedMesh<NormalMappedVertex>>,
+    /// Center of the chunk in the local space of the terrain.
+    center: Vector3<f32>,
+    bounding_radius: f32,
+    /// Object of the chunk while the terrain is spawned.
+    object: Option<ObjectId>,
+}
+
+/// Terrain made of chunks that are spawned as objects of the world.
+pub struct Terrain {
+    settings: TerrainSettings,
+    /// Position of the corner of the terrain with the lowest coordinates.
+    position: Vector3<f32>,
+    device: Arc<Device>,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    material: Arc<TerrainMaterial>,
+    chunks: Vec<Chunk>,
+}
+
+impl Terrain {
+    /// Creates the terrain from the `heightmap` (an `R16` image) with its corner
+    /// at `position`. The uploads of the meshes and textures are scheduled on
+    /// `content.uploads`. The chunks are not rendered until the terrain is spawned.
+    pub fn new(
+        heightmap: &bf::image::Image,
+        textures: &TerrainTextures,
+        settings: TerrainSettings,
+        position: Vector3<f32>,
+        path: &PBRDeffered,
+        content: &Content,
+        fallback: &FallbackMaps,
+    ) -> Result<Self, TerrainError> {
+        settings.validate()?;
+        let heights = Heightmap::from_image(heightmap).map_err(TerrainError::InvalidHeightmap)?;
+        let queue = content.transfer_queue.clone();
+        let device = queue.device().clone();
+        let pipeline = create_pipeline(device.clone(), path);
+
+        let (image, f1) = create_image(heightmap, queue.clone())
+            .map_err(TerrainError::CannotCreateHeightmapImage)?;
+        let view = ImageView::new(image).expect("cannot create view from image");
+        let mut layers = [[0.0; 4]; 4];
+        for (data, layer) in layers.iter_mut().zip(textures.layers.iter()) {
+            *data = [layer.tiling, layer.roughness, layer.metallic, 0.0];
+        }
+        let parameters = TerrainData {
+            layers,
+            height_scale: settings.height_scale,
+            gpu_displacement: (settings.displacement == Displacement::Gpu) as u32,
+            size: settings.size,
+        };
+        let (material, f2) = TerrainMaterial::new(
+            view,
+            textures,
+            parameters,
+            content,
+            pipeline.clone(),
+            path.samplers.aniso_repeat.clone(),
+            fallback,
+        )
+        .map_err(TerrainError::CannotCreateMaterial)?;
+        content
+            .uploads
+            .schedule(f1.join(f2), heightmap.mipmap_data.len());
+
+        let mut chunks = vec![];
+        for z in 0..settings.chunks {
+            for x in 0..settings.chunks {
+                let geometry = chunk_geometry(&heights, &settings, [x, z]);
+                let bytes = geometry.vertices.len() * std::mem::size_of::<NormalMappedVertex>()
+                    + geometry.indices.len() * std::mem::size_of::<u16>();
+
+                let (vertex_buffer, f1) = ImmutableBuffer::from_iter(
+                    geometry.vertices.into_iter(),
+                    BufferUsage::vertex_buffer(),
+                    queue.clone(),
+                )
+                .map_err(TerrainError::CannotCreateBuffer)?;
+                let (index_buffer, f2) = ImmutableBuffer::from_iter(
+                    geometry.indices.into_iter(),
+                    BufferUsage::index_buffer(),
+                    queue.clone(),
+                )
+                .map_err(TerrainError::CannotCreateBuffer)?;
+                content.uploads.schedule(f1.join(f2), bytes);
+
+                let mesh = IndexedMesh::<NormalMappedVertex, u16>::with_lods(
+                    vertex_buffer,
+                    index_buffer,
+                    geometry.lods,
+                );
+                chunks.push(Chunk {
+                    mesh: Arc::new(DynamicIndexedMesh::U16(match Arc::try_unwrap(mesh) {
+                        Ok(t) => t,
+                        Err(_) => unreachable!(),
+                    })),
+                    center: geometry.center,
+                    bounding_radius: geometry.bounding_radius,
+                    object: None,
+                });
+            }
+        }
+
+        Ok(Self {
+            settings,
+            position,
+            device,
+            pipeline,
+            material,
+            chunks,
+        })
+    }
+
+    /// Returns the settings the terrain was created with.
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    /// Returns the ids of objects of the chunks while the terrain is spawned.
+    pub fn objects(&self) -> impl Iterator<Item = ObjectId> + '_ {
+        self.chunks.iter().filter_map(|x| x.object)
+    }
+
+    /// Adds an object for each chunk to the `objects`. Does nothing when the
+    /// terrain is already spawned.
+    pub fn spawn(&mut self, objects: &mut Objects<Object<NormalMappedVertex>>) {
+        for chunk in self.chunks.iter_mut().filter(|x| x.object.is_none()) {
+            let mut object = Object::new(
+                chunk.mesh.clone(),
+                self.material.clone(),
+                self.device.clone(),
+                self.pipeline.clone(),
+                Transform::from_position(self.position + chunk.center),
+            );
+            object.bounding_radius = chunk.bounding_radius;
+            chunk.object = Some(objects.insert(object));
+        }
+    }
+
+    /// Removes objects of the chunks from the `objects`.
+    pub fn despawn(&mut self, objects: &mut Objects<Object<NormalMappedVertex>>) {
+        for chunk in self.chunks.iter_mut() {
+            if let Some(id) = chunk.object.take() {
+                objects.remove(id);
+            }
+        }
+    }
+
+    /// Hides the chunks whose bounding spheres are outside of the view frustum
+    /// of the `camera` and shows the rest. Should be called before each frame is
+    /// rendered (eg. from `Game::update` with `GameState::render_camera`).
+    pub fn cull(
+        &self,
+        camera: &dyn Camera<f32>,
+        objects: &mut Objects<Object<NormalMappedVertex>>,
+    ) {
+        let frustum = camera.frustum();
+
+        for id in self.objects() {
+            if let Some(object) = objects.get_mut(id) {
+                let (center, radius) = object.bounding_sphere();
+                object.visible = frustum.intersects_sphere(Point3::from_vec(center), radius);
+            }
+        }
+    }
+}
+
+/// Creates the pipeline that draws chunks into the G-Buffer in the geometry subpass.
+fn create_pipeline(
+    device: Arc<Device>,
+    path: &PBRDeffered,
+) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+    let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+    let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+    let cached_vs = CachedShader::load(device.clone(), "vs_terrain");
+    let cached_fs = CachedShader::load_variant(device.clone(), "fs_terrain", GEOMETRY_DEFINES);
+    let main = &path.main_graph;
+
+    Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<NormalMappedVertex>()
+            .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+            .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil(DepthStencil::simple_depth_test())
+            .cull_mode_back()
+            .front_face_clockwise()
+            .render_pass(main.graph.subpass(&path.render_pass, main.geometry))
+            .build_with_cache(pipeline_cache(&device))
+            .build(device)
+            .expect("cannot create graphics pipeline for terrain"),
+    )
+}
@@ -0,0 +1,122 @@
+//! Composition of the bloom with the HDR buffer and tonemapping of the result.
+
+use crate::render::descriptor_set_layout;
+use crate::render::post::{
+    InputSets, PostEffect, PostImage, PostInput, PostOutput, PostPasses, PostPipelines,
+};
+use crate::render::shader_cache::CachedShader;
+use crate::render::shaders::{fs_tonemap, vs_passtrough};
+use crate::render::vertex::PositionOnlyVertex;
+use crate::render::vulkan::pipeline_cache;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+const TONEMAP_DESCRIPTOR_SET: usize = 0;
+
+pub struct Tonemap {
+    pipelines: PostPipelines,
+    sets: InputSets,
+    sampler: Arc<Sampler>,
+}
+
+impl Tonemap {
+    pub fn new(device: Arc<Device>, passes: &PostPasses) -> Self {
+        // bloom has half resolution of the hdr buffer and is upsampled when read
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .expect("cannot create sampler for tonemap");
+
+        let vs = vs_passtrough::Shader::load(device.clone()).unwrap();
+        let fs = fs_tonemap::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_passtrough");
+        let cached_fs = CachedShader::load(device.clone(), "fs_tonemap");
+
+        let pipelines = PostPipelines::new(passes, |subpass| {
+            Arc::new(
+                GraphicsPipeline::start()
+                    .vertex_input_single_buffer::<PositionOnlyVertex>()
+                    .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                    .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                    .triangle_list()
+                    .viewports_dynamic_scissors_irrelevant(1)
+                    .render_pass(subpass)
+                    .build_with_cache(pipeline_cache(&device))
+                    .build(device.clone())
+                    .expect("cannot build tonemap graphics pipeline"),
+            )
+        });
+
+        Self {
+            pipelines,
+            sets: InputSets::default(),
+            sampler,
+        }
+    }
+}
+
+impl PostEffect for Tonemap {
+    fn name(&self) -> &'static str {
+        "tonemap"
+    }
+
+    fn dimensions_changed(&mut self, _: &PostImage, _: [u32; 2]) {
+        self.sets.clear();
+    }
+
+    fn record(
+        &self,
+        input: &mut PostInput,
+        output: PostOutput,
+        cmd: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) {
+        // the input is bound instead of the bloom when bloom was not rendered
+        let (bloom, bloom_intensity) = input
+            .bloom
+            .clone()
+            .unwrap_or_else(|| (input.color.clone(), 0.0));
+
+        let ds = self.sets.get(&[&input.color, &bloom], || {
+            Arc::new(
+                PersistentDescriptorSet::start(descriptor_set_layout(
+                    self.pipelines.any().layout(),
+                    TONEMAP_DESCRIPTOR_SET,
+                ))
+                .add_sampled_image(input.color.clone(), self.sampler.clone())
+                .unwrap()
+                .add_sampled_image(bloom.clone(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+            )
+        });
+
+        let frame = input.frame;
+        cmd.draw_indexed(
+            self.pipelines.get(output).clone(),
+            frame.dynamic_state,
+            vec![frame.fst.vertex_buffer().clone()],
+            frame.fst.index_buffer().clone(),
+            ds,
+            fs_tonemap::ty::PushConstants {
+                resolution: frame.resolution,
+                bloom_intensity,
+            },
+        )
+        .expect("cannot do tonemap pass");
+    }
+}
@@ -0,0 +1,440 @@
+//! Transform struct that is used to represent *position*, *rotation* and *scale* of objects.
+
+use crate::render::lod_fade::VISIBLE;
+use crate::render::ubo::ObjectMatrixData;
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, Quaternion, SquareMatrix,
+    Vector3, VectorSpace,
+};
+
+/// Transform is a struct that is used to represent *position*, *rotation* and *scale*
+/// of an object in *world space*.
+///
+/// The components can only be changed by setters, which mark the transform as
+/// dirty. The model matrix and normal matrix of a dirty transform are computed
+/// on each use until [`update`](#method.update) caches them again (this is done
+/// for all objects once per frame by `GameState::update_transforms`).
+///
+/// Interpolated transforms also keep the components of the previous fixed
+/// update and their matrices are cached for a point between the previous and
+/// the current components (see [`set_interpolated`](#method.set_interpolated)).
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    position: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    scale: Vector3<f32>,
+    /// Components at the previous fixed update (`None` when not interpolated).
+    previous: Option<(Vector3<f32>, Quaternion<f32>, Vector3<f32>)>,
+    /// Fraction between the previous and current components the cached
+    /// matrices were computed for.
+    alpha: f32,
+    dirty: bool,
+    /// Model matrix in double precision (valid when not dirty).
+    matrix: Matrix4<f64>,
+    /// Inverse transpose of the upper 3x3 part of the model matrix (valid
+    /// when not dirty).
+    normal: Matrix3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::from_position(Vector3::new(0.0, 0.0, 0.0))
+    }
+}
+
+impl PartialEq for Transform {
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position
+            && self.rotation == other.rotation
+            && self.scale == other.scale
+    }
+}
+
+impl Transform {
+    /// Creates a transform from its components.
+    pub fn new(position: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
+        let mut transform = Self {
+            position,
+            rotation,
+            scale,
+            previous: None,
+            alpha: 1.0,
+            dirty: true,
+            matrix: Matrix4::identity(),
+            normal: Matrix3::identity(),
+        };
+        transform.update();
+        transform
+    }
+
+    /// Creates a transform that only moves objects to the `position`.
+    pub fn from_position(position: Vector3<f32>) -> Self {
+        Self::new(
+            position,
+            Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    /// Creates a transform at the `eye` position rotated so the forward
+    /// direction (`-Z`) of objects points to the `target`.
+    pub fn look_at(eye: Point3<f32>, target: Point3<f32>, up: Vector3<f32>) -> Self {
+        let back = (eye - target).normalize();
+        let side = up.cross(back).normalize();
+        let rotation = Matrix3::from_cols(side, back.cross(side), back);
+
+        Self::new(eye.to_vec(), rotation.into(), Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    /// Decomposes a model matrix of a transform (translation * scale * rotation)
+    /// back into the components. Returns `None` when the matrix has zero scale or
+    /// contains shear or projection.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Option<Self> {
+        const EPSILON: f32 = 1e-4;
+
+        if matrix.x.w.abs() > EPSILON || matrix.y.w.abs() > EPSILON || matrix.z.w.abs() > EPSILON {
+            return None;
+        }
+
+        // the upper 3x3 part is `scale * rotation`, so each row is a row
+        // of the rotation matrix multiplied by one of the scale factors
+        let m = Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        let rows = [m.row(0), m.row(1), m.row(2)];
+        let mut scale = Vector3::new(
+            rows[0].magnitude(),
+            rows[1].magnitude(),
+            rows[2].magnitude(),
+        );
+        if scale.x < EPSILON || scale.y < EPSILON || scale.z < EPSILON {
+            return None;
+        }
+        if m.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let rotation =
+            Matrix3::from_cols(rows[0] / scale.x, rows[1] / scale.y, rows[2] / scale.z).transpose();
+        let orthogonal = rotation.transpose() * rotation;
+        if (0..3).any(|i| (orthogonal[i] - Matrix3::identity()[i]).magnitude() > EPSILON * 10.0) {
+            return None;
+        }
+
+        Some(Self::new(
+            matrix.w.truncate(),
+            Quaternion::from(rotation).normalize(),
+            scale,
+        ))
+    }
+
+    /// Returns this transform with the `rotation`.
+    pub fn with_rotation(mut self, rotation: Quaternion<f32>) -> Self {
+        self.set_rotation(rotation);
+        self.updated()
+    }
+
+    /// Returns this transform with the `scale`.
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.set_scale(scale);
+        self.updated()
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> Vector3<f32> {
+        self.scale
+    }
+
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.rotation = rotation;
+        self.dirty = true;
+    }
+
+    pub fn set_scale(&mut self, scale: Vector3<f32>) {
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    /// Moves the transform by the `offset`.
+    pub fn translate(&mut self, offset: Vector3<f32>) {
+        self.set_position(self.position + offset);
+    }
+
+    /// Moves the transform and its previous position by the `offset` (eg. when
+    /// the origin is shifted), so the move is not interpolated.
+    pub fn shift(&mut self, offset: Vector3<f32>) {
+        self.translate(offset);
+        if let Some(previous) = self.previous.as_mut() {
+            previous.0 += offset;
+        }
+    }
+
+    /// Sets whether the transform is rendered interpolated between its
+    /// components at the previous and the current fixed update (for objects
+    /// moved by `Game::fixed_update`, so they move smoothly at any frame rate).
+    /// Objects moved once per frame should not be interpolated.
+    pub fn set_interpolated(&mut self, interpolated: bool) {
+        self.previous = if interpolated {
+            Some((self.position, self.rotation, self.scale))
+        } else {
+            None
+        };
+        self.dirty = true;
+    }
+
+    pub fn is_interpolated(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// Stores the current components as the previous ones of an interpolated
+    /// transform. Called for all objects before each fixed update. Call it
+    /// after teleporting an object, so it does not slide to the new position.
+    pub fn save_previous(&mut self) {
+        if let Some(previous) = self.previous.as_mut() {
+            *previous = (self.position, self.rotation, self.scale);
+            self.dirty = true;
+        }
+    }
+
+    /// Returns whether the transform changed since the cached matrices were computed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Recomputes the cached matrices if the transform is dirty. Returns whether
+    /// the matrices were recomputed.
+    pub fn update(&mut self) -> bool {
+        self.update_interpolated(1.0)
+    }
+
+    /// Recomputes the cached matrices for the fraction `alpha` (0 to 1) between
+    /// the previous and current components if the transform is dirty or
+    /// interpolated with a different `alpha`. Transforms that are not
+    /// interpolated use the current components. Returns whether the matrices
+    /// were recomputed.
+    pub fn update_interpolated(&mut self, alpha: f32) -> bool {
+        let moving = self.previous.map_or(false, |(position, rotation, scale)| {
+            position != self.position || rotation != self.rotation || scale != self.scale
+        });
+        let alpha = if moving { alpha.max(0.0).min(1.0) } else { 1.0 };
+        if !self.dirty && alpha == self.alpha {
+            return false;
+        }
+
+        let matrix = match self.previous {
+            Some((position, rotation, scale)) if alpha < 1.0 => compose(
+                position.lerp(self.position, alpha),
+                rotation.nlerp(self.rotation, alpha),
+                scale.lerp(self.scale, alpha),
+            ),
+            _ => compose(self.position, self.rotation, self.scale),
+        };
+        self.matrix = matrix.cast().unwrap();
+        self.normal = normal_matrix(self.matrix);
+        self.alpha = alpha;
+        self.dirty = false;
+        true
+    }
+
+    fn updated(mut self) -> Self {
+        self.update();
+        self
+    }
+
+    /// Returns the model matrix of this transform with translation relative to
+    /// the `eye` position (camera-relative rendering). The difference is computed
+    /// in double precision, so objects far from the origin do not jitter when
+    /// they are close to the camera.
+    pub fn relative_to(&self, eye: Point3<f32>) -> Matrix4<f32> {
+        relative_to_eye(self.matrix_f64(), eye)
+    }
+
+    /// Returns the model matrix of this transform in double precision.
+    pub fn matrix_f64(&self) -> Matrix4<f64> {
+        if self.dirty {
+            let matrix: Matrix4<f32> = (*self).into();
+            return matrix.cast().unwrap();
+        }
+        self.matrix
+    }
+
+    /// Returns the matrix that transforms normals (the inverse transpose of the
+    /// model matrix), which keeps them perpendicular to surfaces of objects
+    /// with non-uniform scale.
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        if self.dirty {
+            return normal_matrix(self.matrix_f64());
+        }
+        self.normal
+    }
+}
+
+/// Returns the `world` matrix with translation relative to the `eye` position
+/// (see [`Transform::relative_to`](struct.Transform.html#method.relative_to)).
+pub fn relative_to_eye(world: Matrix4<f64>, eye: Point3<f32>) -> Matrix4<f32> {
+    let eye = eye.to_vec().cast::<f64>().unwrap();
+
+    (Matrix4::from_translation(-eye) * world).cast().unwrap()
+}
+
+/// Returns the inverse transpose of the upper 3x3 part of the `model` matrix.
+/// Matrices with zero scale (that can't be inverted) are returned unchanged.
+pub fn normal_matrix(model: Matrix4<f64>) -> Matrix3<f32> {
+    let m = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+
+    m.invert()
+        .map(|x| x.transpose())
+        .unwrap_or(m)
+        .cast()
+        .unwrap()
+}
+
+/// Returns the model matrix of a transform with the components.
+fn compose(position: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Matrix4<f32> {
+    let scale = Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+    let rotation = Matrix4::from(rotation);
+    let translate = Matrix4::from_translation(position);
+
+    translate * scale * rotation
+}
+
+impl Into<Matrix4<f32>> for Transform {
+    fn into(self) -> Matrix4<f32> {
+        compose(self.position, self.rotation, self.scale)
+    }
+}
+
+impl Into<ObjectMatrixData> for Transform {
+    fn into(self) -> ObjectMatrixData {
+        ObjectMatrixData {
+            model: self.into(),
+            normal: self.normal_matrix().into(),
+            fade: VISIBLE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::transform::Transform;
+    use cgmath::{
+        vec3, vec4, Deg, InnerSpace, Matrix4, Point3, Quaternion, Rotation, Rotation3, Vector3,
+    };
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn relative_to_eye() {
+        let transform = Transform::from_position(vec3(100_000.0, 2.0, -3.0));
+        let model = transform.relative_to(Point3::new(100_001.0, 0.0, 0.0));
+
+        assert_eq!(model * vec4(0.0, 0.0, 0.0, 1.0), vec4(-1.0, 2.0, -3.0, 1.0));
+    }
+
+    #[test]
+    fn world_relative_to_eye() {
+        let world = Transform::from_position(vec3(100_000.0, 2.0, -3.0)).matrix_f64();
+        let eye = Point3::new(100_001.0, 0.0, 0.0);
+        let model = crate::render::transform::relative_to_eye(world, eye);
+
+        assert_eq!(model * vec4(0.0, 0.0, 0.0, 1.0), vec4(-1.0, 2.0, -3.0, 1.0));
+    }
+
+    #[test]
+    fn setters_mark_transform_dirty() {
+        let mut transform = Transform::default();
+        assert!(!transform.is_dirty());
+
+        transform.translate(vec3(1.0, 0.0, 0.0));
+        assert!(transform.is_dirty());
+        // dirty transforms are not stale
+        assert_eq!(
+            transform.matrix_f64() * vec4(0.0, 0.0, 0.0, 1.0),
+            vec4(1.0, 0.0, 0.0, 1.0)
+        );
+
+        assert!(transform.update());
+        assert!(!transform.update());
+        assert_eq!(transform.matrix_f64().w, vec4(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn interpolates_between_fixed_updates() {
+        let mut transform = Transform::default();
+        transform.set_interpolated(true);
+        transform.save_previous();
+        transform.set_position(vec3(4.0, 0.0, 0.0));
+
+        assert!(transform.update_interpolated(0.25));
+        assert_eq!(transform.matrix_f64().w, vec4(1.0, 0.0, 0.0, 1.0));
+        assert!(!transform.update_interpolated(0.25));
+        assert!(transform.update_interpolated(1.0));
+        assert_eq!(transform.matrix_f64().w, vec4(4.0, 0.0, 0.0, 1.0));
+
+        // shifting the origin does not start a new interpolation
+        transform.save_previous();
+        transform.shift(vec3(-4.0, 0.0, 0.0));
+        transform.update_interpolated(0.5);
+        assert_eq!(transform.matrix_f64().w, vec4(0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_with_non_uniform_scale() {
+        let transform = Transform::default().with_scale(vec3(4.0, 1.0, 1.0));
+        let model: Matrix4<f32> = transform.into();
+
+        // surface of a slope going along x and y axes
+        let tangent = (model * vec3(1.0, 1.0, 0.0).extend(0.0)).truncate();
+        let normal = transform.normal_matrix() * vec3(1.0, -1.0, 0.0);
+        assert!(tangent.dot(normal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn decomposes_matrices() {
+        let transform = Transform::new(
+            vec3(1.0, 2.0, 3.0),
+            Quaternion::from_angle_y(Deg(30.0)) * Quaternion::from_angle_x(Deg(-60.0)),
+            vec3(2.0, 0.5, 3.0),
+        );
+        let decomposed = Transform::from_matrix(transform.into()).unwrap();
+
+        assert_close(decomposed.position(), transform.position());
+        assert_close(decomposed.scale(), transform.scale());
+        let v = vec3(0.3, -0.2, 1.0);
+        assert_close(
+            decomposed.rotation().rotate_vector(v),
+            transform.rotation().rotate_vector(v),
+        );
+
+        assert!(Transform::from_matrix(Matrix4::from_scale(0.0)).is_none());
+    }
+
+    #[test]
+    fn looks_at_target() {
+        let transform = Transform::look_at(
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 10.0),
+            vec3(0.0, 1.0, 0.0),
+        );
+
+        let forward = transform.rotation().rotate_vector(vec3(0.0, 0.0, -1.0));
+        assert_close(forward, vec3(0.0, 0.0, 1.0));
+        assert_close(transform.position(), vec3(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,549 @@
+//! Structs for data passed to shaders via *Uniform Buffer Objects* and other mechanisms.
+
+use crate::camera::Camera;
+use bf::uuid::Uuid;
+use cgmath::{
+    Angle, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector2, Vector3, Vector4, Zero,
+};
+use core::assert_alignment;
+
+/// Maximum number of directional lights in the lights UBO. Must match the
+/// `MAX_LIGHTS` constant in shaders.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 100;
+
+/// Maximum number of spot lights in the spot lights UBO. Must match the
+/// `MAX_SPOT_LIGHTS` constant in shaders.
+pub const MAX_SPOT_LIGHTS: usize = 32;
+
+/// Maximum number of distinct gobo textures used by spot lights in one frame.
+/// Must match the `MAX_GOBOS` constant in shaders.
+pub const MAX_GOBOS: usize = 4;
+
+/// Value of `SpotLightData::gobo` of spot lights without a gobo texture.
+pub const NO_GOBO: i32 = -1;
+
+// todo: remove and use from shader! generated
+/// UBO struct with data about PBR material that is currently being
+/// used.
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct MaterialData {
+    /// Albedo PBR color.
+    pub albedo_color: [f32; 3],
+    /// Alpha cutoff if using `Masked` blend mode.
+    pub alpha_cutoff: f32,
+    /// Roughness PBR parameter.
+    pub roughness: f32,
+    /// Metallic PBR parameters.
+    pub metallic: f32,
+    /// Opacity of a material.
+    pub opacity: f32,
+    /// Index of refraction.
+    pub ior: f32,
+    /// Linear color of emitted light (multiplied by the emissive map).
+    pub emissive_color: [f32; 3],
+    /// Strength of the clear coat layer.
+    pub clear_coat: f32,
+    /// Roughness of the clear coat layer.
+    pub clear_coat_roughness: f32,
+    /// Stretching of the specular highlight along the tangent (positive) or
+    /// the bitangent (negative).
+    pub anisotropy: f32,
+    /// Bits of `bf::material::MaterialFlags` selecting the shading of the surface
+    /// and `MATERIAL_NORMAL_MAP_AG`.
+    pub flags: u32,
+}
+
+/// Bit of `MaterialData::flags` set by the engine (not a `MaterialFlags` bit)
+/// when the normal map stores X in alpha and Y in green (DXT5nm, see
+/// `resources::image::is_dxt5nm`) instead of red and green (BC5 or uncompressed).
+pub const MATERIAL_NORMAL_MAP_AG: u32 = 1 << 31;
+
+/// UBO struct with data that us uniform for every shader during
+/// one frame (such us view matrix, ...).
+///
+/// Rendering is camera-relative: the camera is always at the origin of
+/// the space the shaders work in and model matrices are translated by
+/// the negated camera position (see `Transform::relative_to`).
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct FrameMatrixData {
+    /// View matrix.
+    pub view: Matrix4<f32>,
+    /// Projection matrix.
+    pub projection: Matrix4<f32>,
+    /// Inverse of view matrix.
+    pub inv_projection: Matrix4<f32>,
+    /// Inverse of projection matrix.
+    pub inv_view: Matrix4<f32>,
+    /// Camera position in world-space.
+    pub camera_position: Vector3<f32>,
+    /// Time in seconds used by animated shaders (packed after the `camera_position`).
+    pub time: f32,
+    /// Wind that moves foliage (see [`Wind::packed`](struct.Wind.html#method.packed)).
+    pub wind: [f32; 4],
+}
+
+impl FrameMatrixData {
+    /// Creates the camera-relative frame data from the current state of the `camera`.
+    pub fn new(camera: &dyn Camera<f32>) -> Self {
+        // only the rotation of the view matrix, the camera is at the origin
+        let mut view = camera.view_matrix();
+        view.w = Vector4::unit_w();
+        let projection = camera.projection_matrix();
+
+        Self {
+            camera_position: Vector3::zero(),
+            time: 0.0,
+            wind: [0.0; 4],
+            inv_view: view.invert().expect("view matrix is not invertible"),
+            inv_projection: projection
+                .invert()
+                .expect("projection matrix is not invertible"),
+            view,
+            projection,
+        }
+    }
+}
+
+/// UBO struct representing an uniform buffer that contains data
+/// related to currently rendered object (such as model matrix).
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct ObjectMatrixData {
+    /// Model matrix for currently renderer object.
+    pub model: Matrix4<f32>,
+    /// Normal matrix (inverse transpose of the model matrix) in the upper 3x3 part.
+    pub normal: Matrix4<f32>,
+    /// Range of dither thresholds of pixels that are drawn (see `render::lod_fade`).
+    pub fade: [f32; 2],
+}
+
+/// UBO struct with data of one decal drawn by the decal subpass (see
+/// `render::decals`).
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct DecalData {
+    /// Camera-relative model matrix of the unit box of the decal.
+    pub model: Matrix4<f32>,
+    /// Inverse of the `model` matrix that transforms reconstructed positions
+    /// of pixels into the unit box.
+    pub inv_model: Matrix4<f32>,
+    /// Strength of the decal, multiplies the opacity of its material.
+    pub opacity: f32,
+}
+
+impl DecalData {
+    /// Creates the data of a decal with specified `model` matrix. Returns `None`
+    /// when the matrix can't be inverted (the box has zero size).
+    pub fn new(model: Matrix4<f32>, opacity: f32) -> Option<Self> {
+        Some(Self {
+            inv_model: model.invert()?,
+            model,
+            opacity,
+        })
+    }
+}
+
+/// UBO struct with parameters of a terrain material (see `render::terrain`).
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct TerrainData {
+    /// Tiling (repetitions across the whole terrain), roughness and metallic
+    /// of each layer. The last component is one when the normal map of the
+    /// layer is DXT5nm (set by `TerrainMaterial`).
+    pub layers: [[f32; 4]; 4],
+    /// Height of the terrain at the highest value of the heightmap.
+    pub height_scale: f32,
+    /// Whether vertices are displaced by the vertex shader (non-zero) or
+    /// already contain the heights.
+    pub gpu_displacement: u32,
+    /// Size of the terrain along the x and z axis.
+    pub size: f32,
+}
+
+/// UBO struct representing a directional light (light which
+/// rays are parallel) and its properties.
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct DirectionalLight {
+    /// Direction of the light (from the shaded pixel to the light source).
+    pub direction: Vector3<f32>,
+    /// Intensity of the light.
+    pub intensity: f32,
+    /// Color of the light.
+    pub color: Vector3<f32>,
+}
+
+/// Packs the `lights` into a fixed-size array that is uploaded as UBO. Lights
+/// over the [`MAX_DIRECTIONAL_LIGHTS`](constant.MAX_DIRECTIONAL_LIGHTS.html) limit
+/// are ignored.
+///
+/// Returns the array and number of valid lights in it.
+pub fn pack_directional_lights(
+    lights: &[DirectionalLight],
+) -> ([DirectionalLight; MAX_DIRECTIONAL_LIGHTS], u32) {
+    let mut packed = [DirectionalLight {
+        direction: Vector3::zero(),
+        intensity: 0.0,
+        color: Vector3::zero(),
+    }; MAX_DIRECTIONAL_LIGHTS];
+
+    let count = lights.len().min(MAX_DIRECTIONAL_LIGHTS);
+    packed[..count].copy_from_slice(&lights[..count]);
+
+    (packed, count as u32)
+}
+
+/// Ambient light of the sky approximated by a hemisphere: `sky_color` arrives
+/// from above and `ground_color` from below. It lights opaque objects in the
+/// direction of their bent normals and is attenuated by the sky visibility baked
+/// into vertices of meshes (`obj2bf --sky-occlusion`), so large meshes are
+/// darker where their own parts hide the sky.
+#[derive(Copy, Clone, Debug)]
+pub struct AmbientLight {
+    /// Color of the light arriving from the sky.
+    pub sky_color: Vector3<f32>,
+    /// Color of the light reflected from the ground.
+    pub ground_color: Vector3<f32>,
+    /// Intensity of the light (zero disables it).
+    pub intensity: f32,
+}
+
+impl Default for AmbientLight {
+    fn default() -> Self {
+        Self {
+            sky_color: Vector3::new(0.6, 0.7, 1.0),
+            ground_color: Vector3::new(0.3, 0.25, 0.2),
+            intensity: 0.0,
+        }
+    }
+}
+
+impl AmbientLight {
+    /// Returns the sky and ground colors multiplied by the intensity as passed
+    /// to the lighting shader.
+    pub fn packed(&self) -> ([f32; 4], [f32; 4]) {
+        let sky = self.sky_color * self.intensity;
+        let ground = self.ground_color * self.intensity;
+        (sky.extend(0.0).into(), ground.extend(0.0).into())
+    }
+}
+
+/// Wind that sways foliage (objects with `MaterialFlags::FOLIAGE` materials
+/// and meshes with stiffness of vertices). Vertices are displaced in the
+/// direction of the wind by waves whose amplitude increases as the stiffness
+/// of the vertex decreases.
+#[derive(Copy, Clone, Debug)]
+pub struct Wind {
+    /// Horizontal direction of the wind (x and z axes).
+    pub direction: Vector2<f32>,
+    /// Displacement (in meters) of vertices with zero stiffness.
+    pub strength: f32,
+    /// Frequency of the waves in Hz.
+    pub frequency: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            direction: Vector2::new(1.0, 0.0),
+            strength: 0.1,
+            frequency: 0.5,
+        }
+    }
+}
+
+impl Wind {
+    /// Returns the normalized direction, strength and frequency as passed to
+    /// the foliage shader.
+    pub fn packed(&self) -> [f32; 4] {
+        let direction = if self.direction.magnitude2() > 0.0 {
+            self.direction.normalize()
+        } else {
+            Vector2::zero()
+        };
+        [direction.x, direction.y, self.strength, self.frequency]
+    }
+}
+
+/// Spot light (light emitted from a point into a cone) of the scene.
+///
+/// The light has full intensity inside the inner cone and fades out to zero
+/// at the edge of the outer cone. Optional gobo texture is projected along the
+/// direction of the light and tints it (the outer cone spans the whole texture).
+///
+/// Spot lights do not cast shadows and do not light transparent geometry.
+#[derive(Copy, Clone, Debug)]
+pub struct SpotLight {
+    /// Position of the light source.
+    pub position: Point3<f32>,
+    /// Direction the light is pointing to.
+    pub direction: Vector3<f32>,
+    /// Color of the light.
+    pub color: Vector3<f32>,
+    /// Intensity of the light.
+    pub intensity: f32,
+    /// Distance at which the light fades out completely.
+    pub range: f32,
+    /// Angle between the direction and the edge of the inner cone.
+    pub inner_angle: Rad<f32>,
+    /// Angle between the direction and the edge of the outer cone.
+    pub outer_angle: Rad<f32>,
+    /// UUID of the image asset projected by the light.
+    pub gobo: Option<Uuid>,
+}
+
+/// UBO struct representing a [`SpotLight`](struct.SpotLight.html) relative
+/// to the camera.
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct SpotLightData {
+    /// Camera-relative position of the light.
+    pub position: Vector3<f32>,
+    /// Distance at which the light fades out completely.
+    pub range: f32,
+    /// Normalized direction the light is pointing to.
+    pub direction: Vector3<f32>,
+    /// Intensity of the light.
+    pub intensity: f32,
+    /// Color of the light.
+    pub color: Vector3<f32>,
+    /// Cosine of the inner cone angle.
+    pub cos_inner: f32,
+    /// Cosine of the outer cone angle.
+    pub cos_outer: f32,
+    /// Tangent of the outer cone angle (used to project the gobo).
+    pub tan_outer: f32,
+    /// Index of the gobo texture or `NO_GOBO`.
+    pub gobo: i32,
+}
+
+impl SpotLightData {
+    const OFF: Self = Self {
+        position: Vector3::new(0.0, 0.0, 0.0),
+        range: 0.0,
+        direction: Vector3::new(0.0, 0.0, 0.0),
+        intensity: 0.0,
+        color: Vector3::new(0.0, 0.0, 0.0),
+        cos_inner: 0.0,
+        cos_outer: 0.0,
+        tan_outer: 0.0,
+        gobo: NO_GOBO,
+    };
+
+    /// Creates the UBO data of the `light` relative to the `camera` position.
+    /// The `gobo` is the index of gobo texture of the light.
+    pub fn new(light: &SpotLight, camera: Point3<f32>, gobo: Option<usize>) -> Self {
+        // cones wider than a hemisphere can't be projected
+        let outer = Rad(light.outer_angle.0.clamp(0.0, 1.5));
+        let inner = Rad(light.inner_angle.0.clamp(0.0, outer.0));
+
+        Self {
+            position: light.position - camera,
+            range: light.range,
+            direction: light.direction.normalize(),
+            intensity: light.intensity,
+            color: light.color,
+            cos_inner: inner.cos(),
+            cos_outer: outer.cos(),
+            tan_outer: outer.tan(),
+            gobo: gobo.map_or(NO_GOBO, |x| x as i32),
+        }
+    }
+}
+
+/// Packs the `lights` relative to the `camera` position into a fixed-size array
+/// that is uploaded as UBO. Lights over the [`MAX_SPOT_LIGHTS`](constant.MAX_SPOT_LIGHTS.html)
+/// limit are ignored. The `gobo_index` returns the index of gobo texture with
+/// specified UUID (if the texture is available).
+///
+/// Returns the array and number of valid lights in it.
+pub fn pack_spot_lights<F>(
+    lights: &[SpotLight],
+    camera: Point3<f32>,
+    gobo_index: F,
+) -> ([SpotLightData; MAX_SPOT_LIGHTS], u32)
+where
+    F: Fn(&Uuid) -> Option<usize>,
+{
+    let mut packed = [SpotLightData::OFF; MAX_SPOT_LIGHTS];
+
+    let count = lights.len().min(MAX_SPOT_LIGHTS);
+    for (data, light) in packed.iter_mut().zip(&lights[..count]) {
+        *data = SpotLightData::new(light, camera, light.gobo.as_ref().and_then(&gobo_index));
+    }
+
+    (packed, count as u32)
+}
+
+assert_alignment!(MaterialData, 16);
+assert_alignment!(FrameMatrixData, 16);
+assert_alignment!(ObjectMatrixData, 16);
+assert_alignment!(DecalData, 16);
+assert_alignment!(TerrainData, 16);
+assert_alignment!(DirectionalLight, 16);
+assert_alignment!(SpotLightData, 16);
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::PerspectiveCamera;
+    use crate::render::ubo::{
+        pack_directional_lights, pack_spot_lights, AmbientLight, DecalData, DirectionalLight,
+        FrameMatrixData, SpotLight, SpotLightData, Wind, MAX_DIRECTIONAL_LIGHTS, MAX_SPOT_LIGHTS,
+        NO_GOBO,
+    };
+    use bf::uuid::Uuid;
+    use cgmath::{vec2, vec3, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector4};
+
+    #[test]
+    fn ambient_light_is_disabled_by_default() {
+        assert_eq!(AmbientLight::default().packed(), ([0.0; 4], [0.0; 4]));
+
+        let ambient = AmbientLight {
+            intensity: 2.0,
+            ..AmbientLight::default()
+        };
+        assert_eq!(ambient.packed().0, [1.2, 1.4, 2.0, 0.0]);
+    }
+
+    fn light(intensity: f32) -> DirectionalLight {
+        DirectionalLight {
+            direction: vec3(0.0, 1.0, 0.0),
+            intensity,
+            color: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn spot_light(gobo: Option<Uuid>) -> SpotLight {
+        SpotLight {
+            position: Point3::new(1.0, 5.0, 0.0),
+            direction: vec3(0.0, -2.0, 0.0),
+            color: vec3(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: Rad(0.5),
+            outer_angle: Rad(0.25),
+            gobo,
+        }
+    }
+
+    fn assert_identity(m: Matrix4<f32>) {
+        let identity = Matrix4::identity();
+        for i in 0..4 {
+            let diff: Vector4<f32> = m[i] - identity[i];
+            assert!(diff.x.abs() + diff.y.abs() + diff.z.abs() + diff.w.abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn packs_lights() {
+        let (packed, count) = pack_directional_lights(&[light(1.0), light(2.0)]);
+
+        assert_eq!(count, 2);
+        assert_eq!(packed[0].intensity, 1.0);
+        assert_eq!(packed[1].intensity, 2.0);
+        assert_eq!(packed[2].intensity, 0.0);
+    }
+
+    #[test]
+    fn ignores_lights_over_limit() {
+        let lights = vec![light(1.0); MAX_DIRECTIONAL_LIGHTS + 5];
+        let (packed, count) = pack_directional_lights(&lights);
+
+        assert_eq!(count as usize, MAX_DIRECTIONAL_LIGHTS);
+        assert_eq!(packed[MAX_DIRECTIONAL_LIGHTS - 1].intensity, 1.0);
+    }
+
+    #[test]
+    fn spot_lights_are_camera_relative() {
+        let gobo = Uuid::from_u128(7);
+        let lights = vec![
+            spot_light(None),
+            spot_light(Some(gobo)),
+            spot_light(Some(gobo)),
+        ];
+        let (packed, count) = pack_spot_lights(&lights, Point3::new(1.0, 1.0, 1.0), |x| {
+            if *x == gobo {
+                Some(3)
+            } else {
+                None
+            }
+        });
+
+        assert_eq!(count, 3);
+        assert_eq!(packed[0].position, vec3(0.0, 4.0, -1.0));
+        assert_eq!(packed[0].direction, vec3(0.0, -1.0, 0.0));
+        assert_eq!((packed[0].gobo, packed[1].gobo), (NO_GOBO, 3));
+        assert_eq!(packed[3].intensity, 0.0);
+    }
+
+    #[test]
+    fn spot_light_cones_are_clamped() {
+        // inner cone can't be wider than the outer one
+        let data = SpotLightData::new(&spot_light(None), Point3::new(0.0, 0.0, 0.0), None);
+        assert_eq!(data.cos_inner, data.cos_outer);
+        assert_eq!(data.cos_outer, 0.25f32.cos());
+
+        let mut light = spot_light(None);
+        light.outer_angle = Rad(3.0);
+        let data = SpotLightData::new(&light, Point3::new(0.0, 0.0, 0.0), None);
+        assert!(data.tan_outer.is_finite() && data.tan_outer > 0.0);
+
+        let lights = vec![light; MAX_SPOT_LIGHTS + 1];
+        let (_, count) = pack_spot_lights(&lights, Point3::new(0.0, 0.0, 0.0), |_| None);
+        assert_eq!(count as usize, MAX_SPOT_LIGHTS);
+    }
+
+    #[test]
+    fn frame_data_contains_inverse_matrices() {
+        let camera = PerspectiveCamera {
+            position: Point3::new(1.0, 2.0, 3.0),
+            forward: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            fov: Rad(1.0),
+            aspect_ratio: 16.0 / 9.0,
+            near: 0.1,
+            far: 100.0,
+        };
+        let data = FrameMatrixData::new(&camera);
+
+        // camera-relative
+        assert_eq!(data.camera_position, vec3(0.0, 0.0, 0.0));
+        assert_eq!(data.view[3], Vector4::new(0.0, 0.0, 0.0, 1.0));
+        assert_identity(data.view * data.inv_view);
+        assert_identity(data.projection * data.inv_projection);
+        // time fills the padding after the camera position (std140)
+        assert_eq!(std::mem::size_of::<FrameMatrixData>(), 288);
+    }
+
+    #[test]
+    fn wind_direction_is_normalized() {
+        let wind = Wind {
+            direction: vec2(3.0, 4.0),
+            ..Wind::default()
+        };
+        let packed = wind.packed();
+        assert!((packed[0] - 0.6).abs() < 1e-6 && (packed[1] - 0.8).abs() < 1e-6);
+        assert_eq!(packed[2..], [0.1, 0.5]);
+
+        let calm = Wind {
+            direction: vec2(0.0, 0.0),
+            ..Wind::default()
+        };
+        assert_eq!(calm.packed()[..2], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn decal_data_maps_box_to_unit_cube() {
+        let model = Matrix4::from_translation(vec3(4.0, 0.0, -2.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 4.0);
+        let data = DecalData::new(model, 0.5).unwrap();
+
+        let corner = data.inv_model * Vector4::new(5.0, 0.5, 0.0, 1.0);
+        assert!((corner - Vector4::new(0.5, 0.5, 0.5, 1.0)).magnitude() < 1e-6);
+
+        let flat = Matrix4::from_nonuniform_scale(1.0, 0.0, 1.0);
+        assert!(DecalData::new(flat, 1.0).is_none());
+    }
+}
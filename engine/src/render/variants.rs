@@ -0,0 +1,136 @@
+//! Variants of pipelines selected by properties of materials and meshes.
+//!
+//! Objects are created with a base pipeline (eg. `Buffers::geometry_pipeline`).
+//! Some materials need a different pipeline (eg. foliage animated by the wind,
+//! masked or double-sided materials), so the render path registers variants of
+//! its base pipelines and `Object::new` replaces the base pipeline with the
+//! variant that matches its material and mesh.
+
+use bf::material::{BlendMode, MaterialFlags};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+type Pipeline = Arc<dyn GraphicsPipelineAbstract + Send + Sync>;
+type WeakPipeline = Weak<dyn GraphicsPipelineAbstract + Send + Sync>;
+
+/// Registered variants by the address of the base pipeline and the variant key.
+static VARIANTS: Lazy<Mutex<HashMap<(usize, PipelineVariant), Entry>>> =
+    Lazy::new(Default::default);
+
+/// Registered variant. Only weak references are held, so the pipelines are
+/// released together with the render path that created them.
+struct Entry {
+    base: WeakPipeline,
+    variant: WeakPipeline,
+}
+
+/// Properties of an object that require a different pipeline than the base one.
+/// The default value selects the base pipeline.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct PipelineVariant {
+    /// Vertices are displaced by the wind according to their stiffness
+    /// (`MaterialFlags::FOLIAGE` material and mesh with stiffness).
+    pub foliage: bool,
+    /// Fragments with opacity below the alpha cutoff of the material are
+    /// discarded (`BlendMode::Masked` material).
+    pub masked: bool,
+    /// Back faces are not culled (`MaterialFlags::DOUBLE_SIDED` material).
+    pub double_sided: bool,
+}
+
+impl PipelineVariant {
+    /// Returns the variant used by objects with material with `blend_mode` and
+    /// `flags` and mesh that does or does not have stiffness of vertices.
+    pub fn new(blend_mode: BlendMode, flags: MaterialFlags, has_stiffness: bool) -> Self {
+        Self {
+            foliage: flags.contains(MaterialFlags::FOLIAGE) && has_stiffness,
+            masked: blend_mode == BlendMode::Masked,
+            double_sided: flags.contains(MaterialFlags::DOUBLE_SIDED),
+        }
+    }
+
+    /// Returns all variants except the default one.
+    pub fn all() -> impl Iterator<Item = PipelineVariant> {
+        (1..8u8).map(|bits| PipelineVariant {
+            foliage: bits & 1 != 0,
+            masked: bits & 2 != 0,
+            double_sided: bits & 4 != 0,
+        })
+    }
+}
+
+fn address(pipeline: &Pipeline) -> usize {
+    Arc::as_ptr(pipeline) as *const () as usize
+}
+
+/// Registers `variant` of the `base` pipeline selected by `key`.
+pub fn register_variant(base: &Pipeline, key: PipelineVariant, variant: &Pipeline) {
+    let mut variants = VARIANTS.lock();
+
+    variants.retain(|_, x| x.base.strong_count() > 0 && x.variant.strong_count() > 0);
+    variants.insert(
+        (address(base), key),
+        Entry {
+            base: Arc::downgrade(base),
+            variant: Arc::downgrade(variant),
+        },
+    );
+}
+
+/// Returns the variant of the `base` pipeline selected by `key` or the `base`
+/// pipeline itself when no such variant is registered.
+pub fn select_variant(base: Pipeline, key: PipelineVariant) -> Pipeline {
+    if key == PipelineVariant::default() {
+        return base;
+    }
+
+    let variant = VARIANTS.lock().get(&(address(&base), key)).and_then(|x| {
+        // the address may belong to a new pipeline if the base one was released
+        let registered = x.base.upgrade()?;
+        if address(&registered) != address(&base) {
+            return None;
+        }
+        x.variant.upgrade()
+    });
+
+    variant.unwrap_or(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::variants::PipelineVariant;
+    use bf::material::{BlendMode, MaterialFlags};
+    use std::collections::HashSet;
+
+    #[test]
+    fn foliage_variant_requires_stiffness() {
+        let opaque = BlendMode::Opaque;
+        assert!(PipelineVariant::new(opaque, MaterialFlags::FOLIAGE, true).foliage);
+        assert!(!PipelineVariant::new(opaque, MaterialFlags::FOLIAGE, false).foliage);
+        assert_eq!(
+            PipelineVariant::new(opaque, MaterialFlags::empty(), true),
+            PipelineVariant::default()
+        );
+    }
+
+    #[test]
+    fn masked_and_double_sided_variants() {
+        let leaves = PipelineVariant::new(
+            BlendMode::Masked,
+            MaterialFlags::FOLIAGE | MaterialFlags::DOUBLE_SIDED,
+            true,
+        );
+        assert!(leaves.foliage && leaves.masked && leaves.double_sided);
+        assert!(
+            !PipelineVariant::new(BlendMode::Translucent, MaterialFlags::empty(), false).masked
+        );
+
+        let all = PipelineVariant::all().collect::<HashSet<_>>();
+        assert_eq!(all.len(), 7);
+        assert!(all.contains(&leaves));
+        assert!(!all.contains(&PipelineVariant::default()));
+    }
+}
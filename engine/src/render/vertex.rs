@@ -0,0 +1,138 @@
+//! Declaration of different `Vertex` types.
+
+use cgmath::{Matrix3, Matrix4};
+use safe_transmute::TriviallyTransmutable;
+
+/// Vertex that consists only of *position*.
+///
+/// Layout of this vertex is following:
+///
+/// | f32_0      | f32_1      | f32_2      | f32_3     |
+/// |------------|------------|------------|-----------|
+/// | position.x | position.y | position.z |*(padding)*|
+#[derive(Default, Debug, Clone, Copy)]
+pub struct PositionOnlyVertex {
+    pub position: [f32; 4],
+}
+
+/// Vertex that consists of *position*, *normal* and one *uv coordinate*.
+///
+/// Layout of this vertex is following:
+///
+/// | f32_0      | f32_1      | f32_2      | f32_3     |
+/// |------------|------------|------------|-----------|
+/// | position.x | position.y | position.z | normal.x  |
+/// | normal.y   | normal.z   | uv.x       | uv.y      |
+#[derive(Default, Debug, Clone, Copy)]
+pub struct BasicVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// Vertex that consists of *position*, *normal*, one *uv coordinate* and *tangent*.
+///
+/// Layout of this vertex is following:
+///
+/// | f32_0      | f32_1      | f32_2      | f32_3     |
+/// |------------|------------|------------|-----------|
+/// | position.x | position.y | position.z | normal.x  |
+/// | normal.y   | normal.z   | uv.x       | uv.y      |
+/// | tangent.x  | tangent.y  | tangent.z  |*(padding)*|
+///
+#[derive(Default, Debug, Clone, Copy)]
+pub struct NormalMappedVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub tangent: [f32; 4],
+}
+
+/// Second vertex buffer of foliage meshes with the *stiffness* of each vertex
+/// (see `bf::mesh::Mesh::stiffness`) used by the wind animation.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct WindVertex {
+    pub stiffness: f32,
+}
+
+/// Per-instance data of instanced geometry: columns of the camera-relative
+/// model matrix and of the normal matrix and the cross-fade range.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct InstanceData {
+    pub model_x: [f32; 4],
+    pub model_y: [f32; 4],
+    pub model_z: [f32; 4],
+    pub model_w: [f32; 4],
+    pub normal_x: [f32; 3],
+    pub normal_y: [f32; 3],
+    pub normal_z: [f32; 3],
+    /// Range of dither thresholds of pixels that are drawn (see `render::lod_fade`).
+    pub fade: [f32; 2],
+}
+
+impl InstanceData {
+    pub fn new(model: Matrix4<f32>, normal: Matrix3<f32>, fade: [f32; 2]) -> Self {
+        Self {
+            model_x: model.x.into(),
+            model_y: model.y.into(),
+            model_z: model.z.into(),
+            model_w: model.w.into(),
+            normal_x: normal.x.into(),
+            normal_y: normal.y.into(),
+            normal_z: normal.z.into(),
+            fade,
+        }
+    }
+}
+
+/// Vertex of the overlay consisting of *position* in normalized device
+/// coordinates and *color*.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Vertex of debug lines consisting of *position* relative to the camera and
+/// *color*.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct DebugLineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+/// Vertex of the GUI consisting of *position* in points, *uv* into the font
+/// texture and linear *color* with premultiplied alpha.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct GuiVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+unsafe impl TriviallyTransmutable for PositionOnlyVertex {}
+
+unsafe impl TriviallyTransmutable for BasicVertex {}
+
+unsafe impl TriviallyTransmutable for NormalMappedVertex {}
+
+unsafe impl TriviallyTransmutable for WindVertex {}
+
+vulkano::impl_vertex!(NormalMappedVertex, position, normal, uv, tangent);
+vulkano::impl_vertex!(BasicVertex, position, normal, uv);
+vulkano::impl_vertex!(PositionOnlyVertex, position);
+vulkano::impl_vertex!(WindVertex, stiffness);
+vulkano::impl_vertex!(
+    InstanceData,
+    model_x,
+    model_y,
+    model_z,
+    model_w,
+    normal_x,
+    normal_y,
+    normal_z,
+    fade
+);
+vulkano::impl_vertex!(OverlayVertex, position, color);
+vulkano::impl_vertex!(DebugLineVertex, position, color);
+vulkano::impl_vertex!(GuiVertex, position, uv, color);
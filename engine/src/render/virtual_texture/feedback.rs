@@ -0,0 +1,262 @@
+//! Feedback pass finding pages of a virtual texture needed by the visible surfaces.
+//!
+//! Objects using the virtual texture are rendered into a buffer with a fraction of
+//! the screen resolution. Each pixel contains the page (and its level) needed to
+//! texture the pixel in full resolution. The buffer is then read back to the CPU
+//! with a [`ReadbackManager`](../../readback/struct.ReadbackManager.html).
+
+use crate::camera::Camera;
+use crate::render::graph::{AttachmentId, RenderGraph};
+use crate::render::object::Object;
+use crate::render::readback::ReadbackManager;
+use crate::render::shader_cache::CachedShader;
+use crate::render::ubo::FrameMatrixData;
+use crate::render::vertex::NormalMappedVertex;
+use crate::render::virtual_texture::VirtualTexture;
+use crate::render::vulkan::pipeline_cache;
+use crate::resources::mesh::DynamicIndexedMesh;
+use std::sync::Arc;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, DynamicState, PrimaryAutoCommandBuffer,
+    SubpassContents,
+};
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::render_pass::{Framebuffer, FramebufferAbstract, LoadOp, RenderPass, StoreOp};
+
+pub mod shaders {
+    pub mod vertex {
+        const X: &str = include_str!("../../../shaders/vs_virtual_texture_feedback.glsl");
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            path: "shaders/vs_virtual_texture_feedback.glsl"
+        }
+    }
+
+    pub mod fragment {
+        const X: &str = include_str!("../../../shaders/fs_virtual_texture_feedback.glsl");
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            path: "shaders/fs_virtual_texture_feedback.glsl"
+        }
+    }
+}
+
+/// Ratio of the screen resolution to the resolution of the feedback buffer.
+pub const FEEDBACK_SCALE: u32 = 8;
+
+/// Returns dimensions of the feedback buffer for the screen `resolution`.
+pub fn feedback_dimensions(resolution: [u32; 2]) -> [u32; 2] {
+    [
+        (resolution[0] / FEEDBACK_SCALE).max(1),
+        (resolution[1] / FEEDBACK_SCALE).max(1),
+    ]
+}
+
+pub struct FeedbackPass {
+    graph: RenderGraph,
+    depth: AttachmentId,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    // the feedback buffer is copied to the cpu, so it is not created by the graph
+    feedback: Arc<AttachmentImage>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    dims: [u32; 2],
+}
+
+impl FeedbackPass {
+    /// Creates the pass for the screen `resolution`.
+    pub fn new(device: Arc<Device>, resolution: [u32; 2]) -> Self {
+        let mut graph = RenderGraph::new();
+        let feedback = graph.attachment(
+            "Virtual texture feedback",
+            Format::R8G8B8A8Unorm,
+            LoadOp::Clear,
+            StoreOp::Store,
+        );
+        let depth = graph.attachment(
+            "Virtual texture feedback depth",
+            Format::D16Unorm,
+            LoadOp::Clear,
+            StoreOp::DontCare,
+        );
+        // zero alpha means that no page is requested
+        graph.clear_value(feedback, ClearValue::Float([0.0, 0.0, 0.0, 0.0]));
+        graph.clear_value(depth, ClearValue::Depth(1.0));
+        let pass = graph
+            .pass("Virtual Texture Feedback")
+            .color(feedback)
+            .depth_stencil(depth)
+            .add();
+        let render_pass = graph
+            .create_render_pass(device.clone())
+            .expect("cannot create render pass for virtual texture feedback");
+
+        let vs = shaders::vertex::Shader::load(device.clone()).unwrap();
+        let fs = shaders::fragment::Shader::load(device.clone()).unwrap();
+        let cached_vs = CachedShader::load(device.clone(), "vs_virtual_texture_feedback");
+        let cached_fs = CachedShader::load(device.clone(), "fs_virtual_texture_feedback");
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<NormalMappedVertex>()
+                .vertex_shader(cached_vs.entry_point(vs.main_entry_point()), ())
+                .fragment_shader(cached_fs.entry_point(fs.main_entry_point()), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil::simple_depth_test())
+                .render_pass(graph.subpass(&render_pass, pass))
+                .build_with_cache(pipeline_cache(&device))
+                .build(device.clone())
+                .expect("cannot create graphics pipeline for virtual texture feedback"),
+        );
+
+        let dims = feedback_dimensions(resolution);
+        let (feedback, framebuffer) = create_buffers(&graph, depth, &render_pass, dims);
+
+        Self {
+            graph,
+            depth,
+            render_pass,
+            pipeline: pipeline as Arc<_>,
+            feedback,
+            framebuffer,
+            dims,
+        }
+    }
+
+    /// Recreates the feedback buffer for the new screen `resolution`.
+    pub fn dimensions_changed(&mut self, resolution: [u32; 2]) {
+        let dims = feedback_dimensions(resolution);
+        let (feedback, framebuffer) =
+            create_buffers(&self.graph, self.depth, &self.render_pass, dims);
+        self.feedback = feedback;
+        self.framebuffer = framebuffer;
+        self.dims = dims;
+    }
+
+    /// Records rendering of the visible `objects` textured with the `texture` into
+    /// the feedback buffer followed by its readback. The requested pages are passed to
+    /// the `texture` once the readback finishes. The command buffer should be executed
+    /// before the frame in which it was recorded is submitted (eg. joined with the
+    /// uploads passed to `RendererState::render_frame`).
+    pub fn record<'a>(
+        &self,
+        camera: &dyn Camera<f32>,
+        objects: impl IntoIterator<Item = &'a Object<NormalMappedVertex>>,
+        texture: &VirtualTexture,
+        readbacks: &mut ReadbackManager,
+        queue: Arc<Queue>,
+    ) -> PrimaryAutoCommandBuffer {
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [self.dims[0] as f32, self.dims[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+        let fmd = FrameMatrixData::new(camera);
+        let view_projection = fmd.projection * fmd.view;
+        let params = texture.params();
+
+        let mut b = AutoCommandBufferBuilder::primary(
+            queue.device().clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        b.begin_render_pass(
+            self.framebuffer.clone(),
+            SubpassContents::Inline,
+            self.graph.clear_values(),
+        )
+        .unwrap();
+
+        for x in objects.into_iter().filter(|x| x.visible) {
+            let push_constants = shaders::fragment::ty::PushConstants {
+                model_view_projection: (view_projection * x.model_matrix(camera.position())).into(),
+                size: params.size,
+                page_size: params.page_size,
+                border: params.border,
+                cache_size: params.cache_size,
+                mip_count: params.mip_count,
+                mip_bias: -(FEEDBACK_SCALE as f32).log2(),
+            };
+
+            // todo: get rid of this dispatch somehow
+            match &*x.mesh.get() {
+                DynamicIndexedMesh::U16(m) => b
+                    .draw_indexed(
+                        self.pipeline.clone(),
+                        &dynamic_state,
+                        vec![m.vertex_buffer().clone()],
+                        m.lod_index_buffer(0),
+                        (),
+                        push_constants,
+                    )
+                    .expect("cannot DrawIndexed this mesh"),
+                DynamicIndexedMesh::U32(m) => b
+                    .draw_indexed(
+                        self.pipeline.clone(),
+                        &dynamic_state,
+                        vec![m.vertex_buffer().clone()],
+                        m.lod_index_buffer(0),
+                        (),
+                        push_constants,
+                    )
+                    .expect("cannot DrawIndexed this mesh"),
+            };
+        }
+        b.end_render_pass().unwrap();
+
+        readbacks.read_image(
+            &mut b,
+            self.feedback.clone(),
+            (self.dims[0] * self.dims[1] * 4) as usize,
+            texture.feedback_callback(),
+        );
+
+        b.build().unwrap()
+    }
+}
+
+/// Creates the feedback buffer (which can be copied to the cpu) and the framebuffer.
+fn create_buffers(
+    graph: &RenderGraph,
+    depth: AttachmentId,
+    render_pass: &Arc<RenderPass>,
+    dims: [u32; 2],
+) -> (
+    Arc<AttachmentImage>,
+    Arc<dyn FramebufferAbstract + Send + Sync>,
+) {
+    let device = render_pass.device().clone();
+    let feedback = AttachmentImage::with_usage(
+        device.clone(),
+        dims,
+        Format::R8G8B8A8Unorm,
+        ImageUsage {
+            transfer_source: true,
+            ..ImageUsage::color_attachment()
+        },
+    )
+    .expect("cannot create virtual texture feedback buffer");
+    let images = graph
+        .create_images(device, dims)
+        .expect("cannot create virtual texture feedback depth buffer");
+
+    let framebuffer = Framebuffer::start(render_pass.clone())
+        .add(ImageView::new(feedback.clone()).expect("cannot create image view"))
+        .and_then(|x| x.add(images.get(depth)))
+        .expect("cannot add attachment to framebuffer")
+        .build()
+        .expect("cannot build framebuffer");
+
+    (feedback, Arc::new(framebuffer))
+}
@@ -0,0 +1,218 @@
+//! Experimental virtual texturing of very large textures (eg. 32K terrain textures)
+//! that are never loaded whole.
+//!
+//! Pages of the [`bf::virtual_texture::VirtualTexture`] needed by the visible
+//! surfaces are found by the [feedback pass](feedback/struct.FeedbackPass.html),
+//! which renders the objects into a small buffer with the page requested by each
+//! pixel. The buffer is read back to the CPU a few frames later and the missing
+//! pages are copied into slots of the physical page cache texture (least recently
+//! used pages are replaced). The page table texture (one texel per page, with
+//! mip-maps matching the levels of the texture) maps each page to its slot or to
+//! the slot of its closest resident coarser page, so the shader always samples the
+//! best available page (see `shaders/inc_virtual_texture.glsl`).
+//!
+//! Pages are read from the asset as they are needed, so with memory-mapped assets
+//! (`mmap_assets` setting) only the used pages are ever loaded from the disk.
+
+use crate::render::readback::ReadbackCallback;
+use crate::render::virtual_texture::page_cache::{decode_feedback, PageCache};
+use crate::resources::image::{CreateImageError, ImageRegion, UpdatableImage, UpdateImageError};
+use bf::image::{Format, Image};
+use bf::virtual_texture::PageId;
+use log::warn;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use vulkano::device::Queue;
+use vulkano::image::view::ImageView;
+use vulkano::image::ImmutableImage;
+use vulkano::sync::GpuFuture;
+
+pub mod feedback;
+pub mod page_cache;
+
+/// Maximum number of pages uploaded into the page cache in one frame.
+const MAX_UPLOADS_PER_FRAME: usize = 16;
+
+/// Parameters of the virtual texture needed by the shaders (see
+/// `VirtualTextureParams` in `shaders/inc_virtual_texture.glsl`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VirtualTextureParams {
+    pub size: f32,
+    pub page_size: f32,
+    pub border: f32,
+    pub cache_size: f32,
+    pub mip_count: f32,
+}
+
+/// Virtual texture with its page table and physical page cache on the GPU.
+pub struct VirtualTexture {
+    texture: Arc<bf::virtual_texture::VirtualTexture>,
+    cache: PageCache,
+    page_table: UpdatableImage,
+    physical: UpdatableImage,
+    /// Pages requested by the latest finished feedback readback.
+    requests: Arc<Mutex<Option<Vec<PageId>>>>,
+    last_requests: Vec<PageId>,
+    frame: u64,
+}
+
+impl VirtualTexture {
+    /// Creates the page table and the page cache with `slots_per_side` x `slots_per_side`
+    /// pages for the `texture`. Formats with three channels are not supported by
+    /// most devices, textures should be stored with alpha.
+    pub fn new(
+        texture: Arc<bf::virtual_texture::VirtualTexture>,
+        slots_per_side: u32,
+        queue: Arc<Queue>,
+    ) -> Result<(Self, impl GpuFuture), CreateImageError> {
+        let pages = texture.pages_per_side(0);
+        let page_table_bytes = (0..texture.mip_count())
+            .map(|mip| ((pages >> mip) as usize).pow(2) * 4)
+            .sum();
+        let (page_table, f1) = UpdatableImage::new(
+            &Image {
+                format: Format::Rgba8,
+                width: pages as u16,
+                height: pages as u16,
+                mipmap_data: vec![0; page_table_bytes].into(),
+            },
+            queue.clone(),
+        )?;
+
+        let cache_size = slots_per_side * texture.padded_page_size();
+        let cache_bytes =
+            (cache_size as usize).pow(2) * texture.format.bits_per_pixel() as usize / 8;
+        let (physical, f2) = UpdatableImage::new(
+            &Image {
+                format: texture.format,
+                width: cache_size as u16,
+                height: cache_size as u16,
+                mipmap_data: vec![0; cache_bytes].into(),
+            },
+            queue,
+        )?;
+
+        let virtual_texture = Self {
+            cache: PageCache::new(slots_per_side, texture.mip_count()),
+            texture,
+            page_table,
+            physical,
+            requests: Arc::new(Mutex::new(None)),
+            last_requests: vec![],
+            frame: 0,
+        };
+
+        Ok((virtual_texture, f1.join(f2)))
+    }
+
+    /// Returns the page table texture (must be sampled with nearest filtering).
+    pub fn page_table(&self) -> Arc<ImageView<Arc<ImmutableImage>>> {
+        self.page_table.view()
+    }
+
+    /// Returns the physical page cache texture.
+    pub fn cache(&self) -> Arc<ImageView<Arc<ImmutableImage>>> {
+        self.physical.view()
+    }
+
+    /// Returns the number of pages in the page cache.
+    pub fn resident_pages(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn params(&self) -> VirtualTextureParams {
+        VirtualTextureParams {
+            size: self.texture.size as f32,
+            page_size: self.texture.page_size as f32,
+            border: self.texture.border as f32,
+            cache_size: self.physical.mip_size(0).unwrap()[0] as f32,
+            mip_count: self.texture.mip_count() as f32,
+        }
+    }
+
+    /// Returns the callback that stores pages requested by the read back feedback
+    /// buffer (see [`FeedbackPass`](feedback/struct.FeedbackPass.html)).
+    pub fn feedback_callback(&self) -> ReadbackCallback {
+        let requests = self.requests.clone();
+        Box::new(move |result| {
+            *requests.lock() = Some(decode_feedback(result.data));
+        })
+    }
+
+    /// Uploads missing pages requested by the latest feedback (at most
+    /// `MAX_UPLOADS_PER_FRAME` of them) and updates the page table. Should be called
+    /// once per frame, the returned future must be waited for before the frame that
+    /// samples the texture is rendered (eg. passed to `RendererState::render_frame`).
+    pub fn update(&mut self, queue: Arc<Queue>) -> Option<Box<dyn GpuFuture + Send>> {
+        self.frame += 1;
+
+        // pages requested by the last feedback stay requested until a new one arrives
+        if let Some(requests) = self.requests.lock().take() {
+            let texture = &self.texture;
+            self.last_requests = requests
+                .into_iter()
+                .filter(|x| texture.page_index(*x).is_some())
+                .collect();
+        }
+
+        let assigned = self
+            .cache
+            .request(&self.last_requests, self.frame, MAX_UPLOADS_PER_FRAME);
+        if assigned.is_empty() {
+            return None;
+        }
+
+        match self.upload(&assigned, queue) {
+            Ok(future) => Some(future),
+            Err(e) => {
+                // usually the previous upload is still in progress, pages will be
+                // requested again in the next frame
+                warn!("Cannot upload pages of virtual texture: {:?}", e);
+                for (page, _) in assigned {
+                    self.cache.remove(page);
+                }
+                None
+            }
+        }
+    }
+
+    /// Copies `assigned` pages into their slots and uploads the new page table.
+    fn upload(
+        &self,
+        assigned: &[(PageId, u32)],
+        queue: Arc<Queue>,
+    ) -> Result<Box<dyn GpuFuture + Send>, UpdateImageError> {
+        let padded = self.texture.padded_page_size();
+        let pages = assigned
+            .iter()
+            .map(|(page, slot)| {
+                let [x, y] = self.cache.slot_position(*slot);
+                ImageRegion {
+                    mip: 0,
+                    offset: [x * padded, y * padded],
+                    size: [padded, padded],
+                    data: self.texture.page(*page).expect("page is not stored"),
+                }
+            })
+            .collect::<Vec<_>>();
+        let f1 = self.physical.update_regions(&pages, queue.clone())?;
+
+        let table = self.cache.page_table(self.texture.pages_per_side(0));
+        let levels = table
+            .iter()
+            .enumerate()
+            .map(|(mip, data)| {
+                let side = self.texture.pages_per_side(mip as u32);
+                ImageRegion {
+                    mip: mip as u32,
+                    offset: [0, 0],
+                    size: [side, side],
+                    data,
+                }
+            })
+            .collect::<Vec<_>>();
+        let f2 = self.page_table.update_regions(&levels, queue)?;
+
+        Ok(f1.join(f2).boxed_send())
+    }
+}
@@ -0,0 +1,294 @@
+//! CPU side bookkeeping of pages resident in the physical page cache.
+
+use bf::virtual_texture::PageId;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Entry of the page table of a page that has no resident page (not even the
+/// smallest level), the alpha is zero.
+const MISSING_ENTRY: [u8; 4] = [0, 0, 0, 0];
+
+struct Resident {
+    slot: u32,
+    /// Index of the last frame the page was requested in.
+    last_used: u64,
+}
+
+/// Assigns pages of one virtual texture to slots of the physical cache texture
+/// (a grid of `slots_per_side` x `slots_per_side` pages) and evicts the least
+/// recently used pages when the cache is full.
+///
+/// The single page of the smallest level (`root`) is never evicted, so there is
+/// always a page the shader can fall back to.
+pub struct PageCache {
+    slots_per_side: u32,
+    root: PageId,
+    resident: HashMap<PageId, Resident>,
+    free: Vec<u32>,
+}
+
+impl PageCache {
+    /// Creates an empty cache for a texture with `mip_count` levels.
+    pub fn new(slots_per_side: u32, mip_count: u32) -> Self {
+        // positions of slots are stored as bytes in the page table
+        assert!(
+            slots_per_side > 0 && slots_per_side <= 256,
+            "page cache must have 1 to 256 slots per side"
+        );
+
+        Self {
+            slots_per_side,
+            root: PageId::new(mip_count as u8 - 1, 0, 0),
+            resident: HashMap::new(),
+            // slots are taken from the end
+            free: (0..slots_per_side * slots_per_side).rev().collect(),
+        }
+    }
+
+    /// Returns the position of the `slot` in the grid of slots.
+    pub fn slot_position(&self, slot: u32) -> [u32; 2] {
+        [slot % self.slots_per_side, slot / self.slots_per_side]
+    }
+
+    /// Returns the slot of the `page` or `None` if the page is not resident.
+    pub fn slot(&self, page: PageId) -> Option<u32> {
+        self.resident.get(&page).map(|x| x.slot)
+    }
+
+    /// Returns the number of resident pages.
+    pub fn len(&self) -> usize {
+        self.resident.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resident.is_empty()
+    }
+
+    /// Marks the `requested` pages (and all levels above them) as used in the `frame`
+    /// and assigns slots to at most `max` of them that are not resident yet. Coarser
+    /// levels are assigned first, so the fallback pages are always available. Slots
+    /// of pages that were not used in this frame are reused when the cache is full.
+    ///
+    /// Returns newly assigned pages with their slots. The caller must upload them
+    /// or [`remove`](#method.remove) them when the upload fails.
+    pub fn request(&mut self, requested: &[PageId], frame: u64, max: usize) -> Vec<(PageId, u32)> {
+        let mut pages = requested
+            .iter()
+            .filter(|x| x.mip <= self.root.mip)
+            .flat_map(|x| self.with_ancestors(*x))
+            .chain(std::iter::once(self.root))
+            .collect::<Vec<_>>();
+        pages.sort_by_key(|x| (Reverse(x.mip), x.y, x.x));
+        pages.dedup();
+
+        // pages requested in this frame must be marked before any page is evicted
+        for page in pages.iter() {
+            if let Some(resident) = self.resident.get_mut(page) {
+                resident.last_used = frame;
+            }
+        }
+
+        let mut assigned = Vec::new();
+        for page in pages {
+            if self.resident.contains_key(&page) {
+                continue;
+            }
+            if assigned.len() == max {
+                break;
+            }
+
+            let slot = match self.free.pop().or_else(|| self.evict(frame)) {
+                Some(t) => t,
+                // all pages are used in this frame
+                None => continue,
+            };
+            self.resident.insert(
+                page,
+                Resident {
+                    slot,
+                    last_used: frame,
+                },
+            );
+            assigned.push((page, slot));
+        }
+
+        assigned
+    }
+
+    /// Returns the `page` followed by pages of all coarser levels that contain it.
+    fn with_ancestors(&self, page: PageId) -> impl Iterator<Item = PageId> {
+        let root = self.root.mip;
+        std::iter::successors(Some(page), move |x| {
+            if x.mip < root {
+                Some(x.parent())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Evicts the least recently used page (preferring finer levels) that was not
+    /// used in the `frame` and returns its slot.
+    fn evict(&mut self, frame: u64) -> Option<u32> {
+        let root = self.root;
+        let page = self
+            .resident
+            .iter()
+            .filter(|(page, x)| x.last_used < frame && **page != root)
+            .min_by_key(|(page, x)| (x.last_used, page.mip))
+            .map(|(page, _)| *page)?;
+
+        self.resident.remove(&page).map(|x| x.slot)
+    }
+
+    /// Removes the `page` from the cache (eg. when its upload failed).
+    pub fn remove(&mut self, page: PageId) {
+        if let Some(resident) = self.resident.remove(&page) {
+            self.free.push(resident.slot);
+        }
+    }
+
+    /// Computes all levels of the page table of a texture whose biggest level has
+    /// `pages_per_side` x `pages_per_side` pages. Each entry (RGBA8) contains the
+    /// position of the slot in the grid of slots (red, green) and the level (blue) of
+    /// the page or its closest resident ancestor. Alpha is 255 for valid entries.
+    pub fn page_table(&self, pages_per_side: u32) -> Vec<Vec<u8>> {
+        let mip_count = self.root.mip as u32 + 1;
+        let mut levels: Vec<Vec<u8>> = Vec::with_capacity(mip_count as usize);
+
+        // levels are computed from the smallest one, so entries of missing pages
+        // can be copied from the level above
+        for mip in (0..mip_count).rev() {
+            let side = pages_per_side >> mip;
+            let mut level = Vec::with_capacity((side * side * 4) as usize);
+
+            for y in 0..side {
+                for x in 0..side {
+                    let page = PageId::new(mip as u8, x, y);
+                    let entry = match (self.slot(page), levels.last()) {
+                        (Some(slot), _) => {
+                            let [sx, sy] = self.slot_position(slot);
+                            [sx as u8, sy as u8, mip as u8, 255]
+                        }
+                        (None, Some(parent)) => {
+                            let idx = ((y / 2) * (side / 2) + x / 2) as usize * 4;
+                            [
+                                parent[idx],
+                                parent[idx + 1],
+                                parent[idx + 2],
+                                parent[idx + 3],
+                            ]
+                        }
+                        (None, None) => MISSING_ENTRY,
+                    };
+                    level.extend_from_slice(&entry);
+                }
+            }
+
+            levels.push(level);
+        }
+
+        levels.reverse();
+        levels
+    }
+}
+
+/// Decodes pages requested by pixels of the feedback buffer (RGBA8). Each pixel
+/// contains the low 8 bits of the page column (red) and row (green), their high
+/// 4 bits (blue) and the level increased by one (alpha), zero alpha means that
+/// no page was requested. Returns unique pages.
+pub fn decode_feedback(data: &[u8]) -> Vec<PageId> {
+    let mut pages = data
+        .chunks_exact(4)
+        .filter(|px| px[3] > 0)
+        .map(|px| {
+            let x = px[0] as u32 | ((px[2] as u32 & 0x0f) << 8);
+            let y = px[1] as u32 | ((px[2] as u32 >> 4) << 8);
+            PageId::new(px[3] - 1, x, y)
+        })
+        .collect::<Vec<_>>();
+    pages.sort_unstable();
+    pages.dedup();
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::virtual_texture::page_cache::{decode_feedback, PageCache};
+    use bf::virtual_texture::PageId;
+
+    #[test]
+    fn assigns_coarse_pages_first() {
+        // 3 levels: 4x4, 2x2 and 1x1 pages
+        let mut cache = PageCache::new(2, 3);
+
+        let assigned = cache.request(&[PageId::new(0, 3, 3)], 1, 2);
+        assert_eq!(
+            assigned,
+            vec![(PageId::new(2, 0, 0), 0), (PageId::new(1, 1, 1), 1)]
+        );
+
+        let assigned = cache.request(&[PageId::new(0, 3, 3)], 2, 2);
+        assert_eq!(assigned, vec![(PageId::new(0, 3, 3), 2)]);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_pages() {
+        let mut cache = PageCache::new(1, 2);
+        cache.request(&[], 1, 4);
+
+        // the only slot is taken by the root, which is never evicted
+        assert!(cache.request(&[PageId::new(0, 0, 0)], 2, 4).is_empty());
+
+        let mut cache = PageCache::new(2, 2);
+        cache.request(&[PageId::new(0, 0, 0), PageId::new(0, 1, 0)], 1, 4);
+        cache.request(&[PageId::new(0, 1, 1)], 2, 4);
+        cache.request(&[PageId::new(0, 1, 0)], 3, 4);
+
+        // (0, 0) was used before (1, 1)
+        let assigned = cache.request(&[PageId::new(0, 0, 1)], 4, 4);
+        assert_eq!(assigned, vec![(PageId::new(0, 0, 1), 1)]);
+        assert_eq!(cache.slot(PageId::new(0, 0, 0)), None);
+
+        // pages requested in the same frame are not evicted
+        let requested = [PageId::new(0, 0, 0), PageId::new(0, 0, 1)];
+        let assigned = cache.request(&requested, 5, 4);
+        assert_eq!(assigned.len(), 1);
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn page_table_falls_back_to_resident_ancestors() {
+        let mut cache = PageCache::new(4, 2);
+        assert_eq!(cache.page_table(2)[1], [0, 0, 0, 0]);
+
+        cache.request(&[PageId::new(0, 1, 0)], 1, 4);
+        let table = cache.page_table(2);
+
+        // root is in slot 0, the requested page in slot 1
+        assert_eq!(table[1], [0, 0, 1, 255]);
+        assert_eq!(
+            table[0],
+            [0, 0, 1, 255, 1, 0, 0, 255, 0, 0, 1, 255, 0, 0, 1, 255]
+        );
+
+        cache.remove(PageId::new(0, 1, 0));
+        assert_eq!(cache.page_table(2)[0][4..8], [0, 0, 1, 255]);
+    }
+
+    #[test]
+    fn decodes_feedback() {
+        let data = [
+            0, 0, 0, 0, // nothing requested
+            5, 7, 0, 3, // page (5, 7) of level 2
+            0x34, 0x12, 0x1a, 1, // page (0xa34, 0x112) of level 0
+            5, 7, 0, 3, // duplicate
+        ];
+
+        assert_eq!(
+            decode_feedback(&data),
+            vec![PageId::new(0, 0xa34, 0x112), PageId::new(2, 5, 7)]
+        );
+    }
+}
@@ -0,0 +1,556 @@
+//! Vulkan state & initialization.
+
+use crate::RendererConfiguration;
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use vulkano::device::physical::{PhysicalDevice, QueueFamily};
+use vulkano::device::{Device, DeviceCreationError, DeviceExtensions, Features, Queue};
+use vulkano::instance::{layers_list, Instance, InstanceExtensions};
+use vulkano::pipeline::cache::PipelineCache as VkPipelineCache;
+use vulkano::swapchain::Surface;
+use vulkano::{app_info_from_cargo_toml, Version};
+use vulkano_win::{CreationError, VkSurfaceBuild};
+use winit::dpi::LogicalSize;
+use winit::event_loop::{EventLoop, EventLoopWindowTarget};
+use winit::window::{Window, WindowBuilder};
+
+/// Lazily created *Vulkan* `Instance`.
+static INSTANCE: OnceCell<Arc<Instance>> = OnceCell::new();
+
+/// Flag that specified whether to use *Vulkan* validation layers.
+const USE_VALIDATION_LAYERS: bool = true;
+
+/// Creates or gets the already existing `Instance` struct representing
+/// the Vulkan *Instance*.
+fn get_or_create_instance() -> Arc<Instance> {
+    INSTANCE
+        .get_or_init(|| {
+            info!("Creating Vulkan instance...");
+
+            let layers = if USE_VALIDATION_LAYERS && validation_layers_available() {
+                Some("VK_LAYER_KHRONOS_validation")
+            } else {
+                None
+            };
+
+            // we create vulkan instance object with extensions
+            // required to create a windows which we will render to.
+            Instance::new(
+                Some(&app_info_from_cargo_toml!()),
+                Version::V1_1,
+                &InstanceExtensions {
+                    ext_debug_utils: true,
+                    ..vulkano_win::required_extensions()
+                },
+                layers,
+            )
+            .expect("cannot create vulkan instance")
+        })
+        .clone()
+}
+
+/// Returns whether the *Khronos* validation layers are installed.
+fn validation_layers_available() -> bool {
+    match layers_list() {
+        Ok(mut layers) => layers.any(|l| l.name() == "VK_LAYER_KHRONOS_validation"),
+        Err(_) => false,
+    }
+}
+
+/// Pipeline caches of devices by which pipelines are built. Only weak references
+/// are held, so the caches are released together with their `VulkanState`.
+static PIPELINE_CACHES: Lazy<Mutex<Vec<(Weak<Device>, Weak<SharedCache>)>>> =
+    Lazy::new(Default::default);
+
+/// Cache shared by the [`PipelineCache`] and the registry of caches.
+struct SharedCache {
+    cache: Arc<VkPipelineCache>,
+    /// Number of pipelines built with the cache.
+    pipelines: AtomicUsize,
+}
+
+/// Returns the pipeline cache of the `device` that should be passed to every
+/// pipeline build (`build_with_cache`). Devices without a [`PipelineCache`]
+/// (eg. headless ones) get a new empty cache that is not persisted.
+pub fn pipeline_cache(device: &Arc<Device>) -> Arc<VkPipelineCache> {
+    let mut caches = PIPELINE_CACHES.lock();
+    caches.retain(|x| x.0.strong_count() > 0 && x.1.strong_count() > 0);
+
+    let shared = caches
+        .iter()
+        .filter(|x| x.0.upgrade().map_or(false, |d| Arc::ptr_eq(&d, device)))
+        .find_map(|x| x.1.upgrade());
+
+    match shared {
+        Some(shared) => {
+            shared.pipelines.fetch_add(1, Ordering::Relaxed);
+            shared.cache.clone()
+        }
+        None => VkPipelineCache::empty(device.clone()).expect("cannot create pipeline cache"),
+    }
+}
+
+/// Size of the header of the pipeline cache data (`VkPipelineCacheHeaderVersionOne`).
+const CACHE_HEADER_SIZE: usize = 32;
+
+/// Returns whether the pipeline cache `data` was created by the GPU with
+/// `vendor_id`, `device_id` and driver with `uuid`. Data of other drivers
+/// would be ignored by the driver anyway, so it is not loaded at all.
+fn is_compatible_cache(data: &[u8], vendor_id: u32, device_id: u32, uuid: &[u8; 16]) -> bool {
+    if data.len() < CACHE_HEADER_SIZE {
+        return false;
+    }
+
+    let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    u32_at(0) as usize >= CACHE_HEADER_SIZE
+        && u32_at(4) == 1 // VK_PIPELINE_CACHE_HEADER_VERSION_ONE
+        && u32_at(8) == vendor_id
+        && u32_at(12) == device_id
+        && &data[16..32] == uuid
+}
+
+/// Statistics of a pipeline cache logged when it is saved.
+///
+/// Vulkan does not report whether a pipeline was found in the cache, so hits
+/// are estimated by the growth of the cache data: when the cache loaded from
+/// the disk did not grow, all pipelines were found in it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineCacheStats {
+    /// Number of pipelines built with the cache.
+    pub pipelines: usize,
+    /// Size of the data loaded from the disk.
+    pub loaded_bytes: usize,
+    /// Current size of the data.
+    pub current_bytes: usize,
+}
+
+impl PipelineCacheStats {
+    /// Returns whether all pipelines were found in the loaded cache.
+    pub fn all_hits(&self) -> bool {
+        self.loaded_bytes > 0 && self.current_bytes <= self.loaded_bytes
+    }
+}
+
+impl std::fmt::Display for PipelineCacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pipelines built, {} bytes loaded, {} bytes now",
+            self.pipelines, self.loaded_bytes, self.current_bytes
+        )?;
+        if self.loaded_bytes == 0 {
+            write!(f, " (cold cache, all pipelines compiled)")
+        } else if self.all_hits() {
+            write!(f, " (all pipelines found in the cache)")
+        } else {
+            write!(f, " (some pipelines were compiled and added)")
+        }
+    }
+}
+
+/// Pipeline cache (`VkPipelineCache`) of a device that is loaded from a file
+/// per GPU at startup and saved back on shutdown, so pipelines compiled by the
+/// driver in previous runs are reused.
+pub struct PipelineCache {
+    shared: Arc<SharedCache>,
+    /// File the cache is persisted to, the cache lives only in memory when `None`.
+    path: Option<PathBuf>,
+    loaded_bytes: usize,
+}
+
+impl PipelineCache {
+    /// Creates the cache of the `device` with data loaded from the file for the
+    /// GPU in the `dir` (if the file exists and was created by the same GPU and
+    /// driver) and registers it, so [`pipeline_cache`](fn.pipeline_cache.html)
+    /// returns it.
+    pub fn load(device: &Arc<Device>, dir: Option<&Path>) -> Self {
+        let physical = device.physical_device();
+        let props = physical.properties();
+        let path = dir.map(|x| {
+            x.join(format!(
+                "pipelines-{:04x}-{:04x}.bin",
+                props.vendor_id, props.device_id
+            ))
+        });
+
+        let data = path
+            .as_ref()
+            .and_then(|x| std::fs::read(x).ok())
+            .filter(|data| {
+                let compatible = is_compatible_cache(
+                    data,
+                    props.vendor_id,
+                    props.device_id,
+                    &props.pipeline_cache_uuid,
+                );
+                if !compatible {
+                    warn!(
+                        "Pipeline cache {:?} was created by a different driver",
+                        path
+                    );
+                }
+                compatible
+            })
+            .unwrap_or_default();
+
+        // safe: the header was validated and the driver validates the rest
+        let cache = unsafe { VkPipelineCache::with_data(device.clone(), &data) }
+            .expect("cannot create pipeline cache");
+        if let Some(path) = &path {
+            info!("Loaded {} bytes of pipeline cache {:?}", data.len(), path);
+        }
+
+        let shared = Arc::new(SharedCache {
+            cache,
+            pipelines: AtomicUsize::new(0),
+        });
+        PIPELINE_CACHES
+            .lock()
+            .push((Arc::downgrade(device), Arc::downgrade(&shared)));
+
+        Self {
+            shared,
+            path,
+            loaded_bytes: data.len(),
+        }
+    }
+
+    /// Returns statistics of the cache (the data is retrieved from the driver).
+    pub fn stats(&self) -> PipelineCacheStats {
+        PipelineCacheStats {
+            pipelines: self.shared.pipelines.load(Ordering::Relaxed),
+            loaded_bytes: self.loaded_bytes,
+            current_bytes: self.shared.cache.get_data().map_or(0, |x| x.len()),
+        }
+    }
+
+    /// Writes the data of the cache to its file and logs its statistics.
+    /// Does nothing if the cache is not persisted.
+    pub fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        let data = self
+            .shared
+            .cache
+            .get_data()
+            .map_err(|e| format!("cannot get pipeline cache data: {:?}", e))?;
+        let stats = PipelineCacheStats {
+            pipelines: self.shared.pipelines.load(Ordering::Relaxed),
+            loaded_bytes: self.loaded_bytes,
+            current_bytes: data.len(),
+        };
+        info!("Pipeline cache: {}", stats);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, data).map_err(|e| e.to_string())
+    }
+}
+
+/// Possible errors that may happen during [`VulkanState`](struct.VulkanState.html) creation.
+#[derive(Debug)]
+pub enum VulkanStateError {
+    /// Window or surface couldn't be created.
+    CannotCreateWindow(CreationError),
+    /// Cannot find requested GPU.
+    GPUNotFound(usize),
+    /// Graphical queue family couldn't be found.
+    GraphicalQueueFamilyNotAvailable,
+    /// Transfer queue family couldn't be found.
+    TransferQueueFamilyNotAvailable,
+    /// Device couldn't be created.
+    CannotCreateDevice(DeviceCreationError),
+    /// Graphical queue was requested but never created.
+    GraphicalQueueNotCreated,
+    /// Transfer queue was requested but never created.
+    TransferQueueNotCreated,
+    /// Graphical queue can't present to the surface of a new window.
+    SurfaceNotSupported,
+}
+
+/// State of Vulkan in the application. Contains Vulkan *Device*, used
+/// *surface* and *queues* that were created with the device.
+///
+/// Represents one running Vulkan application. Vulkan *Instance* is
+/// created automatically when first `VulkanState` object is created.
+pub struct VulkanState {
+    device: Arc<Device>,
+    surface: Arc<Surface<Window>>,
+    graphical_queue: Arc<Queue>,
+    transfer_queue: Arc<Queue>,
+    pipeline_cache: PipelineCache,
+}
+
+impl VulkanState {
+    /// Creates or uses already created Vulkan instance and creates a new
+    /// window with surface, device and queues for this `VulkanState`.
+    pub fn new(
+        conf: &RendererConfiguration,
+        event_loop: &EventLoop<()>,
+    ) -> Result<Self, VulkanStateError> {
+        Self::with_window(conf, event_loop, true)
+    }
+
+    /// Same as [`new`](#method.new) except the window is never shown and the
+    /// cursor is not grabbed. Used to render frames without showing them
+    /// (eg. by the startup self-test).
+    pub fn new_hidden(
+        conf: &RendererConfiguration,
+        event_loop: &EventLoop<()>,
+    ) -> Result<Self, VulkanStateError> {
+        Self::with_window(conf, event_loop, false)
+    }
+
+    fn with_window(
+        conf: &RendererConfiguration,
+        event_loop: &EventLoop<()>,
+        visible: bool,
+    ) -> Result<Self, VulkanStateError> {
+        let instance = get_or_create_instance();
+        // the hidden window of the self-test must not cover the screen
+        let fullscreen = if visible {
+            conf.window_mode
+                .fullscreen(event_loop.primary_monitor(), conf.resolution)
+        } else {
+            None
+        };
+        let surface = WindowBuilder::new()
+            .with_title("renderer")
+            .with_inner_size(conf)
+            .with_resizable(true)
+            .with_fullscreen(fullscreen)
+            .with_visible(visible)
+            .build_vk_surface(event_loop, instance.clone())
+            .map_err(VulkanStateError::CannotCreateWindow)?;
+
+        // todo: move this to camera::init code
+        if visible {
+            surface.window().set_cursor_grab(true).unwrap();
+            surface.window().set_cursor_visible(false);
+        }
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        };
+
+        let physical: PhysicalDevice = PhysicalDevice::enumerate(&instance)
+            .nth(conf.gpu)
+            .ok_or(VulkanStateError::GPUNotFound(conf.gpu))?;
+
+        let props = physical.properties();
+
+        info!(
+            "Using device: {:?} {:?} Vulkan {:?}",
+            props.device_name, props.device_type, props.api_version
+        );
+
+        let graphical_queue_family = physical
+            .queue_families()
+            .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap())
+            .ok_or(VulkanStateError::GraphicalQueueFamilyNotAvailable)?;
+
+        let (device, graphical_queue, transfer_queue) =
+            create_device(physical, graphical_queue_family, &device_extensions)?;
+        let pipeline_cache = PipelineCache::load(&device, conf.pipeline_cache.as_deref());
+
+        Ok(Self {
+            device,
+            surface,
+            graphical_queue,
+            transfer_queue,
+            pipeline_cache,
+        })
+    }
+
+    /// Creates another window with a surface the graphical queue can present
+    /// to (eg. for a viewport, see `RendererState::for_window`). The `size`
+    /// is in logical pixels.
+    pub fn create_window(
+        &self,
+        event_loop: &EventLoopWindowTarget<()>,
+        title: &str,
+        size: [u16; 2],
+    ) -> Result<Arc<Surface<Window>>, VulkanStateError> {
+        let surface = WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(size[0] as f64, size[1] as f64))
+            .with_resizable(true)
+            .build_vk_surface(event_loop, get_or_create_instance())
+            .map_err(VulkanStateError::CannotCreateWindow)?;
+
+        match surface.is_supported(self.graphical_queue.family()) {
+            Ok(true) => Ok(surface),
+            _ => Err(VulkanStateError::SurfaceNotSupported),
+        }
+    }
+
+    /// Returns new `Arc` to the surface used by this `VulkanState`.
+    #[inline]
+    pub fn surface(&self) -> Arc<Surface<Window>> {
+        self.surface.clone()
+    }
+
+    /// Returns new `Arc` to the `Device` used by this `VulkanState`.
+    #[inline]
+    pub fn device(&self) -> Arc<Device> {
+        self.device.clone()
+    }
+
+    /// Returns new `Arc` to the `Queue` with transfer capabilities
+    /// used by this `VulkanState`.
+    #[inline]
+    pub fn transfer_queue(&self) -> Arc<Queue> {
+        self.transfer_queue.clone()
+    }
+
+    /// Returns new `Arc` to the `Queue` with transfer graphical
+    /// used by this `VulkanState`.
+    #[inline]
+    pub fn graphical_queue(&self) -> Arc<Queue> {
+        self.graphical_queue.clone()
+    }
+
+    /// Returns the pipeline cache of the device.
+    #[inline]
+    pub fn pipeline_cache(&self) -> &PipelineCache {
+        &self.pipeline_cache
+    }
+}
+
+/// Creates a `Device` with one graphical queue from `graphical_queue_family` and
+/// one transfer queue.
+fn create_device(
+    physical: PhysicalDevice,
+    graphical_queue_family: QueueFamily,
+    extensions: &DeviceExtensions,
+) -> Result<(Arc<Device>, Arc<Queue>, Arc<Queue>), VulkanStateError> {
+    let transfer_queue_family = physical
+        .queue_families()
+        .find(|&q| q.explicitly_supports_transfers())
+        .ok_or(VulkanStateError::TransferQueueFamilyNotAvailable)?;
+
+    let (device, mut queues) = Device::new(
+        physical,
+        &Features {
+            independent_blend: true,
+            sampler_anisotropy: true,
+            ..Features::none()
+        },
+        &physical.required_extensions().union(extensions),
+        [(graphical_queue_family, 0.5), (transfer_queue_family, 0.5)]
+            .iter()
+            .cloned(),
+    )
+    .map_err(VulkanStateError::CannotCreateDevice)?;
+
+    let graphical_queue = queues
+        .next()
+        .ok_or(VulkanStateError::GraphicalQueueNotCreated)?;
+    let transfer_queue = queues
+        .next()
+        .ok_or(VulkanStateError::TransferQueueNotCreated)?;
+
+    Ok((device, graphical_queue, transfer_queue))
+}
+
+/// Vulkan *Device* and *queues* created without any window or surface.
+///
+/// Allows running the rendering code (uploading resources, recording and
+/// submitting command buffers) on machines without a display, eg. in tests.
+pub struct HeadlessVulkanState {
+    device: Arc<Device>,
+    graphical_queue: Arc<Queue>,
+    transfer_queue: Arc<Queue>,
+}
+
+impl HeadlessVulkanState {
+    /// Creates or uses already created Vulkan instance and creates device and
+    /// queues on GPU with index `gpu`.
+    pub fn new(gpu: usize) -> Result<Self, VulkanStateError> {
+        let instance = get_or_create_instance();
+
+        let physical: PhysicalDevice = PhysicalDevice::enumerate(&instance)
+            .nth(gpu)
+            .ok_or(VulkanStateError::GPUNotFound(gpu))?;
+
+        let graphical_queue_family = physical
+            .queue_families()
+            .find(|&q| q.supports_graphics())
+            .ok_or(VulkanStateError::GraphicalQueueFamilyNotAvailable)?;
+
+        let (device, graphical_queue, transfer_queue) =
+            create_device(physical, graphical_queue_family, &DeviceExtensions::none())?;
+
+        Ok(Self {
+            device,
+            graphical_queue,
+            transfer_queue,
+        })
+    }
+
+    /// Returns new `Arc` to the `Device` used by this `HeadlessVulkanState`.
+    #[inline]
+    pub fn device(&self) -> Arc<Device> {
+        self.device.clone()
+    }
+
+    /// Returns new `Arc` to the `Queue` with transfer capabilities.
+    #[inline]
+    pub fn transfer_queue(&self) -> Arc<Queue> {
+        self.transfer_queue.clone()
+    }
+
+    /// Returns new `Arc` to the `Queue` with graphical capabilities.
+    #[inline]
+    pub fn graphical_queue(&self) -> Arc<Queue> {
+        self.graphical_queue.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::vulkan::{is_compatible_cache, PipelineCacheStats};
+
+    fn header(vendor_id: u32, device_id: u32, uuid: [u8; 16]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&32u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&vendor_id.to_le_bytes());
+        data.extend_from_slice(&device_id.to_le_bytes());
+        data.extend_from_slice(&uuid);
+        data
+    }
+
+    #[test]
+    fn cache_of_other_gpu_or_driver_is_incompatible() {
+        let mut data = header(0x10de, 0x1f08, [7; 16]);
+        data.extend_from_slice(&[1, 2, 3]);
+
+        assert!(is_compatible_cache(&data, 0x10de, 0x1f08, &[7; 16]));
+        assert!(!is_compatible_cache(&data, 0x1002, 0x1f08, &[7; 16]));
+        assert!(!is_compatible_cache(&data, 0x10de, 0x1f08, &[8; 16]));
+        assert!(!is_compatible_cache(&data[..20], 0x10de, 0x1f08, &[7; 16]));
+    }
+
+    #[test]
+    fn hits_are_estimated_by_growth_of_the_cache() {
+        let stats = |loaded_bytes, current_bytes| PipelineCacheStats {
+            pipelines: 10,
+            loaded_bytes,
+            current_bytes,
+        };
+
+        assert!(stats(1000, 1000).all_hits());
+        assert!(!stats(1000, 1200).all_hits());
+        assert!(!stats(0, 1200).all_hits());
+    }
+}
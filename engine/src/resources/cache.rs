@@ -0,0 +1,132 @@
+//! Sharing of GPU resources created from the same asset.
+//!
+//! Resources are cached by the uuid and revision of the asset they were created
+//! from. The cache only holds weak references, so a resource is released as
+//! soon as nothing (eg. no object) uses it. Reloaded asset has a new revision
+//! and therefore never returns resources created from the previous version.
+
+use crate::frame_stats::{self, FrameEvent};
+use bf::uuid::Uuid;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+/// Cache of meshes and images created from assets loaded by `Content`.
+pub(crate) static GPU_RESOURCES: Lazy<ResourceCache> = Lazy::new(ResourceCache::default);
+
+type Key = (Uuid, u64);
+
+/// Map of weak references to resources created from assets.
+#[derive(Default)]
+pub struct ResourceCache {
+    entries: Mutex<HashMap<Key, Weak<dyn Any + Send + Sync>>>,
+}
+
+impl ResourceCache {
+    /// Returns the resource created from revision `revision` of asset `uuid`
+    /// if it is still alive and has type `T`.
+    pub fn get<T: Any + Send + Sync>(&self, uuid: &Uuid, revision: u64) -> Option<Arc<T>> {
+        let resource = self.entries.lock().get(&(*uuid, revision))?.upgrade()?;
+
+        resource.downcast().ok()
+    }
+
+    /// Stores weak reference to `resource` created from revision `revision` of
+    /// asset `uuid`. Entries of resources that were already released are removed.
+    pub fn insert<T: Any + Send + Sync>(&self, uuid: Uuid, revision: u64, resource: &Arc<T>) {
+        let weak = Arc::downgrade(resource) as Weak<dyn Any + Send + Sync>;
+        let mut entries = self.entries.lock();
+
+        entries.retain(|_, x| x.strong_count() > 0);
+        entries.insert((uuid, revision), weak);
+    }
+
+    /// Returns the resource from the cache or creates it using `create` and
+    /// caches it. The second returned value is `Some` only when the resource
+    /// was created and contains the rest of the `create` result (eg. a future).
+    pub fn get_or_create<T, R, E, F>(
+        &self,
+        uuid: Uuid,
+        revision: u64,
+        create: F,
+    ) -> Result<(Arc<T>, Option<R>), E>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> Result<(Arc<T>, R), E>,
+    {
+        if let Some(t) = self.get(&uuid, revision) {
+            return Ok((t, None));
+        }
+
+        let (t, rest) = create()?;
+        frame_stats::record(FrameEvent::AssetUploaded);
+        self.insert(uuid, revision, &t);
+        Ok((t, Some(rest)))
+    }
+
+    /// Returns number of cached resources that are still alive.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .values()
+            .filter(|x| x.strong_count() > 0)
+            .count()
+    }
+
+    /// Returns whether there are no alive resources in the cache.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::cache::ResourceCache;
+    use bf::uuid::Uuid;
+    use std::sync::Arc;
+
+    fn uuid(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    #[test]
+    fn shares_resources_of_same_revision() {
+        let cache = ResourceCache::default();
+        let create = || Ok::<_, ()>((Arc::new(1u32), ()));
+
+        let (a, created) = cache.get_or_create(uuid(1), 1, create).unwrap();
+        assert!(created.is_some());
+        let (b, created) = cache.get_or_create(uuid(1), 1, create).unwrap();
+        assert!(created.is_none());
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let (c, created) = cache.get_or_create(uuid(1), 2, create).unwrap();
+        assert!(created.is_some());
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn releases_unused_resources() {
+        let cache = ResourceCache::default();
+        let resource = Arc::new(1u32);
+
+        cache.insert(uuid(1), 1, &resource);
+        assert_eq!(cache.len(), 1);
+
+        drop(resource);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get::<u32>(&uuid(1), 1), None);
+    }
+
+    #[test]
+    fn ignores_resources_of_other_type() {
+        let cache = ResourceCache::default();
+        let resource = Arc::new(1u32);
+
+        cache.insert(uuid(1), 1, &resource);
+        assert_eq!(cache.get::<u64>(&uuid(1), 1), None);
+        assert_eq!(cache.get::<u32>(&uuid(1), 1), Some(resource));
+    }
+}
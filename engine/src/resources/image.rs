@@ -0,0 +1,806 @@
+//! Images and code related to image creation and streaming of mip-maps.
+
+use crate::assets::Content;
+use crate::frame_stats::{self, FrameEvent};
+use crate::resources::cache::GPU_RESOURCES;
+use crate::resources::swap::Swap;
+use crate::resources::transcode::{transcode, TranscodeError};
+use bf::uuid::Uuid;
+use log::warn;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferExecError, CommandBufferUsage, PrimaryAutoCommandBuffer,
+    PrimaryCommandBuffer,
+};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::immutable::ImmutableImageInitialization;
+use vulkano::image::view::ImageView;
+use vulkano::image::{
+    ImageAccess, ImageCreateFlags, ImageCreationError, ImageDimensions, ImageLayout, ImageUsage,
+    ImmutableImage, MipmapsCount,
+};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::sync::{FenceSignalFuture, GpuFuture};
+
+/// Helper function to convert `bf::image::Format` into
+/// Vulkano `Format` enum.
+fn to_vulkan_format(format: bf::image::Format) -> Format {
+    match format {
+        bf::image::Format::R8 => Format::R8Unorm,
+        bf::image::Format::Dxt1 => Format::BC1_RGBUnormBlock,
+        bf::image::Format::Dxt3 => Format::BC2UnormBlock,
+        bf::image::Format::Dxt5 => Format::BC3UnormBlock,
+        bf::image::Format::Rgb8 => Format::R8G8B8Unorm,
+        bf::image::Format::Rgba8 => Format::R8G8B8A8Unorm,
+        bf::image::Format::SrgbDxt1 => Format::BC1_RGBSrgbBlock,
+        bf::image::Format::SrgbDxt3 => Format::BC2SrgbBlock,
+        bf::image::Format::SrgbDxt5 => Format::BC3SrgbBlock,
+        bf::image::Format::Srgb8 => Format::R8G8B8Srgb,
+        bf::image::Format::Srgb8A8 => Format::R8G8B8A8Srgb,
+        bf::image::Format::BC6H => Format::BC6HUfloatBlock,
+        bf::image::Format::BC7 => Format::BC7UnormBlock,
+        bf::image::Format::SrgbBC7 => Format::BC7SrgbBlock,
+        bf::image::Format::BC5 => Format::BC5UnormBlock,
+        bf::image::Format::R16 => Format::R16Unorm,
+        bf::image::Format::Rg16 => Format::R16G16Unorm,
+        bf::image::Format::Rgba16F => Format::R16G16B16A16Sfloat,
+        bf::image::Format::Rgba32F => Format::R32G32B32A32Sfloat,
+    }
+}
+
+/// Errors that may happen when creating an image.
+#[derive(Debug)]
+pub enum CreateImageError {
+    CannotCreateImage(Format, ImageCreationError),
+    CannotAllocateBuffer(DeviceMemoryAllocError),
+    CannotTranscode(TranscodeError),
+    CannotGenerateMipmaps(Format),
+    /// The format is not supported by the device and the image cannot be transcoded.
+    UnsupportedFormat(Format),
+}
+
+/// Returns whether normal maps in specified format are DXT5nm (X in alpha
+/// and Y in green, packed by `img2bf --pack-normal-map`). Normal maps in other
+/// formats (BC5 or uncompressed) have X in red and Y in green. Images
+/// transcoded on the CPU keep the channels, so the asset format decides.
+pub fn is_dxt5nm(format: bf::image::Format) -> bool {
+    format == bf::image::Format::Dxt5
+}
+
+/// Returns whether the image in specified format can be sampled
+/// from shaders on specified device.
+fn is_format_supported(format: Format, device: PhysicalDevice) -> bool {
+    format
+        .properties(device)
+        .optimal_tiling_features
+        .sampled_image
+}
+
+/// Returns whether mip-maps of images in specified format can be generated
+/// on specified device by linear blits.
+fn can_generate_mipmaps(format: Format, device: PhysicalDevice) -> bool {
+    let features = format.properties(device).optimal_tiling_features;
+    features.blit_src && features.blit_dst && features.sampled_image_filter_linear
+}
+
+/// This function creates an `ImmutableImage` struct from provided `bf::image::Image` asset.
+/// If the format of the image is not supported by the device, the image is transcoded
+/// on the CPU to a supported format (see `transcode` module). Uncompressed images with
+/// a single mip-map get the rest of mip-maps generated on the GPU when possible (see
+/// [`create_image_with_mipmaps`](fn.create_image_with_mipmaps.html)). This function returns
+/// the image and `GpuFuture` that represents the time when the image is ready to use.
+pub fn create_image(
+    image: &bf::image::Image,
+    queue: Arc<Queue>,
+) -> Result<(Arc<ImmutableImage>, impl GpuFuture), CreateImageError> {
+    create_image_from_mip(image, 0, queue)
+}
+
+/// Same as [`create_image`](fn.create_image.html) except only mip-maps starting at
+/// `first_mip` are uploaded. The created image has dimensions of the `first_mip`
+/// mip-map. The `first_mip` is clamped to the smallest mip-map of the image.
+pub fn create_image_from_mip(
+    image: &bf::image::Image,
+    first_mip: u32,
+    queue: Arc<Queue>,
+) -> Result<(Arc<ImmutableImage>, impl GpuFuture), CreateImageError> {
+    // images without mip-maps (eg. previews or procedurally created images)
+    if first_mip == 0 && image.mipmap_count() == 1 && image.width.max(image.height) > 1 {
+        if let Ok((immutable, future)) = create_image_with_mipmaps(image, queue.clone()) {
+            return Ok((immutable, future.boxed_send()));
+        }
+    }
+
+    let physical = queue.device().physical_device();
+    let transcoded;
+    let image = if is_format_supported(to_vulkan_format(image.format), physical) {
+        image
+    } else {
+        warn!(
+            "Format {:?} is not supported by the device, transcoding...",
+            image.format
+        );
+        transcoded = transcode(image, physical).map_err(CreateImageError::CannotTranscode)?;
+        &transcoded
+    };
+
+    let (immutable, _, future) = upload_image(image, first_mip, queue)?;
+    Ok((immutable, future))
+}
+
+/// Creates an image on the gpu with mip-maps of `image` starting at `first_mip` and
+/// records their upload. Returns the image, the handle that allows writing into it
+/// and the future of the upload.
+fn upload_image(
+    image: &bf::image::Image,
+    first_mip: u32,
+    queue: Arc<Queue>,
+) -> Result<
+    (
+        Arc<ImmutableImage>,
+        Arc<ImmutableImageInitialization>,
+        Box<dyn GpuFuture + Send>,
+    ),
+    CreateImageError,
+> {
+    let first_mip = first_mip.min(image.mipmap_count().saturating_sub(1));
+    let first = image
+        .mipmaps()
+        .nth(first_mip as usize)
+        .expect("image has no mip-maps");
+
+    // create image on the gpu and allocate memory for it
+    let format = to_vulkan_format(image.format);
+    let (immutable, init) = ImmutableImage::uninitialized(
+        queue.device().clone(),
+        ImageDimensions::Dim2d {
+            width: first.width as u32,
+            height: first.height as u32,
+            array_layers: 1,
+        },
+        format,
+        image.mipmap_count() - first_mip,
+        ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            ..ImageUsage::none()
+        },
+        ImageCreateFlags::none(),
+        ImageLayout::ShaderReadOnlyOptimal,
+        Some(queue.family()),
+    )
+    .map_err(|e| CreateImageError::CannotCreateImage(format, e))?;
+
+    // we need to wrap the init into `Arc` as we need to send it multiple
+    // times as owned variable in the for loop later
+    let init = Arc::new(init);
+
+    let mut cb = AutoCommandBufferBuilder::primary(
+        queue.device().clone(),
+        queue.family(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    for (idx, mipmap) in image.mipmaps().skip(first_mip as usize).enumerate() {
+        record_upload(
+            &mut cb,
+            queue.device().clone(),
+            init.clone(),
+            mipmap.data,
+            [0, 0],
+            [mipmap.width as u32, mipmap.height as u32],
+            idx as u32,
+        )
+        .map_err(CreateImageError::CannotAllocateBuffer)?;
+    }
+
+    let cb = cb.build().unwrap();
+
+    let future = match cb.execute(queue) {
+        Ok(f) => f,
+        Err(_) => unreachable!(),
+    };
+
+    Ok((immutable, init, future.boxed_send()))
+}
+
+/// Records a copy of `data` into the region of the mip-map `mip` of the `image`
+/// at `offset` with `size` pixels.
+fn record_upload<I>(
+    cb: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    device: Arc<Device>,
+    image: I,
+    data: &[u8],
+    offset: [u32; 2],
+    size: [u32; 2],
+    mip: u32,
+) -> Result<(), DeviceMemoryAllocError>
+where
+    I: ImageAccess + Send + Sync + 'static,
+{
+    let source = CpuAccessibleBuffer::from_iter(
+        device,
+        BufferUsage::transfer_source(),
+        false,
+        data.iter().cloned(),
+    )?;
+
+    cb.copy_buffer_to_image_dimensions(
+        source,
+        image,
+        [offset[0], offset[1], 0],
+        [size[0], size[1], 1],
+        0,
+        1,
+        mip,
+    )
+    .unwrap();
+
+    Ok(())
+}
+
+/// Creates an `ImmutableImage` from the first mip-map of provided uncompressed
+/// `bf::image::Image` and generates the full mip chain on the GPU. Each mip-map
+/// is blitted (with linear filter) from the previous one in the same command buffer
+/// as the upload.
+///
+/// Blits are only available on queues with graphics capabilities. If the `queue`
+/// does not support them, or the format of the image is compressed or cannot be
+/// blitted, `CannotGenerateMipmaps` error is returned.
+pub fn create_image_with_mipmaps(
+    image: &bf::image::Image,
+    queue: Arc<Queue>,
+) -> Result<(Arc<ImmutableImage>, impl GpuFuture), CreateImageError> {
+    let format = to_vulkan_format(image.format);
+
+    if image.format.compressed()
+        || !queue.family().supports_graphics()
+        || !can_generate_mipmaps(format, queue.device().physical_device())
+    {
+        return Err(CreateImageError::CannotGenerateMipmaps(format));
+    }
+
+    let first = image.mipmaps().next().expect("image has no mip-maps");
+
+    // vulkano records the blits of all levels when the image is created
+    // with more than one mip-map
+    ImmutableImage::from_iter(
+        first.data.iter().cloned(),
+        ImageDimensions::Dim2d {
+            width: first.width as u32,
+            height: first.height as u32,
+            array_layers: 1,
+        },
+        MipmapsCount::Log2,
+        format,
+        queue,
+    )
+    .map_err(|e| CreateImageError::CannotCreateImage(format, e))
+}
+
+/// Creates an *Image* that has specified color and is of size 1x1 pixels.
+/// This function returns the image and `GpuFuture` that represents the time
+/// when the image is ready to use.
+pub fn create_single_pixel_image(
+    queue: Arc<Queue>,
+    color: [u8; 4],
+) -> Result<(Arc<ImmutableImage>, impl GpuFuture), CreateImageError> {
+    ImmutableImage::from_iter(
+        color.iter().cloned(),
+        ImageDimensions::Dim2d {
+            width: 1,
+            height: 1,
+            array_layers: 1,
+        },
+        MipmapsCount::One,
+        Format::R8G8B8A8Unorm,
+        queue,
+    )
+    .map_err(|e| CreateImageError::CannotCreateImage(Format::R8G8B8A8Unorm, e))
+}
+
+/// Errors that may happen when updating an [`UpdatableImage`](struct.UpdatableImage.html).
+#[derive(Debug)]
+pub enum UpdateImageError {
+    /// The image has no mip-map with this index.
+    InvalidMipmap(u32),
+    /// The region is empty, does not fit into the mip-map or (for compressed
+    /// formats) is not aligned to blocks of 4x4 pixels.
+    InvalidRegion {
+        offset: [u32; 2],
+        size: [u32; 2],
+    },
+    /// The length of the data does not match the size of the region.
+    InvalidDataLength {
+        expected: usize,
+        actual: usize,
+    },
+    CannotAllocateBuffer(DeviceMemoryAllocError),
+    /// The copy could not be submitted, usually because the previous update
+    /// of the image is still in progress.
+    CannotExecute(CommandBufferExecError),
+}
+
+/// Returns the number of bytes of a region of `size` pixels in `format`. The region
+/// at `offset` must fit into the mip-map of `mip_size` pixels and regions of compressed
+/// formats must start and end on boundaries of 4x4 blocks (or on the edge of the mip-map).
+fn region_bytes(
+    format: bf::image::Format,
+    mip_size: [u32; 2],
+    offset: [u32; 2],
+    size: [u32; 2],
+) -> Result<usize, UpdateImageError> {
+    for i in 0..2 {
+        let end = offset[i].checked_add(size[i]);
+        let fits = size[i] > 0 && end.map_or(false, |x| x <= mip_size[i]);
+        let aligned = !format.compressed()
+            || (offset[i] % 4 == 0 && (size[i] % 4 == 0 || end == Some(mip_size[i])));
+        if !fits || !aligned {
+            return Err(UpdateImageError::InvalidRegion { offset, size });
+        }
+    }
+
+    let bits_per_pixel = format.bits_per_pixel() as usize;
+    Ok(if format.compressed() {
+        let blocks = ((size[0] + 3) / 4) as usize * ((size[1] + 3) / 4) as usize;
+        blocks * 16 * bits_per_pixel / 8
+    } else {
+        size[0] as usize * size[1] as usize * bits_per_pixel / 8
+    })
+}
+
+/// Region of a mip-map of an [`UpdatableImage`](struct.UpdatableImage.html) with
+/// the data it is overwritten with.
+pub struct ImageRegion<'a> {
+    pub mip: u32,
+    pub offset: [u32; 2],
+    pub size: [u32; 2],
+    /// Tightly packed rows (or 4x4 blocks) in the format of the image.
+    pub data: &'a [u8],
+}
+
+/// Image whose mip-maps (or their regions) can be overwritten after it was created,
+/// eg. for painting into textures at run-time or streaming parts of large textures.
+///
+/// The image keeps the handle it was initialized with, so copies can be recorded
+/// into it later. Each copy transitions the written mip-map from the shader read-only
+/// layout to the transfer destination layout and back. Updates are not synchronized
+/// with frames that sample the image, so frames in flight may see a partially updated
+/// image. Only one update can be in progress at a time.
+pub struct UpdatableImage {
+    init: Arc<ImmutableImageInitialization>,
+    view: Arc<ImageView<Arc<ImmutableImage>>>,
+    format: bf::image::Format,
+    width: u32,
+    height: u32,
+    mip_count: u32,
+}
+
+impl UpdatableImage {
+    /// Creates the image with all mip-maps of the `image`. The format of the image
+    /// must be supported by the device, because updates are not transcoded.
+    pub fn new(
+        image: &bf::image::Image,
+        queue: Arc<Queue>,
+    ) -> Result<(Self, impl GpuFuture), CreateImageError> {
+        let format = to_vulkan_format(image.format);
+        if !is_format_supported(format, queue.device().physical_device()) {
+            return Err(CreateImageError::UnsupportedFormat(format));
+        }
+
+        let (immutable, init, future) = upload_image(image, 0, queue)?;
+        let updatable = Self {
+            init,
+            view: ImageView::new(immutable).expect("cannot create view from image"),
+            format: image.format,
+            width: image.width as u32,
+            height: image.height as u32,
+            mip_count: image.mipmap_count(),
+        };
+
+        Ok((updatable, future))
+    }
+
+    /// Returns the view of all mip-maps of the image.
+    pub fn view(&self) -> Arc<ImageView<Arc<ImmutableImage>>> {
+        self.view.clone()
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.mip_count
+    }
+
+    /// Returns the dimensions of the mip-map `mip` in pixels.
+    pub fn mip_size(&self, mip: u32) -> Option<[u32; 2]> {
+        if mip < self.mip_count {
+            Some([(self.width >> mip).max(1), (self.height >> mip).max(1)])
+        } else {
+            None
+        }
+    }
+
+    /// Overwrites the region of `size` pixels at `offset` in the mip-map `mip` with
+    /// `data` (tightly packed rows or 4x4 blocks in the format of the image). Returns
+    /// the `GpuFuture` of the copy, that should be waited for before the next frame
+    /// is rendered (eg. by scheduling it on `Content::uploads`).
+    pub fn update_region(
+        &self,
+        mip: u32,
+        offset: [u32; 2],
+        size: [u32; 2],
+        data: &[u8],
+        queue: Arc<Queue>,
+    ) -> Result<impl GpuFuture, UpdateImageError> {
+        let region = ImageRegion {
+            mip,
+            offset,
+            size,
+            data,
+        };
+        self.update_regions(&[region], queue)
+    }
+
+    /// Overwrites all `regions` with one submission. Regions are validated before
+    /// anything is copied, so either all of them or none of them are updated.
+    pub fn update_regions(
+        &self,
+        regions: &[ImageRegion],
+        queue: Arc<Queue>,
+    ) -> Result<impl GpuFuture, UpdateImageError> {
+        for region in regions {
+            let mip_size = self
+                .mip_size(region.mip)
+                .ok_or(UpdateImageError::InvalidMipmap(region.mip))?;
+            let expected = region_bytes(self.format, mip_size, region.offset, region.size)?;
+            if region.data.len() != expected {
+                return Err(UpdateImageError::InvalidDataLength {
+                    expected,
+                    actual: region.data.len(),
+                });
+            }
+        }
+
+        let mut cb = AutoCommandBufferBuilder::primary(
+            queue.device().clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        for region in regions {
+            record_upload(
+                &mut cb,
+                queue.device().clone(),
+                self.init.clone(),
+                region.data,
+                region.offset,
+                region.size,
+                region.mip,
+            )
+            .map_err(UpdateImageError::CannotAllocateBuffer)?;
+        }
+
+        cb.build()
+            .unwrap()
+            .execute(queue)
+            .map_err(UpdateImageError::CannotExecute)
+    }
+
+    /// Overwrites the whole mip-map `mip` with `data`.
+    pub fn update_mip(
+        &self,
+        mip: u32,
+        data: &[u8],
+        queue: Arc<Queue>,
+    ) -> Result<impl GpuFuture, UpdateImageError> {
+        let size = self
+            .mip_size(mip)
+            .ok_or(UpdateImageError::InvalidMipmap(mip))?;
+        self.update_region(mip, [0, 0], size, data, queue)
+    }
+}
+
+/// Number of the smallest mip-maps that are uploaded when a streamed
+/// image is created.
+pub const INITIAL_RESIDENT_MIPS: u32 = 4;
+
+/// Maximum number of streaming uploads that can be started in one frame.
+const MAX_UPLOADS_PER_FRAME: usize = 2;
+
+/// Registry of all streamed images that are alive.
+static STREAMED_IMAGES: Lazy<Mutex<Vec<Weak<StreamedImage>>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Image whose higher resolution mip-maps are uploaded (and released) on demand
+/// by [`TextureStreamer`](struct.TextureStreamer.html). The image always contains
+/// the mip-maps from `resident_mip` to the smallest one.
+///
+/// Because the underlying `ImageView` is replaced when the resident mip-maps
+/// change, users of this image should compare the `revision` to find out whether
+/// their descriptor sets need to be rebuilt. Replaced views are retired, so frames
+/// in flight can still use them.
+pub struct StreamedImage {
+    uuid: Option<Uuid>,
+    /// Format of the image asset (`None` for resident images).
+    format: Option<bf::image::Format>,
+    width: u16,
+    mip_count: u32,
+    view: Swap<(u32, Arc<ImageView<Arc<ImmutableImage>>>)>,
+}
+
+impl StreamedImage {
+    /// Wraps an existing image view that will be never streamed.
+    pub fn resident(view: Arc<ImageView<Arc<ImmutableImage>>>) -> Arc<Self> {
+        Arc::new(Self {
+            uuid: None,
+            format: None,
+            width: 0,
+            mip_count: 1,
+            view: Swap::new(Arc::new((0, view))),
+        })
+    }
+
+    /// Returns the view of currently resident mip-maps.
+    pub fn view(&self) -> Arc<ImageView<Arc<ImmutableImage>>> {
+        self.view.get().1.clone()
+    }
+
+    /// Returns the format of the image asset or `None` for wrapped image views.
+    pub fn format(&self) -> Option<bf::image::Format> {
+        self.format
+    }
+
+    /// Returns the index of the highest resolution mip-map that is resident.
+    pub fn resident_mip(&self) -> u32 {
+        self.view.get().0
+    }
+
+    /// Returns the number that is incremented each time the view is replaced.
+    pub fn revision(&self) -> u64 {
+        self.view.generation()
+    }
+
+    /// Returns the index of the highest resolution mip-map that can be released
+    /// while keeping `INITIAL_RESIDENT_MIPS` smallest mip-maps resident.
+    fn lowest_mip(&self) -> u32 {
+        self.mip_count.saturating_sub(INITIAL_RESIDENT_MIPS)
+    }
+
+    /// Returns the mip-map that should be resident when the image covers
+    /// `coverage` fraction of the screen that is `screen_height` pixels high.
+    fn desired_mip(&self, coverage: f32, screen_height: u32) -> u32 {
+        if coverage <= 0.0 {
+            return self.lowest_mip();
+        }
+
+        let needed = coverage.sqrt() * screen_height as f32;
+        let mip = (self.width as f32 / needed).log2().floor().max(0.0) as u32;
+
+        mip.min(self.lowest_mip())
+    }
+
+    fn replace_view(&self, first_mip: u32, view: Arc<ImageView<Arc<ImmutableImage>>>) {
+        self.view.replace(Arc::new((first_mip, view)));
+    }
+}
+
+/// Creates a [`StreamedImage`](struct.StreamedImage.html) from provided `bf::image::Image`
+/// asset with specified `uuid`. Only `INITIAL_RESIDENT_MIPS` smallest mip-maps are
+/// uploaded, the rest is streamed in by `TextureStreamer` when needed.
+pub fn create_streamed_image(
+    uuid: Uuid,
+    image: &bf::image::Image,
+    queue: Arc<Queue>,
+) -> Result<(Arc<StreamedImage>, impl GpuFuture), CreateImageError> {
+    let mip_count = image.mipmap_count();
+    let first_mip = mip_count.saturating_sub(INITIAL_RESIDENT_MIPS);
+    let (immutable, future) = create_image_from_mip(image, first_mip, queue)?;
+
+    let streamed = Arc::new(StreamedImage {
+        uuid: Some(uuid),
+        format: Some(image.format),
+        width: image.width.max(image.height),
+        mip_count,
+        view: Swap::new(Arc::new((
+            first_mip,
+            ImageView::new(immutable).expect("cannot create view from image"),
+        ))),
+    });
+
+    STREAMED_IMAGES.lock().push(Arc::downgrade(&streamed));
+
+    Ok((streamed, future))
+}
+
+/// Same as [`create_streamed_image`](fn.create_streamed_image.html) except the image
+/// asset is loaded from `content` (if it was not loaded yet) and the created image is
+/// shared by everyone who requests the same revision of the asset. The upload of the
+/// image is scheduled on `content.uploads`, so it can be used by the render loop right away.
+pub fn create_streamed_image_cached(
+    uuid: Uuid,
+    content: &Content,
+) -> Result<Arc<StreamedImage>, CreateImageError> {
+    let revision = content.ensure_loaded::<bf::image::Image>(uuid);
+    let queue = content.transfer_queue.clone();
+
+    let (image, _) = GPU_RESOURCES.get_or_create(uuid, revision, || {
+        let asset = content.get_blocking::<bf::image::Image>(&uuid);
+        let (image, future) = create_streamed_image(uuid, &asset, queue.clone())?;
+        let first_mip = asset.mipmap_count().saturating_sub(INITIAL_RESIDENT_MIPS);
+        let bytes = asset
+            .mipmaps()
+            .skip(first_mip as usize)
+            .map(|x| x.data.len())
+            .sum();
+        content.uploads.schedule(future, bytes);
+        Ok((image, ()))
+    })?;
+
+    Ok(image)
+}
+
+/// Upload of mip-maps that is in progress.
+struct PendingUpload {
+    image: Arc<StreamedImage>,
+    first_mip: u32,
+    view: Arc<ImageView<Arc<ImmutableImage>>>,
+    fence: FenceSignalFuture<Box<dyn GpuFuture>>,
+}
+
+/// Streams mip-maps of [`StreamedImage`s](struct.StreamedImage.html) in and out
+/// based on their loading priority in `Content` (screen coverage of the objects
+/// using them). Higher resolution mip-maps are uploaded on the transfer queue
+/// and swapped in once the upload has finished.
+pub struct TextureStreamer {
+    queue: Arc<Queue>,
+    pending: Vec<PendingUpload>,
+}
+
+impl TextureStreamer {
+    /// Creates a new streamer that uploads mip-maps using specified queue.
+    pub fn new(queue: Arc<Queue>) -> Self {
+        Self {
+            queue,
+            pending: vec![],
+        }
+    }
+
+    /// Should be called once per frame. Swaps in finished uploads and starts
+    /// new uploads for images whose resident mip-maps do not match their
+    /// current screen coverage.
+    pub fn update(&mut self, content: &Content, screen_height: u32) {
+        self.finish_uploads();
+
+        let mut images = STREAMED_IMAGES.lock();
+        images.retain(|x| x.strong_count() > 0);
+
+        // compute desired mip-map of each image and upload most visible images first
+        let mut wanted = images
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|img| !self.pending.iter().any(|p| Arc::ptr_eq(&p.image, img)))
+            .filter_map(|img| {
+                let uuid = img.uuid?;
+                let priority = content.priority(&uuid);
+                let desired = img.desired_mip(priority, screen_height);
+                let resident = img.resident_mip();
+                // release mip-maps only when they are clearly not needed
+                // so the image does not flip between two mip-maps
+                if desired < resident || desired > resident + 1 {
+                    Some((priority, desired, img))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        drop(images);
+
+        wanted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (_, desired, image) in wanted.into_iter().take(MAX_UPLOADS_PER_FRAME) {
+            let uuid = image.uuid.unwrap();
+            let asset = match content.get::<bf::image::Image>(&uuid) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            let (immutable, future) =
+                match create_image_from_mip(&asset, desired, self.queue.clone()) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        warn!("Cannot stream image {}: {:?}", uuid, e);
+                        continue;
+                    }
+                };
+
+            let fence = match future.boxed().then_signal_fence_and_flush() {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("Cannot stream image {}: {:?}", uuid, e);
+                    continue;
+                }
+            };
+
+            frame_stats::record(FrameEvent::AssetUploaded);
+            self.pending.push(PendingUpload {
+                image,
+                first_mip: desired,
+                view: ImageView::new(immutable).expect("cannot create view from image"),
+                fence,
+            });
+        }
+    }
+
+    /// Replaces views of images whose upload has finished.
+    fn finish_uploads(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            if self.pending[i]
+                .fence
+                .wait(Some(Duration::from_secs(0)))
+                .is_ok()
+            {
+                let upload = self.pending.swap_remove(i);
+                upload.image.replace_view(upload.first_mip, upload.view);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::image::{region_bytes, UpdateImageError};
+    use bf::image::Format;
+
+    #[test]
+    fn computes_size_of_regions() {
+        assert_eq!(
+            region_bytes(Format::Rgba8, [8, 8], [2, 3], [4, 5]).unwrap(),
+            80
+        );
+        assert_eq!(
+            region_bytes(Format::R8, [8, 8], [0, 0], [8, 8]).unwrap(),
+            64
+        );
+        // one BC1 block has 8 bytes and BC7 block 16 bytes
+        assert_eq!(
+            region_bytes(Format::Dxt1, [16, 16], [4, 8], [8, 8]).unwrap(),
+            32
+        );
+        assert_eq!(
+            region_bytes(Format::BC7, [16, 16], [0, 0], [16, 4]).unwrap(),
+            64
+        );
+        // partial blocks at the edge of small mip-maps
+        assert_eq!(
+            region_bytes(Format::BC7, [2, 2], [0, 0], [2, 2]).unwrap(),
+            16
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_regions() {
+        let invalid = |format, offset, size| {
+            matches!(
+                region_bytes(format, [16, 16], offset, size),
+                Err(UpdateImageError::InvalidRegion { .. })
+            )
+        };
+
+        assert!(invalid(Format::Rgba8, [0, 0], [0, 4]));
+        assert!(invalid(Format::Rgba8, [12, 0], [8, 4]));
+        assert!(invalid(Format::Rgba8, [u32::MAX, 0], [2, 4]));
+        assert!(invalid(Format::Dxt5, [2, 0], [4, 4]));
+        assert!(invalid(Format::Dxt5, [0, 0], [6, 4]));
+        assert!(!invalid(Format::Dxt5, [12, 12], [4, 4]));
+    }
+}
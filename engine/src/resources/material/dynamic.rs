@@ -1,6 +1,6 @@
 //! Dynamic material that can change its properties in each frame.
 
-use crate::render::ubo::MaterialData;
+use crate::render::ubo::{MaterialData, MATERIAL_NORMAL_MAP_AG};
 use std::sync::{Arc, Mutex};
 use vulkano::buffer::{BufferUsage, CpuBufferPool};
 use vulkano::descriptor_set::DescriptorSet;
@@ -9,15 +9,17 @@ use vulkano::descriptor_set::{
 };
 
 use crate::assets::Content;
-use crate::resources::image::create_image;
-use crate::resources::material::{FallbackMaps, Material, MATERIAL_UBO_DESCRIPTOR_SET};
-use bf::material::BlendMode;
+use crate::resources::image::{create_image, is_dxt5nm};
+use crate::resources::material::{
+    texture_uuids, FallbackMaps, Material, MATERIAL_UBO_DESCRIPTOR_SET,
+};
+use bf::material::{BlendMode, MaterialFlags};
+use bf::uuid::Uuid;
 use vulkano::image::view::ImageView;
 use vulkano::image::ImmutableImage;
 use vulkano::memory::DeviceMemoryAllocError;
 use vulkano::pipeline::GraphicsPipelineAbstract;
 use vulkano::sampler::Sampler;
-use vulkano::sync::GpuFuture;
 
 /// Errors that may happen when creating a dynamic material.
 #[derive(Debug)]
@@ -41,11 +43,15 @@ pub enum DynamicMaterialError {
 /// for dynamic materials is rebuild on each frame.
 pub struct DynamicMaterial {
     blend_mode: BlendMode,
+    flags: MaterialFlags,
+    textures: Vec<Uuid>,
     uniform_buffer_pool: CpuBufferPool<MaterialData>,
     descriptor_set_pool: Mutex<FixedSizeDescriptorSetsPool>,
     // todo: needs &mut reference to work internally
     pub fallback: Arc<FallbackMaps>,
     pub sampler: Arc<Sampler>,
+    /// Parameters of the material. Keep `MATERIAL_NORMAL_MAP_AG` in the flags
+    /// in sync with the format of the `normal_map` when replacing it.
     pub data: MaterialData,
     pub albedo_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
     pub normal_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
@@ -54,6 +60,7 @@ pub struct DynamicMaterial {
     pub ao_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
     pub metallic_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
     pub opacity_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
+    pub emissive_map: Option<Arc<ImageView<Arc<ImmutableImage>>>>,
 }
 
 impl DynamicMaterial {
@@ -64,32 +71,44 @@ impl DynamicMaterial {
         sampler: Arc<Sampler>,
         fallback: Arc<FallbackMaps>,
     ) -> Result<Arc<Self>, DynamicMaterialError> {
-        macro_rules! load_image_sync {
+        // returns the view of the image and the format of its asset
+        macro_rules! load_image_with_format {
             ($map: expr) => {
                 match &$map {
                     None => None,
                     Some(uuid) => {
                         let guard = content.request_load(*uuid);
                         let image = guard.wait();
+                        let bytes = image.mipmap_data.len();
+                        let format = image.format;
                         let (image, f) = create_image(&image, content.transfer_queue.clone())
                             .expect("cannot create image");
 
-                        f.then_signal_fence_and_flush().ok();
+                        content.uploads.schedule(f, bytes);
 
-                        Some(ImageView::new(image).expect("cannot create view from image"))
+                        Some((
+                            ImageView::new(image).expect("cannot create view from image"),
+                            format,
+                        ))
                     }
                 }
             };
         }
+        macro_rules! load_image_sync {
+            ($map: expr) => {
+                load_image_with_format!($map).map(|(view, _)| view)
+            };
+        }
 
         // use loaded textures or fallbacks
         let albedo_map = load_image_sync!(material.albedo_map);
-        let normal_map = load_image_sync!(material.normal_map);
+        let normal_map = load_image_with_format!(material.normal_map);
         let displacement_map = load_image_sync!(material.displacement_map);
         let roughness_map = load_image_sync!(material.roughness_map);
         let ao_map = load_image_sync!(material.ao_map);
         let metallic_map = load_image_sync!(material.metallic_map);
         let opacity_map = load_image_sync!(material.opacity_map);
+        let emissive_map = load_image_sync!(material.emissive_map);
 
         // create a descriptor set layout from pipeline
         let layout = pipeline
@@ -98,8 +117,19 @@ impl DynamicMaterial {
             .get(MATERIAL_UBO_DESCRIPTOR_SET)
             .ok_or(DynamicMaterialError::InvalidDescriptorSetNumber)?;
 
+        let mut data: MaterialData = (*material).into();
+        if normal_map
+            .as_ref()
+            .map_or(false, |(_, format)| is_dxt5nm(*format))
+        {
+            data.flags |= MATERIAL_NORMAL_MAP_AG;
+        }
+        let normal_map = normal_map.map(|(view, _)| view);
+
         Ok(Arc::new(DynamicMaterial {
             blend_mode: material.blend_mode,
+            flags: material.flags,
+            textures: texture_uuids(material),
             albedo_map,
             normal_map,
             displacement_map,
@@ -107,9 +137,10 @@ impl DynamicMaterial {
             ao_map,
             metallic_map,
             opacity_map,
+            emissive_map,
             sampler,
             fallback,
-            data: (*material).into(),
+            data,
             uniform_buffer_pool: CpuBufferPool::new(
                 pipeline.device().clone(),
                 BufferUsage::uniform_buffer(),
@@ -134,6 +165,7 @@ impl Material for DynamicMaterial {
             let ao = mat.fallback.white(&mat.ao_map);
             let metallic = mat.fallback.black(&mat.metallic_map);
             let opacity = mat.fallback.white(&mat.opacity_map);
+            let emissive = mat.fallback.white(&mat.emissive_map);
 
             // create a uniform buffer for this frame
             let buffer = mat
@@ -163,6 +195,8 @@ impl Material for DynamicMaterial {
                 .map_err(DynamicMaterialError::CannotCreateDescriptorSet)?
                 .add_sampled_image(opacity, mat.sampler.clone())
                 .map_err(DynamicMaterialError::CannotCreateDescriptorSet)?
+                .add_sampled_image(emissive, mat.sampler.clone())
+                .map_err(DynamicMaterialError::CannotCreateDescriptorSet)?
                 .build()
                 .map_err(DynamicMaterialError::CannotBuildDescriptorSet)?;
 
@@ -182,4 +216,12 @@ impl Material for DynamicMaterial {
     fn blend_mode(&self) -> BlendMode {
         self.blend_mode
     }
+
+    fn flags(&self) -> MaterialFlags {
+        self.flags
+    }
+
+    fn textures(&self) -> &[Uuid] {
+        &self.textures
+    }
 }
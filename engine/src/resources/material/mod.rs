@@ -8,9 +8,10 @@ mod dynamic;
 mod r#static;
 
 use crate::resources::image::create_single_pixel_image;
-use bf::material::BlendMode;
+use bf::material::{BlendMode, MaterialFlags};
+use bf::uuid::Uuid;
 pub use dynamic::DynamicMaterial;
-pub use r#static::StaticMaterial;
+pub use r#static::{StaticMaterial, StaticMaterialError};
 use vulkano::descriptor_set::DescriptorSet;
 use vulkano::device::Queue;
 use vulkano::image::view::ImageView;
@@ -27,6 +28,36 @@ pub trait Material {
     fn descriptor_set(&self) -> Arc<dyn DescriptorSet + Send + Sync>;
 
     fn blend_mode(&self) -> BlendMode;
+
+    /// Returns flags of the material that select a variant of the pipeline
+    /// objects with this material are drawn with (see `render::variants`).
+    fn flags(&self) -> MaterialFlags {
+        MaterialFlags::empty()
+    }
+
+    /// Returns UUIDs of image assets this material samples from. Used to
+    /// prioritize loading of textures that are visible on the screen.
+    fn textures(&self) -> &[Uuid] {
+        &[]
+    }
+}
+
+/// Returns UUIDs of all maps used by specified material.
+fn texture_uuids(material: &bf::material::Material) -> Vec<Uuid> {
+    [
+        material.albedo_map,
+        material.normal_map,
+        material.displacement_map,
+        material.roughness_map,
+        material.ao_map,
+        material.metallic_map,
+        material.opacity_map,
+        material.emissive_map,
+    ]
+    .iter()
+    .flatten()
+    .copied()
+    .collect()
 }
 
 impl Into<MaterialData> for bf::material::Material {
@@ -38,6 +69,11 @@ impl Into<MaterialData> for bf::material::Material {
             metallic: self.metallic,
             opacity: self.opacity,
             ior: self.ior,
+            emissive_color: self.emissive_color,
+            clear_coat: self.clear_coat,
+            clear_coat_roughness: self.clear_coat_roughness,
+            anisotropy: self.anisotropy,
+            flags: self.flags.bits(),
         }
     }
 }
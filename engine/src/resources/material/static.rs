@@ -0,0 +1,255 @@
+//! Static material whose properties are determined at creation time.
+
+use crate::assets::Content;
+use crate::render::ubo::{MaterialData, MATERIAL_NORMAL_MAP_AG};
+use crate::resources::image::{create_streamed_image_cached, is_dxt5nm, StreamedImage};
+use crate::resources::material::{
+    texture_uuids, FallbackMaps, Material, MATERIAL_UBO_DESCRIPTOR_SET,
+};
+use bf::material::{BlendMode, MaterialFlags};
+use bf::uuid::Uuid;
+use log::error;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, ImmutableBuffer};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::descriptor_set::{
+    PersistentDescriptorSet, PersistentDescriptorSetBuildError, PersistentDescriptorSetError,
+};
+use vulkano::device::Queue;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+/// Errors that may happen when creating a dynamic material.
+#[derive(Debug)]
+pub enum StaticMaterialError {
+    /// Uniform Buffer couldn't be created because of allocation error.
+    CannotCreateUniformBuffer(DeviceMemoryAllocError),
+    /// Descriptor set has invalid number.
+    InvalidDescriptorSetNumber,
+    /// Persistent descriptor set could be created.
+    CannotCreateDescriptorSet(PersistentDescriptorSetError),
+    /// Persistent descriptor set could be built.
+    CannotBuildDescriptorSet(PersistentDescriptorSetBuildError),
+}
+
+/// Maps of the material in order: albedo, normal, displacement,
+/// roughness, ao, metallic, opacity, emissive.
+type Maps = [Arc<StreamedImage>; 8];
+
+/// Static materials are unable to change their properties or
+/// textures at run-time. Static materials should be used when
+/// possible as they might be faster and more performant then dynamic.
+///
+/// The only thing that may change are resident mip-maps of streamed
+/// textures. The descriptor set is rebuilt when that happens.
+pub struct StaticMaterial {
+    blend_mode: BlendMode,
+    flags: MaterialFlags,
+    textures: Vec<Uuid>,
+    maps: Maps,
+    buffer: Arc<ImmutableBuffer<MaterialData>>,
+    layout: Arc<DescriptorSetLayout>,
+    sampler: Arc<Sampler>,
+    /// Current descriptor set and the revision of maps it was built with.
+    descriptor_set: Mutex<(u64, Arc<dyn DescriptorSet + Send + Sync>)>,
+}
+
+impl StaticMaterial {
+    pub fn from_material(
+        material: &bf::material::Material,
+        content: &Content,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        sampler: Arc<Sampler>,
+        queue: Arc<Queue>,
+        fallback: Arc<FallbackMaps>,
+    ) -> Result<(Arc<Self>, impl GpuFuture), StaticMaterialError> {
+        macro_rules! load_image_sync {
+            ($map: expr, $def: expr) => {
+                match &$map {
+                    None => StreamedImage::resident((&$def).clone()),
+                    Some(uuid) => create_streamed_image_cached(*uuid, content)
+                        .expect(&format!("cannot create image for: {}", uuid)),
+                }
+            };
+        }
+
+        // use loaded textures or fallbacks
+        let maps = [
+            load_image_sync!(material.albedo_map, fallback.fallback_white),
+            load_image_sync!(material.normal_map, fallback.fallback_normal),
+            load_image_sync!(material.displacement_map, fallback.fallback_black),
+            load_image_sync!(material.roughness_map, fallback.fallback_white),
+            load_image_sync!(material.ao_map, fallback.fallback_white),
+            load_image_sync!(material.metallic_map, fallback.fallback_black),
+            load_image_sync!(material.opacity_map, fallback.fallback_white),
+            load_image_sync!(material.emissive_map, fallback.fallback_white),
+        ];
+
+        // create a uniform buffer with material data
+        let mut data: MaterialData = (*material).into();
+        if maps[1].format().map_or(false, is_dxt5nm) {
+            data.flags |= MATERIAL_NORMAL_MAP_AG;
+        }
+        let (buffer, future) =
+            ImmutableBuffer::from_data(data, BufferUsage::uniform_buffer(), queue)
+                .map_err(StaticMaterialError::CannotCreateUniformBuffer)?;
+
+        Ok((
+            Self::new(
+                material.blend_mode,
+                material.flags,
+                texture_uuids(material),
+                maps,
+                buffer,
+                pipeline,
+                sampler,
+            )?,
+            future,
+        ))
+    }
+
+    pub fn from_material_data(
+        blend_mode: BlendMode,
+        parameters: MaterialData,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        sampler: Arc<Sampler>,
+        queue: Arc<Queue>,
+        fallback: Arc<FallbackMaps>,
+    ) -> Result<(Arc<Self>, impl GpuFuture), StaticMaterialError> {
+        // create a uniform buffer with material data
+        let (buffer, future) =
+            ImmutableBuffer::from_data(parameters, BufferUsage::uniform_buffer(), queue)
+                .map_err(StaticMaterialError::CannotCreateUniformBuffer)?;
+
+        // use fallbacks
+        let maps = [
+            StreamedImage::resident(fallback.fallback_white.clone()),
+            StreamedImage::resident(fallback.fallback_normal.clone()),
+            StreamedImage::resident(fallback.fallback_black.clone()),
+            StreamedImage::resident(fallback.fallback_white.clone()),
+            StreamedImage::resident(fallback.fallback_white.clone()),
+            StreamedImage::resident(fallback.fallback_white.clone()),
+            StreamedImage::resident(fallback.fallback_white.clone()),
+            StreamedImage::resident(fallback.fallback_white.clone()),
+        ];
+
+        Ok((
+            Self::new(
+                blend_mode,
+                MaterialFlags::empty(),
+                vec![],
+                maps,
+                buffer,
+                pipeline,
+                sampler,
+            )?,
+            future,
+        ))
+    }
+
+    fn new(
+        blend_mode: BlendMode,
+        flags: MaterialFlags,
+        textures: Vec<Uuid>,
+        maps: Maps,
+        buffer: Arc<ImmutableBuffer<MaterialData>>,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        sampler: Arc<Sampler>,
+    ) -> Result<Arc<Self>, StaticMaterialError> {
+        // create a descriptor set layout from pipeline
+        let layout = pipeline
+            .layout()
+            .descriptor_set_layouts()
+            .get(MATERIAL_UBO_DESCRIPTOR_SET)
+            .ok_or(StaticMaterialError::InvalidDescriptorSetNumber)?
+            .clone();
+
+        let set = build_descriptor_set(&layout, &sampler, &maps, &buffer)?;
+
+        Ok(Arc::new(Self {
+            descriptor_set: Mutex::new((revision(&maps), set)),
+            blend_mode,
+            flags,
+            textures,
+            maps,
+            buffer,
+            layout,
+            sampler,
+        }))
+    }
+}
+
+/// Returns the number that changes when any of the maps changes.
+fn revision(maps: &Maps) -> u64 {
+    maps.iter().map(|x| x.revision()).sum()
+}
+
+fn build_descriptor_set(
+    layout: &Arc<DescriptorSetLayout>,
+    sampler: &Arc<Sampler>,
+    maps: &Maps,
+    buffer: &Arc<ImmutableBuffer<MaterialData>>,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>, StaticMaterialError> {
+    let [albedo, normal, displacement, roughness, ao, metallic, opacity, emissive] = maps;
+
+    let set = PersistentDescriptorSet::start(layout.clone())
+        .add_sampled_image(albedo.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(normal.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(displacement.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(roughness.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(ao.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(metallic.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_buffer(buffer.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(opacity.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .add_sampled_image(emissive.view(), sampler.clone())
+        .map_err(StaticMaterialError::CannotCreateDescriptorSet)?
+        .build()
+        .map_err(StaticMaterialError::CannotBuildDescriptorSet)?;
+
+    Ok(Arc::new(set))
+}
+
+impl Material for StaticMaterial {
+    fn descriptor_set(&self) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let mut current = self.descriptor_set.lock();
+        let revision = revision(&self.maps);
+
+        // some of the streamed maps were replaced
+        if current.0 != revision {
+            match build_descriptor_set(&self.layout, &self.sampler, &self.maps, &self.buffer) {
+                Ok(set) => *current = (revision, set),
+                Err(e) => {
+                    error!("Cannot rebuild descriptor set of material: {:?}", e);
+                    // keep using the old descriptor set
+                    current.0 = revision;
+                }
+            }
+        }
+
+        current.1.clone()
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn flags(&self) -> MaterialFlags {
+        self.flags
+    }
+
+    fn textures(&self) -> &[Uuid] {
+        &self.textures
+    }
+}
@@ -0,0 +1,663 @@
+//! Meshes and functions used to created meshes.
+
+use crate::assets::Content;
+use crate::picking::PickMesh;
+use crate::render::vertex::{PositionOnlyVertex, WindVertex};
+use crate::resources::cache::GPU_RESOURCES;
+use crate::resources::upload::{upload_path, UploadPath};
+use bf::mesh::{select_lod, IndexType, Lod, MeshEncodingError};
+use bf::uuid::Uuid;
+use safe_transmute::{Error, TriviallyTransmutable};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use vulkano::buffer::{
+    BufferAccess, BufferSlice, BufferUsage, CpuAccessibleBuffer, ImmutableBuffer, TypedBufferAccess,
+};
+use vulkano::device::Queue;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::input_assembly::Index;
+use vulkano::pipeline::vertex::Vertex;
+use vulkano::sync::{now, GpuFuture};
+
+/// Buffer of vertices (of any type) on the GPU.
+pub type VertexBuffer = Arc<dyn BufferAccess + Send + Sync>;
+
+/// Buffer of indices of type `I` on the GPU.
+pub type IndexBuffer<I> = Arc<dyn TypedBufferAccess<Content = [I]> + Send + Sync>;
+
+/// Renderable indexed triangular geometry with specified vertex format
+/// and index type.
+///
+/// Buffers are either device-local buffers filled by a copy from staging
+/// buffer or host-visible buffers written directly by the CPU (see `upload`
+/// module).
+pub struct IndexedMesh<V, I>
+where
+    V: Vertex,
+    I: Index,
+{
+    /// Vertex buffer.
+    vertex_buffer: VertexBuffer,
+    /// Index buffer.
+    index_buffer: IndexBuffer<I>,
+    /// Levels of detail (ranges of the index buffer). Empty when the mesh
+    /// has only one level.
+    lods: Vec<Lod>,
+    /// Buffer of `WindVertex` with stiffness of each vertex used by foliage
+    /// materials. `None` when the mesh asset has no stiffness.
+    stiffness_buffer: Option<VertexBuffer>,
+    /// Triangles retained on the CPU for picking. `None` for meshes that
+    /// were not created from assets.
+    pick_mesh: Option<Arc<PickMesh>>,
+    vertex: PhantomData<V>,
+}
+
+impl<V, I> IndexedMesh<V, I>
+where
+    V: Vertex,
+    I: Index,
+{
+    /// Creates a new `Mesh` from provided buffers.
+    pub fn new(vertex_buffer: VertexBuffer, index_buffer: IndexBuffer<I>) -> Arc<Self> {
+        Arc::new(Self {
+            vertex_buffer,
+            index_buffer,
+            lods: vec![],
+            stiffness_buffer: None,
+            pick_mesh: None,
+            vertex: PhantomData,
+        })
+    }
+
+    /// Creates a new `Mesh` with levels of detail stored in ranges of the index buffer.
+    pub fn with_lods(
+        vertex_buffer: VertexBuffer,
+        index_buffer: IndexBuffer<I>,
+        lods: Vec<Lod>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            vertex_buffer,
+            index_buffer,
+            lods,
+            stiffness_buffer: None,
+            pick_mesh: None,
+            vertex: PhantomData,
+        })
+    }
+
+    /// Returns the `Arc` reference to vertex buffer of this mesh.
+    #[inline]
+    pub fn vertex_buffer(&self) -> &VertexBuffer {
+        &self.vertex_buffer
+    }
+
+    /// Returns the vertex buffers the mesh is drawn with. The stiffness buffer
+    /// follows the vertex buffer when `stiffness` is requested and the mesh has it.
+    pub fn vertex_buffers(&self, stiffness: bool) -> Vec<VertexBuffer> {
+        let mut buffers = vec![self.vertex_buffer.clone()];
+        if let Some(t) = self.stiffness_buffer.as_ref().filter(|_| stiffness) {
+            buffers.push(t.clone());
+        }
+        buffers
+    }
+
+    /// Returns whether this mesh has stiffness of vertices (see `WindVertex`).
+    #[inline]
+    pub fn has_stiffness(&self) -> bool {
+        self.stiffness_buffer.is_some()
+    }
+
+    /// Returns the triangles of this mesh retained on the CPU (see `picking` module).
+    #[inline]
+    pub fn pick_mesh(&self) -> Option<&Arc<PickMesh>> {
+        self.pick_mesh.as_ref()
+    }
+
+    /// Returns the `Arc` reference to index buffer of this mesh.
+    #[inline]
+    pub fn index_buffer(&self) -> &IndexBuffer<I> {
+        &self.index_buffer
+    }
+
+    /// Returns the number of levels of detail of this mesh.
+    #[inline]
+    pub fn lod_count(&self) -> usize {
+        self.lods.len().max(1)
+    }
+
+    /// Returns the level of detail that should be used when the mesh covers
+    /// `coverage` fraction of the screen.
+    #[inline]
+    pub fn select_lod(&self, coverage: f32) -> usize {
+        select_lod(&self.lods, coverage)
+    }
+
+    /// Returns the part of index buffer that contains indices of the specified
+    /// level of detail. Whole index buffer is returned for invalid levels.
+    pub fn lod_index_buffer(&self, level: usize) -> BufferSlice<[I], IndexBuffer<I>> {
+        let whole = BufferSlice::from_typed_buffer_access(self.index_buffer.clone());
+
+        match self.lods.get(level) {
+            Some(lod) => {
+                let start = lod.first_index as usize;
+                let end = start + lod.index_count as usize;
+                whole.clone().slice(start..end).unwrap_or(whole)
+            }
+            None => whole,
+        }
+    }
+}
+
+/// Possible errors that can happen when creating a buffer.
+#[derive(Debug)]
+pub enum CreateBufferError {
+    /// Generic parameters representing a single element in the created buffer
+    /// is of incorrect type.
+    IncorrectElementType(&'static str),
+    /// The buffer couldn't be allocated.
+    CannotAllocateBuffer(DeviceMemoryAllocError),
+    /// Encoded mesh data couldn't be decoded.
+    CannotDecodeMesh(MeshEncodingError),
+}
+
+/// Buffer created by [`create_buffer`](fn.create_buffer.html) function.
+enum UploadedBuffer<T> {
+    Staged(Arc<ImmutableBuffer<[T]>>),
+    Direct(Arc<CpuAccessibleBuffer<[T]>>),
+}
+
+impl<T: Send + Sync + 'static> UploadedBuffer<T> {
+    fn into_untyped(self) -> VertexBuffer {
+        match self {
+            UploadedBuffer::Staged(b) => b,
+            UploadedBuffer::Direct(b) => b,
+        }
+    }
+
+    fn into_typed(self) -> Arc<dyn TypedBufferAccess<Content = [T]> + Send + Sync> {
+        match self {
+            UploadedBuffer::Staged(b) => b,
+            UploadedBuffer::Direct(b) => b,
+        }
+    }
+}
+
+/// Helper function to create a GPU buffer from array elements of type `T` encoded
+/// as array of bytes. The buffer is written directly when the device supports
+/// it, otherwise through a staging buffer.
+///
+/// This function is internally used by [`create_mesh`](fn.create_mesh.html) fucntion.
+fn create_buffer<T>(
+    bytes: &[u8],
+    queue: Arc<Queue>,
+    usage: BufferUsage,
+) -> Result<(UploadedBuffer<T>, Box<dyn GpuFuture + Send>), CreateBufferError>
+where
+    T: TriviallyTransmutable + Send + Sync + 'static,
+{
+    fn possible_non_zero_copy<'a, T: TriviallyTransmutable>(
+        bytes: &'a [u8],
+        possible_owner: &'a mut std::vec::Vec<T>,
+    ) -> &'a [T] {
+        match safe_transmute::transmute_many_pedantic::<T>(bytes) {
+            Ok(safe) => safe,
+            Err(Error::Unaligned(e)) => {
+                log::error!(
+                    "cannot zero-copy unaligned &[{:?}] data: {:?}",
+                    std::any::type_name::<T>(),
+                    e
+                );
+                *possible_owner = e.copy();
+                possible_owner.as_slice()
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+    }
+
+    // copy data to correctly aligned temporary array
+    let mut index_vec = Vec::new();
+    let items = possible_non_zero_copy::<T>(bytes, &mut index_vec);
+
+    match upload_path(queue.device().physical_device()) {
+        UploadPath::Direct => {
+            // the buffer is written when it is created so it is ready right away
+            let buffer = CpuAccessibleBuffer::from_iter(
+                queue.device().clone(),
+                usage,
+                false,
+                items.iter().cloned(),
+            )
+            .map_err(CreateBufferError::CannotAllocateBuffer)?;
+
+            Ok((
+                UploadedBuffer::Direct(buffer),
+                now(queue.device().clone()).boxed_send(),
+            ))
+        }
+        UploadPath::Staging => {
+            // copy data from temporary aligned array to staging buffer and
+            // then issue gpu-copy between staging and final buffer
+            let (buffer, future) = ImmutableBuffer::from_iter(items.iter().cloned(), usage, queue)
+                .map_err(CreateBufferError::CannotAllocateBuffer)?;
+
+            Ok((UploadedBuffer::Staged(buffer), future.boxed_send()))
+        }
+    }
+}
+
+/// This function creates a `Mesh` struct from provided `bf::mesh::Mesh` asset
+/// without any conversion (meshopt encoded data is decoded first). Stiffness of
+/// vertices is uploaded to a second vertex buffer. This function returns the mesh
+/// and `GpuFuture` that represents the time when all buffers (and thus the mesh)
+/// are ready to use.
+pub fn create_mesh<V, I>(
+    from: &bf::mesh::Mesh,
+    queue: Arc<Queue>,
+) -> Result<(Arc<IndexedMesh<V, I>>, impl GpuFuture), CreateBufferError>
+where
+    V: Vertex + TriviallyTransmutable + Send + Sync + 'static,
+    I: Index + TriviallyTransmutable + Send + Sync + 'static,
+{
+    // verify that the method was invoked with correct index type
+    if from.index_type.size_of_one_index() != std::mem::size_of::<I>() {
+        return Err(CreateBufferError::IncorrectElementType(
+            "Index type is incorrect",
+        ));
+    }
+
+    // verify that the method was invoked with correct index type
+    if from.vertex_format.size_of_one_vertex() != std::mem::size_of::<V>() {
+        return Err(CreateBufferError::IncorrectElementType(
+            "Vertex type is incorrect",
+        ));
+    }
+
+    let (vertex_data, index_data) = from
+        .decoded_data()
+        .map_err(CreateBufferError::CannotDecodeMesh)?;
+
+    // verify that there is stiffness for every vertex
+    if let Some(t) = &from.stiffness {
+        if t.len() / std::mem::size_of::<f32>() != vertex_data.len() / std::mem::size_of::<V>() {
+            return Err(CreateBufferError::IncorrectElementType(
+                "Stiffness doesn't match vertices",
+            ));
+        }
+    }
+
+    let (vertex, f1) =
+        create_buffer::<V>(&vertex_data, queue.clone(), BufferUsage::vertex_buffer())?;
+    let (index, f2) = create_buffer::<I>(&index_data, queue.clone(), BufferUsage::index_buffer())?;
+    let (stiffness, f3) = match &from.stiffness {
+        Some(t) => {
+            let (buffer, f) = create_buffer::<WindVertex>(t, queue, BufferUsage::vertex_buffer())?;
+            (Some(buffer.into_untyped()), Some(f))
+        }
+        None => (None, None),
+    };
+
+    // only the most detailed level is retained for picking
+    let index_size = std::mem::size_of::<I>();
+    let lod_indices = match from.lods.first() {
+        Some(lod) => {
+            let start = lod.first_index as usize * index_size;
+            let end = start + lod.index_count as usize * index_size;
+            index_data.get(start..end).unwrap_or(&index_data)
+        }
+        None => &index_data,
+    };
+    let pick_mesh = PickMesh::from_bytes(
+        &vertex_data,
+        std::mem::size_of::<V>(),
+        lod_indices,
+        index_size,
+    );
+
+    let mesh = Arc::new(IndexedMesh {
+        vertex_buffer: vertex.into_untyped(),
+        index_buffer: index.into_typed(),
+        lods: from.lods.clone(),
+        stiffness_buffer: stiffness,
+        pick_mesh: Some(Arc::new(pick_mesh)),
+        vertex: PhantomData,
+    });
+
+    let future = f1.join(f2);
+    Ok((
+        mesh,
+        match f3 {
+            Some(f3) => future.join(f3).boxed_send(),
+            None => future.boxed_send(),
+        },
+    ))
+}
+
+/// Generates a new `Mesh` instance that is a full-screen triangle that can be used
+/// to perform full-screen passes. This function returns the mesh and `GpuFuture` that
+/// represents the time when both buffers (and thus the mesh) are ready to use.
+pub fn create_full_screen_triangle(
+    queue: Arc<Queue>,
+) -> Result<(Arc<IndexedMesh<PositionOnlyVertex, u16>>, impl GpuFuture), DeviceMemoryAllocError> {
+    const VERTEX_DATA_FST: [PositionOnlyVertex; 3] = [
+        PositionOnlyVertex {
+            position: [-1.0, -1.0, 0.0, 0.0],
+        },
+        PositionOnlyVertex {
+            position: [3.0, -1.0, 0.0, 0.0],
+        },
+        PositionOnlyVertex {
+            position: [-1.0, 3.0, 0.0, 0.0],
+        },
+    ];
+    const INDEX_DATA_FST: [u16; 3] = [0, 1, 2];
+
+    let (vertex_buffer, vbo_future) = ImmutableBuffer::from_iter(
+        (&VERTEX_DATA_FST).iter().cloned(),
+        BufferUsage::vertex_buffer(),
+        queue.clone(),
+    )?;
+    let (index_buffer, ibo_future) = ImmutableBuffer::from_iter(
+        (&INDEX_DATA_FST).iter().cloned(),
+        BufferUsage::index_buffer(),
+        queue,
+    )?;
+
+    Ok((
+        IndexedMesh::new(vertex_buffer, index_buffer),
+        vbo_future.join(ibo_future),
+    ))
+}
+
+/// Generates a new `Mesh` instance that is an unit cube centered at the origin
+/// (from `-0.5` to `0.5` on each axis). Faces are wound counter-clockwise when
+/// seen from the outside.
+///
+/// This function returns the mesh and `GpuFuture` that represents the time when
+/// both buffers (and thus the mesh) are ready to use.
+pub fn create_cube(
+    queue: Arc<Queue>,
+) -> Result<(Arc<IndexedMesh<PositionOnlyVertex, u16>>, impl GpuFuture), DeviceMemoryAllocError> {
+    // bits of the index are the coordinates of the corner (x, y, z)
+    let vertex_data = (0..8).map(|i| PositionOnlyVertex {
+        position: [
+            (i & 1) as f32 - 0.5,
+            ((i >> 1) & 1) as f32 - 0.5,
+            ((i >> 2) & 1) as f32 - 0.5,
+            0.0,
+        ],
+    });
+    const INDEX_DATA_CUBE: [u16; 36] = [
+        4, 6, 2, 4, 2, 0, // -x
+        1, 3, 7, 1, 7, 5, // +x
+        0, 1, 5, 0, 5, 4, // -y
+        6, 7, 3, 6, 3, 2, // +y
+        2, 3, 1, 2, 1, 0, // -z
+        4, 5, 7, 4, 7, 6, // +z
+    ];
+
+    let (vertex_buffer, vbo_future) =
+        ImmutableBuffer::from_iter(vertex_data, BufferUsage::vertex_buffer(), queue.clone())?;
+    let (index_buffer, ibo_future) = ImmutableBuffer::from_iter(
+        (&INDEX_DATA_CUBE).iter().cloned(),
+        BufferUsage::index_buffer(),
+        queue,
+    )?;
+
+    Ok((
+        IndexedMesh::new(vertex_buffer, index_buffer),
+        vbo_future.join(ibo_future),
+    ))
+}
+
+/// Generates a new `Mesh` instance that is a icosphere. First the icosahedron is
+/// generated, then more faces are added depending on the level of refinement.
+///
+/// Refinement level zero will cause this function to generate icosahedron. Each
+/// level will cause to subdivide triangle into 4 triangles.
+///
+/// This function returns the mesh and `GpuFuture` that represents the time when
+/// both buffers (and thus the mesh) are ready to use.
+///
+/// ![Icosahderon](https://upload.wikimedia.org/wikipedia/commons/thumb/e/e8/Zeroth_stellation_of_icosahedron.png/240px-Zeroth_stellation_of_icosahedron.png)
+pub fn create_icosphere(
+    queue: Arc<Queue>,
+    refine_levels: u32,
+) -> Result<(Arc<IndexedMesh<PositionOnlyVertex, u16>>, impl GpuFuture), DeviceMemoryAllocError> {
+    // macro to create and normalize `PositionOnlyVertex` in less code
+    macro_rules! v {
+        ($($points:expr),+) => {
+            {
+                let length = ($($points * $points+)+ 0.0).sqrt();
+                let normalized = [$($points / length),+, 0.0];
+                PositionOnlyVertex { position: normalized }
+            }
+        };
+    }
+
+    let phi = (1.0 + (5.0_f32.sqrt())) / 2.0;
+    let mut vertex_data = vec![
+        v!(-1.0, phi, 0.0),
+        v!(1.0, phi, 0.0),
+        v!(-1.0, -phi, 0.0),
+        v!(1.0, -phi, 0.0),
+        v!(0.0, -1.0, phi),
+        v!(0.0, 1.0, phi),
+        v!(0.0, -1.0, -phi),
+        v!(0.0, 1.0, -phi),
+        v!(phi, 0.0, -1.0),
+        v!(phi, 0.0, 1.0),
+        v!(-phi, 0.0, -1.0),
+        v!(-phi, 0.0, 1.0),
+    ];
+    let mut index_data = vec![
+        0u16, 11, 5, //
+        0, 5, 1, //
+        0, 1, 7, //
+        0, 7, 10, //
+        0, 10, 11, //
+        1, 5, 9, //
+        5, 11, 4, //
+        11, 10, 2, //
+        10, 7, 6, //
+        7, 1, 8, //
+        3, 9, 4, //
+        3, 4, 2, //
+        3, 2, 6, //
+        3, 6, 8, //
+        3, 8, 9, //
+        4, 9, 5, //
+        2, 4, 11, //
+        6, 2, 10, //
+        8, 6, 7, //
+        9, 8, 1, //
+    ];
+
+    // refinements with cache to merge same vertices
+    let mut cache: HashMap<u32, u16> = HashMap::new();
+    let mut middle_point = |p1: u16, p2: u16| {
+        let small = p1.min(p2) as u32;
+        let big = p1.max(p2) as u32;
+        let key = (small << 16) + big;
+
+        match cache.entry(key) {
+            Entry::Occupied(t) => *t.get(),
+            Entry::Vacant(t) => {
+                let v1 = vertex_data[p1 as usize].position;
+                let v2 = vertex_data[p2 as usize].position;
+
+                // compute middle point
+                let mx = (v1[0] + v2[0]) / 2.0;
+                let my = (v1[1] + v2[1]) / 2.0;
+                let mz = (v1[2] + v2[2]) / 2.0;
+
+                let index = vertex_data.len();
+                vertex_data.push(v!(mx, my, mz));
+                assert!(index < std::u16::MAX as usize);
+                *t.insert(index as u16)
+            }
+        }
+    };
+
+    for _ in 0..refine_levels {
+        let mut new_index_data = vec![];
+
+        for triangle in index_data.chunks(3) {
+            let v1 = triangle[0];
+            let v2 = triangle[1];
+            let v3 = triangle[2];
+
+            let a = middle_point(v1, v2);
+            let b = middle_point(v2, v3);
+            let c = middle_point(v3, v1);
+
+            // replace this face with 4 faces
+            new_index_data.extend_from_slice(&[
+                v1, a, c, //
+                v2, b, a, //
+                v3, c, b, //
+                a, b, c,
+            ])
+        }
+
+        index_data = new_index_data;
+    }
+
+    let (vertex_buffer, vbo_future) = ImmutableBuffer::from_iter(
+        vertex_data.into_iter(),
+        BufferUsage::vertex_buffer(),
+        queue.clone(),
+    )?;
+    let (index_buffer, ibo_future) =
+        ImmutableBuffer::from_iter(index_data.into_iter(), BufferUsage::index_buffer(), queue)?;
+
+    Ok((
+        IndexedMesh::new(vertex_buffer, index_buffer),
+        vbo_future.join(ibo_future),
+    ))
+}
+
+/// Renderable indexed triangular geometry with specified vertex format
+/// and **dynamic runtime chosen** index format.
+///
+/// You need to always match on variant before using the inner `IndexeMesh`.
+pub enum DynamicIndexedMesh<V: Vertex> {
+    U16(IndexedMesh<V, u16>),
+    U32(IndexedMesh<V, u32>),
+}
+
+impl<V> From<IndexedMesh<V, u16>> for DynamicIndexedMesh<V>
+where
+    V: Vertex,
+{
+    fn from(idx: IndexedMesh<V, u16>) -> Self {
+        DynamicIndexedMesh::U16(idx)
+    }
+}
+
+impl<V> From<IndexedMesh<V, u32>> for DynamicIndexedMesh<V>
+where
+    V: Vertex,
+{
+    fn from(idx: IndexedMesh<V, u32>) -> Self {
+        DynamicIndexedMesh::U32(idx)
+    }
+}
+
+impl<V: Vertex> DynamicIndexedMesh<V> {
+    /// Returns the level of detail that should be used when the mesh covers
+    /// `coverage` fraction of the screen.
+    pub fn select_lod(&self, coverage: f32) -> usize {
+        match self {
+            DynamicIndexedMesh::U16(m) => m.select_lod(coverage),
+            DynamicIndexedMesh::U32(m) => m.select_lod(coverage),
+        }
+    }
+
+    /// Returns whether the mesh has stiffness of vertices (see `WindVertex`).
+    pub fn has_stiffness(&self) -> bool {
+        match self {
+            DynamicIndexedMesh::U16(m) => m.has_stiffness(),
+            DynamicIndexedMesh::U32(m) => m.has_stiffness(),
+        }
+    }
+
+    /// Returns the triangles of the mesh retained on the CPU (see `picking` module).
+    pub fn pick_mesh(&self) -> Option<&Arc<PickMesh>> {
+        match self {
+            DynamicIndexedMesh::U16(m) => m.pick_mesh(),
+            DynamicIndexedMesh::U32(m) => m.pick_mesh(),
+        }
+    }
+
+    /// Returns the radius of the sphere centered at the origin that contains
+    /// the whole mesh or `None` if its positions are not retained on the CPU.
+    pub fn bounding_radius(&self) -> Option<f32> {
+        self.pick_mesh().map(|x| x.bounding_radius())
+    }
+}
+
+/// Result of [`create_mesh_dynamic`](fn.create_mesh_dynamic.html) function invocation.
+pub type DynamicIndexedMeshResult<V> =
+    Result<(Arc<DynamicIndexedMesh<V>>, Box<dyn GpuFuture + Send>), CreateBufferError>;
+
+/// Same as [`create_mesh`](fn.create_mesh.html) except the index type is chosen at
+/// runtime.
+///
+/// This function creates a `DynamicMesh` enum from provided `bf::mesh::Mesh` asset
+/// without any conversion. It automatically select the appropriate index type based
+/// on the information in `mesh` parameters.
+///
+/// This function returns the mesh and `GpuFuture` that represents the time when both
+/// buffers (and thus the mesh) are ready to use.
+pub fn create_mesh_dynamic<V: Vertex + TriviallyTransmutable>(
+    mesh: &bf::mesh::Mesh,
+    queue: Arc<Queue>,
+) -> DynamicIndexedMeshResult<V> {
+    macro_rules! impl_for_types {
+        ($($typ:ident),+) => {
+            match mesh.index_type {
+                $(IndexType::$typ => match create_mesh(&mesh, queue) {
+                    Ok((t, f)) => return Ok((
+                        Arc::new(DynamicIndexedMesh::$typ(match Arc::try_unwrap(t) {
+                            Ok(t) => t,
+                            Err(_) => unreachable!(),
+                        })),
+                        f.boxed_send(),
+                    )),
+                    Err(e) => {
+                        return Err(e)
+                    }
+                }),+
+            }
+        };
+    }
+
+    impl_for_types!(U16, U32);
+}
+
+/// Same as [`create_mesh_dynamic`](fn.create_mesh_dynamic.html) except the mesh asset
+/// is loaded from `content` (if it was not loaded yet) and the created buffers are shared
+/// by everyone who requests the same revision of the asset. The buffers are released
+/// when the last user of the mesh is dropped.
+///
+/// The upload of the buffers is scheduled on `content.uploads`, so the mesh can be
+/// used by the render loop right away.
+pub fn create_mesh_cached<V: Vertex + TriviallyTransmutable>(
+    uuid: Uuid,
+    content: &Content,
+) -> Result<Arc<DynamicIndexedMesh<V>>, CreateBufferError> {
+    let revision = content.ensure_loaded::<bf::mesh::Mesh>(uuid);
+    let queue = content.transfer_queue.clone();
+
+    let (mesh, _) = GPU_RESOURCES.get_or_create(uuid, revision, || {
+        let asset = content.get_blocking::<bf::mesh::Mesh>(&uuid);
+        let (mesh, future) = create_mesh_dynamic(&asset, queue.clone())?;
+        let bytes = asset.vertex_data.len() + asset.index_data.len();
+        content.uploads.schedule(future, bytes);
+        Ok((mesh, ()))
+    })?;
+
+    Ok(mesh)
+}
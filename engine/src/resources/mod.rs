@@ -3,6 +3,11 @@
 //! All `create_` functions accept parameter of type `Arc<Queue>`. This is the Vulkan
 //! queue that will be used to upload the data to the GPU buffers / images.
 
+pub mod cache;
 pub mod image;
 pub mod material;
 pub mod mesh;
+pub mod swap;
+pub mod transcode;
+pub mod transfer;
+pub mod upload;
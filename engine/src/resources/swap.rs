@@ -0,0 +1,170 @@
+//! Replacing of GPU resources that may still be used by frames in flight.
+//!
+//! Command buffers of previous frames may still reference a resource when it is
+//! replaced (eg. by a reloaded or streamed version). Instead of dropping the old
+//! version right away it is *retired*: moved to the garbage list of the current
+//! frame and dropped only after all frames that could use it have finished.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Resources retired during a single frame.
+type GarbageList = Vec<Box<dyn Any + Send>>;
+
+/// Garbage lists of all frames that may still be in flight.
+static GARBAGE: Lazy<Mutex<Garbage>> = Lazy::new(|| Mutex::new(Garbage::default()));
+
+/// Per-frame lists of retired resources.
+#[derive(Default)]
+pub struct Garbage {
+    /// Number of frames started so far.
+    frame: u64,
+    /// Lists of retired resources with the frame they were retired in (oldest first).
+    lists: VecDeque<(u64, GarbageList)>,
+}
+
+impl Garbage {
+    /// Keeps the `resource` alive until all frames that could use it have finished.
+    pub fn retire<T: Send + 'static>(&mut self, resource: T) {
+        match self.lists.back_mut() {
+            Some((frame, list)) if *frame == self.frame => list.push(Box::new(resource)),
+            _ => self
+                .lists
+                .push_back((self.frame, vec![Box::new(resource) as Box<dyn Any + Send>])),
+        }
+    }
+
+    /// Starts a new frame and drops resources that were retired more than
+    /// `frames_in_flight` frames ago. Returns number of dropped resources.
+    pub fn next_frame(&mut self, frames_in_flight: usize) -> usize {
+        self.frame += 1;
+
+        let mut released = 0;
+        while let Some((frame, _)) = self.lists.front() {
+            if self.frame - frame <= frames_in_flight as u64 {
+                break;
+            }
+            released += self.lists.pop_front().map_or(0, |(_, list)| list.len());
+        }
+        released
+    }
+
+    /// Returns number of retired resources that are still alive.
+    pub fn len(&self) -> usize {
+        self.lists.iter().map(|(_, list)| list.len()).sum()
+    }
+
+    /// Returns whether there are no retired resources waiting to be dropped.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Retires the `resource` to the global garbage list. It will be dropped once
+/// the renderer has finished all frames that could use it.
+pub fn retire<T: Send + 'static>(resource: T) {
+    GARBAGE.lock().retire(resource)
+}
+
+/// Starts a new frame in the global garbage list. Called by the renderer once
+/// per frame after the finished frames were cleaned up.
+pub fn next_frame(frames_in_flight: usize) -> usize {
+    GARBAGE.lock().next_frame(frames_in_flight)
+}
+
+/// Resource that can be replaced while it is used for rendering. The previous
+/// version is retired (see [`retire`](fn.retire.html)) so it stays alive until
+/// the frames that reference it are finished.
+///
+/// Each replacement increments the `generation`. Users that cache objects
+/// derived from the resource (eg. descriptor sets) should compare it to find
+/// out whether they need to be rebuilt.
+pub struct Swap<T> {
+    current: Mutex<Arc<T>>,
+    generation: AtomicU64,
+}
+
+impl<T: Send + Sync + 'static> Swap<T> {
+    /// Creates a new swappable resource with `resource` as its first generation.
+    pub fn new(resource: Arc<T>) -> Self {
+        Self {
+            current: Mutex::new(resource),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns current version of the resource.
+    pub fn get(&self) -> Arc<T> {
+        self.current.lock().clone()
+    }
+
+    /// Returns the number that is incremented each time the resource is replaced.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Replaces the resource with a new version and retires the old one.
+    /// Returns the new generation.
+    pub fn replace(&self, resource: Arc<T>) -> u64 {
+        let old = std::mem::replace(&mut *self.current.lock(), resource);
+        retire(old);
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resources_live_until_frames_finish() {
+        let mut garbage = Garbage::default();
+        let resource = Arc::new(());
+
+        garbage.retire(resource.clone());
+        assert_eq!(garbage.len(), 1);
+
+        // the frame that retired the resource and two more may be in flight
+        assert_eq!(garbage.next_frame(2), 0);
+        assert_eq!(garbage.next_frame(2), 0);
+        assert_eq!(Arc::strong_count(&resource), 2);
+
+        assert_eq!(garbage.next_frame(2), 1);
+        assert_eq!(Arc::strong_count(&resource), 1);
+        assert!(garbage.is_empty());
+    }
+
+    #[test]
+    fn frames_are_released_separately() {
+        let mut garbage = Garbage::default();
+
+        garbage.retire(1u32);
+        garbage.retire(2u32);
+        garbage.next_frame(1);
+        garbage.retire(3u32);
+
+        assert_eq!(garbage.len(), 3);
+        assert_eq!(garbage.next_frame(1), 2);
+        assert_eq!(garbage.len(), 1);
+        assert_eq!(garbage.next_frame(1), 1);
+        assert!(garbage.is_empty());
+    }
+
+    #[test]
+    fn swap_retires_previous_version() {
+        let first = Arc::new(1u32);
+        let swap = Swap::new(first.clone());
+        assert_eq!(swap.generation(), 0);
+
+        assert_eq!(swap.replace(Arc::new(2)), 1);
+        assert_eq!(*swap.get(), 2);
+        assert_eq!(swap.generation(), 1);
+
+        // the old version is kept alive by the global garbage list
+        assert_eq!(Arc::strong_count(&first), 2);
+    }
+}
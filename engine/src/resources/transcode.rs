@@ -0,0 +1,175 @@
+//! CPU fallback for image formats that are not supported by the GPU.
+//!
+//! Images in unsupported formats are decoded into uncompressed format with the
+//! same color space. Because decoding of large block-compressed images is slow
+//! the results are cached on disk. The cache is keyed by the GPU (vendor & device
+//! id) so the cache directory can be shared between multiple machines.
+
+use bcndecode::{BcnDecoderFormat, BcnEncoding};
+use bf::image::{Format, Image, MipMap};
+use bf::{load_bf_from_bytes, save_bf_to_bytes, Container, File};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use vulkano::device::physical::PhysicalDevice;
+
+/// Lazily configured on-disk cache of transcoded images.
+static TRANSCODE_CACHE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Errors that may happen while transcoding an image.
+#[derive(Debug)]
+pub enum TranscodeError {
+    /// There is no fallback format (or decoder) for this format.
+    NoFallback(Format),
+    /// The compressed data couldn't be decoded.
+    CannotDecode(bcndecode::Error),
+}
+
+/// Sets the directory where transcoded images will be cached. Transcoded
+/// images are not cached if this function is never called.
+pub fn set_transcode_cache_dir(dir: PathBuf) {
+    if TRANSCODE_CACHE.set(dir).is_err() {
+        warn!("Transcode cache directory can be set only once!");
+    }
+}
+
+/// Returns the uncompressed format this format should be transcoded into when
+/// the GPU does not support it or `None` if there is no such format.
+pub fn fallback_format(format: Format) -> Option<Format> {
+    match format {
+        Format::Dxt1 | Format::Dxt3 | Format::Dxt5 | Format::BC7 | Format::BC5 | Format::Rgb8 => {
+            Some(Format::Rgba8)
+        }
+        Format::SrgbDxt1 | Format::SrgbDxt3 | Format::SrgbDxt5 | Format::SrgbBC7 => {
+            Some(Format::Srgb8A8)
+        }
+        Format::Srgb8 => Some(Format::Srgb8A8),
+        Format::Rgba8
+        | Format::Srgb8A8
+        | Format::R8
+        | Format::BC6H
+        | Format::R16
+        | Format::Rg16
+        | Format::Rgba16F
+        | Format::Rgba32F => None,
+    }
+}
+
+/// Decodes the image into its fallback format. The result is loaded from
+/// the on-disk cache if it was already transcoded on this GPU.
+pub fn transcode(image: &Image, device: PhysicalDevice) -> Result<Image, TranscodeError> {
+    let target = fallback_format(image.format).ok_or(TranscodeError::NoFallback(image.format))?;
+    let cache_path = TRANSCODE_CACHE
+        .get()
+        .map(|dir| dir.join(cache_file_name(image, target, device)));
+
+    if let Some(cached) = cache_path.as_ref().and_then(|p| load_cached(p)) {
+        return Ok(cached);
+    }
+
+    info!(
+        "Transcoding {}x{} image from {:?} to {:?}...",
+        image.width, image.height, image.format, target
+    );
+
+    let mut mipmap_data = Vec::with_capacity(image.width as usize * image.height as usize * 8);
+    for mipmap in image.mipmaps() {
+        // mip-maps smaller than one block can't be decoded, the chain is
+        // shorter in that case
+        if image.format.compressed() && (mipmap.width < 4 || mipmap.height < 4) {
+            break;
+        }
+        mipmap_data.extend(decode_rgba(image.format, &mipmap)?);
+    }
+
+    let transcoded = Image {
+        format: target,
+        width: image.width,
+        height: image.height,
+        mipmap_data: mipmap_data.into(),
+    };
+
+    Ok(match cache_path {
+        Some(path) => store_cached(&path, transcoded),
+        None => transcoded,
+    })
+}
+
+/// Decodes single mip-map into RGBA8 pixels.
+fn decode_rgba(format: Format, mipmap: &MipMap) -> Result<Vec<u8>, TranscodeError> {
+    let encoding = match format {
+        Format::Rgb8 | Format::Srgb8 => {
+            return Ok(mipmap
+                .data
+                .chunks_exact(3)
+                .flat_map(|px| [px[0], px[1], px[2], 255].to_vec())
+                .collect())
+        }
+        Format::Dxt1 | Format::SrgbDxt1 => BcnEncoding::Bc1,
+        Format::Dxt3 | Format::SrgbDxt3 => BcnEncoding::Bc2,
+        Format::Dxt5 | Format::SrgbDxt5 => BcnEncoding::Bc3,
+        Format::BC7 | Format::SrgbBC7 => BcnEncoding::Bc7,
+        Format::BC5 => BcnEncoding::Bc5,
+        _ => return Err(TranscodeError::NoFallback(format)),
+    };
+
+    bcndecode::decode(
+        mipmap.data,
+        mipmap.width,
+        mipmap.height,
+        encoding,
+        BcnDecoderFormat::RGBA,
+    )
+    .map_err(TranscodeError::CannotDecode)
+}
+
+/// Computes the cache file name for specified image. The name contains the
+/// identification of the GPU, target format and hash of the image data.
+fn cache_file_name(image: &Image, target: Format, device: PhysicalDevice) -> String {
+    let props = device.properties();
+    let mut hasher = DefaultHasher::new();
+    image.width.hash(&mut hasher);
+    image.height.hash(&mut hasher);
+    image.mipmap_data.hash(&mut hasher);
+
+    format!(
+        "{:04x}-{:04x}-{:?}-{:016x}.bf",
+        props.vendor_id,
+        props.device_id,
+        target,
+        hasher.finish()
+    )
+}
+
+fn load_cached(path: &PathBuf) -> Option<Image> {
+    let bytes = std::fs::read(path).ok()?;
+    match load_bf_from_bytes(&bytes).ok()?.try_to_image() {
+        Ok(t) => Some(t),
+        Err(_) => {
+            warn!("Transcode cache file {:?} is not an image!", path);
+            None
+        }
+    }
+}
+
+/// Stores the image in the cache and returns it back. Failure to write the cache
+/// is not fatal.
+fn store_cached(path: &PathBuf, image: Image) -> Image {
+    let file = File::create_compressed(Container::Image(image));
+    let write = save_bf_to_bytes(&file)
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|bytes| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(path, bytes).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = write {
+        warn!("Cannot write transcode cache file {:?}: {}", path, e);
+    }
+
+    file.try_to_image().unwrap()
+}
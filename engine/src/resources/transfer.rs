@@ -0,0 +1,149 @@
+//! Scheduling of uploads on the transfer queue.
+//!
+//! Functions that create GPU resources record the copy of the data from a
+//! staging buffer and return a future of the copy that is not submitted yet.
+//! Instead of flushing (and waiting for) every such future when the resource is
+//! created, the future can be handed to the [`UploadScheduler`]. Once per frame
+//! the scheduler submits the oldest pending uploads that fit into the budget of
+//! bytes per frame as one batch on the transfer queue. The batch signals a
+//! semaphore that is waited on by the next rendered frame, which is the first
+//! frame that can use the uploaded resources. Later frames don't wait for it.
+
+use log::error;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use vulkano::device::Queue;
+use vulkano::sync::GpuFuture;
+
+/// Default number of bytes uploaded per frame.
+pub const DEFAULT_UPLOAD_BUDGET: usize = 16 * 1024 * 1024;
+
+type UploadFuture = Box<dyn GpuFuture + Send>;
+
+#[derive(Default)]
+struct State {
+    /// Futures of uploads that were not submitted yet with their sizes.
+    pending: VecDeque<(usize, UploadFuture)>,
+    /// Semaphores of batches submitted since the last frame was rendered.
+    submitted: Option<UploadFuture>,
+}
+
+/// Batches uploads of resources onto the transfer queue with limited number of
+/// bytes uploaded per frame.
+pub struct UploadScheduler {
+    queue: Arc<Queue>,
+    budget: AtomicUsize,
+    state: Mutex<State>,
+}
+
+impl UploadScheduler {
+    /// Creates a scheduler that submits uploads to specified queue.
+    pub fn new(queue: Arc<Queue>) -> Self {
+        Self {
+            queue,
+            budget: AtomicUsize::new(DEFAULT_UPLOAD_BUDGET),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the queue the uploads should be recorded for.
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    /// Sets the maximum number of bytes submitted in one frame.
+    pub fn set_budget(&self, bytes: usize) {
+        self.budget.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Schedules the upload represented by the `future` (that was not flushed
+    /// yet). The `bytes` is the amount of uploaded data counted to the budget.
+    pub fn schedule<F>(&self, future: F, bytes: usize)
+    where
+        F: GpuFuture + Send + 'static,
+    {
+        self.state
+            .lock()
+            .pending
+            .push_back((bytes, Box::new(future)));
+    }
+
+    /// Returns the number of uploads waiting for submission.
+    pub fn pending(&self) -> usize {
+        self.state.lock().pending.len()
+    }
+
+    /// Submits the oldest pending uploads that fit into the budget as one batch.
+    /// Called by the engine once per frame.
+    pub fn submit(&self) {
+        let mut state = self.state.lock();
+        let budget = self.budget.load(Ordering::Relaxed);
+        let count = batch_len(state.pending.iter().map(|x| x.0), budget);
+
+        let batch = state
+            .pending
+            .drain(..count)
+            .map(|x| x.1)
+            .reduce(|a, b| a.join(b).boxed_send());
+
+        let batch = match batch {
+            None => return,
+            Some(t) => t,
+        };
+
+        match batch.then_signal_semaphore_and_flush() {
+            Ok(f) => {
+                state.submitted = Some(match state.submitted.take() {
+                    None => f.boxed_send(),
+                    Some(previous) => previous.join(f).boxed_send(),
+                })
+            }
+            Err(e) => error!("Cannot submit uploads {:?}", e),
+        }
+    }
+
+    /// Returns the future of batches submitted since the last call. The render
+    /// loop joins it with the future of the next frame, so the frame waits for
+    /// the uploads on the GPU.
+    pub fn take_submitted(&self) -> Option<UploadFuture> {
+        self.state.lock().submitted.take()
+    }
+}
+
+/// Returns the number of the oldest uploads with specified sizes that fit into
+/// the `budget`. At least one upload is taken, so uploads bigger than the
+/// budget are not postponed forever.
+fn batch_len<I: IntoIterator<Item = usize>>(sizes: I, budget: usize) -> usize {
+    let mut total = 0;
+    let mut count = 0;
+
+    for size in sizes {
+        total += size;
+        if count > 0 && total > budget {
+            break;
+        }
+        count += 1;
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::transfer::batch_len;
+
+    #[test]
+    fn batches_fit_into_budget() {
+        assert_eq!(batch_len(vec![], 100), 0);
+        assert_eq!(batch_len(vec![40, 60, 1], 100), 2);
+        assert_eq!(batch_len(vec![0, 0, 100, 0], 100), 4);
+        assert_eq!(batch_len(vec![10, 100], 100), 1);
+    }
+
+    #[test]
+    fn takes_upload_bigger_than_budget() {
+        assert_eq!(batch_len(vec![500, 1], 100), 1);
+    }
+}
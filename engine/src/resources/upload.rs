@@ -0,0 +1,117 @@
+//! Selection of the way buffer data is uploaded to the GPU.
+//!
+//! On discrete GPUs buffers live in device-local memory the CPU can't write
+//! to, so the data is written into a staging buffer and copied on the GPU.
+//! Integrated (UMA) GPUs have only one memory that is both device-local and
+//! host-visible, so the copy is pure overhead and buffers are written by the
+//! CPU directly.
+//!
+//! Images are always uploaded through a staging buffer, because optimal tiling
+//! (required for block-compressed formats and mip-maps) can't be written by
+//! the CPU.
+
+use log::info;
+use vulkano::device::physical::PhysicalDevice;
+
+/// Smallest host-visible device-local heap buffers are written to directly.
+/// Discrete GPUs without resizable BAR expose only a 256 MiB window into the
+/// video memory which is too small to hold all the meshes.
+const MIN_DIRECT_HEAP_SIZE: u64 = 256 * 1024 * 1024;
+
+/// How data of buffers is uploaded to the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UploadPath {
+    /// Written to a host-visible staging buffer and copied into device-local
+    /// buffer by a transfer command.
+    Staging,
+    /// Written directly into a buffer in host-visible device-local memory.
+    Direct,
+}
+
+/// Properties of one memory type of the physical device.
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryTypeInfo {
+    pub device_local: bool,
+    pub host_visible: bool,
+    /// Size of the heap the memory type allocates from.
+    pub heap_size: u64,
+}
+
+/// Selects the upload path from memory types of the device. Buffers are
+/// written directly when all host-visible memory is device-local and its
+/// heaps are large enough.
+///
+/// Resizable BAR GPUs also have host-visible device-local memory, but they
+/// report host-visible system memory too and CPU-accessible buffers can't
+/// be told which of them to prefer, so they keep using the staging path.
+pub fn select_upload_path<I>(memory_types: I) -> UploadPath
+where
+    I: IntoIterator<Item = MemoryTypeInfo>,
+{
+    let mut direct = false;
+
+    for t in memory_types.into_iter().filter(|x| x.host_visible) {
+        if !t.device_local || t.heap_size <= MIN_DIRECT_HEAP_SIZE {
+            return UploadPath::Staging;
+        }
+        direct = true;
+    }
+
+    if direct {
+        UploadPath::Direct
+    } else {
+        UploadPath::Staging
+    }
+}
+
+/// Returns the upload path that should be used for buffers on the `physical` device.
+pub fn upload_path(physical: PhysicalDevice) -> UploadPath {
+    let path = select_upload_path(physical.memory_types().map(|t| MemoryTypeInfo {
+        device_local: t.is_device_local(),
+        host_visible: t.is_host_visible(),
+        heap_size: t.heap().size() as u64,
+    }));
+
+    static LOGGED: std::sync::Once = std::sync::Once::new();
+    LOGGED.call_once(|| info!("Using {:?} upload path for buffers", path));
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::upload::{select_upload_path, MemoryTypeInfo, UploadPath};
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn memory(device_local: bool, host_visible: bool, heap_size: u64) -> MemoryTypeInfo {
+        MemoryTypeInfo {
+            device_local,
+            host_visible,
+            heap_size,
+        }
+    }
+
+    #[test]
+    fn integrated_gpu_uses_direct_path() {
+        let uma = vec![memory(true, false, 8 * GIB), memory(true, true, 8 * GIB)];
+        assert_eq!(select_upload_path(uma), UploadPath::Direct);
+    }
+
+    #[test]
+    fn discrete_gpu_uses_staging() {
+        let dgpu = vec![
+            memory(true, false, 8 * GIB),
+            memory(false, true, 16 * GIB),
+            memory(true, true, 256 * 1024 * 1024),
+        ];
+        assert_eq!(select_upload_path(dgpu), UploadPath::Staging);
+
+        let small_bar = vec![
+            memory(true, false, 8 * GIB),
+            memory(true, true, 256 * 1024 * 1024),
+        ];
+        assert_eq!(select_upload_path(small_bar), UploadPath::Staging);
+        assert_eq!(select_upload_path(vec![]), UploadPath::Staging);
+    }
+}
@@ -0,0 +1,472 @@
+//! Startup self-test that verifies the renderer can run on the current machine.
+//!
+//! The self-test creates the device, all pipelines (including every FXAA preset,
+//! which are separate specializations of the shader) and render targets, uploads
+//! a small mesh, texture and material and renders a few frames into a hidden
+//! window. Each step is timed and any error (or panic) of a step is reported
+//! instead of aborting the application, so the report can be collected from
+//! machines of users and CI agents.
+//!
+//! The headless variant ([`run_headless_self_test`](fn.run_headless_self_test.html))
+//! creates no window, renders into an offscreen image and saves the last frame,
+//! so it also runs on machines without a display.
+
+use crate::camera::{ActiveCamera, OrthographicCamera, PerspectiveCamera};
+use crate::render::fxaa::{FxaaQuality, FXAA};
+use crate::render::headless::HeadlessRenderer;
+use crate::render::hierarchy::Hierarchy;
+use crate::render::object::Object;
+use crate::render::objects::Objects;
+use crate::render::pbr::PBRDeffered;
+use crate::render::renderer::RendererState;
+use crate::render::transform::Transform;
+use crate::render::ubo::{AmbientLight, DirectionalLight, MaterialData, Wind};
+use crate::render::vertex::NormalMappedVertex;
+use crate::render::vulkan::{HeadlessVulkanState, VulkanState};
+use crate::resources::image::create_image;
+use crate::resources::material::{create_default_fallback_maps, StaticMaterial};
+use crate::resources::mesh::create_mesh_dynamic;
+use crate::{GameState, RendererConfiguration};
+use bf::material::BlendMode;
+use bf::mesh::{IndexType, Mesh, MeshEncoding, VertexFormat};
+use cgmath::{vec3, Deg, Point3};
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::sync::GpuFuture;
+use winit::event_loop::EventLoop;
+
+/// Maximal number of additional frames rendered while waiting for the
+/// captured frame to be copied to the CPU.
+const MAX_EXTRA_FRAMES: usize = 60;
+
+/// Result of one step of the self-test.
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub duration: Duration,
+    /// Description of the result or the error that happened.
+    pub result: Result<String, String>,
+}
+
+/// Results of all steps that were run. Steps after the first failed step
+/// are not run, because they depend on it.
+#[derive(Default)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// Returns whether all steps succeeded.
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|x| x.result.is_ok())
+    }
+
+    /// Runs the step `f` unless an earlier step failed. Panics of the step
+    /// are reported as errors.
+    fn step<T>(
+        &mut self,
+        name: &'static str,
+        f: impl FnOnce() -> Result<(T, String), String>,
+    ) -> Option<T> {
+        if !self.passed() {
+            return None;
+        }
+
+        let start = Instant::now();
+        let result = match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(t) => t,
+            Err(e) => Err(panic_message(e.as_ref())),
+        };
+        let duration = start.elapsed();
+
+        let (value, result) = match result {
+            Ok((value, text)) => (Some(value), Ok(text)),
+            Err(e) => (None, Err(e)),
+        };
+        self.steps.push(SelfTestStep {
+            name,
+            duration,
+            result,
+        });
+        value
+    }
+}
+
+impl Display for SelfTestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = if self.passed() { "PASSED" } else { "FAILED" };
+        writeln!(f, "Self-test {}", status)?;
+        for step in self.steps.iter() {
+            let (status, text) = match &step.result {
+                Ok(t) => ("ok", t),
+                Err(e) => ("FAILED", e),
+            };
+            writeln!(
+                f,
+                "  {:<20} {:>6} {:>9.1} ms  {}",
+                step.name,
+                status,
+                step.duration.as_secs_f64() * 1000.0,
+                text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        format!("panicked: {}", s)
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        format!("panicked: {}", s)
+    } else {
+        "panicked".to_string()
+    }
+}
+
+/// Returns a state with a camera looking at the origin lit by one light.
+fn game_state(conf: &RendererConfiguration) -> GameState {
+    let aspect_ratio = conf.resolution[0] as f32 / conf.resolution[1] as f32;
+    GameState {
+        start: Instant::now(),
+        camera: PerspectiveCamera {
+            position: Point3::new(0.0, 0.0, -2.0),
+            forward: vec3(0.0, 0.0, 1.0),
+            up: vec3(0.0, -1.0, 0.0),
+            fov: Deg(90.0).into(),
+            aspect_ratio,
+            near: 0.05,
+            far: 100.0,
+        },
+        camera_rig: None,
+        orthographic_camera: OrthographicCamera {
+            position: Point3::new(0.0, 0.0, -2.0),
+            forward: vec3(0.0, 0.0, 1.0),
+            up: vec3(0.0, -1.0, 0.0),
+            height: 4.0,
+            aspect_ratio,
+            near: 0.05,
+            far: 100.0,
+        },
+        active_camera: ActiveCamera::Perspective,
+        objects: Objects::default(),
+        directional_lights: vec![DirectionalLight {
+            direction: vec3(0.0, 1.0, -1.0),
+            intensity: 2.5,
+            color: vec3(1.0, 1.0, 1.0),
+        }],
+        spot_lights: vec![],
+        decals: vec![],
+        ambient_light: AmbientLight::default(),
+        wind: Wind::default(),
+        hierarchy: Hierarchy::default(),
+        origin: vec3(0.0, 0.0, 0.0),
+    }
+}
+
+/// Returns a unit quad facing the camera of [`game_state`](fn.game_state.html).
+fn quad_mesh() -> Mesh {
+    let vertex = |x: f32, y: f32| NormalMappedVertex {
+        position: [x, y, 0.0],
+        normal: [0.0, 0.0, -1.0],
+        uv: [x + 0.5, y + 0.5],
+        tangent: [1.0, 0.0, 0.0, 1.0],
+    };
+    let vertices = [
+        vertex(-0.5, -0.5),
+        vertex(0.5, -0.5),
+        vertex(0.5, 0.5),
+        vertex(-0.5, 0.5),
+    ];
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    let vertex_data = vertices
+        .iter()
+        .flat_map(|v| {
+            v.position
+                .iter()
+                .chain(v.normal.iter())
+                .chain(v.uv.iter())
+                .chain(v.tangent.iter())
+                .flat_map(|x| x.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>()
+        })
+        .collect::<Vec<u8>>();
+    let index_data = indices
+        .iter()
+        .flat_map(|x| x.to_le_bytes().to_vec())
+        .collect::<Vec<u8>>();
+
+    Mesh {
+        vertex_format: VertexFormat::PositionNormalUvTangent,
+        vertex_data: vertex_data.into(),
+        index_type: IndexType::U16,
+        index_data: index_data.into(),
+        lods: vec![],
+        encoding: MeshEncoding::None,
+        stiffness: None,
+    }
+}
+
+/// Returns a 8x8 checkerboard texture without mip-maps.
+fn checker_image() -> bf::image::Image {
+    let pixels = (0..64)
+        .flat_map(|i| {
+            let c = if (i % 8 + i / 8) % 2 == 0 { 255 } else { 64 };
+            vec![c, c, c, 255]
+        })
+        .collect::<Vec<u8>>();
+
+    bf::image::Image {
+        format: bf::image::Format::Rgba8,
+        width: 8,
+        height: 8,
+        mipmap_data: pixels.into(),
+    }
+}
+
+/// Creates every FXAA preset (separate specializations of the shader) and
+/// restores the preset from `conf`.
+fn create_permutations(
+    path: &mut PBRDeffered,
+    conf: &RendererConfiguration,
+) -> Result<((), String), String> {
+    let fxaa = path
+        .post
+        .effect_mut::<FXAA>()
+        .ok_or("fxaa is not in the post-processing chain")?;
+    let presets = [
+        FxaaQuality::Off,
+        FxaaQuality::Low,
+        FxaaQuality::Medium,
+        FxaaQuality::High,
+    ];
+    for quality in presets.iter() {
+        fxaa.set_quality(*quality);
+    }
+    fxaa.set_quality(conf.fxaa_quality);
+    Ok(((), format!("{} fxaa presets", presets.len())))
+}
+
+/// Uploads the quad with a checkerboard texture and a material, adds it to the
+/// `state` and returns the future of the uploads.
+fn upload_scene(
+    transfer_queue: Arc<Queue>,
+    graphical_queue: Arc<Queue>,
+    path: &PBRDeffered,
+    state: &mut GameState,
+) -> Result<(Box<dyn GpuFuture + Send>, String), String> {
+    let device = transfer_queue.device().clone();
+    let (mesh, f1) =
+        create_mesh_dynamic::<NormalMappedVertex>(&quad_mesh(), transfer_queue.clone())
+            .map_err(|e| format!("cannot create mesh: {:?}", e))?;
+    let (texture, f2) = create_image(&checker_image(), graphical_queue)
+        .map_err(|e| format!("cannot create texture: {:?}", e))?;
+    ImageView::new(texture).map_err(|e| format!("cannot create texture view: {:?}", e))?;
+    let (fallback_maps, f3) = create_default_fallback_maps(transfer_queue.clone());
+    let (material, f4) = StaticMaterial::from_material_data(
+        BlendMode::Opaque,
+        MaterialData {
+            albedo_color: [1.0; 3],
+            alpha_cutoff: 0.0,
+            roughness: 0.5,
+            metallic: 0.0,
+            opacity: 1.0,
+            ior: 1.0,
+            emissive_color: [0.0; 3],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: 0,
+        },
+        path.buffers.geometry_pipeline.clone(),
+        path.samplers.aniso_repeat.clone(),
+        transfer_queue,
+        fallback_maps,
+    )
+    .map_err(|e| format!("cannot create material: {:?}", e))?;
+
+    state.objects.insert(Object::new(
+        mesh,
+        material,
+        device,
+        path.buffers.geometry_pipeline.clone(),
+        Transform::default(),
+    ));
+
+    let future = f1.join(f2).join(f3).join(f4).boxed_send();
+    Ok((future, "mesh, texture and material".to_string()))
+}
+
+/// Runs the self-test with `conf` (the render targets are created in its
+/// resolution) and renders `frames` frames.
+pub fn run_self_test(
+    conf: &RendererConfiguration,
+    event_loop: &EventLoop<()>,
+    frames: usize,
+) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    let vulkan = report.step("device", || {
+        let vulkan = VulkanState::new_hidden(conf, event_loop).map_err(|e| format!("{:?}", e))?;
+        let props = vulkan.device().physical_device().properties();
+        let text = format!(
+            "{} ({:?}, Vulkan {:?})",
+            props.device_name, props.device_type, props.api_version
+        );
+        Ok((vulkan, text))
+    });
+
+    let mut renderer = vulkan.as_ref().and_then(|vulkan| {
+        report.step("pipelines", || {
+            let renderer = RendererState::new(vulkan, conf).map_err(|e| format!("{:?}", e))?;
+            Ok((renderer, "render path created".to_string()))
+        })
+    });
+
+    if let Some(renderer) = renderer.as_mut() {
+        report.step("shader permutations", || {
+            create_permutations(&mut renderer.render_path, conf)
+        });
+
+        report.step("render targets", || {
+            let [width, height] = conf.resolution;
+            renderer
+                .render_path
+                .dimensions_changed([width as u32, height as u32]);
+            Ok(((), format!("{}x{}", width, height)))
+        });
+    }
+
+    let mut state = game_state(conf);
+    let uploads = match (vulkan.as_ref(), renderer.as_ref()) {
+        (Some(vulkan), Some(renderer)) => report.step("uploads", || {
+            upload_scene(
+                vulkan.transfer_queue(),
+                vulkan.graphical_queue(),
+                &renderer.render_path,
+                &mut state,
+            )
+        }),
+        _ => None,
+    };
+
+    if let (Some(renderer), Some(uploads)) = (renderer.as_mut(), uploads) {
+        report.step("frames", || {
+            state.update_transforms();
+            state.update_lods();
+            let screenshot = renderer.request_screenshot();
+
+            let start = Instant::now();
+            let mut uploads = Some(uploads);
+            for _ in 0..frames {
+                renderer.render_frame(&state, uploads.take());
+            }
+            let frame_time = start.elapsed() / frames.max(1) as u32;
+
+            // the first frame is copied to the cpu once it is finished
+            let mut extra = 0;
+            let image = loop {
+                if let Ok(image) = screenshot.try_recv() {
+                    break image;
+                }
+                if extra == MAX_EXTRA_FRAMES {
+                    return Err("rendered frames never finished".to_string());
+                }
+                renderer.render_frame(&state, None);
+                extra += 1;
+            };
+
+            Ok((
+                (),
+                format!(
+                    "{} frames, {:.2} ms average, captured {}x{}",
+                    frames,
+                    frame_time.as_secs_f64() * 1000.0,
+                    image.width(),
+                    image.height()
+                ),
+            ))
+        });
+    }
+
+    report
+}
+
+/// Runs the self-test without a window (on the GPU `conf.gpu`), renders
+/// `conf.headless_frames` frames into an offscreen image with the resolution
+/// of `conf` and saves the last one to `conf.headless_output`.
+pub fn run_headless_self_test(conf: &RendererConfiguration) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    let vulkan = report.step("device", || {
+        let vulkan = HeadlessVulkanState::new(conf.gpu).map_err(|e| format!("{:?}", e))?;
+        let props = vulkan.device().physical_device().properties();
+        let text = format!(
+            "{} ({:?}, Vulkan {:?}, headless)",
+            props.device_name, props.device_type, props.api_version
+        );
+        Ok((vulkan, text))
+    });
+
+    let mut renderer = vulkan.as_ref().and_then(|vulkan| {
+        report.step("pipelines", || {
+            let renderer = HeadlessRenderer::new(vulkan, conf)?;
+            let [width, height] = renderer.dimensions();
+            Ok((
+                renderer,
+                format!("render path created ({}x{})", width, height),
+            ))
+        })
+    });
+
+    if let Some(renderer) = renderer.as_mut() {
+        report.step("shader permutations", || {
+            create_permutations(&mut renderer.render_path, conf)
+        });
+    }
+
+    let mut state = game_state(conf);
+    let uploads = match (vulkan.as_ref(), renderer.as_ref()) {
+        (Some(vulkan), Some(renderer)) => report.step("uploads", || {
+            upload_scene(
+                vulkan.transfer_queue(),
+                vulkan.graphical_queue(),
+                &renderer.render_path,
+                &mut state,
+            )
+        }),
+        _ => None,
+    };
+
+    if let (Some(renderer), Some(uploads)) = (renderer.as_mut(), uploads) {
+        report.step("frames", || {
+            state.update_transforms();
+            state.update_lods();
+
+            let frames = conf.headless_frames.max(1);
+            let start = Instant::now();
+            renderer.render_frames(&state, Some(uploads), frames)?;
+            let frame_time = start.elapsed() / frames as u32;
+
+            Ok((
+                (),
+                format!(
+                    "{} frames, {:.2} ms average",
+                    frames,
+                    frame_time.as_secs_f64() * 1000.0
+                ),
+            ))
+        });
+
+        report.step("output", || {
+            renderer.save(&conf.headless_output)?;
+            Ok(((), format!("saved to {:?}", conf.headless_output)))
+        });
+    }
+
+    report
+}
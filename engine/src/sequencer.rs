@@ -0,0 +1,300 @@
+//! Playback of keyframed sequences (camera paths and object visibility) used
+//! for benchmark flythroughs, trailers and scripted cutscenes.
+
+use crate::GameState;
+use bf::sequence::{Key, Sequence};
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
+use std::time::Duration;
+
+/// Plays a [`Sequence`](../../bf/sequence/struct.Sequence.html) and applies
+/// its state to the camera and objects of the `GameState`.
+///
+/// Positions in the sequence are world-space positions, so the playback is
+/// not affected by moving the floating origin.
+pub struct Sequencer {
+    sequence: Sequence,
+    time: f32,
+    speed: f32,
+    playing: bool,
+    looping: bool,
+}
+
+impl Sequencer {
+    /// Creates a new paused sequencer at the start of the `sequence`.
+    pub fn new(sequence: Sequence) -> Self {
+        Self {
+            sequence,
+            time: 0.0,
+            speed: 1.0,
+            playing: false,
+            looping: false,
+        }
+    }
+
+    /// Starts (or resumes) the playback. Playback of a finished sequence
+    /// starts from the beginning.
+    pub fn play(&mut self) {
+        if self.time >= self.duration() {
+            self.time = 0.0;
+        }
+        self.playing = true;
+    }
+
+    /// Pauses the playback at current time.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Stops the playback and rewinds to the start.
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.time = 0.0;
+    }
+
+    /// Moves the playback to specified time (in seconds).
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.0).min(self.duration());
+    }
+
+    /// Sets the playback speed multiplier (`1.0` is real-time).
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    /// Sets whether the playback should continue from the start after the end.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns current time of the playback in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Returns the duration of the played sequence in seconds.
+    pub fn duration(&self) -> f32 {
+        self.sequence.duration()
+    }
+
+    /// Advances the playback by `dt` if it is playing. Playback that reaches
+    /// the end of the sequence is paused at the end unless it is looping.
+    pub fn advance(&mut self, dt: Duration) {
+        if !self.playing {
+            return;
+        }
+
+        let duration = self.duration();
+        self.time += dt.as_secs_f32() * self.speed;
+
+        if self.time >= duration {
+            if self.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+    }
+
+    /// Returns the position of the camera at current time. Positions between
+    /// keys are interpolated by Catmull-Rom spline, so the camera moves smoothly
+    /// through all keys.
+    pub fn camera_position(&self) -> Option<Point3<f32>> {
+        let keys = &self.sequence.position.keys;
+        let (i, t) = segment(keys, self.time)?;
+        let p = |idx: usize| Vector3::from(keys[idx].value);
+        let last = keys.len() - 1;
+
+        // missing neighbours of the first and last key are extrapolated
+        let (p1, p2) = (p(i), p((i + 1).min(last)));
+        let p0 = if i > 0 { p(i - 1) } else { p1 * 2.0 - p2 };
+        let p3 = if i + 2 <= last {
+            p(i + 2)
+        } else {
+            p2 * 2.0 - p1
+        };
+        let (t2, t3) = (t * t, t * t * t);
+
+        let v = (p1 * 2.0
+            + (p2 - p0) * t
+            + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+            + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+            * 0.5;
+
+        Some(Point3::new(v.x, v.y, v.z))
+    }
+
+    /// Returns the direction the camera looks in at current time.
+    pub fn camera_forward(&self) -> Option<Vector3<f32>> {
+        let keys = &self.sequence.forward.keys;
+        let (i, t) = segment(keys, self.time)?;
+        let a = Vector3::from(keys[i].value).normalize();
+        let b = Vector3::from(keys[(i + 1).min(keys.len() - 1)].value).normalize();
+
+        // keys are expected to be close enough to use normalized lerp instead of slerp
+        let forward = a + (b - a) * t;
+        if forward.magnitude2() < f32::EPSILON {
+            return Some(a);
+        }
+        Some(forward.normalize())
+    }
+
+    /// Returns the vertical field of view of the camera at current time.
+    pub fn camera_fov(&self) -> Option<Rad<f32>> {
+        let keys = &self.sequence.fov.keys;
+        let (i, t) = segment(keys, self.time)?;
+        let a = keys[i].value;
+        let b = keys[(i + 1).min(keys.len() - 1)].value;
+
+        Some(Deg(a + (b - a) * t).into())
+    }
+
+    /// Returns whether the object with specified index is visible at current time
+    /// or `None` when the sequence does not control visibility of the object.
+    /// Before its first key the object has the opposite visibility.
+    pub fn is_visible(&self, object: u32) -> Option<bool> {
+        let mut keys = self
+            .sequence
+            .visibility
+            .iter()
+            .filter(|k| k.object == object)
+            .peekable();
+        let first = keys.peek()?.visible;
+
+        Some(
+            keys.take_while(|k| k.time <= self.time)
+                .last()
+                .map_or(!first, |k| k.visible),
+        )
+    }
+
+    /// Applies the state at current time to the camera and objects.
+    pub fn apply(&self, state: &mut GameState) {
+        if let Some(position) = self.camera_position() {
+            state.camera.position = position - state.origin.cast::<f32>().unwrap();
+        }
+        if let Some(forward) = self.camera_forward() {
+            state.camera.forward = forward;
+        }
+        if let Some(fov) = self.camera_fov() {
+            state.camera.fov = fov;
+        }
+        for (idx, object) in state.objects.iter_mut().enumerate() {
+            if let Some(visible) = self.is_visible(idx as u32) {
+                object.visible = visible;
+            }
+        }
+    }
+}
+
+/// Finds the index of the key that starts the segment containing `time` and
+/// the normalized position within the segment. Time outside of the keys is
+/// clamped to the first or last key.
+fn segment<T>(keys: &[Key<T>], time: f32) -> Option<(usize, f32)> {
+    let last = keys.len().checked_sub(1)?;
+    let next = keys.partition_point(|k| k.time <= time);
+
+    if next == 0 {
+        return Some((0, 0.0));
+    }
+    if next > last {
+        return Some((last, 0.0));
+    }
+
+    let (a, b) = (keys[next - 1].time, keys[next].time);
+    let t = if b > a { (time - a) / (b - a) } else { 0.0 };
+    Some((next - 1, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequencer::Sequencer;
+    use bf::sequence::Sequence;
+    use cgmath::{Deg, InnerSpace, Point3, Rad};
+    use std::time::Duration;
+
+    fn sequence() -> Sequence {
+        let mut seq = Sequence::default();
+        seq.position.insert(0.0, [0.0, 0.0, 0.0]);
+        seq.position.insert(1.0, [1.0, 0.0, 0.0]);
+        seq.position.insert(2.0, [2.0, 0.0, 0.0]);
+        seq.forward.insert(0.0, [1.0, 0.0, 0.0]);
+        seq.forward.insert(2.0, [0.0, 0.0, 1.0]);
+        seq.fov.insert(0.0, 90.0);
+        seq.fov.insert(2.0, 60.0);
+        seq.set_visible(1.0, 3, true);
+        seq.set_visible(1.5, 3, false);
+        seq
+    }
+
+    fn at(time: f32) -> Sequencer {
+        let mut s = Sequencer::new(sequence());
+        s.seek(time);
+        s
+    }
+
+    fn approx(a: Point3<f32>, b: Point3<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn interpolates_camera() {
+        let position = |time| at(time).camera_position().unwrap();
+        assert!(approx(position(0.0), Point3::new(0.0, 0.0, 0.0)));
+        assert!(approx(position(0.5), Point3::new(0.5, 0.0, 0.0)));
+        assert!(approx(position(2.0), Point3::new(2.0, 0.0, 0.0)));
+
+        let forward = at(1.0).camera_forward().unwrap();
+        assert!((forward.magnitude() - 1.0).abs() < 1e-5);
+        assert!((forward.x - forward.z).abs() < 1e-5);
+
+        let fov: Rad<f32> = Deg(75.0).into();
+        assert!((at(1.0).camera_fov().unwrap().0 - fov.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn empty_tracks_do_not_control_camera() {
+        let s = Sequencer::new(Sequence::default());
+
+        assert_eq!(s.camera_position(), None);
+        assert_eq!(s.camera_forward(), None);
+        assert_eq!(s.camera_fov(), None);
+    }
+
+    #[test]
+    fn toggles_visibility() {
+        assert_eq!(at(0.5).is_visible(3), Some(false));
+        assert_eq!(at(1.2).is_visible(3), Some(true));
+        assert_eq!(at(2.0).is_visible(3), Some(false));
+        assert_eq!(at(1.2).is_visible(0), None);
+    }
+
+    #[test]
+    fn playback_controls() {
+        let mut s = Sequencer::new(sequence());
+        s.advance(Duration::from_secs(1));
+        assert_eq!(s.time(), 0.0);
+
+        s.play();
+        s.set_speed(2.0);
+        s.advance(Duration::from_millis(500));
+        assert_eq!(s.time(), 1.0);
+
+        s.advance(Duration::from_secs(1));
+        assert_eq!(s.time(), 2.0);
+        assert!(!s.is_playing());
+
+        s.set_looping(true);
+        s.play();
+        s.advance(Duration::from_millis(1250));
+        assert_eq!(s.time(), 0.5);
+
+        s.stop();
+        assert_eq!(s.time(), 0.0);
+        assert!(!s.is_playing());
+    }
+}
@@ -0,0 +1,104 @@
+//! Fixed timestep of the game update.
+//!
+//! Frames take a varying amount of time, but simulation (physics, movement)
+//! should not depend on the frame rate. [`FixedTimestep`](struct.FixedTimestep.html)
+//! accumulates the time of frames and splits it into steps of the same length.
+//! `Game::fixed_update` is called once per step, so it can be called several
+//! times in one frame or not at all. The time left in the accumulator is the
+//! fraction (`alpha`) of the next step that already elapsed, which is used to
+//! interpolate transforms between the last two steps when rendering (see
+//! `Transform::set_interpolated`).
+
+use std::time::Duration;
+
+/// Maximum number of steps in one frame. When frames take longer (eg. while
+/// loading) the remaining time is dropped and the simulation slows down
+/// instead of doing more and more steps each frame.
+pub const MAX_STEPS_PER_FRAME: u32 = 8;
+
+/// Accumulator of frame time that is consumed in steps of fixed length.
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    /// Creates a timestep with `rate` steps per second.
+    pub fn new(rate: f32) -> Self {
+        Self {
+            step: Duration::from_secs_f32(1.0 / rate),
+            accumulator: Duration::default(),
+        }
+    }
+
+    /// Returns the length of one step.
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the `elapsed` time of a frame and returns the number of steps
+    /// that should be done in this frame.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        if steps > MAX_STEPS_PER_FRAME {
+            self.accumulator = Duration::default();
+            steps = MAX_STEPS_PER_FRAME;
+        }
+        steps
+    }
+
+    /// Returns the fraction (0 to 1) of the next step that already elapsed.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timestep::{FixedTimestep, MAX_STEPS_PER_FRAME};
+    use std::time::Duration;
+
+    #[test]
+    fn steps_do_not_depend_on_frame_rate() {
+        let mut fast = FixedTimestep::new(50.0);
+        let mut slow = FixedTimestep::new(50.0);
+
+        let fast_steps: u32 = (0..12)
+            .map(|_| fast.advance(Duration::from_millis(5)))
+            .sum();
+        let slow_steps: u32 = (0..2)
+            .map(|_| slow.advance(Duration::from_millis(30)))
+            .sum();
+
+        assert_eq!(fast_steps, 3);
+        assert_eq!(slow_steps, 3);
+    }
+
+    #[test]
+    fn alpha_is_elapsed_fraction_of_next_step() {
+        let mut timestep = FixedTimestep::new(10.0);
+        assert_eq!(timestep.alpha(), 0.0);
+
+        assert_eq!(timestep.advance(Duration::from_millis(125)), 1);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut timestep = FixedTimestep::new(60.0);
+
+        assert_eq!(
+            timestep.advance(Duration::from_secs(5)),
+            MAX_STEPS_PER_FRAME
+        );
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+}
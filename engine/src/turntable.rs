@@ -0,0 +1,253 @@
+//! Material review mode. While it is enabled, lighting of the scene is replaced
+//! by a standardized three-point rig (key, fill and rim light) positioned relative
+//! to the camera and a neutral ambient light, so materials look the same in every
+//! scene and from every side. The selected object can spin around the vertical
+//! axis like on a turntable.
+//!
+//! The engine has no image based lighting, so the neutral environment is the
+//! hemispherical ambient light with the same color of the sky and the ground.
+
+use crate::camera::Camera;
+use crate::render::objects::ObjectId;
+use crate::render::ubo::{AmbientLight, DirectionalLight, SpotLight};
+use crate::GameState;
+use cgmath::{Deg, InnerSpace, Matrix4, Quaternion, Rotation3, Vector3};
+use core::color::luminance;
+
+/// Light of the rig positioned relative to the camera.
+#[derive(Copy, Clone, Debug)]
+struct RigLight {
+    /// Angle around the vertical axis, zero is behind the camera (the light
+    /// shines in the view direction) and positive angles are to the right.
+    azimuth: f32,
+    /// Angle above the horizon.
+    elevation: f32,
+    /// Share of the total luminance of the rig.
+    weight: f32,
+    color: [f32; 3],
+}
+
+/// Warm key light from the front right, cool fill light from the left and
+/// a rim light from behind that separates the silhouette from the background.
+const RIG: [RigLight; 3] = [
+    RigLight {
+        azimuth: 45.0,
+        elevation: 35.0,
+        weight: 0.6,
+        color: [1.0, 0.95, 0.88],
+    },
+    RigLight {
+        azimuth: -60.0,
+        elevation: 10.0,
+        weight: 0.25,
+        color: [0.88, 0.94, 1.0],
+    },
+    RigLight {
+        azimuth: 160.0,
+        elevation: 30.0,
+        weight: 0.15,
+        color: [1.0, 1.0, 1.0],
+    },
+];
+
+/// Returns the lights of the rig for the camera with `view` matrix. The lights
+/// turn with the camera around the vertical axis, but their elevation is above
+/// the horizon (not the camera), so the object is always lit from above.
+///
+/// Intensities are normalized by luminance of the light colors, so the luminances
+/// of the lights add up to `total_luminance` and the tints don't change the exposure.
+pub fn rig_lights(view: Matrix4<f32>, total_luminance: f32) -> Vec<DirectionalLight> {
+    // rows of the rotation part of the view matrix are the axes of the camera
+    let camera_right = Vector3::new(view.x.x, view.y.x, view.z.x);
+    let camera_back = Vector3::new(view.x.z, view.y.z, view.z.z);
+
+    let up = Vector3::unit_y();
+    let right = Vector3::new(camera_right.x, 0.0, camera_right.z).normalize();
+    let mut back = up.cross(right);
+    if back.dot(camera_back) < 0.0 {
+        back = -back;
+    }
+
+    RIG.iter()
+        .map(|x| {
+            let (azimuth, elevation) = (x.azimuth.to_radians(), x.elevation.to_radians());
+            let horizontal = back * azimuth.cos() + right * azimuth.sin();
+            DirectionalLight {
+                direction: (horizontal * elevation.cos() + up * elevation.sin()).normalize(),
+                intensity: x.weight * total_luminance / luminance(x.color),
+                color: x.color.into(),
+            }
+        })
+        .collect()
+}
+
+/// Lighting of the scene saved while the review mode is enabled.
+struct SceneLighting {
+    directional_lights: Vec<DirectionalLight>,
+    spot_lights: Vec<SpotLight>,
+    ambient_light: AmbientLight,
+}
+
+/// State of the material review mode. Changed by `turntable.*` cvars.
+pub struct Turntable {
+    /// Sum of luminances of the lights of the rig.
+    pub luminance: f32,
+    /// Intensity of the neutral ambient light.
+    pub ambient: f32,
+    /// Object that spins while the mode is enabled. The previous object returns
+    /// to its rotation when it is changed.
+    pub object: Option<ObjectId>,
+    /// Speed of the spinning in degrees per second (zero stops it).
+    pub speed: f32,
+    /// Lighting of the scene restored when the mode is disabled, `None` while
+    /// the mode is disabled.
+    saved: Option<SceneLighting>,
+    /// Rotation the spinning object had when it started spinning.
+    initial_rotation: Option<(ObjectId, Quaternion<f32>)>,
+    angle: f32,
+}
+
+impl Default for Turntable {
+    fn default() -> Self {
+        Self {
+            luminance: 3.0,
+            ambient: 0.3,
+            object: None,
+            speed: 30.0,
+            saved: None,
+            initial_rotation: None,
+            angle: 0.0,
+        }
+    }
+}
+
+impl Turntable {
+    pub fn is_enabled(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Replaces the lighting of the scene with the rig. The lights of the scene
+    /// are restored by [`disable`](#method.disable).
+    pub fn enable(&mut self, state: &mut GameState) {
+        if self.saved.is_none() {
+            self.saved = Some(SceneLighting {
+                directional_lights: std::mem::take(&mut state.directional_lights),
+                spot_lights: std::mem::take(&mut state.spot_lights),
+                ambient_light: state.ambient_light,
+            });
+        }
+    }
+
+    /// Restores the lighting of the scene and the rotation of the spinning object.
+    pub fn disable(&mut self, state: &mut GameState) {
+        if let Some(saved) = self.saved.take() {
+            state.directional_lights = saved.directional_lights;
+            state.spot_lights = saved.spot_lights;
+            state.ambient_light = saved.ambient_light;
+        }
+        self.stop_spinning(state);
+    }
+
+    fn stop_spinning(&mut self, state: &mut GameState) {
+        if let Some((id, rotation)) = self.initial_rotation.take() {
+            if let Some(object) = state.objects.get_mut(id) {
+                object.transform.set_rotation(rotation);
+            }
+        }
+        self.angle = 0.0;
+    }
+
+    /// Updates the lights of the rig for the current camera and spins the
+    /// selected object by `delta` seconds. Lights changed by the game while the
+    /// mode is enabled are overwritten. Does nothing while the mode is disabled.
+    pub fn update(&mut self, state: &mut GameState, delta: f32) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        state.directional_lights = rig_lights(state.render_camera().view_matrix(), self.luminance);
+        state.spot_lights.clear();
+        state.ambient_light = AmbientLight {
+            sky_color: Vector3::new(1.0, 1.0, 1.0),
+            ground_color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: self.ambient,
+        };
+
+        if let Some((id, _)) = self.initial_rotation {
+            if self.object != Some(id) {
+                self.stop_spinning(state);
+            }
+        }
+        let (id, object) = match self.object {
+            Some(id) => match state.objects.get_mut(id) {
+                Some(t) => (id, t),
+                None => return,
+            },
+            None => return,
+        };
+        let initial = self
+            .initial_rotation
+            .get_or_insert((id, object.transform.rotation()))
+            .1;
+
+        self.angle = (self.angle + self.speed * delta) % 360.0;
+        object
+            .transform
+            .set_rotation(Quaternion::from_angle_y(Deg(self.angle)) * initial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::turntable::rig_lights;
+    use cgmath::{vec3, InnerSpace, Matrix4, Point3};
+    use core::color::luminance;
+
+    #[test]
+    fn rig_preserves_luminance() {
+        let view = Matrix4::look_to_rh(
+            Point3::new(0.0, 0.0, 3.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+        );
+        let lights = rig_lights(view, 3.0);
+
+        let total = lights
+            .iter()
+            .map(|x| x.intensity * luminance(x.color.into()))
+            .sum::<f32>();
+        assert!((total - 3.0).abs() < 1e-4);
+        assert!(lights
+            .iter()
+            .all(|x| (x.direction.magnitude() - 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn rig_follows_camera() {
+        // camera looking along -z with the key light from the front right top
+        let view = Matrix4::look_to_rh(
+            Point3::new(0.0, 0.0, 3.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+        );
+        let key = rig_lights(view, 1.0)[0].direction;
+        assert!(key.x > 0.0 && key.y > 0.0 && key.z > 0.0);
+
+        // camera looking along +x sees the key light from its right (+z)
+        let view = Matrix4::look_to_rh(
+            Point3::new(-3.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        );
+        let key = rig_lights(view, 1.0)[0].direction;
+        assert!(key.z > 0.0 && key.y > 0.0 && key.x < 0.0);
+
+        // upside down camera (eg. of the viewer) still has the lights above
+        let view = Matrix4::look_to_rh(
+            Point3::new(0.0, 0.0, 3.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, -1.0, 0.0),
+        );
+        assert!(rig_lights(view, 1.0).iter().all(|x| x.direction.y > 0.0));
+    }
+}
@@ -0,0 +1,59 @@
+//! Additional windows rendering their own world (eg. a material preview of an editor).
+//!
+//! Each [`Viewport`](struct.Viewport.html) has its own `GameState` and its own
+//! `RendererState` (swapchain, framebuffers and render path), while the device,
+//! queues and loaded content are shared with the main window. Viewports are
+//! opened by `Engine::open_viewport`; the window is created by the event loop
+//! before the next frame (windows can't be created without the event loop),
+//! so the renderer is `None` until then. Input is handled only in the main
+//! window. Closing the window of a viewport removes it.
+
+use crate::render::renderer::RendererState;
+use crate::GameState;
+use winit::window::WindowId;
+
+/// Handle of a viewport that stays the same while it is open.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ViewportId(pub(crate) u64);
+
+/// Window rendering the `game_state`.
+pub struct Viewport {
+    id: ViewportId,
+    title: String,
+    /// Initial size of the window in logical pixels.
+    size: [u16; 2],
+    /// World rendered in the viewport. Objects may use pipelines of the main
+    /// render path (render passes of all windows are compatible).
+    pub game_state: GameState,
+    /// Renderer of the window (`None` until the window is created).
+    pub renderer_state: Option<RendererState>,
+}
+
+impl Viewport {
+    pub(crate) fn new(id: ViewportId, title: &str, size: [u16; 2], game_state: GameState) -> Self {
+        Self {
+            id,
+            title: title.to_string(),
+            size,
+            game_state,
+            renderer_state: None,
+        }
+    }
+
+    pub fn id(&self) -> ViewportId {
+        self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn size(&self) -> [u16; 2] {
+        self.size
+    }
+
+    /// Returns the id of the window or `None` if it was not created yet.
+    pub fn window_id(&self) -> Option<WindowId> {
+        self.renderer_state.as_ref().map(|x| x.window_id())
+    }
+}
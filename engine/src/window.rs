@@ -0,0 +1,201 @@
+//! Size of the window in physical and logical pixels.
+//!
+//! The swapchain and all render targets have the physical size of the window
+//! (pixels of the display). Logical pixels are physical pixels divided by the
+//! scale factor of the display (eg. `1.5` for 4K display with 150% scaling),
+//! so UI sized in logical pixels looks the same on all displays.
+//!
+//! The window is shown in one of the [`WindowMode`](enum.WindowMode.html)s,
+//! which can be changed at run-time (see `Engine::set_window_mode`).
+
+use std::str::FromStr;
+use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{Fullscreen, Window};
+
+/// How the window is shown.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WindowMode {
+    /// Resizable window with decorations.
+    Windowed,
+    /// Window without decorations covering the whole monitor (the resolution
+    /// of the desktop is kept, so switching is fast).
+    Borderless,
+    /// Exclusive fullscreen in the video mode of the monitor closest to the
+    /// configured resolution.
+    Fullscreen,
+}
+
+impl Default for WindowMode {
+    fn default() -> Self {
+        WindowMode::Windowed
+    }
+}
+
+impl WindowMode {
+    /// All modes in the order they are cycled through.
+    pub const ALL: [WindowMode; 3] = [
+        WindowMode::Windowed,
+        WindowMode::Borderless,
+        WindowMode::Fullscreen,
+    ];
+
+    /// Returns the mode that follows this one (wraps around to `Windowed`).
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Returns the name used by the `window_mode` setting and cvar.
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Borderless => "borderless",
+            WindowMode::Fullscreen => "fullscreen",
+        }
+    }
+
+    /// Returns the fullscreen state of a window in this mode on the `monitor`
+    /// (the current one when `None`). Exclusive fullscreen uses the video mode
+    /// closest to the `resolution` (in physical pixels) and falls back to
+    /// borderless when the monitor has no video modes.
+    pub fn fullscreen(
+        self,
+        monitor: Option<MonitorHandle>,
+        resolution: [u16; 2],
+    ) -> Option<Fullscreen> {
+        match self {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Fullscreen => {
+                let modes = monitor
+                    .as_ref()
+                    .map(|x| x.video_modes().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                match closest_video_mode(&modes, resolution) {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode.clone())),
+                    None => Some(Fullscreen::Borderless(monitor)),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for WindowMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown window mode {:?} (expected windowed, borderless or fullscreen)",
+                    s
+                )
+            })
+    }
+}
+
+/// Returns the video mode with the `resolution` and the highest refresh rate
+/// and bit depth, or the largest mode when no mode has the resolution.
+fn closest_video_mode(modes: &[VideoMode], resolution: [u16; 2]) -> Option<&VideoMode> {
+    let size = PhysicalSize::new(resolution[0] as u32, resolution[1] as u32);
+    let quality = |x: &&VideoMode| (x.refresh_rate(), x.bit_depth());
+
+    modes
+        .iter()
+        .filter(|x| x.size() == size)
+        .max_by_key(quality)
+        .or_else(|| {
+            modes
+                .iter()
+                .max_by_key(|x| (x.size().width * x.size().height, quality(x)))
+        })
+}
+
+/// Physical size and scale factor of the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindowSize {
+    pub physical: PhysicalSize<u32>,
+    pub scale_factor: f64,
+}
+
+impl WindowSize {
+    pub fn new(physical: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        Self {
+            physical,
+            scale_factor,
+        }
+    }
+
+    /// Returns the current size of the `window`.
+    pub fn of(window: &Window) -> Self {
+        Self::new(window.inner_size(), window.scale_factor())
+    }
+
+    /// Returns the size of the window in logical pixels.
+    pub fn logical(&self) -> LogicalSize<f64> {
+        self.physical.to_logical(self.scale_factor)
+    }
+
+    /// Returns the aspect ratio of the window or `None` if the window is
+    /// minimized (one of its dimensions is zero).
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        if self.physical.width == 0 || self.physical.height == 0 {
+            return None;
+        }
+        Some(self.physical.width as f32 / self.physical.height as f32)
+    }
+
+    /// Converts the `position` in physical pixels (eg. of the cursor) to
+    /// logical pixels.
+    pub fn to_logical(&self, position: PhysicalPosition<f64>) -> LogicalPosition<f64> {
+        position.to_logical(self.scale_factor)
+    }
+
+    /// Converts the `position` in logical pixels to physical pixels.
+    pub fn to_physical(&self, position: LogicalPosition<f64>) -> PhysicalPosition<f64> {
+        position.to_physical(self.scale_factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::window::{WindowMode, WindowSize};
+    use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+
+    #[test]
+    fn converts_between_physical_and_logical_pixels() {
+        let size = WindowSize::new(PhysicalSize::new(3840, 2160), 1.5);
+
+        assert_eq!(size.logical(), LogicalSize::new(2560.0, 1440.0));
+        assert_eq!(
+            size.to_logical(PhysicalPosition::new(300.0, 150.0)),
+            LogicalPosition::new(200.0, 100.0)
+        );
+        assert_eq!(
+            size.to_physical(LogicalPosition::new(200.0, 100.0)),
+            PhysicalPosition::new(300.0, 150.0)
+        );
+    }
+
+    #[test]
+    fn window_modes_cycle_and_parse() {
+        for mode in WindowMode::ALL.iter() {
+            assert_eq!(mode.name().parse::<WindowMode>(), Ok(*mode));
+        }
+        assert_eq!(WindowMode::Fullscreen.next(), WindowMode::Windowed);
+        assert!("maximized".parse::<WindowMode>().is_err());
+    }
+
+    #[test]
+    fn minimized_window_has_no_aspect_ratio() {
+        let size = WindowSize::new(PhysicalSize::new(1920, 1080), 1.0);
+        assert!((size.aspect_ratio().unwrap() - 16.0 / 9.0).abs() < 1e-6);
+        assert_eq!(
+            WindowSize::new(PhysicalSize::new(0, 0), 1.0).aspect_ratio(),
+            None
+        );
+    }
+}
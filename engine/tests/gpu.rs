@@ -0,0 +1,151 @@
+//! Integration tests that need a GPU with Vulkan driver. Run them with
+//! `cargo test -p engine --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use bf::image::{Format, Image};
+use engine::render::samplers::Samplers;
+use engine::render::virtual_texture::VirtualTexture;
+use engine::render::vulkan::HeadlessVulkanState;
+use engine::resources::image::{create_image, UpdatableImage, UpdateImageError};
+use engine::resources::material::create_default_fallback_maps;
+use engine::resources::mesh::{create_full_screen_triangle, create_icosphere};
+use std::sync::Arc;
+use vulkano::sync::GpuFuture;
+
+fn vulkan() -> HeadlessVulkanState {
+    HeadlessVulkanState::new(0).expect("cannot create headless vulkan state")
+}
+
+/// Waits until the operation represented by `future` is finished.
+fn wait(future: impl GpuFuture) {
+    future
+        .then_signal_fence_and_flush()
+        .expect("cannot flush")
+        .wait(None)
+        .expect("cannot wait for fence");
+}
+
+#[test]
+fn creates_samplers() {
+    Samplers::new(vulkan().device()).expect("cannot create samplers");
+}
+
+#[test]
+fn uploads_meshes() {
+    let vulkan = vulkan();
+
+    let (fst, f1) = create_full_screen_triangle(vulkan.transfer_queue()).unwrap();
+    let (sphere, f2) = create_icosphere(vulkan.transfer_queue(), 2).unwrap();
+    wait(f1.join(f2));
+
+    assert_eq!(fst.vertex_buffer().len(), 3);
+    assert!(sphere.index_buffer().len() >= 60); // at least an icosahedron
+}
+
+#[test]
+fn uploads_images() {
+    let vulkan = vulkan();
+
+    let (_, f1) = create_default_fallback_maps(vulkan.transfer_queue());
+    wait(f1);
+
+    // 4x4 image with all mip-maps
+    let image = Image {
+        format: Format::Rgba8,
+        width: 4,
+        height: 4,
+        mipmap_data: vec![255; (16 + 4 + 1) * 4].into(),
+    };
+    let (image, f2) = create_image(&image, vulkan.transfer_queue()).unwrap();
+    wait(f2);
+
+    assert_eq!(image.mipmap_levels(), 3);
+    assert_eq!(image.dimensions().width_height(), [4, 4]);
+}
+
+#[test]
+fn updates_image_regions() {
+    let vulkan = vulkan();
+
+    // 8x8 image with all mip-maps
+    let image = Image {
+        format: Format::Rgba8,
+        width: 8,
+        height: 8,
+        mipmap_data: vec![0; (64 + 16 + 4 + 1) * 4].into(),
+    };
+    let (image, f) = UpdatableImage::new(&image, vulkan.transfer_queue()).unwrap();
+    wait(f);
+    assert_eq!(image.mip_count(), 4);
+    assert_eq!(image.mip_size(2), Some([2, 2]));
+
+    let f = image
+        .update_region(
+            0,
+            [2, 2],
+            [4, 2],
+            &[255; 4 * 2 * 4],
+            vulkan.transfer_queue(),
+        )
+        .unwrap();
+    wait(f);
+    let f = image
+        .update_mip(1, &[128; 16 * 4], vulkan.transfer_queue())
+        .unwrap();
+    wait(f);
+
+    assert!(matches!(
+        image.update_mip(4, &[0; 4], vulkan.transfer_queue()),
+        Err(UpdateImageError::InvalidMipmap(4))
+    ));
+    assert!(matches!(
+        image.update_region(0, [6, 0], [4, 4], &[0; 64], vulkan.transfer_queue()),
+        Err(UpdateImageError::InvalidRegion { .. })
+    ));
+    assert!(matches!(
+        image.update_mip(3, &[0; 8], vulkan.transfer_queue()),
+        Err(UpdateImageError::InvalidDataLength {
+            expected: 4,
+            actual: 8
+        })
+    ));
+}
+
+#[test]
+fn generates_mipmaps() {
+    let vulkan = vulkan();
+
+    // only the first mip-map, rest is generated by blits on the graphical queue
+    let image = Image {
+        format: Format::Rgba8,
+        width: 8,
+        height: 4,
+        mipmap_data: vec![255; 8 * 4 * 4].into(),
+    };
+    let (image, f) = create_image(&image, vulkan.graphical_queue()).unwrap();
+    wait(f);
+
+    assert_eq!(image.mipmap_levels(), 4);
+}
+
+#[test]
+fn uploads_pages_of_virtual_textures() {
+    let vulkan = vulkan();
+
+    // 4x4 pages of the biggest level, root page is always requested
+    let pixels = vec![255; 64 * 64 * 4];
+    let texture =
+        bf::virtual_texture::VirtualTexture::from_pixels(Format::Rgba8, 64, 16, 2, &pixels)
+            .unwrap();
+    let (mut texture, f) =
+        VirtualTexture::new(Arc::new(texture), 4, vulkan.transfer_queue()).unwrap();
+    wait(f);
+
+    wait(
+        texture
+            .update(vulkan.transfer_queue())
+            .expect("root page is not uploaded"),
+    );
+    assert_eq!(texture.resident_pages(), 1);
+    assert!(texture.update(vulkan.transfer_queue()).is_none());
+}
@@ -0,0 +1,143 @@
+use bf::shader::{Shader, ShaderStage, Variant};
+use bf::{save_bf_to_bytes, Container, File};
+use shaderc::{CompileOptions, Compiler, IncludeType, ResolvedInclude, ShaderKind};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "glsl2bf")]
+pub struct Glsl2BfParameters {
+    /// Input file (.glsl)
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+
+    /// Output file (.bf)
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// Shader stage (vert, frag or comp). Determined from the file name if not specified.
+    #[structopt(long, parse(try_from_str = parse_stage))]
+    stage: Option<ShaderStage>,
+
+    /// Comma separated definitions (`NAME` or `NAME=VALUE`) of one variant to compile. Can be
+    /// specified multiple times. Only the variant without any definitions is compiled if not
+    /// specified.
+    #[structopt(long)]
+    variant: Vec<String>,
+}
+
+fn parse_stage(src: &str) -> Result<ShaderStage, &'static str> {
+    match src.to_lowercase().as_str() {
+        "vert" | "vertex" => Ok(ShaderStage::Vertex),
+        "frag" | "fragment" => Ok(ShaderStage::Fragment),
+        "comp" | "compute" => Ok(ShaderStage::Compute),
+        _ => Err("invalid shader stage"),
+    }
+}
+
+fn parse_defines(src: &str) -> Vec<String> {
+    src.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Resolves `#include "file"` directives relative to the including file.
+fn resolve_include(
+    name: &str,
+    _: IncludeType,
+    including: &str,
+    _: usize,
+) -> Result<ResolvedInclude, String> {
+    let path = core::glsl::include_path(Path::new(including), name);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("cannot read include {:?}: {}", path, e))?;
+
+    Ok(ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
+
+fn compile(
+    compiler: &mut Compiler,
+    source: &str,
+    params: &Glsl2BfParameters,
+    stage: ShaderStage,
+    defines: Vec<String>,
+) -> Variant {
+    let mut options = CompileOptions::new().expect("cannot create compile options");
+    options.set_include_callback(resolve_include);
+    for define in defines.iter() {
+        match define.split_once('=') {
+            Some((name, value)) => options.add_macro_definition(name, Some(value)),
+            None => options.add_macro_definition(define, None),
+        }
+    }
+
+    let kind = match stage {
+        ShaderStage::Vertex => ShaderKind::Vertex,
+        ShaderStage::Fragment => ShaderKind::Fragment,
+        ShaderStage::Compute => ShaderKind::Compute,
+    };
+    let artifact = compiler
+        .compile_into_spirv(
+            source,
+            kind,
+            &params.input.to_string_lossy(),
+            "main",
+            Some(&options),
+        )
+        .unwrap_or_else(|e| panic!("cannot compile variant {:?}: {}", defines, e));
+
+    if artifact.get_num_warnings() > 0 {
+        eprintln!("{}", artifact.get_warning_messages());
+    }
+
+    Variant {
+        defines,
+        spirv: artifact.as_binary_u8().to_vec(),
+    }
+}
+
+fn main() {
+    let params = Glsl2BfParameters::from_args();
+    let file_name = params
+        .input
+        .file_name()
+        .and_then(|x| x.to_str())
+        .expect("invalid input file name");
+    let stage = params
+        .stage
+        .or_else(|| ShaderStage::from_file_name(file_name))
+        .expect("cannot determine shader stage, specify --stage");
+    let source = std::fs::read_to_string(&params.input).expect("cannot read input file");
+
+    let mut permutations = params
+        .variant
+        .iter()
+        .map(|x| parse_defines(x))
+        .collect::<Vec<_>>();
+    if permutations.is_empty() {
+        permutations.push(vec![]);
+    }
+
+    let mut compiler = Compiler::new().expect("cannot create shader compiler");
+    let shader = Shader {
+        name: file_name.split('.').next().unwrap_or_default().to_string(),
+        stage,
+        variants: permutations
+            .into_iter()
+            .map(|defines| compile(&mut compiler, &source, &params, stage, defines))
+            .collect(),
+    };
+
+    println!("stage={:?}", shader.stage);
+    println!("variants={}", shader.variants.len());
+
+    let file = File::create_uncompressed(Container::Shader(shader));
+    let bytes = save_bf_to_bytes(&file).expect("cannot convert bf::shader::Shader");
+
+    std::fs::write(&params.output, bytes).expect("cannot save file!");
+}
@@ -0,0 +1,629 @@
+//! Reading of images stored in DDS and KTX2 containers, which artists often
+//! have already block compressed by other tools.
+//!
+//! Blocks of the input can be copied to the output without recompression when
+//! the requested format has the same block layout (see
+//! [`ContainerImage::same_blocks`](struct.ContainerImage.html#method.same_blocks)).
+//! Otherwise the first mip-map is decoded and converted as any other image.
+
+use bf::image::Format;
+use image::codecs::dxt::{DxtDecoder, DxtVariant};
+use image::{DynamicImage, GrayImage, ImageBuffer, RgbImage, RgbaImage};
+use std::convert::TryFrom;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ContainerError {
+    /// The file ends before the data described by its header.
+    Truncated,
+    /// The file does not start with the DDS or KTX2 identifier.
+    InvalidMagic,
+    /// Pixel format of the container has no equivalent BF format.
+    UnsupportedFormat(String),
+    /// The container stores something else than a single 2D image (eg. cube map).
+    UnsupportedLayout(&'static str),
+    /// Blocks of this format can't be decoded, so the image can only be passed
+    /// through to the output in the same format.
+    UnsupportedDecode(Format),
+    /// The header describes an image that can't exist (eg. more mip-maps than
+    /// the size allows or a level outside of the file).
+    InvalidHeader(&'static str),
+    DecodeError(image::ImageError),
+}
+
+/// Image read from a DDS or KTX2 container.
+#[derive(Debug)]
+pub struct ContainerImage {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    /// Data of the stored mip-maps from the biggest one.
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Returns whether the file at `path` should be read as a DDS or KTX2 container.
+pub fn is_container(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_lowercase())
+            .as_deref(),
+        Some("dds") | Some("ktx2")
+    )
+}
+
+/// Parses the DDS or KTX2 container (detected by its identifier) in `bytes`.
+pub fn read_container(bytes: &[u8]) -> Result<ContainerImage, ContainerError> {
+    if bytes.starts_with(b"DDS ") {
+        read_dds(bytes)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        read_ktx2(bytes)
+    } else {
+        Err(ContainerError::InvalidMagic)
+    }
+}
+
+/// Returns the size of a mip-map of `width` x `height` pixels as stored in the
+/// containers (compressed formats use whole 4x4 blocks) or `None` if it overflows.
+fn level_size(format: Format, width: u32, height: u32) -> Option<usize> {
+    let bits = format.bits_per_pixel() as usize;
+    let (width, height) = if format.compressed() {
+        // size of the whole blocks
+        (
+            (width as usize).div_ceil(4).max(1) * 4,
+            (height as usize).div_ceil(4).max(1) * 4,
+        )
+    } else {
+        (width as usize, height as usize)
+    };
+    width.checked_mul(height)?.checked_mul(bits).map(|x| x / 8)
+}
+
+/// Validates the size and the number of mip-maps read from a header.
+fn validate_dimensions(width: u32, height: u32, count: u32) -> Result<(), ContainerError> {
+    if width == 0 || height == 0 {
+        return Err(ContainerError::InvalidHeader("image without pixels"));
+    }
+    // the smallest mip-map is 1x1
+    let max_count = 32 - width.max(height).leading_zeros();
+    if count > max_count {
+        return Err(ContainerError::InvalidHeader(
+            "more mip-maps than the size allows",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the size of the `mip`-th mip-map of an image of `width` x `height` pixels.
+fn mip_dimensions(width: u32, height: u32, mip: u32) -> Result<(u32, u32), ContainerError> {
+    let shr = |x: u32| x.checked_shr(mip).map(|x| x.max(1));
+    match (shr(width), shr(height)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(ContainerError::InvalidHeader(
+            "more mip-maps than the size allows",
+        )),
+    }
+}
+
+/// Splits the data following the header into `count` mip-maps.
+fn split_levels(
+    data: &[u8],
+    format: Format,
+    width: u32,
+    height: u32,
+    count: u32,
+) -> Result<Vec<Vec<u8>>, ContainerError> {
+    validate_dimensions(width, height, count)?;
+
+    let mut levels = Vec::with_capacity(count as usize);
+    let mut offset = 0usize;
+    for mip in 0..count {
+        let (w, h) = mip_dimensions(width, height, mip)?;
+        let end = level_size(format, w, h)
+            .and_then(|size| offset.checked_add(size))
+            .ok_or(ContainerError::Truncated)?;
+        let level = data.get(offset..end).ok_or(ContainerError::Truncated)?;
+        levels.push(level.to_vec());
+        offset = end;
+    }
+    Ok(levels)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    let mut le = [0; 4];
+    le.copy_from_slice(
+        bytes
+            .get(offset..offset + 4)
+            .ok_or(ContainerError::Truncated)?,
+    );
+    Ok(u32::from_le_bytes(le))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ContainerError> {
+    let mut le = [0; 8];
+    le.copy_from_slice(
+        bytes
+            .get(offset..offset + 8)
+            .ok_or(ContainerError::Truncated)?,
+    );
+    Ok(u64::from_le_bytes(le))
+}
+
+// flags of the dds header and pixel format
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_HEADER_END: usize = 128;
+const DX10_HEADER_END: usize = DDS_HEADER_END + 20;
+
+/// Returns the format of the `DXGI_FORMAT` or `None` if it has no BF equivalent.
+fn from_dxgi_format(dxgi: u32) -> Option<Format> {
+    match dxgi {
+        71 => Some(Format::Dxt1),
+        72 => Some(Format::SrgbDxt1),
+        74 => Some(Format::Dxt3),
+        75 => Some(Format::SrgbDxt3),
+        77 => Some(Format::Dxt5),
+        78 => Some(Format::SrgbDxt5),
+        83 => Some(Format::BC5),
+        95 => Some(Format::BC6H),
+        98 => Some(Format::BC7),
+        99 => Some(Format::SrgbBC7),
+        28 => Some(Format::Rgba8),
+        29 => Some(Format::Srgb8A8),
+        61 => Some(Format::R8),
+        56 => Some(Format::R16),
+        35 => Some(Format::Rg16),
+        10 => Some(Format::Rgba16F),
+        2 => Some(Format::Rgba32F),
+        _ => None,
+    }
+}
+
+/// Returns the format described by the legacy DDS pixel format (without the `DX10`
+/// header) starting at `offset`.
+fn legacy_dds_format(bytes: &[u8], offset: usize) -> Result<Format, ContainerError> {
+    let flags = read_u32(bytes, offset + 4)?;
+    let four_cc = bytes
+        .get(offset + 8..offset + 12)
+        .ok_or(ContainerError::Truncated)?;
+    let bit_count = read_u32(bytes, offset + 12)?;
+    let masks = [
+        read_u32(bytes, offset + 16)?,
+        read_u32(bytes, offset + 20)?,
+        read_u32(bytes, offset + 24)?,
+    ];
+
+    let format = if flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => Some(Format::Dxt1),
+            b"DXT3" => Some(Format::Dxt3),
+            b"DXT5" => Some(Format::Dxt5),
+            b"ATI2" | b"BC5U" => Some(Format::BC5),
+            _ => None,
+        }
+    } else if flags & DDPF_RGB != 0 && masks == [0xff, 0xff00, 0xff0000] {
+        match bit_count {
+            24 => Some(Format::Rgb8),
+            32 => Some(Format::Rgba8),
+            _ => None,
+        }
+    } else if flags & DDPF_LUMINANCE != 0 && bit_count == 8 {
+        Some(Format::R8)
+    } else {
+        None
+    };
+
+    format.ok_or_else(|| {
+        ContainerError::UnsupportedFormat(format!(
+            "DDS pixel format (flags={:#x}, fourcc={:?}, bits={}, masks={:x?})",
+            flags,
+            String::from_utf8_lossy(four_cc),
+            bit_count,
+            masks
+        ))
+    })
+}
+
+fn read_dds(bytes: &[u8]) -> Result<ContainerImage, ContainerError> {
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    let caps2 = read_u32(bytes, 112)?;
+    if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        return Err(ContainerError::UnsupportedLayout("cube map"));
+    }
+    if caps2 & DDSCAPS2_VOLUME != 0 {
+        return Err(ContainerError::UnsupportedLayout("volume texture"));
+    }
+
+    let (format, data_start) = if bytes.get(84..88) == Some(&b"DX10"[..]) {
+        let dxgi = read_u32(bytes, DDS_HEADER_END)?;
+        if read_u32(bytes, DDS_HEADER_END + 12)? > 1 {
+            return Err(ContainerError::UnsupportedLayout("texture array"));
+        }
+        let format = from_dxgi_format(dxgi)
+            .ok_or_else(|| ContainerError::UnsupportedFormat(format!("DXGI_FORMAT {}", dxgi)))?;
+        (format, DX10_HEADER_END)
+    } else {
+        (legacy_dds_format(bytes, 76)?, DDS_HEADER_END)
+    };
+
+    let data = bytes.get(data_start..).ok_or(ContainerError::Truncated)?;
+    Ok(ContainerImage {
+        format,
+        width,
+        height,
+        levels: split_levels(data, format, width, height, mip_count)?,
+    })
+}
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const KTX2_LEVEL_INDEX: usize = 80;
+
+/// Returns the format of the `VkFormat` or `None` if it has no BF equivalent.
+fn from_vk_format(vk_format: u32) -> Option<Format> {
+    match vk_format {
+        9 => Some(Format::R8),
+        23 => Some(Format::Rgb8),
+        29 => Some(Format::Srgb8),
+        37 => Some(Format::Rgba8),
+        43 => Some(Format::Srgb8A8),
+        // both RGB and RGBA variants of BC1 are the same blocks
+        131 | 133 => Some(Format::Dxt1),
+        132 | 134 => Some(Format::SrgbDxt1),
+        135 => Some(Format::Dxt3),
+        136 => Some(Format::SrgbDxt3),
+        137 => Some(Format::Dxt5),
+        138 => Some(Format::SrgbDxt5),
+        141 => Some(Format::BC5),
+        143 => Some(Format::BC6H),
+        145 => Some(Format::BC7),
+        146 => Some(Format::SrgbBC7),
+        70 => Some(Format::R16),
+        77 => Some(Format::Rg16),
+        97 => Some(Format::Rgba16F),
+        109 => Some(Format::Rgba32F),
+        _ => None,
+    }
+}
+
+fn read_ktx2(bytes: &[u8]) -> Result<ContainerImage, ContainerError> {
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    if read_u32(bytes, 28)? > 0 {
+        return Err(ContainerError::UnsupportedLayout("volume texture"));
+    }
+    if read_u32(bytes, 32)? > 1 {
+        return Err(ContainerError::UnsupportedLayout("texture array"));
+    }
+    if read_u32(bytes, 36)? != 1 {
+        return Err(ContainerError::UnsupportedLayout("cube map"));
+    }
+    // zero levels means that the mip-maps should be generated
+    let level_count = read_u32(bytes, 40)?.max(1);
+    if read_u32(bytes, 44)? != 0 {
+        return Err(ContainerError::UnsupportedLayout("supercompressed texture"));
+    }
+
+    let format = from_vk_format(vk_format)
+        .ok_or_else(|| ContainerError::UnsupportedFormat(format!("VkFormat {}", vk_format)))?;
+    validate_dimensions(width, height, level_count)?;
+
+    // levels are indexed from the biggest one even though they are stored from the smallest
+    let levels = (0..level_count)
+        .map(|mip| {
+            let entry = KTX2_LEVEL_INDEX + mip as usize * 24;
+            let offset = read_u64(bytes, entry)?;
+            let length = read_u64(bytes, entry + 8)?;
+            let (w, h) = mip_dimensions(width, height, mip)?;
+            if level_size(format, w, h).map(|x| x as u64) != Some(length) {
+                return Err(ContainerError::InvalidHeader(
+                    "level length does not match its size",
+                ));
+            }
+            let range = usize::try_from(offset)
+                .ok()
+                .and_then(|start| Some(start..start.checked_add(length as usize)?))
+                .ok_or(ContainerError::Truncated)?;
+            bytes
+                .get(range)
+                .map(|x| x.to_vec())
+                .ok_or(ContainerError::Truncated)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ContainerImage {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+/// Returns the format with the same blocks without the sRGB interpretation.
+fn linear(format: Format) -> Format {
+    match format {
+        Format::SrgbDxt1 => Format::Dxt1,
+        Format::SrgbDxt3 => Format::Dxt3,
+        Format::SrgbDxt5 => Format::Dxt5,
+        Format::SrgbBC7 => Format::BC7,
+        Format::Srgb8 => Format::Rgb8,
+        Format::Srgb8A8 => Format::Rgba8,
+        t => t,
+    }
+}
+
+impl ContainerImage {
+    /// Returns whether the data of this image is also valid data of `format`. Formats
+    /// that differ only in the color space have the same blocks.
+    pub fn same_blocks(&self, format: Format) -> bool {
+        linear(self.format) == linear(format)
+    }
+
+    /// Returns the mip-maps in the layout of BF images (from the biggest one) or
+    /// `None` if the container does not store the whole chain that img2bf would
+    /// generate (down to 4 pixels wide). Smaller levels are kept as long as their
+    /// sizes can be represented in BF images (that is not for partial blocks).
+    pub fn bf_payload(&self) -> Option<Vec<u8>> {
+        let mut payload = vec![];
+        let mut complete = false;
+        for (mip, level) in self.levels.iter().enumerate() {
+            let (width, height) = (self.width >> mip, self.height >> mip);
+            let size = width as usize * height as usize * self.format.bits_per_pixel() as usize / 8;
+            if width == 0 || height == 0 || level.len() != size {
+                break;
+            }
+            payload.extend_from_slice(level);
+            complete |= width <= 4;
+        }
+        if complete {
+            Some(payload)
+        } else {
+            None
+        }
+    }
+
+    /// Decodes the first mip-map of the image.
+    pub fn decode(&self) -> Result<DynamicImage, ContainerError> {
+        let (width, height) = (self.width, self.height);
+        let data = self.levels[0].clone();
+        let dxt = |variant| {
+            DxtDecoder::new(data.as_slice(), width, height, variant)
+                .and_then(DynamicImage::from_decoder)
+                .map_err(ContainerError::DecodeError)
+        };
+        // dimensions were validated when the levels were split
+        let invalid = || ContainerError::Truncated;
+
+        match self.format {
+            Format::R8 => GrayImage::from_raw(width, height, data)
+                .map(DynamicImage::ImageLuma8)
+                .ok_or_else(invalid),
+            Format::Rgb8 | Format::Srgb8 => RgbImage::from_raw(width, height, data)
+                .map(DynamicImage::ImageRgb8)
+                .ok_or_else(invalid),
+            Format::Rgba8 | Format::Srgb8A8 => RgbaImage::from_raw(width, height, data)
+                .map(DynamicImage::ImageRgba8)
+                .ok_or_else(invalid),
+            Format::Dxt1 | Format::SrgbDxt1 => dxt(DxtVariant::DXT1),
+            Format::Dxt3 | Format::SrgbDxt3 => dxt(DxtVariant::DXT3),
+            Format::Dxt5 | Format::SrgbDxt5 => dxt(DxtVariant::DXT5),
+            Format::BC5 => Ok(DynamicImage::ImageRgb8(decode_bc5(&data, width, height))),
+            Format::R16 => ImageBuffer::from_raw(width, height, read_u16s(&data))
+                .map(DynamicImage::ImageLuma16)
+                .ok_or_else(invalid),
+            Format::Rg16 => {
+                // blue is zero as in decoded BC5 images
+                let rgb = read_u16s(&data)
+                    .chunks_exact(2)
+                    .flat_map(|x| vec![x[0], x[1], 0])
+                    .collect();
+                ImageBuffer::from_raw(width, height, rgb)
+                    .map(DynamicImage::ImageRgb16)
+                    .ok_or_else(invalid)
+            }
+            t @ Format::BC6H
+            | t @ Format::BC7
+            | t @ Format::SrgbBC7
+            | t @ Format::Rgba16F
+            | t @ Format::Rgba32F => Err(ContainerError::UnsupportedDecode(t)),
+        }
+    }
+}
+
+/// Reads little-endian 16-bit channels.
+fn read_u16s(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect()
+}
+
+/// Decodes BC5 blocks into an image with red and green channels (blue is zero).
+fn decode_bc5(data: &[u8], width: u32, height: u32) -> RgbImage {
+    let blocks_x = width.div_ceil(4).max(1);
+    let mut image = RgbImage::new(width, height);
+
+    for (idx, block) in data.chunks_exact(16).enumerate() {
+        let (bx, by) = (idx as u32 % blocks_x * 4, idx as u32 / blocks_x * 4);
+        let red = decode_bc4_block(&block[..8]);
+        let green = decode_bc4_block(&block[8..]);
+
+        for i in 0..16 {
+            let (x, y) = (bx + i as u32 % 4, by + i as u32 / 4);
+            if x < width && y < height {
+                image.put_pixel(x, y, image::Rgb([red[i], green[i], 0]));
+            }
+        }
+    }
+
+    image
+}
+
+/// Decodes one BC4 block (8 bytes) into 16 values in row-major order.
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let (a, b) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a, b, 0, 0, 0, 0, 0, 255];
+    if a > b {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as u32) * a + i as u32 * b) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as u32) * a + i as u32 * b) / 5;
+        }
+    }
+
+    let mut indices = 0u64;
+    for (i, x) in block[2..8].iter().enumerate() {
+        indices |= (*x as u64) << (8 * i);
+    }
+
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (3 * i)) & 0b111) as usize] as u8;
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::container::{read_container, ContainerError, KTX2_IDENTIFIER};
+
+    /// Returns a DXT1 DDS file of `width` x `height` pixels declaring `mips` mip-maps
+    /// followed by `data` bytes.
+    fn dds(width: u32, height: u32, mips: u32, data: usize) -> Vec<u8> {
+        let mut bytes = vec![0; 128 + data];
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[8..12].copy_from_slice(&0x20000u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&mips.to_le_bytes());
+        bytes[80..84].copy_from_slice(&0x4u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes
+    }
+
+    /// Returns an RGBA KTX2 file of `width` x `height` pixels with the level index
+    /// `levels` of (offset, length) pairs followed by `data` bytes.
+    fn ktx2(width: u32, height: u32, levels: &[(u64, u64)], data: usize) -> Vec<u8> {
+        let mut bytes = vec![0; 80 + levels.len() * 24 + data];
+        bytes[..12].copy_from_slice(&KTX2_IDENTIFIER);
+        bytes[12..16].copy_from_slice(&37u32.to_le_bytes());
+        bytes[20..24].copy_from_slice(&width.to_le_bytes());
+        bytes[24..28].copy_from_slice(&height.to_le_bytes());
+        bytes[36..40].copy_from_slice(&1u32.to_le_bytes());
+        bytes[40..44].copy_from_slice(&(levels.len() as u32).to_le_bytes());
+        for (idx, (offset, length)) in levels.iter().enumerate() {
+            let entry = 80 + idx * 24;
+            bytes[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+            bytes[entry + 8..entry + 16].copy_from_slice(&length.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn dds_levels_are_split() {
+        let image = read_container(&dds(8, 8, 4, 32 + 3 * 8)).unwrap();
+        let sizes: Vec<_> = image.levels.iter().map(|x| x.len()).collect();
+        assert_eq!(sizes, vec![32, 8, 8, 8]);
+    }
+
+    #[test]
+    fn dds_with_too_many_mipmaps_is_rejected() {
+        for mips in &[5, 33, u32::MAX] {
+            assert!(matches!(
+                read_container(&dds(8, 8, *mips, 1024)),
+                Err(ContainerError::InvalidHeader(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn dds_without_pixels_is_rejected() {
+        assert!(matches!(
+            read_container(&dds(0, 8, 1, 32)),
+            Err(ContainerError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_dds_is_rejected() {
+        assert!(matches!(
+            read_container(&dds(8, 8, 2, 32)),
+            Err(ContainerError::Truncated)
+        ));
+        assert!(matches!(
+            read_container(&dds(u32::MAX, u32::MAX, 32, 32)),
+            Err(ContainerError::Truncated)
+        ));
+        assert!(matches!(
+            read_container(&dds(8, 8, 1, 0)[..100]),
+            Err(ContainerError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn ktx2_levels_are_read() {
+        let start = 80 + 2 * 24;
+        let image = read_container(&ktx2(2, 2, &[(start + 4, 16), (start, 4)], 20)).unwrap();
+        assert_eq!(image.levels[0].len(), 16);
+        assert_eq!(image.levels[1].len(), 4);
+    }
+
+    #[test]
+    fn ktx2_with_invalid_level_index_is_rejected() {
+        // more levels than the size allows
+        let levels = vec![(0, 0); 40];
+        assert!(matches!(
+            read_container(&ktx2(2, 2, &levels, 0)),
+            Err(ContainerError::InvalidHeader(_))
+        ));
+        // length that does not match the size of the level
+        assert!(matches!(
+            read_container(&ktx2(2, 2, &[(0, u64::MAX)], 0)),
+            Err(ContainerError::InvalidHeader(_))
+        ));
+        // level after the end of the file
+        assert!(matches!(
+            read_container(&ktx2(2, 2, &[(u64::MAX, 16)], 0)),
+            Err(ContainerError::Truncated)
+        ));
+        assert!(matches!(
+            read_container(&ktx2(2, 2, &[(usize::MAX as u64 - 8, 16)], 0)),
+            Err(ContainerError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn bf_payload_requires_whole_chain() {
+        // 16x16 DXT1 is 128 bytes, 8x8 is 32 and 4x4 is 8
+        let image = read_container(&dds(16, 16, 1, 128)).unwrap();
+        assert!(image.bf_payload().is_none());
+        let image = read_container(&dds(16, 16, 2, 128 + 32)).unwrap();
+        assert!(image.bf_payload().is_none());
+
+        // levels with partial blocks can't be stored in BF images
+        let image = read_container(&dds(16, 16, 5, 128 + 32 + 3 * 8)).unwrap();
+        assert_eq!(image.bf_payload().map(|x| x.len()), Some(128 + 32 + 8));
+    }
+
+    #[test]
+    fn bf_payload_keeps_small_uncompressed_levels() {
+        let start = 80 + 3 * 24;
+        let levels = [(start + 20, 64), (start + 4, 16), (start, 4)];
+        let image = read_container(&ktx2(4, 4, &levels, 84)).unwrap();
+        assert_eq!(image.bf_payload().map(|x| x.len()), Some(64 + 16 + 4));
+    }
+}
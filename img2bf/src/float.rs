@@ -0,0 +1,93 @@
+//! Floating point images for the `Rgba16F` and `Rgba32F` formats. `DynamicImage`
+//! has no floating point variant, so these images skip the usual pipeline and are
+//! kept in `Rgba32FImage` buffers to preserve values above one.
+
+use bf::image::{f32_to_f16, Format};
+use image::codecs::hdr::HdrDecoder;
+use image::{DynamicImage, ImageBuffer, ImageError, Rgba};
+use std::ffi::OsStr;
+use std::io::BufReader;
+use std::path::Path;
+
+pub type Rgba32FImage = ImageBuffer<Rgba<f32>, Vec<f32>>;
+
+#[derive(Debug)]
+pub enum FloatImageError {
+    Image(ImageError),
+    Exr(exr::error::Error),
+}
+
+/// Opens the image at `path` without quantizing it to 8 bits. Radiance HDR and
+/// OpenEXR images are read as floats, other images with 16 bits per channel.
+pub fn open(path: &Path) -> Result<Rgba32FImage, FloatImageError> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase);
+
+    match extension.as_deref() {
+        Some("hdr") => open_hdr(path).map_err(FloatImageError::Image),
+        Some("exr") => open_exr(path).map_err(FloatImageError::Exr),
+        _ => image::open(path)
+            .map(|x| from_dynamic(&x))
+            .map_err(FloatImageError::Image),
+    }
+}
+
+fn open_hdr(path: &Path) -> Result<Rgba32FImage, ImageError> {
+    let reader = BufReader::new(std::fs::File::open(path).map_err(ImageError::IoError)?);
+    let decoder = HdrDecoder::new(reader)?;
+    let meta = decoder.metadata();
+    let pixels = decoder.read_image_hdr()?;
+
+    Ok(ImageBuffer::from_fn(meta.width, meta.height, |x, y| {
+        let p = pixels[(y * meta.width + x) as usize];
+        Rgba([p[0], p[1], p[2], 1.0])
+    }))
+}
+
+fn open_exr(path: &Path) -> Result<Rgba32FImage, exr::error::Error> {
+    use exr::prelude::*;
+
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution: Vec2<usize>, _| {
+            Rgba32FImage::new(resolution.width() as u32, resolution.height() as u32)
+        },
+        |image: &mut Rgba32FImage, position: Vec2<usize>, (r, g, b, a): (f32, f32, f32, f32)| {
+            image.put_pixel(position.x() as u32, position.y() as u32, Rgba([r, g, b, a]))
+        },
+    )?;
+
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+/// Converts the `image` (eg. a 16-bit PNG or a decoded DDS) to floats in the
+/// `0.0` to `1.0` range.
+pub fn from_dynamic(image: &DynamicImage) -> Rgba32FImage {
+    let image = image.to_rgba16();
+
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        Rgba([
+            p[0] as f32 / 65535.0,
+            p[1] as f32 / 65535.0,
+            p[2] as f32 / 65535.0,
+            p[3] as f32 / 65535.0,
+        ])
+    })
+}
+
+/// Returns little-endian bytes of the `image` in the `format` (`Rgba16F`
+/// or `Rgba32F`).
+pub fn to_bytes(format: Format, image: &Rgba32FImage) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(image.len() * format.bits_per_pixel() as usize / 32);
+    for x in image.iter() {
+        match format {
+            Format::Rgba16F => bytes.extend_from_slice(&f32_to_f16(*x).to_le_bytes()),
+            Format::Rgba32F => bytes.extend_from_slice(&x.to_le_bytes()),
+            _ => panic!("Format {:?} is not a floating point format.", format),
+        }
+    }
+    bytes
+}
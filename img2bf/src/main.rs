@@ -1,9 +1,15 @@
+use crate::specgloss::SpecGlossTarget;
 use crate::tool::Img2Bf;
 use bf::image::Format;
+use bf::Codec;
+use core::color::ColorSpace;
 use image::imageops::FilterType;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+mod container;
+mod float;
+mod specgloss;
 mod tool;
 
 /// You can use destination parameters to swizzle channels around or replace some channel
@@ -11,7 +17,9 @@ mod tool;
 #[derive(StructOpt, Debug)]
 #[structopt(name = "img2bf")]
 pub struct Img2BfParameters {
-    /// Input file (.jpeg, .png, .bmp, ...)
+    /// Input file (.jpeg, .png, .bmp, .hdr, .exr, ...). 16-bit and floating point
+    /// formats keep the precision of 16-bit and HDR inputs. Blocks of DDS and KTX2 files are copied
+    /// without recompression when the requested format has the same blocks and they store all mip-maps.
     #[structopt(short, long, parse(from_os_str))]
     input: PathBuf,
 
@@ -39,6 +47,30 @@ pub struct Img2BfParameters {
     #[structopt(short, long)]
     pack_normal_map: bool,
 
+    /// Generates metallic/roughness texture from specular/glossiness textures ("albedo",
+    /// "metallic" or "roughness"). Input is the diffuse, specular or glossiness map.
+    #[structopt(long, parse(try_from_str = parse_spec_gloss_target))]
+    spec_gloss: Option<SpecGlossTarget>,
+
+    /// Specular map used with `--spec-gloss albedo`
+    #[structopt(long, parse(from_os_str))]
+    specular: Option<PathBuf>,
+
+    /// Diffuse map used with `--spec-gloss metallic`
+    #[structopt(long, parse(from_os_str))]
+    diffuse: Option<PathBuf>,
+
+    /// Color space of the input image ("srgb", "linear" or "acescg"). Colors are
+    /// converted to the output color space when the spaces differ. Should not be
+    /// specified for data maps (normals, roughness, ...).
+    #[structopt(long)]
+    input_color_space: Option<ColorSpace>,
+
+    /// Color space of the output image. Defaults to "srgb" for sRGB formats and
+    /// to "linear" for other formats.
+    #[structopt(long)]
+    output_color_space: Option<ColorSpace>,
+
     /// Swizzle destination: red channel
     #[structopt(long)]
     destination_r: Option<String>,
@@ -54,6 +86,32 @@ pub struct Img2BfParameters {
     /// Swizzle destination: alpha channel
     #[structopt(long)]
     destination_a: Option<String>,
+
+    /// Codec the output file is compressed with ("lz4" or "zstd"). The "zstd" codec
+    /// produces smaller files but requires building with the `zstd` feature.
+    #[structopt(long, parse(try_from_str = parse_codec))]
+    codec: Option<Codec>,
+
+    /// Compression level of the codec (lz4: 1-12, zstd: 1-22). Uses the default
+    /// level of the codec if not specified.
+    #[structopt(long)]
+    compression_level: Option<i32>,
+
+    /// Outputs a virtual texture split into pages of this size (in pixels) instead
+    /// of an image. The input must be square with power of two size and the format
+    /// uncompressed. The output is not compressed, so it can be memory-mapped.
+    #[structopt(long)]
+    virtual_texture: Option<u32>,
+
+    /// Number of pixels of neighbouring pages stored on each side of the pages
+    /// of the virtual texture.
+    #[structopt(long, default_value = "4")]
+    page_border: u32,
+
+    /// Number of threads used for block compression. Uses all logical cores if
+    /// not specified.
+    #[structopt(long)]
+    threads: Option<usize>,
 }
 
 fn parse_format(src: &str) -> Result<Format, &'static str> {
@@ -63,6 +121,7 @@ fn parse_format(src: &str) -> Result<Format, &'static str> {
         "bc3" | "dxt5" => Ok(Format::Dxt5),
         "bc6h" => Ok(Format::BC6H),
         "bc7" => Ok(Format::BC7),
+        "bc5" => Ok(Format::BC5),
         "r8" => Ok(Format::R8),
         "rgb" => Ok(Format::Rgb8),
         "rgba" => Ok(Format::Rgba8),
@@ -72,6 +131,10 @@ fn parse_format(src: &str) -> Result<Format, &'static str> {
         "srgb_bc7" => Ok(Format::SrgbBC7),
         "srgb" => Ok(Format::Srgb8),
         "srgba" => Ok(Format::Srgb8A8),
+        "r16" => Ok(Format::R16),
+        "rg16" => Ok(Format::Rg16),
+        "rgba16f" => Ok(Format::Rgba16F),
+        "rgba32f" => Ok(Format::Rgba32F),
         _ => Err("unknown format"),
     }
 }
@@ -87,16 +150,45 @@ fn parse_mip_filter(src: &str) -> Result<FilterType, &'static str> {
     }
 }
 
+fn parse_spec_gloss_target(src: &str) -> Result<SpecGlossTarget, &'static str> {
+    match src.to_lowercase().as_str() {
+        "albedo" => Ok(SpecGlossTarget::Albedo),
+        "metallic" => Ok(SpecGlossTarget::Metallic),
+        "roughness" => Ok(SpecGlossTarget::Roughness),
+        _ => Err("unknown spec/gloss target"),
+    }
+}
+
+fn parse_codec(src: &str) -> Result<Codec, &'static str> {
+    match src.to_lowercase().as_str() {
+        "lz4" => Ok(Codec::Lz4),
+        "zstd" => Ok(Codec::Zstd),
+        _ => Err("unknown codec"),
+    }
+}
+
 fn main() {
     let params = Img2BfParameters::from_args();
-    let stats = Img2Bf::convert(params).expect("conversion failed!");
+    let report = Img2Bf::convert(params).expect("conversion failed!");
+    let stats = report.stats;
 
     println!("load={}ms", stats.load.total_time().as_millis());
+    println!("specgloss={}ms", stats.specgloss.total_time().as_millis());
     println!("vflip={}ms", stats.vflip.total_time().as_millis());
     println!("hflip={}ms", stats.hflip.total_time().as_millis());
     println!("channels={}ms", stats.channels.total_time().as_millis());
+    println!("color={}ms", stats.color.total_time().as_millis());
     println!("swizzle={}ms", stats.swizzle.total_time().as_millis());
     println!("mipmaps={}ms", stats.mipmaps.total_time().as_millis());
     println!("dxt={}ms", stats.dxt.total_time().as_millis());
+    for (idx, mip) in report.mipmaps.iter().enumerate() {
+        println!(
+            "dxt.mip{}({}x{})={}ms",
+            idx,
+            mip.width,
+            mip.height,
+            mip.time.as_millis()
+        );
+    }
     println!("save={}ms", stats.save.total_time().as_millis());
 }
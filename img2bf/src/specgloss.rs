@@ -0,0 +1,137 @@
+//! Conversion of specular/glossiness workflow textures to metallic/roughness.
+//!
+//! Metallic and base color are solved with the approximation used by the glTF
+//! `KHR_materials_pbrSpecularGlossiness` converter: dielectrics are assumed
+//! to reflect 4% of light and the metalness is found so that the perceived
+//! brightness of diffuse and specular color is preserved.
+
+use core::color;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Luma, Rgba};
+
+/// Specular reflectance of dielectric materials.
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+
+/// Texture of the metallic/roughness workflow to generate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpecGlossTarget {
+    /// Base color from diffuse (input) and specular map.
+    Albedo,
+    /// Metallic map from specular (input) and diffuse map.
+    Metallic,
+    /// Roughness map from glossiness (input) map.
+    Roughness,
+}
+
+fn srgb_to_linear(x: u8) -> f32 {
+    color::srgb_to_linear(x as f32 / 255.0)
+}
+
+fn linear_to_srgb(x: f32) -> u8 {
+    (color::linear_to_srgb(x.min(1.0)) * 255.0).round() as u8
+}
+
+fn perceived_brightness(c: [f32; 3]) -> f32 {
+    (0.299 * c[0] * c[0] + 0.587 * c[1] * c[1] + 0.114 * c[2] * c[2]).sqrt()
+}
+
+/// Solves the metalness from the perceived brightness of diffuse and specular
+/// color.
+fn solve_metallic(diffuse: f32, specular: f32, one_minus_specular_strength: f32) -> f32 {
+    if specular < DIELECTRIC_SPECULAR {
+        return 0.0;
+    }
+
+    let a = DIELECTRIC_SPECULAR;
+    let b = diffuse * one_minus_specular_strength / (1.0 - a) + specular - 2.0 * a;
+    let c = a - specular;
+    let d = (b * b - 4.0 * a * c).max(0.0);
+
+    ((-b + d.sqrt()) / (2.0 * a)).max(0.0).min(1.0)
+}
+
+/// Converts linear diffuse and specular color to linear base color and metalness.
+fn convert(diffuse: [f32; 3], specular: [f32; 3]) -> ([f32; 3], f32) {
+    let one_minus_specular_strength = 1.0 - specular[0].max(specular[1]).max(specular[2]);
+    let metallic = solve_metallic(
+        perceived_brightness(diffuse),
+        perceived_brightness(specular),
+        one_minus_specular_strength,
+    );
+
+    let mut base_color = [0.0; 3];
+    for i in 0..3 {
+        let from_diffuse = diffuse[i] * one_minus_specular_strength
+            / (1.0 - DIELECTRIC_SPECULAR)
+            / (1.0 - metallic).max(f32::EPSILON);
+        let from_specular =
+            (specular[i] - DIELECTRIC_SPECULAR * (1.0 - metallic)) / metallic.max(f32::EPSILON);
+        let t = metallic * metallic;
+
+        base_color[i] = (from_diffuse + (from_specular - from_diffuse) * t)
+            .max(0.0)
+            .min(1.0);
+    }
+
+    (base_color, metallic)
+}
+
+/// Generates the `target` texture. The `input` is the texture specified by
+/// the target and `other` is the second texture needed to solve metalness
+/// (specular map for albedo and diffuse map for metallic). Diffuse and
+/// specular maps are expected to be in sRGB. Generated albedo is in sRGB and
+/// keeps the alpha of the diffuse map, metallic and roughness are linear.
+///
+/// Returns `None` if `other` is required but missing or has different dimensions.
+pub fn spec_gloss_to_metal_rough(
+    target: SpecGlossTarget,
+    input: &DynamicImage,
+    other: Option<&DynamicImage>,
+) -> Option<DynamicImage> {
+    let (width, height) = input.dimensions();
+
+    if target == SpecGlossTarget::Roughness {
+        let mut roughness = input.to_luma8();
+        for p in roughness.pixels_mut() {
+            p[0] = 255 - p[0];
+        }
+        return Some(DynamicImage::ImageLuma8(roughness));
+    }
+
+    let other = other.filter(|x| x.dimensions() == (width, height))?;
+    let (diffuse, specular) = match target {
+        SpecGlossTarget::Albedo => (input.to_rgba8(), other.to_rgba8()),
+        _ => (other.to_rgba8(), input.to_rgba8()),
+    };
+    let linear = |p: &Rgba<u8>| {
+        [
+            srgb_to_linear(p[0]),
+            srgb_to_linear(p[1]),
+            srgb_to_linear(p[2]),
+        ]
+    };
+    let solve = |x: u32, y: u32| {
+        convert(
+            linear(diffuse.get_pixel(x, y)),
+            linear(specular.get_pixel(x, y)),
+        )
+    };
+
+    Some(match target {
+        SpecGlossTarget::Albedo => {
+            DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+                let (c, _) = solve(x, y);
+                let alpha = diffuse.get_pixel(x, y)[3];
+                Rgba([
+                    linear_to_srgb(c[0]),
+                    linear_to_srgb(c[1]),
+                    linear_to_srgb(c[2]),
+                    alpha,
+                ])
+            }))
+        }
+        _ => DynamicImage::ImageLuma8(ImageBuffer::from_fn(width, height, |x, y| {
+            let (_, metallic) = solve(x, y);
+            Luma([(metallic * 255.0).round() as u8])
+        })),
+    })
+}
@@ -1,16 +1,46 @@
+use crate::container::{is_container, read_container, ContainerError, ContainerImage};
+use crate::float::{FloatImageError, Rgba32FImage};
+use crate::specgloss::{spec_gloss_to_metal_rough, SpecGlossTarget};
 use crate::Img2BfParameters;
 use bf::image::{Format, Image};
-use bf::{save_bf_to_bytes, Container, File};
+use bf::virtual_texture::{VirtualTexture, VirtualTextureError};
+use bf::{save_bf_to_bytes, Codec, Container, File};
+use core::color::ColorSpace;
 use core::impl_stats_struct;
 use core::measure_scope;
 use core::tool::Tool;
 use image::codecs::dxt::{DxtEncoder, DxtVariant};
 use image::imageops::FilterType;
-use image::{DynamicImage, GenericImageView, ImageBuffer, ImageError, Pixel};
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, ImageError, Pixel};
+use rayon::prelude::*;
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
 use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
 
 // generate `Statistics` struct with `CPUProfiler`s
-impl_stats_struct!(pub Statistics; load, vflip, hflip, channels, swizzle, mipmaps, dxt, save);
+impl_stats_struct!(pub Statistics; load, specgloss, vflip, hflip, channels, color, swizzle, mipmaps, dxt, save);
+
+/// Number of rows of 4x4 blocks compressed by one task. Mip-maps are split into
+/// horizontal strips of this many block rows that are compressed in parallel.
+const BLOCK_ROWS_PER_TASK: u32 = 8;
+
+/// Time it took to build the payload of one mip-map. Mip-maps are compressed
+/// concurrently, so the times overlap.
+#[derive(Debug)]
+pub struct MipMapTiming {
+    pub width: u32,
+    pub height: u32,
+    pub time: Duration,
+}
+
+/// Statistics of a finished conversion.
+#[derive(Debug)]
+pub struct Report {
+    pub stats: Statistics<'static>,
+    /// Timings of mip-maps from the biggest one. Empty when nothing was compressed
+    /// (eg. the input blocks were passed through).
+    pub mipmaps: Vec<MipMapTiming>,
+}
 
 #[derive(Debug)]
 pub enum Img2BfError {
@@ -20,25 +50,86 @@ pub enum Img2BfError {
     SerializationError(bf::LoadError),
     SaveIOError(std::io::Error),
     InvalidSwizzle(&'static str),
+    InvalidSpecGlossInput(&'static str),
+    InvalidVirtualTexture(VirtualTextureError),
+    InputContainerError(ContainerError),
+    ThreadPoolError(ThreadPoolBuildError),
+    InputFloatImageError(FloatImageError),
+    UnsupportedFloatOperation(&'static str),
+    UnsupportedColorConversion(&'static str),
 }
 
 pub struct Img2Bf {
     params: Img2BfParameters,
     stats: Statistics<'static>,
+    mipmap_timings: Vec<MipMapTiming>,
 }
 
 impl Img2Bf {
-    /// Loads the image.
-    fn load_image(&mut self) -> Result<DynamicImage, Img2BfError> {
+    /// Loads the image. Images stored in DDS or KTX2 `container` are decoded from
+    /// their first mip-map.
+    fn load_image(
+        &mut self,
+        container: Option<&ContainerImage>,
+    ) -> Result<DynamicImage, Img2BfError> {
         measure_scope!(self.stats.load);
 
-        Ok(image::open(&self.params.input).map_err(Img2BfError::InputImageError)?)
+        match container {
+            Some(t) => t.decode().map_err(Img2BfError::InputContainerError),
+            None => Ok(image::open(&self.params.input).map_err(Img2BfError::InputImageError)?),
+        }
+    }
+
+    /// Reads the input if it is a DDS or KTX2 container.
+    fn load_container(&mut self) -> Result<Option<ContainerImage>, Img2BfError> {
+        measure_scope!(self.stats.load);
+
+        if !is_container(&self.params.input) {
+            return Ok(None);
+        }
+
+        let bytes = std::fs::read(&self.params.input)
+            .map_err(|e| Img2BfError::InputImageError(ImageError::IoError(e)))?;
+        read_container(&bytes)
+            .map(Some)
+            .map_err(Img2BfError::InputContainerError)
+    }
+
+    /// Returns the payload of the `container` if its blocks can be copied to the
+    /// output without recompression. That is when the requested format has the
+    /// same blocks, no operation that changes the pixels was requested and the
+    /// container stores all the mip-maps.
+    fn pass_through(&self, container: &ContainerImage) -> Option<Vec<u8>> {
+        let p = &self.params;
+        let changes_pixels = p.v_flip
+            || p.h_flip
+            || p.pack_normal_map
+            || p.spec_gloss.is_some()
+            || self.color_conversion().is_some()
+            || p.virtual_texture.is_some()
+            || p.destination_r.is_some()
+            || p.destination_g.is_some()
+            || p.destination_b.is_some()
+            || p.destination_a.is_some();
+
+        if changes_pixels || !container.same_blocks(p.format) {
+            return None;
+        }
+
+        // otherwise the image is decoded and the mip-maps are generated
+        // (eg. images without mip-maps or with sizes that are not multiples of the block size)
+        container.bf_payload()
     }
 
     /// Validates the dimensions of image and returns them as pair of `u16`.
     fn extract_dimensions(&self, image: &DynamicImage) -> Result<(u16, u16), Img2BfError> {
         let (width, height) = image.dimensions();
 
+        // virtual textures are validated when they are split into pages
+        if self.params.virtual_texture.is_some() {
+            return Ok((0, 0));
+        }
+
         if width > 65535 || height > 65535 {
             return Err(Img2BfError::InvalidDimensions(width, height));
         }
@@ -46,6 +137,32 @@ impl Img2Bf {
         Ok((width as u16, height as u16))
     }
 
+    /// Converts the specular/glossiness workflow texture to metallic/roughness
+    /// workflow texture if requested via parameters.
+    fn spec_gloss(&mut self, image: DynamicImage) -> Result<DynamicImage, Img2BfError> {
+        measure_scope!(self.stats.specgloss);
+
+        let target = match self.params.spec_gloss {
+            Some(t) => t,
+            None => return Ok(image),
+        };
+        let other = match target {
+            SpecGlossTarget::Albedo => self.params.specular.as_ref(),
+            SpecGlossTarget::Metallic => self.params.diffuse.as_ref(),
+            SpecGlossTarget::Roughness => None,
+        };
+        let other = match other {
+            Some(path) => Some(image::open(path).map_err(Img2BfError::InputImageError)?),
+            None => None,
+        };
+
+        spec_gloss_to_metal_rough(target, &image, other.as_ref()).ok_or(
+            Img2BfError::InvalidSpecGlossInput(
+                "missing --specular / --diffuse map or its dimensions differ from input",
+            ),
+        )
+    }
+
     /// Vertically flips the image if requested via parameters.
     fn v_flip(&mut self, image: DynamicImage) -> Result<DynamicImage, Img2BfError> {
         measure_scope!(self.stats.vflip);
@@ -68,20 +185,69 @@ impl Img2Bf {
         }
     }
 
-    /// Converts the `DynamicImage` into correct channel form.
+    /// Converts the `DynamicImage` into correct channel form and bit depth.
     fn convert_channels(&mut self, image: DynamicImage) -> Result<DynamicImage, Img2BfError> {
         measure_scope!(self.stats.channels);
 
-        if image.color().channel_count() != self.params.format.channels() {
-            match self.params.format.channels() {
-                1 => Ok(DynamicImage::ImageLuma8(image.to_luma8())),
-                3 => Ok(DynamicImage::ImageRgb8(image.to_rgb8())),
-                4 => Ok(DynamicImage::ImageRgba8(image.to_rgba8())),
+        let target = match self.params.format {
+            Format::R16 => ColorType::L16,
+            // two channel formats are stored (or compressed) from red & green channels
+            Format::Rg16 => ColorType::Rgba16,
+            format => match format.channels() {
+                1 => ColorType::L8,
+                2 => ColorType::Rgba8,
+                3 => ColorType::Rgb8,
+                4 => ColorType::Rgba8,
                 _ => panic!("requested output format has unsupported num of channels"),
+            },
+        };
+
+        if image.color() == target {
+            return Ok(image);
+        }
+
+        match target {
+            ColorType::L8 => Ok(DynamicImage::ImageLuma8(image.to_luma8())),
+            ColorType::L16 => Ok(DynamicImage::ImageLuma16(image.to_luma16())),
+            ColorType::Rgb8 => Ok(DynamicImage::ImageRgb8(image.to_rgb8())),
+            ColorType::Rgba16 => Ok(DynamicImage::ImageRgba16(image.to_rgba16())),
+            _ => Ok(DynamicImage::ImageRgba8(image.to_rgba8())),
+        }
+    }
+
+    /// Returns the color spaces the colors should be converted between or `None`
+    /// when no conversion was requested or the spaces are the same.
+    fn color_conversion(&self) -> Option<(ColorSpace, ColorSpace)> {
+        let from = self.params.input_color_space?;
+        let to = self
+            .params
+            .output_color_space
+            .unwrap_or_else(|| default_color_space(self.params.format));
+
+        if from == to {
+            return None;
+        }
+        Some((from, to))
+    }
+
+    /// Converts colors of the image between color spaces. Alpha is not changed.
+    fn convert_color_space(&mut self, image: &mut DynamicImage) -> Result<(), Img2BfError> {
+        let (from, to) = match self.color_conversion() {
+            None => return Ok(()),
+            Some(t) => t,
+        };
+        measure_scope!(self.stats.color);
+
+        match image {
+            DynamicImage::ImageRgb8(t) => convert_colors_u8(t, 3, from, to),
+            DynamicImage::ImageRgba8(t) => convert_colors_u8(t, 4, from, to),
+            _ => {
+                return Err(Img2BfError::UnsupportedColorConversion(
+                    "color space conversion needs RGB or RGBA format",
+                ))
             }
-        } else {
-            Ok(image)
         }
+        Ok(())
     }
 
     /// Swizzles the channels in the image according to parameters.
@@ -208,12 +374,35 @@ impl Img2Bf {
         Ok(mipmaps)
     }
 
-    /// Performs the image block compression to specified `target_format`. Parameters
-    /// `width` and `height` represent width and height of image data in parameter
-    /// `raw`.
+    /// Performs the image block compression to specified `target_format`. The image
+    /// is split into horizontal strips of `BLOCK_ROWS_PER_TASK` block rows which are
+    /// compressed in parallel on the current thread pool. Blocks are stored row by
+    /// row, so the compressed strips are simply concatenated.
+    fn compress_image(target_format: Format, image: &DynamicImage) -> Result<Vec<u8>, Img2BfError> {
+        let strip_height = BLOCK_ROWS_PER_TASK * 4;
+        let strips = (0..image.height())
+            .step_by(strip_height as usize)
+            .collect::<Vec<_>>();
+
+        let compressed = strips
+            .par_iter()
+            .map(|y| {
+                let height = strip_height.min(image.height() - y);
+                let strip = image.crop_imm(0, *y, image.width(), height);
+                Img2Bf::compress_blocks(target_format, &strip)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(compressed.concat())
+    }
+
+    /// Compresses the whole `image` to the `target_format` on the calling thread.
     ///
     /// Depending on the `target_format` best encoder will be used.
-    fn compress_image(target_format: Format, image: &DynamicImage) -> Result<Vec<u8>, Img2BfError> {
+    fn compress_blocks(
+        target_format: Format,
+        image: &DynamicImage,
+    ) -> Result<Vec<u8>, Img2BfError> {
         // image-rs dxt encoder function
         let image_dxt = |variant| {
             let mut storage: Vec<u8> = vec![];
@@ -237,6 +426,20 @@ impl Img2Bf {
         let intel_tex_bc7 =
             |settings| intel_tex::bc7::compress_blocks(&settings, &intel_tex_surface());
 
+        let intel_tex_bc5 = || {
+            let rg = rgba_image
+                .pixels()
+                .flat_map(|p| vec![p[0], p[1]])
+                .collect::<Vec<u8>>();
+
+            intel_tex::bc5::compress_blocks(&intel_tex::RgSurface {
+                data: &rg,
+                width: image.width(),
+                height: image.height(),
+                stride: image.width() * 2,
+            })
+        };
+
         // match the requested format and compress with best encoder for specified
         // format.
         let result = match target_format {
@@ -250,8 +453,9 @@ impl Img2Bf {
             Format::BC7 => intel_tex_bc7(intel_tex::bc7::alpha_slow_settings()),
             Format::SrgbBC7 => intel_tex_bc7(intel_tex::bc7::opaque_slow_settings()),
             Format::BC6H => intel_tex_bc6h(intel_tex::bc6h::slow_settings()),
+            Format::BC5 => intel_tex_bc5(),
             _ => panic!(
-                "Format {:?} is not compressed but `compress_image` was called.",
+                "Format {:?} is not compressed but `compress_blocks` was called.",
                 target_format
             ),
         };
@@ -284,24 +488,68 @@ impl Img2Bf {
         }
     }
 
+    /// Returns the pixels of the `image` converted to the uncompressed `format`.
+    /// Channels of 16-bit formats are stored in little-endian order.
+    fn uncompressed_bytes(format: Format, image: &DynamicImage) -> Vec<u8> {
+        let mut bytes = vec![];
+        match (format, image) {
+            (Format::R16, DynamicImage::ImageLuma16(t)) => {
+                for x in t.iter() {
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+            (Format::Rg16, DynamicImage::ImageRgba16(t)) => {
+                for p in t.pixels() {
+                    bytes.extend_from_slice(&p[0].to_le_bytes());
+                    bytes.extend_from_slice(&p[1].to_le_bytes());
+                }
+            }
+            _ => bytes = image.to_bytes(),
+        }
+        bytes
+    }
+
     /// Builds the payload of specified mip-maps by:
-    ///   1. compressing them with requested block compression algorithm
+    ///   1. compressing them with requested block compression algorithm (all
+    ///      mip-maps in parallel on a pool of `--threads` threads)
     ///   2. appending them to `Vec<u8>`
     /// The function returns the resulting payload.
     fn build_payload(&mut self, mipmaps: Vec<DynamicImage>) -> Result<Vec<u8>, Img2BfError> {
         measure_scope!(self.stats.dxt);
 
-        let mut payload = vec![];
-        for img in mipmaps {
-            // if the target format is compressed we need to compress raw image
-            // data before appending it to payload
-            let result = if self.params.format.compressed() {
-                Img2Bf::compress_image(self.params.format, &img)?
-            } else {
-                img.to_bytes()
-            };
+        let format = self.params.format;
+        // zero threads means the default (number of logical cores)
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.params.threads.unwrap_or(0))
+            .build()
+            .map_err(Img2BfError::ThreadPoolError)?;
+
+        let results = pool.install(|| {
+            mipmaps
+                .par_iter()
+                .map(|img| {
+                    let start = Instant::now();
+                    // if the target format is compressed we need to compress raw image
+                    // data before appending it to payload
+                    let data = if format.compressed() {
+                        Img2Bf::compress_image(format, img)?
+                    } else {
+                        Img2Bf::uncompressed_bytes(format, img)
+                    };
+                    let timing = MipMapTiming {
+                        width: img.width(),
+                        height: img.height(),
+                        time: start.elapsed(),
+                    };
+                    Ok((data, timing))
+                })
+                .collect::<Result<Vec<_>, Img2BfError>>()
+        })?;
 
-            payload.extend(result);
+        let mut payload = vec![];
+        for (data, timing) in results {
+            payload.extend(data);
+            self.mipmap_timings.push(timing);
         }
 
         Ok(payload)
@@ -317,12 +565,21 @@ impl Img2Bf {
     ) -> Result<(), Img2BfError> {
         measure_scope!(self.stats.save);
 
-        let file = File::create_compressed(Container::Image(Image {
-            width,
-            height,
-            format: self.params.format,
-            mipmap_data: payload,
-        }));
+        let codec = self.params.codec.unwrap_or(Codec::Lz4);
+        let level = self
+            .params
+            .compression_level
+            .unwrap_or_else(|| codec.default_level());
+        let file = File::create_compressed_with(
+            Container::Image(Image {
+                width,
+                height,
+                format: self.params.format,
+                mipmap_data: payload.into(),
+            }),
+            codec,
+            level,
+        );
 
         let default_output = self.params.input.with_extension("bf");
         let save_path = self.params.output.clone().unwrap_or(default_output);
@@ -333,13 +590,53 @@ impl Img2Bf {
         Ok(())
     }
 
+    /// Splits the `image` into pages of a virtual texture and saves it uncompressed
+    /// to path specified by parameters.
+    fn save_virtual_texture(
+        &mut self,
+        image: DynamicImage,
+        page_size: u32,
+    ) -> Result<(), Img2BfError> {
+        let texture = {
+            measure_scope!(self.stats.mipmaps);
+
+            if image.width() != image.height() {
+                return Err(Img2BfError::InvalidDimensions(
+                    image.width(),
+                    image.height(),
+                ));
+            }
+
+            VirtualTexture::from_pixels(
+                self.params.format,
+                image.width(),
+                page_size,
+                self.params.page_border,
+                &image.to_bytes(),
+            )
+            .map_err(Img2BfError::InvalidVirtualTexture)?
+        };
+
+        measure_scope!(self.stats.save);
+
+        let file = File::create_uncompressed(Container::VirtualTexture(texture));
+        let default_output = self.params.input.with_extension("bf");
+        let save_path = self.params.output.clone().unwrap_or(default_output);
+        let bytes = save_bf_to_bytes(&file).map_err(Img2BfError::SerializationError)?;
+
+        std::fs::write(save_path, bytes).map_err(Img2BfError::SaveIOError)?;
+
+        Ok(())
+    }
+
     /// Calling this method performs the conversion specified by `Img2BfParameters` parameter.
-    /// If the conversion is successful the `Statistics` object will be returned which
+    /// If the conversion is successful the `Report` object will be returned which
     /// contains statistic information about the conversion. Error will be returned otherwise.
-    pub fn convert(params: Img2BfParameters) -> Result<Statistics<'static>, Img2BfError> {
+    pub fn convert(params: Img2BfParameters) -> Result<Report, Img2BfError> {
         let mut tool = Img2Bf {
             params,
             stats: Statistics::default(),
+            mipmap_timings: vec![],
         };
 
         if tool.params.pack_normal_map {
@@ -349,32 +646,164 @@ impl Img2Bf {
             tool.params.destination_a = Some("r".to_string());
         }
 
-        let image = tool.load_image()?;
+        let container = tool.load_container()?;
+        if let Some(c) = container.as_ref() {
+            if let Some(payload) = tool.pass_through(c) {
+                if c.width > 65535 || c.height > 65535 {
+                    return Err(Img2BfError::InvalidDimensions(c.width, c.height));
+                }
+                tool.save_bf_image(c.width as u16, c.height as u16, payload)?;
+                return Ok(tool.report());
+            }
+        }
+
+        if matches!(tool.params.format, Format::Rgba16F | Format::Rgba32F) {
+            return tool.convert_float(container.as_ref());
+        }
+
+        let image = tool.load_image(container.as_ref())?;
+        let image = tool.spec_gloss(image)?;
         let (width, height) = tool.extract_dimensions(&image)?;
         let image = tool.v_flip(image)?;
         let image = tool.h_flip(image)?;
         let mut image = tool.convert_channels(image)?;
 
+        tool.convert_color_space(&mut image)?;
         tool.swizzle(&mut image)?;
 
         if tool.params.pack_normal_map {
             Img2Bf::clear_channels(&mut image, &[0, 2]);
         }
 
+        if let Some(page_size) = tool.params.virtual_texture {
+            tool.save_virtual_texture(image, page_size)?;
+            return Ok(tool.report());
+        }
+
         let mipmaps = tool.generate_mipmaps(image)?;
         let payload = tool.build_payload(mipmaps)?;
 
         tool.save_bf_image(width, height, payload)?;
 
-        Ok(tool.stats)
+        Ok(tool.report())
     }
+
+    /// Converts the input to uncompressed floating point `Rgba16F` or `Rgba32F`
+    /// format. Only flips and mip-map generation are supported for these formats.
+    fn convert_float(mut self, container: Option<&ContainerImage>) -> Result<Report, Img2BfError> {
+        let p = &self.params;
+        if p.spec_gloss.is_some() || p.virtual_texture.is_some() {
+            return Err(Img2BfError::UnsupportedFloatOperation(
+                "spec/gloss conversion and virtual textures need 8-bit formats",
+            ));
+        }
+        if p.destination_r.is_some()
+            || p.destination_g.is_some()
+            || p.destination_b.is_some()
+            || p.destination_a.is_some()
+        {
+            return Err(Img2BfError::UnsupportedFloatOperation(
+                "swizzle unsupported for floating point formats",
+            ));
+        }
+
+        let mut image = {
+            measure_scope!(self.stats.load);
+            match container {
+                Some(t) => crate::float::from_dynamic(
+                    &t.decode().map_err(Img2BfError::InputContainerError)?,
+                ),
+                None => crate::float::open(&self.params.input)
+                    .map_err(Img2BfError::InputFloatImageError)?,
+            }
+        };
+
+        let (width, height) = image.dimensions();
+        if width > 65535 || height > 65535 {
+            return Err(Img2BfError::InvalidDimensions(width, height));
+        }
+
+        if let Some((from, to)) = self.color_conversion() {
+            measure_scope!(self.stats.color);
+            image.par_chunks_mut(4).for_each(|p| {
+                let c = from.convert([p[0], p[1], p[2]], to);
+                p[..3].copy_from_slice(&c);
+            });
+        }
+        if self.params.v_flip {
+            measure_scope!(self.stats.vflip);
+            image::imageops::flip_vertical_in_place(&mut image);
+        }
+        if self.params.h_flip {
+            measure_scope!(self.stats.hflip);
+            image::imageops::flip_horizontal_in_place(&mut image);
+        }
+
+        let mipmaps = {
+            measure_scope!(self.stats.mipmaps);
+            let filter = self.params.mip_filter.unwrap_or(FilterType::Lanczos3);
+            let mut mipmaps: Vec<Rgba32FImage> = vec![image];
+            while mipmaps.last().unwrap().width() > 4 {
+                let higher = mipmaps.last().unwrap();
+                let lower = image::imageops::resize(
+                    higher,
+                    higher.width() / 2,
+                    higher.height() / 2,
+                    filter,
+                );
+                mipmaps.push(lower);
+            }
+            mipmaps
+        };
+
+        let format = self.params.format;
+        let payload = mipmaps
+            .iter()
+            .flat_map(|x| crate::float::to_bytes(format, x))
+            .collect();
+        self.save_bf_image(width as u16, height as u16, payload)?;
+
+        Ok(self.report())
+    }
+
+    fn report(self) -> Report {
+        Report {
+            stats: self.stats,
+            mipmaps: self.mipmap_timings,
+        }
+    }
+}
+
+/// Returns the color space colors of the `format` are stored in.
+fn default_color_space(format: Format) -> ColorSpace {
+    match format {
+        Format::SrgbDxt1
+        | Format::SrgbDxt3
+        | Format::SrgbDxt5
+        | Format::SrgbBC7
+        | Format::Srgb8
+        | Format::Srgb8A8 => ColorSpace::Srgb,
+        _ => ColorSpace::LinearRec709,
+    }
+}
+
+/// Converts the first three channels of pixels with `channels` 8-bit channels
+/// between color spaces.
+fn convert_colors_u8(data: &mut [u8], channels: usize, from: ColorSpace, to: ColorSpace) {
+    data.par_chunks_mut(channels).for_each(|p| {
+        let unorm = |x: u8| x as f32 / 255.0;
+        let c = from.convert([unorm(p[0]), unorm(p[1]), unorm(p[2])], to);
+        for (x, c) in p.iter_mut().zip(c.iter()) {
+            *x = (c.max(0.0).min(1.0) * 255.0).round() as u8;
+        }
+    });
 }
 
 impl Tool for Img2Bf {
     type Params = Img2BfParameters;
-    type Result = Result<Statistics<'static>, Img2BfError>;
+    type Result = Result<Report, Img2BfError>;
 
-    fn execute(&self, params: Self::Params) -> Result<Statistics<'static>, Img2BfError> {
+    fn execute(&self, params: Self::Params) -> Result<Report, Img2BfError> {
         Img2Bf::convert(params)
     }
 }
@@ -0,0 +1,248 @@
+//! Baking of material maps into the flattened texture set.
+//!
+//! Maps are sampled in texture space of the baked material, which covers
+//! `tiling` repetitions of the source maps. Sources are downscaled to the size
+//! of one repetition first, so textures tiled many times don't alias. Color is
+//! filtered and multiplied in linear space and stored as sRGB again.
+//!
+//! Displacement (parallax) maps are not baked, because the parallax offset
+//! depends on the view direction.
+
+use crate::MatBakeParameters;
+use core::color;
+use image::imageops::FilterType;
+use image::{GrayImage, ImageBuffer, ImageError, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+
+/// Size of maps of material properties that are constant over the surface.
+const CONSTANT_SIZE: u32 = 4;
+
+#[derive(Debug)]
+pub enum BakeError {
+    InvalidResolution(u32),
+    InputImageError(PathBuf, ImageError),
+    OutputDirError(std::io::Error),
+    SaveError(PathBuf, ImageError),
+}
+
+impl std::fmt::Display for BakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BakeError::InvalidResolution(x) => write!(f, "invalid resolution {}", x),
+            BakeError::InputImageError(p, e) => write!(f, "cannot load {}: {}", p.display(), e),
+            BakeError::OutputDirError(e) => write!(f, "cannot create output directory: {}", e),
+            BakeError::SaveError(p, e) => write!(f, "cannot save {}: {}", p.display(), e),
+        }
+    }
+}
+
+fn to_u8(x: f32) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Source map with values in range `0..1` (linear for color maps).
+struct Map {
+    image: ImageBuffer<Rgba<f32>, Vec<f32>>,
+}
+
+impl Map {
+    /// Loads the map at `path` and downscales it to the size of one repetition
+    /// of the map in the baked texture.
+    fn load(path: &Path, srgb: bool, resolution: u32, tiling: [f32; 2]) -> Result<Self, BakeError> {
+        let source = image::open(path)
+            .map_err(|e| BakeError::InputImageError(path.to_path_buf(), e))?
+            .to_rgba8();
+
+        let image = ImageBuffer::from_fn(source.width(), source.height(), |x, y| {
+            let [r, g, b, a] = source.get_pixel(x, y).0;
+            let [r, g, b] = [r, g, b].map(|c| {
+                let c = c as f32 / 255.0;
+                if srgb {
+                    color::srgb_to_linear(c)
+                } else {
+                    c
+                }
+            });
+            Rgba([r, g, b, a as f32 / 255.0])
+        });
+
+        let width = ((resolution as f32 / tiling[0]).ceil() as u32).max(1);
+        let height = ((resolution as f32 / tiling[1]).ceil() as u32).max(1);
+        let image = if width < image.width() || height < image.height() {
+            image::imageops::resize(
+                &image,
+                width.min(image.width()),
+                height.min(image.height()),
+                FilterType::Triangle,
+            )
+        } else {
+            image
+        };
+
+        Ok(Self { image })
+    }
+
+    /// Samples the map at texture coordinates `u`, `v` with bilinear filtering
+    /// and repeat addressing.
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let (width, height) = self.image.dimensions();
+        let x = u * width as f32 - 0.5;
+        let y = v * height as f32 - 0.5;
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+        let x0 = (x.floor() as i64).rem_euclid(width as i64) as u32;
+        let y0 = (y.floor() as i64).rem_euclid(height as i64) as u32;
+        let x1 = (x0 + 1) % width;
+        let y1 = (y0 + 1) % height;
+
+        let p = |x, y| self.image.get_pixel(x, y).0;
+        let (a, b, c, d) = (p(x0, y0), p(x1, y0), p(x0, y1), p(x1, y1));
+
+        let mut result = [0.0; 4];
+        for i in 0..4 {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            result[i] = top + (bottom - top) * fy;
+        }
+        result
+    }
+}
+
+fn load_optional(
+    path: &Option<PathBuf>,
+    srgb: bool,
+    params: &MatBakeParameters,
+) -> Result<Option<Map>, BakeError> {
+    path.as_ref()
+        .map(|p| Map::load(p, srgb, params.resolution, params.tiling))
+        .transpose()
+}
+
+/// Target texture of the baked material.
+struct Target<'a> {
+    params: &'a MatBakeParameters,
+    files: Vec<PathBuf>,
+}
+
+impl<'a> Target<'a> {
+    /// Returns the size of the baked map, which is small when the baked
+    /// property is constant.
+    fn size(&self, constant: bool) -> u32 {
+        if constant {
+            CONSTANT_SIZE
+        } else {
+            self.params.resolution
+        }
+    }
+
+    /// Returns texture coordinates of the source maps at the center of pixel
+    /// `x`, `y` of the baked map of `size`.
+    fn uv(&self, size: u32, x: u32, y: u32) -> (f32, f32) {
+        (
+            (x as f32 + 0.5) / size as f32 * self.params.tiling[0],
+            (y as f32 + 0.5) / size as f32 * self.params.tiling[1],
+        )
+    }
+
+    fn save<P, C>(&mut self, suffix: &str, image: ImageBuffer<P, C>) -> Result<(), BakeError>
+    where
+        P: image::Pixel<Subpixel = u8> + 'static,
+        C: std::ops::Deref<Target = [u8]>,
+    {
+        let path = self
+            .params
+            .output_dir
+            .join(format!("{}_{}.png", self.params.name, suffix));
+        image
+            .save(&path)
+            .map_err(|e| BakeError::SaveError(path.clone(), e))?;
+        self.files.push(path);
+        Ok(())
+    }
+
+    /// Bakes a single channel map from the red channel of the `map` multiplied
+    /// by the `factor`.
+    fn bake_scalar(
+        &mut self,
+        suffix: &str,
+        map: Option<Map>,
+        factor: f32,
+    ) -> Result<(), BakeError> {
+        let size = self.size(map.is_none());
+        let image: GrayImage = ImageBuffer::from_fn(size, size, |x, y| {
+            let (u, v) = self.uv(size, x, y);
+            let value = map.as_ref().map_or(1.0, |m| m.sample(u, v)[0]);
+            Luma([to_u8(value * factor)])
+        });
+        self.save(suffix, image)
+    }
+}
+
+/// Bakes maps of the material described by `params` into its output directory.
+/// Returns paths of the written files.
+pub fn bake_material(params: &MatBakeParameters) -> Result<Vec<PathBuf>, BakeError> {
+    if params.resolution == 0 || params.resolution > 16384 {
+        return Err(BakeError::InvalidResolution(params.resolution));
+    }
+    std::fs::create_dir_all(&params.output_dir).map_err(BakeError::OutputDirError)?;
+
+    let mut target = Target {
+        params,
+        files: vec![],
+    };
+
+    // albedo with the color and ambient occlusion multiplied in
+    let albedo = load_optional(&params.albedo_map, true, params)?;
+    let ao = load_optional(&params.ao_map, false, params)?;
+    let albedo_color = params.albedo_color.unwrap_or([1.0, 1.0, 1.0]);
+    let size = target.size(albedo.is_none() && ao.is_none());
+    let image: RgbaImage = ImageBuffer::from_fn(size, size, |x, y| {
+        let (u, v) = target.uv(size, x, y);
+        let [r, g, b, a] = albedo.as_ref().map_or([1.0; 4], |m| m.sample(u, v));
+        let occlusion = ao.as_ref().map_or(1.0, |m| m.sample(u, v)[0]);
+        Rgba([
+            to_u8(color::linear_to_srgb(r * albedo_color[0] * occlusion)),
+            to_u8(color::linear_to_srgb(g * albedo_color[1] * occlusion)),
+            to_u8(color::linear_to_srgb(b * albedo_color[2] * occlusion)),
+            to_u8(a),
+        ])
+    });
+    target.save("albedo", image)?;
+
+    // normals are renormalized after filtering, flat surfaces need no map
+    if let Some(normal) = load_optional(&params.normal_map, false, params)? {
+        let size = params.resolution;
+        let image: RgbImage = ImageBuffer::from_fn(size, size, |x, y| {
+            let (u, v) = target.uv(size, x, y);
+            let [r, g, b, _] = normal.sample(u, v);
+            let n = [r * 2.0 - 1.0, g * 2.0 - 1.0, b * 2.0 - 1.0];
+            let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            let n = if length > 1e-6 {
+                n.map(|c| c / length)
+            } else {
+                [0.0, 0.0, 1.0]
+            };
+            Rgb(n.map(|c| to_u8(c * 0.5 + 0.5)))
+        });
+        target.save("normal", image)?;
+    }
+
+    // factors default to the same values as in `matcomp`
+    let roughness = load_optional(&params.roughness_map, false, params)?;
+    let factor = params
+        .roughness
+        .unwrap_or(if roughness.is_none() { 0.5 } else { 1.0 });
+    target.bake_scalar("roughness", roughness, factor)?;
+
+    let metallic = load_optional(&params.metallic_map, false, params)?;
+    let factor = params
+        .metallic
+        .unwrap_or(if metallic.is_none() { 0.0 } else { 1.0 });
+    target.bake_scalar("metallic", metallic, factor)?;
+
+    // the opacity factor is kept in the material, so only the map is baked
+    if let Some(opacity) = load_optional(&params.opacity_map, false, params)? {
+        target.bake_scalar("opacity", Some(opacity), 1.0)?;
+    }
+
+    Ok(target.files)
+}
@@ -0,0 +1,104 @@
+use crate::bake::bake_material;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+mod bake;
+
+/// Bakes a material into a flattened texture set: constant factors, ambient
+/// occlusion and tiling are applied to the maps, so the result can be used as
+/// a simple material (eg. for distant LODs) without them.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "matbake")]
+pub struct MatBakeParameters {
+    /// Directory the baked maps are written to
+    #[structopt(short, long, parse(from_os_str))]
+    output_dir: PathBuf,
+
+    /// Prefix of names of the baked maps (eg. "rock" results in "rock_albedo.png")
+    #[structopt(short, long)]
+    name: String,
+
+    /// Width and height of the baked maps
+    #[structopt(short, long, default_value = "512")]
+    resolution: u32,
+
+    /// Number of repetitions of the maps over the baked texture in U and V direction
+    #[structopt(long, parse(try_from_str = parse_tiling), default_value = "1,1")]
+    tiling: [f32; 2],
+
+    #[structopt(long, parse(try_from_str = parse_color))]
+    albedo_color: Option<[f32; 3]>,
+
+    #[structopt(long)]
+    roughness: Option<f32>,
+
+    #[structopt(long)]
+    metallic: Option<f32>,
+
+    /// Albedo map (.png, .jpg, ...)
+    #[structopt(long, parse(from_os_str))]
+    albedo_map: Option<PathBuf>,
+
+    /// Normal map (.png, .jpg, ...)
+    #[structopt(long, parse(from_os_str))]
+    normal_map: Option<PathBuf>,
+
+    /// Roughness map (.png, .jpg, ...)
+    #[structopt(long, parse(from_os_str))]
+    roughness_map: Option<PathBuf>,
+
+    /// Metallic map (.png, .jpg, ...)
+    #[structopt(long, parse(from_os_str))]
+    metallic_map: Option<PathBuf>,
+
+    /// Ambient occlusion map (.png, .jpg, ...), multiplied into the baked albedo
+    #[structopt(long, parse(from_os_str))]
+    ao_map: Option<PathBuf>,
+
+    /// Opacity map (.png, .jpg, ...)
+    #[structopt(long, parse(from_os_str))]
+    opacity_map: Option<PathBuf>,
+}
+
+fn parse_color(src: &str) -> Result<[f32; 3], &'static str> {
+    let parts = src
+        .split(',')
+        .map(|x| x.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "cannot parse float")?;
+
+    match parts.as_slice() {
+        [r, g, b] => Ok([*r, *g, *b]),
+        _ => Err("color must have three components"),
+    }
+}
+
+fn parse_tiling(src: &str) -> Result<[f32; 2], &'static str> {
+    let parts = src
+        .split(',')
+        .map(|x| x.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "cannot parse float")?;
+
+    match parts.as_slice() {
+        [t] if *t > 0.0 => Ok([*t, *t]),
+        [u, v] if *u > 0.0 && *v > 0.0 => Ok([*u, *v]),
+        _ => Err("tiling must be one or two positive numbers"),
+    }
+}
+
+fn main() {
+    let params = MatBakeParameters::from_args();
+
+    let files = match bake_material(&params) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("baking failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for x in files {
+        println!("{}", x.display());
+    }
+}
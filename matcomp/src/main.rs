@@ -1,4 +1,4 @@
-use bf::material::{BlendMode, Material};
+use bf::material::{BlendMode, Material, MaterialFlags};
 use bf::{save_bf_to_bytes, Container, File};
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -20,6 +20,11 @@ pub struct MatCompParameters {
     #[structopt(long)]
     roughness: Option<f32>,
 
+    /// Glossiness of legacy specular/glossiness material, used as `1 - glossiness`
+    /// roughness when roughness is not specified
+    #[structopt(long)]
+    glossiness: Option<f32>,
+
     #[structopt(long)]
     metallic: Option<f32>,
 
@@ -35,6 +40,33 @@ pub struct MatCompParameters {
     #[structopt(long)]
     sss: Option<f32>,
 
+    /// Linear color of emitted light, components may be above 1.0 for surfaces
+    /// bright enough to bloom
+    #[structopt(long, parse(try_from_str = parse_color))]
+    emissive_color: Option<[f32; 3]>,
+
+    /// Strength of the transparent clear coat layer (eg. car paint), enables
+    /// the clear coat shading when above zero
+    #[structopt(long)]
+    clear_coat: Option<f32>,
+
+    #[structopt(long)]
+    clear_coat_roughness: Option<f32>,
+
+    /// Stretching of the specular highlight along the tangent (positive) or
+    /// the bitangent (negative) in range [-1, 1] (eg. brushed metal), enables
+    /// the anisotropic shading when not zero
+    #[structopt(long)]
+    anisotropy: Option<f32>,
+
+    /// Material of foliage that is animated by wind (requires mesh with stiffness).
+    #[structopt(long)]
+    foliage: bool,
+
+    /// Material that is visible from both sides (back faces are not culled).
+    #[structopt(long)]
+    double_sided: bool,
+
     #[structopt(long)]
     albedo_map: Option<String>,
 
@@ -55,6 +87,9 @@ pub struct MatCompParameters {
 
     #[structopt(long)]
     metallic_map: Option<String>,
+
+    #[structopt(long)]
+    emissive_map: Option<String>,
 }
 
 fn parse_blend_mode(src: &str) -> Result<BlendMode, &'static str> {
@@ -81,13 +116,33 @@ fn parse_uuid(str: Option<String>) -> Option<Uuid> {
     str.map(|x| Uuid::parse_str(x.as_str()).expect("cannot parse uuid"))
 }
 
+/// Returns flags of the material selected by the parameters.
+fn flags(params: &MatCompParameters) -> MaterialFlags {
+    let mut flags = MaterialFlags::empty();
+    if params.foliage {
+        flags.insert(MaterialFlags::FOLIAGE);
+    }
+    if params.double_sided {
+        flags.insert(MaterialFlags::DOUBLE_SIDED);
+    }
+    if params.clear_coat.unwrap_or(0.0) > 0.0 {
+        flags.insert(MaterialFlags::CLEAR_COAT);
+    }
+    if params.anisotropy.unwrap_or(0.0) != 0.0 {
+        flags.insert(MaterialFlags::ANISOTROPIC);
+    }
+    flags
+}
+
 fn main() {
     let params = MatCompParameters::from_args();
+    let flags = flags(&params);
     let material = Material {
         blend_mode: params.blend_mode.unwrap_or(BlendMode::Opaque),
         albedo_color: params.albedo_color.unwrap_or([1.0, 1.0, 1.0]),
         roughness: params
             .roughness
+            .or_else(|| params.glossiness.map(|x| 1.0 - x))
             .unwrap_or(if params.roughness_map.is_none() {
                 0.5
             } else {
@@ -101,6 +156,16 @@ fn main() {
         opacity: params.opacity.unwrap_or(1.0),
         ior: params.opacity.unwrap_or(1.0),
         sss: params.sss.unwrap_or(0.0),
+        emissive_color: params
+            .emissive_color
+            .unwrap_or(if params.emissive_map.is_none() {
+                [0.0, 0.0, 0.0]
+            } else {
+                [1.0, 1.0, 1.0]
+            }),
+        clear_coat: params.clear_coat.unwrap_or(0.0),
+        clear_coat_roughness: params.clear_coat_roughness.unwrap_or(0.1),
+        anisotropy: params.anisotropy.unwrap_or(0.0).max(-1.0).min(1.0),
         alpha_cutoff: params.alpha_cutoff.unwrap_or(0.5),
         albedo_map: parse_uuid(params.albedo_map),
         normal_map: parse_uuid(params.normal_map),
@@ -109,6 +174,8 @@ fn main() {
         ao_map: parse_uuid(params.ao_map),
         metallic_map: parse_uuid(params.metallic_map),
         opacity_map: parse_uuid(params.opacity_map),
+        emissive_map: parse_uuid(params.emissive_map),
+        flags,
     };
 
     let file = File::create_uncompressed(Container::Material(material));
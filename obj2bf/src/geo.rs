@@ -1,7 +1,9 @@
 use crate::format::VertexFormatExt;
 use crate::math::Vec3;
-use bf::mesh::{IndexType, VertexFormat};
+use crate::occlusion::compute_sky_occlusion;
+use bf::mesh::{pack_sky_occlusion, IndexType, Lod, VertexFormat};
 use byteorder::{LittleEndian, WriteBytesExt};
+use meshopt::VertexDataAdapter;
 use ordered_float::{FloatIsNan, NotNan};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
@@ -9,12 +11,26 @@ use std::convert::TryFrom;
 use wavefront_obj::obj::Primitive::Triangle;
 use wavefront_obj::obj::{Object, TVertex, Vertex};
 
+// fraction of the screen covered by the mesh above which the original geometry is used
+const FULL_DETAIL_COVERAGE: f32 = 0.1;
+
+// maximal error of simplified levels relative to the size of the mesh
+const LOD_TARGET_ERROR: f32 = 0.05;
+
+// size of the simulated post-transform cache used to compute ACMR
+const VERTEX_CACHE_SIZE: u32 = 16;
+
+// how much the overdraw optimization may degrade the vertex cache efficiency
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
 #[derive(Default)]
 pub struct Geometry {
     pub positions: Vec<Vec3<f64>>,
     pub normals: Vec<Vec3<f64>>,
     pub tex_coords: Vec<Vec3<f64>>,
     pub tangents: Vec<Vec3<f64>>,
+    /* bent normals scaled by sky visibility, empty when not computed */
+    pub sky_occlusion: Vec<Vec3<f64>>,
     /* 3 consecutive values represent one triangle (when correctly aligned) */
     pub indices: Vec<usize>,
 }
@@ -85,6 +101,34 @@ impl Geometry {
         self.tangents.iter_mut().for_each(|it| it.normalize());
     }
 
+    /// Computes sky visibility and bent normals of vertices by casting `rays`
+    /// rays from each vertex against the geometry.
+    pub fn compute_sky_occlusion(&mut self, rays: usize) {
+        self.sky_occlusion =
+            compute_sky_occlusion(&self.positions, &self.normals, &self.indices, rays.max(1));
+    }
+
+    /// Encodes stiffness of vertices for wind animation as little-endian `f32`
+    /// values. The lowest vertex of the geometry is rigid (`1.0`) and the
+    /// stiffness decreases linearly with height to `0.0` at the highest one.
+    pub fn generate_stiffness_data(&self) -> Vec<u8> {
+        let min = self.positions.iter().map(|x| x.y).fold(f64::MAX, f64::min);
+        let max = self.positions.iter().map(|x| x.y).fold(f64::MIN, f64::max);
+        let height = max - min;
+
+        let mut buf = Vec::with_capacity(self.positions.len() * std::mem::size_of::<f32>());
+        for pos in self.positions.iter() {
+            let stiffness = if height > 0.0 {
+                1.0 - (pos.y - min) / height
+            } else {
+                1.0
+            };
+            buf.write_f32::<LittleEndian>(stiffness as f32)
+                .expect("cannot write f32");
+        }
+        buf
+    }
+
     /// Generates and .OBJ format representation of this geometry. The
     /// resulting OBJ file is returned as String.
     pub fn to_obj(&self) -> String {
@@ -143,7 +187,8 @@ impl Geometry {
             .zip(nor_iter)
             .zip(uv_iter)
             .zip(tan_iter)
-            .for_each(|(((pos, nor), uv), tan)| {
+            .enumerate()
+            .for_each(|(idx, (((pos, nor), uv), tan))| {
                 if format.has_position() {
                     buf.write_f32::<LittleEndian>(pos.x as f32)
                         .expect("cannot write f32");
@@ -178,6 +223,20 @@ impl Geometry {
                         .expect("cannot write f32");
                 }
 
+                if format.has_tangents() && !self.sky_occlusion.is_empty() {
+                    // sky occlusion is stored in the padding after tangent
+                    let sky = &self.sky_occlusion[idx];
+                    let visibility = sky.length();
+                    let bent = if visibility > 0.0 { sky } else { nor };
+                    let packed = pack_sky_occlusion(
+                        visibility as f32,
+                        [bent.x as f32, bent.y as f32, bent.z as f32],
+                    );
+                    buf.write_f32::<LittleEndian>(packed)
+                        .expect("cannot write f32");
+                    return;
+                }
+
                 for _ in 0..format.padding_length() {
                     buf.write_u8(0) // padding
                         .expect("cannot write f32");
@@ -187,6 +246,123 @@ impl Geometry {
         buf
     }
 
+    /// Generates up to `count - 1` simplified levels of detail, each with about half of the
+    /// triangles of the previous one, and appends their indices after the indices of the
+    /// original geometry. Returns all levels including the original one.
+    ///
+    /// Levels are used while the number of their triangles relative to the original is
+    /// smaller than the screen coverage relative to `FULL_DETAIL_COVERAGE`. Generation stops
+    /// early when the simplification cannot remove enough triangles without exceeding the
+    /// `LOD_TARGET_ERROR`.
+    pub fn generate_lods(&mut self, count: usize) -> Vec<Lod> {
+        let positions = self.position_data();
+        let vertices = VertexDataAdapter::new(&positions, 12, 0).expect("invalid vertex data");
+        let original = self.indices.iter().map(|x| *x as u32).collect::<Vec<_>>();
+        let mut lods = vec![Lod {
+            first_index: 0,
+            index_count: original.len() as u32,
+            min_coverage: FULL_DETAIL_COVERAGE,
+        }];
+        let mut previous = original.len();
+
+        while lods.len() < count {
+            let target = original.len() >> lods.len();
+            let simplified = meshopt::simplify(&original, &vertices, target, LOD_TARGET_ERROR);
+
+            // stop when the level would not be noticeably cheaper than the previous one
+            if simplified.is_empty() || simplified.len() * 4 > previous * 3 {
+                break;
+            }
+
+            lods.push(Lod {
+                first_index: self.indices.len() as u32,
+                index_count: simplified.len() as u32,
+                min_coverage: FULL_DETAIL_COVERAGE * simplified.len() as f32
+                    / original.len() as f32,
+            });
+            self.indices.extend(simplified.iter().map(|x| *x as usize));
+            previous = simplified.len();
+        }
+
+        // the least detailed level is used for any coverage
+        lods.last_mut().unwrap().min_coverage = 0.0;
+        lods
+    }
+
+    /// Reorders the indices of each level of detail for the post-transform vertex cache
+    /// and to reduce overdraw, then reorders the vertices in the order they are first
+    /// referenced for better vertex fetch locality (same as `meshopt_optimizeVertexFetch`). Vertices not referenced by any level
+    /// are removed.
+    ///
+    /// Returns the average cache miss ratio (ACMR) of the first level before and after
+    /// the optimization.
+    pub fn optimize(&mut self, lods: &[Lod]) -> (f32, f32) {
+        let vertex_count = self.positions.len();
+        let mut indices = self.indices.iter().map(|x| *x as u32).collect::<Vec<_>>();
+        let acmr = |indices: &[u32], lod: &Lod| {
+            let range = lod.first_index as usize..(lod.first_index + lod.index_count) as usize;
+            meshopt::analyze_vertex_cache(&indices[range], vertex_count, VERTEX_CACHE_SIZE, 0, 0)
+                .acmr
+        };
+        let before = acmr(&indices, &lods[0]);
+
+        let positions = self.position_data();
+        let vertices = VertexDataAdapter::new(&positions, 12, 0).expect("invalid vertex data");
+        for lod in lods {
+            let range = lod.first_index as usize..(lod.first_index + lod.index_count) as usize;
+            let optimized = meshopt::optimize_vertex_cache(&indices[range.clone()], vertex_count);
+            meshopt::optimize_overdraw_in_place(&optimized, &vertices, OVERDRAW_THRESHOLD);
+            indices[range].copy_from_slice(&optimized);
+        }
+
+        // vertices are numbered in order of their first use, unused vertices are dropped
+        let mut remap = vec![None; vertex_count];
+        let mut used = 0;
+        for index in indices.iter_mut() {
+            let new = *remap[*index as usize].get_or_insert_with(|| {
+                used += 1;
+                used - 1
+            });
+            *index = new as u32;
+        }
+        let reorder = |data: &mut Vec<Vec3<f64>>| {
+            if data.is_empty() {
+                return;
+            }
+            let mut reordered = data[..used].to_vec();
+            for (old, new) in remap.iter().enumerate() {
+                if let Some(new) = new {
+                    reordered[*new] = data[old];
+                }
+            }
+            *data = reordered;
+        };
+
+        reorder(&mut self.positions);
+        reorder(&mut self.normals);
+        reorder(&mut self.tex_coords);
+        reorder(&mut self.tangents);
+        reorder(&mut self.sky_occlusion);
+
+        let after = acmr(&indices, &lods[0]);
+        self.indices = indices.iter().map(|x| *x as usize).collect();
+
+        (before, after)
+    }
+
+    /// Returns positions of the vertices as tightly packed `f32` triplets.
+    fn position_data(&self) -> Vec<u8> {
+        let mut positions = Vec::with_capacity(self.positions.len() * 12);
+        for p in self.positions.iter() {
+            for x in &[p.x, p.y, p.z] {
+                positions
+                    .write_f32::<LittleEndian>(*x as f32)
+                    .expect("cannot write f32");
+            }
+        }
+        positions
+    }
+
     /// Returns the `IndexType` which is considered the best to store
     /// this geometry index data. The type returned depends on number
     /// of indices in this geometry. Current algorithm returns the
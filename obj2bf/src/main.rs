@@ -1,11 +1,13 @@
 use crate::tool::Obj2Bf;
 use bf::mesh::{IndexType, VertexFormat};
+use bf::Codec;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
 mod format;
 mod geo;
 mod math;
+mod occlusion;
 mod tool;
 
 #[derive(StructOpt, Debug)]
@@ -31,6 +33,29 @@ pub struct Obj2BfParameters {
     #[structopt(short, long)]
     lod: Option<u8>,
 
+    /// Number of levels of detail to generate including the original geometry (defaults to 4).
+    #[structopt(long)]
+    lods: Option<usize>,
+
+    /// Disables reordering of indices and vertices for GPU vertex cache, overdraw and vertex fetch.
+    #[structopt(long)]
+    no_optimize: bool,
+
+    /// Compresses vertex and index data with meshoptimizer codecs (decoded when the mesh is loaded).
+    #[structopt(long)]
+    meshopt: bool,
+
+    /// Bakes sky visibility and bent normals of vertices by casting this many rays from each
+    /// vertex against the mesh (stored in the tangent padding, `pnut` format only). Useful for
+    /// terrain and large meshes that shadow their own parts.
+    #[structopt(long)]
+    sky_occlusion: Option<usize>,
+
+    /// Stores stiffness of vertices used by wind-animated foliage materials. Vertices at the
+    /// bottom of the mesh (lowest y) are rigid and the stiffness decreases with height.
+    #[structopt(long)]
+    wind_stiffness: bool,
+
     /// Name of object to import from input file. Selects first non-empty object if not specified.
     #[structopt(long)]
     object_name: Option<String>,
@@ -39,6 +64,11 @@ pub struct Obj2BfParameters {
     #[structopt(long)]
     geometry_index: Option<usize>,
 
+    /// Converts all non-empty objects to meshes (saved as `<uuid>.bf` next to the output file)
+    /// and saves a tree referencing them as the output file.
+    #[structopt(long)]
+    all_objects: bool,
+
     /// Causes the application to inspect the input file and print all possible convert commands.
     #[structopt(short, long)]
     print_options: bool,
@@ -50,6 +80,16 @@ pub struct Obj2BfParameters {
     /// Whether to dump .obj file back after importing it. Useful for comparisons with original.
     #[structopt(short, long)]
     dump_obj: bool,
+
+    /// Codec the output file is compressed with ("lz4" or "zstd"). The "zstd" codec
+    /// produces smaller files but requires building with the `zstd` feature.
+    #[structopt(long, parse(try_from_str = parse_codec))]
+    codec: Option<Codec>,
+
+    /// Compression level of the codec (lz4: 1-12, zstd: 1-22). Uses the default
+    /// level of the codec if not specified.
+    #[structopt(long)]
+    compression_level: Option<i32>,
 }
 
 fn parse_index_type(src: &str) -> Result<IndexType, &'static str> {
@@ -69,6 +109,14 @@ fn parse_vertex_format(src: &str) -> Result<VertexFormat, &'static str> {
     }
 }
 
+fn parse_codec(src: &str) -> Result<Codec, &'static str> {
+    match src.to_lowercase().as_str() {
+        "lz4" => Ok(Codec::Lz4),
+        "zstd" => Ok(Codec::Zstd),
+        _ => Err("unknown codec"),
+    }
+}
+
 fn main() {
     let params: Obj2BfParameters = Obj2BfParameters::from_args();
 
@@ -80,6 +128,7 @@ fn main() {
         println!("load={}ms", stats.load.total_time().as_millis());
         println!("lods={}ms", stats.lods.total_time().as_millis());
         println!("normalize={}ms", stats.normalize.total_time().as_millis());
+        println!("occlusion={}ms", stats.occlusion.total_time().as_millis());
         println!("optimize={}ms", stats.optimize.total_time().as_millis());
         println!("save={}ms", stats.save.total_time().as_millis());
     }
@@ -0,0 +1,312 @@
+//! Baking of sky occlusion of vertices: the fraction of the sky visible from each
+//! vertex and its bent normal (the average unoccluded direction). Rays are cast
+//! against the geometry itself, so large meshes (terrain, buildings) are shadowed
+//! by their own parts. Occlusion by other meshes of the scene is not captured.
+
+use crate::math::Vec3;
+use rayon::prelude::*;
+
+// maximal number of triangles in a leaf of the bounding volume hierarchy
+const LEAF_TRIANGLES: usize = 4;
+
+// distance rays start above the surface relative to the size of the mesh
+const RAY_OFFSET: f64 = 1e-4;
+
+/// Axis aligned bounding box.
+#[derive(Copy, Clone)]
+struct Aabb {
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl Aabb {
+    fn empty() -> Self {
+        Self {
+            min: [f64::INFINITY; 3],
+            max: [f64::NEG_INFINITY; 3],
+        }
+    }
+
+    fn grow(&mut self, p: [f64; 3]) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(p[i]);
+            self.max[i] = self.max[i].max(p[i]);
+        }
+    }
+
+    fn largest_axis(&self) -> usize {
+        let size = |i: usize| self.max[i] - self.min[i];
+        (0..3).fold(0, |a, i| if size(i) > size(a) { i } else { a })
+    }
+
+    /// Returns whether the ray hits the box (slab test).
+    fn hit(&self, origin: [f64; 3], inv_dir: [f64; 3]) -> bool {
+        let (mut near, mut far) = (0.0f64, f64::INFINITY);
+        for i in 0..3 {
+            let t0 = (self.min[i] - origin[i]) * inv_dir[i];
+            let t1 = (self.max[i] - origin[i]) * inv_dir[i];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        near <= far
+    }
+}
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// Bounding volume hierarchy of triangles used to find any intersection of a ray.
+struct Bvh {
+    triangles: Vec<[[f64; 3]; 3]>,
+    nodes: Vec<Node>,
+}
+
+impl Bvh {
+    fn new(mut triangles: Vec<[[f64; 3]; 3]>) -> Self {
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            let count = triangles.len();
+            Self::build(&mut nodes, &mut triangles, 0, count);
+        }
+        Self { triangles, nodes }
+    }
+
+    /// Builds the node of triangles `first..first + count` by splitting them in
+    /// the middle along the largest axis of their centroids. Returns index of the node.
+    fn build(
+        nodes: &mut Vec<Node>,
+        triangles: &mut [[[f64; 3]; 3]],
+        first: usize,
+        count: usize,
+    ) -> usize {
+        let slice = &mut triangles[first..first + count];
+        let mut bounds = Aabb::empty();
+        let mut centroids = Aabb::empty();
+        for t in slice.iter() {
+            t.iter().for_each(|p| bounds.grow(*p));
+            centroids.grow(centroid(t));
+        }
+
+        let index = nodes.len();
+        if count <= LEAF_TRIANGLES {
+            nodes.push(Node::Leaf {
+                bounds,
+                first,
+                count,
+            });
+            return index;
+        }
+
+        let axis = centroids.largest_axis();
+        slice.select_nth_unstable_by(count / 2, |a, b| {
+            centroid(a)[axis].partial_cmp(&centroid(b)[axis]).unwrap()
+        });
+
+        nodes.push(Node::Leaf {
+            bounds,
+            first,
+            count,
+        });
+        let left = Self::build(nodes, triangles, first, count / 2);
+        let right = Self::build(nodes, triangles, first + count / 2, count - count / 2);
+        nodes[index] = Node::Inner {
+            bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /// Returns whether the ray hits any triangle.
+    fn occluded(&self, origin: [f64; 3], dir: [f64; 3], min_t: f64) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = [1.0 / dir[0], 1.0 / dir[1], 1.0 / dir[2]];
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            match &self.nodes[index] {
+                Node::Inner {
+                    bounds,
+                    left,
+                    right,
+                } => {
+                    if bounds.hit(origin, inv_dir) {
+                        stack.push(*left);
+                        stack.push(*right);
+                    }
+                }
+                Node::Leaf {
+                    bounds,
+                    first,
+                    count,
+                } => {
+                    if bounds.hit(origin, inv_dir)
+                        && self.triangles[*first..*first + *count]
+                            .iter()
+                            .any(|t| intersect(t, origin, dir).map_or(false, |x| x > min_t))
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+fn centroid(t: &[[f64; 3]; 3]) -> [f64; 3] {
+    let mut c = [0.0; 3];
+    for i in 0..3 {
+        c[i] = (t[0][i] + t[1][i] + t[2][i]) / 3.0;
+    }
+    c
+}
+
+fn to_array(v: &Vec3<f64>) -> [f64; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Returns the distance along the ray to the intersection with the triangle
+/// (Möller–Trumbore algorithm, both sides of the triangle are hit).
+fn intersect(t: &[[f64; 3]; 3], origin: [f64; 3], dir: [f64; 3]) -> Option<f64> {
+    let edge1 = sub(t[1], t[0]);
+    let edge2 = sub(t[2], t[0]);
+    let p = cross(dir, edge2);
+    let det = dot(edge1, p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let s = sub(origin, t[0]);
+    let u = dot(s, p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = cross(s, edge1);
+    let v = dot(dir, q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some(dot(edge2, q) / det)
+}
+
+/// Returns `i`-th of `count` cosine weighted directions on the hemisphere around
+/// the +Z axis (Hammersley sequence), so all vertices use the same directions.
+fn hemisphere_direction(i: usize, count: usize) -> [f64; 3] {
+    let u = (i as f64 + 0.5) / count as f64;
+    let v = (i as u32).reverse_bits() as f64 / (1u64 << 32) as f64;
+    let r = u.sqrt();
+    let phi = 2.0 * std::f64::consts::PI * v;
+    [r * phi.cos(), r * phi.sin(), (1.0 - u).sqrt()]
+}
+
+/// Computes sky occlusion of each vertex by casting `rays` rays into the hemisphere
+/// around its normal against triangles of `indices`. Returns bent normals scaled by
+/// the fraction of unoccluded rays (the sky visibility).
+pub fn compute_sky_occlusion(
+    positions: &[Vec3<f64>],
+    normals: &[Vec3<f64>],
+    indices: &[usize],
+    rays: usize,
+) -> Vec<Vec3<f64>> {
+    let bvh = Bvh::new(
+        indices
+            .chunks(3)
+            .map(|f| {
+                [
+                    to_array(&positions[f[0]]),
+                    to_array(&positions[f[1]]),
+                    to_array(&positions[f[2]]),
+                ]
+            })
+            .collect(),
+    );
+    let size = bvh.nodes.first().map_or(1.0, |n| match n {
+        Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => sub(bounds.max, bounds.min)
+            .iter()
+            .fold(0.0, |a, x| a + x * x)
+            .sqrt(),
+    });
+    let offset = size * RAY_OFFSET;
+    let directions = (0..rays)
+        .map(|i| hemisphere_direction(i, rays))
+        .collect::<Vec<_>>();
+
+    positions
+        .par_iter()
+        .zip(normals.par_iter())
+        .map(|(position, normal)| {
+            // orthonormal frame around the normal
+            let n = to_array(normal);
+            let helper = if n[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            let mut t = cross(helper, n);
+            let length = dot(t, t).sqrt();
+            t = [t[0] / length, t[1] / length, t[2] / length];
+            let b = cross(n, t);
+
+            let origin = to_array(position);
+            let origin = [
+                origin[0] + n[0] * offset,
+                origin[1] + n[1] * offset,
+                origin[2] + n[2] * offset,
+            ];
+
+            let mut bent = [0.0; 3];
+            let mut visible = 0;
+            for d in directions.iter() {
+                let dir = [
+                    t[0] * d[0] + b[0] * d[1] + n[0] * d[2],
+                    t[1] * d[0] + b[1] * d[1] + n[1] * d[2],
+                    t[2] * d[0] + b[2] * d[1] + n[2] * d[2],
+                ];
+                if !bvh.occluded(origin, dir, offset) {
+                    visible += 1;
+                    (0..3).for_each(|i| bent[i] += dir[i]);
+                }
+            }
+
+            if visible == 0 {
+                return Vec3::new(0.0, 0.0, 0.0);
+            }
+            let visibility = visible as f64 / rays as f64;
+            let length = dot(bent, bent).sqrt();
+            Vec3::new(
+                bent[0] / length * visibility,
+                bent[1] / length * visibility,
+                bent[2] / length * visibility,
+            )
+        })
+        .collect()
+}
@@ -1,20 +1,29 @@
 use crate::geo::{Geometry, ObjImportError};
 use crate::Obj2BfParameters;
-use bf::mesh::{Mesh, VertexFormat};
-use bf::{save_bf_to_bytes, Container, File};
+use bf::mesh::{Lod, Mesh, MeshEncoding, MeshEncodingError, VertexFormat};
+use bf::tree::{Component, Node, Tree};
+use bf::{save_bf_to_bytes, Codec, Container, File};
 use core::impl_stats_struct;
 use core::measure_scope;
 use std::convert::TryFrom;
 use std::io::Error;
+use std::path::Path;
+use uuid::Uuid;
 use wavefront_obj::obj::{parse, ObjSet, Object};
 use wavefront_obj::ParseError;
 
 // generate `Statistics` struct with `CPUProfiler`s
-impl_stats_struct!(pub Statistics; load, lods, normalize, optimize, save);
+impl_stats_struct!(pub Statistics; load, lods, normalize, occlusion, optimize, save);
 
 // default vertex format to use when no is specified
 const DEFAULT_VERTEX_FORMAT: VertexFormat = VertexFormat::PositionNormalUvTangent;
 
+// default number of levels of detail including the original geometry
+const DEFAULT_LOD_COUNT: usize = 4;
+
+// namespace of uuids of meshes exported with `--all-objects`
+const MESH_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_3b0e_8a2d_4f5e_9c47_2e8b_1d3a_5c90);
+
 #[derive(Debug)]
 pub enum Obj2BfError {
     InvalidInputFile(&'static str),
@@ -23,6 +32,7 @@ pub enum Obj2BfError {
     ObjectNotFound(String),
     CannotNormalizeObj(ObjImportError),
     NoNonEmptyGeometriesFound,
+    CannotEncodeMesh(MeshEncodingError),
     SerializationError(bf::LoadError),
     SaveIOError(std::io::Error),
 }
@@ -103,11 +113,46 @@ impl Obj2Bf {
         Ok(geometry)
     }
 
-    /// Chooses appropriate vertex and index formats and encodes the mesh and saves the output
-    /// file.
-    fn save_bf_mesh(&mut self, geo: Geometry) -> Result<(), Obj2BfError> {
-        measure_scope!(self.stats.save);
+    /// Bakes sky occlusion of the vertices if requested by parameters.
+    fn compute_sky_occlusion(&mut self, geo: &mut Geometry) {
+        let rays = match self.params.sky_occlusion {
+            Some(t) => t,
+            None => return,
+        };
+
+        measure_scope!(self.stats.occlusion);
+
+        if self.params.vertex_format.unwrap_or(DEFAULT_VERTEX_FORMAT)
+            != VertexFormat::PositionNormalUvTangent
+        {
+            println!("sky occlusion is only stored in pnut vertex format, skipping");
+            return;
+        }
+        geo.compute_sky_occlusion(rays);
+    }
 
+    /// Optimizes the geometry for vertex cache, overdraw and vertex fetch unless
+    /// disabled by parameters.
+    fn optimize(&mut self, geo: &mut Geometry, lods: &[Lod]) {
+        if self.params.no_optimize {
+            return;
+        }
+
+        measure_scope!(self.stats.optimize);
+
+        let (before, after) = geo.optimize(lods);
+        println!("acmr={:.3}->{:.3}", before, after);
+    }
+
+    /// Generates simplified levels of detail of the geometry.
+    fn generate_lods(&mut self, geo: &mut Geometry) -> Vec<Lod> {
+        measure_scope!(self.stats.lods);
+
+        geo.generate_lods(self.params.lods.unwrap_or(DEFAULT_LOD_COUNT).max(1))
+    }
+
+    /// Chooses appropriate vertex and index formats and encodes the mesh.
+    fn encode_mesh(&self, geo: &Geometry, lods: Vec<Lod>) -> Result<Mesh, Obj2BfError> {
         // choose vertex format or use default vertex format
         let vertex_format = self.params.vertex_format.unwrap_or(DEFAULT_VERTEX_FORMAT);
         let vertex_data = geo.generate_vertex_data(vertex_format);
@@ -118,23 +163,114 @@ impl Obj2Bf {
             .index_type
             .unwrap_or_else(|| geo.suggest_index_type());
         let index_data = geo.generate_index_data(index_type);
+        let stiffness = if self.params.wind_stiffness {
+            Some(geo.generate_stiffness_data().into())
+        } else {
+            None
+        };
 
-        let file = File::create_compressed(Container::Mesh(Mesh {
+        let mesh = Mesh {
             vertex_format,
             index_type,
-            vertex_data,
-            index_data,
-        }));
+            vertex_data: vertex_data.into(),
+            index_data: index_data.into(),
+            lods,
+            encoding: MeshEncoding::None,
+            stiffness,
+        };
+
+        if self.params.meshopt {
+            mesh.encode_meshopt().map_err(Obj2BfError::CannotEncodeMesh)
+        } else {
+            Ok(mesh)
+        }
+    }
+
+    /// Compresses the container and writes it to specified path.
+    fn write_bf(&mut self, path: &Path, container: Container) -> Result<(), Obj2BfError> {
+        measure_scope!(self.stats.save);
+
+        let codec = self.params.codec.unwrap_or(Codec::Lz4);
+        let level = self
+            .params
+            .compression_level
+            .unwrap_or_else(|| codec.default_level());
+        let file = File::create_compressed_with(container, codec, level);
+        let save_bytes = save_bf_to_bytes(&file).map_err(Obj2BfError::SerializationError)?;
+
+        std::fs::write(path, save_bytes).map_err(Obj2BfError::SaveIOError)
+    }
+
+    /// Encodes the mesh and saves the output file.
+    fn save_bf_mesh(&mut self, geo: Geometry, lods: Vec<Lod>) -> Result<(), Obj2BfError> {
+        let mesh = self.encode_mesh(&geo, lods)?;
 
         let default_output = self.params.input.with_extension("bf");
         let save_path = self.params.output.clone().unwrap_or(default_output);
-        let save_bytes = save_bf_to_bytes(&file).map_err(Obj2BfError::SerializationError)?;
 
-        if self.params.dump_obj {
-            std::fs::write("./obj2bf_dump.obj", geo.to_obj()).expect("cannot dump .obj file");
+        self.write_bf(&save_path, Container::Mesh(mesh))
+    }
+
+    /// Converts every object with non-empty geometry to a mesh saved as `<uuid>.bf`
+    /// next to the output file and saves a `Tree` with one node per object as the
+    /// output file. Uuids of meshes are derived from the input file name and object
+    /// name, so converting the same file again produces the same uuids.
+    ///
+    /// Vertices in .obj files are already in scene space, so all nodes have identity
+    /// transforms. Materials are not imported, nodes reference a nil material.
+    fn save_bf_tree(&mut self, obj_set: &ObjSet) -> Result<(), Obj2BfError> {
+        let default_output = self.params.input.with_extension("bf");
+        let save_path = self.params.output.clone().unwrap_or(default_output);
+        let output_dir = save_path.parent().unwrap_or_else(|| Path::new("."));
+        let input_name = self
+            .params
+            .input
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        let mut tree = Tree::new();
+
+        for object in obj_set.objects.iter() {
+            if object.geometry.iter().all(|g| g.shapes.is_empty()) {
+                continue;
+            }
+
+            let mut geo = self.select_geo_and_normalize(object)?;
+            self.compute_sky_occlusion(&mut geo);
+            let lods = self.generate_lods(&mut geo);
+            self.optimize(&mut geo, &lods);
+            let mesh = self.encode_mesh(&geo, lods)?;
+            let uuid = Uuid::new_v5(
+                &MESH_NAMESPACE,
+                format!("{}/{}", input_name, object.name).as_bytes(),
+            );
+
+            let mesh_path = output_dir.join(format!("{}.bf", uuid.to_hyphenated()));
+            self.write_bf(&mesh_path, Container::Mesh(mesh))?;
+            println!("{} -> {}", object.name, uuid.to_hyphenated());
+
+            let mut node = Node::default();
+            node.add_component(Component::Name(object.name.clone()));
+            node.add_component(Component::Transform {
+                position: [0.0, 0.0, 0.0],
+                rotation: [0.0, 0.0, 0.0],
+                scale: [1.0, 1.0, 1.0],
+            });
+            node.add_component(Component::MeshRenderer {
+                mesh: uuid,
+                material: Uuid::nil(),
+            });
+
+            let handle = tree.add_node(node);
+            tree.root_mut().add_child(handle);
+        }
+
+        if tree.root().children().next().is_none() {
+            return Err(Obj2BfError::NoNonEmptyGeometriesFound);
         }
 
-        std::fs::write(save_path, save_bytes).map_err(Obj2BfError::SaveIOError)
+        self.write_bf(&save_path, Container::Tree(tree))
     }
 
     /// Calling this method performs the conversion specified by `Obj2BfParameters` parameter.
@@ -149,13 +285,24 @@ impl Obj2Bf {
         // todo: add support for importing materials
 
         let obj_set = tool.load()?;
+
+        if tool.params.all_objects {
+            tool.save_bf_tree(&obj_set)?;
+            return Ok(tool.stats);
+        }
+
         let object = tool.select_object(&obj_set)?;
-        let geo = tool.select_geo_and_normalize(object)?;
+        let mut geo = tool.select_geo_and_normalize(object)?;
+
+        if tool.params.dump_obj {
+            std::fs::write("./obj2bf_dump.obj", geo.to_obj()).expect("cannot dump .obj file");
+        }
 
-        // todo: generate lods (simplify mesh)
-        // todo: optimize meshes (forsyth)
+        tool.compute_sky_occlusion(&mut geo);
+        let lods = tool.generate_lods(&mut geo);
+        tool.optimize(&mut geo, &lods);
 
-        tool.save_bf_mesh(geo)?;
+        tool.save_bf_mesh(geo, lods)?;
 
         Ok(tool.stats)
     }
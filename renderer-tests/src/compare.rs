@@ -0,0 +1,248 @@
+//! Comparison of rendered images with golden images.
+
+use image::{Rgba, RgbaImage};
+use std::fmt::{Display, Formatter};
+
+/// Size of the windows the structural similarity is computed in.
+const SSIM_WINDOW: u32 = 8;
+
+/// Limits of differences between a rendered image and its golden image.
+#[derive(Copy, Clone, Debug)]
+pub struct Thresholds {
+    /// Maximal difference of a channel (0-255) of a pixel that is not counted
+    /// as a differing pixel (GPUs and drivers don't round the same way).
+    pub pixel_tolerance: u8,
+    /// Maximal fraction of differing pixels.
+    pub max_differing: f64,
+    /// Minimal structural similarity of the images (1 for identical images).
+    pub min_ssim: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            pixel_tolerance: 8,
+            max_differing: 0.001,
+            min_ssim: 0.98,
+        }
+    }
+}
+
+/// Differences between a rendered image and its golden image.
+pub struct Comparison {
+    /// Maximal difference of a channel of any pixel.
+    pub max_difference: u8,
+    /// Number of pixels with a channel that differs more than the tolerance.
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    /// Mean structural similarity of the luminance of the images.
+    pub ssim: f64,
+    /// The golden image in grayscale with the differing pixels in red.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    /// Returns the fraction of differing pixels.
+    pub fn differing(&self) -> f64 {
+        self.differing_pixels as f64 / self.total_pixels.max(1) as f64
+    }
+
+    /// Returns whether the differences are within the `thresholds`.
+    pub fn passed(&self, thresholds: &Thresholds) -> bool {
+        self.differing() <= thresholds.max_differing && self.ssim >= thresholds.min_ssim
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} differing pixels ({:.3}%), max difference {}, ssim {:.4}",
+            self.differing_pixels,
+            self.differing() * 100.0,
+            self.max_difference,
+            self.ssim
+        )
+    }
+}
+
+/// Compares the `actual` image with the `expected` image. Pixels with a channel
+/// differing more than `pixel_tolerance` are counted as differing.
+pub fn compare(
+    expected: &RgbaImage,
+    actual: &RgbaImage,
+    pixel_tolerance: u8,
+) -> Result<Comparison, String> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(format!(
+            "image is {:?} instead of {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+
+    let mut max_difference = 0;
+    let mut differing_pixels = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+
+    for ((e, a), d) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let difference =
+            e.0.iter()
+                .zip(a.0.iter())
+                .map(|(x, y)| x.max(y) - x.min(y))
+                .max()
+                .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        *d = if difference > pixel_tolerance {
+            differing_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // dimmed, so the differing pixels stand out
+            let gray = (luma(e) / 2.0) as u8;
+            Rgba([gray, gray, gray, 255])
+        };
+    }
+
+    Ok(Comparison {
+        max_difference,
+        differing_pixels,
+        total_pixels: (expected.width() * expected.height()) as usize,
+        ssim: ssim(expected, actual),
+        diff,
+    })
+}
+
+/// Returns the luminance (0-255) of the pixel with sRGB encoded color.
+fn luma(pixel: &Rgba<u8>) -> f64 {
+    let [r, g, b, _] = pixel.0;
+    0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64
+}
+
+/// Returns the mean structural similarity of the luminance of two images of the
+/// same size computed in non-overlapping windows of 8x8 pixels (windows at the
+/// right and bottom edges may be smaller).
+pub fn ssim(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = a.dimensions();
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    for wy in (0..height).step_by(SSIM_WINDOW as usize) {
+        for wx in (0..width).step_by(SSIM_WINDOW as usize) {
+            let pixels = (wy..(wy + SSIM_WINDOW).min(height))
+                .flat_map(|y| (wx..(wx + SSIM_WINDOW).min(width)).map(move |x| (x, y)))
+                .map(|(x, y)| (luma(a.get_pixel(x, y)), luma(b.get_pixel(x, y))))
+                .collect::<Vec<_>>();
+            let n = pixels.len() as f64;
+
+            let mean_a = pixels.iter().map(|x| x.0).sum::<f64>() / n;
+            let mean_b = pixels.iter().map(|x| x.1).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (x, y) in pixels.iter() {
+                var_a += (x - mean_a) * (x - mean_a);
+                var_b += (y - mean_b) * (y - mean_b);
+                covariance += (x - mean_a) * (y - mean_b);
+            }
+            let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.0
+    } else {
+        total / windows as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::{compare, ssim, Thresholds};
+    use image::{Rgba, RgbaImage};
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(32, 24, |x, y| {
+            Rgba([(x * 8) as u8, (y * 10) as u8, ((x + y) * 4) as u8, 255])
+        })
+    }
+
+    #[test]
+    fn identical_images_pass() {
+        let image = gradient();
+        let comparison = compare(&image, &image, 0).unwrap();
+
+        assert_eq!(comparison.max_difference, 0);
+        assert_eq!(comparison.differing_pixels, 0);
+        assert!((comparison.ssim - 1.0).abs() < 1e-9);
+        assert!(comparison.passed(&Thresholds::default()));
+    }
+
+    #[test]
+    fn small_differences_are_tolerated() {
+        let expected = gradient();
+        let mut actual = expected.clone();
+        for pixel in actual.pixels_mut() {
+            pixel.0[0] = pixel.0[0].saturating_add(2);
+        }
+
+        let comparison = compare(&expected, &actual, 4).unwrap();
+        assert_eq!(comparison.max_difference, 2);
+        assert_eq!(comparison.differing_pixels, 0);
+        assert!(comparison.passed(&Thresholds::default()));
+    }
+
+    #[test]
+    fn changed_region_fails() {
+        let expected = gradient();
+        let mut actual = expected.clone();
+        for y in 0..8 {
+            for x in 0..8 {
+                actual.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+
+        let comparison = compare(&expected, &actual, 8).unwrap();
+        assert_eq!(comparison.differing_pixels, 64);
+        assert_eq!(comparison.diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert!(comparison.ssim < 0.98);
+        assert!(!comparison.passed(&Thresholds::default()));
+    }
+
+    #[test]
+    fn different_sizes_are_error() {
+        let a = RgbaImage::new(4, 4);
+        let b = RgbaImage::new(4, 5);
+        assert!(compare(&a, &b, 0).is_err());
+    }
+
+    #[test]
+    fn structure_matters_more_than_brightness() {
+        let expected = gradient();
+        let brighter = RgbaImage::from_fn(32, 24, |x, y| {
+            let p = expected.get_pixel(x, y).0;
+            Rgba([
+                p[0].saturating_add(3),
+                p[1].saturating_add(3),
+                p[2].saturating_add(3),
+                255,
+            ])
+        });
+        let noisy = RgbaImage::from_fn(32, 24, |x, y| {
+            let p = expected.get_pixel(x, y).0;
+            let n = if (x * 7 + y * 13) % 3 == 0 { 60 } else { 0 };
+            Rgba([p[0] ^ n, p[1] ^ n, p[2] ^ n, 255])
+        });
+
+        assert!(ssim(&expected, &brighter) > ssim(&expected, &noisy));
+    }
+}
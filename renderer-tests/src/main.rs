@@ -0,0 +1,229 @@
+//! Golden-image regression tests of the render passes.
+//!
+//! Each test scene is rendered without a window for a fixed number of frames
+//! with the time of shaders frozen and the last frame is compared with the
+//! golden image `<golden_dir>/<scene>.png`. Images of failed scenes and the
+//! differences are saved to `output_dir`.
+
+use crate::compare::{compare, Thresholds};
+use crate::scenes::{CreateScene, SceneBuilder, SCENES};
+use core::settings::{parse_set_arg, Overrides};
+use engine::config::ENV_PREFIX;
+use engine::render::headless::HeadlessRenderer;
+use engine::render::vulkan::HeadlessVulkanState;
+use engine::RendererConfiguration;
+use log::{info, warn, LevelFilter};
+use std::path::PathBuf;
+use std::thread;
+use structopt::StructOpt;
+
+mod compare;
+mod scenes;
+
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "renderer-tests")]
+struct Opt {
+    /// Saves the rendered images as the new golden images instead of comparing them.
+    #[structopt(long)]
+    bless: bool,
+
+    /// Runs only scenes with names containing this text.
+    #[structopt(long)]
+    scene: Option<String>,
+
+    /// Overrides a setting (`name=value`, see README for the list of settings)
+    #[structopt(long = "set", number_of_values = 1, parse(try_from_str = parse_set_arg))]
+    set: Vec<(String, String)>,
+}
+
+/// Settings of the tests. Renderer settings (eg. `gpu`, `bloom`) are read
+/// from the same overrides.
+struct Settings {
+    golden_dir: PathBuf,
+    output_dir: PathBuf,
+    /// Number of rendered frames of each scene (effects that adapt over time
+    /// like the exposure need a few frames to settle).
+    frames: usize,
+    thresholds: Thresholds,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        Self {
+            golden_dir: dir.join("golden"),
+            output_dir: dir.join("output"),
+            frames: 8,
+            thresholds: Thresholds::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn apply_overrides(&mut self, overrides: &Overrides) -> Result<(), String> {
+        overrides.apply("golden_dir", &mut self.golden_dir)?;
+        overrides.apply("output_dir", &mut self.output_dir)?;
+        overrides.apply("frames", &mut self.frames)?;
+        overrides.apply("pixel_tolerance", &mut self.thresholds.pixel_tolerance)?;
+        overrides.apply("max_differing", &mut self.thresholds.max_differing)?;
+        overrides.apply("min_ssim", &mut self.thresholds.min_ssim)?;
+        Ok(())
+    }
+}
+
+/// Result of one scene.
+enum Outcome {
+    Passed(String),
+    Blessed,
+    /// The golden image does not exist (run with `--bless` to create it).
+    Missing,
+    Failed(String),
+}
+
+fn main() {
+    // increase default stack size to 8MB
+    let child = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(boot)
+        .unwrap();
+
+    let passed = child.join().unwrap();
+    std::process::exit(if passed { 0 } else { 1 });
+}
+
+/// Runs the tests and returns whether all of them passed.
+fn boot() -> bool {
+    simple_logger::SimpleLogger::new()
+        .with_level(LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    let opt = Opt::from_args();
+
+    // load configuration (overridden by `--set name=value` and environment variables)
+    let overrides = Overrides::from_env(ENV_PREFIX, opt.set);
+    let mut settings = Settings::default();
+    let mut conf = RendererConfiguration {
+        // small images keep the golden images small and the tests fast
+        resolution: [320, 180],
+        ..RendererConfiguration::default()
+    };
+    if let Err(e) = settings
+        .apply_overrides(&overrides)
+        .and_then(|_| conf.apply_overrides(&overrides))
+    {
+        panic!("Invalid configuration: {}", e);
+    }
+    for name in overrides.unused() {
+        warn!("Unknown setting {:?} was ignored", name);
+    }
+
+    let vulkan = HeadlessVulkanState::new(conf.gpu).expect("cannot create vulkan device");
+    let mut renderer = HeadlessRenderer::new(&vulkan, &conf).expect("cannot create renderer");
+    renderer.render_path.fixed_time = Some(0.0);
+
+    if let Err(e) = std::fs::create_dir_all(&settings.output_dir) {
+        panic!("Cannot create {:?}: {}", settings.output_dir, e);
+    }
+
+    let mut results = Vec::new();
+    for (name, create) in SCENES.iter() {
+        if let Some(filter) = &opt.scene {
+            if !name.contains(filter.as_str()) {
+                continue;
+            }
+        }
+
+        info!("Rendering scene {}", name);
+        let outcome = run_scene(&vulkan, &mut renderer, &settings, opt.bless, name, *create)
+            .unwrap_or_else(Outcome::Failed);
+        results.push((name, outcome));
+    }
+
+    let mut passed = true;
+    println!("Golden-image tests");
+    for (name, outcome) in results.iter() {
+        let (status, text) = match outcome {
+            Outcome::Passed(t) => ("ok", t.as_str()),
+            Outcome::Blessed => ("blessed", "saved as the golden image"),
+            Outcome::Missing => ("MISSING", "golden image does not exist (run with --bless)"),
+            Outcome::Failed(e) => ("FAILED", e.as_str()),
+        };
+        passed &= matches!(outcome, Outcome::Passed(_) | Outcome::Blessed);
+        println!("  {:<16} {:>8}  {}", name, status, text);
+    }
+    if !passed {
+        println!(
+            "Rendered images and differences are in {:?}",
+            settings.output_dir
+        );
+    }
+    passed
+}
+
+/// Renders the scene `name` created by `create` and compares it with (or
+/// saves it as) its golden image.
+fn run_scene(
+    vulkan: &HeadlessVulkanState,
+    renderer: &mut HeadlessRenderer,
+    settings: &Settings,
+    bless: bool,
+    name: &str,
+    create: CreateScene,
+) -> Result<Outcome, String> {
+    let dimensions = renderer.dimensions();
+    let mut builder = SceneBuilder::new(
+        vulkan.transfer_queue(),
+        &mut renderer.render_path,
+        dimensions,
+    );
+    create(&mut builder)?;
+    let (mut state, uploads) = builder.finish();
+    state.update_transforms();
+    state.update_lods();
+
+    renderer.render_frames(&state, uploads, settings.frames.max(1))?;
+    let actual = renderer.capture()?;
+
+    let golden = settings.golden_dir.join(format!("{}.png", name));
+    if bless {
+        std::fs::create_dir_all(&settings.golden_dir)
+            .map_err(|e| format!("cannot create {:?}: {}", settings.golden_dir, e))?;
+        actual
+            .save(&golden)
+            .map_err(|e| format!("cannot save {:?}: {}", golden, e))?;
+        return Ok(Outcome::Blessed);
+    }
+    if !golden.exists() {
+        save_output(settings, name, "actual", &actual)?;
+        return Ok(Outcome::Missing);
+    }
+
+    let expected = image::open(&golden)
+        .map_err(|e| format!("cannot load {:?}: {}", golden, e))?
+        .to_rgba8();
+    let comparison = compare(&expected, &actual, settings.thresholds.pixel_tolerance)?;
+
+    if comparison.passed(&settings.thresholds) {
+        Ok(Outcome::Passed(comparison.to_string()))
+    } else {
+        save_output(settings, name, "actual", &actual)?;
+        save_output(settings, name, "diff", &comparison.diff)?;
+        Ok(Outcome::Failed(comparison.to_string()))
+    }
+}
+
+/// Saves the `image` as `<output_dir>/<scene>.<kind>.png`.
+fn save_output(
+    settings: &Settings,
+    scene: &str,
+    kind: &str,
+    image: &image::RgbaImage,
+) -> Result<(), String> {
+    let path = settings.output_dir.join(format!("{}.{}.png", scene, kind));
+    image
+        .save(&path)
+        .map_err(|e| format!("cannot save {:?}: {}", path, e))
+}
@@ -0,0 +1,342 @@
+//! Small test scenes built from generated meshes and materials without
+//! textures, so they don't need any assets.
+
+use bf::material::{BlendMode, MaterialFlags};
+use bf::mesh::{IndexType, Mesh, MeshEncoding, VertexFormat};
+use cgmath::{vec3, Deg, InnerSpace, Point3, Vector3};
+use core::color::srgb8;
+use engine::camera::{ActiveCamera, OrthographicCamera, PerspectiveCamera};
+use engine::render::hierarchy::Hierarchy;
+use engine::render::object::Object;
+use engine::render::objects::Objects;
+use engine::render::pbr::PBRDeffered;
+use engine::render::transform::Transform;
+use engine::render::ubo::{AmbientLight, DirectionalLight, MaterialData, Wind};
+use engine::render::vertex::NormalMappedVertex;
+use engine::resources::material::{create_default_fallback_maps, FallbackMaps, StaticMaterial};
+use engine::resources::mesh::{create_mesh_dynamic, DynamicIndexedMesh};
+use engine::GameState;
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::sync::GpuFuture;
+
+/// Function that adds objects of a test scene to the state and sets up the
+/// camera, lights and the sky.
+pub type CreateScene = fn(&mut SceneBuilder) -> Result<(), String>;
+
+/// Test scenes by their names (names of the golden images).
+pub const SCENES: [(&str, CreateScene); 3] = [
+    ("deferred", deferred),
+    ("transparency", transparency),
+    ("sky", sky),
+];
+
+/// Creates objects of a test scene and collects the futures of their uploads.
+pub struct SceneBuilder<'a> {
+    pub state: GameState,
+    pub path: &'a mut PBRDeffered,
+    queue: Arc<Queue>,
+    fallback_maps: Arc<FallbackMaps>,
+    uploads: Vec<Box<dyn GpuFuture + Send>>,
+}
+
+impl<'a> SceneBuilder<'a> {
+    /// Creates a builder of a scene rendered in `resolution` by the `path`.
+    /// Resources are uploaded on the transfer `queue`.
+    pub fn new(queue: Arc<Queue>, path: &'a mut PBRDeffered, resolution: [u32; 2]) -> Self {
+        let (fallback_maps, f) = create_default_fallback_maps(queue.clone());
+        // the sky is shared by all scenes, so settings of previous scenes are reset
+        path.sky.turbidity = 2.0;
+        Self {
+            state: game_state(resolution[0] as f32 / resolution[1] as f32),
+            path,
+            queue,
+            fallback_maps,
+            uploads: vec![f.boxed_send()],
+        }
+    }
+
+    /// Returns the state of the scene and the future of all uploads. The sun
+    /// of the sky is in the direction of the first directional light.
+    pub fn finish(self) -> (GameState, Option<Box<dyn GpuFuture + Send>>) {
+        if let Some(light) = self.state.directional_lights.first() {
+            self.path.sky.sun_dir = light.direction;
+        }
+        let uploads = self
+            .uploads
+            .into_iter()
+            .reduce(|a, b| a.join(b).boxed_send());
+        (self.state, uploads)
+    }
+
+    fn mesh(&mut self, mesh: &Mesh) -> Result<Arc<DynamicIndexedMesh<NormalMappedVertex>>, String> {
+        let (mesh, f) = create_mesh_dynamic(mesh, self.queue.clone())
+            .map_err(|e| format!("cannot create mesh: {:?}", e))?;
+        self.uploads.push(f);
+        Ok(mesh)
+    }
+
+    fn material(
+        &mut self,
+        blend_mode: BlendMode,
+        data: MaterialData,
+    ) -> Result<Arc<StaticMaterial>, String> {
+        let (material, f) = StaticMaterial::from_material_data(
+            blend_mode,
+            data,
+            self.path.buffers.geometry_pipeline.clone(),
+            self.path.samplers.aniso_repeat.clone(),
+            self.queue.clone(),
+            self.fallback_maps.clone(),
+        )
+        .map_err(|e| format!("cannot create material: {:?}", e))?;
+        self.uploads.push(f.boxed_send());
+        Ok(material)
+    }
+
+    fn add(
+        &mut self,
+        mesh: Arc<DynamicIndexedMesh<NormalMappedVertex>>,
+        material: Arc<StaticMaterial>,
+        transform: Transform,
+    ) {
+        self.state.objects.insert(Object::new(
+            mesh,
+            material,
+            self.queue.device().clone(),
+            self.path.buffers.geometry_pipeline.clone(),
+            transform,
+        ));
+    }
+
+    /// Adds a gray floor of 20x20 meters at the height of zero.
+    fn floor(&mut self) -> Result<(), String> {
+        let mesh = self.mesh(&plane_mesh(10.0))?;
+        let material = self.material(BlendMode::Opaque, material([0.5; 3], 0.8, 0.0))?;
+        self.add(mesh, material, Transform::default());
+        Ok(())
+    }
+
+    /// Points the camera from `position` to `target`.
+    fn look_at(&mut self, position: Point3<f32>, target: Point3<f32>) {
+        self.state.camera.position = position;
+        self.state.camera.forward = (target - position).normalize();
+    }
+}
+
+/// Returns an empty state with a camera with `aspect_ratio` and one light.
+fn game_state(aspect_ratio: f32) -> GameState {
+    GameState {
+        start: Instant::now(),
+        camera: PerspectiveCamera {
+            position: Point3::new(0.0, 1.5, -4.0),
+            forward: vec3(0.0, 0.0, 1.0),
+            up: vec3(0.0, -1.0, 0.0),
+            fov: Deg(60.0).into(),
+            aspect_ratio,
+            near: 0.05,
+            far: 100.0,
+        },
+        camera_rig: None,
+        orthographic_camera: OrthographicCamera {
+            position: Point3::new(0.0, 10.0, 0.0),
+            forward: vec3(0.0, -1.0, 0.0),
+            up: vec3(0.0, 0.0, 1.0),
+            height: 10.0,
+            aspect_ratio,
+            near: 0.05,
+            far: 100.0,
+        },
+        active_camera: ActiveCamera::Perspective,
+        objects: Objects::default(),
+        directional_lights: vec![DirectionalLight {
+            direction: vec3(1.0, 2.0, -1.0).normalize(),
+            intensity: 2.5,
+            color: vec3(1.0, 1.0, 1.0),
+        }],
+        spot_lights: vec![],
+        decals: vec![],
+        ambient_light: AmbientLight {
+            intensity: 0.3,
+            ..AmbientLight::default()
+        },
+        wind: Wind::default(),
+        hierarchy: Hierarchy::default(),
+        origin: vec3(0.0, 0.0, 0.0),
+    }
+}
+
+/// Returns parameters of a material without textures.
+fn material(albedo_color: [f32; 3], roughness: f32, metallic: f32) -> MaterialData {
+    MaterialData {
+        albedo_color,
+        alpha_cutoff: 0.0,
+        roughness,
+        metallic,
+        opacity: 1.0,
+        ior: 1.5,
+        emissive_color: [0.0; 3],
+        clear_coat: 0.0,
+        clear_coat_roughness: 0.0,
+        anisotropy: 0.0,
+        flags: 0,
+    }
+}
+
+/// Opaque spheres with different materials on the floor (geometry, lighting
+/// and sky passes).
+fn deferred(scene: &mut SceneBuilder) -> Result<(), String> {
+    scene.floor()?;
+    scene.look_at(Point3::new(0.0, 1.5, -4.0), Point3::new(0.0, 0.5, 0.0));
+
+    let sphere = scene.mesh(&sphere_mesh(32, 16))?;
+    let materials = [
+        material(srgb8(200, 30, 30), 0.9, 0.0),
+        material(srgb8(255, 200, 80), 0.2, 1.0),
+        MaterialData {
+            clear_coat: 1.0,
+            clear_coat_roughness: 0.05,
+            flags: MaterialFlags::CLEAR_COAT.bits(),
+            ..material(srgb8(30, 60, 200), 0.6, 0.0)
+        },
+    ];
+    for (i, data) in materials.iter().enumerate() {
+        let material = scene.material(BlendMode::Opaque, *data)?;
+        let position = vec3(i as f32 * 1.2 - 1.2, 0.5, 0.0);
+        scene.add(
+            sphere.clone(),
+            material,
+            Transform::from_position(position).with_scale(vec3(0.5, 0.5, 0.5)),
+        );
+    }
+    Ok(())
+}
+
+/// Overlapping translucent spheres in front of an opaque one (transparency
+/// accumulation and resolve passes).
+fn transparency(scene: &mut SceneBuilder) -> Result<(), String> {
+    scene.floor()?;
+    scene.look_at(Point3::new(0.0, 1.0, -3.5), Point3::new(0.0, 0.5, 0.0));
+
+    let sphere = scene.mesh(&sphere_mesh(32, 16))?;
+    let opaque = scene.material(BlendMode::Opaque, material([0.8; 3], 0.5, 0.0))?;
+    scene.add(
+        sphere.clone(),
+        opaque,
+        Transform::from_position(vec3(0.0, 0.6, 1.5)).with_scale(vec3(0.6, 0.6, 0.6)),
+    );
+
+    let glasses = [
+        (srgb8(0, 204, 0), 0.3, vec3(-0.3, 0.5, 0.0)),
+        (srgb8(204, 0, 0), 0.5, vec3(0.3, 0.5, 0.2)),
+    ];
+    for (color, opacity, position) in glasses.iter() {
+        let glass = scene.material(
+            BlendMode::Translucent,
+            MaterialData {
+                opacity: *opacity,
+                ..material(*color, 0.2, 0.0)
+            },
+        )?;
+        scene.add(
+            sphere.clone(),
+            glass,
+            Transform::from_position(*position).with_scale(vec3(0.4, 0.4, 0.4)),
+        );
+    }
+    Ok(())
+}
+
+/// Sky with the sun low above the horizon.
+fn sky(scene: &mut SceneBuilder) -> Result<(), String> {
+    scene.look_at(Point3::new(0.0, 1.0, 0.0), Point3::new(0.0, 1.3, 5.0));
+    scene.state.directional_lights[0].direction = vec3(0.3, 0.15, 1.0).normalize();
+    scene.path.sky.turbidity = 3.0;
+    Ok(())
+}
+
+/// Returns a mesh with the vertices and triangles (wound counter-clockwise when
+/// seen from the front).
+fn mesh(vertices: &[NormalMappedVertex], indices: &[u16]) -> Mesh {
+    let vertex_data = vertices
+        .iter()
+        .flat_map(|v| {
+            v.position
+                .iter()
+                .chain(v.normal.iter())
+                .chain(v.uv.iter())
+                .chain(v.tangent.iter())
+                .flat_map(|x| x.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>()
+        })
+        .collect::<Vec<u8>>();
+    let index_data = indices
+        .iter()
+        .flat_map(|x| x.to_le_bytes().to_vec())
+        .collect::<Vec<u8>>();
+
+    Mesh {
+        vertex_format: VertexFormat::PositionNormalUvTangent,
+        vertex_data: vertex_data.into(),
+        index_type: IndexType::U16,
+        index_data: index_data.into(),
+        lods: vec![],
+        encoding: MeshEncoding::None,
+        stiffness: None,
+    }
+}
+
+/// Returns a square in the XZ plane facing up with sides of `2 * extent`.
+fn plane_mesh(extent: f32) -> Mesh {
+    let vertex = |x: f32, z: f32| NormalMappedVertex {
+        position: [x * extent, 0.0, z * extent],
+        normal: [0.0, 1.0, 0.0],
+        uv: [(x + 1.0) * extent / 2.0, (z + 1.0) * extent / 2.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
+    };
+    let vertices = [
+        vertex(-1.0, -1.0),
+        vertex(-1.0, 1.0),
+        vertex(1.0, 1.0),
+        vertex(1.0, -1.0),
+    ];
+    mesh(&vertices, &[0, 1, 2, 0, 2, 3])
+}
+
+/// Returns a unit sphere with `segments` around the vertical axis and `rings`
+/// from the top to the bottom.
+fn sphere_mesh(segments: u16, rings: u16) -> Mesh {
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let phi = 2.0 * std::f32::consts::PI * segment as f32 / segments as f32;
+            let normal = Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            vertices.push(NormalMappedVertex {
+                position: normal.into(),
+                normal: normal.into(),
+                uv: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                tangent: [-phi.sin(), 0.0, phi.cos(), 1.0],
+            });
+        }
+    }
+
+    let index = |ring: u16, segment: u16| ring * (segments + 1) + segment;
+    let mut indices = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = index(ring, segment);
+            let b = index(ring + 1, segment);
+            let c = index(ring + 1, segment + 1);
+            let d = index(ring, segment + 1);
+            indices.extend_from_slice(&[a, c, b, a, d, c]);
+        }
+    }
+
+    mesh(&vertices, &indices)
+}
@@ -1,12 +1,19 @@
-use crate::camera::PerspectiveCamera;
-use crate::config::RendererConfiguration;
-use crate::engine::Engine;
-use crate::render::object::Object;
-use crate::render::ubo::DirectionalLight;
-use crate::render::vertex::NormalMappedVertex;
-use crate::resources::material::StaticMaterial;
-use cgmath::{vec3, Deg, InnerSpace, Point3};
-use log::{info, LevelFilter};
+use crate::scenes::{basic, roughness_test, transparency};
+use bf::material::BlendMode;
+use cgmath::{vec3, Deg, InnerSpace, Point3, Rad, Vector3};
+use core::color::{srgb8, srgb_to_linear_rgb};
+use core::settings::{parse_set_args, Overrides};
+use engine::camera::{ActiveCamera, OrthographicCamera, PerspectiveCamera};
+use engine::config::{config_file, ENV_PREFIX};
+use engine::egui;
+use engine::render::hierarchy::Hierarchy;
+use engine::render::objects::{ObjectId, Objects};
+use engine::render::ubo::{AmbientLight, DirectionalLight, MaterialData, Wind};
+use engine::resources::material::{create_default_fallback_maps, FallbackMaps, StaticMaterial};
+use engine::self_test::{run_headless_self_test, run_self_test};
+use engine::{Engine, Game, GameState, RendererConfiguration};
+use log::{info, warn, LevelFilter};
+use rand::Rng;
 use std::sync::Arc;
 use std::thread;
 use std::time::Instant;
@@ -18,27 +25,155 @@ use winit::platform::windows::EventLoopExtWindows;
 #[cfg(unix)]
 use winit::platform::unix::EventLoopExtUnix;
 
-mod assets;
-mod camera;
-mod config;
-mod engine;
-mod input;
-mod movement;
-mod render;
-mod resources;
 mod scenes;
 
-pub struct GameState {
-    start: Instant,
-    camera: PerspectiveCamera,
-    objects: Vec<Object<NormalMappedVertex>>,
-    directional_lights: Vec<DirectionalLight>,
+/// State of the demo that is not part of the engine.
+#[derive(Default)]
+pub struct Demo {
     materials: Vec<Arc<StaticMaterial>>,
     floor_mat: usize,
+    /// Floor of the current scene, its material can be changed at run-time.
+    floor: Option<ObjectId>,
+    /// Parameters of the floor material set in the tweaks window.
+    floor_roughness: f32,
+    floor_metallic: f32,
+    fallback_maps: Option<Arc<FallbackMaps>>,
+}
+
+impl Demo {
+    /// Draws the window with parameters of the scene that can be changed at run-time.
+    fn tweaks_window(&mut self, engine: &mut Engine) {
+        let ctx = engine.egui.ctx().clone();
+        let mut floor_changed = false;
+
+        egui::Window::new("Tweaks").show(&ctx, |ui| {
+            let sky = &mut engine.renderer_state.render_path.sky;
+            ui.add(egui::Slider::new(&mut sky.turbidity, 1.0..=10.0).text("sky turbidity"));
+
+            if let Some(light) = engine.game_state.directional_lights.first_mut() {
+                let d = light.direction;
+                let mut azimuth = Deg::from(Rad(d.z.atan2(d.x))).0;
+                let mut elevation = Deg::from(Rad(d.y.clamp(-1.0, 1.0).asin())).0;
+
+                let a =
+                    ui.add(egui::Slider::new(&mut azimuth, -180.0..=180.0).text("light azimuth"));
+                let e =
+                    ui.add(egui::Slider::new(&mut elevation, -90.0..=90.0).text("light elevation"));
+                if a.changed() || e.changed() {
+                    let (azimuth, elevation) = (Rad::from(Deg(azimuth)), Rad::from(Deg(elevation)));
+                    light.direction = vec3(
+                        elevation.0.cos() * azimuth.0.cos(),
+                        elevation.0.sin(),
+                        elevation.0.cos() * azimuth.0.sin(),
+                    );
+                }
+            }
+
+            let r = ui.add(
+                egui::Slider::new(&mut self.floor_roughness, 0.0..=1.0).text("floor roughness"),
+            );
+            let m = ui
+                .add(egui::Slider::new(&mut self.floor_metallic, 0.0..=1.0).text("floor metallic"));
+            floor_changed = r.changed() || m.changed();
+        });
+
+        if floor_changed {
+            self.update_floor_material(engine);
+        }
+    }
+
+    /// Replaces the material of the floor with a material without textures
+    /// that uses the parameters from the tweaks window.
+    fn update_floor_material(&mut self, engine: &mut Engine) {
+        let queue = engine.vulkan_state.transfer_queue();
+        let fallback_maps = self
+            .fallback_maps
+            .get_or_insert_with(|| create_default_fallback_maps(queue.clone()).0)
+            .clone();
+        let path = &engine.renderer_state.render_path;
+
+        let (material, f) = StaticMaterial::from_material_data(
+            BlendMode::Opaque,
+            MaterialData {
+                albedo_color: [1.0; 3],
+                alpha_cutoff: 0.0,
+                roughness: self.floor_roughness,
+                metallic: self.floor_metallic,
+                opacity: 1.0,
+                ior: 1.0,
+                emissive_color: [0.0; 3],
+                clear_coat: 0.0,
+                clear_coat_roughness: 0.0,
+                anisotropy: 0.0,
+                flags: 0,
+            },
+            path.buffers.geometry_pipeline.clone(),
+            path.samplers.aniso_repeat.clone(),
+            queue,
+            fallback_maps,
+        )
+        .expect("cannot create floor material");
+        engine.content.uploads.schedule(f, 0);
+
+        if let Some(floor) = self
+            .floor
+            .and_then(|x| engine.game_state.objects.get_mut(x))
+        {
+            floor.material = material;
+        }
+    }
+}
+
+impl Game for Demo {
+    fn update(&mut self, engine: &mut Engine) {
+        if engine.input_state.is_action_pressed("cycle_floor_material") {
+            if let Some(obj) = self
+                .floor
+                .and_then(|x| engine.game_state.objects.get_mut(x))
+            {
+                obj.material = self.materials[self.floor_mat % self.materials.len()].clone();
+                self.floor_mat += 1;
+            }
+        }
+
+        if engine.input_state.is_action_pressed("spawn_light") {
+            let mut rng = rand::thread_rng();
+            engine.game_state.directional_lights.push(DirectionalLight {
+                direction: Vector3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(0.0..2.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .normalize(),
+                intensity: 1.0,
+                color: srgb_to_linear_rgb([
+                    rng.gen_range(0.3..1.0),
+                    rng.gen_range(0.3..1.0),
+                    rng.gen_range(0.3..1.0),
+                ])
+                .into(),
+            })
+        }
+
+        self.tweaks_window(engine);
+    }
+
+    fn load_scene(&mut self, engine: &mut Engine, name: &str) -> Result<(), String> {
+        match name {
+            "basic" => basic::create(engine, self),
+            "roughness_test" => roughness_test::create(engine, self),
+            "transparency" => transparency::create(engine, self),
+            _ => return Err(format!("unknown scene {:?}", name)),
+        }
+        Ok(())
+    }
 }
 
 const STACK_SIZE: usize = 8 * 1024 * 1024;
 
+/// Number of frames rendered by the `--self-test` mode.
+const SELF_TEST_FRAMES: usize = 8;
+
 fn main() {
     // increase default stack size to 8MB
     let child = thread::Builder::new()
@@ -57,12 +192,40 @@ fn boot() {
         .init()
         .unwrap();
 
-    // load configuration
-    let conf = RendererConfiguration::default();
+    // `--self-test` verifies the renderer works on this machine and exits
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let self_test = args.iter().any(|x| x == "--self-test");
+
+    // load configuration from `renderer.toml` (overridden by `--set name=value`
+    // and environment variables)
+    let overrides = match parse_set_args(args.into_iter().filter(|x| x != "--self-test")) {
+        Ok(t) => Overrides::from_env(ENV_PREFIX, t),
+        Err(e) => panic!("Cannot parse arguments: {}", e),
+    };
+    let conf = match RendererConfiguration::load(&config_file(), &overrides) {
+        Ok(t) => t,
+        Err(e) => panic!("Invalid configuration: {}", e),
+    };
+    for name in overrides.unused() {
+        warn!("Unknown setting {:?} was ignored", name);
+    }
+
+    // headless mode renders the self-test scene into `headless_output` without a window
+    if conf.headless {
+        let report = run_headless_self_test(&conf);
+        println!("{}", report);
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
 
     // start event loop
     let event_loop = EventLoop::new_any_thread();
 
+    if self_test {
+        let report = run_self_test(&conf, &event_loop, SELF_TEST_FRAMES);
+        println!("{}", report);
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
     // initialize engine
     let mut engine = Engine::new(
         GameState {
@@ -76,35 +239,55 @@ fn boot() {
                 near: 0.05,
                 far: 100.0,
             },
-            objects: vec![],
+            camera_rig: None,
+            // top view of the scene
+            orthographic_camera: OrthographicCamera {
+                position: Point3::new(0.0, 50.0, 0.0),
+                forward: vec3(0.0, -1.0, 0.0),
+                up: vec3(0.0, 0.0, 1.0),
+                height: 30.0,
+                aspect_ratio: conf.resolution[0] as f32 / conf.resolution[1] as f32,
+                near: 0.05,
+                far: 100.0,
+            },
+            active_camera: ActiveCamera::Perspective,
+            objects: Objects::default(),
             directional_lights: vec![
                 DirectionalLight {
                     direction: vec3(5.0, 5.0, 1.0).normalize(),
                     intensity: 2.5,
-                    color: vec3(1.0, 1.0, 0.8),
+                    color: srgb8(255, 255, 204).into(),
                 },
                 DirectionalLight {
                     direction: vec3(-5.0, 5.0, 1.0).normalize(),
                     intensity: 2.5,
-                    color: vec3(0.8, 1.0, 1.0),
+                    color: srgb8(204, 255, 255).into(),
                 },
             ],
-            materials: vec![],
-            floor_mat: 0,
+            spot_lights: vec![],
+            decals: vec![],
+            ambient_light: AmbientLight {
+                intensity: 0.3,
+                ..AmbientLight::default()
+            },
+            wind: Wind::default(),
+            origin: vec3(0.0, 0.0, 0.0),
+            hierarchy: Hierarchy::default(),
         },
         &conf,
         event_loop,
     );
+    let mut demo = Demo::default();
 
     // load scene and data
-    load(&mut engine);
+    load(&mut engine, &mut demo);
 
     // run engine
-    engine.run_forever();
+    engine.run_forever(demo);
 }
 
-fn load(engine: &mut Engine) {
+fn load(engine: &mut Engine, demo: &mut Demo) {
     info!("Loading scene and data...");
 
-    scenes::transparency::create(engine);
+    transparency::create(engine, demo);
 }
@@ -1,33 +1,26 @@
-use crate::assets::lookup;
-use crate::engine::Engine;
-use crate::render::object::Object;
-use crate::render::transform::Transform;
-use crate::resources::material::{create_default_fallback_maps, StaticMaterial};
-use crate::resources::mesh::create_mesh_dynamic;
+use crate::Demo;
 use cgmath::{vec3, Deg, Quaternion, Rotation3, Vector3};
+use engine::assets::lookup;
+use engine::render::object::Object;
+use engine::render::transform::Transform;
+use engine::resources::material::{create_default_fallback_maps, StaticMaterial};
+use engine::resources::mesh::create_mesh_cached;
+use engine::Engine;
 use log::info;
 use std::time::Instant;
-use vulkano::sync::GpuFuture;
 
-pub fn create(engine: &mut Engine) {
+pub fn create(engine: &mut Engine, demo: &mut Demo) {
     let start = Instant::now();
     let device = &engine.vulkan_state.device();
     let assets = &engine.content;
     let path = &mut engine.renderer_state.render_path;
 
-    let (fallback_maps, _) = create_default_fallback_maps(engine.vulkan_state.transfer_queue());
+    let (fallback_maps, f) = create_default_fallback_maps(engine.vulkan_state.transfer_queue());
+    assets.uploads.schedule(f, 0);
 
     macro_rules! mesh {
         ($name: expr) => {{
-            let guard = assets.request_load(lookup($name));
-
-            let mesh = guard.wait::<bf::mesh::Mesh>();
-
-            let (mesh, f) = create_mesh_dynamic(&mesh, assets.transfer_queue.clone())
-                .expect("cannot create mesh");
-            f.then_signal_fence_and_flush().ok();
-
-            mesh
+            create_mesh_cached(lookup($name), assets).expect("cannot create mesh")
         }};
     }
 
@@ -48,7 +41,7 @@ pub fn create(engine: &mut Engine) {
                 fallback_maps.clone(),
             )
             .expect("cannot create material");
-            f.then_signal_fence_and_flush().ok();
+            assets.uploads.schedule(f, 0);
 
             material
         }};
@@ -59,11 +52,11 @@ pub fn create(engine: &mut Engine) {
         material!("pbr_sneaker.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.1, 0.1, 0.1),
-            position: vec3(3.0, 5.0, 3.0),
-            rotation: Quaternion::from_angle_x(Deg(-90.0)),
-        },
+        Transform::new(
+            vec3(3.0, 5.0, 3.0),
+            Quaternion::from_angle_x(Deg(-90.0)),
+            vec3(0.1, 0.1, 0.1),
+        ),
     );
 
     let cabinet = Object::new(
@@ -71,11 +64,11 @@ pub fn create(engine: &mut Engine) {
         material!("pbr_cabinet.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.05, 0.05, 0.05),
-            position: vec3(3.0, 5.0, 9.0),
-            rotation: Quaternion::from_angle_y(Deg(-45.0)),
-        },
+        Transform::new(
+            vec3(3.0, 5.0, 9.0),
+            Quaternion::from_angle_y(Deg(-45.0)),
+            vec3(0.05, 0.05, 0.05),
+        ),
     );
 
     let welding_setup = Object::new(
@@ -83,11 +76,7 @@ pub fn create(engine: &mut Engine) {
         material!("pbr_welding_setup.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.01, 0.01, 0.01),
-            position: vec3(-3.0, 0.1, -3.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-3.0, 0.1, -3.0)).with_scale(vec3(0.01, 0.01, 0.01)),
     );
 
     let cottage = Object::new(
@@ -95,11 +84,7 @@ pub fn create(engine: &mut Engine) {
         material!("pbr_cottage.mat"),
         device.clone(),
         path.buffers.transparency.accumulation_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(0.0, 0.0, -15.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.0, -15.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let red_barn = Object::new(
@@ -107,11 +92,7 @@ pub fn create(engine: &mut Engine) {
         material!("pbr_red_barn.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(0.0, 0.1, 30.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.1, 30.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let apple = Object::new(
@@ -119,11 +100,7 @@ pub fn create(engine: &mut Engine) {
         material!("3DApple002_2K-JPG.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(6.0, 6.0, 6.0),
-            position: vec3(0.0, 0.3, 0.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.3, 0.0)).with_scale(vec3(6.0, 6.0, 6.0)),
     );
 
     let woman = Object::new(
@@ -131,11 +108,7 @@ pub fn create(engine: &mut Engine) {
         material!("autumn_casualwoman_01.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.1, 0.1, 0.1),
-            position: vec3(7.0, 0.0, 0.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(7.0, 0.0, 0.0)).with_scale(vec3(0.1, 0.1, 0.1)),
     );
 
     let bread1 = Object::new(
@@ -143,11 +116,7 @@ pub fn create(engine: &mut Engine) {
         material!("3DBread001_LowPoly.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(5.0, 5.0, 5.0),
-            position: vec3(3.0, 0.3, 0.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(3.0, 0.3, 0.0)).with_scale(vec3(5.0, 5.0, 5.0)),
     );
 
     let rock1 = Object::new(
@@ -155,11 +124,7 @@ pub fn create(engine: &mut Engine) {
         material!("3DRock001_2K.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(3.0, 0.3, 0.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(3.0, 0.3, 0.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let rock2 = Object::new(
@@ -167,11 +132,7 @@ pub fn create(engine: &mut Engine) {
         material!("3DRock002_9K.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(2.0, 2.0, 2.0),
-            position: vec3(-3.0, 0.3, 0.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-3.0, 0.3, 0.0)).with_scale(vec3(2.0, 2.0, 2.0)),
     );
 
     let jess = Object::new(
@@ -179,11 +140,11 @@ pub fn create(engine: &mut Engine) {
         material!("Jess_Casual_Walking_001.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.001, 0.001, 0.001),
-            position: vec3(-1.65, 0.5, -9.72),
-            rotation: Quaternion::from_angle_x(Deg(-90.0)),
-        },
+        Transform::new(
+            vec3(-1.65, 0.5, -9.72),
+            Quaternion::from_angle_x(Deg(-90.0)),
+            vec3(0.001, 0.001, 0.001),
+        ),
     );
 
     let fern = Object::new(
@@ -191,11 +152,7 @@ pub fn create(engine: &mut Engine) {
         material!("Soi_Foliage_OBJ\\T_Ferns.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(0.0, 0.0, -9.5),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.0, -9.5)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let test_cube = Object::new(
@@ -203,11 +160,7 @@ pub fn create(engine: &mut Engine) {
         material!("test_cube.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(-5.0, 0.5, -5.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-5.0, 0.5, -5.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let tv = Object::new(
@@ -215,11 +168,7 @@ pub fn create(engine: &mut Engine) {
         material!("uploads_files_2529155_Textures_Baked.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(-2.0, 0.5, 2.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-2.0, 0.5, 2.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let trashbin = Object::new(
@@ -227,11 +176,7 @@ pub fn create(engine: &mut Engine) {
         material!("Trashbin.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(1.0, 0.5, 3.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(1.0, 0.5, 3.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let church = Object::new(
@@ -239,11 +184,7 @@ pub fn create(engine: &mut Engine) {
         material!("Church4K.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(-20.0, 0.5, 3.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-20.0, 0.5, 3.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let gerl = Object::new(
@@ -251,11 +192,7 @@ pub fn create(engine: &mut Engine) {
         material!("Post_Apocalypse_Gerl.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(1.0, 1.0, 1.0),
-            position: vec3(-5.0, 3.0, 3.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(-5.0, 3.0, 3.0)).with_scale(vec3(1.0, 1.0, 1.0)),
     );
 
     let set02shot = Object::new(
@@ -263,11 +200,7 @@ pub fn create(engine: &mut Engine) {
         material!("051F_03SET_02SHOT.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(0.03, 0.03, 0.03),
-            position: vec3(0.0, 0.0, 5.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.0, 5.0)).with_scale(vec3(0.03, 0.03, 0.03)),
     );
 
     // todo: rewrite using a pipeline
@@ -359,7 +292,7 @@ pub fn create(engine: &mut Engine) {
     let materials = materials
         .into_iter()
         .map(|(x, f)| {
-            f.then_signal_fence_and_flush().ok();
+            assets.uploads.schedule(f, 0);
             x
         })
         .collect();
@@ -376,22 +309,20 @@ pub fn create(engine: &mut Engine) {
 
     let state = &mut engine.game_state;
 
-    state.materials = materials;
+    demo.materials = materials;
 
     let plane = Object::new(
         plane_mesh,
-        state.materials.get(0).unwrap().clone(),
+        demo.materials.get(0).unwrap().clone(),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(50.0, 1.0, 50.0),
-            ..Transform::default()
-        },
+        Transform::default().with_scale(vec3(50.0, 1.0, 50.0)),
     );
     info!("data loaded after {}s!", start.elapsed().as_secs_f32());
 
-    state.objects = vec![
-        plane,
+    state.objects.clear();
+    demo.floor = Some(state.objects.insert(plane));
+    state.objects.extend(vec![
         fern,
         test_cube,
         apple,
@@ -410,5 +341,5 @@ pub fn create(engine: &mut Engine) {
         church,
         gerl,
         set02shot,
-    ];
+    ]);
 }
@@ -1,17 +1,19 @@
-use crate::assets::lookup;
-use crate::engine::Engine;
-use crate::render::object::Object;
-use crate::render::transform::Transform;
-use crate::render::ubo::MaterialData;
-use crate::resources::material::{create_default_fallback_maps, StaticMaterial};
-use crate::resources::mesh::create_mesh_dynamic;
+use crate::Demo;
 use bf::material::BlendMode;
 use cgmath::vec3;
+use core::color::srgb8;
+use engine::assets::lookup;
+use engine::render::object::Object;
+use engine::render::transform::Transform;
+use engine::render::ubo::MaterialData;
+use engine::resources::material::{create_default_fallback_maps, StaticMaterial};
+use engine::resources::mesh::create_mesh_cached;
+use engine::Engine;
 use log::info;
 use std::time::Instant;
 use vulkano::sync::GpuFuture;
 
-pub fn create(engine: &mut Engine) {
+pub fn create(engine: &mut Engine, demo: &mut Demo) {
     let device = &engine.vulkan_state.device();
     let assets = &engine.content;
     let path = &mut engine.renderer_state.render_path;
@@ -20,15 +22,7 @@ pub fn create(engine: &mut Engine) {
 
     macro_rules! mesh {
         ($name: expr) => {{
-            let guard = assets.request_load(lookup($name));
-
-            let mesh = guard.wait::<bf::mesh::Mesh>();
-
-            let (mesh, f) = create_mesh_dynamic(&mesh, assets.transfer_queue.clone())
-                .expect("cannot create mesh");
-            f.then_signal_fence_and_flush().ok();
-
-            mesh
+            create_mesh_cached(lookup($name), assets).expect("cannot create mesh")
         }};
     }
 
@@ -49,6 +43,11 @@ pub fn create(engine: &mut Engine) {
             metallic: 0.0,
             opacity: 1.0,
             ior: 1.0,
+            emissive_color: [0.0; 3],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: 0,
         },
         path.buffers.geometry_pipeline.clone(),
         path.samplers.aniso_repeat.clone(),
@@ -57,20 +56,18 @@ pub fn create(engine: &mut Engine) {
     )
     .expect("Cannot create material");
 
-    f1.join(f2).then_signal_fence().wait(None);
+    assets.uploads.schedule(f1.join(f2), 0);
 
     let plane = Object::new(
         plane_mesh,
         floor_mat,
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(50.0, 1.0, 50.0),
-            ..Transform::default()
-        },
+        Transform::default().with_scale(vec3(50.0, 1.0, 50.0)),
     );
 
-    state.objects = vec![plane];
+    state.objects.clear();
+    demo.floor = Some(state.objects.insert(plane));
 
     let steps = 10;
 
@@ -82,12 +79,17 @@ pub fn create(engine: &mut Engine) {
             let (sphere_mat, f) = StaticMaterial::from_material_data(
                 BlendMode::Opaque,
                 MaterialData {
-                    albedo_color: [0.8, 0.4, 0.3],
+                    albedo_color: srgb8(204, 102, 77),
                     alpha_cutoff: 0.0,
                     roughness,
                     metallic,
                     opacity: 1.0,
                     ior: 1.0,
+                    emissive_color: [0.0; 3],
+                    clear_coat: 0.0,
+                    clear_coat_roughness: 0.0,
+                    anisotropy: 0.0,
+                    flags: 0,
                 },
                 path.buffers.geometry_pipeline.clone(),
                 path.samplers.aniso_repeat.clone(),
@@ -97,21 +99,18 @@ pub fn create(engine: &mut Engine) {
             .ok()
             .unwrap();
 
-            f.then_signal_fence().wait(None);
+            assets.uploads.schedule(f, 0);
 
             let sphere = Object::new(
                 sphere_mesh.clone(),
                 sphere_mat,
                 device.clone(),
                 path.buffers.geometry_pipeline.clone(),
-                Transform {
-                    position: vec3(0.0, 3.0 + m as f32, 0.0 + r as f32),
-                    scale: vec3(0.5, 0.5, 0.5),
-                    ..Transform::default()
-                },
+                Transform::from_position(vec3(0.0, 3.0 + m as f32, 0.0 + r as f32))
+                    .with_scale(vec3(0.5, 0.5, 0.5)),
             );
 
-            state.objects.push(sphere);
+            state.objects.insert(sphere);
         }
     }
 
@@ -1,18 +1,20 @@
-use crate::assets::lookup;
-use crate::engine::Engine;
-use crate::render::object::Object;
-use crate::render::transform::Transform;
-use crate::render::ubo::MaterialData;
-use crate::render::vertex::NormalMappedVertex;
-use crate::resources::material::{create_default_fallback_maps, StaticMaterial};
-use crate::resources::mesh::create_mesh_dynamic;
+use crate::Demo;
 use bf::material::BlendMode;
 use cgmath::{point3, vec3};
+use core::color::srgb8;
+use engine::assets::lookup;
+use engine::render::object::Object;
+use engine::render::transform::Transform;
+use engine::render::ubo::MaterialData;
+use engine::render::vertex::NormalMappedVertex;
+use engine::resources::material::{create_default_fallback_maps, StaticMaterial};
+use engine::resources::mesh::create_mesh_cached;
+use engine::Engine;
 use log::info;
 use std::time::Instant;
 use vulkano::sync::GpuFuture;
 
-pub fn create(engine: &mut Engine) {
+pub fn create(engine: &mut Engine, demo: &mut Demo) {
     let device = &engine.vulkan_state.device();
     let assets = &engine.content;
     let path = &mut engine.renderer_state.render_path;
@@ -21,15 +23,7 @@ pub fn create(engine: &mut Engine) {
 
     macro_rules! mesh {
         ($name: expr) => {{
-            let guard = assets.request_load(lookup($name));
-
-            let mesh = guard.wait::<bf::mesh::Mesh>();
-
-            let (mesh, f) = create_mesh_dynamic(&mesh, assets.transfer_queue.clone())
-                .expect("cannot create mesh");
-            f.then_signal_fence_and_flush().ok();
-
-            mesh
+            create_mesh_cached(lookup($name), assets).expect("cannot create mesh")
         }};
     }
 
@@ -50,7 +44,7 @@ pub fn create(engine: &mut Engine) {
                 fallback_maps.clone(),
             )
             .expect("cannot create material");
-            f.then_signal_fence_and_flush().ok();
+            assets.uploads.schedule(f, 0);
 
             material
         }};
@@ -69,10 +63,7 @@ pub fn create(engine: &mut Engine) {
         material!("1k_floor.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            scale: vec3(50.0, 1.0, 50.0),
-            ..Transform::default()
-        },
+        Transform::default().with_scale(vec3(50.0, 1.0, 50.0)),
     );
 
     let table = Object::new(
@@ -80,22 +71,23 @@ pub fn create(engine: &mut Engine) {
         material!("TableType_A.mat"),
         device.clone(),
         path.buffers.geometry_pipeline.clone(),
-        Transform {
-            position: vec3(0.0, 0.0, 0.0),
-            scale: vec3(0.06, 0.06, 0.06),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 0.0, 0.0)).with_scale(vec3(0.06, 0.06, 0.06)),
     );
 
     let (glass_mat1, f4) = StaticMaterial::from_material_data(
         BlendMode::Translucent,
         MaterialData {
-            albedo_color: [0.0, 0.8, 0.0],
+            albedo_color: srgb8(0, 204, 0),
             alpha_cutoff: 0.0,
             roughness: 0.2,
             metallic: 0.0,
             opacity: 0.3,
             ior: 1.5,
+            emissive_color: [0.0; 3],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: 0,
         },
         path.buffers.geometry_pipeline.clone(),
         path.samplers.aniso_repeat.clone(),
@@ -108,12 +100,17 @@ pub fn create(engine: &mut Engine) {
     let (glass_mat2, f5) = StaticMaterial::from_material_data(
         BlendMode::Translucent,
         MaterialData {
-            albedo_color: [0.8, 0.0, 0.0],
+            albedo_color: srgb8(204, 0, 0),
             alpha_cutoff: 0.0,
             roughness: 0.2,
             metallic: 0.0,
             opacity: 0.5,
             ior: 1.5,
+            emissive_color: [0.0; 3],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: 0,
         },
         path.buffers.geometry_pipeline.clone(),
         path.samplers.aniso_repeat.clone(),
@@ -132,6 +129,11 @@ pub fn create(engine: &mut Engine) {
             metallic: 0.0,
             opacity: 0.5,
             ior: 1.5,
+            emissive_color: [0.0; 3],
+            clear_coat: 0.0,
+            clear_coat_roughness: 0.0,
+            anisotropy: 0.0,
+            flags: 0,
         },
         path.buffers.geometry_pipeline.clone(),
         path.samplers.aniso_repeat.clone(),
@@ -146,11 +148,7 @@ pub fn create(engine: &mut Engine) {
         glass_mat1,
         device.clone(),
         path.buffers.transparency.accumulation_pipeline.clone(),
-        Transform {
-            position: vec3(0.0, 5.35, 1.0),
-            scale: vec3(0.15, 0.15, 0.15),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 5.35, 1.0)).with_scale(vec3(0.15, 0.15, 0.15)),
     );
 
     let glass2 = Object::new(
@@ -158,11 +156,7 @@ pub fn create(engine: &mut Engine) {
         glass_mat2,
         device.clone(),
         path.buffers.transparency.accumulation_pipeline.clone(),
-        Transform {
-            position: vec3(0.0, 5.35, -1.0),
-            scale: vec3(2.0, 2.0, 2.0),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 5.35, -1.0)).with_scale(vec3(2.0, 2.0, 2.0)),
     );
 
     let glass_sphere: Object<NormalMappedVertex> = Object::new(
@@ -170,18 +164,18 @@ pub fn create(engine: &mut Engine) {
         glass_mat3,
         device.clone(),
         path.buffers.transparency.accumulation_pipeline.clone(),
-        Transform {
-            position: vec3(0.0, 6.35, 0.0),
-            scale: vec3(0.2, 0.2, 0.2),
-            ..Transform::default()
-        },
+        Transform::from_position(vec3(0.0, 6.35, 0.0)).with_scale(vec3(0.2, 0.2, 0.2)),
     );
 
-    f1.join(f4).join(f5).join(f6).then_signal_fence().wait(None);
+    assets.uploads.schedule(f1.join(f4).join(f5).join(f6), 0);
 
     state.camera.position = point3(0.0, 6.0, 4.0);
     state.camera.forward = vec3(1.0, 0.0, 0.0);
-    state.objects = vec![plane, table, glass, glass2, glass_sphere];
+    state.objects.clear();
+    demo.floor = Some(state.objects.insert(plane));
+    state
+        .objects
+        .extend(vec![table, glass, glass2, glass_sphere]);
 
     info!("data loaded after {}s!", start.elapsed().as_secs_f32());
 }